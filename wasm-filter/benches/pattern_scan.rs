@@ -0,0 +1,63 @@
+//! Pattern scanning throughput: per-pattern FSM vs flattened table
+//!
+//! Scans a chat-completion-like body against the default prompt injection
+//! patterns with both engines, in one criterion group so the report compares
+//! them directly. Builds and runs natively and as wasm32:
+//!
+//! ```text
+//! cargo bench --bench pattern_scan
+//! cargo bench --bench pattern_scan --target wasm32-wasip1
+//! ```
+
+use ai_guard_filter::governance::PromptInjectionDetector;
+use ai_guard_filter::streaming::{Pattern, PatternScanner, PatternTable, ScanResult};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use std::hint::black_box;
+use std::rc::Rc;
+
+/// Size of the scanned body
+const BODY_SIZE: usize = 1 << 20;
+
+/// A benign chat request body of about `size` bytes
+fn body(size: usize) -> Vec<u8> {
+    let turn = concat!(
+        r#"{"role": "user", "content": "Summarise the quarterly report and list the three "#,
+        r#"biggest risks to the roadmap, with owners and due dates."},"#
+    );
+    let mut body = br#"{"model": "gpt-4o", "messages": ["#.to_vec();
+    while body.len() + turn.len() < size {
+        body.extend_from_slice(turn.as_bytes());
+    }
+    body.extend_from_slice(br#"{"role": "user", "content": "thanks"}]}"#);
+    body
+}
+
+/// Scan `body` with a fresh scanner, which must not match
+fn scan(mut scanner: PatternScanner, body: &[u8]) {
+    if let ScanResult::Match(m) = scanner.scan_bytes(black_box(body)) {
+        panic!("benign body matched '{}'", m.pattern_name);
+    }
+}
+
+fn pattern_scan(c: &mut Criterion) {
+    let patterns: Vec<Pattern> = PromptInjectionDetector::default_patterns()
+        .iter()
+        .map(|p| Pattern::from_string(p))
+        .collect();
+    let patterns: Rc<[Pattern]> = patterns.into();
+    let table = Rc::new(PatternTable::build(patterns.clone()).expect("table fits"));
+    let body = body(BODY_SIZE);
+
+    let mut group = c.benchmark_group("pattern_scan");
+    group.throughput(Throughput::Bytes(body.len() as u64));
+    group.bench_function("per_pattern_fsm", |b| {
+        b.iter(|| scan(PatternScanner::new(patterns.clone()), &body))
+    });
+    group.bench_function("flattened_table", |b| {
+        b.iter(|| scan(PatternScanner::with_table(table.clone()), &body))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, pattern_scan);
+criterion_main!(benches);
@@ -0,0 +1,39 @@
+// Runs a wasm32-wasip1 benchmark (or test) binary under Node's V8, the
+// engine Envoy embeds by default. Used as the cargo runner for the target:
+//
+//     cargo bench --target wasm32-wasip1
+//
+// The library links the proxy-wasm SDK, so the binary imports the Envoy host
+// ABI; benchmarks never call it, and every such import traps if reached.
+
+import { readFile } from "node:fs/promises";
+import { argv, env, exit } from "node:process";
+import { WASI } from "node:wasi";
+
+const [wasmPath, ...args] = argv.slice(2);
+// Criterion saves its reports under the target directory: `target/` of the
+// working directory unless CARGO_TARGET_DIR points elsewhere
+const preopens = { ".": "." };
+if (env.CARGO_TARGET_DIR) preopens[env.CARGO_TARGET_DIR] = env.CARGO_TARGET_DIR;
+const wasi = new WASI({
+  version: "preview1",
+  args: [wasmPath, ...args],
+  env,
+  preopens,
+  returnOnExit: true,
+});
+const module = await WebAssembly.compile(await readFile(wasmPath));
+
+const imports = { wasi_snapshot_preview1: wasi.wasiImport };
+for (const { module: name, kind, name: field } of WebAssembly.Module.imports(module)) {
+  if (kind === "function" && !(name in imports && field in imports[name])) {
+    imports[name] ??= {};
+    imports[name][field] = () => {
+      throw new Error(`host function ${name}.${field} is not available outside Envoy`);
+    };
+  }
+}
+
+// The filter's own `_initialize` export is for Envoy; run the command entry point only
+const { exports } = await WebAssembly.instantiate(module, imports);
+exit(wasi.start({ exports: { memory: exports.memory, _start: exports._start } }));
@@ -0,0 +1,248 @@
+//! Streaming scanner throughput and per-request overhead
+//!
+//! Feeds AI payload corpora through the scanners that see every request
+//! body, in 16 KiB chunks as Envoy delivers them, and prints for each pair:
+//!
+//! - `MB/s`: throughput over a large body
+//! - `us/req`: creating the scanner and scanning a typical small request
+//!
+//! Self-contained (no benchmark crate) so the same binary runs natively and
+//! as wasm32 (under V8, through `benches/run_wasi.mjs`, or under wasmtime):
+//!
+//! ```text
+//! cargo bench --bench streaming_scanners
+//! cargo bench --bench streaming_scanners --target wasm32-wasip1
+//! CARGO_TARGET_WASM32_WASIP1_RUNNER="wasmtime run -W unknown-imports-trap=y" \
+//!     cargo bench --bench streaming_scanners --target wasm32-wasip1
+//! ```
+//!
+//! V8 is the default runner because it is the engine Envoy embeds; wasmtime
+//! measures the same binary under Cranelift.
+//!
+//! Compare the output against the previous release before tagging one.
+
+use ai_guard_filter::governance::pii_redaction::PiiAction;
+use ai_guard_filter::governance::{PiiRedactor, PromptInjectionDetector};
+use ai_guard_filter::streaming::{
+    Pattern, PatternScanner, PatternTable, RingBuffer, ScanResult, Utf8Buffer,
+};
+use std::hint::black_box;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Size of the large body of each corpus
+const LARGE_BODY: usize = 512 * 1024;
+
+/// Size of the small body of each corpus
+const SMALL_BODY: usize = 2 * 1024;
+
+/// Body chunk size
+const CHUNK: usize = 16 * 1024;
+
+/// Ring buffer capacity (the `ring_buffer_size` default)
+const RING_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Minimum measuring time per measurement
+const MEASURE: Duration = Duration::from_millis(500);
+
+/// A request body shape: JSON before and after a repeated item
+struct Corpus {
+    name: &'static str,
+    head: &'static str,
+    item: &'static str,
+    tail: &'static str,
+}
+
+const CORPORA: &[Corpus] = &[
+    Corpus {
+        name: "chat",
+        head: r#"{"model": "gpt-4o", "stream": true, "messages": ["#,
+        item: concat!(
+            r#"{"role": "user", "content": "Summarise the quarterly report and list the "#,
+            r#"three biggest risks to the roadmap, with owners and due dates."},"#,
+            r#"{"role": "assistant", "content": "1. Hiring: the platform team is two "#,
+            r#"engineers short (owner: Dana, due Q3).\n2. Vendor lock-in..."},"#
+        ),
+        tail: r#"{"role": "user", "content": "thanks"}]}"#,
+    },
+    Corpus {
+        name: "multilingual",
+        head: r#"{"model": "claude-sonnet", "max_tokens": 1024, "messages": ["#,
+        item: concat!(
+            r#"{"role": "user", "content": "请总结这份季度报告 📊 und nenne die drei "#,
+            r#"größten Risiken — リスクを三つ挙げてください 🚀"},"#
+        ),
+        tail: r#"{"role": "user", "content": "merci 🙏"}]}"#,
+    },
+    Corpus {
+        name: "mcp_tool_call",
+        head: concat!(
+            r#"{"jsonrpc": "2.0", "id": 7, "method": "tools/call", "params": "#,
+            r#"{"name": "send_email", "arguments": {"body": ""#
+        ),
+        item: concat!(
+            r"Hi team, the customer (jane.doe@example.com, +1 415-555-0134) asked ",
+            r"for a refund on order 88213; please follow up before Friday.\n"
+        ),
+        tail: r#""}}}"#,
+    },
+    Corpus {
+        name: "a2a_message",
+        head: concat!(
+            r#"{"jsonrpc": "2.0", "id": "m-1", "method": "message/send", "params": "#,
+            r#"{"message": {"role": "user", "messageId": "3f2a", "parts": ["#
+        ),
+        item: concat!(
+            r#"{"kind": "text", "text": "Plan the data migration for the billing "#,
+            r#"service and report progress as task artifacts."},"#,
+            r#"{"kind": "data", "data": {"priority": "high", "deadline": "2026-11-30"}},"#
+        ),
+        tail: r#"{"kind": "text", "text": "done"}]}}}"#,
+    },
+];
+
+impl Corpus {
+    /// A body of about `size` bytes
+    fn body(&self, size: usize) -> String {
+        let mut body = self.head.to_string();
+        while body.len() + self.item.len() + self.tail.len() < size {
+            body.push_str(self.item);
+        }
+        body.push_str(self.tail);
+        body
+    }
+}
+
+/// A scanner under test, fed one request body at a time
+trait Bench {
+    fn name(&self) -> &'static str;
+    fn scan(&self, body: &str) -> usize;
+}
+
+/// Default prompt injection patterns
+fn patterns() -> Rc<[Pattern]> {
+    let patterns: Vec<Pattern> = PromptInjectionDetector::default_patterns()
+        .iter()
+        .map(|p| Pattern::from_string(p))
+        .collect();
+    patterns.into()
+}
+
+/// `RingBuffer` over the configured patterns' table, as in the body scanner
+struct RingBufferBench(Rc<PatternTable>);
+
+impl Bench for RingBufferBench {
+    fn name(&self) -> &'static str {
+        "RingBuffer"
+    }
+
+    fn scan(&self, body: &str) -> usize {
+        let mut buffer = RingBuffer::with_table(RING_BUFFER_SIZE, self.0.clone());
+        for chunk in body.as_bytes().chunks(CHUNK) {
+            if let ScanResult::Match(m) = buffer.process_chunk(chunk) {
+                panic!("benign body matched '{}'", m.pattern_name);
+            }
+        }
+        buffer.total_written()
+    }
+}
+
+/// `PatternScanner` walking each pattern, as in per-request scanners
+struct PatternScannerBench(Rc<[Pattern]>);
+
+impl Bench for PatternScannerBench {
+    fn name(&self) -> &'static str {
+        "PatternScanner"
+    }
+
+    fn scan(&self, body: &str) -> usize {
+        let mut scanner = PatternScanner::new(self.0.clone());
+        for chunk in body.as_bytes().chunks(CHUNK) {
+            if let ScanResult::Match(m) = scanner.scan_bytes(chunk) {
+                panic!("benign body matched '{}'", m.pattern_name);
+            }
+        }
+        scanner.bytes_scanned()
+    }
+}
+
+/// `Utf8Buffer` splitting sequences across chunk boundaries
+struct Utf8BufferBench;
+
+impl Bench for Utf8BufferBench {
+    fn name(&self) -> &'static str {
+        "Utf8Buffer"
+    }
+
+    fn scan(&self, body: &str) -> usize {
+        let mut buffer = Utf8Buffer::new();
+        body.as_bytes()
+            .chunks(CHUNK)
+            .map(|chunk| black_box(buffer.process_chunk(chunk)).len())
+            .sum()
+    }
+}
+
+/// `PiiRedactor` over each chunk's text
+struct PiiRedactorBench(PiiRedactor);
+
+impl Bench for PiiRedactorBench {
+    fn name(&self) -> &'static str {
+        "PiiRedactor"
+    }
+
+    fn scan(&self, body: &str) -> usize {
+        let mut rest = body;
+        let mut found = 0;
+        while !rest.is_empty() {
+            let mut end = CHUNK.min(rest.len());
+            while !rest.is_char_boundary(end) {
+                end -= 1;
+            }
+            found += self.0.scan(&rest[..end]).len();
+            rest = &rest[end..];
+        }
+        found
+    }
+}
+
+/// Run `scan` for at least `MEASURE`, returning (runs, elapsed)
+fn measure(bench: &dyn Bench, body: &str) -> (usize, Duration) {
+    let start = Instant::now();
+    let mut runs = 0;
+    while start.elapsed() < MEASURE {
+        black_box(bench.scan(black_box(body)));
+        runs += 1;
+    }
+    (runs, start.elapsed())
+}
+
+fn main() {
+    let patterns = patterns();
+    let table = Rc::new(PatternTable::build(patterns.clone()).expect("table fits"));
+    let benches: Vec<Box<dyn Bench>> = vec![
+        Box::new(RingBufferBench(table)),
+        Box::new(PatternScannerBench(patterns)),
+        Box::new(Utf8BufferBench),
+        Box::new(PiiRedactorBench(PiiRedactor::new(PiiAction::Redact))),
+    ];
+
+    println!(
+        "{} KiB large / {} KiB small bodies, {} KiB chunks",
+        LARGE_BODY / 1024,
+        SMALL_BODY / 1024,
+        CHUNK / 1024
+    );
+    println!("{:<14} {:<15} {:>11} {:>9}", "corpus", "scanner", "MB/s", "us/req");
+    for corpus in CORPORA {
+        let large = corpus.body(LARGE_BODY);
+        let small = corpus.body(SMALL_BODY);
+        for bench in &benches {
+            let (runs, elapsed) = measure(bench.as_ref(), &large);
+            let mbps = (runs * large.len()) as f64 / elapsed.as_secs_f64() / 1e6;
+            let (runs, elapsed) = measure(bench.as_ref(), &small);
+            let micros = elapsed.as_secs_f64() * 1e6 / runs as f64;
+            println!("{:<14} {:<15} {:>11.1} {:>9.2}", corpus.name, bench.name(), mbps, micros);
+        }
+    }
+}
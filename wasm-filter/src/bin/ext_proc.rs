@@ -0,0 +1,490 @@
+//! AI-Guard ext_proc Server
+//!
+//! For clusters that run Envoy's external processing filter rather than
+//! Wasm. Each HTTP request is one gRPC stream; its body is decoded and
+//! scanned by the same core as the Wasm filter (`RequestInspection`), and a
+//! blocked request gets the filter's 403 body as an immediate response.
+//!
+//! ```text
+//! cargo build --release --no-default-features --features ext-proc
+//! ai-guard-ext-proc --config ai-guard.json --listen 0.0.0.0:50051
+//! ```
+//!
+//! The configuration is the filter's plugin configuration, validated the
+//! same way. Only the pattern scan runs here (`SUPPORTED_KEYS`): a
+//! configuration using anything else, such as rate limits or PII rules, is
+//! rejected at startup rather than served with those protections off.
+//! Envoy must send the request headers and buffer the request body:
+//!
+//! ```yaml
+//! processing_mode:
+//!   request_header_mode: SEND
+//!   request_body_mode: BUFFERED
+//!   response_header_mode: SKIP
+//! ```
+//!
+//! In STREAMED mode Envoy forwards each chunk upstream once it is answered,
+//! before a pattern split across chunks is seen; an inspected body that
+//! arrives in more than one message fails its stream instead. Bodies over
+//! Envoy's buffer limit get a 413 from Envoy.
+//!
+//! Like Envoy's Wasm VMs, each worker thread compiles its own configuration
+//! and runs its streams on a single-threaded runtime. Streams are handed to
+//! workers round-robin.
+
+use ai_guard_filter::config::{ConfigError, ConfigSnapshot, FilterConfig};
+use ai_guard_filter::governance::RequestInspection;
+use ai_guard_filter::telemetry::REQUEST_ID_HEADER;
+use envoy_types::pb::envoy::config::core::v3::{HeaderValue, HeaderValueOption};
+use envoy_types::pb::envoy::r#type::v3::HttpStatus;
+use envoy_types::pb::envoy::service::ext_proc::v3::external_processor_server::{
+    ExternalProcessor, ExternalProcessorServer,
+};
+use envoy_types::pb::envoy::service::ext_proc::v3::processing_request::Request as Phase;
+use envoy_types::pb::envoy::service::ext_proc::v3::processing_response::Response as Reply;
+use envoy_types::pb::envoy::service::ext_proc::v3::{
+    BodyResponse, HeaderMutation, HeadersResponse, HttpHeaders, ImmediateResponse,
+    ProcessingRequest, ProcessingResponse, TrailersResponse,
+};
+use log::{error, info, warn, LevelFilter, Log, Metadata, Record};
+use serde_json::Value;
+use std::net::SocketAddr;
+use std::process::ExitCode;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::mpsc;
+use tokio::task::LocalSet;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status, Streaming};
+
+/// Listen address when `--listen` is not given
+const DEFAULT_LISTEN: &str = "0.0.0.0:50051";
+
+/// Responses buffered per stream before the worker waits for Envoy
+const STREAM_BUFFER: usize = 4;
+
+/// Top-level configuration keys the ext_proc server enforces
+///
+/// The rest (rate limits, PII, MCP and response policies, ...) need the
+/// proxy-wasm host.
+const SUPPORTED_KEYS: &[&str] = &[
+    "blocked_patterns",
+    "multilingual_patterns",
+    "max_body_size",
+    "ring_buffer_size",
+    "json_string_scanning",
+    "role_patterns",
+    "ndjson",
+    "decompression",
+    "multipart",
+    "grpc_web",
+    "inspect_content_types",
+    "binary_content_types",
+    "block_response_verbosity",
+];
+
+/// A stream handed to a worker: messages from Envoy, and where replies go
+type Job = (Streaming<ProcessingRequest>, mpsc::Sender<Result<ProcessingResponse, Status>>);
+
+/// Command-line options
+#[derive(Debug)]
+struct Options {
+    /// Path of the configuration (JSON)
+    config: String,
+    /// gRPC listen address
+    listen: SocketAddr,
+    /// Worker threads
+    workers: usize,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut config = None;
+        let mut listen = DEFAULT_LISTEN.to_string();
+        let mut workers = std::thread::available_parallelism().map_or(1, |n| n.get());
+        while let Some(flag) = args.next() {
+            let value = match args.next() {
+                Some(value) => value,
+                None => return Err(format!("{}: missing value", flag)),
+            };
+            match flag.as_str() {
+                "--config" => config = Some(value),
+                "--listen" => listen = value,
+                "--workers" => {
+                    workers = match value.parse() {
+                        Ok(n) if n > 0 => n,
+                        _ => return Err(format!("--workers: not a positive number: {}", value)),
+                    }
+                }
+                _ => return Err(format!("unknown option {}", flag)),
+            }
+        }
+        let config = match config {
+            Some(config) => config,
+            None => return Err("--config is required".to_string()),
+        };
+        let listen = match listen.parse() {
+            Ok(addr) => addr,
+            Err(_) => return Err(format!("--listen: not an address: {}", listen)),
+        };
+        Ok(Self { config, listen, workers })
+    }
+}
+
+/// One HTTP request's exchange with Envoy
+struct Exchange {
+    snapshot: Rc<ConfigSnapshot>,
+    /// Body scan (None = body not inspected)
+    inspection: Option<RequestInspection>,
+    request_id: Option<String>,
+}
+
+impl Exchange {
+    fn new(snapshot: Rc<ConfigSnapshot>) -> Self {
+        Self { snapshot, inspection: None, request_id: None }
+    }
+
+    /// Reply to one message from Envoy
+    fn respond(&mut self, request: ProcessingRequest) -> Result<ProcessingResponse, Status> {
+        let reply = match request.request {
+            Some(Phase::RequestHeaders(headers)) => match self.on_request_headers(&headers) {
+                Some(blocked) => Reply::ImmediateResponse(blocked),
+                None => Reply::RequestHeaders(HeadersResponse::default()),
+            },
+            Some(Phase::RequestBody(body)) if !body.end_of_stream && self.inspection.is_some() => {
+                return Err(Status::failed_precondition(
+                    "request body streamed: request_body_mode must be BUFFERED",
+                ));
+            }
+            Some(Phase::RequestBody(body)) => {
+                match self.on_request_body(&body.body, body.end_of_stream) {
+                    Some(blocked) => Reply::ImmediateResponse(blocked),
+                    None => Reply::RequestBody(BodyResponse::default()),
+                }
+            }
+            Some(Phase::RequestTrailers(_)) => Reply::RequestTrailers(TrailersResponse::default()),
+            Some(Phase::ResponseHeaders(_)) => Reply::ResponseHeaders(HeadersResponse::default()),
+            Some(Phase::ResponseBody(_)) => Reply::ResponseBody(BodyResponse::default()),
+            Some(Phase::ResponseTrailers(_)) => {
+                Reply::ResponseTrailers(TrailersResponse::default())
+            }
+            None => return Err(Status::invalid_argument("processing request without a phase")),
+        };
+        Ok(ProcessingResponse { response: Some(reply), ..Default::default() })
+    }
+
+    /// Set up the body scan; the immediate response when the body cannot be decoded
+    fn on_request_headers(&mut self, headers: &HttpHeaders) -> Option<ImmediateResponse> {
+        self.request_id = header(headers, REQUEST_ID_HEADER);
+        self.inspection = RequestInspection::new(&self.snapshot, |name| header(headers, name));
+        self.blocked()
+    }
+
+    /// Scan a body chunk; the immediate response when it blocks
+    fn on_request_body(&mut self, chunk: &[u8], end_of_stream: bool) -> Option<ImmediateResponse> {
+        self.inspection.as_mut()?.on_body_chunk(chunk, end_of_stream);
+        self.blocked()
+    }
+
+    /// The immediate response once the inspection blocks
+    fn blocked(&self) -> Option<ImmediateResponse> {
+        let inspection = self.inspection.as_ref()?;
+        let body = inspection.block_body(&self.snapshot, self.request_id.as_deref())?;
+        warn!(
+            "AI-Guard ext_proc: BLOCKED request {} ({})",
+            self.request_id.as_deref().unwrap_or("-"),
+            inspection.category_name()
+        );
+        Some(block_response(&body))
+    }
+}
+
+/// Value of a request header (Envoy sends either `value` or `raw_value`)
+fn header(headers: &HttpHeaders, name: &str) -> Option<String> {
+    let map = headers.headers.as_ref()?;
+    let header = map.headers.iter().find(|h| h.key.eq_ignore_ascii_case(name))?;
+    match header.value.is_empty() {
+        false => Some(header.value.clone()),
+        true => Some(String::from_utf8_lossy(&header.raw_value).into_owned()),
+    }
+}
+
+/// 403 immediate response with a JSON body
+fn block_response(body: &Value) -> ImmediateResponse {
+    let content_type = HeaderValueOption {
+        header: Some(HeaderValue {
+            key: "content-type".to_string(),
+            raw_value: b"application/json".to_vec(),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    ImmediateResponse {
+        status: Some(HttpStatus { code: 403 }),
+        headers: Some(HeaderMutation { set_headers: vec![content_type], ..Default::default() }),
+        body: body.to_string().into(),
+        ..Default::default()
+    }
+}
+
+/// Run one stream to its end
+async fn run_stream(
+    snapshot: Rc<ConfigSnapshot>,
+    mut requests: Streaming<ProcessingRequest>,
+    replies: mpsc::Sender<Result<ProcessingResponse, Status>>,
+) {
+    let mut exchange = Exchange::new(snapshot);
+    loop {
+        let request = match requests.message().await {
+            Ok(Some(request)) => request,
+            Ok(None) => return,
+            Err(status) => {
+                warn!("AI-Guard ext_proc: stream error: {}", status);
+                return;
+            }
+        };
+        let reply = exchange.respond(request);
+        let failed = reply.is_err();
+        if replies.send(reply).await.is_err() || failed {
+            return;
+        }
+    }
+}
+
+/// Start a worker thread compiling its own copy of the configuration
+fn spawn_worker(config: FilterConfig) -> std::io::Result<mpsc::UnboundedSender<Job>> {
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+    let (jobs, mut queue) = mpsc::unbounded_channel::<Job>();
+    std::thread::spawn(move || {
+        let snapshot = Rc::new(ConfigSnapshot::new(config));
+        LocalSet::new().block_on(&runtime, async move {
+            while let Some((requests, replies)) = queue.recv().await {
+                tokio::task::spawn_local(run_stream(snapshot.clone(), requests, replies));
+            }
+        });
+    });
+    Ok(jobs)
+}
+
+/// ext_proc service: hands each stream to a worker
+struct GuardProcessor {
+    workers: Vec<mpsc::UnboundedSender<Job>>,
+    next: AtomicUsize,
+}
+
+#[tonic::async_trait]
+impl ExternalProcessor for GuardProcessor {
+    type ProcessStream = ReceiverStream<Result<ProcessingResponse, Status>>;
+
+    async fn process(
+        &self,
+        request: Request<Streaming<ProcessingRequest>>,
+    ) -> Result<Response<Self::ProcessStream>, Status> {
+        let (replies, stream) = mpsc::channel(STREAM_BUFFER);
+        let worker = self.next.fetch_add(1, Ordering::Relaxed) % self.workers.len();
+        if self.workers[worker].send((request.into_inner(), replies)).is_err() {
+            return Err(Status::unavailable("worker stopped"));
+        }
+        Ok(Response::new(ReceiverStream::new(stream)))
+    }
+}
+
+/// Read and validate the configuration
+fn load_config(path: &str) -> Result<FilterConfig, String> {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => return Err(format!("{}: {}", path, e)),
+    };
+    let config = match FilterConfig::from_bytes_validated(&bytes) {
+        Ok(config) => config,
+        Err(ConfigError::Invalid(diagnostics)) => return Err(diagnostics.join("\n")),
+        Err(e) => return Err(e.to_string()),
+    };
+    let unsupported = unsupported_keys(&bytes);
+    if !unsupported.is_empty() {
+        return Err(format!("not enforced by ext_proc: {}", unsupported.join(", ")));
+    }
+    Ok(config)
+}
+
+/// Top-level keys of a configuration that are not in `SUPPORTED_KEYS`
+fn unsupported_keys(bytes: &[u8]) -> Vec<String> {
+    match serde_json::from_slice::<Value>(bytes) {
+        Ok(Value::Object(object)) => object
+            .into_iter()
+            .map(|(key, _)| key)
+            .filter(|key| !SUPPORTED_KEYS.contains(&key.as_str()))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Writes log records to stderr
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("[{}] {}", record.level(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(LevelFilter::Info);
+    }
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            error!("AI-Guard ext_proc: {}", e);
+            error!("usage: ai-guard-ext-proc --config <file> [--listen <addr>] [--workers <n>]");
+            return ExitCode::from(2);
+        }
+    };
+    let config = match load_config(&options.config) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("AI-Guard ext_proc: Rejecting configuration: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let workers: std::io::Result<Vec<_>> =
+        (0..options.workers).map(|_| spawn_worker(config.clone())).collect();
+    let workers = match workers {
+        Ok(workers) => workers,
+        Err(e) => {
+            error!("AI-Guard ext_proc: workers not started: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let processor = GuardProcessor { workers, next: AtomicUsize::new(0) };
+
+    info!("AI-Guard ext_proc: listening on {} ({} workers)", options.listen, options.workers);
+    let served = Server::builder()
+        .add_service(ExternalProcessorServer::new(processor))
+        .serve(options.listen)
+        .await;
+    match served {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            error!("AI-Guard ext_proc: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use envoy_types::pb::envoy::config::core::v3::HeaderMap;
+    use envoy_types::pb::envoy::service::ext_proc::v3::HttpBody;
+
+    fn headers(pairs: &[(&str, &str)]) -> ProcessingRequest {
+        let headers = pairs
+            .iter()
+            .map(|(key, value)| HeaderValue {
+                key: key.to_string(),
+                raw_value: value.as_bytes().to_vec(),
+                ..Default::default()
+            })
+            .collect();
+        let headers = HttpHeaders { headers: Some(HeaderMap { headers }), ..Default::default() };
+        ProcessingRequest { request: Some(Phase::RequestHeaders(headers)), ..Default::default() }
+    }
+
+    fn body(chunk: &[u8], end_of_stream: bool) -> ProcessingRequest {
+        let body = HttpBody { body: chunk.to_vec(), end_of_stream, ..Default::default() };
+        ProcessingRequest { request: Some(Phase::RequestBody(body)), ..Default::default() }
+    }
+
+    fn exchange() -> Exchange {
+        let json = r#"{"blocked_patterns": ["jailbreak"], "block_response_verbosity": "full"}"#;
+        let config = FilterConfig::from_bytes_validated(json.as_bytes()).unwrap();
+        Exchange::new(Rc::new(ConfigSnapshot::new(config)))
+    }
+
+    #[test]
+    fn test_blocks_in_the_body_phase() {
+        let mut exchange = exchange();
+        let reply = exchange
+            .respond(headers(&[
+                (":path", "/v1/chat/completions"),
+                ("content-type", "application/json"),
+                ("x-request-id", "req-1"),
+            ]))
+            .unwrap();
+        assert!(matches!(reply.response, Some(Reply::RequestHeaders(_))));
+
+        let blocked = match exchange.respond(body(br#"{"prompt": "please jailbreak"}"#, true)) {
+            Ok(ProcessingResponse { response: Some(Reply::ImmediateResponse(r)), .. }) => r,
+            other => panic!("expected an immediate response, got {:?}", other),
+        };
+        assert_eq!(blocked.status.map(|s| s.code), Some(403));
+        let body: Value = serde_json::from_slice(blocked.body.as_ref()).unwrap();
+        assert!(body["reason"].as_str().unwrap().starts_with("Pattern 'jailbreak' detected"));
+        assert_eq!(body["request_id"], "req-1");
+    }
+
+    #[test]
+    fn test_bodies_must_be_buffered() {
+        let mut exchange = exchange();
+        exchange.respond(headers(&[("content-type", "application/json")])).unwrap();
+        let streamed = exchange.respond(body(br#"{"prompt": "please "#, false));
+        assert_eq!(streamed.unwrap_err().code(), tonic::Code::FailedPrecondition);
+    }
+
+    #[test]
+    fn test_undecodable_bodies_are_blocked_with_the_headers() {
+        let mut exchange = exchange();
+        let reply = exchange
+            .respond(headers(&[("content-type", "application/json"), ("content-encoding", "br")]))
+            .unwrap();
+        let blocked = match reply.response {
+            Some(Reply::ImmediateResponse(blocked)) => blocked,
+            other => panic!("expected an immediate response, got {:?}", other),
+        };
+        assert_eq!(blocked.status.map(|s| s.code), Some(403));
+        let body: Value = serde_json::from_slice(blocked.body.as_ref()).unwrap();
+        assert_eq!(body["reason"], "Unsupported content-encoding: br");
+    }
+
+    #[test]
+    fn test_binary_bodies_pass() {
+        let mut exchange = exchange();
+        exchange.respond(headers(&[("content-type", "image/png")])).unwrap();
+        let reply = exchange.respond(body(b"jailbreak", true)).unwrap();
+        assert!(matches!(reply.response, Some(Reply::RequestBody(_))));
+        assert!(exchange.respond(ProcessingRequest::default()).is_err());
+    }
+
+    #[test]
+    fn test_unsupported_keys() {
+        let json = r#"{"blocked_patterns": ["jailbreak"], "rate_limits": {}, "pii_types": []}"#;
+        assert_eq!(unsupported_keys(json.as_bytes()), vec!["pii_types", "rate_limits"]);
+        let json = r#"{"blocked_patterns": ["jailbreak"], "ndjson": {"enabled": true}}"#;
+        assert!(unsupported_keys(json.as_bytes()).is_empty());
+    }
+
+    #[test]
+    fn test_options() {
+        let args = |s: &str| s.split_whitespace().map(str::to_string).collect::<Vec<_>>();
+        let options = Options::parse(args("--config a.json --workers 2").into_iter()).unwrap();
+        assert_eq!(options.config, "a.json");
+        assert_eq!(options.listen, DEFAULT_LISTEN.parse().unwrap());
+        assert_eq!(options.workers, 2);
+        assert!(Options::parse(args("--listen 0.0.0.0:1").into_iter()).is_err());
+        assert!(Options::parse(args("--config a.json --workers 0").into_iter()).is_err());
+        assert!(Options::parse(args("--config").into_iter()).is_err());
+    }
+}
@@ -3,27 +3,100 @@
 //! CRITICAL: Configuration is loaded from Envoy plugin configuration,
 //! NOT from external files. This avoids file I/O in the Wasm sandbox.
 
+use crate::crypto::{sha256, to_hex};
+use crate::governance::{
+    ApprovalConfig, BinaryPolicy, BlockedPatterns, HeaderPolicyConfig, HeaderScrubConfig, LanguagePackConfig, MarkupConfig, McpResultPolicy, ModelPolicy, MultipartConfig,
+    NotificationLimitConfig, PiiRedactor, PiiRegion, PiiVaultConfig, PromptTemplateConfig, QuarantineConfig, RateLimits, ReplayConfig, ResponseScanConfig,
+    ResponsePiiConfig, ResultLimits, RolePatterns, ScanBudget, ToolArgumentConfig,
+    PenaltyConfig, RepetitionConfig, SessionConfig, SeverityActionsConfig, TokenCounter, ToolCallPolicy,
+    DryRunConfig, PatternExperimentConfig, ThreatFeedConfig, UrlPolicyConfig, VerdictCacheConfig,
+};
+use crate::governance::prompt_injection::{multilingual_patterns, MULTILINGUAL_PATTERNS};
+use crate::metrics::MetricDimensionsConfig;
+use crate::policy::rollout;
+use crate::policy::rules::MAX_UTC_OFFSET_MINUTES;
+use crate::policy::{
+    BlockVerbosity, BypassConfig, ClassificationConfig, ControlConfig, NetworkPolicy, PdpConfig,
+    PolicyRule, RouteSpec, TenancyConfig, TierConfig,
+};
+use crate::protocols::mcp::method_policy::glob_match;
+use crate::protocols::mcp::stdio_detect::StdioDetectionConfig;
+use crate::protocols::mcp::MethodPolicy;
+use crate::streaming::{EntropyConfig, Pattern, PatternTable, StreamWatchdogConfig};
+use crate::telemetry::{
+    AuditCaptureConfig, AuditFormat, AuditSigningConfig, HealthConfig, OverheadBudgetConfig,
+    VerdictPropagationConfig,
+};
 use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::ops::Deref;
+use std::rc::Rc;
+
+/// Maximum number of blocked patterns accepted
+pub const MAX_BLOCKED_PATTERNS: usize = 1024;
+
+/// Longest pattern accepted, in bytes
+pub const MAX_PATTERN_LEN: usize = 4096;
+
+/// Largest plugin configuration accepted, in bytes
+pub const MAX_CONFIG_BYTES: usize = 1024 * 1024;
+
+/// Hex digits of a digest kept in a version string
+const VERSION_LEN: usize = 12;
 
 /// Filter configuration loaded from Envoy plugin configuration
 #[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct FilterConfig {
-    /// Patterns to detect in request body (prompt injection signatures)
+    /// Patterns to detect in request body (prompt injection signatures),
+    /// each optionally with an `id` and `description`
     #[serde(default = "default_blocked_patterns")]
-    pub blocked_patterns: Vec<String>,
+    pub blocked_patterns: BlockedPatterns,
+
+    /// Add the built-in translated injection signatures (ten languages
+    /// besides English) to `blocked_patterns`
+    #[serde(default)]
+    pub multilingual_patterns: bool,
 
     /// PII types to detect
     #[serde(default = "default_pii_types")]
     pub pii_types: Vec<String>,
 
-    /// MCP methods allowed
+    /// Email domains not treated as PII (e.g. corporate domains; subdomains included)
+    #[serde(default)]
+    pub pii_allowed_domains: Vec<String>,
+
+    /// Deployment regions whose national identifiers are detected (e.g. "uk", "de")
+    #[serde(default = "default_pii_regions")]
+    pub pii_regions: Vec<PiiRegion>,
+
+    /// Confidence (0-1) a PII match needs to be acted on; context keywords
+    /// near a match raise or lower its confidence
+    #[serde(default)]
+    pub pii_min_confidence: f32,
+
+    /// Replace request PII by reversible, format-preserving tokens and
+    /// restore them in responses (disabled when absent)
+    #[serde(default)]
+    pub pii_vault: Option<PiiVaultConfig>,
+
+    /// MCP methods allowed (glob patterns, e.g. "tools/*")
     #[serde(default = "default_mcp_methods")]
     pub mcp_allowed_methods: Vec<String>,
 
+    /// MCP methods denied (glob patterns); deny wins over allow
+    #[serde(default)]
+    pub mcp_denied_methods: Vec<String>,
+
     /// Maximum body size to inspect (prevent OOM)
     #[serde(default = "default_max_body_size")]
     pub max_body_size: usize,
 
+    /// Bytes and time spent scanning each request body (unbounded when absent)
+    #[serde(default)]
+    pub scan_budget: Option<ScanBudget>,
+
     /// Ring buffer size for streaming inspection
     #[serde(default = "default_ring_buffer_size")]
     pub ring_buffer_size: usize,
@@ -31,10 +104,514 @@ pub struct FilterConfig {
     /// Whether to log matched patterns (for debugging)
     #[serde(default = "default_log_matches")]
     pub log_matches: bool,
+
+    /// Model pricing entries (override or extend the built-in table)
+    #[serde(default)]
+    pub model_pricing: Vec<ModelPricing>,
+
+    /// Drop the built-in pricing table and use only `model_pricing`
+    #[serde(default)]
+    pub replace_default_pricing: bool,
+
+    /// Per-agent rate limits (disabled when absent)
+    #[serde(default)]
+    pub rate_limits: Option<RateLimits>,
+
+    /// Request header scanning, size limits and strip/block lists (disabled when absent)
+    #[serde(default)]
+    pub header_policy: Option<HeaderPolicyConfig>,
+
+    /// Tenant resolution and per-tenant overrides (single tenant when absent)
+    #[serde(default)]
+    pub tenancy: Option<TenancyConfig>,
+
+    /// Per-route configuration overrides by path prefix and HTTP method
+    #[serde(default)]
+    pub routes: Vec<RouteSpec>,
+
+    /// Request classification label header and admin paths (label not sent when absent)
+    #[serde(default)]
+    pub classification: Option<ClassificationConfig>,
+
+    /// Identity tiers varying limits and allowances by caller (disabled when absent)
+    #[serde(default)]
+    pub identity_tiers: Option<TierConfig>,
+
+    /// Client network allow/deny lists and per-network enforcement (disabled when absent)
+    #[serde(default)]
+    pub network_policy: Option<NetworkPolicy>,
+
+    /// Request header carrying the calling agent's identity
+    #[serde(default = "default_agent_id_header")]
+    pub agent_id_header: String,
+
+    /// Inject x-ai-tokens-* and x-ai-cost-usd headers into responses
+    ///
+    /// Opt-in: JSON responses are held until the body completes so the
+    /// headers can carry usage extracted from it.
+    #[serde(default)]
+    pub usage_response_headers: bool,
+
+    /// Guardrail span export (disabled when absent)
+    #[serde(default)]
+    pub tracing: Option<TracingConfig>,
+
+    /// Interval between blocked-pattern summary events (0 disables)
+    #[serde(default = "default_pattern_stats_interval_secs")]
+    pub pattern_stats_interval_secs: u64,
+
+    /// Number of patterns listed in the summary
+    #[serde(default = "default_pattern_stats_top_k")]
+    pub pattern_stats_top_k: usize,
+
+    /// Verdict explanation debug mode (disabled when absent)
+    #[serde(default)]
+    pub debug: Option<DebugConfig>,
+
+    /// Audit event format for logs and the external collector (json, ocsf, cef)
+    #[serde(default)]
+    pub audit_format: AuditFormat,
+
+    /// Audit event shipping to an external collector (disabled when absent)
+    #[serde(default)]
+    pub audit_sink: Option<AuditSinkConfig>,
+
+    /// How much 403 bodies say about the violation (opaque, category, full)
+    #[serde(default)]
+    pub block_response_verbosity: BlockVerbosity,
+
+    /// Redacted excerpt of the content before a match in block events (disabled when absent)
+    #[serde(default)]
+    pub audit_capture: Option<AuditCaptureConfig>,
+
+    /// HMAC signatures and sequence numbers on audit events (disabled when absent)
+    #[serde(default)]
+    pub audit_signing: Option<AuditSigningConfig>,
+
+    /// Request counters by tenant, protocol, transport and category (disabled when absent)
+    #[serde(default)]
+    pub metric_dimensions: Option<MetricDimensionsConfig>,
+
+    /// Warning audit event when the filter's p99 overhead exceeds a budget (disabled when absent)
+    #[serde(default)]
+    pub overhead_budget: Option<OverheadBudgetConfig>,
+
+    /// Unauthenticated health and readiness endpoint (disabled when absent)
+    #[serde(default)]
+    pub health: Option<HealthConfig>,
+
+    /// Signed `x-guardrail-bypass` tokens skipping inspection (disabled when absent)
+    #[serde(default)]
+    pub bypass: Option<BypassConfig>,
+
+    /// Signed verdict forwarded upstream in `x-guardrail-verdict` (disabled when absent)
+    #[serde(default)]
+    pub verdict_propagation: Option<VerdictPropagationConfig>,
+
+    /// gzip/deflate body decompression before scanning
+    #[serde(default)]
+    pub decompression: DecompressionConfig,
+
+    /// multipart/form-data parsing: only text parts are scanned
+    #[serde(default)]
+    pub multipart: MultipartConfig,
+
+    /// NDJSON / JSON Lines bodies: scanned record by record
+    #[serde(default)]
+    pub ndjson: NdjsonConfig,
+
+    /// gRPC-Web and Connect bodies: protobuf string fields are scanned
+    #[serde(default)]
+    pub grpc_web: GrpcWebConfig,
+
+    /// Content types (globs over the media type) whose bodies are scanned
+    #[serde(default = "default_inspect_content_types")]
+    pub inspect_content_types: Vec<String>,
+
+    /// Content types never scanned, even when they match `inspect_content_types`
+    #[serde(default)]
+    pub binary_content_types: Vec<String>,
+
+    /// Magic-byte classification and allow/deny of non-text bodies (disabled when absent)
+    #[serde(default)]
+    pub binary_policy: Option<BinaryPolicy>,
+
+    /// Scan only decoded string values of JSON bodies (keys are skipped)
+    #[serde(default = "default_true")]
+    pub json_string_scanning: bool,
+
+    /// Per-role pattern sets for OpenAI chat completion requests (disabled when absent)
+    #[serde(default)]
+    pub role_patterns: Option<RolePatterns>,
+
+    /// Per-language pattern sets selected by the detected prompt language
+    /// (disabled when absent)
+    #[serde(default)]
+    pub language_packs: Option<LanguagePackConfig>,
+
+    /// Detection of long high-entropy runs (encoded or encrypted blobs) in
+    /// text prompts (disabled when absent)
+    #[serde(default)]
+    pub entropy_detection: Option<EntropyConfig>,
+
+    /// Detection of token-burning prompts: massive repetition or whitespace
+    /// (disabled when absent)
+    #[serde(default)]
+    pub repetition_detection: Option<RepetitionConfig>,
+
+    /// Markup-aware scanning: HTML and markdown stripped, with comments,
+    /// attributes and link titles scanned separately (disabled when absent)
+    #[serde(default)]
+    pub markup_scanning: Option<MarkupConfig>,
+
+    /// Destination policy for links in prompts and MCP results (disabled
+    /// when absent)
+    #[serde(default)]
+    pub url_policy: Option<UrlPolicyConfig>,
+
+    /// Periodically fetched feed of known-bad prompt fingerprints (disabled
+    /// when absent)
+    #[serde(default)]
+    pub threat_feed: Option<ThreatFeedConfig>,
+
+    /// Candidate pattern bundle enforced for a share of requests, with the
+    /// other bundle scanning in shadow (disabled when absent)
+    #[serde(default)]
+    pub pattern_experiment: Option<PatternExperimentConfig>,
+
+    /// Candidate configuration evaluated in shadow, with verdict
+    /// discrepancies audited (disabled when absent)
+    #[serde(default)]
+    pub dry_run: Option<DryRunConfig>,
+
+    /// Share of requests each subsystem or language pack (`language_packs.de`)
+    /// applies to, in percent (subsystems not listed apply to all)
+    #[serde(default)]
+    pub rollout_percent: BTreeMap<String, u8>,
+
+    /// Largest `max_tokens` a JSON request may ask for (unlimited when absent)
+    #[serde(default)]
+    pub max_tokens_limit: Option<u64>,
+
+    /// Rewrite an excessive `max_tokens` down to `max_tokens_limit` instead of blocking
+    #[serde(default)]
+    pub clamp_max_tokens: bool,
+
+    /// Largest estimated prompt size in tokens (unlimited when absent)
+    #[serde(default)]
+    pub max_prompt_tokens: Option<u32>,
+
+    /// Largest number of messages in a JSON request (unlimited when absent)
+    #[serde(default)]
+    pub max_messages: Option<usize>,
+
+    /// Scanning of streamed (SSE) completion text (disabled when absent)
+    #[serde(default)]
+    pub response_scanning: Option<ResponseScanConfig>,
+
+    /// Inspection of model-generated tool-call arguments (disabled when absent)
+    #[serde(default)]
+    pub tool_call_policy: Option<ToolCallPolicy>,
+
+    /// Model allowlist and overrides for JSON request bodies (disabled when absent)
+    #[serde(default)]
+    pub model_policy: Option<ModelPolicy>,
+
+    /// Indirect injection scanning of MCP result payloads (disabled when absent)
+    #[serde(default)]
+    pub mcp_result_scanning: Option<McpResultPolicy>,
+
+    /// Size, nesting depth and array length limits on MCP tool results
+    /// (disabled when absent)
+    #[serde(default)]
+    pub tool_result_limits: Option<ResultLimits>,
+
+    /// Template and argument scanning of MCP prompts/get (disabled when absent)
+    #[serde(default)]
+    pub prompt_templates: Option<PromptTemplateConfig>,
+
+    /// Dangerous-argument heuristics for MCP tools/call, per tool name (disabled when absent)
+    #[serde(default)]
+    pub tool_arguments: Option<ToolArgumentConfig>,
+
+    /// PII detection and redaction in responses, per PII type (disabled when absent)
+    #[serde(default)]
+    pub response_pii: Option<ResponsePiiConfig>,
+
+    /// Sensitive request headers stripped, hashed or redacted before
+    /// forwarding upstream (disabled when absent)
+    #[serde(default)]
+    pub header_scrubbing: Option<HeaderScrubConfig>,
+
+    /// Per-session correlation and escalation (disabled when absent)
+    #[serde(default)]
+    pub sessions: Option<SessionConfig>,
+
+    /// Rejection of replayed message and request ids per session (disabled when absent)
+    #[serde(default)]
+    pub replay_protection: Option<ReplayConfig>,
+
+    /// Reuse of scan outcomes for retried identical bodies
+    #[serde(default)]
+    pub verdict_cache: VerdictCacheConfig,
+
+    /// Runtime kill switch, monitor mode and admin endpoint (disabled when absent)
+    #[serde(default)]
+    pub control: Option<ControlConfig>,
+
+    /// Route medium-risk requests to quarantine instead of blocking (disabled when absent)
+    #[serde(default)]
+    pub quarantine: Option<QuarantineConfig>,
+
+    /// Action per match severity instead of always blocking (disabled when absent)
+    #[serde(default)]
+    pub severity_actions: Option<SeverityActionsConfig>,
+
+    /// Escalating penalties for agents with repeated violations (disabled when absent)
+    #[serde(default)]
+    pub agent_penalties: Option<PenaltyConfig>,
+
+    /// Detection of off-mesh STDIO MCP attempts, attributed to the calling
+    /// workload (disabled when absent)
+    #[serde(default)]
+    pub stdio_detection: Option<StdioDetectionConfig>,
+
+    /// Lifetime, event and data-rate limits for event-stream responses
+    /// (disabled when absent)
+    #[serde(default)]
+    pub stream_watchdog: Option<StreamWatchdogConfig>,
+
+    /// Per-session caps on MCP notifications pushed over event streams
+    /// (disabled when absent)
+    #[serde(default)]
+    pub notification_limits: Option<NotificationLimitConfig>,
+
+    /// Human approval of high-risk MCP tool calls (disabled when absent)
+    #[serde(default)]
+    pub tool_approval: Option<ApprovalConfig>,
+
+    /// Allow/deny rules over request attributes, first match decides
+    #[serde(default)]
+    pub policy_rules: Vec<PolicyRule>,
+
+    /// Deployment environment label (`environment` in policy rules)
+    #[serde(default)]
+    pub environment: Option<String>,
+
+    /// Time zone of `hour`/`minute`/`weekday` in policy rules, as minutes east of UTC
+    #[serde(default)]
+    pub policy_utc_offset_minutes: i32,
+
+    /// External policy decision point consulted before forwarding (disabled when absent)
+    #[serde(default)]
+    pub pdp: Option<PdpConfig>,
 }
 
-fn default_blocked_patterns() -> Vec<String> {
-    vec![
+/// Body decompression settings
+///
+/// Compressed request bodies are inflated before scanning, and compressed
+/// responses before usage extraction. Only gzip and deflate are decoded:
+/// brotli (`br`), zstd, stacked and unknown encodings are blocked, or
+/// scanned as raw bytes when `block_unsupported` is off. Responses the
+/// filter inspects are requested in gzip, deflate or identity only; one
+/// that still arrives in another encoding is blocked, or forwarded
+/// uninspected when `block_unsupported` is off.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DecompressionConfig {
+    /// Inflate gzip/deflate bodies
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Largest decompressed body; larger bodies are blocked
+    #[serde(default = "default_max_decompressed_size")]
+    pub max_decompressed_size: usize,
+    /// Largest decompressed/compressed size ratio; higher ratios are blocked
+    #[serde(default = "default_max_compression_ratio")]
+    pub max_ratio: u32,
+    /// Block requests with an encoding that cannot be decoded
+    #[serde(default = "default_true")]
+    pub block_unsupported: bool,
+}
+
+impl Default for DecompressionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_decompressed_size: default_max_decompressed_size(),
+            max_ratio: default_max_compression_ratio(),
+            block_unsupported: true,
+        }
+    }
+}
+
+/// NDJSON / JSON Lines settings
+///
+/// Bodies of an NDJSON content type are split on newlines and each record
+/// is scanned as its own JSON document, with its index in match paths.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NdjsonConfig {
+    /// Scan NDJSON bodies record by record (otherwise as raw bytes)
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Block requests with a record that is not valid JSON
+    #[serde(default)]
+    pub block_invalid: bool,
+}
+
+impl Default for NdjsonConfig {
+    fn default() -> Self {
+        Self { enabled: true, block_invalid: false }
+    }
+}
+
+/// gRPC-Web / Connect settings
+///
+/// Bodies of gRPC-Web (binary or base64 text), Connect streaming and
+/// Connect unary protobuf requests are unwrapped, and the string fields of
+/// each message scanned. Messages are buffered until complete.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GrpcWebConfig {
+    /// Decode gRPC-Web / Connect bodies (otherwise they are not scanned)
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Largest message decoded; bodies with larger messages are scanned raw
+    #[serde(default = "default_max_grpc_message_size")]
+    pub max_message_size: usize,
+}
+
+impl Default for GrpcWebConfig {
+    fn default() -> Self {
+        Self { enabled: true, max_message_size: default_max_grpc_message_size() }
+    }
+}
+
+/// Debug mode settings
+///
+/// Requests carrying an `x-guardrail-debug` token signed with `secret`
+/// get an `x-guardrail-verdict` response header explaining the decision.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DebugConfig {
+    /// HMAC-SHA256 key for debug tokens
+    pub secret: String,
+    /// Longest accepted token lifetime
+    #[serde(default = "default_debug_max_ttl_secs")]
+    pub max_ttl_secs: u64,
+}
+
+/// External audit collector (SIEM) settings
+///
+/// Events are buffered per worker and POSTed as a JSON array. TLS to the
+/// collector is configured on the Envoy cluster.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuditSinkConfig {
+    /// Envoy cluster of the collector
+    pub cluster: String,
+    /// Collector path
+    #[serde(default = "default_audit_path")]
+    pub path: String,
+    /// `:authority` sent to the collector (defaults to the cluster name)
+    #[serde(default)]
+    pub authority: Option<String>,
+    /// `authorization` header value sent with each batch
+    #[serde(default)]
+    pub authorization: Option<String>,
+    /// Events per batch; a full batch is flushed on the next tick
+    #[serde(default = "default_audit_batch_size")]
+    pub batch_size: usize,
+    /// Maximum time an event waits before being flushed
+    #[serde(default = "default_audit_flush_interval_ms")]
+    pub flush_interval_ms: u64,
+    /// Events buffered before the oldest are dropped
+    #[serde(default = "default_audit_max_queue")]
+    pub max_queue: usize,
+    /// Delivery attempts per batch before it is dropped
+    #[serde(default = "default_audit_max_attempts")]
+    pub max_attempts: u32,
+    /// Request timeout in milliseconds
+    #[serde(default = "default_audit_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+/// OpenTelemetry span export settings
+///
+/// Spans are only produced for requests carrying a sampled `traceparent`.
+/// With `collector_cluster` set they are POSTed as OTLP/HTTP JSON;
+/// otherwise they are recorded in filter state (`ai_guard.spans`).
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TracingConfig {
+    /// Envoy cluster of the OTLP/HTTP collector
+    #[serde(default)]
+    pub collector_cluster: Option<String>,
+    /// Collector path
+    #[serde(default = "default_collector_path")]
+    pub collector_path: String,
+    /// `:authority` sent to the collector (defaults to the cluster name)
+    #[serde(default)]
+    pub collector_authority: Option<String>,
+    /// Export timeout in milliseconds
+    #[serde(default = "default_collector_timeout_ms")]
+    pub collector_timeout_ms: u64,
+    /// `service.name` resource attribute
+    #[serde(default = "default_service_name")]
+    pub service_name: String,
+}
+
+/// Token pricing for a model, supplied via plugin configuration
+///
+/// `model` is matched exactly, or as a prefix when it ends with `*`
+/// (e.g. `"gpt-4o*"`). The longest matching rule wins.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModelPricing {
+    /// Model name or prefix rule
+    pub model: String,
+    /// USD per 1K input (prompt) tokens
+    pub input_per_1k: f64,
+    /// USD per 1K output (completion) tokens
+    pub output_per_1k: f64,
+    /// USD per 1K cached input tokens (defaults to `input_per_1k`)
+    #[serde(default)]
+    pub cached_input_per_1k: Option<f64>,
+}
+
+impl ModelPricing {
+    /// Check if this rule applies to a model, returning the match specificity
+    pub fn matches(&self, model: &str) -> Option<usize> {
+        match self.model.strip_suffix('*') {
+            Some(prefix) if model.starts_with(prefix) => Some(prefix.len()),
+            None if self.model == model => Some(self.model.len()),
+            _ => None,
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_max_grpc_message_size() -> usize {
+    4 * 1024 * 1024 // gRPC's default receive limit
+}
+
+fn default_inspect_content_types() -> Vec<String> {
+    vec!["*json*".to_string(), "*text*".to_string(), "*form*".to_string()]
+}
+
+fn default_max_decompressed_size() -> usize {
+    16 * 1024 * 1024 // 16MB
+}
+
+fn default_max_compression_ratio() -> u32 {
+    100
+}
+
+fn default_blocked_patterns() -> BlockedPatterns {
+    BlockedPatterns::from(vec![
         "ignore previous instructions".to_string(),
         "ignore all previous".to_string(),
         "disregard previous".to_string(),
@@ -47,7 +624,11 @@ fn default_blocked_patterns() -> Vec<String> {
         "delete database".to_string(),
         "drop table".to_string(),
         "rm -rf".to_string(),
-    ]
+    ])
+}
+
+fn default_pii_regions() -> Vec<PiiRegion> {
+    vec![PiiRegion::Us]
 }
 
 fn default_pii_types() -> Vec<String> {
@@ -74,15 +655,145 @@ fn default_log_matches() -> bool {
     true
 }
 
+fn default_agent_id_header() -> String {
+    "x-agent-id".to_string()
+}
+
+fn default_collector_path() -> String {
+    "/v1/traces".to_string()
+}
+
+fn default_collector_timeout_ms() -> u64 {
+    1000
+}
+
+fn default_service_name() -> String {
+    "ai-guard".to_string()
+}
+
+fn default_pattern_stats_interval_secs() -> u64 {
+    300
+}
+
+fn default_pattern_stats_top_k() -> usize {
+    10
+}
+
+fn default_debug_max_ttl_secs() -> u64 {
+    3600
+}
+
+fn default_audit_path() -> String {
+    "/".to_string()
+}
+
+fn default_audit_batch_size() -> usize {
+    100
+}
+
+fn default_audit_flush_interval_ms() -> u64 {
+    5000
+}
+
+fn default_audit_max_queue() -> usize {
+    10_000
+}
+
+fn default_audit_max_attempts() -> u32 {
+    3
+}
+
+fn default_audit_timeout_ms() -> u64 {
+    5000
+}
+
 impl Default for FilterConfig {
     fn default() -> Self {
         Self {
             blocked_patterns: default_blocked_patterns(),
+            multilingual_patterns: false,
             pii_types: default_pii_types(),
+            pii_allowed_domains: Vec::new(),
+            pii_regions: default_pii_regions(),
+            pii_min_confidence: 0.0,
+            pii_vault: None,
             mcp_allowed_methods: default_mcp_methods(),
+            mcp_denied_methods: Vec::new(),
             max_body_size: default_max_body_size(),
+            scan_budget: None,
             ring_buffer_size: default_ring_buffer_size(),
             log_matches: default_log_matches(),
+            model_pricing: Vec::new(),
+            replace_default_pricing: false,
+            rate_limits: None,
+            header_policy: None,
+            tenancy: None,
+            routes: Vec::new(),
+            classification: None,
+            identity_tiers: None,
+            network_policy: None,
+            agent_id_header: default_agent_id_header(),
+            usage_response_headers: false,
+            tracing: None,
+            pattern_stats_interval_secs: default_pattern_stats_interval_secs(),
+            pattern_stats_top_k: default_pattern_stats_top_k(),
+            debug: None,
+            audit_format: AuditFormat::Json,
+            audit_sink: None,
+            block_response_verbosity: BlockVerbosity::Opaque,
+            audit_capture: None,
+            audit_signing: None,
+            metric_dimensions: None,
+            overhead_budget: None,
+            health: None,
+            bypass: None,
+            verdict_propagation: None,
+            decompression: DecompressionConfig::default(),
+            multipart: MultipartConfig::default(),
+            ndjson: NdjsonConfig::default(),
+            grpc_web: GrpcWebConfig::default(),
+            inspect_content_types: default_inspect_content_types(),
+            binary_content_types: Vec::new(),
+            binary_policy: None,
+            json_string_scanning: true,
+            role_patterns: None,
+            language_packs: None,
+            entropy_detection: None,
+            repetition_detection: None,
+            markup_scanning: None,
+            url_policy: None,
+            threat_feed: None,
+            pattern_experiment: None,
+            dry_run: None,
+            rollout_percent: BTreeMap::new(),
+            max_tokens_limit: None,
+            clamp_max_tokens: false,
+            max_prompt_tokens: None,
+            max_messages: None,
+            response_scanning: None,
+            tool_call_policy: None,
+            model_policy: None,
+            mcp_result_scanning: None,
+            tool_result_limits: None,
+            prompt_templates: None,
+            tool_arguments: None,
+            response_pii: None,
+            header_scrubbing: None,
+            sessions: None,
+            replay_protection: None,
+            verdict_cache: VerdictCacheConfig::default(),
+            control: None,
+            quarantine: None,
+            severity_actions: None,
+            agent_penalties: None,
+            stdio_detection: None,
+            stream_watchdog: None,
+            notification_limits: None,
+            tool_approval: None,
+            policy_rules: Vec::new(),
+            environment: None,
+            policy_utc_offset_minutes: 0,
+            pdp: None,
         }
     }
 }
@@ -97,38 +808,570 @@ impl FilterConfig {
             .map_err(|e| ConfigError::InvalidJson(e.to_string()))
     }
 
-    /// Check if an MCP method is allowed
-    pub fn is_mcp_method_allowed(&self, method: &str) -> bool {
-        self.mcp_allowed_methods.iter().any(|m| m == "*" || m == method)
+    /// Parse and validate configuration, collecting every problem found
+    ///
+    /// Unlike `from_bytes`, which stops at the first serde error, each
+    /// top-level key is checked on its own so one bad rollout reports all
+    /// unknown keys and wrong types at once, followed by semantic checks.
+    pub fn from_bytes_validated(bytes: &[u8]) -> Result<Self, ConfigError> {
+        if bytes.len() > MAX_CONFIG_BYTES {
+            return Err(ConfigError::TooLarge(bytes.len()));
+        }
+        let config_str = std::str::from_utf8(bytes)
+            .map_err(|e| ConfigError::InvalidUtf8(e.to_string()))?;
+        let value: Value = serde_json::from_str(config_str)
+            .map_err(|e| ConfigError::InvalidJson(e.to_string()))?;
+        let object = match value.as_object() {
+            Some(object) => object,
+            None => {
+                return Err(ConfigError::Invalid(vec![
+                    "configuration must be a JSON object".to_string(),
+                ]))
+            }
+        };
+
+        let mut diagnostics = Vec::new();
+        for (key, field) in object {
+            let mut single = serde_json::Map::new();
+            single.insert(key.clone(), field.clone());
+            if let Err(e) = serde_json::from_value::<FilterConfig>(Value::Object(single)) {
+                let message = e.to_string();
+                if message.starts_with(&format!("unknown field `{}`", key)) {
+                    diagnostics.push(format!("{}: unknown key", key));
+                } else {
+                    diagnostics.push(format!("{}: {}", key, message));
+                }
+            }
+        }
+        if !diagnostics.is_empty() {
+            return Err(ConfigError::Invalid(diagnostics));
+        }
+
+        let config: FilterConfig = serde_json::from_value(value)
+            .map_err(|e| ConfigError::InvalidJson(e.to_string()))?;
+        let diagnostics = config.validate();
+        if !diagnostics.is_empty() {
+            return Err(ConfigError::Invalid(diagnostics));
+        }
+        Ok(config)
     }
-}
 
-/// Configuration parsing errors
-#[derive(Debug)]
-pub enum ConfigError {
-    InvalidUtf8(String),
-    InvalidJson(String),
-}
+    /// Semantic checks serde cannot express
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
 
-impl std::fmt::Display for ConfigError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ConfigError::InvalidUtf8(e) => write!(f, "Invalid UTF-8: {}", e),
-            ConfigError::InvalidJson(e) => write!(f, "Invalid JSON: {}", e),
+        let builtin = if self.multilingual_patterns { MULTILINGUAL_PATTERNS.len() } else { 0 };
+        if self.blocked_patterns.len() + builtin > MAX_BLOCKED_PATTERNS {
+            diagnostics.push(format!(
+                "blocked_patterns: {} patterns exceeds the cap of {}",
+                self.blocked_patterns.len() + builtin,
+                MAX_BLOCKED_PATTERNS
+            ));
+        }
+        diagnostics.extend(self.blocked_patterns.validate());
+        for (i, pattern) in self.blocked_patterns.iter().enumerate() {
+            if pattern.trim().is_empty() {
+                diagnostics.push(format!("blocked_patterns[{}]: empty pattern", i));
+            } else if pattern.len() > MAX_PATTERN_LEN {
+                diagnostics.push(format!(
+                    "blocked_patterns[{}]: {} bytes exceeds the cap of {}",
+                    i,
+                    pattern.len(),
+                    MAX_PATTERN_LEN
+                ));
+            } else if pattern.len() > self.ring_buffer_size {
+                diagnostics.push(format!(
+                    "blocked_patterns[{}]: longer than ring_buffer_size ({} > {})",
+                    i,
+                    pattern.len(),
+                    self.ring_buffer_size
+                ));
+            }
+        }
+        if self.max_body_size == 0 {
+            diagnostics.push("max_body_size: must be greater than 0".to_string());
+        }
+        if let Some(budget) = &self.scan_budget {
+            diagnostics.extend(budget.validate());
+        }
+        if self.ring_buffer_size == 0 {
+            diagnostics.push("ring_buffer_size: must be greater than 0".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.pii_min_confidence) {
+            diagnostics.push("pii_min_confidence: must be between 0 and 1".to_string());
+        }
+        for (i, domain) in self.pii_allowed_domains.iter().enumerate() {
+            let name = domain.trim_start_matches('@');
+            if name.is_empty() || name.contains(['@', '/', ' ']) {
+                diagnostics.push(format!("pii_allowed_domains[{}]: invalid domain", i));
+            }
+        }
+        for (i, rule) in self.model_pricing.iter().enumerate() {
+            if rule.input_per_1k < 0.0 || rule.output_per_1k < 0.0 {
+                diagnostics.push(format!("model_pricing[{}]: negative price", i));
+            }
         }
+        if let Some(sink) = &self.audit_sink {
+            if sink.cluster.is_empty() {
+                diagnostics.push("audit_sink.cluster: must not be empty".to_string());
+            }
+        }
+        if let Some(capture) = &self.audit_capture {
+            diagnostics.extend(capture.validate(self.ring_buffer_size));
+        }
+        if let Some(signing) = &self.audit_signing {
+            diagnostics.extend(signing.validate());
+        }
+        if let Some(dimensions) = &self.metric_dimensions {
+            diagnostics.extend(dimensions.validate());
+        }
+        if let Some(budget) = &self.overhead_budget {
+            diagnostics.extend(budget.validate());
+        }
+        if let Some(health) = &self.health {
+            diagnostics.extend(health.validate());
+        }
+        if let Some(bypass) = &self.bypass {
+            diagnostics.extend(bypass.validate());
+        }
+        if let Some(propagation) = &self.verdict_propagation {
+            diagnostics.extend(propagation.validate());
+        }
+        if let Some(debug) = &self.debug {
+            if debug.secret.len() < 16 {
+                diagnostics.push("debug.secret: must be at least 16 bytes".to_string());
+            }
+        }
+        if let Some(policy) = &self.header_policy {
+            diagnostics.extend(policy.validate());
+        }
+        if self.decompression.max_decompressed_size == 0 {
+            diagnostics
+                .push("decompression.max_decompressed_size: must be greater than 0".to_string());
+        }
+        if self.decompression.max_ratio == 0 {
+            diagnostics.push("decompression.max_ratio: must be greater than 0".to_string());
+        }
+        diagnostics.extend(self.multipart.validate());
+        if self.grpc_web.max_message_size == 0 {
+            diagnostics.push("grpc_web.max_message_size: must be greater than 0".to_string());
+        }
+        let content_types = [
+            ("inspect_content_types", &self.inspect_content_types),
+            ("binary_content_types", &self.binary_content_types),
+        ];
+        for (field, patterns) in content_types {
+            if patterns.iter().any(|p| p.trim().is_empty()) {
+                diagnostics.push(format!("{}: patterns must not be empty", field));
+            }
+        }
+        if let Some(policy) = &self.binary_policy {
+            diagnostics.extend(policy.validate());
+        }
+        if let Some(policy) = &self.role_patterns {
+            diagnostics.extend(policy.validate());
+        }
+        if let Some(packs) = &self.language_packs {
+            diagnostics.extend(packs.validate());
+        }
+        if let Some(entropy) = &self.entropy_detection {
+            diagnostics.extend(entropy.validate());
+        }
+        if let Some(repetition) = &self.repetition_detection {
+            diagnostics.extend(repetition.validate());
+        }
+        if let Some(markup) = &self.markup_scanning {
+            diagnostics.extend(markup.validate());
+        }
+        if let Some(urls) = &self.url_policy {
+            diagnostics.extend(urls.validate());
+        }
+        if let Some(feed) = &self.threat_feed {
+            diagnostics.extend(feed.validate());
+        }
+        if let Some(experiment) = &self.pattern_experiment {
+            diagnostics.extend(experiment.validate());
+        }
+        if let Some(dry_run) = &self.dry_run {
+            diagnostics.extend(dry_run.validate());
+        }
+        diagnostics.extend(rollout::validate(self));
+        if self.max_tokens_limit == Some(0) {
+            diagnostics.push("max_tokens_limit: must be greater than 0".to_string());
+        }
+        if self.clamp_max_tokens && self.max_tokens_limit.is_none() {
+            diagnostics.push("clamp_max_tokens: requires max_tokens_limit".to_string());
+        }
+        if self.max_prompt_tokens == Some(0) {
+            diagnostics.push("max_prompt_tokens: must be greater than 0".to_string());
+        }
+        if self.max_messages == Some(0) {
+            diagnostics.push("max_messages: must be greater than 0".to_string());
+        }
+        if let Some(scan) = &self.response_scanning {
+            diagnostics.extend(scan.validate());
+        }
+        if let Some(policy) = &self.tool_call_policy {
+            diagnostics.extend(policy.validate());
+        }
+        if let Some(policy) = &self.model_policy {
+            diagnostics.extend(policy.validate());
+        }
+        if let Some(policy) = &self.mcp_result_scanning {
+            diagnostics.extend(policy.validate());
+        }
+        if let Some(limits) = &self.tool_result_limits {
+            diagnostics.extend(limits.validate());
+        }
+        if let Some(templates) = &self.prompt_templates {
+            diagnostics.extend(templates.validate());
+        }
+        if let Some(arguments) = &self.tool_arguments {
+            diagnostics.extend(arguments.validate());
+        }
+        if let Some(response_pii) = &self.response_pii {
+            diagnostics.extend(response_pii.validate());
+        }
+        if let Some(scrubbing) = &self.header_scrubbing {
+            diagnostics.extend(scrubbing.validate());
+        }
+        if let Some(vault) = &self.pii_vault {
+            diagnostics.extend(vault.validate());
+        }
+        if let Some(sessions) = &self.sessions {
+            diagnostics.extend(sessions.validate());
+        }
+        if let Some(replay) = &self.replay_protection {
+            diagnostics.extend(replay.validate());
+            if self.sessions.is_none() {
+                diagnostics.push("replay_protection: requires sessions".to_string());
+            }
+        }
+        diagnostics.extend(self.verdict_cache.validate());
+        if let Some(control) = &self.control {
+            diagnostics.extend(control.validate());
+        }
+        if let Some(quarantine) = &self.quarantine {
+            diagnostics.extend(quarantine.validate());
+        }
+        if let Some(actions) = &self.severity_actions {
+            diagnostics.extend(actions.validate());
+            if actions.bans() && self.sessions.is_none() {
+                diagnostics.push("severity_actions: ban requires sessions".to_string());
+            }
+        }
+        if let Some(penalties) = &self.agent_penalties {
+            diagnostics.extend(penalties.validate());
+        }
+        if let Some(approval) = &self.tool_approval {
+            diagnostics.extend(approval.validate());
+        }
+        if let Some(watchdog) = &self.stream_watchdog {
+            diagnostics.extend(watchdog.validate());
+        }
+        if let Some(limits) = &self.notification_limits {
+            diagnostics.extend(limits.validate());
+        }
+        diagnostics.extend(crate::policy::rules::validate(&self.policy_rules));
+        diagnostics.extend(crate::policy::routes::validate(&self.routes));
+        if let Some(classification) = &self.classification {
+            diagnostics.extend(classification.validate());
+        }
+        if let Some(tiers) = &self.identity_tiers {
+            diagnostics.extend(tiers.validate());
+        }
+        if let Some(network) = &self.network_policy {
+            diagnostics.extend(network.validate());
+        }
+        if self.policy_utc_offset_minutes.abs() > MAX_UTC_OFFSET_MINUTES {
+            diagnostics.push(format!(
+                "policy_utc_offset_minutes: must be within ±{}",
+                MAX_UTC_OFFSET_MINUTES
+            ));
+        }
+        if let Some(pdp) = &self.pdp {
+            diagnostics.extend(pdp.validate());
+        }
+
+        diagnostics
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Whether bodies of a `Content-Type` are scanned
+    ///
+    /// Globs match the lowercased media type, without parameters.
+    pub fn inspects_content_type(&self, content_type: &str) -> bool {
+        let media = content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+        let matches = |p: &String| glob_match(&p.to_ascii_lowercase(), &media);
+        self.inspect_content_types.iter().any(matches)
+            && !self.binary_content_types.iter().any(matches)
+    }
 
-    #[test]
-    fn test_default_config() {
-        let config = FilterConfig::default();
-        assert!(!config.blocked_patterns.is_empty());
-        assert!(config.max_body_size > 0);
-        assert!(config.ring_buffer_size > 0);
+    /// Whether response bodies are inspected or rewritten
+    ///
+    /// Such responses must arrive in an encoding the filter can decode.
+    pub fn inspects_responses(&self) -> bool {
+        self.response_scanning.is_some()
+            || self.tool_call_policy.is_some()
+            || self.stream_watchdog.is_some()
+            || self.notification_limits.is_some()
+            || self.mcp_result_scanning.is_some()
+            || self.tool_result_limits.is_some()
+            || self.prompt_templates.as_ref().is_some_and(|t| t.scan_results)
+            || self.response_pii.is_some()
+            || self.pii_vault.as_ref().is_some_and(|v| v.detokenize_responses)
+    }
+
+    /// Whether a check reads the tool name or arguments of an MCP call
+    ///
+    /// The JSON-RPC sniffer then captures the request's `params`.
+    pub fn reads_call_params(&self) -> bool {
+        let tier_tools = self
+            .identity_tiers
+            .as_ref()
+            .is_some_and(|t| t.tiers.values().any(|tier| tier.allowed_tools.is_some()));
+        let prompt_arguments = self.prompt_templates.as_ref().is_some_and(|p| p.scan_arguments);
+        self.tool_approval.is_some()
+            || self.tool_arguments.is_some()
+            || !self.policy_rules.is_empty()
+            || self.pdp.is_some()
+            || tier_tools
+            || prompt_arguments
+    }
+
+    /// Check if an MCP method is allowed
+    pub fn is_mcp_method_allowed(&self, method: &str) -> bool {
+        self.mcp_method_policy().is_allowed(method)
+    }
+
+    /// MCP method allow/deny policy
+    pub fn mcp_method_policy(&self) -> MethodPolicy {
+        MethodPolicy::new(
+            self.mcp_allowed_methods.clone(),
+            self.mcp_denied_methods.clone(),
+        )
+    }
+
+    /// Append the built-in pattern packs enabled by flags to `blocked_patterns`
+    ///
+    /// Idempotent: patterns already listed are not added twice.
+    pub fn expand_builtin_patterns(&mut self) {
+        if !self.multilingual_patterns {
+            return;
+        }
+        for pattern in multilingual_patterns() {
+            if !self.blocked_patterns.contains(&pattern) {
+                self.blocked_patterns.push(pattern);
+            }
+        }
+    }
+
+    /// PII redactor honouring the allowed email domains and deployment regions
+    pub fn pii_redactor(&self) -> PiiRedactor {
+        PiiRedactor::default()
+            .with_allowed_domains(&self.pii_allowed_domains)
+            .with_regions(&self.pii_regions)
+            .with_min_confidence(self.pii_min_confidence)
+    }
+}
+
+/// Short digest identifying a pattern list: the first 12 hex digits of the
+/// SHA-256 of the patterns, one per line
+pub fn pattern_digest(patterns: &[String]) -> String {
+    let mut digest = to_hex(&sha256(patterns.join("\n").as_bytes()));
+    digest.truncate(VERSION_LEN);
+    digest
+}
+
+/// Configuration compiled once per `on_configure` and shared by requests
+///
+/// Requests hold an `Rc` handle instead of a copy of the configuration:
+/// the blocked patterns and the pricing table are built here, once, rather
+/// than for every request. Policy rules are already compiled by parsing.
+#[derive(Clone, Debug)]
+pub struct ConfigSnapshot {
+    config: FilterConfig,
+    /// `blocked_patterns`, compiled for the scanner
+    patterns: Rc<[Pattern]>,
+    /// `blocked_patterns` flattened into a transition table (None = too large)
+    table: Option<Rc<PatternTable>>,
+    /// Pricing table built from `model_pricing`
+    token_counter: Rc<TokenCounter>,
+    /// Same configuration with the experiment's candidate bundle as
+    /// `blocked_patterns`
+    candidate: Option<Rc<ConfigSnapshot>>,
+    /// Compiled dry-run candidate configuration
+    dry_run_snapshot: Option<Rc<ConfigSnapshot>>,
+    /// SHA-256 (hex) of the configuration as pushed, when pushed
+    digest: Option<String>,
+    /// Digest of the compiled blocked patterns
+    pattern_version: String,
+    /// Pattern identifiers, by pattern (None when no pattern has one)
+    pattern_ids: Option<Rc<BTreeMap<String, String>>>,
+}
+
+impl ConfigSnapshot {
+    /// Compile a configuration
+    pub fn new(mut config: FilterConfig) -> Self {
+        config.expand_builtin_patterns();
+        let patterns: Vec<Pattern> =
+            config.blocked_patterns.iter().map(|s| Pattern::from_string(s)).collect();
+        let patterns: Rc<[Pattern]> = patterns.into();
+        let candidate = config.pattern_experiment.as_ref().map(|experiment| {
+            let mut candidate = config.clone();
+            candidate.blocked_patterns = experiment.candidate_patterns.clone().into();
+            candidate.pattern_experiment = None;
+            candidate.dry_run = None;
+            Rc::new(Self::new(candidate))
+        });
+        let dry_run_snapshot =
+            config.dry_run.as_ref().map(|d| Rc::new(Self::new((*d.config).clone())));
+        let pattern_ids = config.blocked_patterns.ids();
+        Self {
+            table: PatternTable::build(patterns.clone()).map(Rc::new),
+            pattern_version: pattern_digest(&config.blocked_patterns),
+            pattern_ids: (!pattern_ids.is_empty()).then(|| Rc::new(pattern_ids)),
+            digest: None,
+            patterns,
+            token_counter: Rc::new(TokenCounter::from_config(&config)),
+            candidate,
+            dry_run_snapshot,
+            config,
+        }
+    }
+
+    /// Compiled blocked patterns
+    pub fn patterns(&self) -> &Rc<[Pattern]> {
+        &self.patterns
+    }
+
+    /// Compiled transition table of the blocked patterns
+    pub fn table(&self) -> Option<&Rc<PatternTable>> {
+        self.table.as_ref()
+    }
+
+    /// Token counter with the configured pricing
+    pub fn token_counter(&self) -> &Rc<TokenCounter> {
+        &self.token_counter
+    }
+
+    /// Pattern identifiers, by pattern
+    pub fn pattern_ids(&self) -> Option<&Rc<BTreeMap<String, String>>> {
+        self.pattern_ids.as_ref()
+    }
+
+    /// Compiled candidate bundle of the pattern experiment
+    pub fn candidate(&self) -> Option<&Rc<ConfigSnapshot>> {
+        self.candidate.as_ref()
+    }
+
+    /// Compiled dry-run candidate configuration
+    pub fn dry_run(&self) -> Option<&Rc<ConfigSnapshot>> {
+        self.dry_run_snapshot.as_ref()
+    }
+
+    /// Record the digest of the configuration bytes this was parsed from
+    pub fn with_digest(mut self, digest: String) -> Self {
+        self.digest = Some(digest);
+        self
+    }
+
+    /// SHA-256 (hex) of the pushed configuration (None for the defaults)
+    pub fn digest(&self) -> Option<&str> {
+        self.digest.as_deref()
+    }
+
+    /// Version of the blocked pattern set (digest of the patterns)
+    pub fn pattern_version(&self) -> &str {
+        &self.pattern_version
+    }
+
+    /// Short version of the configuration: a digest prefix, or `default`
+    pub fn config_version(&self) -> &str {
+        match &self.digest {
+            Some(digest) => &digest[..digest.len().min(VERSION_LEN)],
+            None => "default",
+        }
+    }
+
+    /// Mutable configuration, for per-request limits (e.g. identity tiers)
+    ///
+    /// `blocked_patterns` and `model_pricing` must not be changed here: their
+    /// compiled forms are not rebuilt.
+    pub fn config_mut(&mut self) -> &mut FilterConfig {
+        &mut self.config
+    }
+}
+
+impl Default for ConfigSnapshot {
+    fn default() -> Self {
+        Self::new(FilterConfig::default())
+    }
+}
+
+impl Deref for ConfigSnapshot {
+    type Target = FilterConfig;
+
+    fn deref(&self) -> &FilterConfig {
+        &self.config
+    }
+}
+
+/// Configuration parsing errors
+#[derive(Debug)]
+pub enum ConfigError {
+    InvalidUtf8(String),
+    InvalidJson(String),
+    /// One or more field-level problems
+    Invalid(Vec<String>),
+    /// Configuration larger than `MAX_CONFIG_BYTES` (its size)
+    TooLarge(usize),
+}
+
+impl ConfigError {
+    /// Stable snake_case name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConfigError::InvalidUtf8(_) => "invalid_utf8",
+            ConfigError::InvalidJson(_) => "invalid_json",
+            ConfigError::Invalid(_) => "invalid",
+            ConfigError::TooLarge(_) => "too_large",
+        }
+    }
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::InvalidUtf8(e) => write!(f, "Invalid UTF-8: {}", e),
+            ConfigError::InvalidJson(e) => write!(f, "Invalid JSON: {}", e),
+            ConfigError::Invalid(diagnostics) => {
+                write!(f, "Invalid configuration: {}", diagnostics.join("; "))
+            }
+            ConfigError::TooLarge(size) => write!(
+                f,
+                "Configuration of {} bytes exceeds the cap of {}",
+                size, MAX_CONFIG_BYTES
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::governance::{
+        BinaryKind, InjectionSeverity, Language, McpResultAction, PiiAction, PiiType,
+        RepetitionAction, ScrubAction, SeverityAction, UrlAction,
+    };
+    use crate::policy::RequestClass;
+    use crate::streaming::EntropyAction;
+
+    #[test]
+    fn test_default_config() {
+        let config = FilterConfig::default();
+        assert!(!config.blocked_patterns.is_empty());
+        assert!(config.max_body_size > 0);
+        assert!(config.ring_buffer_size > 0);
     }
 
     #[test]
@@ -150,5 +1393,924 @@ mod tests {
         };
         assert!(restricted.is_mcp_method_allowed("tools/list"));
         assert!(!restricted.is_mcp_method_allowed("tools/call"));
+
+        let json = r#"{"mcp_allowed_methods": ["tools/*"], "mcp_denied_methods": ["tools/call"]}"#;
+        let globbed = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        assert!(globbed.is_mcp_method_allowed("tools/list"));
+        assert!(!globbed.is_mcp_method_allowed("tools/call"));
+        assert!(!globbed.is_mcp_method_allowed("resources/read"));
+    }
+
+    #[test]
+    fn test_usage_response_headers_opt_in() {
+        assert!(!FilterConfig::default().usage_response_headers);
+
+        let json = r#"{"usage_response_headers": true}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        assert!(config.usage_response_headers);
+    }
+
+    #[test]
+    fn test_parse_model_pricing() {
+        let json = r#"{"model_pricing": [
+            {"model": "gpt-4o*", "input_per_1k": 0.0025, "output_per_1k": 0.01, "cached_input_per_1k": 0.00125},
+            {"model": "my-finetune", "input_per_1k": 0.001, "output_per_1k": 0.002}
+        ]}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        assert_eq!(config.model_pricing.len(), 2);
+        assert!(!config.replace_default_pricing);
+
+        let prefix = &config.model_pricing[0];
+        assert_eq!(prefix.matches("gpt-4o-mini"), Some(6));
+        assert_eq!(prefix.matches("gpt-4"), None);
+
+        let exact = &config.model_pricing[1];
+        assert!(exact.matches("my-finetune").is_some());
+        assert!(exact.matches("my-finetune-v2").is_none());
+    }
+
+    #[test]
+    fn test_parse_tracing() {
+        assert!(FilterConfig::default().tracing.is_none());
+
+        let json = r#"{"tracing": {"collector_cluster": "otel"}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let tracing = config.tracing.unwrap();
+        assert_eq!(tracing.collector_cluster.as_deref(), Some("otel"));
+        assert_eq!(tracing.collector_path, "/v1/traces");
+        assert_eq!(tracing.service_name, "ai-guard");
+    }
+
+    #[test]
+    fn test_parse_pii_allowed_domains() {
+        let json = r#"{"pii_allowed_domains": ["corp.example", "@partner.io"]}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        assert_eq!(config.pii_allowed_domains.len(), 2);
+        assert!(config.pii_redactor().scan("mail ops@eu.corp.example").is_empty());
+
+        let found = diagnostics(r#"{"pii_allowed_domains": ["ops@corp.example", ""]}"#);
+        assert_eq!(
+            found,
+            vec![
+                "pii_allowed_domains[0]: invalid domain".to_string(),
+                "pii_allowed_domains[1]: invalid domain".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_pii_regions() {
+        let config = FilterConfig::from_bytes(b"{}").unwrap();
+        assert_eq!(config.pii_regions, vec![PiiRegion::Us]);
+
+        let config = FilterConfig::from_bytes(br#"{"pii_regions": ["uk", "de"]}"#).unwrap();
+        let redactor = config.pii_redactor();
+        assert_eq!(redactor.scan("NI AB123456C").len(), 1);
+        assert!(redactor.scan("SSN 123-45-6789").is_empty());
+
+        assert!(FilterConfig::from_bytes(br#"{"pii_regions": ["mars"]}"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_pii_min_confidence() {
+        let config = FilterConfig::from_bytes(br#"{"pii_min_confidence": 0.5}"#).unwrap();
+        let redactor = config.pii_redactor();
+        assert!(redactor.contains_pii("call 555-123-4567"));
+        assert!(!redactor.contains_pii("order 555-123-4567"));
+
+        let found = diagnostics(r#"{"pii_min_confidence": 1.5}"#);
+        assert_eq!(found, vec!["pii_min_confidence: must be between 0 and 1".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_pii_vault() {
+        let json = r#"{"pii_vault": {"key": "0123456789abcdef"}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        assert!(config.pii_vault.unwrap().detokenize_responses);
+
+        let found = diagnostics(r#"{"pii_vault": {"key": "short"}}"#);
+        assert_eq!(found, vec!["pii_vault.key: must be at least 16 bytes".to_string()]);
+        assert!(FilterConfig::from_bytes(br#"{"pii_vault": {}}"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_audit_capture() {
+        let json = r#"{"audit_capture": {"bytes": 64, "hash": true}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let capture = config.audit_capture.unwrap();
+        assert_eq!(capture.bytes, 64);
+        assert_eq!(capture.max_len, 256);
+        assert!(capture.hash);
+
+        let found = diagnostics(r#"{"ring_buffer_size": 32, "audit_capture": {"bytes": 64}}"#);
+        let expected = "audit_capture.bytes: must not exceed ring_buffer_size".to_string();
+        assert_eq!(found, vec![expected]);
+    }
+
+    #[test]
+    fn test_parse_audit_signing() {
+        let json = r#"{"audit_signing": {"key": "0123456789abcdef", "key_id": "k1"}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        assert_eq!(config.audit_signing.unwrap().key_id, "k1");
+
+        let found = diagnostics(r#"{"audit_signing": {"key": "short", "key_id": "k1"}}"#);
+        assert_eq!(found, vec!["audit_signing.key: must be at least 16 bytes".to_string()]);
+        assert!(FilterConfig::from_bytes(br#"{"audit_signing": {"key_id": "k1"}}"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_metric_dimensions() {
+        let json = r#"{"metric_dimensions": {"transport": false, "max_values": 8}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let dimensions = config.metric_dimensions.unwrap();
+        assert!(dimensions.tenant && !dimensions.transport);
+        assert_eq!(dimensions.max_values, 8);
+
+        let found = diagnostics(r#"{"metric_dimensions": {"max_values": 0}}"#);
+        assert_eq!(found, vec!["metric_dimensions.max_values: must be greater than 0".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_overhead_budget() {
+        let json = r#"{"overhead_budget": {"budget_us": 500}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let budget = config.overhead_budget.unwrap();
+        assert_eq!(budget.budget_us, 500);
+        assert_eq!(budget.window, 500);
+
+        let found = diagnostics(r#"{"overhead_budget": {"window": 0}}"#);
+        assert_eq!(found, vec!["overhead_budget.window: must be greater than 0".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_audit_sink() {
+        let json = r#"{"audit_sink": {"cluster": "siem", "path": "/ingest", "batch_size": 10}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let sink = config.audit_sink.unwrap();
+        assert_eq!(sink.cluster, "siem");
+        assert_eq!(sink.path, "/ingest");
+        assert_eq!(sink.batch_size, 10);
+        assert_eq!(sink.max_attempts, 3);
+        assert_eq!(config.audit_format, AuditFormat::Json);
+
+        let json = r#"{"audit_format": "cef"}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        assert_eq!(config.audit_format, AuditFormat::Cef);
+    }
+
+    #[test]
+    fn test_parse_block_response_verbosity() {
+        let config = FilterConfig::from_bytes(b"{}").unwrap();
+        assert_eq!(config.block_response_verbosity, BlockVerbosity::Opaque);
+        let json = r#"{"block_response_verbosity": "category"}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        assert_eq!(config.block_response_verbosity, BlockVerbosity::Category);
+        let json = r#"{"block_response_verbosity": "verbose"}"#;
+        assert!(FilterConfig::from_bytes(json.as_bytes()).is_err());
+    }
+
+    fn diagnostics(json: &str) -> Vec<String> {
+        match FilterConfig::from_bytes_validated(json.as_bytes()) {
+            Err(ConfigError::Invalid(diagnostics)) => diagnostics,
+            other => panic!("expected diagnostics, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_parse_header_policy() {
+        let json = r#"{"header_policy": {"blocked_patterns": ["ignore previous"], "strip_headers": ["x-internal"]}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let policy = config.header_policy.unwrap();
+        assert_eq!(policy.strip_headers, vec!["x-internal".to_string()]);
+        assert_eq!(policy.max_value_size, 8 * 1024);
+
+        let found = diagnostics(r#"{"header_policy": {"max_total_size": 0}}"#);
+        assert_eq!(found, vec!["header_policy.max_total_size: must be greater than 0".to_string()]);
+    }
+
+    #[test]
+    fn test_multilingual_patterns() {
+        let json = r#"{"blocked_patterns": ["jailbreak"], "multilingual_patterns": true}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        assert!(config.validate().is_empty());
+        let snapshot = ConfigSnapshot::new(config);
+        assert_eq!(snapshot.blocked_patterns.len(), 1 + MULTILINGUAL_PATTERNS.len());
+        assert!(snapshot.blocked_patterns.iter().any(|p| p == "ignoriere alle vorherigen"));
+        assert_eq!(snapshot.patterns().len(), snapshot.blocked_patterns.len());
+        assert!(!ConfigSnapshot::default().multilingual_patterns);
+    }
+
+    #[test]
+    fn test_parse_entropy_detection() {
+        let json = r#"{"entropy_detection": {"action": "decode_and_scan", "min_run": 128}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let entropy = config.entropy_detection.unwrap();
+        assert_eq!(entropy.action, EntropyAction::DecodeAndScan);
+        assert_eq!(entropy.window, 64);
+
+        let found = diagnostics(r#"{"entropy_detection": {"threshold_bits": 9.0}}"#);
+        let expected = "entropy_detection.threshold_bits: must be within (0, 8]".to_string();
+        assert_eq!(found, vec![expected]);
+    }
+
+    #[test]
+    fn test_parse_repetition_detection() {
+        let json = r#"{"repetition_detection": {"action": "limit", "limit_max_tokens": 128}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let repetition = config.repetition_detection.unwrap();
+        assert_eq!(repetition.action, RepetitionAction::Limit);
+        assert_eq!(repetition.max_repeats, 200);
+
+        let found = diagnostics(r#"{"repetition_detection": {"max_char_run": 0}}"#);
+        let expected = "repetition_detection.max_char_run: must be greater than 0".to_string();
+        assert_eq!(found, vec![expected]);
+    }
+
+    #[test]
+    fn test_parse_pattern_experiment() {
+        let json = r#"{"blocked_patterns": ["ignore previous"],
+            "pattern_experiment": {"candidate_patterns": ["ignore previous", "act as dan"]}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let experiment = config.pattern_experiment.clone().unwrap();
+        assert_eq!(experiment.name, "candidate");
+        assert_eq!(experiment.percent, 10);
+        let snapshot = ConfigSnapshot::new(config);
+        let candidate = snapshot.candidate().unwrap();
+        assert_eq!(candidate.patterns().len(), 2);
+        assert!(candidate.pattern_experiment.is_none());
+
+        let found = diagnostics(r#"{"pattern_experiment": {"candidate_patterns": [""]}}"#);
+        let expected = "pattern_experiment.candidate_patterns: empty pattern".to_string();
+        assert_eq!(found, vec![expected]);
+    }
+
+    #[test]
+    fn test_parse_health() {
+        let config = FilterConfig::from_bytes(br#"{"health": {}}"#).unwrap();
+        assert_eq!(config.health.unwrap().path, "/.well-known/ai-guard/health");
+
+        let found = diagnostics(r#"{"health": {"path": "health"}}"#);
+        assert_eq!(found, vec!["health.path: must start with '/'".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_pattern_ids() {
+        let json = r#"{"blocked_patterns": ["drop table",
+            {"pattern": "jailbreak", "id": "PI-0001", "description": "Known jailbreak"}]}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        assert_eq!(config.blocked_patterns, vec!["drop table", "jailbreak"]);
+        let snapshot = ConfigSnapshot::new(config);
+        assert_eq!(snapshot.patterns().len(), 2);
+        assert_eq!(snapshot.pattern_ids().unwrap().get("jailbreak").unwrap(), "PI-0001");
+
+        let found = diagnostics(
+            r#"{"blocked_patterns": [{"pattern": "a", "id": "X"}, {"pattern": "b", "id": "X"}]}"#,
+        );
+        assert_eq!(found, vec!["blocked_patterns: duplicate id X".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_bypass() {
+        let json = r#"{"bypass": {"keys": {"healthcheck": "0123456789abcdef"}}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let bypass = config.bypass.unwrap();
+        assert_eq!(bypass.max_ttl_secs, 300);
+        assert_eq!(bypass.max_per_minute, 60);
+
+        let found = diagnostics(r#"{"bypass": {}}"#);
+        assert_eq!(found, vec!["bypass.keys: must not be empty".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_verdict_propagation() {
+        let json = r#"{"verdict_propagation": {"key": "0123456789abcdef", "key_id": "edge"}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        assert_eq!(config.verdict_propagation.unwrap().max_age_secs, 30);
+
+        let found = diagnostics(r#"{"verdict_propagation": {"key": "short", "key_id": "edge"}}"#);
+        let expected = "verdict_propagation.key: must be at least 16 bytes".to_string();
+        assert_eq!(found, vec![expected]);
+    }
+
+    #[test]
+    fn test_parse_dry_run() {
+        let json = r#"{"dry_run": {"config": {"blocked_patterns": ["developer mode"]},
+            "sample_percent": 20}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        assert_eq!(config.dry_run.as_ref().unwrap().max_body_size, 256 * 1024);
+        let snapshot = ConfigSnapshot::new(config);
+        assert_eq!(snapshot.dry_run().unwrap().patterns().len(), 1);
+
+        let found = diagnostics(r#"{"dry_run": {"config": {"max_messages": 0}}}"#);
+        let expected = "dry_run.config.max_messages: must be greater than 0".to_string();
+        assert_eq!(found, vec![expected]);
+    }
+
+    #[test]
+    fn test_parse_rollout_percent() {
+        let json = r#"{"response_scanning": {}, "rollout_percent": {"response_scanning": 5}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        assert_eq!(config.rollout_percent.get("response_scanning"), Some(&5));
+
+        let found = diagnostics(r#"{"rollout_percent": {"scanning": 5}}"#);
+        let expected = "rollout_percent.scanning: unknown subsystem or language pack".to_string();
+        assert_eq!(found, vec![expected]);
+    }
+
+    #[test]
+    fn test_parse_threat_feed() {
+        let json = r#"{"threat_feed": {"cluster": "intel", "refresh_interval_secs": 60}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let feed = config.threat_feed.unwrap();
+        assert_eq!(feed.path, "/threat-feed.json");
+        assert_eq!(feed.refresh_interval_secs, 60);
+
+        let found = diagnostics(r#"{"threat_feed": {"cluster": "intel", "path": "feed"}}"#);
+        assert_eq!(found, vec!["threat_feed.path: must start with '/'".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_url_policy() {
+        let json = r#"{"url_policy": {"allow_hosts": ["*.corp.example"], "action": "defang"}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let urls = config.url_policy.unwrap();
+        assert_eq!(urls.action, UrlAction::Defang);
+        assert!(urls.block_private && urls.scan_results);
+
+        let found = diagnostics(r#"{"url_policy": {"deny_hosts": [""]}}"#);
+        assert_eq!(found, vec!["url_policy.deny_hosts: empty pattern".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_markup_scanning() {
+        let json = r#"{"markup_scanning": {"hidden_patterns": ["exfiltrate"], "hidden_weight": 3.0}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let markup = config.markup_scanning.unwrap();
+        assert_eq!(markup.hidden_patterns, vec!["exfiltrate".to_string()]);
+        assert_eq!(markup.block_score, 0.5);
+
+        let found = diagnostics(r#"{"markup_scanning": {"block_score": 0}}"#);
+        let expected = "markup_scanning.block_score: must be greater than 0".to_string();
+        assert_eq!(found, vec![expected]);
+    }
+
+    #[test]
+    fn test_parse_language_packs() {
+        let json = r#"{"language_packs": {"packs": {"de": ["ignoriere alle vorherigen"]}}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let packs = config.language_packs.unwrap();
+        assert!(packs.scan_undetected);
+        assert_eq!(packs.pack(Language::German).map(Vec::len), Some(1));
+
+        let found = diagnostics(r#"{"language_packs": {"packs": {"klingon": []}}}"#);
+        assert_eq!(found, vec!["language_packs.packs.klingon: unknown language".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_chat_policy() {
+        let json = r#"{"role_patterns": {"system": [], "tool": ["exfiltrate"]}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let policy = config.role_patterns.unwrap();
+        assert_eq!(policy.system, Some(Vec::new()));
+        assert!(policy.user.is_none());
+
+        let found = diagnostics(r#"{"role_patterns": {"tool": [""]}, "max_tokens_limit": 0}"#);
+        assert_eq!(
+            found,
+            vec![
+                "role_patterns.tool: empty pattern".to_string(),
+                "max_tokens_limit: must be greater than 0".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_request_limits() {
+        let json = r#"{"max_tokens_limit": 4096, "clamp_max_tokens": true,
+                        "max_prompt_tokens": 100000, "max_messages": 200}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        assert!(config.clamp_max_tokens);
+        assert_eq!(config.max_prompt_tokens, Some(100000));
+        assert_eq!(config.max_messages, Some(200));
+        assert!(!FilterConfig::default().clamp_max_tokens);
+
+        let found = diagnostics(r#"{"clamp_max_tokens": true, "max_messages": 0}"#);
+        assert_eq!(
+            found,
+            vec![
+                "clamp_max_tokens: requires max_tokens_limit".to_string(),
+                "max_messages: must be greater than 0".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_response_scanning() {
+        let json = r#"{"response_scanning": {"max_choices": 2}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let scan = config.response_scanning.unwrap();
+        assert_eq!(scan.max_choices, 2);
+        assert!(scan.blocked_patterns.is_none());
+        assert!(FilterConfig::default().response_scanning.is_none());
+
+        let found = diagnostics(r#"{"response_scanning": {"max_event_size": 0}}"#);
+        assert_eq!(
+            found,
+            vec!["response_scanning.max_event_size: must be greater than 0".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_parse_tool_call_policy() {
+        let json = r#"{"tool_call_policy": {"blocked_commands": ["curl "]}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let policy = config.tool_call_policy.unwrap();
+        assert!(policy.detect_injection);
+        assert_eq!(policy.blocked_commands, vec!["curl ".to_string()]);
+
+        let found = diagnostics(r#"{"tool_call_policy": {"blocked_commands": [""]}}"#);
+        assert_eq!(found, vec!["tool_call_policy.blocked_commands: empty command".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_model_policy() {
+        let json = r#"{"model_policy": {"allowed_models": ["gpt-4o*"],
+                        "overrides": {"o1-preview": "gpt-4o"}}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let policy = config.model_policy.unwrap();
+        assert_eq!(policy.allowed_models, vec!["gpt-4o*".to_string()]);
+        assert_eq!(policy.overrides.get("o1-preview").map(String::as_str), Some("gpt-4o"));
+
+        let found = diagnostics(r#"{"model_policy": {"allowed_models": [""]}}"#);
+        assert_eq!(found, vec!["model_policy.allowed_models: empty model".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_mcp_result_scanning() {
+        let json = r#"{"mcp_result_scanning": {"action": "annotate", "methods": ["tools/*"]}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let policy = config.mcp_result_scanning.unwrap();
+        assert_eq!(policy.action, McpResultAction::Annotate);
+        assert!(policy.applies_to("tools/call"));
+        assert!(!policy.applies_to("resources/read"));
+
+        let json = r#"{"mcp_result_scanning": {"action": "quarantine"}}"#;
+        assert!(FilterConfig::from_bytes(json.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_parse_tool_result_limits() {
+        let json = r#"{"tool_result_limits": {"max_depth": 8, "action": "truncate"}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let limits = config.tool_result_limits.unwrap();
+        assert_eq!(limits.max_depth, 8);
+        assert_eq!(limits.max_array_len, 10_000);
+        assert_eq!(limits.action, crate::governance::ResultLimitAction::Truncate);
+
+        let found = diagnostics(r#"{"tool_result_limits": {"max_depth": 1}}"#);
+        let expected = "tool_result_limits.max_depth: must be between 2 and 64".to_string();
+        assert_eq!(found, vec![expected]);
+    }
+
+    #[test]
+    fn test_parse_prompt_templates() {
+        let json = r#"{"prompt_templates": {"scan_results": false, "patterns": ["reveal the key"]}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let templates = config.prompt_templates.unwrap();
+        assert!(templates.scan_arguments);
+        assert!(!templates.scan_results);
+        assert_eq!(templates.patterns.unwrap(), vec!["reveal the key".to_string()]);
+
+        let found = diagnostics(r#"{"prompt_templates": {"max_event_size": 0}}"#);
+        let expected = "prompt_templates.max_event_size: must be greater than 0".to_string();
+        assert_eq!(found, vec![expected]);
+    }
+
+    #[test]
+    fn test_parse_tool_arguments() {
+        let config = FilterConfig::from_bytes(br#"{"tool_arguments": {}}"#).unwrap();
+        assert_eq!(config.tool_arguments.unwrap().rules.len(), 4);
+
+        let json = r#"{"tool_arguments": {"rules": [
+            {"tools": ["read_file"], "checks": ["absolute_path"], "allowed_paths": ["/srv"]}]}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let rule = &config.tool_arguments.unwrap().rules[0];
+        assert_eq!(rule.checks, vec![crate::governance::ArgumentCheck::AbsolutePath]);
+
+        let json = r#"{"tool_arguments": {"rules": [
+            {"tools": ["read_file"], "checks": ["traversal"], "allowed_paths": ["srv"]}]}}"#;
+        let expected = "tool_arguments.rules[0].allowed_paths: must be absolute paths".to_string();
+        assert_eq!(diagnostics(json), vec![expected]);
+
+        let json = r#"{"tool_arguments": {"rules": [{"tools": ["x"], "checks": ["eval"]}]}}"#;
+        assert!(FilterConfig::from_bytes(json.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_parse_response_pii() {
+        let json = r#"{"response_pii": {"actions": {"email": "log", "ssn": "block"}}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let response_pii = config.response_pii.unwrap();
+        assert_eq!(response_pii.action_for(PiiType::Ssn), PiiAction::Block);
+        assert_eq!(response_pii.action_for(PiiType::Phone), PiiAction::Redact);
+        assert!(!response_pii.redactions_header);
+
+        let found = diagnostics(r#"{"response_pii": {"actions": {"passport": "redact"}}}"#);
+        assert_eq!(found, vec!["response_pii.actions.passport: unknown PII type".to_string()]);
+
+        let json = r#"{"response_pii": {"default_action": "mask"}}"#;
+        assert!(FilterConfig::from_bytes(json.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_parse_header_scrubbing() {
+        let config = FilterConfig::from_bytes(br#"{"header_scrubbing": {}}"#).unwrap();
+        let scrubbing = config.header_scrubbing.unwrap();
+        assert_eq!(scrubbing.action_for("Cookie"), Some(ScrubAction::Strip));
+        assert_eq!(scrubbing.action_for("authorization"), None);
+
+        let json = r#"{"header_scrubbing": {"rules": [{"headers": ["authorization"], "action": "hash"}]}}"#;
+        let expected = "header_scrubbing.hash_key: required by hash rules".to_string();
+        assert_eq!(diagnostics(json), vec![expected]);
+
+        let json = r#"{"header_scrubbing": {"hash_key": "short"}}"#;
+        let expected = "header_scrubbing.hash_key: must be at least 16 bytes".to_string();
+        assert_eq!(diagnostics(json), vec![expected]);
+    }
+
+    #[test]
+    fn test_parse_sessions() {
+        let json = r#"{"sessions": {"headers": ["x-thread-id"], "block_after": 10}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let sessions = config.sessions.unwrap();
+        assert_eq!(sessions.headers, vec!["x-thread-id".to_string()]);
+        assert_eq!(sessions.block_after, 10);
+        assert_eq!(sessions.warn_after, 1);
+
+        let found = diagnostics(r#"{"sessions": {"headers": []}}"#);
+        assert_eq!(found, vec!["sessions.headers: must not be empty".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_replay_protection() {
+        let json = r#"{"sessions": {}, "replay_protection": {"ttl_secs": 60}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let replay = config.replay_protection.unwrap();
+        assert_eq!(replay.ttl_secs, 60);
+        assert_eq!(replay.max_entries_per_bucket, 256);
+
+        let found = diagnostics(r#"{"replay_protection": {}}"#);
+        assert_eq!(found, vec!["replay_protection: requires sessions".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_verdict_cache() {
+        assert!(FilterConfig::default().verdict_cache.enabled);
+        let json = r#"{"verdict_cache": {"enabled": false}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        assert!(!config.verdict_cache.enabled);
+
+        let found = diagnostics(r#"{"verdict_cache": {"max_entries": 0}}"#);
+        assert_eq!(found, vec!["verdict_cache.max_entries: must be greater than 0".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_control() {
+        let json = r#"{"control": {"admin_path": "/ai-guard/control",
+            "admin_token": "0123456789abcdef", "flags": {"monitor_mode": true}}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let control = config.control.unwrap();
+        assert!(control.is_admin_path("/ai-guard/control"));
+        assert!(control.flags.unwrap().monitor_mode);
+
+        let found = diagnostics(r#"{"control": {"flags": {"disabled_categories": [""]}}}"#);
+        assert_eq!(found, vec!["control.flags.disabled_categories: empty category".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_quarantine() {
+        let json = r#"{"quarantine": {"header": "x-route-sandbox", "value": "1"}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let quarantine = config.quarantine.unwrap();
+        assert_eq!(quarantine.header, "x-route-sandbox");
+        assert!(quarantine.applies_to(InjectionSeverity::Medium));
+
+        let found = diagnostics(r#"{"quarantine": {"severities": []}}"#);
+        assert_eq!(found, vec!["quarantine.severities: must not be empty".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_severity_actions() {
+        let json = r#"{"sessions": {}, "severity_actions": {"medium": "block"}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let actions = config.severity_actions.unwrap();
+        assert_eq!(actions.action(InjectionSeverity::Medium), SeverityAction::Block);
+        assert_eq!(actions.action(InjectionSeverity::Critical), SeverityAction::Ban);
+
+        let found = diagnostics(r#"{"severity_actions": {}}"#);
+        assert_eq!(found, vec!["severity_actions: ban requires sessions".to_string()]);
+        let json = r#"{"severity_actions": {"critical": "block"}}"#;
+        assert!(FilterConfig::from_bytes_validated(json.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn test_parse_agent_penalties() {
+        let json = r#"{"agent_penalties": {"block_after": 10, "block_secs": 60}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let penalties = config.agent_penalties.unwrap();
+        assert_eq!(penalties.block_after, 10);
+        assert_eq!(penalties.rate_limit_after, 3);
+
+        let found = diagnostics(r#"{"agent_penalties": {"block_after": 2}}"#);
+        let expected = "agent_penalties: rate_limit_after <= block_after required".to_string();
+        assert_eq!(found, vec![expected]);
+    }
+
+    #[test]
+    fn test_parse_stdio_detection() {
+        let config = FilterConfig::from_bytes(br#"{"stdio_detection": {}}"#).unwrap();
+        assert!(config.stdio_detection.unwrap().block);
+        let json = r#"{"stdio_detection": {"block": false}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        assert!(!config.stdio_detection.unwrap().block);
+        assert!(FilterConfig::from_bytes(br#"{"stdio_detection": {"ban": true}}"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_stream_watchdog() {
+        let json = r#"{"stream_watchdog": {"max_events": 500, "min_bytes_per_sec": 16}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let watchdog = config.stream_watchdog.unwrap();
+        assert_eq!(watchdog.max_events, Some(500));
+        assert_eq!(watchdog.max_duration_secs, Some(3600));
+        assert_eq!(watchdog.grace_secs, 30);
+
+        let found = diagnostics(r#"{"stream_watchdog": {"max_duration_secs": 0}}"#);
+        let expected = "stream_watchdog.max_duration_secs: must be greater than 0".to_string();
+        assert_eq!(found, vec![expected]);
+    }
+
+    #[test]
+    fn test_parse_notification_limits() {
+        let json = r#"{"notification_limits": {"max_per_window": 5, "action": "block"}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let limits = config.notification_limits.unwrap();
+        assert_eq!(limits.max_per_window, 5);
+        assert_eq!(limits.window_secs, 10);
+        assert_eq!(limits.action, crate::governance::NotificationAction::Block);
+
+        let found = diagnostics(r#"{"notification_limits": {"window_secs": 0}}"#);
+        let expected = "notification_limits.window_secs: must be greater than 0".to_string();
+        assert_eq!(found, vec![expected]);
+    }
+
+    #[test]
+    fn test_parse_scan_budget() {
+        let json = r#"{"scan_budget": {"max_scan_bytes": 32768, "max_chunk_micros": 500}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let budget = config.scan_budget.unwrap();
+        assert_eq!(budget.max_scan_bytes, Some(32768));
+        assert!(budget.exceeds_time(501));
+
+        let found = diagnostics(r#"{"scan_budget": {"max_scan_bytes": 0}}"#);
+        assert_eq!(found, vec!["scan_budget.max_scan_bytes: must be greater than 0".to_string()]);
+    }
+
+    #[test]
+    fn test_inspected_content_types() {
+        let config = FilterConfig::default();
+        assert!(config.inspects_content_type("application/json; charset=utf-8"));
+        assert!(config.inspects_content_type("Text/Plain"));
+        assert!(config.inspects_content_type("application/x-www-form-urlencoded"));
+        assert!(!config.inspects_content_type("application/octet-stream"));
+
+        let json = r#"{"inspect_content_types": ["*json*", "application/grpc-web*"],
+            "binary_content_types": ["application/grpc-web+proto"]}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        assert!(config.inspects_content_type("application/grpc-web-text"));
+        assert!(!config.inspects_content_type("application/grpc-web+proto"));
+        assert!(!config.inspects_content_type("text/plain"));
+
+        let found = diagnostics(r#"{"binary_content_types": [""]}"#);
+        assert_eq!(found, vec!["binary_content_types: patterns must not be empty".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_ndjson() {
+        let config = FilterConfig::from_bytes(b"{}").unwrap();
+        assert!(config.ndjson.enabled && !config.ndjson.block_invalid);
+
+        let config = FilterConfig::from_bytes(br#"{"ndjson": {"block_invalid": true}}"#).unwrap();
+        assert!(config.ndjson.enabled && config.ndjson.block_invalid);
+        assert!(FilterConfig::from_bytes(br#"{"ndjson": {"max_records": 1}}"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_grpc_web() {
+        let config = FilterConfig::from_bytes(b"{}").unwrap();
+        assert!(config.grpc_web.enabled);
+        assert_eq!(config.grpc_web.max_message_size, 4 * 1024 * 1024);
+
+        let json = br#"{"grpc_web": {"enabled": false, "max_message_size": 1024}}"#;
+        let config = FilterConfig::from_bytes(json).unwrap();
+        assert!(!config.grpc_web.enabled);
+        assert_eq!(config.grpc_web.max_message_size, 1024);
+
+        let found = diagnostics(r#"{"grpc_web": {"max_message_size": 0}}"#);
+        assert_eq!(found, vec!["grpc_web.max_message_size: must be greater than 0".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_binary_policy() {
+        let json = r#"{"binary_policy": {"allow": ["image", "audio"], "block_unknown": true,
+            "max_size": {"image": 5242880}, "text_only_paths": ["/v1/embeddings"]}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let policy = config.binary_policy.unwrap();
+        assert_eq!(policy.deny, vec![BinaryKind::Executable]);
+        assert_eq!(policy.max_size.get(&BinaryKind::Image), Some(&5242880));
+        assert!(policy.is_text_only("/v1/embeddings"));
+
+        let found = diagnostics(r#"{"binary_policy": {"default_max_size": 0}}"#);
+        let expected = "binary_policy.default_max_size: must be greater than 0";
+        assert_eq!(found, vec![expected.to_string()]);
+        assert!(FilterConfig::from_bytes(br#"{"binary_policy": {"deny": ["zip"]}}"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_tool_approval() {
+        let json = r#"{"tool_approval": {"tools": ["execute_sql"], "cluster": "review",
+            "timeout_ms": 60000}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let approval = config.tool_approval.unwrap();
+        assert!(approval.requires_approval("execute_sql"));
+        assert_eq!(approval.timeout_ms, 60000);
+        assert!(!approval.fail_open);
+
+        let found = diagnostics(r#"{"tool_approval": {"tools": [], "cluster": "review"}}"#);
+        assert_eq!(found, vec!["tool_approval.tools: must list at least one tool".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_policy_rules() {
+        let json = r#"{"policy_rules": [{"name": "no-shell", "when": "tool == 'shell'"}]}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        assert_eq!(config.policy_rules.len(), 1);
+        assert_eq!(config.policy_rules[0].when.source(), "tool == 'shell'");
+
+        let found = diagnostics(r#"{"policy_rules": [{"name": "x", "when": "plan == 1"}]}"#);
+        assert_eq!(found.len(), 1);
+        assert!(found[0].contains("unknown attribute 'plan'"), "{:?}", found);
+    }
+
+    #[test]
+    fn test_parse_routes() {
+        let json = r#"{"routes": [{"name": "mcp", "path_prefixes": ["/mcp"],
+            "overrides": {"max_body_size": 65536}}]}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        assert_eq!(config.routes[0].name, "mcp");
+        assert!(config.routes[0].methods.is_empty());
+
+        let found = diagnostics(r#"{"routes": [{"name": "", "path_prefixes": ["/a"]}]}"#);
+        assert_eq!(found, vec!["routes[0].name: must not be empty".to_string()]);
+    }
+
+    #[test]
+    fn test_config_snapshot_shared() {
+        let config = FilterConfig {
+            blocked_patterns: vec!["Ignore Previous".to_string()].into(),
+            ..Default::default()
+        };
+        let snapshot = Rc::new(ConfigSnapshot::new(config));
+        assert_eq!(snapshot.patterns()[0].bytes, b"ignore previous");
+        assert_eq!(snapshot.max_body_size, default_max_body_size());
+
+        // Handles share the compiled parts; a per-request change copies the rest
+        let mut handle = Rc::clone(&snapshot);
+        Rc::make_mut(&mut handle).config_mut().max_prompt_tokens = Some(10);
+        assert!(Rc::ptr_eq(handle.patterns(), snapshot.patterns()));
+        assert!(Rc::ptr_eq(handle.table().unwrap(), snapshot.table().unwrap()));
+        assert!(Rc::ptr_eq(handle.token_counter(), snapshot.token_counter()));
+        assert_eq!(snapshot.max_prompt_tokens, None);
+    }
+
+    #[test]
+    fn test_parse_classification() {
+        let json = r#"{"classification": {"header": "x-ai-guard-class",
+            "admin_path_prefixes": ["/internal"]}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let classification = config.classification.unwrap();
+        assert_eq!(classification.header.as_deref(), Some("x-ai-guard-class"));
+        assert_eq!(classification.classify(Some("/internal/keys"), false), RequestClass::Admin);
+
+        let found = diagnostics(r#"{"classification": {"header": ""}}"#);
+        assert_eq!(found, vec!["classification.header: must be a regular header name".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_identity_tiers() {
+        let json = r#"{"identity_tiers": {"default_tier": "free",
+            "tiers": {"free": {"max_prompt_tokens": 1000, "allowed_tools": []}}}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let tiers = config.identity_tiers.unwrap();
+        assert_eq!(tiers.resolve(None, None), Some("free"));
+        assert_eq!(tiers.policy("free").unwrap().max_prompt_tokens, Some(1000));
+
+        let found = diagnostics(r#"{"identity_tiers": {"tiers": {}}}"#);
+        assert_eq!(found, vec!["identity_tiers.tiers: must define at least one tier".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_network_policy() {
+        let json = r#"{"network_policy": {"xff_trusted_hops": 1, "deny": ["203.0.113.0/24"]}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let network = config.network_policy.unwrap();
+        assert_eq!(network.xff_trusted_hops, 1);
+        assert!(!network.admits(Some("203.0.113.9".parse().unwrap())));
+
+        let found = diagnostics(r#"{"network_policy": {"allow": ["10.0.0.0/40"]}}"#);
+        assert_eq!(found.len(), 1);
+        assert!(found[0].contains("invalid prefix length"), "{:?}", found);
+    }
+
+    #[test]
+    fn test_parse_policy_environment() {
+        let json = r#"{"environment": "staging", "policy_utc_offset_minutes": -300}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        assert_eq!(config.environment.as_deref(), Some("staging"));
+        assert_eq!(config.policy_utc_offset_minutes, -300);
+
+        let found = diagnostics(r#"{"policy_utc_offset_minutes": 900}"#);
+        assert_eq!(found, vec!["policy_utc_offset_minutes: must be within ±840".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_pdp() {
+        let json = r#"{"pdp": {"cluster": "opa", "timeout_ms": 50}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let pdp = config.pdp.unwrap();
+        assert_eq!(pdp.path, "/v1/data/ai_guard/decision");
+        assert_eq!(pdp.timeout_ms, 50);
+
+        let found = diagnostics(r#"{"pdp": {"cluster": "opa", "path": "decide"}}"#);
+        assert_eq!(found, vec!["pdp.path: must start with '/'".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_decompression() {
+        let config = FilterConfig::default();
+        assert!(config.decompression.enabled);
+        assert!(config.decompression.block_unsupported);
+
+        let json = r#"{"decompression": {"block_unsupported": false, "max_ratio": 50}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        assert!(config.decompression.enabled);
+        assert!(!config.decompression.block_unsupported);
+        assert_eq!(config.decompression.max_ratio, 50);
+    }
+
+    #[test]
+    fn test_validated_accepts_good_config() {
+        let json = r#"{"blocked_patterns": ["jailbreak"], "max_body_size": 1024}"#;
+        assert!(FilterConfig::from_bytes_validated(json.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn test_validated_reports_all_field_errors() {
+        let found = diagnostics(
+            r#"{"blocked_patern": ["x"], "max_body_size": "big", "tracing": {"colector": "x"}}"#,
+        );
+        assert_eq!(found.len(), 3);
+        assert!(found.contains(&"blocked_patern: unknown key".to_string()));
+        assert!(found.iter().any(|d| d.starts_with("max_body_size: invalid type")));
+        assert!(found.iter().any(|d| d.starts_with("tracing: unknown field `colector`")));
+    }
+
+    #[test]
+    fn test_validated_semantic_checks() {
+        let many: Vec<String> = (0..=MAX_BLOCKED_PATTERNS).map(|i| format!("p{}", i)).collect();
+        let json = serde_json::json!({ "blocked_patterns": many }).to_string();
+        assert!(diagnostics(&json)[0].contains("exceeds the cap"));
+
+        let found = diagnostics(r#"{"blocked_patterns": ["ok", " "], "ring_buffer_size": 0}"#);
+        assert!(found.contains(&"blocked_patterns[1]: empty pattern".to_string()));
+        assert!(found.contains(&"ring_buffer_size: must be greater than 0".to_string()));
+
+        let long = "x".repeat(MAX_PATTERN_LEN + 1);
+        let json = serde_json::json!({ "blocked_patterns": [long] }).to_string();
+        let expected = "blocked_patterns[0]: 4097 bytes exceeds the cap of 4096".to_string();
+        assert_eq!(diagnostics(&json), vec![expected]);
+
+        let huge = format!(r#"{{"blocked_patterns": ["{}"]}}"#, "x".repeat(MAX_CONFIG_BYTES));
+        let err = FilterConfig::from_bytes_validated(huge.as_bytes()).unwrap_err();
+        assert_eq!(err.as_str(), "too_large");
+    }
+
+    #[test]
+    fn test_validated_rejects_non_object() {
+        assert!(FilterConfig::from_bytes_validated(b"[1, 2]").is_err());
+        assert!(matches!(
+            FilterConfig::from_bytes_validated(b"{"),
+            Err(ConfigError::InvalidJson(_))
+        ));
     }
 }
@@ -0,0 +1,137 @@
+//! Admin endpoint
+//!
+//! Runtime control flags, pattern and status reports, agent resets and the
+//! health report, served as local responses.
+
+use super::*;
+
+impl AiGuardHttpContext {
+    /// Serve the admin API: reports are read-only, the control endpoint
+    /// reads (`GET`), replaces (`PUT`/`POST`) and resets agents (`DELETE`)
+    pub(super) fn handle_admin_request(
+        &mut self,
+        control: &ControlConfig,
+        route: AdminRoute,
+        end_of_stream: bool,
+    ) -> Action {
+        let authorization = self.get_http_request_header("authorization");
+        if !control.authorize(authorization.as_deref()) {
+            warn!("[context_id={}] Unauthorized control request", self.context_id);
+            let response = AdminResponse::Error(401, "Unauthorized".to_string());
+            self.send_admin_response(&response);
+            return Action::Pause;
+        }
+        let method = self.get_http_request_header(":method").unwrap_or_default();
+        let response = match (route, method.as_str()) {
+            (AdminRoute::NotFound, _) => AdminResponse::Error(404, "Not found".to_string()),
+            (AdminRoute::Status, "GET") => {
+                let flags = ControlFlags::load(&HostSharedStore);
+                let feed = THREAT_FEED.with(|cache| cache.borrow().feed());
+                AdminResponse::Report(status_report(&self.config, &flags, feed.as_deref()))
+            }
+            (AdminRoute::Patterns, "GET") => {
+                let hits = pattern_stats::load(&HostSharedStore);
+                let labels = self.config.blocked_patterns.labels();
+                AdminResponse::Report(pattern_report(&hits, &labels))
+            }
+            (AdminRoute::Control, method) => {
+                match self.handle_control_request(method, end_of_stream) {
+                    Some(response) => response,
+                    None => return Action::Pause,
+                }
+            }
+            (_, method) => AdminResponse::Error(405, format!("Method {} not allowed", method)),
+        };
+        self.send_admin_response(&response);
+        Action::Pause
+    }
+
+    /// Serve the runtime control endpoint; None while an update's body is awaited
+    fn handle_control_request(
+        &mut self,
+        method: &str,
+        end_of_stream: bool,
+    ) -> Option<AdminResponse> {
+        let response = match method {
+            "GET" => AdminResponse::Flags(ControlFlags::load(&HostSharedStore)),
+            "PUT" | "POST" if !end_of_stream => {
+                self.admin_update = true;
+                return None;
+            }
+            "PUT" | "POST" => AdminResponse::Error(400, "Missing control flags".to_string()),
+            "DELETE" => {
+                let path = self.get_http_request_header(":path").unwrap_or_default();
+                let response = reset_agent(&HostSharedStore, &path);
+                if let AdminResponse::AgentReset { agent, found: true } = &response {
+                    info!("AI-Guard: Penalty record of agent {} cleared", agent);
+                    self.audit(telemetry::audit_agent_reset(agent));
+                }
+                response
+            }
+            _ => AdminResponse::Error(405, format!("Method {} not allowed", method)),
+        };
+        Some(response)
+    }
+
+    /// Apply the body of an admin update once complete
+    pub(super) fn finish_admin_update(&mut self, body_size: usize, end_of_stream: bool) -> Action {
+        if !end_of_stream {
+            return Action::Pause;
+        }
+        let body = match body_size {
+            0 => None,
+            size if size > MAX_ADMIN_BODY => {
+                let response = AdminResponse::Error(413, "Control flags too large".to_string());
+                self.send_admin_response(&response);
+                return Action::Pause;
+            }
+            size => self.get_http_request_body(0, size),
+        };
+        let response = match body {
+            Some(body) => update_flags(&HostSharedStore, &body),
+            None => AdminResponse::Error(400, "Missing control flags".to_string()),
+        };
+        if let AdminResponse::Flags(flags) = &response {
+            let flags = serde_json::to_string(flags).unwrap_or_default();
+            info!("AI-Guard: Runtime controls set to {}", flags);
+            self.audit(telemetry::audit_control_changed(&flags));
+        }
+        self.send_admin_response(&response);
+        Action::Pause
+    }
+
+    /// Answer a health probe with the state of each dependency
+    pub(super) fn send_health_report(&mut self) {
+        let health = match &self.config.health {
+            Some(health) => health,
+            None => return,
+        };
+        let now_secs = self.now_ns() / 1_000_000_000;
+        let mut report = HealthReport::default();
+        report.add("patterns", check_patterns(&self.config));
+        if let Some(feed) = &self.config.threat_feed {
+            let updated_at = threat_intel::updated_at(&HostSharedStore);
+            report.add("threat_feed", check_threat_feed(health, feed, updated_at, now_secs));
+        }
+        if self.config.audit_sink.is_some() {
+            let sink = AUDIT_SHIPPER.with(|s| s.borrow().as_ref().map(check_audit_sink));
+            if let Some(check) = sink {
+                report.add("audit_sink", check);
+            }
+        }
+        report.add("shared_data", check_shared_data(&HostSharedStore, now_secs));
+        if !report.is_ready() {
+            warn!("AI-Guard: Health probe reports {}", report.status());
+        }
+        let (status, body) = report.to_http(self.config.digest());
+        let headers = vec![("content-type", "application/json"), (STATUS_HEADER, report.status())];
+        self.send_http_response(status, headers, Some(body.as_bytes()));
+    }
+
+    /// Reply to an admin request with JSON
+    fn send_admin_response(&mut self, response: &AdminResponse) {
+        let (status, body) = response.to_http();
+        let headers = vec![("content-type", "application/json")];
+        self.send_http_response(status, headers, Some(body.as_bytes()));
+    }
+}
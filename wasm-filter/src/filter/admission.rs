@@ -0,0 +1,621 @@
+//! Request admission
+//!
+//! Who is calling and whether they may: tenant, route, rollout and tier
+//! resolution, source networks, bypass tokens, rate and token limits,
+//! sessions, agent penalties and replay protection.
+
+use super::*;
+
+impl AiGuardHttpContext {
+    /// Resolve the request's tenant and switch to its configuration
+    pub(super) fn resolve_tenant(&mut self, path: Option<&str>) {
+        if !self.tenants.is_enabled() {
+            return;
+        }
+        let header_name = self.tenants.header().map(str::to_string);
+
+        let header_value = header_name.and_then(|h| self.get_http_request_header(&h));
+        let sni = self
+            .get_property(vec!["connection", "requested_server_name"])
+            .and_then(|b| String::from_utf8(b).ok())
+            .filter(|s| !s.is_empty());
+
+        let tenants = &self.tenants;
+        let resolved = tenants
+            .resolve(header_value.as_deref(), sni.as_deref(), path)
+            .map(|id| (id.to_string(), tenants.config(id).cloned()));
+        if let Some((id, config)) = resolved {
+            debug!("[context_id={}] Tenant: {}", self.context_id, id);
+            if let Some(config) = config {
+                self.scanner = StreamingBodyScanner::from_snapshot(&config);
+                self.token_counter = config.token_counter().clone();
+                self.config = config;
+            }
+            self.verdict.tenant = Some(id.clone());
+            self.tenant = Some(id);
+        }
+    }
+
+    /// Label the request from its path, before tenant and route resolution
+    pub(super) fn classify_request(&mut self, path: Option<&str>) {
+        let classification = self.config.classification.clone().unwrap_or_default();
+        self.class = classification.classify(path, self.is_mcp);
+        self.verdict.class = Some(self.class.as_str().to_string());
+    }
+
+    /// Refine the label from the complete body
+    pub(super) fn refine_class(&mut self) {
+        let refined = self.class.refine(self.jsonrpc.method());
+        if refined != self.class {
+            debug!("[context_id={}] Request class: {}", self.context_id, refined.as_str());
+            self.class = refined;
+            self.verdict.class = Some(refined.as_str().to_string());
+            self.publish_verdict();
+        }
+        self.label_request();
+    }
+
+    /// Send the label upstream, replacing any client-supplied value
+    pub(super) fn label_request(&self) {
+        let header = self.config.classification.as_ref().and_then(|c| c.header.as_deref());
+        if let Some(header) = header {
+            self.set_http_request_header(header, Some(self.class.as_str()));
+        }
+    }
+
+    /// Switch to the configuration of the route covering the request
+    pub(super) fn resolve_route(&mut self, path: Option<&str>) {
+        let path = match path {
+            Some(path) => path,
+            None => return,
+        };
+        let method = self.get_http_request_header(":method");
+        let routes = &self.routes;
+        let resolved = routes.resolve(path, method.as_deref(), self.class).map(|route| {
+            (route.to_string(), routes.config(self.tenant.as_deref(), route).cloned())
+        });
+        if let Some((route, config)) = resolved {
+            debug!("[context_id={}] Route: {}", self.context_id, route);
+            if let Some(config) = config {
+                self.scanner = StreamingBodyScanner::from_snapshot(&config);
+                self.token_counter = config.token_counter().clone();
+                self.config = config;
+            }
+            self.verdict.route = Some(route);
+        }
+    }
+
+    /// Switch off the subsystems whose rollout the request is outside of
+    pub(super) fn apply_rollout(&mut self) {
+        if self.config.rollout_percent.is_empty() {
+            return;
+        }
+        let key = self
+            .session_header_id()
+            .or_else(|| self.request_id().map(str::to_string))
+            .unwrap_or_default();
+        let decisions = rollout::decide(&self.config.rollout_percent, &key);
+        for (subsystem, included) in &decisions {
+            with_metrics(|m| m.rollout(subsystem, *included));
+        }
+        let excluded: Vec<&String> =
+            decisions.iter().filter(|(_, included)| !included).map(|(s, _)| s).collect();
+        if excluded.is_empty() {
+            return;
+        }
+        debug!("[context_id={}] Outside rollout: {:?}", self.context_id, excluded);
+        let config = Rc::make_mut(&mut self.config).config_mut();
+        for subsystem in excluded {
+            rollout::exclude(config, subsystem);
+        }
+    }
+
+    /// Apply the limits of the caller's identity tier
+    pub(super) fn resolve_tier(&mut self) {
+        let tiers = match &self.config.identity_tiers {
+            Some(tiers) => tiers.clone(),
+            None => return,
+        };
+        let claims = tiers.claims_header.as_deref().and_then(|h| self.get_http_request_header(h));
+        let api_key = tiers.api_key_header.as_deref().and_then(|h| self.get_http_request_header(h));
+        let tier = match tiers.resolve(claims.as_deref(), api_key.as_deref()) {
+            Some(tier) => tier,
+            None => return,
+        };
+        debug!("[context_id={}] Identity tier: {}", self.context_id, tier);
+        if let Some(policy) = tiers.policy(tier) {
+            policy.apply(Rc::make_mut(&mut self.config).config_mut());
+            self.tier = Some(policy.clone());
+        }
+        self.verdict.tier = Some(tier.to_string());
+    }
+
+    /// Strip, hash or redact sensitive request headers before they go upstream
+    pub(super) fn scrub_request_headers(&mut self) {
+        let config = self.config.clone();
+        let scrubbing = match &config.header_scrubbing {
+            Some(scrubbing) => scrubbing,
+            None => return,
+        };
+        let scrubbed = scrubbing.scrub(&self.get_http_request_headers(), &config.pii_redactor());
+        if scrubbed.is_empty() {
+            return;
+        }
+        for header in &scrubbed {
+            self.set_http_request_header(&header.name, header.value.as_deref());
+            with_metrics(|m| m.header_scrubbed(header.action.as_str()));
+        }
+        let listed: Vec<(String, &str)> =
+            scrubbed.iter().map(|h| (h.name.clone(), h.action.as_str())).collect();
+        debug!("[context_id={}] Scrubbed {} request headers", self.context_id, listed.len());
+        self.audit(telemetry::audit_headers_scrubbed(&listed));
+    }
+
+    /// Apply the source network policy; false if blocked
+    pub(super) fn check_network(&mut self) -> bool {
+        let policy = match &self.config.network_policy {
+            Some(policy) => policy.clone(),
+            None => return true,
+        };
+        let xff = self.get_http_request_header(XFF_HEADER);
+        let source = self
+            .get_property(vec!["source", "address"])
+            .and_then(|b| String::from_utf8(b).ok());
+        let client = policy.client_address(xff.as_deref(), source.as_deref());
+        if let Some(network) = client.and_then(|addr| policy.network(addr)) {
+            debug!("[context_id={}] Client network: {}", self.context_id, network.name);
+            network.apply(&mut self.control);
+        }
+        if policy.admits(client) {
+            return true;
+        }
+        let reason = match client {
+            Some(addr) => format!("Client address {} is not allowed", addr),
+            None => "Client address unknown".to_string(),
+        };
+        !self.block_request("network_policy", &reason, None)
+    }
+
+    /// Run a closure against this tenant's rate limiter (None if not configured)
+    pub(super) fn with_rate_limiter<R, F: FnOnce(&mut RateLimiter) -> R>(&self, f: F) -> Option<R> {
+        let limits = self.config.rate_limits.as_ref()?;
+        let mut key = self.tenant.clone().unwrap_or_default();
+        if let Some(route) = &self.verdict.route {
+            key = format!("{}#{}", key, route);
+        }
+        if let Some(tier) = &self.verdict.tier {
+            key = format!("{}@{}", key, tier);
+        }
+        Some(RATE_LIMITERS.with(|r| {
+            let mut limiters = r.borrow_mut();
+            let limiter = limiters
+                .entry(key)
+                .or_insert_with(|| RateLimiter::with_limits(limits.clone()));
+            f(limiter)
+        }))
+    }
+
+    /// Honor a signed bypass token; true when the request skips inspection
+    pub(super) fn check_bypass(&mut self) -> bool {
+        let config = self.config.clone();
+        let bypass = match &config.bypass {
+            Some(bypass) => bypass,
+            None => return false,
+        };
+        let token = match self.get_http_request_header(BYPASS_HEADER) {
+            Some(token) => token,
+            None => return false,
+        };
+        // Never forward the token upstream
+        self.set_http_request_header(BYPASS_HEADER, None);
+        let now_secs = self.now_ns() / 1_000_000_000;
+        let key_id = match bypass.verify(&token, now_secs) {
+            Ok(key_id) => key_id,
+            Err(rejection) => {
+                self.reject_bypass(rejection, None);
+                return false;
+            }
+        };
+        match admit_bypass(&HostSharedStore, key_id, bypass.max_per_minute, now_secs) {
+            Ok(true) => {}
+            Ok(false) => {
+                self.reject_bypass(BypassRejection::RateLimited, Some(key_id));
+                return false;
+            }
+            Err(e) => {
+                // Without the shared count the cap cannot be enforced: inspect
+                warn!("[context_id={}] Bypass not counted: {}", self.context_id, e);
+                return false;
+            }
+        }
+        info!("[context_id={}] Inspection bypassed with key {}", self.context_id, key_id);
+        with_metrics(|m| m.bypass("granted"));
+        self.bypassed = true;
+        self.verdict.action = VerdictAction::Bypassed;
+        self.publish_verdict();
+        self.audit(telemetry::audit_inspection_bypassed(key_id));
+        true
+    }
+
+    /// Record a bypass token that is not honored; the request is inspected as usual
+    fn reject_bypass(&mut self, rejection: BypassRejection, key_id: Option<&str>) {
+        warn!(
+            "[context_id={}] Bypass token rejected: {}",
+            self.context_id,
+            rejection.as_str()
+        );
+        with_metrics(|m| m.bypass(rejection.as_str()));
+        self.audit(telemetry::audit_bypass_rejected(rejection.as_str(), key_id));
+    }
+
+    /// Send a 429 Too Many Requests response
+    fn send_rate_limited_response(&mut self, reason: &str, retry_after_secs: u64) {
+        if self.request_blocked {
+            return;
+        }
+        self.request_blocked = true;
+
+        // MCP clients expect a JSON-RPC body: reply 200 with a rate_limited error
+        let (status, body_bytes) = if self.is_mcp || self.jsonrpc.is_jsonrpc() {
+            let id = self.jsonrpc.id().cloned().unwrap_or(serde_json::Value::Null);
+            let error = JsonRpcError::rate_limited(reason, retry_after_secs)
+                .with_request_id(self.request_id());
+            let response = JsonRpcResponse::error(id, error);
+            (200, serde_json::to_string(&response).unwrap_or_default())
+        } else {
+            let error_body = serde_json::json!({
+                "error": "Rate Limited by AI-Guard",
+                "reason": reason,
+                "status": 429,
+                "retry_after_secs": retry_after_secs,
+                "request_id": self.request_id(),
+            });
+            (429, error_body.to_string())
+        };
+        let retry_after = retry_after_secs.to_string();
+        let explanation = self.explanation_header();
+
+        warn!(
+            "[context_id={}] RATE LIMITED: {}",
+            self.context_id, reason
+        );
+
+        let mut headers = vec![
+            ("content-type", "application/json"),
+            ("retry-after", retry_after.as_str()),
+            ("x-ai-guard-action", "rate_limit"),
+        ];
+        if let Some(value) = &explanation {
+            headers.push((VERDICT_RESPONSE_HEADER, value.as_str()));
+        }
+        if let Some(id) = self.request_id() {
+            headers.push((GUARDRAIL_REQUEST_ID_HEADER, id));
+        }
+
+        self.send_http_response(status, headers, Some(body_bytes.as_bytes()));
+    }
+
+    /// Metrics, verdict, audit and response of a request over its rate limit
+    pub(super) fn reject_rate_limited(&mut self, info: &RateLimitInfo) {
+        with_metrics(|m| m.rate_limited());
+        self.verdict.action = VerdictAction::RateLimited;
+        self.publish_verdict();
+        self.audit(telemetry::audit_rate_limited(&info.reason));
+        self.send_rate_limited_response(&info.reason, info.retry_after_secs);
+    }
+
+    /// Refuse a prompt whose estimated tokens would overrun the token budget
+    /// before it is forwarded; false if rate limited
+    ///
+    /// The estimate is only checked: actual usage is recorded from the response.
+    pub(super) fn check_token_budget(&mut self) -> bool {
+        let estimate = self.token_estimator.estimate(self.request_model());
+        if self.request_blocked || estimate == 0 {
+            return true;
+        }
+        let agent = self.rate_limit_key();
+        let start_ns = self.now_ns();
+        let now_secs = start_ns / 1_000_000_000;
+        let decision =
+            match self.with_rate_limiter(|l| l.check_tokens(&agent, estimate, now_secs)) {
+                Some(decision) => decision,
+                None => return true,
+            };
+        let outcome = if decision.is_limited() { "rate_limit" } else { "allow" };
+        let attributes = vec![("ai_guard.estimated_tokens".to_string(), estimate.to_string())];
+        self.record_span(Stage::RateLimit, start_ns, outcome, attributes);
+        match decision {
+            RateDecision::RateLimited(info) if self.enforce("rate_limit") => {
+                self.reject_rate_limited(&info);
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// Session ID from the first configured session header carrying a valid one
+    fn session_header_id(&self) -> Option<String> {
+        self.config
+            .sessions
+            .as_ref()?
+            .headers
+            .iter()
+            .filter_map(|name| self.get_http_request_header(name))
+            .find(|id| session::valid_session_id(id))
+    }
+
+    /// Apply the session's escalation step and record the request; false if rejected
+    pub(super) fn check_session(&mut self) -> bool {
+        let sessions = match &self.config.sessions {
+            Some(sessions) => sessions.clone(),
+            None => return true,
+        };
+        let id = match self.session_header_id() {
+            Some(id) => id,
+            None => return true,
+        };
+        let now_secs = self.now_ns() / 1_000_000_000;
+        let state = session::lookup(&HostSharedStore, &sessions, &id, now_secs);
+        let action = sessions.action(&state, now_secs);
+        self.session_id = Some(id);
+        match action {
+            SessionAction::Block => {
+                let reason = format!("Session blocked after {} violations", state.violations);
+                if self.block_request("session", &reason, None) {
+                    return false;
+                }
+            }
+            SessionAction::RateLimit { retry_after_secs } if self.enforce("session") => {
+                let reason = format!("Session limited after {} violations", state.violations);
+                with_metrics(|m| m.rate_limited());
+                self.verdict.action = VerdictAction::RateLimited;
+                self.publish_verdict();
+                self.audit(telemetry::audit_rate_limited(&reason));
+                self.send_rate_limited_response(&reason, retry_after_secs);
+                return false;
+            }
+            SessionAction::Warn => self.session_warn = true,
+            SessionAction::RateLimit { .. } | SessionAction::Allow => {}
+        }
+        self.update_session(|s| s.last_request_secs = now_secs);
+        true
+    }
+
+    /// Audit (and reject) STDIO transport indicators, naming the calling
+    /// workload; false if rejected
+    pub(super) fn check_stdio_bypass(&mut self) -> bool {
+        let block = match &self.config.stdio_detection {
+            Some(detection) => detection.block,
+            None => return true,
+        };
+        let headers = self.get_http_request_headers();
+        let detector = StdioDetector::new();
+        let attempt = match detector.detect_from_headers(&headers) {
+            Some(attempt) => attempt,
+            None => return true,
+        };
+        // The peer is the caller at an inbound sidecar or gateway; without
+        // peer metadata this proxy is the caller's own outbound sidecar
+        let workload = WorkloadIdentity::from_peer_headers(&headers)
+            .or_else(|| WorkloadIdentity::from_node(|path| self.get_property(path.to_vec())));
+        let event = detector.create_audit_event(&attempt, workload);
+        let namespace = event.workload.as_ref().and_then(|w| w.namespace.as_deref());
+        with_metrics(|m| m.stdio_bypass(namespace.unwrap_or("unknown")));
+        warn!(
+            "[context_id={}] STDIO bypass attempt from {}: {}",
+            self.context_id,
+            event.workload.as_ref().and_then(|w| w.pod.as_deref()).unwrap_or("unknown workload"),
+            event.description
+        );
+        let mut audit = telemetry::audit_stdio_bypass(&event.description).with_metadata(
+            serde_json::json!({"bypass_type": event.bypass_type, "severity": event.severity}),
+        );
+        if let Some(workload) = &event.workload {
+            audit = audit.with_metadata(workload.to_metadata());
+        }
+        self.audit(audit);
+        !(block && self.block_request("stdio_bypass", &event.description, None))
+    }
+
+    /// Apply the agent's progressive penalty; false if rejected
+    pub(super) fn check_agent_penalty(&mut self) -> bool {
+        let (config, agent) = match (&self.config.agent_penalties, &self.verdict.agent_id) {
+            (Some(config), Some(agent)) if penalties::valid_agent_id(agent) => {
+                (config.clone(), agent.clone())
+            }
+            _ => return true,
+        };
+        let now_secs = self.now_ns() / 1_000_000_000;
+        let record = penalties::lookup(&HostSharedStore, &config, &agent, now_secs);
+        let penalty = config.penalty(&record, now_secs);
+        self.agent_penalty = Some((penalty, record.violations));
+        match penalty {
+            Penalty::Blocked { retry_after_secs } => {
+                let reason = format!(
+                    "Agent blocked after {} violations ({}s left)",
+                    record.violations, retry_after_secs
+                );
+                if self.block_request("agent_penalty", &reason, None) {
+                    return false;
+                }
+            }
+            Penalty::RateLimited { retry_after_secs } if retry_after_secs > 0 => {
+                if self.enforce("agent_penalty") {
+                    let reason =
+                        format!("Agent limited after {} violations", record.violations);
+                    with_metrics(|m| m.rate_limited());
+                    self.verdict.action = VerdictAction::RateLimited;
+                    self.publish_verdict();
+                    self.audit(telemetry::audit_rate_limited(&reason));
+                    self.send_rate_limited_response(&reason, retry_after_secs);
+                    return false;
+                }
+            }
+            Penalty::RateLimited { .. } => {
+                let store = &HostSharedStore;
+                let update = |r: &mut AgentRecord| r.last_request_secs = now_secs;
+                if let Err(e) = penalties::record(store, &config, &agent, now_secs, update) {
+                    debug!("[context_id={}] Agent record not updated: {}", self.context_id, e);
+                }
+            }
+            Penalty::Logged | Penalty::None => {}
+        }
+        true
+    }
+
+    /// Count a violation against the agent, auditing an escalated penalty
+    pub(super) fn record_agent_violation(&mut self) {
+        let (config, agent) = match (&self.config.agent_penalties, &self.verdict.agent_id) {
+            (Some(config), Some(agent)) if penalties::valid_agent_id(agent) => {
+                (config.clone(), agent.clone())
+            }
+            _ => return,
+        };
+        let now_secs = self.now_ns() / 1_000_000_000;
+        let record = match penalties::record_violation(&HostSharedStore, &config, &agent, now_secs)
+        {
+            Ok(record) => record,
+            Err(e) => {
+                debug!("[context_id={}] Agent violation not recorded: {}", self.context_id, e);
+                return;
+            }
+        };
+        let before = self.agent_penalty.map_or("none", |(p, _)| p.as_str());
+        let after = config.penalty(&record, now_secs);
+        self.agent_penalty = Some((after, record.violations));
+        let escalated = matches!(after, Penalty::RateLimited { .. } | Penalty::Blocked { .. });
+        if escalated && before != after.as_str() {
+            warn!(
+                "[context_id={}] Agent {} {} after {} violations",
+                self.context_id,
+                agent,
+                after.as_str(),
+                record.violations
+            );
+            with_metrics(|m| m.agent_penalized(after.as_str()));
+            self.audit(telemetry::audit_agent_penalized(&agent, after.as_str(), record.violations));
+        }
+    }
+
+    /// Reject a message or request id already seen in this session; false if rejected
+    pub(super) fn check_replay(&mut self) -> bool {
+        let (config, session) = match (&self.config.replay_protection, &self.session_id) {
+            (Some(config), Some(session)) => (config.clone(), session.clone()),
+            _ => return true,
+        };
+        let id = self.jsonrpc.id().filter(|id| !id.is_null());
+        let nonce = match (self.scanner.message_id(), id) {
+            (Some(message_id), _) => Nonce::MessageId(message_id.to_string()),
+            (None, Some(id)) if self.jsonrpc.is_jsonrpc() => Nonce::RequestId(id.to_string()),
+            _ => return true,
+        };
+        let now_secs = self.now_ns() / 1_000_000_000;
+        match replay::check_and_record(&HostSharedStore, &config, &session, &nonce, now_secs) {
+            Ok(true) => true,
+            Ok(false) => {
+                let reason = format!("Replayed {} {}", nonce.kind(), nonce.value());
+                !self.reject_replay(&reason)
+            }
+            Err(e) => {
+                debug!("[context_id={}] Replay check skipped: {}", self.context_id, e);
+                true
+            }
+        }
+    }
+
+    /// Block a replayed request with a dedicated error; false in monitor mode
+    fn reject_replay(&mut self, reason: &str) -> bool {
+        if !self.enforce("replay") {
+            return false;
+        }
+        with_metrics(|m| m.request_blocked("replay"));
+        self.verdict.action = VerdictAction::Blocked;
+        self.verdict.category = Some("replay".to_string());
+        self.publish_verdict();
+        self.audit(telemetry::audit_blocked(reason, None));
+        self.request_blocked = true;
+
+        // JSON-RPC clients (MCP, A2A) get an error response they can match to the call
+        if self.jsonrpc.is_jsonrpc() {
+            let id = self.jsonrpc.id().cloned().unwrap_or(serde_json::Value::Null);
+            let error = JsonRpcError::replay_detected(reason).with_request_id(self.request_id());
+            let response = JsonRpcResponse::error(id, error);
+            let body = serde_json::to_string(&response).unwrap_or_default();
+            self.send_local_response(200, Some(body.as_bytes()), reason);
+            return true;
+        }
+        let body = serde_json::json!({
+            "error": "Replay Detected by AI-Guard",
+            "reason": reason,
+            "status": 409,
+            "request_id": self.request_id(),
+        });
+        self.send_local_response(409, Some(body.to_string().as_bytes()), reason);
+        true
+    }
+
+    /// Update the state of this request's session
+    pub(super) fn update_session<F: FnMut(&mut session::SessionState)>(&self, update: F) {
+        let (sessions, id) = match (&self.config.sessions, &self.session_id) {
+            (Some(sessions), Some(id)) => (sessions, id),
+            _ => return,
+        };
+        let now_secs = self.now_ns() / 1_000_000_000;
+        if let Err(e) = session::record(&HostSharedStore, sessions, id, now_secs, update) {
+            debug!("[context_id={}] Session not updated: {}", self.context_id, e);
+        }
+    }
+
+    /// Enforce prompt size, message count and `max_tokens` limits; false if blocked
+    pub(super) fn check_request_limits(&mut self) -> bool {
+        if let (Some(limit), Some(requested)) =
+            (self.config.max_tokens_limit, self.scanner.max_tokens())
+        {
+            if requested > limit && self.config.clamp_max_tokens {
+                self.clamp_max_tokens = true;
+            } else if requested > limit {
+                let reason = format!("max_tokens {} exceeds limit of {}", requested, limit);
+                return !self.block_request("token_limit", &reason, None);
+            }
+        }
+        if let Some(limit) = self.config.max_messages {
+            let count = self.scanner.message_count();
+            if count > limit {
+                let reason = format!("{} messages exceeds limit of {}", count, limit);
+                return !self.block_request("message_limit", &reason, None);
+            }
+        }
+        if let Some(limit) = self.config.max_prompt_tokens {
+            let estimate = self.token_estimator.estimate(self.request_model());
+            if estimate > limit {
+                let reason =
+                    format!("Estimated prompt of {} tokens exceeds limit of {}", estimate, limit);
+                return !self.block_request("prompt_limit", &reason, None);
+            }
+        }
+        true
+    }
+
+    /// Apply the model policy once the body's model is known; false if blocked
+    pub(super) fn check_model_policy(&mut self) -> bool {
+        if self.model_checked {
+            return true;
+        }
+        let (policy, model) = match (&self.config.model_policy, self.scanner.model()) {
+            (Some(policy), Some(model)) => (policy, model),
+            _ => return true,
+        };
+        self.model_checked = true;
+        match policy.decide(model) {
+            ModelDecision::Allow => true,
+            ModelDecision::Override(replacement) => {
+                self.model_override = Some(replacement);
+                true
+            }
+            ModelDecision::Block => {
+                let reason = format!("Model '{}' is not allowed", model);
+                !self.block_request("model_policy", &reason, None)
+            }
+        }
+    }
+}
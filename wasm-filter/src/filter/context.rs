@@ -0,0 +1,601 @@
+//! HTTP context lifecycle
+//!
+//! Creation, the proxy-wasm callbacks (each dispatching to its phase
+//! handler), callout responses, and what every phase shares: spans, audit
+//! events, the published verdict and local replies.
+
+use super::*;
+
+impl AiGuardHttpContext {
+    pub(super) fn new(context_id: u32) -> Self {
+        // The context keeps these handles for its whole life: a reload
+        // replaces the thread-locals, never what a stream already holds
+        let config = CONFIG.with(|c| c.borrow().clone());
+        let scanner = StreamingBodyScanner::from_snapshot(&config);
+        let token_counter = config.token_counter().clone();
+        let generation = CONFIG_GENERATION.with(Cell::get);
+        let mut jsonrpc = JsonRpcSniffer::new();
+        if config.reads_call_params() {
+            jsonrpc.capture_params();
+        }
+        LIVE_CONTEXTS.with(|l| *l.borrow_mut().entry(generation).or_insert(0) += 1);
+
+        Self {
+            context_id,
+            scanner,
+            token_counter,
+            token_estimator: TokenEstimator::new(),
+            header_usage: None,
+            path_model: None,
+            model_checked: false,
+            model_override: None,
+            clamp_max_tokens: false,
+            repetition_cap: None,
+            hold_response_headers: false,
+            response_body_size: 0,
+            session_id: None,
+            session_warn: false,
+            severity_tag: None,
+            entropy_tag: false,
+            session_ban: false,
+            agent_penalty: None,
+            verdict_cache_key: None,
+            experiment: None,
+            dry_run: None,
+            control: ControlFlags::default(),
+            bypassed: false,
+            verdict_forwarded: false,
+            monitored: Vec::new(),
+            admin_update: false,
+            pending_approval: None,
+            pending_decision: None,
+            request_blocked: false,
+            verdict: Verdict::new(VerdictAction::Allowed),
+            tenant: None,
+            tier: None,
+            class: RequestClass::Other,
+            audit_stamp: None,
+            debug: false,
+            explanation: Explanation::default(),
+            spans: None,
+            scan_start_ns: None,
+            scan_busy_us: 0,
+            overhead_us: 0,
+            config,
+            tenants: TENANTS.with(|t| t.borrow().clone()),
+            routes: ROUTES.with(|r| r.borrow().clone()),
+            generation,
+            is_mcp: false,
+            transport: None,
+            jsonrpc,
+            request_decoder: None,
+            request_charset: None,
+            response_encoding: ContentEncoding::Identity,
+            response_decoder: None,
+            multipart: None,
+            binary: None,
+            text_values: None,
+            language_scan: None,
+            entropy: None,
+            repetition: None,
+            markup: None,
+            urls: None,
+            threat_scan: None,
+            response_scanner: None,
+            response_truncated: false,
+            watchdog: None,
+            stream_terminated: false,
+            notifications: None,
+            mcp_events: None,
+            mcp_result_body: false,
+            result_limit_events: None,
+            result_limit_body: false,
+            prompt_events: None,
+            prompt_result_body: false,
+            pii_events: None,
+            pii_body: false,
+            response_pii_counts: BTreeMap::new(),
+            vault: None,
+            is_text_content: true,
+            body_bytes_processed: 0,
+            request_body_size: 0,
+        }
+    }
+
+    /// Attach opt-in usage and cost headers to the response
+    pub(super) fn set_usage_headers(&self, usage: &TokenUsage) {
+        if !self.config.usage_response_headers {
+            return;
+        }
+        self.set_http_response_header(
+            HEADER_TOKENS_PROMPT,
+            Some(&usage.prompt_tokens.to_string()),
+        );
+        self.set_http_response_header(
+            HEADER_TOKENS_COMPLETION,
+            Some(&usage.completion_tokens.to_string()),
+        );
+        if let Some(cost) = usage.estimated_cost_usd {
+            self.set_http_response_header(HEADER_COST_USD, Some(&format!("{:.6}", cost)));
+        }
+    }
+
+    /// Current host time in Unix nanoseconds
+    pub(super) fn now_ns(&self) -> u64 {
+        self.get_current_time()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0)
+    }
+
+    /// Run a filter hook, measuring the time spent in it
+    fn timed(&mut self, phase: Phase, hook: impl FnOnce(&mut Self) -> Action) -> Action {
+        let start = self.get_current_time();
+        let action = hook(self);
+        let micros = self
+            .get_current_time()
+            .duration_since(start)
+            .map(|d| d.as_micros() as u64)
+            .unwrap_or(0);
+        self.overhead_us += micros;
+        with_metrics(|m| m.phase_latency_us(phase.as_str(), micros));
+        action
+    }
+
+    /// Check the worker's p99 overhead against the budget once a request is done
+    fn check_overhead_budget(&self) {
+        with_metrics(|m| m.request_overhead_us(self.overhead_us));
+        let budget = match &self.config.overhead_budget {
+            Some(budget) => budget,
+            None => return,
+        };
+        let exceeded = OVERHEAD.with(|o| o.borrow_mut().observe(budget, self.overhead_us));
+        if let Some(p99) = exceeded {
+            warn!(
+                "AI-Guard: p99 overhead {}us exceeds budget of {}us",
+                p99, budget.budget_us
+            );
+            with_metrics(|m| m.overhead_budget_exceeded());
+            self.audit(telemetry::audit_overhead_budget(p99, budget.budget_us));
+        }
+    }
+
+    /// Record the span of a guardrail stage that started at `start_ns` and ends now
+    pub(super) fn record_span(
+        &mut self,
+        stage: Stage,
+        start_ns: u64,
+        outcome: &str,
+        mut attributes: Vec<(String, String)>,
+    ) {
+        if self.spans.is_none() {
+            return;
+        }
+        let end_ns = self.now_ns();
+        attributes.insert(0, ("ai_guard.outcome".to_string(), outcome.to_string()));
+        if let Some(spans) = self.spans.as_mut() {
+            spans.record(stage, start_ns, end_ns, attributes);
+        }
+    }
+
+    /// Close the body scan span once the scanner reaches a final decision
+    pub(super) fn finish_scan_span(&mut self, outcome: &str) {
+        let start_ns = match self.scan_start_ns.take() {
+            Some(start_ns) => start_ns,
+            None => return,
+        };
+        let attributes = vec![
+            ("ai_guard.bytes".to_string(), self.scanner.total_bytes().to_string()),
+            ("ai_guard.busy_us".to_string(), self.scan_busy_us.to_string()),
+        ];
+        self.record_span(Stage::BodyScan, start_ns, outcome, attributes);
+    }
+
+    /// Queue recorded spans for the root to export, or put them into filter state
+    fn export_spans(&self) {
+        let (spans, tracing) = match (&self.spans, &self.config.tracing) {
+            (Some(spans), Some(tracing)) if !spans.is_empty() => (spans, tracing),
+            _ => return,
+        };
+        if tracing.collector_cluster.is_none() {
+            self.set_property(vec!["ai_guard.spans"], Some(spans.summary().as_bytes()));
+            return;
+        }
+        let dropped = SPAN_QUEUE.with(|q| q.borrow_mut().push(spans));
+        if dropped > 0 {
+            with_metrics(|m| m.spans_dropped(dropped));
+        }
+    }
+
+    /// Request ID of audit records (read or generated with the request headers)
+    pub(super) fn request_id(&self) -> Option<&str> {
+        self.audit_stamp.as_ref().map(|s| s.request_id.as_str())
+    }
+
+    /// Stamp, log and ship an audit event
+    ///
+    /// All audit emit paths go through here so every event carries a
+    /// timestamp, request ID and agent ID.
+    pub(super) fn audit(&self, event: AuditEvent) {
+        let now_ns = self.now_ns();
+        let event = match self.agent_penalty {
+            Some((penalty, violations)) if penalty != Penalty::None => {
+                event.with_metadata(serde_json::json!({
+                    "agent_penalty": penalty.as_str(),
+                    "agent_violations": violations,
+                }))
+            }
+            _ => event,
+        };
+        let event = match &self.audit_stamp {
+            Some(stamp) => stamp.apply(event, now_ns / 1_000_000_000),
+            None => AuditStamp::new(None, now_ns, self.context_id)
+                .apply(event, now_ns / 1_000_000_000),
+        };
+        let event =
+            event.with_versions(self.config.config_version(), self.config.pattern_version());
+        publish_audit(event, &self.config, now_ns / 1_000_000);
+    }
+
+    /// Debug explanation header value (None unless debug was requested)
+    pub(super) fn explanation_header(&self) -> Option<String> {
+        if !self.debug {
+            return None;
+        }
+        let mut explanation = self.explanation.clone();
+        explanation.bytes_scanned = self.scanner.total_bytes();
+        explanation.tokens_estimated = self.token_estimator.estimate(self.request_model());
+        Some(explanation.to_header_value(self.verdict.action.as_str()))
+    }
+
+    /// Forward the signed verdict upstream, once, as the request is released
+    ///
+    /// Nothing is vouched for a body no check looked at.
+    fn forward_verdict(&mut self) {
+        let uninspected = !self.is_text_content && self.binary.is_none();
+        let propagation = match &self.config.verdict_propagation {
+            Some(propagation)
+                if !self.verdict_forwarded && !self.control.disabled && !uninspected =>
+            {
+                propagation
+            }
+            _ => return,
+        };
+        let verdict = match serde_json::to_string(&self.verdict) {
+            Ok(json) => json,
+            Err(_) => return,
+        };
+        let now_secs = self.now_ns() / 1_000_000_000;
+        let header = propagation.sign(&verdict, self.request_id(), now_secs);
+        self.set_http_request_header(FORWARDED_VERDICT_HEADER, Some(&header));
+        self.verdict_forwarded = true;
+    }
+
+    /// Let a held request continue upstream
+    pub(super) fn release_request(&mut self) {
+        self.forward_verdict();
+        self.resume_http_request();
+    }
+
+    /// Write the current verdict into filter state for access logs and later filters
+    pub(super) fn publish_verdict(&self) {
+        for (name, value) in self.verdict.properties() {
+            self.set_property(vec![name.as_str()], Some(value.as_bytes()));
+        }
+    }
+
+    /// Model named in the request body, else derived from the path
+    pub(super) fn request_model(&self) -> Option<&str> {
+        self.model_override
+            .as_deref()
+            .or(self.scanner.model())
+            .or(self.path_model.as_deref())
+    }
+
+    /// Whether the request and its response pass uninspected (kill switch or bypass token)
+    pub(super) fn inspection_skipped(&self) -> bool {
+        self.control.disabled || self.bypassed
+    }
+
+    /// Rate-limit key for this request
+    pub(super) fn rate_limit_key(&self) -> String {
+        self.verdict
+            .agent_id
+            .clone()
+            .unwrap_or_else(|| ANONYMOUS_AGENT.to_string())
+    }
+
+    /// Transport of the request, from its headers
+    pub(super) fn request_transport(&self) -> &'static str {
+        let header = |name| self.get_http_request_header(name).unwrap_or_default().to_lowercase();
+        if header("upgrade") == "websocket" {
+            "websocket"
+        } else if header("content-type").starts_with("application/grpc") {
+            "grpc"
+        } else if header("accept").contains("text/event-stream") {
+            "sse"
+        } else {
+            "http"
+        }
+    }
+
+    /// Protocol of the request, for dimensioned metrics
+    fn request_protocol(&self) -> &'static str {
+        let a2a_method = self.jsonrpc.method().and_then(A2AOperation::from_rpc_method);
+        let path = self.get_http_request_header(":path").unwrap_or_default();
+        if a2a_method.is_some() || RestRoute::is_rest_path(&path) {
+            "a2a"
+        } else if self.is_mcp || self.jsonrpc.is_jsonrpc() {
+            "mcp"
+        } else {
+            "http"
+        }
+    }
+
+    /// Block outside the body scanner: metrics, verdict, audit and response
+    ///
+    /// Returns false when runtime controls only let the violation be recorded.
+    pub(super) fn block_request(
+        &mut self,
+        category: &str,
+        reason: &str,
+        pattern: Option<String>,
+    ) -> bool {
+        if !self.enforce(category) {
+            return false;
+        }
+        with_metrics(|m| m.request_blocked(category));
+        self.verdict.action = VerdictAction::Blocked;
+        self.verdict.category = Some(category.to_string());
+        self.verdict.matched_pattern = pattern;
+        self.publish_verdict();
+        self.audit(telemetry::audit_blocked(
+            reason,
+            self.verdict.matched_pattern.as_deref(),
+        ));
+        self.send_block_response(reason);
+        true
+    }
+
+    /// Whether a violation is enforced; otherwise it is recorded (once) as monitored
+    pub(super) fn enforce(&mut self, category: &str) -> bool {
+        if self.control.enforces(category) {
+            return true;
+        }
+        if self.monitored.iter().any(|c| c == category) {
+            return false;
+        }
+        self.monitored.push(category.to_string());
+        warn!(
+            "[context_id={}] MONITORED: '{}' violation forwarded",
+            self.context_id, category
+        );
+        with_metrics(|m| m.violation_monitored(category));
+        if self.verdict.action == VerdictAction::Allowed {
+            self.verdict.action = VerdictAction::Monitored;
+            self.verdict.category = Some(category.to_string());
+            self.publish_verdict();
+        }
+        self.audit(telemetry::audit_violation_monitored(category));
+        false
+    }
+
+    /// Send a 403 Forbidden response with JSON error body
+    pub(super) fn send_block_response(&mut self, reason: &str) {
+        if self.request_blocked {
+            return; // Already blocked, don't send duplicate response
+        }
+
+        self.request_blocked = true;
+
+        // The audit event has the full reason; the client gets what the config discloses
+        let category = self.verdict.category.as_deref();
+        let verbosity = self.config.block_response_verbosity;
+
+        // MCP clients expect a JSON-RPC body: reply 200 with a policy_violation error
+        if self.is_mcp || self.jsonrpc.is_jsonrpc() {
+            let id = self.jsonrpc.id().cloned().unwrap_or(serde_json::Value::Null);
+            let error = JsonRpcError::policy_violation(verbosity.reason(category, reason))
+                .with_request_id(self.request_id());
+            let response = JsonRpcResponse::error(id, error);
+            let body = serde_json::to_string(&response).unwrap_or_default();
+            self.send_local_response(200, Some(body.as_bytes()), reason);
+            return;
+        }
+
+        let error_body = verbosity.block_body(category, reason, self.request_id());
+        self.send_local_response(403, Some(error_body.to_string().as_bytes()), reason);
+    }
+
+    /// Send a JSON block response with the AI-Guard headers
+    pub(super) fn send_local_response(&self, status: u32, body: Option<&[u8]>, reason: &str) {
+        let explanation = self.explanation_header();
+
+        warn!(
+            "[context_id={}] BLOCKED: {}",
+            self.context_id, reason
+        );
+
+        let mut headers = vec![
+            ("content-type", "application/json"),
+            ("x-ai-guard-blocked", "true"),
+            ("x-ai-guard-action", "block"),
+        ];
+        if let Some(value) = &explanation {
+            headers.push((VERDICT_RESPONSE_HEADER, value.as_str()));
+        }
+        if let Some(id) = self.request_id() {
+            headers.push((GUARDRAIL_REQUEST_ID_HEADER, id));
+        }
+
+        self.send_http_response(status, headers, body);
+    }
+}
+
+impl Context for AiGuardHttpContext {
+    fn on_http_call_response(
+        &mut self,
+        token_id: u32,
+        _num_headers: usize,
+        body_size: usize,
+        _num_trailers: usize,
+    ) {
+        let status = self
+            .get_http_call_response_header(":status")
+            .and_then(|s| s.parse::<u16>().ok());
+        let body = self.get_http_call_response_body(0, body_size);
+        if let (Some(token), Some(pdp)) = (self.pending_decision, &self.config.pdp) {
+            if token == token_id {
+                self.pending_decision = None;
+                let decision = pdp.decide(status, body.as_deref());
+                self.finish_pdp(decision);
+                return;
+            }
+        }
+        let tool = match self.pending_approval.take() {
+            Some((token, tool)) if token == token_id => tool,
+            other => {
+                self.pending_approval = other;
+                return;
+            }
+        };
+        let approval = match &self.config.tool_approval {
+            Some(approval) => approval,
+            None => return,
+        };
+        let decision = approval.decide(status, body.as_deref());
+        let approved = matches!(decision, ApprovalDecision::Approved(_));
+        with_metrics(|m| m.tool_approval(approved));
+        match decision {
+            ApprovalDecision::Approved(detail) => {
+                info!("[context_id={}] Tool '{}' {}", self.context_id, tool, detail);
+                self.audit(telemetry::audit_tool_approval(&tool, true, &detail));
+                self.release_request();
+            }
+            ApprovalDecision::Rejected(detail) => {
+                self.audit(telemetry::audit_tool_approval(&tool, false, &detail));
+                let reason = format!("Tool call '{}' rejected: {}", tool, detail);
+                if !self.block_request("tool_approval", &reason, None) {
+                    self.release_request();
+                }
+            }
+        }
+    }
+}
+
+impl Drop for AiGuardHttpContext {
+    fn drop(&mut self) {
+        // Contexts still open when the thread exits outlive its other locals
+        let counted = LIVE_CONTEXTS.try_with(|l| {
+            let mut live = l.borrow_mut();
+            if let Some(count) = live.get_mut(&self.generation) {
+                *count -= 1;
+                if *count == 0 {
+                    live.remove(&self.generation);
+                }
+            }
+        });
+        if counted.is_ok() && self.generation < CONFIG_GENERATION.with(Cell::get) {
+            record_stale_contexts();
+        }
+    }
+}
+
+impl HttpContext for AiGuardHttpContext {
+    fn on_http_request_headers(&mut self, num_headers: usize, end_of_stream: bool) -> Action {
+        self.timed(Phase::RequestHeaders, |s| {
+            let action = s.handle_request_headers(num_headers, end_of_stream);
+            if action == Action::Continue {
+                s.forward_verdict();
+            }
+            action
+        })
+    }
+
+    fn on_http_request_body(&mut self, body_size: usize, end_of_stream: bool) -> Action {
+        self.timed(Phase::RequestBody, |s| {
+            let action = s.handle_request_body(body_size, end_of_stream);
+            if action == Action::Continue && end_of_stream {
+                s.forward_verdict();
+            }
+            action
+        })
+    }
+
+    fn on_http_request_trailers(&mut self, num_trailers: usize) -> Action {
+        self.timed(Phase::RequestBody, |s| {
+            let action = s.handle_request_trailers(num_trailers);
+            if action == Action::Continue {
+                s.forward_verdict();
+            }
+            action
+        })
+    }
+
+    fn on_http_response_headers(&mut self, num_headers: usize, end_of_stream: bool) -> Action {
+        self.timed(Phase::ResponseHeaders, |s| {
+            s.handle_response_headers(num_headers, end_of_stream)
+        })
+    }
+
+    fn on_http_response_body(&mut self, body_size: usize, end_of_stream: bool) -> Action {
+        self.timed(Phase::ResponseBody, |s| s.handle_response_body(body_size, end_of_stream))
+    }
+
+    fn on_http_response_trailers(&mut self, num_trailers: usize) -> Action {
+        self.timed(Phase::ResponseBody, |s| s.handle_response_trailers(num_trailers))
+    }
+
+    fn on_log(&mut self) {
+        self.export_spans();
+        self.check_overhead_budget();
+        self.finish_experiment();
+        self.finish_dry_run();
+
+        // Session- and penalty-triggered rejections are not new violations
+        let tokens = self.verdict.prompt_tokens.unwrap_or(0) as u64
+            + self.verdict.completion_tokens.unwrap_or(0) as u64;
+        let violation = self.verdict.action == VerdictAction::Blocked
+            && !matches!(self.verdict.category.as_deref(), Some("session" | "agent_penalty"));
+        if violation {
+            self.record_agent_violation();
+        }
+        let ban = match &self.config.sessions {
+            Some(sessions) if self.session_ban => sessions.block_after,
+            _ => 0,
+        };
+        if tokens > 0 || violation || ban > 0 {
+            self.update_session(|s| {
+                s.tokens += tokens;
+                s.violations = (s.violations + violation as u32).max(ban);
+            });
+        }
+
+        if let (Some(config), Some(transport)) = (&self.config.metric_dimensions, self.transport) {
+            let dimensions = RequestDimensions {
+                tenant: self.tenant.as_deref(),
+                protocol: self.request_protocol(),
+                transport,
+                action: self.verdict.action.as_str(),
+                category: self.verdict.category.as_deref(),
+                config_version: Some(self.config.config_version()),
+                pattern_version: Some(self.config.pattern_version()),
+            };
+            with_metrics(|m| m.request_completed(config, &dimensions));
+        }
+
+        // Log completion of request processing
+        if self.request_blocked {
+            info!(
+                "[context_id={}] Request BLOCKED by AI-Guard",
+                self.context_id
+            );
+        } else {
+            debug!(
+                "[context_id={}] Request processing complete ({} bytes scanned)",
+                self.context_id,
+                self.scanner.total_bytes()
+            );
+        }
+    }
+}
@@ -0,0 +1,949 @@
+//! Request body inspection
+//!
+//! The checks run over a complete or streamed request body: the verdict
+//! cache, protocol validation, tool and prompt arguments, URLs, markup,
+//! entropy, language packs, policy rules, the policy decision point, tool
+//! approval, experiments and dry runs, and body rewrites.
+
+use super::*;
+
+impl AiGuardHttpContext {
+    /// Put the request in an arm of the pattern experiment
+    ///
+    /// The candidate arm swaps in the candidate bundle's scanner; the other
+    /// bundle's scanner follows along in shadow.
+    pub(super) fn assign_experiment_arm(&mut self) {
+        let (experiment, candidate) =
+            match (&self.config.pattern_experiment, self.config.candidate()) {
+                (Some(experiment), Some(candidate)) => (experiment, candidate.clone()),
+                _ => return,
+            };
+        let arm = experiment.assign(self.request_id().unwrap_or_default());
+        let candidate = StreamingBodyScanner::from_snapshot(&candidate);
+        let shadow = match arm {
+            Arm::Control => candidate,
+            Arm::Candidate => std::mem::replace(&mut self.scanner, candidate),
+        };
+        debug!("[context_id={}] Pattern experiment arm: {}", self.context_id, arm.as_str());
+        with_metrics(|m| m.experiment_request(arm.as_str()));
+        self.experiment = Some(ExperimentRun::new(arm, shadow));
+        self.verdict.experiment_arm = Some(arm.as_str().to_string());
+        self.publish_verdict();
+    }
+
+    /// Evaluate a sampled request against the dry-run candidate configuration
+    pub(super) fn start_dry_run(&mut self) {
+        let (dry_run, candidate) = match (&self.config.dry_run, self.config.dry_run()) {
+            (Some(dry_run), Some(candidate)) => (dry_run, candidate.clone()),
+            _ => return,
+        };
+        if !dry_run.samples(self.request_id().unwrap_or_default()) {
+            return;
+        }
+        let scanner = StreamingBodyScanner::from_snapshot(&candidate);
+        self.dry_run = Some(DryRun::new(scanner, dry_run.max_body_size));
+        with_metrics(|m| m.dry_run_request());
+    }
+
+    /// Audit the dry run's verdict if it differs from the active one
+    pub(super) fn finish_dry_run(&mut self) {
+        let run = match self.dry_run.take() {
+            Some(run) => run,
+            None => return,
+        };
+        if run.is_abandoned() {
+            with_metrics(|m| m.dry_run_skipped());
+            return;
+        }
+        let diff = match run.diff(self.scanner.matched_pattern()) {
+            Some(diff) => diff,
+            None => return,
+        };
+        with_metrics(|m| m.config_diff(diff.kind.as_str()));
+        self.audit(telemetry::audit_config_diff(
+            diff.kind.as_str(),
+            diff.active.as_deref(),
+            diff.candidate.as_deref(),
+        ));
+    }
+
+    /// Record how the experiment's bundles compared on the request
+    pub(super) fn finish_experiment(&mut self) {
+        let outcome = match self.experiment.take() {
+            Some(run) => run.outcome(self.scanner.matched_pattern()),
+            None => return,
+        };
+        let matched = [("control", &outcome.control), ("candidate", &outcome.candidate)];
+        for (bundle, pattern) in matched {
+            if pattern.is_some() {
+                with_metrics(|m| m.experiment_match(bundle));
+            }
+        }
+        let delta = match outcome.delta() {
+            Some(delta) => delta,
+            None => return,
+        };
+        with_metrics(|m| m.experiment_delta(delta.as_str()));
+        let name = self.config.pattern_experiment.as_ref().map_or("", |e| e.name.as_str());
+        let event = telemetry::audit_experiment_delta(
+            name,
+            outcome.arm.as_str(),
+            outcome.control.as_deref(),
+            outcome.candidate.as_deref(),
+        );
+        self.audit(event);
+    }
+
+    /// Scan outcome cached for this body; remembers the key on a miss
+    pub(super) fn lookup_verdict(&mut self, body: &[u8]) -> Option<ScanSummary> {
+        // The cache holds outcomes of the current configuration only
+        if self.generation != CONFIG_GENERATION.with(Cell::get) {
+            return None;
+        }
+        let tenant = self.tenant.as_deref().unwrap_or_default();
+        let route = self.verdict.route.as_deref().unwrap_or_default();
+        let header = |name| self.get_http_request_header(name).unwrap_or_default();
+        let (method, path) = (header(":method"), header(":path"));
+        // The content headers pick the charset and decoder the body goes through
+        let (content_type, encoding) = (header("content-type"), header("content-encoding"));
+        // The arms scan with different bundles
+        let arm = self.experiment.as_ref().map_or("", |e| e.arm().as_str());
+        let mode = self.scanner.mode();
+        let scope = [tenant, route, &method, &path, &content_type, &encoding, arm, mode];
+        let key = cache_key(&scope, body);
+        let now_secs = self.now_ns() / 1_000_000_000;
+        let cached = VERDICT_CACHE.with(|c| c.borrow_mut().get(&key, now_secs));
+        match cached {
+            Some(summary) => {
+                with_metrics(|m| m.verdict_cache_hit());
+                debug!("[context_id={}] Verdict cache hit", self.context_id);
+                Some(summary)
+            }
+            None => {
+                with_metrics(|m| m.verdict_cache_miss());
+                self.verdict_cache_key = Some(key);
+                None
+            }
+        }
+    }
+
+    /// Cache the outcome of a finished scan
+    pub(super) fn store_verdict(&mut self, key: CacheKey, decision: &ScanDecision) {
+        let summary = match self.scanner.summary(decision) {
+            Some(summary) => summary,
+            None => return,
+        };
+        let now_secs = self.now_ns() / 1_000_000_000;
+        let config = &self.config.verdict_cache;
+        VERDICT_CACHE.with(|c| c.borrow_mut().insert(config, key, summary, now_secs));
+    }
+
+    /// Apply the binary policy to the next chunk of a non-text body; false if blocked
+    pub(super) fn check_binary_body(&mut self, body_size: usize, end_of_stream: bool) -> bool {
+        let mut inspector = match self.binary.take() {
+            Some(inspector) => inspector,
+            None => return true,
+        };
+        // Non-text bodies stream through: the buffer holds just this chunk
+        let head = match inspector.wants().min(body_size) {
+            0 => Vec::new(),
+            wanted => self.get_http_request_body(0, wanted).unwrap_or_default(),
+        };
+        let classified = inspector.kind().is_some();
+        let result = inspector.chunk(&head, body_size, end_of_stream);
+        if let (false, Some(kind)) = (classified, inspector.kind()) {
+            with_metrics(|m| m.binary_body(kind.as_str()));
+        }
+        self.binary = Some(inspector);
+        match result {
+            Ok(()) => true,
+            Err(violation) => !self.block_request("binary_policy", &violation.to_string(), None),
+        }
+    }
+
+    /// Scan request trailer values for blocked patterns; false if blocked
+    pub(super) fn check_request_trailers(&mut self) -> bool {
+        let trailers = self.get_http_request_trailers();
+        let mut scanner = PatternScanner::from_strings(&self.config.blocked_patterns);
+        for (name, value) in &trailers {
+            // Patterns must not match across trailer boundaries
+            scanner.reset();
+            if let ScanResult::Match(m) = scanner.scan_bytes(value.as_bytes()) {
+                let category = InjectionCategory::classify(&m.pattern_name).as_str();
+                let reason = format!("Blocked pattern in trailer {}: {}", name, m.pattern_name);
+                if self.block_request(category, &reason, Some(m.pattern_name)) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// Forward a body match with quarantine routing instead of blocking it
+    pub(super) fn quarantine_request(
+        &mut self,
+        category: InjectionCategory,
+        severity: Option<InjectionSeverity>,
+        reason: &str,
+    ) {
+        let (header, value) = match &self.config.quarantine {
+            Some(q) => (q.header.clone(), q.value.clone()),
+            None => return,
+        };
+        // Headers are still held: route rules see the header
+        self.set_http_request_header(&header, Some(&value));
+        warn!("[context_id={}] QUARANTINED: {}", self.context_id, reason);
+        with_metrics(|m| m.request_quarantined(category.as_str()));
+        self.verdict.action = VerdictAction::Quarantined;
+        self.verdict.category = Some(category.as_str().to_string());
+        self.verdict.severity = severity.map(|s| s.as_str().to_string());
+        self.verdict.matched_pattern = self.matched_label();
+        self.publish_verdict();
+        self.audit(telemetry::audit_quarantined(
+            reason,
+            self.verdict.matched_pattern.as_deref(),
+        ));
+    }
+
+    /// How the body scanner's match is reported: the pattern's ID, or the pattern
+    pub(super) fn matched_label(&self) -> Option<String> {
+        let pattern = self.scanner.matched_pattern()?;
+        Some(self.config.blocked_patterns.label(pattern).to_string())
+    }
+
+    /// Description of the body scanner's matched pattern, if configured
+    pub(super) fn matched_description(&self) -> Option<String> {
+        let pattern = self.scanner.matched_pattern()?;
+        self.config.blocked_patterns.info(pattern)?.description.clone()
+    }
+
+    /// Forward a body match whose severity calls for logging or tagging only
+    pub(super) fn forward_graded_match(
+        &mut self,
+        category: InjectionCategory,
+        severity: InjectionSeverity,
+        action: SeverityAction,
+        reason: &str,
+    ) {
+        warn!(
+            "[context_id={}] FORWARDED ({} severity, {}): {}",
+            self.context_id,
+            severity.as_str(),
+            action.as_str(),
+            reason
+        );
+        with_metrics(|m| m.severity_action(action.as_str()));
+        if action == SeverityAction::Tag {
+            self.severity_tag = Some(severity.as_str());
+        }
+        self.verdict.action = VerdictAction::Monitored;
+        self.verdict.category = Some(category.as_str().to_string());
+        self.verdict.severity = Some(severity.as_str().to_string());
+        self.verdict.matched_pattern = self.matched_label();
+        self.publish_verdict();
+        let reason = format!(
+            "{} ({} severity, forwarded: {})",
+            reason,
+            severity.as_str(),
+            action.as_str()
+        );
+        let mut event =
+            telemetry::audit_violation_monitored(category.as_str()).with_reason(&reason);
+        if let Some(pattern) = &self.verdict.matched_pattern {
+            event = event.with_pattern(pattern);
+        }
+        self.audit(event);
+    }
+
+    /// Check a `tools/call` against the tier's allowed tools; false if blocked
+    fn check_tier_tools(&mut self) -> bool {
+        let allowed = match &self.tier {
+            Some(tier) if tier.allowed_tools.is_some() && !self.request_blocked => tier.clone(),
+            _ => return true,
+        };
+        let tool = match self.called_tool() {
+            Some(tool) => tool,
+            None if self.jsonrpc.method() == Some(methods::TOOLS_CALL) => {
+                let reason = "Tool call could not be read for the tier's allowed tools";
+                return !self.block_request("tier_policy", reason, None);
+            }
+            None => return true,
+        };
+        if allowed.allows_tool(&tool) {
+            return true;
+        }
+        let tier = self.verdict.tier.clone().unwrap_or_default();
+        let reason = format!("Tool '{}' is not allowed for tier '{}'", tool, tier);
+        !self.block_request("tier_policy", &reason, None)
+    }
+
+    /// MCP checks of a complete JSON-RPC request (tier tools, tool and prompt
+    /// arguments); false if blocked
+    pub(super) fn check_protocol(&mut self) -> bool {
+        if !(self.is_mcp || self.jsonrpc.is_jsonrpc()) {
+            return true;
+        }
+        let start_ns = self.now_ns();
+        let passed = self.check_tier_tools()
+            && self.check_tool_arguments()
+            && self.check_prompt_arguments();
+        let method = self.jsonrpc.method().unwrap_or_default().to_string();
+        let attributes = vec![("ai_guard.method".to_string(), method)];
+        let outcome = if passed { "allow" } else { "block" };
+        self.record_span(Stage::ProtocolValidation, start_ns, outcome, attributes);
+        passed
+    }
+
+    /// Apply the dangerous-argument heuristics to a tools/call request; false if blocked
+    fn check_tool_arguments(&mut self) -> bool {
+        let config = self.config.clone();
+        let analyzer = match &config.tool_arguments {
+            Some(analyzer) if !self.request_blocked => analyzer,
+            _ => return true,
+        };
+        if self.jsonrpc.method() != Some(methods::TOOLS_CALL) {
+            return true;
+        }
+        let call = match self.jsonrpc.params() {
+            Some(params) => ApprovalRequest::from_params(params, None),
+            None if self.jsonrpc.params_overflow() => {
+                let reason = "Tool call arguments too large to inspect";
+                return !self.block_request("tool_arguments", reason, None);
+            }
+            None => None,
+        };
+        let call = match call {
+            Some(call) => call,
+            None => return true,
+        };
+        let violation = match analyzer.inspect(&call.tool, &call.arguments) {
+            Some(violation) => violation,
+            None => return true,
+        };
+        with_metrics(|m| m.tool_argument_flagged(violation.check.as_str()));
+        let pattern = Some(violation.check.as_str().to_string());
+        !self.block_request("tool_arguments", &violation.to_string(), pattern)
+    }
+
+    /// Inspect the arguments of a prompts/get request; false if blocked
+    fn check_prompt_arguments(&mut self) -> bool {
+        let config = match &self.config.prompt_templates {
+            Some(config) if config.scan_arguments && !self.request_blocked => config.clone(),
+            _ => return true,
+        };
+        if self.jsonrpc.method() != Some(methods::PROMPTS_GET) {
+            return true;
+        }
+        let finding = match self.jsonrpc.params() {
+            Some(params) => PromptTemplateInspector::new(&config).inspect_params(params),
+            None if self.jsonrpc.params_overflow() => Some(TemplateFinding {
+                path: "$.params.arguments".to_string(),
+                issue: TemplateIssue::OversizedArguments,
+            }),
+            None => None,
+        };
+        let finding = match finding {
+            Some(finding) => finding,
+            None => return true,
+        };
+        let reason = finding.to_string();
+        warn!("[context_id={}] PROMPT TEMPLATE: {}", self.context_id, reason);
+        with_metrics(|m| m.prompt_template_violation(finding.issue.as_str()));
+        self.audit(telemetry::audit_prompt_template(finding.issue.as_str(), &reason, "request"));
+        if !self.enforce("prompt_template") {
+            return true;
+        }
+        with_metrics(|m| m.request_blocked("prompt_template"));
+        self.verdict.action = VerdictAction::Blocked;
+        self.verdict.category = Some("prompt_template".to_string());
+        self.verdict.matched_pattern = Some(finding.issue.as_str().to_string());
+        self.publish_verdict();
+        self.send_block_response(&reason);
+        false
+    }
+
+    /// Look for massive repetition or whitespace in the complete request; false if blocked
+    pub(super) fn check_repetition(&mut self) -> bool {
+        let finding = match self.repetition.take() {
+            Some(mut detector) if !self.request_blocked && self.is_text_content => {
+                detector.finish()
+            }
+            _ => None,
+        };
+        let config = self.config.clone();
+        let (finding, repetition) = match (finding, &config.repetition_detection) {
+            (Some(finding), Some(repetition)) => (finding, repetition),
+            _ => return true,
+        };
+        let reason = finding.to_string();
+        let action = repetition.action;
+        warn!("[context_id={}] COST ABUSE ({}): {}", self.context_id, action.as_str(), reason);
+        with_metrics(|m| m.cost_abuse(finding.kind.as_str()));
+        self.audit(telemetry::audit_cost_abuse(finding.kind.as_str(), &reason, action.as_str()));
+        match action {
+            RepetitionAction::Block => !self.block_request("cost_abuse", &reason, None),
+            // Only JSON bodies carry a `max_tokens` to cap
+            RepetitionAction::Limit => {
+                let json = self
+                    .get_http_request_header("content-type")
+                    .is_some_and(|ct| ct.to_lowercase().contains("json"));
+                if json {
+                    self.repetition_cap = Some(repetition.limit_max_tokens);
+                }
+                true
+            }
+        }
+    }
+
+    /// Look up the complete request in the threat-intel feed; false if blocked
+    pub(super) fn check_threat_feed(&mut self) -> bool {
+        let mut scanner = match self.threat_scan.take() {
+            Some(scanner) if !self.request_blocked && self.is_text_content => scanner,
+            _ => return true,
+        };
+        let hit = match scanner.finish() {
+            Some(hit) => hit,
+            None => return true,
+        };
+        warn!(
+            "[context_id={}] THREAT INTEL: {} matches IoC {}",
+            self.context_id, hit.path, hit.id
+        );
+        with_metrics(|m| m.threat_intel_match());
+        self.audit(telemetry::audit_threat_intel(&hit.id, &hit.path, scanner.feed_version()));
+        let reason = format!("Request matches known-bad prompt {}", hit.id);
+        !self.block_request("threat_intel", &reason, Some(hit.id))
+    }
+
+    /// Check the links of the complete request against the URL policy; false if blocked
+    pub(super) fn check_urls(&mut self, body_size: usize) -> bool {
+        let violations = match self.urls.take() {
+            Some(mut scanner) if !self.request_blocked && self.is_text_content => scanner.finish(),
+            _ => return true,
+        };
+        let config = self.config.clone();
+        let urls = match &config.url_policy {
+            Some(urls) if !violations.is_empty() => urls,
+            _ => return true,
+        };
+        self.record_url_violations(&violations, "request", urls.action);
+        match urls.action {
+            UrlAction::Block => {
+                let reason = violations[0].to_string();
+                !self.block_request("url_policy", &reason, None)
+            }
+            UrlAction::Defang => {
+                // Compressed and transcoded bodies cannot be rewritten in place
+                if self.is_plain_body() {
+                    self.defang_request_links(urls, body_size);
+                }
+                true
+            }
+        }
+    }
+
+    /// Defang the violating links of the buffered body
+    ///
+    /// The host only replaces a held body whole, so it is read back here, but
+    /// only once the streamed search found a link to defang.
+    fn defang_request_links(&mut self, urls: &UrlPolicyConfig, body_size: usize) {
+        let body = match self.get_http_request_body(0, body_size) {
+            Some(body) => body,
+            None => return,
+        };
+        let json = self
+            .get_http_request_header("content-type")
+            .is_some_and(|ct| ct.to_lowercase().contains("json"));
+        let parsed = match json {
+            true => serde_json::from_slice::<serde_json::Value>(&body).ok(),
+            false => None,
+        };
+        let rewritten = match parsed {
+            Some(mut value) => {
+                urls.apply_value(&mut value);
+                serde_json::to_vec(&value).ok()
+            }
+            None => {
+                let mut text = String::from_utf8_lossy(&body).into_owned();
+                urls.apply(&mut text);
+                Some(text.into_bytes())
+            }
+        };
+        if let Some(rewritten) = rewritten {
+            self.set_http_request_body(0, body_size, &rewritten);
+        }
+    }
+
+    /// Metrics and audit for links that violated the URL policy
+    pub(super) fn record_url_violations(
+        &mut self,
+        violations: &[UrlViolation],
+        direction: &str,
+        action: UrlAction,
+    ) {
+        for violation in violations {
+            warn!(
+                "[context_id={}] URL POLICY ({}): {} ({})",
+                self.context_id,
+                action.as_str(),
+                violation,
+                direction
+            );
+            with_metrics(|m| m.url_violation(violation.reason.as_str()));
+            self.audit(telemetry::audit_url_violation(
+                &violation.host,
+                violation.reason.as_str(),
+                direction,
+                action.as_str(),
+            ));
+        }
+    }
+
+    /// Scan HTML and markdown in the complete request, hidden parts weighted; false if blocked
+    pub(super) fn check_markup(&mut self) -> bool {
+        let finding = match self.markup.take() {
+            Some(mut inspector) if !self.request_blocked && self.is_text_content => {
+                inspector.finish()
+            }
+            _ => None,
+        };
+        let finding = match finding {
+            Some(finding) => finding,
+            None => return true,
+        };
+        let reason = finding.to_string();
+        warn!(
+            "[context_id={}] BLOCKED: {} (score {:.2})",
+            self.context_id, reason, finding.score
+        );
+        with_metrics(|m| m.markup_match(finding.location()));
+        !self.block_request("markup_injection", &reason, Some(finding.pattern))
+    }
+
+    /// Look for long high-entropy runs in the complete request; false if blocked
+    ///
+    /// JSON bodies are fed one string value at a time, so keys and
+    /// punctuation never form a run.
+    pub(super) fn check_entropy(&mut self) -> bool {
+        let runs = match self.entropy.take() {
+            Some(mut scanner) if !self.request_blocked && self.is_text_content => scanner.finish(),
+            _ => return true,
+        };
+        let action = match &self.config.entropy_detection {
+            Some(entropy) if !runs.is_empty() => entropy.action,
+            _ => return true,
+        };
+        for run in &runs {
+            info!(
+                "[context_id={}] High-entropy run: {} bytes at {:.2} bits/byte",
+                self.context_id, run.len, run.peak_bits
+            );
+            with_metrics(|m| m.entropy_anomaly(action.as_str()));
+            self.audit(telemetry::audit_entropy_anomaly(run.len, run.peak_bits, action.as_str()));
+        }
+        match action {
+            EntropyAction::Tag => {
+                self.entropy_tag = true;
+                true
+            }
+            EntropyAction::Block => {
+                let reason = format!("High-entropy run of {} bytes in prompt", runs[0].len);
+                !self.block_request("high_entropy", &reason, None)
+            }
+            EntropyAction::DecodeAndScan => {
+                let config = self.config.clone();
+                for decoded in runs.iter().filter_map(|run| run.decode()) {
+                    let mut patterns = PatternScanner::from_strings(&config.blocked_patterns);
+                    if let ScanResult::Match(m) = patterns.scan_bytes(&decoded) {
+                        let category = InjectionCategory::classify(&m.pattern_name).as_str();
+                        let reason =
+                            format!("Pattern '{}' detected in encoded content", m.pattern_name);
+                        warn!("[context_id={}] BLOCKED: {}", self.context_id, reason);
+                        return !self.block_request(category, &reason, Some(m.pattern_name));
+                    }
+                }
+                true
+            }
+        }
+    }
+
+    /// Set up the detectors that judge the text values of the request body
+    pub(super) fn start_text_detectors(&mut self) {
+        self.language_scan = self.config.language_packs.as_ref().map(LanguagePackConfig::scanner);
+        self.entropy = self.config.entropy_detection.as_ref().map(EntropyScanner::new);
+        self.repetition = self.config.repetition_detection.as_ref().map(RepetitionDetector::new);
+        let config = &self.config;
+        self.markup = config
+            .markup_scanning
+            .as_ref()
+            .map(|markup| MarkupInspector::new(markup, &config.blocked_patterns));
+        self.urls = config.url_policy.as_ref().map(UrlPolicyConfig::scanner);
+        self.threat_scan = match &config.threat_feed {
+            Some(threat_feed) => THREAT_FEED
+                .with(|f| f.borrow().feed())
+                .map(|feed| ThreatScanner::new(feed, threat_feed.min_length)),
+            None => None,
+        };
+        let detecting = self.language_scan.is_some()
+            || self.entropy.is_some()
+            || self.repetition.is_some()
+            || self.markup.is_some()
+            || self.urls.is_some()
+            || self.threat_scan.is_some();
+        if !detecting {
+            return;
+        }
+        let json = self
+            .get_http_request_header("content-type")
+            .is_some_and(|ct| ct.to_lowercase().contains("json"));
+        self.text_values = Some(TextValues::new(json));
+    }
+
+    /// Feed decoded request bytes to the text detectors
+    pub(super) fn observe_text(&mut self, bytes: &[u8], end_of_stream: bool) {
+        let events = match self.text_values.as_mut() {
+            Some(values) => values.feed(bytes, end_of_stream),
+            None => return,
+        };
+        for event in &events {
+            if let Some(scan) = self.language_scan.as_mut() {
+                scan.on_event(event);
+            }
+            if let Some(scanner) = self.entropy.as_mut() {
+                match event {
+                    JsonEvent::StringData(data) => scanner.feed(data),
+                    JsonEvent::StringEnd => scanner.end_segment(),
+                    _ => {}
+                }
+            }
+            if let Some(detector) = self.repetition.as_mut() {
+                match event {
+                    JsonEvent::StringData(data) => detector.feed(data),
+                    JsonEvent::StringEnd => detector.end_segment(),
+                    _ => {}
+                }
+            }
+            if let Some(inspector) = self.markup.as_mut() {
+                inspector.on_event(event);
+            }
+            if let Some(scanner) = self.urls.as_mut() {
+                scanner.on_event(event);
+            }
+            if let Some(scanner) = self.threat_scan.as_mut() {
+                scanner.on_event(event);
+            }
+        }
+    }
+
+    /// Scan the complete request with the pattern pack of its language; false if blocked
+    pub(super) fn check_language_packs(&mut self) -> bool {
+        let scan = match self.language_scan.take() {
+            Some(scan) if !self.request_blocked && self.is_text_content => scan.finish(),
+            _ => return true,
+        };
+        if let Some(language) = scan.language {
+            with_metrics(|m| m.language_detected(language.code()));
+            self.verdict.language = Some(language.code().to_string());
+            self.publish_verdict();
+        }
+        let (language, pattern) = match scan.matched {
+            Some(matched) => matched,
+            None => return true,
+        };
+        warn!(
+            "[context_id={}] BLOCKED: '{}' pattern '{}' detected",
+            self.context_id,
+            language.code(),
+            pattern
+        );
+        let reason = format!("Blocked pattern detected ({})", language.code());
+        !self.block_request("language_pack", &reason, Some(pattern))
+    }
+
+    /// Evaluate the policy rules over the complete request; false if blocked
+    pub(super) fn check_policy_rules(&mut self) -> bool {
+        if self.config.policy_rules.is_empty() || self.request_blocked {
+            return true;
+        }
+        let attrs = RequestAttributes {
+            method: self.jsonrpc.method().map(str::to_string),
+            tool: self.called_tool(),
+            agent_id: self.verdict.agent_id.clone(),
+            path: self.get_http_request_header(":path"),
+            tenant: self.tenant.clone(),
+            tier: self.verdict.tier.clone(),
+            class: Some(self.class.as_str().to_string()),
+            model: self.request_model().map(str::to_string),
+            severity: self
+                .scanner
+                .matched_pattern()
+                .map(|p| InjectionMatch::for_pattern(p).severity().as_str().to_string()),
+            tokens: self.token_estimator.estimate(self.request_model()) as u64,
+            headers: self.get_http_request_headers(),
+            environment: self.config.environment.clone(),
+            local_time_secs: (self.now_ns() / 1_000_000_000) as i64
+                + i64::from(self.config.policy_utc_offset_minutes) * 60,
+        };
+        let rule = match policy::rules::evaluate(&self.config.policy_rules, &attrs) {
+            Some(rule) => rule.clone(),
+            None => return true,
+        };
+        debug!("[context_id={}] Policy rule '{}' matched", self.context_id, rule.name);
+        match rule.action {
+            RuleAction::Allow => true,
+            RuleAction::Deny => {
+                !self.block_request("policy_rule", &rule.deny_reason(), Some(rule.name))
+            }
+        }
+    }
+
+    /// Whether the request body is scanned as sent (not decompressed or transcoded)
+    pub(super) fn is_plain_body(&self) -> bool {
+        self.request_decoder.is_none() && self.request_charset.is_none()
+    }
+
+    /// Tool named by a `tools/call` request
+    fn called_tool(&self) -> Option<String> {
+        if self.jsonrpc.method() != Some(methods::TOOLS_CALL) {
+            return None;
+        }
+        let name = self.jsonrpc.params()?.get("name")?.as_str()?;
+        Some(name.to_string())
+    }
+
+    /// POST a JSON body to a callout cluster
+    fn dispatch_json(
+        &self,
+        cluster: &str,
+        path: &str,
+        authority: Option<&str>,
+        authorization: Option<&str>,
+        body: &str,
+        timeout_ms: u64,
+    ) -> Result<u32, Status> {
+        let mut headers = vec![
+            (":method", "POST"),
+            (":path", path),
+            (":authority", authority.unwrap_or(cluster)),
+            ("content-type", "application/json"),
+        ];
+        if let Some(auth) = authorization {
+            headers.push(("authorization", auth));
+        }
+        self.dispatch_http_call(
+            cluster,
+            headers,
+            Some(body.as_bytes()),
+            vec![],
+            Duration::from_millis(timeout_ms),
+        )
+    }
+
+    /// Ask the policy decision point about a locally allowed request; true while held
+    pub(super) fn consult_pdp(&mut self) -> bool {
+        let pdp = match &self.config.pdp {
+            Some(pdp) => pdp.clone(),
+            None => return false,
+        };
+        if self.request_blocked {
+            return false;
+        }
+        let matched = self.scanner.matched_pattern().map(|p| MatchSummary {
+            pattern: p.to_string(),
+            category: InjectionCategory::classify(p).as_str().to_string(),
+            severity: InjectionMatch::for_pattern(p).severity().as_str().to_string(),
+        });
+        let input = DecisionInput {
+            protocol: if self.is_mcp || self.jsonrpc.is_jsonrpc() { "mcp" } else { "http" },
+            http_method: self.get_http_request_header(":method"),
+            path: self.get_http_request_header(":path"),
+            method: self.jsonrpc.method().map(str::to_string),
+            tool: self.called_tool(),
+            agent_id: self.verdict.agent_id.clone(),
+            tenant: self.tenant.clone(),
+            class: Some(self.class.as_str().to_string()),
+            session_id: self.session_id.clone(),
+            model: self.request_model().map(str::to_string),
+            tokens: self.token_estimator.estimate(self.request_model()) as u64,
+            local_action: self.verdict.action.as_str().to_string(),
+            matched,
+        };
+        let dispatched = self.dispatch_json(
+            &pdp.cluster,
+            &pdp.path,
+            pdp.authority.as_deref(),
+            pdp.authorization.as_deref(),
+            &input.to_body(),
+            pdp.timeout_ms,
+        );
+        match dispatched {
+            Ok(token) => {
+                self.pending_decision = Some(token);
+                true
+            }
+            Err(e) => {
+                warn!(
+                    "[context_id={}] PDP callout to {} failed, local decision stands: {:?}",
+                    self.context_id, pdp.cluster, e
+                );
+                with_metrics(|m| m.pdp_decision("fallback"));
+                false
+            }
+        }
+    }
+
+    /// Apply the PDP's answer to the held request
+    pub(super) fn finish_pdp(&mut self, decision: PdpDecision) {
+        with_metrics(|m| m.pdp_decision(decision.as_str()));
+        match decision {
+            PdpDecision::Allow(obligations) => {
+                for (name, value) in obligations.request_headers() {
+                    self.set_http_request_header(name, Some(value));
+                }
+            }
+            PdpDecision::Deny(reason) => {
+                if self.block_request("pdp", &reason, None) {
+                    return;
+                }
+            }
+            PdpDecision::Unavailable(problem) => {
+                warn!(
+                    "[context_id={}] No PDP decision ({}), local decision stands",
+                    self.context_id, problem
+                );
+            }
+        }
+        if !self.hold_for_approval() {
+            self.release_request();
+        }
+    }
+
+    /// Hold a high-risk MCP tool call for approval; true while the request is held
+    pub(super) fn hold_for_approval(&mut self) -> bool {
+        let approval = match &self.config.tool_approval {
+            Some(approval) => approval.clone(),
+            None => return false,
+        };
+        let is_mcp = self.is_mcp || self.jsonrpc.is_jsonrpc();
+        if self.request_blocked || !is_mcp || self.jsonrpc.method() != Some(methods::TOOLS_CALL) {
+            return false;
+        }
+        let params = self.jsonrpc.params();
+        let id = self.jsonrpc.id();
+        let mut request = match params.and_then(|params| ApprovalRequest::from_params(params, id)) {
+            Some(request) => request,
+            None => {
+                let reason = "Tool call could not be read for approval";
+                return self.block_request("tool_approval", reason, None);
+            }
+        };
+        if !approval.requires_approval(&request.tool) {
+            return false;
+        }
+        request.request_id = self.audit_stamp.as_ref().map(|s| s.request_id.clone());
+        request.agent_id = self.verdict.agent_id.clone();
+        request.tenant = self.tenant.clone();
+        request.session_id = self.session_id.clone();
+
+        let body = serde_json::to_string(&request).unwrap_or_default();
+        let dispatched = self.dispatch_json(
+            &approval.cluster,
+            &approval.path,
+            approval.authority.as_deref(),
+            approval.authorization.as_deref(),
+            &body,
+            approval.timeout_ms,
+        );
+        match dispatched {
+            Ok(token) => {
+                info!(
+                    "[context_id={}] Tool '{}' held for approval",
+                    self.context_id, request.tool
+                );
+                self.pending_approval = Some((token, request.tool));
+                true
+            }
+            Err(e) => {
+                warn!(
+                    "[context_id={}] Approval callout to {} failed: {:?}",
+                    self.context_id, approval.cluster, e
+                );
+                match approval.decide(None, None) {
+                    ApprovalDecision::Approved(_) => false,
+                    ApprovalDecision::Rejected(detail) => {
+                        let reason = format!("Tool call '{}' rejected: {}", request.tool, detail);
+                        self.block_request("tool_approval", &reason, None)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Rewrite the buffered JSON body (model override, `max_tokens` clamp); false if blocked
+    ///
+    /// The host only replaces a held body whole, so it is read back here, but
+    /// only when the scan found something to change.
+    pub(super) fn apply_body_rewrites(&mut self, body_size: usize) -> bool {
+        let clamp = self.clamp_max_tokens || self.repetition_cap.is_some();
+        if self.request_blocked || (self.model_override.is_none() && !clamp) {
+            return true;
+        }
+        let requested = self.scanner.model().unwrap_or_default().to_string();
+        let limit = match (self.config.max_tokens_limit, self.repetition_cap) {
+            (Some(limit), Some(cap)) => limit.min(cap),
+            (limit, cap) => limit.or(cap).unwrap_or(u64::MAX),
+        };
+        // Compressed, transcoded and multipart bodies cannot be rewritten in place
+        let rewritten = if self.is_plain_body() && self.multipart.is_none() {
+            self.get_http_request_body(0, body_size)
+                .and_then(|body| serde_json::from_slice::<serde_json::Value>(&body).ok())
+                .and_then(|mut value| {
+                    if let Some(model) = &self.model_override {
+                        value["model"] = serde_json::Value::String(model.clone());
+                    }
+                    if clamp {
+                        let fields = MAX_TOKENS_FIELDS.iter().map(|f| f.trim_start_matches("$."));
+                        let mut present = false;
+                        for field in fields {
+                            present |= !value[field].is_null();
+                            let within = value[field]
+                                .as_f64()
+                                .is_some_and(|v| (0.0..=limit as f64).contains(&v));
+                            if !value[field].is_null() && !within {
+                                value[field] = serde_json::Value::from(limit);
+                            }
+                        }
+                        // A capped completion must not fall back to the provider default
+                        if !present && self.repetition_cap.is_some() {
+                            value["max_tokens"] = serde_json::Value::from(limit);
+                        }
+                    }
+                    serde_json::to_vec(&value).ok()
+                })
+        } else {
+            None
+        };
+        let body = match rewritten {
+            Some(body) => body,
+            None => {
+                let reason = "Request body could not be rewritten";
+                return !self.block_request("body_rewrite", reason, None);
+            }
+        };
+        self.set_http_request_body(0, body_size, &body);
+
+        if clamp {
+            info!("[context_id={}] max_tokens clamped to {}", self.context_id, limit);
+        }
+        if let Some(replacement) = self.model_override.clone() {
+            info!(
+                "[context_id={}] Model '{}' rewritten to '{}'",
+                self.context_id, requested, replacement
+            );
+            self.verdict.model = Some(replacement.clone());
+            self.publish_verdict();
+            self.audit(telemetry::audit_model_override(&requested, &replacement));
+        }
+        true
+    }
+}
@@ -0,0 +1,434 @@
+//! proxy-wasm Filter
+//!
+//! Root and HTTP contexts: drives the scanning core from Envoy's proxy-wasm
+//! callbacks and keeps per-worker state (configuration snapshot, rate
+//! limiters, caches) in thread-locals. Built with the `proxy-wasm` feature.
+//!
+//! The contexts and the thread-locals they share are declared here; their
+//! behavior is split by phase and subsystem:
+//!
+//! - `root`: configuration loading and the periodic tasks
+//! - `context`: HTTP context lifecycle, callbacks, audit, spans and replies
+//! - `admission`: tenant, route and tier resolution, bypass, rate limits,
+//!   sessions, penalties and replay protection
+//! - `request`: the request header, body and trailer phases
+//! - `inspection`: the checks run over the request body
+//! - `response`: the response phases, streamed scanning and MCP results
+//! - `pii`: request tokenization and response redaction of PII
+//! - `admin`: the admin endpoint
+
+mod admin;
+mod admission;
+mod context;
+mod inspection;
+mod pii;
+mod request;
+mod response;
+mod root;
+
+use log::{debug, error, info, warn};
+use proxy_wasm::traits::{Context, HttpContext, RootContext};
+use proxy_wasm::types::{Action, ContextType, LogLevel, Status};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::config::{ConfigError, ConfigSnapshot, FilterConfig};
+use crate::crypto::{sha256, to_hex};
+use crate::governance::body_scanner::MAX_TOKENS_FIELDS;
+use crate::governance::penalties;
+use crate::governance::replay::{self, Nonce};
+use crate::governance::response_pii::REDACTIONS_HEADER;
+use crate::governance::session::{self, SESSION_RESPONSE_HEADER};
+use crate::governance::threat_intel;
+use crate::governance::verdict_cache::{cache_key, CacheKey};
+use crate::governance::{
+    AgentRecord, ApprovalDecision, ApprovalRequest, Arm, BinaryInspector, BudgetLimit, DryRun,
+    ExperimentRun, FeedCache, HeaderDecision, HeaderInspector, InjectionCategory, InjectionMatch,
+    InjectionSeverity, LanguagePackConfig, LanguageScanner, MarkupInspector, McpEventRewriter,
+    McpResultAction, McpResultMatch, McpResultScanner, ModelDecision, MultipartInspector,
+    NotificationFilter, NotificationLimiter, Penalty, PiiAction, PiiFinding, PiiType,
+    PromptResultRewriter, PromptTemplateInspector, RateDecision, RateLimitInfo, RateLimiter,
+    RedactedChunk, RepetitionAction, RepetitionDetector, ResponsePiiRedactor, ResponseScanConfig,
+    ResponseScanner, ResponseViolation, ResultLimitAction, ResultLimitRewriter,
+    ResultLimitViolation, ScanDecision, ScanSummary, SessionAction, SeverityAction,
+    StreamingBodyScanner, TemplateFinding, TemplateIssue, ThreatFeed, ThreatScanner, TokenCounter,
+    TokenEstimator, TokenUsage, TokenVault, ToolCallInspector, ToolCallViolation, UrlAction,
+    UrlPolicyConfig, UrlScanner, UrlViolation, VerdictCache,
+};
+use crate::metrics::{FilterMetrics, RequestDimensions};
+use crate::policy::control::{
+    pattern_report, reset_agent, status_report, update_flags, AdminRoute, MAX_ADMIN_BODY,
+};
+use crate::policy::network::XFF_HEADER;
+use crate::policy::rollout;
+use crate::policy::{
+    admit_bypass, AdminResponse, BypassRejection, ControlConfig, ControlFlags, DecisionInput,
+    MatchSummary, PdpDecision, RequestAttributes, RequestClass, RoutePolicies, RuleAction,
+    TenantPolicies, TierPolicy, BYPASS_HEADER,
+};
+use crate::protocols::a2a::{A2AOperation, RestRoute};
+use crate::protocols::mcp::jsonrpc::methods;
+use crate::protocols::mcp::stdio_detect::{StdioDetector, WorkloadIdentity};
+use crate::protocols::mcp::{is_mcp_request, JsonRpcError, JsonRpcResponse, JsonRpcSniffer};
+use crate::protocols::{anthropic, openai, ChatApi, GrpcStatus};
+use crate::shared::HostSharedStore;
+use crate::streaming::decompress::{decodable_codings, decode_all};
+use crate::streaming::multipart::multipart_boundary;
+use crate::streaming::ndjson::is_ndjson;
+use crate::streaming::{
+    BodyDecoder, Charset, CharsetDecoder, ContentEncoding, EntropyAction, EntropyScanner, JsonEvent,
+    PatternScanner, RpcDecoder, RpcProtocol, ScanResult, StreamWatchdog, TextValues,
+};
+use crate::telemetry::pattern_stats;
+use crate::telemetry::{
+    check_audit_sink, check_patterns, check_shared_data, check_threat_feed, verify_debug_token,
+    AuditEvent, AuditFormat, AuditShipper, AuditStamp, Explanation, HealthReport, OverheadTracker,
+    Phase, RuleMatch, ShipOutcome, Verdict, VerdictAction, DEBUG_REQUEST_HEADER,
+    FORWARDED_VERDICT_HEADER, GUARDRAIL_REQUEST_ID_HEADER, REQUEST_ID_HEADER, STATUS_HEADER,
+    VERDICT_RESPONSE_HEADER,
+};
+use crate::trace::{SpanQueue, SpanRecorder, Stage, TraceContext, TRACEPARENT_HEADER};
+use crate::{policy, telemetry};
+
+/// Response header carrying prompt tokens (opt-in)
+const HEADER_TOKENS_PROMPT: &str = "x-ai-tokens-prompt";
+/// Response header carrying completion tokens (opt-in)
+const HEADER_TOKENS_COMPLETION: &str = "x-ai-tokens-completion";
+/// Response header carrying estimated cost in USD (opt-in)
+const HEADER_COST_USD: &str = "x-ai-cost-usd";
+/// Response header naming the configuration and pattern set versions in force
+const HEADER_INSPECTED: &str = "x-guardrail-inspected";
+
+// Thread-local storage for filter configuration
+thread_local! {
+    static CONFIG: RefCell<Rc<ConfigSnapshot>> = RefCell::new(Rc::default());
+}
+
+// Thread-local metrics (one set per Envoy worker VM)
+thread_local! {
+    static METRICS: RefCell<FilterMetrics> = RefCell::new(FilterMetrics::new());
+}
+
+// Thread-local audit shipper, fed by HTTP contexts and flushed by the root
+thread_local! {
+    static AUDIT_SHIPPER: RefCell<Option<AuditShipper>> = const { RefCell::new(None) };
+}
+
+// Thread-local span queue, fed by HTTP contexts and exported by the root
+thread_local! {
+    static SPAN_QUEUE: RefCell<SpanQueue> = RefCell::new(SpanQueue::new());
+}
+
+// Thread-local tenant policies (resolver + per-tenant configs)
+thread_local! {
+    static TENANTS: RefCell<Rc<TenantPolicies>> = RefCell::new(Rc::default());
+}
+
+// Thread-local route policies (matcher + per-tenant route configs)
+thread_local! {
+    static ROUTES: RefCell<Rc<RoutePolicies>> = RefCell::new(Rc::default());
+}
+
+// Configuration generation (bumped per `on_configure`) and live HTTP
+// contexts per generation they were created under
+thread_local! {
+    static CONFIG_GENERATION: Cell<u64> = const { Cell::new(0) };
+    static LIVE_CONTEXTS: RefCell<BTreeMap<u64, usize>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+// Thread-local cache of recent scan verdicts, keyed by body and policy scope
+thread_local! {
+    static VERDICT_CACHE: RefCell<VerdictCache> = RefCell::new(VerdictCache::new());
+}
+
+// Thread-local rate limiters, one per tenant and route ("" for the base config)
+thread_local! {
+    static RATE_LIMITERS: RefCell<HashMap<String, RateLimiter>> = RefCell::new(HashMap::new());
+}
+
+// Thread-local copy of the threat-intel feed, refreshed from shared data by the root
+thread_local! {
+    static THREAT_FEED: RefCell<FeedCache> = RefCell::new(FeedCache::default());
+}
+
+// Thread-local MCP notification counters, per session
+thread_local! {
+    static NOTIFICATION_LIMITER: RefCell<NotificationLimiter> =
+        RefCell::new(NotificationLimiter::new());
+}
+
+/// Rate-limit key for requests without an agent ID
+const ANONYMOUS_AGENT: &str = "anonymous";
+
+/// Run a closure against the worker's metrics
+fn with_metrics<F: FnOnce(&mut FilterMetrics)>(f: F) {
+    METRICS.with(|m| f(&mut m.borrow_mut()));
+}
+
+thread_local! {
+    /// Recent per-request overhead of this worker, for the budget alarm
+    static OVERHEAD: RefCell<OverheadTracker> = RefCell::new(OverheadTracker::new());
+}
+
+thread_local! {
+    /// Sequence number of this worker's last signed audit event
+    static AUDIT_SEQUENCE: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Sign (if configured), log and ship an audit event
+fn publish_audit(event: AuditEvent, config: &FilterConfig, now_ms: u64) {
+    let event = match &config.audit_signing {
+        Some(signing) => {
+            let sequence = AUDIT_SEQUENCE.with(|s| {
+                s.set(s.get() + 1);
+                s.get()
+            });
+            signing.sign(event, sequence)
+        }
+        None => event,
+    };
+    event.emit_as(config.audit_format);
+    ship_audit(&event, config.audit_format, now_ms);
+}
+
+/// Export how many live HTTP contexts still run on a replaced configuration
+fn record_stale_contexts() {
+    let current = CONFIG_GENERATION.with(Cell::get);
+    let stale = LIVE_CONTEXTS.with(|l| l.borrow().range(..current).map(|(_, n)| n).sum());
+    with_metrics(|m| m.stale_contexts(stale));
+}
+
+/// Queue an audit event for the external collector (if configured)
+fn ship_audit(event: &AuditEvent, format: AuditFormat, now_ms: u64) {
+    let rendered = match format.render(event) {
+        Some(rendered) => rendered,
+        None => return,
+    };
+    let dropped = AUDIT_SHIPPER.with(|s| {
+        s.borrow_mut()
+            .as_mut()
+            .map(|shipper| shipper.enqueue(rendered, now_ms))
+            .unwrap_or(0)
+    });
+    if dropped > 0 {
+        with_metrics(|m| m.audit_dropped(dropped));
+    }
+}
+
+/// Record the outcome of an audit batch delivery
+fn record_ship_outcome(outcome: ShipOutcome) {
+    match outcome {
+        ShipOutcome::Delivered(n) => with_metrics(|m| m.audit_shipped(n)),
+        ShipOutcome::Retrying(_) => with_metrics(|m| m.audit_retried()),
+        ShipOutcome::Dropped(n) => {
+            warn!("AI-Guard: Dropping {} audit events after failed deliveries", n);
+            with_metrics(|m| m.audit_dropped(n));
+        }
+        ShipOutcome::Unknown => {}
+    }
+}
+
+/// Root context for filter lifecycle management
+struct AiGuardRootContext {
+    config: FilterConfig,
+    /// SHA-256 (hex) of the pushed configuration bytes
+    config_digest: Option<String>,
+    /// Token of the threat feed fetch in flight
+    feed_call: Option<u32>,
+}
+
+/// HTTP context for per-request processing
+///
+/// CRITICAL: Uses streaming body scanner - does NOT accumulate body in memory.
+struct AiGuardHttpContext {
+    context_id: u32,
+    /// Streaming body scanner (ring buffer based)
+    scanner: StreamingBodyScanner,
+    /// Token counter for cost attribution (shared pricing table)
+    token_counter: Rc<TokenCounter>,
+    /// Pre-flight prompt token estimate (request path)
+    token_estimator: TokenEstimator,
+    /// Token usage reported in response headers (e.g. Bedrock)
+    header_usage: Option<TokenUsage>,
+    /// Model ID derived from the request path (e.g. Bedrock `/model/{id}/...`)
+    path_model: Option<String>,
+    /// The body's model has been checked against the model policy
+    model_checked: bool,
+    /// Replacement model written into the body at end of stream
+    model_override: Option<String>,
+    /// `max_tokens` is clamped to the limit at end of stream
+    clamp_max_tokens: bool,
+    /// `max_tokens` forced onto a token-burning prompt at end of stream
+    repetition_cap: Option<u64>,
+    /// Response headers are held until the body completes (usage headers, tool calls)
+    hold_response_headers: bool,
+    /// Size of the response body as of the last body callback
+    response_body_size: usize,
+    /// Session the request belongs to (when session tracking is enabled)
+    session_id: Option<String>,
+    /// The session has violations on record: flag it in the response
+    session_warn: bool,
+    /// Severity of a forwarded match, tagged onto the response
+    severity_tag: Option<&'static str>,
+    /// The request carried a high-entropy run: tag the response
+    entropy_tag: bool,
+    /// A match banned the session: escalate it to blocked once the request is done
+    session_ban: bool,
+    /// Agent's penalty and violations on record when the request arrived
+    agent_penalty: Option<(Penalty, u32)>,
+    /// Verdict cache key of a body whose scan outcome is to be cached
+    verdict_cache_key: Option<CacheKey>,
+    /// Pattern experiment arm and shadow scanner
+    experiment: Option<ExperimentRun>,
+    /// Shadow scan with the dry-run candidate configuration
+    dry_run: Option<DryRun>,
+    /// Runtime control flags read at the start of the request
+    control: ControlFlags,
+    /// The request carried a valid bypass token: nothing is inspected
+    bypassed: bool,
+    /// The signed verdict went upstream with the request headers
+    verdict_forwarded: bool,
+    /// Categories already recorded as monitored
+    monitored: Vec<String>,
+    /// The request is an update for the admin endpoint (body pending)
+    admin_update: bool,
+    /// Tool call held for approval: callout token and tool name
+    pending_approval: Option<(u32, String)>,
+    /// Request held for the policy decision point: callout token
+    pending_decision: Option<u32>,
+    /// Track if we've already sent a block response
+    request_blocked: bool,
+    /// Guardrail verdict published as filter state
+    verdict: Verdict,
+    /// Resolved tenant (None = base config)
+    tenant: Option<String>,
+    /// Policy of the caller's identity tier
+    tier: Option<TierPolicy>,
+    /// Kind of traffic (path-based until the body refines it)
+    class: RequestClass,
+    /// Request ID and agent stamped onto audit events
+    audit_stamp: Option<AuditStamp>,
+    /// Signed debug token accepted: explain the verdict in a response header
+    debug: bool,
+    /// Decision details for the debug header
+    explanation: Explanation,
+    /// Guardrail spans (only for sampled traces when tracing is enabled)
+    spans: Option<SpanRecorder>,
+    /// Start of the body scan stage (Unix ns)
+    scan_start_ns: Option<u64>,
+    /// Time spent inside the scanner, across chunks
+    scan_busy_us: u64,
+    /// Time spent in the filter's HTTP hooks, across phases
+    overhead_us: u64,
+    /// Configuration snapshot for this request (copied only when a tier changes it)
+    config: Rc<ConfigSnapshot>,
+    /// Tenant policies in force when the context was created
+    tenants: Rc<TenantPolicies>,
+    /// Route policies in force when the context was created
+    routes: Rc<RoutePolicies>,
+    /// Configuration generation the context was created under
+    generation: u64,
+    /// Request headers identify MCP traffic
+    is_mcp: bool,
+    /// Transport of an inspected request (None for admin and bypassed requests)
+    transport: Option<&'static str>,
+    /// JSON-RPC envelope of the request body (blocks reply as JSON-RPC errors)
+    jsonrpc: JsonRpcSniffer,
+    /// Decoder for a gzip/deflate request body
+    request_decoder: Option<BodyDecoder>,
+    /// Transcoder for a UTF-16/Latin-1 request body
+    request_charset: Option<CharsetDecoder>,
+    /// Response Content-Encoding (for usage extraction)
+    response_encoding: ContentEncoding,
+    /// Decoder for an inspected gzip/deflate response, which is forwarded decoded
+    response_decoder: Option<BodyDecoder>,
+    /// Part-by-part inspection of a multipart request body
+    multipart: Option<MultipartInspector>,
+    /// Binary policy for a non-text request body
+    binary: Option<BinaryInspector>,
+    /// Text values of the request body, for the detectors below
+    text_values: Option<TextValues>,
+    /// Language detection and pack scan of the request text
+    language_scan: Option<LanguageScanner>,
+    /// High-entropy runs in the request text
+    entropy: Option<EntropyScanner>,
+    /// Repetition in the request text
+    repetition: Option<RepetitionDetector>,
+    /// Injections hidden in the markup of the request text
+    markup: Option<MarkupInspector>,
+    /// Links in the request text
+    urls: Option<UrlScanner>,
+    /// Fingerprints of the request text, against the threat-intel feed
+    threat_scan: Option<ThreatScanner>,
+    /// Scanner for a streamed (SSE) completion
+    response_scanner: Option<ResponseScanner>,
+    /// The streamed response was cut short after a match
+    response_truncated: bool,
+    /// Limits on a streamed (SSE) response
+    watchdog: Option<StreamWatchdog>,
+    /// The watchdog cut the streamed response off
+    stream_terminated: bool,
+    /// Notification counting for an MCP event stream, and the session counted against
+    notifications: Option<(NotificationFilter, String)>,
+    /// Rewriter for the SSE response of a scanned MCP call
+    mcp_events: Option<McpEventRewriter>,
+    /// The JSON response of a scanned MCP call is inspected at end of stream
+    mcp_result_body: bool,
+    /// Size and depth limits on the SSE response of an MCP tool call
+    result_limit_events: Option<ResultLimitRewriter>,
+    /// The JSON response of an MCP tool call is checked against the limits at end of stream
+    result_limit_body: bool,
+    /// Template inspection of the SSE response of a prompts/get call
+    prompt_events: Option<PromptResultRewriter>,
+    /// The JSON response of a prompts/get call is inspected at end of stream
+    prompt_result_body: bool,
+    /// PII redaction of an SSE response
+    pii_events: Option<ResponsePiiRedactor>,
+    /// The JSON response is checked for PII at end of stream
+    pii_body: bool,
+    /// PII matches in the response, per type
+    response_pii_counts: BTreeMap<&'static str, u32>,
+    /// Tokens that replaced PII in the request body (vault mode)
+    vault: Option<TokenVault>,
+    /// Content type of request
+    is_text_content: bool,
+    /// Number of request-body bytes already processed.
+    ///
+    /// CRITICAL: In proxy-wasm, `body_size` in `on_http_request_body` is the
+    /// size of the buffered body so far (not just the new chunk). We must
+    /// only read and scan the newly appended bytes to avoid reprocessing and
+    /// to keep filter memory usage flat.
+    body_bytes_processed: usize,
+    /// Size of the buffered request body as of the last body callback
+    request_body_size: usize,
+}
+
+// Register the filter with proxy-wasm runtime
+proxy_wasm::main! {{
+    proxy_wasm::set_log_level(LogLevel::Debug);
+    proxy_wasm::set_root_context(|_| -> Box<dyn RootContext> {
+        Box::new(AiGuardRootContext::new())
+    });
+}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_default() {
+        let config = FilterConfig::default();
+        assert!(!config.blocked_patterns.is_empty());
+        assert!(config.max_body_size > 0);
+        assert!(config.ring_buffer_size > 0);
+    }
+
+    #[test]
+    fn test_scanner_creation() {
+        let config = FilterConfig::default();
+        let scanner = StreamingBodyScanner::new(&config);
+        assert!(!scanner.is_complete());
+    }
+}
@@ -0,0 +1,199 @@
+//! PII handling
+//!
+//! Tokenization of PII in requests (vault mode), and detokenization and
+//! redaction of PII in responses.
+
+use super::*;
+
+impl AiGuardHttpContext {
+    /// The request's vault, when its tokens are to be restored in the response
+    pub(super) fn detokenizing_vault(&self) -> Option<&TokenVault> {
+        let detokenize = self.config.pii_vault.as_ref().is_some_and(|v| v.detokenize_responses);
+        self.vault.as_ref().filter(|v| detokenize && !v.is_empty())
+    }
+
+    /// PII redaction and vault de-tokenization of the response, if configured
+    pub(super) fn response_pii_redactor(&self) -> Option<ResponsePiiRedactor> {
+        let vault = self.detokenizing_vault().cloned();
+        let redactor = match (&self.config.response_pii, vault) {
+            (Some(response_pii), vault) => {
+                let enforce = self.control.enforces("response_pii");
+                let redactor =
+                    ResponsePiiRedactor::new(response_pii, self.config.pii_redactor(), enforce);
+                match vault {
+                    Some(vault) => redactor.with_vault(vault),
+                    None => redactor,
+                }
+            }
+            (None, Some(vault)) => ResponsePiiRedactor::detokenizer(vault),
+            (None, None) => return None,
+        };
+        Some(redactor)
+    }
+
+    /// Replace PII in the request body by vault tokens before it goes upstream
+    ///
+    /// Tokens are as long as the values they replace: the body is rewritten
+    /// in place and its length does not change.
+    pub(super) fn tokenize_request_pii(&mut self, body_size: usize) {
+        let config = self.config.clone();
+        let vault_config = match &config.pii_vault {
+            Some(vault_config) => vault_config,
+            None => return,
+        };
+        // Compressed and transcoded bodies cannot be rewritten in place
+        if self.request_blocked || !self.is_text_content || !self.is_plain_body() {
+            return;
+        }
+        let mut body = match self.get_http_request_body(0, body_size) {
+            Some(body) => body,
+            None => return,
+        };
+        let json = self
+            .get_http_request_header("content-type")
+            .is_some_and(|ct| ct.to_lowercase().contains("json"));
+        let start_ns = self.now_ns();
+        let vault = self.vault.get_or_insert_with(|| TokenVault::new(&vault_config.key));
+        let tokenized = vault.tokenize_body(&mut body, json, &config.pii_redactor());
+        let outcome = if tokenized.is_empty() { "allow" } else { "tokenize" };
+        let attributes = vec![
+            ("ai_guard.direction".to_string(), "request".to_string()),
+            ("ai_guard.findings".to_string(), tokenized.len().to_string()),
+        ];
+        self.record_span(Stage::PiiScan, start_ns, outcome, attributes);
+        if tokenized.is_empty() {
+            return;
+        }
+        self.set_http_request_body(0, body_size, &body);
+        info!("[context_id={}] {} PII values tokenized", self.context_id, tokenized.len());
+        let mut audited: Vec<PiiType> = Vec::new();
+        for pii_type in tokenized {
+            with_metrics(|m| m.pii_tokenized(pii_type.as_str()));
+            if !audited.contains(&pii_type) {
+                audited.push(pii_type);
+                let metadata = serde_json::json!({"direction": "request", "action": "tokenize"});
+                self.audit(telemetry::audit_pii(pii_type.as_str()).with_metadata(metadata));
+            }
+        }
+    }
+
+    /// Redact PII in a chunk of an SSE response; returns the size of the chunk left to forward
+    pub(super) fn redact_response_chunk(&mut self, body_size: usize, end_of_stream: bool) -> usize {
+        let chunk = self.get_http_response_body(0, body_size).unwrap_or_default();
+        let out = match self.pii_events.as_mut() {
+            Some(redactor) => {
+                let mut out = redactor.feed(&chunk);
+                if end_of_stream && !out.blocked {
+                    let rest = redactor.finish();
+                    out.bytes.extend(rest.bytes);
+                    out.findings.extend(rest.findings);
+                    out.blocked = rest.blocked;
+                }
+                out
+            }
+            None => return body_size,
+        };
+        self.record_response_pii(&out.findings);
+        let mut bytes = out.bytes;
+        if out.blocked {
+            let reason = self.block_response_pii(&out.findings);
+            self.pii_events = None;
+            self.stream_terminated = true;
+            let error = serde_json::json!({"error": {"type": "response_pii", "message": reason}});
+            bytes.extend(format!("event: error\ndata: {}\n\n", error).into_bytes());
+        }
+        self.set_http_response_body(0, body_size, &bytes);
+        bytes.len()
+    }
+
+    /// Redact PII in the complete JSON response; None when the response was blocked
+    pub(super) fn redact_response_body(
+        &mut self,
+        body: Option<Vec<u8>>,
+        body_size: usize,
+    ) -> Option<(Option<Vec<u8>>, usize)> {
+        let (message, redactor) = match (&body, self.response_pii_redactor()) {
+            (Some(message), Some(redactor)) => (message, redactor),
+            _ => return Some((body, body_size)),
+        };
+        let start_ns = self.now_ns();
+        let mut out = RedactedChunk::default();
+        let redacted = redactor.redact_body(message, &mut out);
+        let outcome = match (out.blocked, &redacted) {
+            (true, _) => "block",
+            (false, Some(_)) => "redact",
+            (false, None) => "allow",
+        };
+        let attributes = vec![
+            ("ai_guard.direction".to_string(), "response".to_string()),
+            ("ai_guard.findings".to_string(), out.findings.len().to_string()),
+        ];
+        self.record_span(Stage::PiiScan, start_ns, outcome, attributes);
+        self.record_response_pii(&out.findings);
+        let header = self.config.response_pii.as_ref().is_some_and(|r| r.redactions_header);
+        if let (true, Some(summary)) = (header, self.verdict.redaction_summary()) {
+            self.set_http_response_header(REDACTIONS_HEADER, Some(&summary));
+        }
+        if out.blocked {
+            let reason = self.block_response_pii(&out.findings);
+            self.send_block_response(&reason);
+            return None;
+        }
+        match redacted {
+            Some(bytes) => {
+                self.set_http_response_body(0, body_size, &bytes);
+                let size = bytes.len();
+                Some((Some(bytes), size))
+            }
+            None => Some((body, body_size)),
+        }
+    }
+
+    /// Metrics, audit and redaction counts for PII found in the response
+    ///
+    /// Every match is counted; each type is audited once per response.
+    fn record_response_pii(&mut self, findings: &[PiiFinding]) {
+        let redacted = findings.iter().any(|f| f.action == PiiAction::Redact);
+        for finding in findings {
+            let name = finding.pii_type.as_str();
+            with_metrics(|m| m.pii_detected(name));
+            if finding.action == PiiAction::Redact {
+                let redactions = self.verdict.redactions.get_or_insert_with(BTreeMap::new);
+                *redactions.entry(name.to_string()).or_insert(0) += 1;
+            }
+            let count = self.response_pii_counts.entry(name).or_insert(0);
+            *count += 1;
+            if *count > 1 {
+                continue;
+            }
+            info!(
+                "[context_id={}] PII in response: {} ({})",
+                self.context_id,
+                name,
+                finding.action.as_str()
+            );
+            let metadata =
+                serde_json::json!({"direction": "response", "action": finding.action.as_str()});
+            self.audit(telemetry::audit_pii(name).with_metadata(metadata));
+        }
+        if redacted {
+            self.publish_verdict();
+        }
+    }
+
+    /// Metrics and verdict for a response withheld for its PII; returns the reason
+    fn block_response_pii(&mut self, findings: &[PiiFinding]) -> String {
+        let pii_type = findings
+            .iter()
+            .find(|f| f.action == PiiAction::Block)
+            .map_or("pii", |f| f.pii_type.as_str());
+        let reason = format!("Response withheld: it contains PII of type '{}'", pii_type);
+        warn!("[context_id={}] RESPONSE PII: {}", self.context_id, reason);
+        with_metrics(|m| m.request_blocked("response_pii"));
+        self.verdict.action = VerdictAction::Blocked;
+        self.verdict.category = Some("response_pii".to_string());
+        self.verdict.matched_pattern = Some(pii_type.to_string());
+        self.publish_verdict();
+        reason
+    }
+}
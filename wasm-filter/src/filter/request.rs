@@ -0,0 +1,645 @@
+//! Request phase
+//!
+//! Request header, body and trailer handling: admission in the header
+//! phase, then streaming the body through the scanner and the inspection
+//! checks.
+
+use super::*;
+
+impl AiGuardHttpContext {
+    pub(super) fn handle_request_headers(
+        &mut self,
+        _num_headers: usize,
+        end_of_stream: bool,
+    ) -> Action {
+        debug!(
+            "[context_id={}] Processing request headers",
+            self.context_id
+        );
+
+        // Log request path for debugging
+        let path = self.get_http_request_header(":path");
+        if let Some(path) = &path {
+            debug!("[context_id={}] Request path: {}", self.context_id, path);
+            self.path_model = TokenCounter::bedrock_model_from_path(path);
+        }
+
+        // Probes bypass authentication and policy alike
+        if let Some(health) = &self.config.health {
+            if path.as_deref().is_some_and(|p| health.is_health_path(p)) {
+                self.send_health_report();
+                return Action::Pause;
+            }
+        }
+
+        // Only the filter vouches for a request
+        if self.config.verdict_propagation.is_some() {
+            self.set_http_request_header(FORWARDED_VERDICT_HEADER, None);
+        }
+
+        // Runtime controls come from the base configuration, before tenant overrides
+        if let Some(control) = self.config.control.clone() {
+            if let Some(route) = path.as_deref().and_then(|p| control.admin_route(p)) {
+                return self.handle_admin_request(&control, route, end_of_stream);
+            }
+            self.control = ControlFlags::load(&HostSharedStore);
+            if self.control.disabled {
+                debug!("[context_id={}] Inspection disabled by kill switch", self.context_id);
+                return Action::Continue;
+            }
+        }
+
+        // Classification, tenant and route first: everything below uses them
+        self.is_mcp = is_mcp_request(&self.get_http_request_headers());
+        self.transport = Some(self.request_transport());
+        self.classify_request(path.as_deref());
+        self.resolve_tenant(path.as_deref());
+        self.resolve_route(path.as_deref());
+        self.resolve_tier();
+        if !self.check_network() {
+            return Action::Pause;
+        }
+
+        if self.config.tracing.is_some() {
+            self.spans = self
+                .get_http_request_header(TRACEPARENT_HEADER)
+                .and_then(|h| TraceContext::parse(&h))
+                .and_then(|ctx| SpanRecorder::new(ctx, self.context_id));
+        }
+
+        self.verdict.agent_id = self.get_http_request_header(&self.config.agent_id_header);
+
+        // Correlate audit events with access logs; generate an ID if Envoy didn't
+        let stamp = AuditStamp::new(
+            self.get_http_request_header(REQUEST_ID_HEADER),
+            self.now_ns(),
+            self.context_id,
+        )
+        .with_agent_id(self.verdict.agent_id.clone());
+        if stamp.generated {
+            self.set_http_request_header(REQUEST_ID_HEADER, Some(&stamp.request_id));
+        }
+        self.audit_stamp = Some(stamp);
+        // A bypass skips the inspection checks, not the header hygiene
+        let bypassed = self.check_bypass();
+        if !bypassed {
+            self.apply_rollout();
+            self.assign_experiment_arm();
+            self.start_dry_run();
+        }
+
+        if let Some(debug) = &self.config.debug {
+            if let Some(token) = self.get_http_request_header(DEBUG_REQUEST_HEADER) {
+                let now_secs = self.now_ns() / 1_000_000_000;
+                self.debug = verify_debug_token(
+                    &token,
+                    debug.secret.as_bytes(),
+                    now_secs,
+                    debug.max_ttl_secs,
+                );
+                if !self.debug {
+                    warn!("[context_id={}] Invalid debug token ignored", self.context_id);
+                }
+            }
+            // Never forward the token upstream
+            self.set_http_request_header(DEBUG_REQUEST_HEADER, None);
+        }
+
+        if !bypassed && !self.admit_request_headers() {
+            return Action::Pause;
+        }
+        self.label_request();
+        // Without a body every attribute is known now
+        if !bypassed && end_of_stream && (!self.check_policy_rules() || self.consult_pdp()) {
+            return Action::Pause;
+        }
+        // Only the filter may route a request to quarantine
+        if let Some(header) = self.config.quarantine.as_ref().map(|q| q.header.clone()) {
+            self.set_http_request_header(&header, None);
+        }
+        self.scrub_request_headers();
+        self.publish_verdict();
+        if bypassed {
+            return Action::Continue;
+        }
+
+        // Compressed bodies are inflated before scanning
+        if self.config.decompression.enabled {
+            let encoding = self
+                .get_http_request_header("content-encoding")
+                .map(|v| ContentEncoding::parse(&v))
+                .unwrap_or(ContentEncoding::Identity);
+            if let ContentEncoding::Unsupported(name) = &encoding {
+                if self.config.decompression.block_unsupported {
+                    let reason = format!("Unsupported content-encoding: {}", name);
+                    if self.block_request("unsupported_encoding", &reason, None) {
+                        return Action::Pause;
+                    }
+                }
+                warn!(
+                    "[context_id={}] Scanning undecodable content-encoding as raw bytes: {}",
+                    self.context_id, name
+                );
+            }
+            let limits = &self.config.decompression;
+            self.request_decoder =
+                BodyDecoder::new(&encoding, limits.max_decompressed_size, limits.max_ratio);
+        }
+        // Responses the filter inspects must come in an encoding it can decode
+        if self.config.inspects_responses() {
+            let accept = if self.config.decompression.enabled {
+                self.get_http_request_header("accept-encoding").map(|v| decodable_codings(&v))
+            } else {
+                Some("identity".to_string())
+            };
+            if let Some(accept) = accept {
+                self.set_http_request_header("accept-encoding", Some(&accept));
+            }
+        }
+
+        // Check Content-Type - only inspect JSON/text bodies
+        if let Some(content_type) = self.get_http_request_header("content-type") {
+            if self.config.multipart.enabled {
+                self.multipart = multipart_boundary(&content_type)
+                    .map(|boundary| MultipartInspector::new(&boundary, &self.config.multipart));
+            }
+            let ct_lower = content_type.to_lowercase();
+            if self.multipart.is_none() {
+                let charset = Charset::from_content_type(&content_type);
+                if let Charset::Unsupported(name) = &charset {
+                    debug!(
+                        "[context_id={}] Scanning unsupported charset as raw bytes: {}",
+                        self.context_id, name
+                    );
+                }
+                self.request_charset = CharsetDecoder::new(&charset);
+            }
+            let rpc = match self.config.grpc_web.enabled {
+                true => RpcProtocol::from_content_type(&content_type),
+                false => None,
+            };
+            if self.multipart.is_none()
+                && rpc.is_none()
+                && !self.config.inspects_content_type(&content_type)
+            {
+                debug!(
+                    "[context_id={}] Skipping non-text content-type: {}",
+                    self.context_id, content_type
+                );
+                self.is_text_content = false;
+                if let Some(policy) = &self.config.binary_policy {
+                    let text_route = path
+                        .as_deref()
+                        .is_some_and(|p| ChatApi::from_path(p).is_some() || policy.is_text_only(p));
+                    self.binary = Some(BinaryInspector::new(policy, &content_type, text_route));
+                }
+                return self.hold_request_headers(end_of_stream);
+            }
+            if let Some(protocol) = rpc {
+                let encoding = protocol
+                    .encoding_header()
+                    .and_then(|name| self.get_http_request_header(name))
+                    .map_or(ContentEncoding::Identity, |v| ContentEncoding::parse(&v));
+                let max_message_size = self.config.grpc_web.max_message_size;
+                let max_ratio = self.config.decompression.max_ratio;
+                let decoder = RpcDecoder::new(protocol, encoding, max_message_size, max_ratio);
+                self.scanner.enable_rpc(decoder);
+            } else if self.multipart.is_none()
+                && self.config.ndjson.enabled
+                && is_ndjson(&content_type)
+            {
+                self.scanner.enable_ndjson(self.config.ndjson.block_invalid);
+                self.token_estimator = TokenEstimator::json();
+            } else if self.multipart.is_none() && ct_lower.contains("json") {
+                self.token_estimator = TokenEstimator::json();
+                let api = path.as_deref().and_then(ChatApi::from_path);
+                self.scanner.enable_json_for(&self.config, api);
+                // Overrides and clamping change the body length
+                let overrides = self.config.model_policy.as_ref().map(|p| &p.overrides);
+                let limits_repetition = self
+                    .config
+                    .repetition_detection
+                    .as_ref()
+                    .is_some_and(|r| r.action == RepetitionAction::Limit);
+                if overrides.is_some_and(|o| !o.is_empty())
+                    || self.config.clamp_max_tokens
+                    || limits_repetition
+                {
+                    self.set_http_request_header("content-length", None);
+                }
+            }
+            // Defanged links lengthen the body
+            if self.config.url_policy.as_ref().is_some_and(|u| u.action == UrlAction::Defang) {
+                self.set_http_request_header("content-length", None);
+            }
+        }
+        self.start_text_detectors();
+
+        self.hold_request_headers(end_of_stream)
+    }
+
+    pub(super) fn handle_request_body(&mut self, body_size: usize, end_of_stream: bool) -> Action {
+        self.request_body_size = body_size;
+        // If already blocked, don't process further
+        if self.request_blocked {
+            return Action::Pause;
+        }
+        if self.admin_update {
+            return self.finish_admin_update(body_size, end_of_stream);
+        }
+        if self.inspection_skipped() {
+            return Action::Continue;
+        }
+
+        // Skip inspection for non-text content
+        if !self.is_text_content {
+            if !self.check_binary_body(body_size, end_of_stream) {
+                return Action::Pause;
+            }
+            if end_of_stream {
+                self.refine_class();
+            }
+            if end_of_stream && (!self.check_policy_rules() || self.consult_pdp()) {
+                return Action::Pause;
+            }
+            return Action::Continue;
+        }
+
+        debug!(
+            "[context_id={}] Body chunk: {} bytes, end_of_stream: {}",
+            self.context_id, body_size, end_of_stream
+        );
+
+        // Only read the newly appended bytes (do NOT re-read the full body).
+        if body_size < self.body_bytes_processed {
+            // Body buffer was reset by Envoy (unexpected), reset our cursor.
+            self.body_bytes_processed = 0;
+        }
+        let new_len = body_size.saturating_sub(self.body_bytes_processed);
+        // An empty last chunk (or trailers) still ends a scan that has started
+        let finish_scan =
+            end_of_stream && self.body_bytes_processed > 0 && !self.scanner.is_complete();
+
+        if new_len == 0 && !finish_scan {
+            return if end_of_stream && self.run_end_of_stream_checks(body_size) {
+                Action::Continue
+            } else {
+                Action::Pause
+            };
+        }
+
+        // Cacheable bodies are inspected once complete, so a retry can skip the scan
+        let cacheable = self.body_bytes_processed == 0
+            && self.config.verdict_cache.applies_to(body_size);
+        if cacheable && !end_of_stream {
+            return Action::Pause;
+        }
+
+        let new_bytes = match new_len {
+            0 => Some(Vec::new()),
+            _ => self.get_http_request_body(self.body_bytes_processed, new_len),
+        };
+        if let Some(new_bytes) = new_bytes {
+            if self.body_bytes_processed == 0 {
+                with_metrics(|m| m.request_inspected());
+            }
+            let cached = if cacheable { self.lookup_verdict(&new_bytes) } else { None };
+            self.body_bytes_processed += new_bytes.len();
+
+            let new_bytes = match self.request_decoder.as_mut() {
+                None => new_bytes,
+                Some(decoder) => {
+                    let mut plain = Vec::new();
+                    if let Err(e) = decoder.decode(&new_bytes, &mut plain) {
+                        let reason = format!("Request body decompression failed: {}", e);
+                        if self.block_request("decompression", &reason, None) {
+                            return Action::Pause;
+                        }
+                        // Monitored: the rest of the body cannot be inspected
+                        self.is_text_content = false;
+                        return Action::Continue;
+                    }
+                    plain
+                }
+            };
+            // Text in another charset is transcoded to UTF-8 for the scanner
+            if self.request_charset.is_none()
+                && self.multipart.is_none()
+                && self.scanner.total_bytes() == 0
+            {
+                self.request_charset = Charset::from_bom(&new_bytes)
+                    .as_ref()
+                    .and_then(CharsetDecoder::new);
+            }
+            let new_bytes = match self.request_charset.as_mut() {
+                None => new_bytes,
+                Some(decoder) => {
+                    let mut text = Vec::new();
+                    decoder.decode(&new_bytes, &mut text);
+                    if end_of_stream {
+                        decoder.finish(&mut text);
+                    }
+                    text
+                }
+            };
+            // Only text parts of a multipart body are scanned
+            let new_bytes = match self.multipart.as_mut() {
+                None => new_bytes,
+                Some(inspector) => match inspector.feed(&new_bytes, end_of_stream) {
+                    Ok(text) => text,
+                    Err(violation) => {
+                        if self.block_request("multipart", &violation.to_string(), None) {
+                            return Action::Pause;
+                        }
+                        self.is_text_content = false;
+                        return Action::Continue;
+                    }
+                },
+            };
+            self.token_estimator.observe(&new_bytes);
+            self.jsonrpc.observe(&new_bytes);
+            self.observe_text(&new_bytes, end_of_stream);
+
+            // CRITICAL: Stream through scanner - O(n) time, O(1) filter memory
+            let scan_start = self.get_current_time();
+            if self.scan_start_ns.is_none() {
+                self.scan_start_ns = Some(self.now_ns());
+            }
+            let decision = match &cached {
+                Some(summary) => self.scanner.restore(summary),
+                None => self.scanner.on_body_chunk(&new_bytes, end_of_stream),
+            };
+            if let (None, Some(key)) = (&cached, self.verdict_cache_key) {
+                self.store_verdict(key, &decision);
+            }
+            if let Some(run) = self.experiment.as_mut() {
+                run.feed(&self.scanner, &new_bytes, end_of_stream);
+            }
+            if let Some(run) = self.dry_run.as_mut() {
+                run.feed(&self.scanner, &new_bytes, end_of_stream);
+            }
+            if self.verdict.model.is_none() {
+                if let Some(model) = self.scanner.model() {
+                    self.verdict.model = Some(model.to_string());
+                    self.publish_verdict();
+                }
+            }
+            let scan_micros = self
+                .get_current_time()
+                .duration_since(scan_start)
+                .map(|d| d.as_micros() as u64)
+                .unwrap_or(0);
+            self.scan_busy_us += scan_micros;
+            with_metrics(|m| {
+                m.scan_bytes(new_bytes.len());
+                m.scan_latency_us(scan_micros);
+            });
+            // A chunk over the time budget ends the scan: the rest goes unscanned
+            let decision = match (&self.config.scan_budget, decision) {
+                (Some(budget), ScanDecision::Continue) if budget.exceeds_time(scan_micros) => {
+                    self.scanner.exhaust(BudgetLimit::Time)
+                }
+                (_, decision) => decision,
+            };
+
+            if !self.check_request_limits() || !self.check_model_policy() {
+                self.finish_scan_span("block");
+                return Action::Pause;
+            }
+
+            let category = self
+                .scanner
+                .matched_pattern()
+                .map(InjectionCategory::classify)
+                .unwrap_or(InjectionCategory::Other);
+            let severity = self
+                .scanner
+                .matched_pattern()
+                .map(|p| InjectionMatch::for_pattern(p).severity());
+            let quarantined = match (&self.config.quarantine, severity) {
+                (Some(quarantine), Some(severity)) => quarantine.applies_to(severity),
+                _ => false,
+            };
+            let graded = match (&self.config.severity_actions, severity) {
+                (Some(actions), Some(severity)) => Some((severity, actions.action(severity))),
+                _ => None,
+            };
+            match decision {
+                ScanDecision::Block(_) if !self.enforce(category.as_str()) => {
+                    self.finish_scan_span("monitor");
+                }
+                ScanDecision::Block(reason) if quarantined => {
+                    self.finish_scan_span("quarantine");
+                    self.quarantine_request(category, severity, &reason);
+                }
+                ScanDecision::Block(reason) if graded.is_some_and(|(_, a)| a.forwards()) => {
+                    if let Some((severity, action)) = graded {
+                        self.finish_scan_span(action.as_str());
+                        self.forward_graded_match(category, severity, action, &reason);
+                    }
+                }
+                ScanDecision::Block(reason) => {
+                    if let Some((_, action)) = graded {
+                        with_metrics(|m| m.severity_action(action.as_str()));
+                        self.session_ban = action == SeverityAction::Ban;
+                    }
+                    let pattern = self.matched_label();
+                    with_metrics(|m| m.request_blocked(category.as_str()));
+                    if let Some(p) = &pattern {
+                        with_metrics(|m| m.pattern_hit(p));
+                        if let Err(e) = pattern_stats::record_hit(&HostSharedStore, p) {
+                            debug!(
+                                "[context_id={}] Pattern stats not updated: {}",
+                                self.context_id, e
+                            );
+                        }
+                    }
+                    self.finish_scan_span("block");
+
+                    if let (Some(p), Some(sev)) = (&pattern, severity) {
+                        self.explanation.rules.push(RuleMatch {
+                            pattern: p.clone(),
+                            category: category.as_str().to_string(),
+                            severity: sev.as_str().to_string(),
+                            score: sev.score(),
+                            path: self.scanner.matched_path().map(str::to_string),
+                        });
+                    }
+
+                    self.verdict.action = VerdictAction::Blocked;
+                    self.verdict.category = Some(category.as_str().to_string());
+                    self.verdict.severity = severity.map(|s| s.as_str().to_string());
+                    self.verdict.matched_pattern = pattern;
+                    self.publish_verdict();
+
+                    let mut event = telemetry::audit_blocked(
+                        &reason,
+                        self.verdict.matched_pattern.as_deref(),
+                    );
+                    if let Some(description) = self.matched_description() {
+                        let fields = serde_json::json!({ "pattern_description": description });
+                        event = event.with_metadata(fields);
+                    }
+                    if let Some(capture) = &self.config.audit_capture {
+                        let content = self.scanner.recent_bytes(capture.bytes);
+                        let redactor = self.config.pii_redactor();
+                        if let Some(excerpt) = capture.excerpt(&content, &redactor) {
+                            event = event.with_metadata(excerpt);
+                        }
+                    }
+                    self.audit(event);
+
+                    self.send_block_response(&reason);
+                    return Action::Pause;
+                }
+                ScanDecision::Continue => {
+                    // More chunks expected, keep buffering
+                    return Action::Pause;
+                }
+                ScanDecision::Allow => {
+                    self.finish_scan_span("allow");
+                    // Body is safe, forward to upstream
+                    debug!(
+                        "[context_id={}] Body passed security check ({} bytes, ~{} prompt tokens)",
+                        self.context_id,
+                        self.scanner.total_bytes(),
+                        self.token_estimator.estimate(self.request_model())
+                    );
+                }
+                ScanDecision::Skip(reason) => {
+                    self.finish_scan_span("skip");
+                    match self.scanner.budget_exhausted() {
+                        Some(limit) => {
+                            with_metrics(|m| m.scan_budget_exhausted(limit.as_str()));
+                            info!(
+                                "[context_id={}] {} at {} body bytes, rest forwarded unscanned",
+                                self.context_id,
+                                reason,
+                                self.scanner.total_bytes()
+                            );
+                        }
+                        None => debug!(
+                            "[context_id={}] Skipping scan: {}",
+                            self.context_id, reason
+                        ),
+                    }
+                }
+            }
+        }
+
+        if end_of_stream && !self.run_end_of_stream_checks(body_size) {
+            return Action::Pause;
+        }
+        Action::Continue
+    }
+
+    /// Checks that need the complete body; false if the request is refused or held
+    fn run_end_of_stream_checks(&mut self, body_size: usize) -> bool {
+        self.refine_class();
+        self.tokenize_request_pii(body_size);
+        self.check_replay()
+            && self.check_token_budget()
+            && self.check_threat_feed()
+            && self.check_language_packs()
+            && self.check_entropy()
+            && self.check_repetition()
+            && self.check_markup()
+            && self.check_urls(body_size)
+            && self.apply_body_rewrites(body_size)
+            && self.check_protocol()
+            && self.check_policy_rules()
+            && !self.consult_pdp()
+            && !self.hold_for_approval()
+    }
+
+    pub(super) fn handle_request_trailers(&mut self, _num_trailers: usize) -> Action {
+        if self.request_blocked {
+            return Action::Pause;
+        }
+        if !self.admin_update && !self.inspection_skipped() && !self.check_request_trailers() {
+            return Action::Pause;
+        }
+        // The last body chunk was not end_of_stream: the body ends here.
+        // A streamed non-text body has nothing left to take in.
+        let body_size = if self.is_text_content { self.request_body_size } else { 0 };
+        self.handle_request_body(body_size, true)
+    }
+
+    /// Pause on headers that must wait for the body's checks
+    fn hold_request_headers(&self, end_of_stream: bool) -> Action {
+        // Routing happens on headers: hold them until the body scan decides.
+        // So does the forwarded verdict.
+        // Held tool calls must not reach the upstream before approval either.
+        // The class header waits for the body to refine the label.
+        let approval = self.config.tool_approval.is_some() && self.is_mcp;
+        let labelled = self.config.classification.as_ref().is_some_and(|c| c.header.is_some());
+        let hold = self.config.quarantine.is_some()
+            || self.config.pdp.is_some()
+            || self.config.verdict_propagation.is_some()
+            || approval
+            || labelled;
+        if hold && !end_of_stream {
+            return Action::Pause;
+        }
+        Action::Continue
+    }
+
+    /// Header-phase inspection and admission checks; false if the request was refused
+    fn admit_request_headers(&mut self) -> bool {
+        let inspector = self.config.header_policy.as_ref().map(HeaderInspector::new);
+        if let Some(inspector) = inspector {
+            match inspector.inspect(&self.get_http_request_headers()) {
+                HeaderDecision::Allow { strip } => {
+                    for name in strip {
+                        self.set_http_request_header(&name, None);
+                    }
+                }
+                HeaderDecision::Block(violation) => {
+                    let category = violation
+                        .pattern
+                        .as_deref()
+                        .map(|p| InjectionCategory::classify(p).as_str())
+                        .unwrap_or("header_policy");
+                    if self.block_request(category, &violation.reason, violation.pattern) {
+                        return false;
+                    }
+                }
+            }
+        }
+
+        if !self.check_stdio_bypass() {
+            return false;
+        }
+
+        let agent = self.rate_limit_key();
+        let start_ns = self.now_ns();
+        let now_secs = start_ns / 1_000_000_000;
+        let limited = self.with_rate_limiter(|limiter| {
+            let decision = limiter.check_request(&agent, now_secs);
+            (decision, limiter.get_state(&agent))
+        });
+        if let Some((decision, state)) = limited {
+            let outcome = match decision {
+                RateDecision::RateLimited(_) => "rate_limit",
+                _ => "allow",
+            };
+            self.record_span(Stage::RateLimit, start_ns, outcome, vec![]);
+            if let (Some(state), Some(limits)) = (state, &self.config.rate_limits) {
+                self.explanation.rate_limit = Some(format!(
+                    "{}/{} requests, {}/{} tokens",
+                    state.request_count,
+                    limits.requests_per_minute,
+                    state.token_count,
+                    limits.tokens_per_minute
+                ));
+            }
+            let enforced = matches!(decision, RateDecision::RateLimited(_))
+                && self.enforce("rate_limit");
+            if let (RateDecision::RateLimited(info), true) = (decision, enforced) {
+                self.reject_rate_limited(&info);
+                return false;
+            }
+        }
+        self.check_session() && self.check_agent_penalty()
+    }
+}
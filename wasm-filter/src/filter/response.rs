@@ -0,0 +1,864 @@
+//! Response phase
+//!
+//! Response header and body handling: usage extraction, streamed (SSE)
+//! completion scanning and limits, MCP result inspection and rewriting, and
+//! tool calls in completions.
+
+use super::*;
+
+impl AiGuardHttpContext {
+    /// Count a gRPC call's status and audit it unless OK
+    fn record_grpc_status(&mut self, headers: &[(String, String)]) {
+        let status = match GrpcStatus::from_headers(headers) {
+            Some(status) => status,
+            None => return,
+        };
+        with_metrics(|m| m.grpc_status(status.name()));
+        if status.is_ok() {
+            return;
+        }
+        info!(
+            "[context_id={}] gRPC call failed with status {} ({})",
+            self.context_id,
+            status.code,
+            status.name()
+        );
+        self.audit(telemetry::audit_grpc_error(
+            status.code,
+            status.name(),
+            status.message.as_deref(),
+        ));
+    }
+
+    /// Decompress a complete response body for usage extraction
+    fn decode_response_body(&self, body: Vec<u8>) -> Option<Vec<u8>> {
+        let limits = &self.config.decompression;
+        match decode_all(
+            &self.response_encoding,
+            &body,
+            limits.max_decompressed_size,
+            limits.max_ratio,
+        ) {
+            None if self.response_encoding == ContentEncoding::Identity => Some(body),
+            None => None,
+            Some(Ok(plain)) => Some(plain),
+            Some(Err(e)) => {
+                debug!(
+                    "[context_id={}] Response body not decompressed: {}",
+                    self.context_id, e
+                );
+                None
+            }
+        }
+    }
+
+    /// Whether chunks of a streamed response are inspected or rewritten
+    fn inspects_stream(&self) -> bool {
+        self.watchdog.is_some()
+            || self.notifications.is_some()
+            || self.response_scanner.is_some()
+            || self.mcp_events.is_some()
+            || self.result_limit_events.is_some()
+            || self.prompt_events.is_some()
+            || self.pii_events.is_some()
+    }
+
+    /// Set up decoding of an encoded response the filter inspects; false once it is blocked
+    ///
+    /// The response is forwarded decoded. One in an encoding that cannot be
+    /// decoded is blocked, or passed through uninspected when
+    /// `block_unsupported` is off.
+    fn decode_response(&mut self, encoding: ContentEncoding) -> bool {
+        let limits = &self.config.decompression;
+        let decoder = match limits.enabled {
+            true => BodyDecoder::new(&encoding, limits.max_decompressed_size, limits.max_ratio),
+            false => None,
+        };
+        if let Some(decoder) = decoder {
+            self.response_decoder = Some(decoder);
+            // Usage is then read from the decoded body
+            self.response_encoding = ContentEncoding::Identity;
+            self.set_http_response_header("content-encoding", None);
+            self.set_http_response_header("content-length", None);
+            return true;
+        }
+        let name = self.get_http_response_header("content-encoding").unwrap_or_default();
+        let reason = format!("Undecodable response content-encoding: {}", name);
+        if self.config.decompression.block_unsupported
+            && self.block_request("unsupported_encoding", &reason, None)
+        {
+            return false;
+        }
+        warn!(
+            "[context_id={}] Passing undecodable response content-encoding uninspected: {}",
+            self.context_id, name
+        );
+        self.watchdog = None;
+        self.notifications = None;
+        self.response_scanner = None;
+        self.mcp_events = None;
+        self.result_limit_events = None;
+        self.prompt_events = None;
+        self.pii_events = None;
+        self.mcp_result_body = false;
+        self.result_limit_body = false;
+        self.prompt_result_body = false;
+        self.pii_body = false;
+        true
+    }
+
+    /// Decode the next chunk of a response forwarded decoded; returns its size
+    ///
+    /// None once the response is cut off, as it cannot go on undecoded.
+    fn decode_response_chunk(&mut self, body_size: usize) -> Option<usize> {
+        let chunk = self.get_http_response_body(0, body_size).unwrap_or_default();
+        let mut plain = Vec::new();
+        let decoded = match self.response_decoder.as_mut() {
+            Some(decoder) => decoder.decode(&chunk, &mut plain),
+            None => return Some(body_size),
+        };
+        let reason = match decoded {
+            Ok(()) => {
+                self.set_http_response_body(0, body_size, &plain);
+                return Some(plain.len());
+            }
+            Err(e) => format!("Response body decompression failed: {}", e),
+        };
+        warn!("[context_id={}] RESPONSE BLOCKED: {}", self.context_id, reason);
+        self.response_decoder = None;
+        with_metrics(|m| m.request_blocked("decompression"));
+        self.verdict.action = VerdictAction::Blocked;
+        self.verdict.category = Some("decompression".to_string());
+        self.publish_verdict();
+        self.audit(telemetry::audit_blocked(&reason, None));
+        if self.hold_response_headers {
+            self.send_block_response(&reason);
+            return None;
+        }
+        // Headers are gone: end the stream with an SSE error event instead
+        self.stream_terminated = true;
+        let error = serde_json::json!({
+            "error": {"type": "decompression", "message": reason}
+        });
+        let event = format!("event: error\ndata: {}\n\n", error);
+        self.set_http_response_body(0, body_size, event.as_bytes());
+        None
+    }
+
+    /// Scan a streamed response chunk; after a match the rest of the stream is dropped
+    fn scan_response_chunk(&mut self, body_size: usize) {
+        if self.response_truncated {
+            self.set_http_response_body(0, body_size, &[]);
+            return;
+        }
+        let hit = match self.get_http_response_body(0, body_size) {
+            Some(chunk) => self.response_scanner.as_mut().and_then(|s| s.feed(&chunk)),
+            None => None,
+        };
+        let reason = match hit.and_then(|v| self.record_response_violation(v)) {
+            Some(reason) => reason,
+            None => return,
+        };
+        warn!("[context_id={}] RESPONSE TRUNCATED: {}", self.context_id, reason);
+
+        // Headers are gone: end the stream with an SSE error event instead
+        self.response_truncated = true;
+        let error = serde_json::json!({
+            "error": {"type": "policy_violation", "message": reason}
+        });
+        let event = format!("event: error\ndata: {}\n\n", error);
+        self.set_http_response_body(0, body_size, event.as_bytes());
+    }
+
+    /// Apply the stream watchdog to the next chunk; false once the stream is cut off
+    fn check_stream_watchdog(&mut self, body_size: usize) -> bool {
+        if self.stream_terminated {
+            // The client was told why; an upstream still sending is reset
+            self.set_http_response_body(0, body_size, &[]);
+            self.reset_http_response();
+            return false;
+        }
+        let config = self.config.clone();
+        let now_ns = self.now_ns();
+        let chunk = self.get_http_response_body(0, body_size).unwrap_or_default();
+        let trip = match (self.watchdog.as_mut(), &config.stream_watchdog) {
+            (Some(watchdog), Some(limits)) => watchdog.observe(limits, &chunk, now_ns),
+            _ => None,
+        };
+        let trip = match trip {
+            Some(trip) => trip,
+            None => return true,
+        };
+        // Monitored: recorded once, then the stream runs unwatched
+        self.watchdog = None;
+        if !self.enforce("stream_watchdog") {
+            return true;
+        }
+        warn!("[context_id={}] STREAM TERMINATED: {}", self.context_id, trip.reason);
+        with_metrics(|m| m.stream_terminated(trip.limit.as_str()));
+        self.audit(telemetry::audit_stream_terminated(trip.limit.as_str(), &trip.reason));
+        self.stream_terminated = true;
+        let error = serde_json::json!({
+            "error": {"type": "stream_limit", "message": trip.reason}
+        });
+        let event = format!("event: error\ndata: {}\n\n", error);
+        self.set_http_response_body(0, body_size, event.as_bytes());
+        false
+    }
+
+    /// Drop (or end the stream on) notifications over the session's cap;
+    /// returns the size of the chunk left to forward
+    fn limit_notifications(&mut self, body_size: usize) -> usize {
+        let config = self.config.clone();
+        let limits = match &config.notification_limits {
+            Some(limits) => limits,
+            None => return body_size,
+        };
+        let chunk = self.get_http_response_body(0, body_size).unwrap_or_default();
+        let now_secs = self.now_ns() / 1_000_000_000;
+        let (out, session) = match self.notifications.as_mut() {
+            Some((filter, session)) => {
+                let out = NOTIFICATION_LIMITER
+                    .with(|l| filter.feed(&chunk, limits, &mut l.borrow_mut(), session, now_secs));
+                (out, session.clone())
+            }
+            None => return body_size,
+        };
+        if out.dropped > 0 {
+            with_metrics(|m| m.notifications_dropped(out.dropped));
+        }
+        if let Some(method) = &out.exceeded {
+            warn!(
+                "[context_id={}] NOTIFICATION FLOOD: session {} over its cap ({})",
+                self.context_id,
+                session,
+                limits.action.as_str()
+            );
+            self.audit(telemetry::audit_notification_flood(
+                method,
+                &session,
+                limits.action.as_str(),
+            ));
+        }
+        let mut bytes = out.bytes;
+        if out.blocked {
+            with_metrics(|m| m.request_blocked("notification_flood"));
+            self.notifications = None;
+            self.stream_terminated = true;
+            let error = serde_json::json!({
+                "error": {"type": "notification_flood", "message": "MCP notification cap exceeded"}
+            });
+            bytes.extend(format!("event: error\ndata: {}\n\n", error).into_bytes());
+        }
+        self.set_http_response_body(0, body_size, &bytes);
+        bytes.len()
+    }
+
+    /// Metrics, verdict and audit for a response violation; returns the reason
+    ///
+    /// None when runtime controls only let the violation be recorded.
+    fn record_response_violation(&mut self, violation: ResponseViolation) -> Option<String> {
+        let (category, reason, pattern, severity, canary) = match violation {
+            ResponseViolation::Pattern(hit) if hit.canary => {
+                let name = hit.pattern.pattern_name;
+                let reason = format!("System prompt canary '{}' leaked in response", name);
+                ("canary_leak", reason, name, Some(InjectionSeverity::Critical), true)
+            }
+            ResponseViolation::Pattern(hit) => {
+                let pattern = hit.pattern.pattern_name;
+                let category = InjectionCategory::classify(&pattern).as_str();
+                let reason = format!("Pattern '{}' detected in response", pattern);
+                (category, reason, pattern, None, false)
+            }
+            ResponseViolation::ToolCall(v) => {
+                let reason = v.to_string();
+                ("tool_call", reason, v.pattern().to_string(), Some(v.severity()), false)
+            }
+        };
+        if !self.enforce(category) {
+            return None;
+        }
+        with_metrics(|m| {
+            m.request_blocked(category);
+            if !canary {
+                m.pattern_hit(&pattern);
+            }
+        });
+        self.verdict.action = VerdictAction::Blocked;
+        self.verdict.category = Some(category.to_string());
+        if let Some(severity) = severity {
+            self.verdict.severity = Some(severity.as_str().to_string());
+        }
+        self.verdict.matched_pattern = Some(pattern);
+        self.publish_verdict();
+        let event = match &self.verdict.matched_pattern {
+            Some(name) if canary => telemetry::audit_canary_leak(name),
+            pattern => telemetry::audit_blocked(&reason, pattern.as_deref()),
+        };
+        self.audit(event);
+        Some(reason)
+    }
+
+    /// Rewrite the complete JSON response of a scanned MCP call
+    fn rewrite_mcp_result(&mut self, body: &[u8], body_size: usize) {
+        let mut scanner = match &self.config.mcp_result_scanning {
+            Some(policy) => McpResultScanner::new(policy),
+            None => return,
+        };
+        if let Some(urls) = self.config.url_policy.as_ref().filter(|u| u.scan_results) {
+            scanner = scanner.with_url_policy(urls);
+        }
+        let (matches, rewritten) = scanner.scan_body(body);
+        if let Some(rewritten) = rewritten {
+            self.set_http_response_body(0, body_size, &rewritten);
+        }
+        self.record_mcp_result_matches(&matches);
+        self.record_result_url_violations(&scanner.take_url_violations());
+    }
+
+    /// Metrics, verdict and audit for links in MCP results that violated the URL policy
+    fn record_result_url_violations(&mut self, violations: &[UrlViolation]) {
+        let action = match (violations.first(), &self.config.url_policy) {
+            (Some(_), Some(urls)) => urls.action,
+            _ => return,
+        };
+        self.record_url_violations(violations, "result", action);
+        if action == UrlAction::Block {
+            with_metrics(|m| m.request_blocked("url_policy"));
+            self.verdict.action = VerdictAction::Blocked;
+            self.verdict.category = Some("url_policy".to_string());
+            self.publish_verdict();
+        }
+    }
+
+    /// Rewrite a chunk of the SSE response of a scanned MCP call
+    fn rewrite_mcp_chunk(&mut self, body_size: usize) {
+        let chunk = match self.get_http_response_body(0, body_size) {
+            Some(chunk) => chunk,
+            None => return,
+        };
+        let out = match self.mcp_events.as_mut() {
+            Some(rewriter) => rewriter.feed(&chunk),
+            None => return,
+        };
+        self.set_http_response_body(0, body_size, &out.bytes);
+        if out.oversized {
+            warn!(
+                "[context_id={}] MCP result event over the size limit replaced by an error",
+                self.context_id
+            );
+        }
+        self.record_mcp_result_matches(&out.matches);
+        self.record_result_url_violations(&out.url_violations);
+    }
+
+    /// Apply the tool-result limits to a chunk of an MCP SSE response;
+    /// returns the size of the chunk left to forward
+    fn limit_result_chunk(&mut self, body_size: usize) -> usize {
+        let chunk = match self.get_http_response_body(0, body_size) {
+            Some(chunk) => chunk,
+            None => return body_size,
+        };
+        let out = match self.result_limit_events.as_mut() {
+            Some(rewriter) => rewriter.feed(&chunk),
+            None => return body_size,
+        };
+        self.set_http_response_body(0, body_size, &out.bytes);
+        for violation in &out.violations {
+            self.record_result_limit(violation);
+        }
+        out.bytes.len()
+    }
+
+    /// Apply the tool-result limits to the complete JSON response of an MCP call
+    fn limit_result_body(
+        &mut self,
+        body: Option<Vec<u8>>,
+        body_size: usize,
+    ) -> (Option<Vec<u8>>, usize) {
+        let config = self.config.clone();
+        let (message, limits) = match (&body, &config.tool_result_limits) {
+            (Some(message), Some(limits)) => (message, limits),
+            _ => return (body, body_size),
+        };
+        let id = self.jsonrpc.id().cloned().unwrap_or(serde_json::Value::Null);
+        let limited = limits.apply(message, &id);
+        match (limited.violation, limited.bytes) {
+            (Some(violation), Some(bytes)) => {
+                self.record_result_limit(&violation);
+                self.set_http_response_body(0, body_size, &bytes);
+                let size = bytes.len();
+                (Some(bytes), size)
+            }
+            _ => (body, body_size),
+        }
+    }
+
+    /// Inspect a chunk of a prompts/get SSE response; returns the size of the chunk left to forward
+    fn inspect_prompt_chunk(&mut self, body_size: usize) -> usize {
+        let chunk = match self.get_http_response_body(0, body_size) {
+            Some(chunk) => chunk,
+            None => return body_size,
+        };
+        let out = match self.prompt_events.as_mut() {
+            Some(rewriter) => rewriter.feed(&chunk),
+            None => return body_size,
+        };
+        self.set_http_response_body(0, body_size, &out.bytes);
+        for finding in &out.findings {
+            self.record_prompt_result(finding);
+        }
+        out.bytes.len()
+    }
+
+    /// Inspect the complete JSON response of a prompts/get call
+    fn inspect_prompt_body(
+        &mut self,
+        body: Option<Vec<u8>>,
+        body_size: usize,
+    ) -> (Option<Vec<u8>>, usize) {
+        let config = self.config.clone();
+        let (message, templates) = match (&body, &config.prompt_templates) {
+            (Some(message), Some(templates)) => (message, templates),
+            _ => return (body, body_size),
+        };
+        match PromptTemplateInspector::new(templates).inspect_result_body(message) {
+            Some((finding, bytes)) => {
+                self.record_prompt_result(&finding);
+                self.set_http_response_body(0, body_size, &bytes);
+                let size = bytes.len();
+                (Some(bytes), size)
+            }
+            None => (body, body_size),
+        }
+    }
+
+    /// Metrics, verdict and audit for a prompts/get result replaced by an error
+    fn record_prompt_result(&mut self, finding: &TemplateFinding) {
+        let reason = finding.to_string();
+        warn!("[context_id={}] PROMPT TEMPLATE: {} (result)", self.context_id, reason);
+        with_metrics(|m| {
+            m.prompt_template_violation(finding.issue.as_str());
+            m.request_blocked("prompt_template");
+        });
+        self.verdict.action = VerdictAction::Blocked;
+        self.verdict.category = Some("prompt_template".to_string());
+        self.verdict.matched_pattern = Some(finding.issue.as_str().to_string());
+        self.publish_verdict();
+        self.audit(telemetry::audit_prompt_template(finding.issue.as_str(), &reason, "result"));
+    }
+
+    /// Metrics, verdict and audit for a tool result over a limit
+    fn record_result_limit(&mut self, violation: &ResultLimitViolation) {
+        let action = match (&self.config.tool_result_limits, violation) {
+            (_, ResultLimitViolation::Size { .. }) => ResultLimitAction::Reject,
+            (Some(limits), _) => limits.action,
+            (None, _) => return,
+        };
+        warn!(
+            "[context_id={}] TOOL RESULT LIMITED: {} ({})",
+            self.context_id,
+            violation,
+            action.as_str()
+        );
+        with_metrics(|m| {
+            m.tool_result_limited(violation.as_str());
+            if action == ResultLimitAction::Reject {
+                m.request_blocked("tool_result_limit");
+            }
+        });
+        if action == ResultLimitAction::Reject {
+            self.verdict.action = VerdictAction::Blocked;
+        }
+        self.verdict.category = Some("tool_result_limit".to_string());
+        self.verdict.matched_pattern = Some(violation.as_str().to_string());
+        self.publish_verdict();
+        self.audit(telemetry::audit_tool_result_limited(
+            violation.as_str(),
+            &violation.to_string(),
+            action.as_str(),
+        ));
+    }
+
+    /// Metrics, verdict and audit for injection patterns found in MCP results
+    fn record_mcp_result_matches(&mut self, matches: &[McpResultMatch]) {
+        let (first, action) = match (matches.first(), &self.config.mcp_result_scanning) {
+            (Some(first), Some(policy)) => (first, policy.action),
+            _ => return,
+        };
+        warn!(
+            "[context_id={}] INDIRECT INJECTION: {} match(es) in MCP result ({})",
+            self.context_id,
+            matches.len(),
+            action.as_str()
+        );
+        with_metrics(|m| {
+            for hit in matches {
+                m.pattern_hit(&hit.pattern);
+            }
+            if action == McpResultAction::Block {
+                m.request_blocked("indirect_injection");
+            }
+        });
+        if action == McpResultAction::Block {
+            self.verdict.action = VerdictAction::Blocked;
+        }
+        self.verdict.category = Some("indirect_injection".to_string());
+        self.verdict.matched_pattern = Some(first.pattern.clone());
+        self.publish_verdict();
+        self.audit(telemetry::audit_indirect_injection(
+            &first.pattern,
+            &first.path,
+            action.as_str(),
+        ));
+    }
+
+    /// Apply the tool-call policy to the tool calls of a complete JSON response
+    fn inspect_tool_calls(&self, body: &[u8]) -> Option<ToolCallViolation> {
+        let policy = self.config.tool_call_policy.as_ref()?;
+        let value: serde_json::Value = serde_json::from_slice(body).ok()?;
+        let inspector = ToolCallInspector::new(policy);
+        let mut calls = openai::tool_calls(&value);
+        calls.extend(anthropic::tool_calls(&value));
+        calls.iter().find_map(|call| inspector.inspect(call))
+    }
+
+    pub(super) fn handle_response_headers(
+        &mut self,
+        _num_headers: usize,
+        end_of_stream: bool,
+    ) -> Action {
+        if self.inspection_skipped() {
+            return Action::Continue;
+        }
+        // Add header to indicate request was inspected
+        self.set_http_response_header("x-ai-guard-inspected", Some("true"));
+        let versions = format!(
+            "config={}; patterns={}",
+            self.config.config_version(),
+            self.config.pattern_version()
+        );
+        self.set_http_response_header(HEADER_INSPECTED, Some(&versions));
+        if let Some(id) = self.request_id() {
+            self.set_http_response_header(GUARDRAIL_REQUEST_ID_HEADER, Some(id));
+        }
+        if self.session_warn {
+            self.set_http_response_header(SESSION_RESPONSE_HEADER, Some("warn"));
+        }
+        let severity_actions = self.config.severity_actions.as_ref();
+        if let (Some(severity), Some(actions)) = (self.severity_tag, severity_actions) {
+            self.set_http_response_header(&actions.header, Some(severity));
+        }
+        if let (true, Some(entropy)) = (self.entropy_tag, &self.config.entropy_detection) {
+            self.set_http_response_header(&entropy.header, Some("high_entropy"));
+        }
+        if let Some(value) = self.explanation_header() {
+            self.set_http_response_header(VERDICT_RESPONSE_HEADER, Some(&value));
+        }
+
+        let content_type =
+            self.get_http_response_header("content-type").unwrap_or_default().to_lowercase();
+        let is_sse = content_type.contains("text/event-stream");
+        let is_json = content_type.contains("json");
+        let encoding = self
+            .get_http_response_header("content-encoding")
+            .map(|v| ContentEncoding::parse(&v))
+            .unwrap_or(ContentEncoding::Identity);
+        if self.config.decompression.enabled {
+            self.response_encoding = encoding.clone();
+        }
+
+        if self.config.stream_watchdog.is_some() && is_sse {
+            self.watchdog = Some(StreamWatchdog::new(self.now_ns()));
+        }
+
+        // Notifications are passed through uncounted while enforcement is relaxed
+        let limits = self.config.notification_limits.as_ref();
+        let is_mcp = self.is_mcp || self.jsonrpc.is_jsonrpc();
+        if let Some(limits) = limits.filter(|_| self.control.enforces("notification_flood")) {
+            if is_mcp && is_sse {
+                let session = self
+                    .get_http_request_header("mcp-session-id")
+                    .or_else(|| self.session_id.clone())
+                    .unwrap_or_else(|| self.rate_limit_key());
+                self.notifications = Some((NotificationFilter::new(limits), session));
+                // Dropped notifications change the length
+                self.set_http_response_header("content-length", None);
+            }
+        }
+
+        let tool_policy = self.config.tool_call_policy.as_ref();
+        if (self.config.response_scanning.is_some() || tool_policy.is_some()) && is_sse {
+            // Tool calls alone: the scanner runs without text patterns
+            let default_scan = ResponseScanConfig::default();
+            let (patterns, scan) = match &self.config.response_scanning {
+                Some(scan) => (
+                    scan.blocked_patterns.as_ref().unwrap_or(&*self.config.blocked_patterns),
+                    scan,
+                ),
+                None => (&Vec::new(), &default_scan),
+            };
+            let mut scanner = ResponseScanner::new(patterns, scan, self.config.ring_buffer_size);
+            if let Some(policy) = tool_policy {
+                scanner = scanner.with_tool_calls(policy);
+            }
+            self.response_scanner = Some(scanner);
+        }
+
+        // Results are passed through unmodified while enforcement is relaxed
+        let mcp_policy = self.config.mcp_result_scanning.as_ref();
+        if let Some(policy) = mcp_policy.filter(|_| self.control.enforces("indirect_injection")) {
+            if is_mcp && self.jsonrpc.method().is_some_and(|m| policy.applies_to(m)) {
+                // Results are rewritten: the length changes
+                if is_sse {
+                    let mut rewriter = McpEventRewriter::new(policy);
+                    if let Some(urls) = self.config.url_policy.as_ref().filter(|u| u.scan_results) {
+                        rewriter = rewriter.with_url_policy(urls);
+                    }
+                    self.mcp_events = Some(rewriter);
+                    self.set_http_response_header("content-length", None);
+                } else if is_json && !end_of_stream {
+                    self.mcp_result_body = true;
+                    self.set_http_response_header("content-length", None);
+                }
+            }
+        }
+
+        // Limits are not applied while enforcement is relaxed
+        let limits = self.config.tool_result_limits.as_ref();
+        if let Some(limits) = limits.filter(|_| self.control.enforces("tool_result_limit")) {
+            if is_mcp && self.jsonrpc.method().is_some_and(|m| limits.applies_to(m)) {
+                // Over-limit results are replaced: the length changes
+                if is_sse {
+                    self.result_limit_events = Some(ResultLimitRewriter::new(limits));
+                    self.set_http_response_header("content-length", None);
+                } else if is_json && !end_of_stream {
+                    self.result_limit_body = true;
+                    self.set_http_response_header("content-length", None);
+                }
+            }
+        }
+
+        // Rendered prompts are not inspected while enforcement is relaxed
+        let templates = self.config.prompt_templates.as_ref().filter(|t| t.scan_results);
+        if let Some(templates) = templates.filter(|_| self.control.enforces("prompt_template")) {
+            if is_mcp && self.jsonrpc.method() == Some(methods::PROMPTS_GET) {
+                // Abused templates are replaced by an error: the length changes
+                if is_sse {
+                    self.prompt_events = Some(PromptResultRewriter::new(templates));
+                    self.set_http_response_header("content-length", None);
+                } else if is_json && !end_of_stream {
+                    self.prompt_result_body = true;
+                    self.set_http_response_header("content-length", None);
+                }
+            }
+        }
+
+        // In monitor mode PII is recorded but left in the response
+        if self.config.response_pii.is_some() || self.detokenizing_vault().is_some() {
+            // Redaction changes the length
+            if is_sse {
+                self.pii_events = self.response_pii_redactor();
+                self.set_http_response_header("content-length", None);
+            } else if is_json && !end_of_stream {
+                self.pii_body = true;
+                self.set_http_response_header("content-length", None);
+            }
+        }
+
+        // Some providers (e.g. Bedrock InvokeModel) report usage in headers only
+        let headers = self.get_http_response_headers();
+        self.header_usage = self.token_counter.extract_from_headers(&headers);
+        // Trailers-only gRPC response: the status comes with the headers
+        if end_of_stream {
+            self.record_grpc_status(&headers);
+        }
+
+        let tool_calls = self.config.tool_call_policy.is_some();
+        let result_bodies = self.mcp_result_body
+            || self.result_limit_body
+            || self.prompt_result_body
+            || self.pii_body;
+        let usage = self.config.usage_response_headers;
+        let hold = (usage || tool_calls || result_bodies) && is_json && !end_of_stream;
+
+        // Bodies inspected in an encoding are decoded, or blocked if they cannot be
+        let inspected = self.inspects_stream() || result_bodies || (hold && tool_calls);
+        if inspected && encoding != ContentEncoding::Identity && !self.decode_response(encoding) {
+            return Action::Pause;
+        }
+        if hold {
+            // Usage, tool calls and MCP results live in the JSON body: hold headers
+            // until it completes. Streaming (SSE) responses are never held.
+            self.hold_response_headers = true;
+            return Action::Pause;
+        }
+
+        if let Some(usage) = &self.header_usage {
+            self.set_usage_headers(usage);
+        }
+
+        Action::Continue
+    }
+
+    pub(super) fn handle_response_body(&mut self, body_size: usize, end_of_stream: bool) -> Action {
+        self.response_body_size = body_size;
+        if self.inspection_skipped() {
+            return Action::Continue;
+        }
+        // A held body is decoded whole once it is complete
+        let decoded = self.response_decoder.is_some() && !self.stream_terminated;
+        let body_size = match decoded && (end_of_stream || !self.hold_response_headers) {
+            true => match self.decode_response_chunk(body_size) {
+                Some(size) => size,
+                None if self.hold_response_headers => return Action::Pause,
+                None => return Action::Continue,
+            },
+            false => body_size,
+        };
+        if (self.watchdog.is_some() || self.stream_terminated)
+            && !self.check_stream_watchdog(body_size)
+        {
+            return Action::Continue;
+        }
+        let body_size = if self.notifications.is_some() {
+            self.limit_notifications(body_size)
+        } else {
+            body_size
+        };
+        if self.stream_terminated {
+            return Action::Continue;
+        }
+        if self.response_scanner.is_some() {
+            self.scan_response_chunk(body_size);
+        }
+        let body_size = if self.result_limit_events.is_some() {
+            self.limit_result_chunk(body_size)
+        } else {
+            body_size
+        };
+        let body_size = if self.prompt_events.is_some() {
+            self.inspect_prompt_chunk(body_size)
+        } else {
+            body_size
+        };
+        let body_size = if self.pii_events.is_some() {
+            self.redact_response_chunk(body_size, end_of_stream)
+        } else {
+            body_size
+        };
+        if self.stream_terminated {
+            return Action::Continue;
+        }
+        if self.mcp_events.is_some() {
+            self.rewrite_mcp_chunk(body_size);
+        }
+
+        // Keep buffering until the full body is available
+        if self.hold_response_headers && !end_of_stream {
+            return Action::Pause;
+        }
+
+        // Extract token usage from response body (for cost attribution)
+        if end_of_stream {
+            let body = self
+                .get_http_response_body(0, body_size)
+                .and_then(|body| self.decode_response_body(body));
+
+            // Headers are still held: a tool-call violation can replace the response
+            if self.hold_response_headers {
+                let violation = body.as_deref().and_then(|b| self.inspect_tool_calls(b));
+                let reason = violation
+                    .and_then(|v| self.record_response_violation(ResponseViolation::ToolCall(v)));
+                if let Some(reason) = reason {
+                    self.send_block_response(&reason);
+                    return Action::Pause;
+                }
+            }
+            let (body, body_size) = if self.pii_body {
+                match self.redact_response_body(body, body_size) {
+                    Some(redacted) => redacted,
+                    None => return Action::Pause,
+                }
+            } else {
+                (body, body_size)
+            };
+            let (body, body_size) = if self.result_limit_body {
+                self.limit_result_body(body, body_size)
+            } else {
+                (body, body_size)
+            };
+            let (body, body_size) = if self.prompt_result_body {
+                self.inspect_prompt_body(body, body_size)
+            } else {
+                (body, body_size)
+            };
+            if self.mcp_result_body {
+                if let Some(body) = &body {
+                    self.rewrite_mcp_result(body, body_size);
+                }
+            }
+
+            let body_usage = body.and_then(|body| self.token_counter.extract_from_body(&body));
+
+            // Fall back to header-reported usage (e.g. Bedrock InvokeModel)
+            if let Some(mut usage) = body_usage.or_else(|| self.header_usage.take()) {
+                if usage.model.is_none() {
+                    usage.model = self.request_model().map(str::to_string);
+                }
+                if usage.estimated_cost_usd.is_none() {
+                    if let Some(model) = &usage.model {
+                        usage.estimated_cost_usd = self.token_counter.calculate_cost(model, &usage);
+                    }
+                }
+
+                info!(
+                    "[context_id={}] Token usage: prompt={}, completion={}, total={}",
+                    self.context_id,
+                    usage.prompt_tokens,
+                    usage.completion_tokens,
+                    usage.total_tokens
+                );
+
+                if let Some(cost) = usage.estimated_cost_usd {
+                    info!(
+                        "[context_id={}] Estimated cost: ${:.4}",
+                        self.context_id, cost
+                    );
+                }
+
+                // Add usage headers for observability
+                self.set_http_response_header(
+                    "x-ai-guard-tokens-total",
+                    Some(&usage.total_tokens.to_string()),
+                );
+                self.set_usage_headers(&usage);
+
+                let agent = self.rate_limit_key();
+                let now_secs = self.now_ns() / 1_000_000_000;
+                self.with_rate_limiter(|limiter| {
+                    limiter.record_tokens(&agent, usage.total_tokens, now_secs)
+                });
+
+                self.verdict.prompt_tokens = Some(usage.prompt_tokens);
+                self.verdict.completion_tokens = Some(usage.completion_tokens);
+                self.verdict.cost_usd = usage.estimated_cost_usd;
+                self.publish_verdict();
+            }
+        }
+
+        Action::Continue
+    }
+
+    pub(super) fn handle_response_trailers(&mut self, _num_trailers: usize) -> Action {
+        if self.inspection_skipped() {
+            return Action::Continue;
+        }
+        self.record_grpc_status(&self.get_http_response_trailers());
+        if !self.hold_response_headers {
+            return Action::Continue;
+        }
+        // Held headers wait for the whole body, which ends here
+        self.handle_response_body(self.response_body_size, true)
+    }
+}
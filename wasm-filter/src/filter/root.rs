@@ -0,0 +1,397 @@
+//! Root context
+//!
+//! Loads and commits the plugin configuration, and runs the periodic tasks
+//! of the worker on its tick: audit shipping, span export, pattern hit
+//! summaries and threat feed refreshes.
+
+use super::*;
+
+/// Maximum root tick period while audit shipping is enabled
+const AUDIT_TICK_MAX: Duration = Duration::from_secs(1);
+
+/// Root tick period while spans are exported to a collector
+const SPAN_TICK: Duration = Duration::from_secs(1);
+
+/// Maximum root tick period while a threat feed is configured, so workers
+/// pick up a feed another worker fetched soon after it lands
+const FEED_TICK_MAX: Duration = Duration::from_secs(30);
+
+/// Parse and validate a plugin configuration with its tenant and route policies
+///
+/// Logs the problems and returns None when any part is rejected.
+fn load_configuration(
+    config_bytes: &[u8],
+) -> Option<(FilterConfig, TenantPolicies, RoutePolicies)> {
+    let mut config = match FilterConfig::from_bytes_validated(config_bytes) {
+        Ok(config) => config,
+        Err(ConfigError::Invalid(diagnostics)) => {
+            with_metrics(|m| m.config_rejected("invalid"));
+            for diagnostic in &diagnostics {
+                error!("AI-Guard: Invalid configuration: {}", diagnostic);
+            }
+            error!("AI-Guard: Rejecting configuration ({} problems)", diagnostics.len());
+            return None;
+        }
+        Err(e) => {
+            error!("AI-Guard: Rejecting configuration: {}", e);
+            with_metrics(|m| m.config_rejected(e.as_str()));
+            return None;
+        }
+    };
+    config.expand_builtin_patterns();
+    let policies = TenantPolicies::from_bytes(config_bytes, &config)
+        .and_then(|tenants| Ok((tenants, RoutePolicies::from_bytes(config_bytes, &config)?)));
+    let (tenants, routes) = match policies {
+        Ok(policies) => policies,
+        Err(e) => {
+            error!("AI-Guard: Rejecting configuration: {}", e);
+            with_metrics(|m| m.config_rejected(e.as_str()));
+            return None;
+        }
+    };
+
+    info!(
+        "AI-Guard: Loaded configuration with {} blocked patterns",
+        config.blocked_patterns.len()
+    );
+    if !config.model_pricing.is_empty() {
+        info!("AI-Guard: Loaded {} model pricing rules", config.model_pricing.len());
+    }
+    if tenants.is_enabled() {
+        info!("AI-Guard: Loaded {} tenant policies", tenants.len());
+    }
+    if routes.is_enabled() {
+        info!("AI-Guard: Loaded {} route policies", routes.len());
+    }
+    Some((config, tenants, routes))
+}
+
+/// Root tick period needed by the configured periodic tasks
+fn tick_period(config: &FilterConfig) -> Option<Duration> {
+    let audit = config
+        .audit_sink
+        .as_ref()
+        .map(|sink| Duration::from_millis(sink.flush_interval_ms.max(1)).min(AUDIT_TICK_MAX));
+    let stats = Some(config.pattern_stats_interval_secs)
+        .filter(|&secs| secs > 0)
+        .map(Duration::from_secs);
+    let feed = config
+        .threat_feed
+        .as_ref()
+        .map(|feed| Duration::from_secs(feed.refresh_interval_secs).min(FEED_TICK_MAX));
+    let spans = config
+        .tracing
+        .as_ref()
+        .and_then(|tracing| tracing.collector_cluster.as_ref())
+        .map(|_| SPAN_TICK);
+
+    [audit, stats, feed, spans].into_iter().flatten().min()
+}
+
+impl AiGuardRootContext {
+    pub(super) fn new() -> Self {
+        Self {
+            config: FilterConfig::default(),
+            config_digest: None,
+            feed_call: None,
+        }
+    }
+}
+
+impl AiGuardRootContext {
+    /// Current host time in Unix milliseconds
+    fn now_ms(&self) -> u64 {
+        self.get_current_time()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0)
+    }
+
+    /// Emit the blocked-pattern summary if this worker wins the interval
+    fn emit_pattern_summary(&self) {
+        let interval = self.config.pattern_stats_interval_secs;
+        if interval == 0 {
+            return;
+        }
+        let now_ms = self.now_ms();
+        if !pattern_stats::claim_summary(&HostSharedStore, now_ms / 1000, interval) {
+            return;
+        }
+
+        let hits = pattern_stats::load(&HostSharedStore);
+        let summary = pattern_stats::summarize(
+            &hits,
+            &self.config.blocked_patterns.labels(),
+            self.config.pattern_stats_top_k,
+        );
+        let mut event = summary.to_audit_event();
+        event.timestamp_secs = Some(now_ms / 1000);
+        publish_audit(event, &self.config, now_ms);
+    }
+
+    /// Pick up a feed fetched by any worker, and fetch it if this worker wins the interval
+    fn refresh_threat_feed(&mut self) {
+        let feed = match &self.config.threat_feed {
+            Some(feed) => feed,
+            None => return,
+        };
+        THREAT_FEED.with(|f| f.borrow_mut().refresh(&HostSharedStore));
+        let now_secs = self.now_ms() / 1000;
+        if self.feed_call.is_some()
+            || !threat_intel::claim_fetch(&HostSharedStore, now_secs, feed.refresh_interval_secs)
+        {
+            return;
+        }
+        let authority = feed.authority.as_deref().unwrap_or(&feed.cluster);
+        let mut headers =
+            vec![(":method", "GET"), (":path", feed.path.as_str()), (":authority", authority)];
+        if let Some(auth) = &feed.authorization {
+            headers.push(("authorization", auth.as_str()));
+        }
+        let timeout = Duration::from_millis(feed.timeout_ms);
+        match self.dispatch_http_call(&feed.cluster, headers, None, vec![], timeout) {
+            Ok(token) => self.feed_call = Some(token),
+            Err(_) => {
+                warn!("AI-Guard: Failed to dispatch threat feed fetch to {}", feed.cluster);
+                with_metrics(|m| m.threat_feed_fetch("error"));
+            }
+        }
+    }
+
+    /// Validate a fetched threat feed and share it with every worker
+    fn store_threat_feed(&mut self, status: Option<u16>, body_size: usize) {
+        let body = self.get_http_call_response_body(0, body_size).unwrap_or_default();
+        let parsed = match status {
+            Some(200) => ThreatFeed::parse(&body),
+            _ => Err(format!("status {:?}", status)),
+        };
+        let published = parsed.and_then(|feed| {
+            threat_intel::publish(&HostSharedStore, &body, self.now_ms() / 1000)
+                .map(|()| feed.len())
+                .map_err(|e| e.to_string())
+        });
+        match published {
+            Ok(count) => {
+                info!("AI-Guard: Fetched threat feed with {} IoCs", count);
+                with_metrics(|m| m.threat_feed_fetch("ok"));
+                THREAT_FEED.with(|f| f.borrow_mut().refresh(&HostSharedStore));
+            }
+            // The previous feed stays in force
+            Err(e) => {
+                warn!("AI-Guard: Threat feed rejected: {}", e);
+                with_metrics(|m| m.threat_feed_fetch("error"));
+            }
+        }
+    }
+
+    /// Dispatch every audit batch that is due
+    fn flush_audit(&self) {
+        let sink = match &self.config.audit_sink {
+            Some(sink) => sink,
+            None => return,
+        };
+        let now_ms = self.now_ms();
+
+        while let Some(batch) =
+            AUDIT_SHIPPER.with(|s| s.borrow_mut().as_mut().and_then(|sh| sh.take_batch(now_ms)))
+        {
+            let format = self.config.audit_format;
+            let body = batch.body(format);
+            let authority = sink.authority.as_deref().unwrap_or(&sink.cluster);
+            let mut headers = vec![
+                (":method", "POST"),
+                (":path", sink.path.as_str()),
+                (":authority", authority),
+                ("content-type", format.content_type()),
+            ];
+            if let Some(auth) = &sink.authorization {
+                headers.push(("authorization", auth.as_str()));
+            }
+
+            let result = self.dispatch_http_call(
+                &sink.cluster,
+                headers,
+                Some(body.as_bytes()),
+                vec![],
+                Duration::from_millis(sink.timeout_ms),
+            );
+            let outcome = AUDIT_SHIPPER.with(|s| {
+                let mut shipper = s.borrow_mut();
+                let shipper = shipper.as_mut()?;
+                match result {
+                    Ok(token) => {
+                        shipper.sent(token, batch);
+                        None
+                    }
+                    Err(_) => Some(shipper.send_failed(batch)),
+                }
+            });
+            if let Some(outcome) = outcome {
+                // Host refused the callout; stop and retry on the next tick
+                warn!("AI-Guard: Failed to dispatch audit batch to {}", sink.cluster);
+                record_ship_outcome(outcome);
+                break;
+            }
+        }
+    }
+
+    /// Export the spans requests queued since the last tick
+    fn flush_spans(&self) {
+        let tracing = match &self.config.tracing {
+            Some(tracing) => tracing,
+            None => return,
+        };
+        let cluster = match &tracing.collector_cluster {
+            Some(cluster) => cluster,
+            None => return,
+        };
+        let authority = tracing.collector_authority.as_deref().unwrap_or(cluster);
+        while let Some(body) =
+            SPAN_QUEUE.with(|q| q.borrow_mut().take_export(&tracing.service_name))
+        {
+            let result = self.dispatch_http_call(
+                cluster,
+                vec![
+                    (":method", "POST"),
+                    (":path", &tracing.collector_path),
+                    (":authority", authority),
+                    ("content-type", "application/json"),
+                ],
+                Some(body.as_bytes()),
+                vec![],
+                Duration::from_millis(tracing.collector_timeout_ms),
+            );
+            if let Err(e) = result {
+                // Spans are best effort: the batch is not retried
+                warn!("AI-Guard: Failed to export spans to {}: {:?}", cluster, e);
+                break;
+            }
+        }
+    }
+}
+
+impl Context for AiGuardRootContext {
+    fn on_http_call_response(
+        &mut self,
+        token_id: u32,
+        _num_headers: usize,
+        body_size: usize,
+        _num_trailers: usize,
+    ) {
+        let status = self
+            .get_http_call_response_header(":status")
+            .and_then(|s| s.parse::<u16>().ok());
+        if self.feed_call == Some(token_id) {
+            self.feed_call = None;
+            self.store_threat_feed(status, body_size);
+            return;
+        }
+        let outcome = AUDIT_SHIPPER.with(|s| {
+            s.borrow_mut()
+                .as_mut()
+                .map(|shipper| shipper.on_response(token_id, status))
+                .unwrap_or(ShipOutcome::Unknown)
+        });
+        record_ship_outcome(outcome);
+    }
+}
+
+impl RootContext for AiGuardRootContext {
+    fn on_configure(&mut self, _plugin_configuration_size: usize) -> bool {
+        // CRITICAL: Load configuration from Envoy plugin configuration, NOT external files
+        if let Some(config_bytes) = self.get_plugin_configuration() {
+            // Bad rollouts fail loudly: Envoy rejects the config update, and
+            // nothing is committed until every part of it has parsed
+            let (config, tenants, routes) = match load_configuration(&config_bytes) {
+                Some(loaded) => loaded,
+                None => return false,
+            };
+            TENANTS.with(|t| *t.borrow_mut() = Rc::new(tenants));
+            ROUTES.with(|r| *r.borrow_mut() = Rc::new(routes));
+            self.config = config;
+            self.config_digest = Some(to_hex(&sha256(&config_bytes)));
+        } else {
+            info!("AI-Guard: No configuration provided, using defaults");
+        }
+
+        // Store config in thread-local for HTTP contexts to access
+        CONFIG.with(|c| {
+            let mut snapshot = ConfigSnapshot::new(self.config.clone());
+            if let Some(digest) = &self.config_digest {
+                snapshot = snapshot.with_digest(digest.clone());
+            }
+            *c.borrow_mut() = Rc::new(snapshot);
+        });
+        // Contexts already running keep the configuration they started with
+        CONFIG_GENERATION.with(|g| g.set(g.get() + 1));
+        record_stale_contexts();
+        // Limits may have changed: start every tenant's window afresh
+        RATE_LIMITERS.with(|r| r.borrow_mut().clear());
+        // Cached outcomes were computed under the old patterns
+        VERDICT_CACHE.with(|c| c.borrow_mut().clear());
+        // Dimension caps count values seen under this configuration
+        with_metrics(|m| m.reset_dimensions());
+        // The overhead window measured the previous pattern set
+        OVERHEAD.with(|o| *o.borrow_mut() = OverheadTracker::new());
+        // A config push replaces flags set through the admin endpoint
+        if let Some(flags) = self.config.control.as_ref().and_then(|c| c.flags.as_ref()) {
+            match flags.publish(&HostSharedStore) {
+                Ok(()) => info!("AI-Guard: Published runtime control flags"),
+                Err(e) => error!("AI-Guard: Runtime control flags not published: {}", e),
+            }
+        }
+
+        // Audit shipping: a fresh shipper per configuration
+        let shipper = self.config.audit_sink.as_ref().map(AuditShipper::new);
+        AUDIT_SHIPPER.with(|s| *s.borrow_mut() = shipper);
+        if let Some(sink) = &self.config.audit_sink {
+            info!("AI-Guard: Shipping audit events to cluster '{}'", sink.cluster);
+        }
+        if let Some(tick) = tick_period(&self.config) {
+            self.set_tick_period(tick);
+        }
+
+        info!(
+            "AI-Guard Filter initialized - {} patterns, {}KB ring buffer",
+            self.config.blocked_patterns.len(),
+            self.config.ring_buffer_size / 1024
+        );
+
+        true
+    }
+
+    fn on_tick(&mut self) {
+        self.emit_pattern_summary();
+        self.flush_audit();
+        self.flush_spans();
+        self.refresh_threat_feed();
+    }
+
+    fn create_http_context(&self, context_id: u32) -> Option<Box<dyn HttpContext>> {
+        Some(Box::new(AiGuardHttpContext::new(context_id)))
+    }
+
+    fn get_type(&self) -> Option<ContextType> {
+        Some(ContextType::HttpContext)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tick_period() {
+        let mut config = FilterConfig::default();
+        assert_eq!(tick_period(&config), Some(Duration::from_secs(300)));
+
+        config.pattern_stats_interval_secs = 0;
+        assert_eq!(tick_period(&config), None);
+
+        config.tracing = serde_json::from_str(r#"{"collector_cluster": "otel"}"#).ok();
+        assert_eq!(tick_period(&config), Some(SPAN_TICK));
+
+        config.audit_sink = serde_json::from_str(r#"{"cluster": "siem"}"#).ok();
+        assert_eq!(tick_period(&config), Some(AUDIT_TICK_MAX));
+    }
+}
@@ -0,0 +1,229 @@
+//! Human-in-the-Loop Tool Approval
+//!
+//! Some MCP tools act on the world (`execute_sql`, `send_email`), and an
+//! agent steered by injected instructions calls them just as readily as a
+//! benign one. For configured high-risk tools, a `tools/call` request is
+//! held while an approval callout goes to a review webhook; the request is
+//! forwarded if a reviewer approves it within the timeout and rejected
+//! otherwise.
+//!
+//! Webhook protocol: the filter POSTs an [`ApprovalRequest`] as JSON and
+//! expects a 2xx answer `{"approved": bool, "reason": "...", "approver": "..."}`.
+//! A timeout, a non-2xx status or an unreadable answer counts as the service
+//! being unavailable, which rejects the call unless `fail_open` is set.
+
+use crate::protocols::mcp::method_policy::glob_match;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Tool approval configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApprovalConfig {
+    /// Tools requiring approval (glob patterns)
+    pub tools: Vec<String>,
+    /// Envoy cluster of the approval webhook
+    pub cluster: String,
+    /// Webhook path
+    #[serde(default = "default_approval_path")]
+    pub path: String,
+    /// `:authority` sent to the webhook (defaults to the cluster name)
+    #[serde(default)]
+    pub authority: Option<String>,
+    /// `authorization` header value sent with each callout
+    #[serde(default)]
+    pub authorization: Option<String>,
+    /// Time to wait for a decision, in milliseconds
+    #[serde(default = "default_approval_timeout_ms")]
+    pub timeout_ms: u64,
+    /// Forward the call when the webhook is unavailable
+    #[serde(default)]
+    pub fail_open: bool,
+}
+
+fn default_approval_path() -> String {
+    "/approve".to_string()
+}
+
+fn default_approval_timeout_ms() -> u64 {
+    30_000
+}
+
+impl ApprovalConfig {
+    /// Validate the configuration, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        if self.tools.is_empty() || self.tools.iter().any(|t| t.is_empty()) {
+            diagnostics.push("tool_approval.tools: must list at least one tool".to_string());
+        }
+        if self.cluster.is_empty() {
+            diagnostics.push("tool_approval.cluster: must not be empty".to_string());
+        }
+        if !self.path.starts_with('/') {
+            diagnostics.push("tool_approval.path: must start with '/'".to_string());
+        }
+        if self.timeout_ms == 0 {
+            diagnostics.push("tool_approval.timeout_ms: must be greater than 0".to_string());
+        }
+        diagnostics
+    }
+
+    /// Whether calls to a tool must be approved
+    pub fn requires_approval(&self, tool: &str) -> bool {
+        self.tools.iter().any(|p| glob_match(p, tool))
+    }
+
+    /// Final decision from the webhook's answer (`status` None = no answer)
+    pub fn decide(&self, status: Option<u16>, body: Option<&[u8]>) -> ApprovalDecision {
+        let answer = match status {
+            Some(status) if (200..300).contains(&status) => body
+                .and_then(|b| serde_json::from_slice::<ApprovalAnswer>(b).ok())
+                .ok_or_else(|| "unreadable answer".to_string()),
+            Some(status) => Err(format!("status {}", status)),
+            None => Err("no answer".to_string()),
+        };
+        match answer {
+            Ok(ApprovalAnswer { approved: true, approver, .. }) => ApprovalDecision::Approved(
+                match approver {
+                    Some(approver) => format!("approved by {}", approver),
+                    None => "approved".to_string(),
+                },
+            ),
+            Ok(ApprovalAnswer { approved: false, reason, .. }) => {
+                ApprovalDecision::Rejected(reason.unwrap_or_else(|| "rejected by reviewer".into()))
+            }
+            Err(problem) if self.fail_open => ApprovalDecision::Approved(format!(
+                "approval service unavailable ({}), failing open",
+                problem
+            )),
+            Err(problem) => {
+                ApprovalDecision::Rejected(format!("approval service unavailable ({})", problem))
+            }
+        }
+    }
+}
+
+/// Answer of the approval webhook
+#[derive(Debug, Deserialize)]
+struct ApprovalAnswer {
+    approved: bool,
+    #[serde(default)]
+    reason: Option<String>,
+    #[serde(default)]
+    approver: Option<String>,
+}
+
+/// Outcome of an approval
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApprovalDecision {
+    /// Forward the call (detail for the audit trail)
+    Approved(String),
+    /// Reject the call (reason)
+    Rejected(String),
+}
+
+/// Body of the approval callout
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ApprovalRequest {
+    /// Tool being called
+    pub tool: String,
+    /// Arguments of the call
+    pub arguments: Value,
+    /// JSON-RPC id of the call
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jsonrpc_id: Option<Value>,
+    /// Request ID (correlates with audit events and access logs)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// Calling agent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agent_id: Option<String>,
+    /// Resolved tenant
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    /// Correlated session
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+}
+
+impl ApprovalRequest {
+    /// Read the tool name and arguments from the `params` of a `tools/call` request
+    pub fn from_params(params: &Value, jsonrpc_id: Option<&Value>) -> Option<Self> {
+        Some(Self {
+            tool: params.get("name")?.as_str()?.to_string(),
+            arguments: params.get("arguments").cloned().unwrap_or(Value::Null),
+            jsonrpc_id: jsonrpc_id.cloned(),
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ApprovalConfig {
+        serde_json::from_str(r#"{"tools": ["execute_sql", "send_*"], "cluster": "review"}"#)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_requires_approval() {
+        let config = config();
+        assert!(config.requires_approval("execute_sql"));
+        assert!(config.requires_approval("send_email"));
+        assert!(!config.requires_approval("read_file"));
+        assert_eq!(config.path, "/approve");
+        assert!(config.validate().is_empty());
+    }
+
+    #[test]
+    fn test_decide() {
+        let config = config();
+        let approved = br#"{"approved": true, "approver": "alice"}"#;
+        assert_eq!(
+            config.decide(Some(200), Some(approved)),
+            ApprovalDecision::Approved("approved by alice".to_string())
+        );
+        let rejected = br#"{"approved": false, "reason": "drops a table"}"#;
+        assert_eq!(
+            config.decide(Some(200), Some(rejected)),
+            ApprovalDecision::Rejected("drops a table".to_string())
+        );
+        assert_eq!(
+            config.decide(None, None),
+            ApprovalDecision::Rejected("approval service unavailable (no answer)".to_string())
+        );
+        assert_eq!(
+            config.decide(Some(503), None),
+            ApprovalDecision::Rejected("approval service unavailable (status 503)".to_string())
+        );
+    }
+
+    #[test]
+    fn test_fail_open() {
+        let config = ApprovalConfig { fail_open: true, ..config() };
+        assert!(matches!(config.decide(None, None), ApprovalDecision::Approved(_)));
+        // An explicit rejection still rejects
+        let rejected = br#"{"approved": false}"#;
+        assert_eq!(
+            config.decide(Some(200), Some(rejected)),
+            ApprovalDecision::Rejected("rejected by reviewer".to_string())
+        );
+    }
+
+    #[test]
+    fn test_request_from_params() {
+        let params = serde_json::json!({
+            "name": "execute_sql",
+            "arguments": {"query": "DELETE FROM users"}
+        });
+        let request = ApprovalRequest::from_params(&params, Some(&Value::from(7))).unwrap();
+        assert_eq!(request.tool, "execute_sql");
+        assert_eq!(request.arguments["query"], "DELETE FROM users");
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["jsonrpc_id"], 7);
+        assert!(json.get("agent_id").is_none());
+        assert!(ApprovalRequest::from_params(&serde_json::json!({}), None).is_none());
+    }
+}
@@ -0,0 +1,384 @@
+//! Binary Body Policy
+//!
+//! Bodies with a non-text content type are not scanned. Rather than letting
+//! all of them through, the policy classifies each body by its magic bytes
+//! (falling back to the declared content type, so a renamed executable is
+//! still an executable) and then:
+//!
+//! - blocks denied kinds (executables by default), or kinds outside `allow`
+//! - caps body size per kind
+//! - optionally blocks unrecognized binary content on text-only AI routes
+//!   (chat APIs and `text_only_paths`)
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// Body bytes needed to recognize every magic number
+pub const SNIFF_LEN: usize = 16;
+
+/// Kind of binary content
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BinaryKind {
+    Image,
+    Audio,
+    Video,
+    Pdf,
+    Archive,
+    Executable,
+    Protobuf,
+    /// Not recognized
+    Unknown,
+}
+
+impl BinaryKind {
+    /// Stable snake_case name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BinaryKind::Image => "image",
+            BinaryKind::Audio => "audio",
+            BinaryKind::Video => "video",
+            BinaryKind::Pdf => "pdf",
+            BinaryKind::Archive => "archive",
+            BinaryKind::Executable => "executable",
+            BinaryKind::Protobuf => "protobuf",
+            BinaryKind::Unknown => "unknown",
+        }
+    }
+
+    /// Classify a body: magic bytes first, then the content type
+    pub fn detect(content_type: &str, prefix: &[u8]) -> Self {
+        Self::sniff(prefix).unwrap_or_else(|| Self::from_content_type(content_type))
+    }
+
+    /// Kind announced by the magic bytes at the start of a body
+    pub fn sniff(prefix: &[u8]) -> Option<Self> {
+        let kind = match prefix {
+            [0x89, b'P', b'N', b'G', ..]
+            | [0xFF, 0xD8, 0xFF, ..]
+            | [b'G', b'I', b'F', b'8', ..] => BinaryKind::Image,
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => BinaryKind::Image,
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..]
+            | [b'I', b'D', b'3', ..]
+            | [0xFF, 0xFB | 0xF3 | 0xF2, ..]
+            | [b'O', b'g', b'g', b'S', ..]
+            | [b'f', b'L', b'a', b'C', ..] => BinaryKind::Audio,
+            [_, _, _, _, b'f', b't', b'y', b'p', ..] | [0x1A, 0x45, 0xDF, 0xA3, ..] => {
+                BinaryKind::Video
+            }
+            [b'%', b'P', b'D', b'F', b'-', ..] => BinaryKind::Pdf,
+            [b'P', b'K', 0x03, 0x04, ..]
+            | [0x1F, 0x8B, ..]
+            | [b'7', b'z', 0xBC, 0xAF, 0x27, 0x1C, ..]
+            | [b'R', b'a', b'r', b'!', ..] => BinaryKind::Archive,
+            [0x7F, b'E', b'L', b'F', ..]
+            | [b'M', b'Z', ..]
+            | [0xFE, 0xED, 0xFA, 0xCE | 0xCF, ..]
+            | [0xCE | 0xCF, 0xFA, 0xED, 0xFE, ..]
+            | [0xCA, 0xFE, 0xBA, 0xBE, ..]
+            | [0x00, b'a', b's', b'm', ..] => BinaryKind::Executable,
+            _ => return None,
+        };
+        Some(kind)
+    }
+
+    /// Kind declared by a `Content-Type` header value
+    pub fn from_content_type(content_type: &str) -> Self {
+        let media = content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+        match media.split_once('/') {
+            Some(("image", _)) => BinaryKind::Image,
+            Some(("audio", _)) => BinaryKind::Audio,
+            Some(("video", _)) => BinaryKind::Video,
+            _ => match media.as_str() {
+                "application/pdf" => BinaryKind::Pdf,
+                "application/zip" | "application/gzip" | "application/x-tar"
+                | "application/x-7z-compressed" | "application/vnd.rar" => BinaryKind::Archive,
+                "application/x-msdownload" | "application/x-executable" | "application/wasm" => {
+                    BinaryKind::Executable
+                }
+                "application/x-protobuf" | "application/protobuf"
+                | "application/vnd.google.protobuf" => BinaryKind::Protobuf,
+                m if m.starts_with("application/grpc") => BinaryKind::Protobuf,
+                _ => BinaryKind::Unknown,
+            },
+        }
+    }
+}
+
+/// Binary body policy configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BinaryPolicy {
+    /// Kinds accepted (empty: every kind not denied)
+    pub allow: Vec<BinaryKind>,
+    /// Kinds that block the request
+    pub deny: Vec<BinaryKind>,
+    /// Largest body of a kind, in bytes
+    pub max_size: BTreeMap<BinaryKind, usize>,
+    /// Largest body of a kind without its own `max_size`
+    pub default_max_size: Option<usize>,
+    /// Block unrecognized binary content on text-only routes
+    pub block_unknown: bool,
+    /// Path prefixes that only carry text, besides the chat APIs
+    pub text_only_paths: Vec<String>,
+}
+
+impl Default for BinaryPolicy {
+    fn default() -> Self {
+        Self {
+            allow: Vec::new(),
+            deny: vec![BinaryKind::Executable],
+            max_size: BTreeMap::new(),
+            default_max_size: None,
+            block_unknown: false,
+            text_only_paths: Vec::new(),
+        }
+    }
+}
+
+impl BinaryPolicy {
+    /// Validate the policy, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        for kind in self.allow.iter().filter(|k| self.deny.contains(k)) {
+            diagnostics.push(format!(
+                "binary_policy: '{}' is both allowed and denied",
+                kind.as_str()
+            ));
+        }
+        for (kind, _) in self.max_size.iter().filter(|(_, &size)| size == 0) {
+            diagnostics.push(format!(
+                "binary_policy.max_size.{}: must be greater than 0",
+                kind.as_str()
+            ));
+        }
+        if self.default_max_size == Some(0) {
+            diagnostics.push("binary_policy.default_max_size: must be greater than 0".to_string());
+        }
+        for path in self.text_only_paths.iter().filter(|p| !p.starts_with('/')) {
+            diagnostics.push(format!(
+                "binary_policy.text_only_paths: '{}' must start with '/'",
+                path
+            ));
+        }
+        diagnostics
+    }
+
+    /// Whether a path is listed as text-only
+    pub fn is_text_only(&self, path: &str) -> bool {
+        self.text_only_paths.iter().any(|prefix| path.starts_with(prefix.as_str()))
+    }
+
+    /// Size cap of a kind
+    fn max_size_of(&self, kind: BinaryKind) -> Option<usize> {
+        self.max_size.get(&kind).copied().or(self.default_max_size)
+    }
+}
+
+/// Binary policy violations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BinaryViolation {
+    /// Kind denied, or not allowed
+    Denied(BinaryKind),
+    /// Unrecognized binary content on a text-only route
+    Unknown,
+    /// Body exceeded the kind's size cap
+    TooLarge(BinaryKind, usize),
+}
+
+impl std::fmt::Display for BinaryViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BinaryViolation::Denied(kind) => {
+                write!(f, "Binary content not allowed: {}", kind.as_str())
+            }
+            BinaryViolation::Unknown => write!(f, "Unrecognized binary content on text-only route"),
+            BinaryViolation::TooLarge(kind, max) => {
+                write!(f, "Binary body exceeds {} bytes: {}", max, kind.as_str())
+            }
+        }
+    }
+}
+
+/// Applies the binary policy to one request body
+pub struct BinaryInspector {
+    policy: BinaryPolicy,
+    content_type: String,
+    text_route: bool,
+    kind: Option<BinaryKind>,
+    /// First bytes of the body, until there are enough to classify it
+    head: Vec<u8>,
+    size: usize,
+}
+
+impl BinaryInspector {
+    /// Create an inspector for a body of a content type on a (text-only or not) route
+    pub fn new(policy: &BinaryPolicy, content_type: &str, text_route: bool) -> Self {
+        Self {
+            policy: policy.clone(),
+            content_type: content_type.to_string(),
+            text_route,
+            kind: None,
+            head: Vec::new(),
+            size: 0,
+        }
+    }
+
+    /// Kind of the body, once classified
+    pub fn kind(&self) -> Option<BinaryKind> {
+        self.kind
+    }
+
+    /// Bytes from the start of the next chunk still needed to classify the body
+    pub fn wants(&self) -> usize {
+        match self.kind {
+            Some(_) => 0,
+            None => SNIFF_LEN - self.head.len(),
+        }
+    }
+
+    /// Take in a chunk of `len` bytes, given its first `wants()` bytes.
+    /// The body is classified once `SNIFF_LEN` bytes are in or it ends.
+    pub fn chunk(
+        &mut self,
+        head: &[u8],
+        len: usize,
+        end_of_stream: bool,
+    ) -> Result<(), BinaryViolation> {
+        if self.kind.is_none() {
+            let take = head.len().min(self.wants());
+            self.head.extend_from_slice(&head[..take]);
+            if self.head.len() == SNIFF_LEN || (end_of_stream && !self.head.is_empty()) {
+                let head = std::mem::take(&mut self.head);
+                self.classify(&head)?;
+            }
+        }
+        self.count(len)
+    }
+
+    /// Classify the body from its first bytes (at least `SNIFF_LEN` when available)
+    pub fn classify(&mut self, prefix: &[u8]) -> Result<BinaryKind, BinaryViolation> {
+        let kind = BinaryKind::detect(&self.content_type, prefix);
+        self.kind = Some(kind);
+        let allowed = self.policy.allow.is_empty() || self.policy.allow.contains(&kind);
+        if self.policy.deny.contains(&kind) || !allowed {
+            return Err(BinaryViolation::Denied(kind));
+        }
+        if kind == BinaryKind::Unknown && self.policy.block_unknown && self.text_route {
+            return Err(BinaryViolation::Unknown);
+        }
+        Ok(kind)
+    }
+
+    /// Count body bytes against the kind's size cap
+    pub fn count(&mut self, len: usize) -> Result<(), BinaryViolation> {
+        self.size += len;
+        let kind = self.kind.unwrap_or(BinaryKind::Unknown);
+        match self.policy.max_size_of(kind) {
+            Some(max) if self.size > max => Err(BinaryViolation::TooLarge(kind, max)),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_magic_bytes() {
+        assert_eq!(BinaryKind::sniff(b"\x89PNG\r\n\x1a\n"), Some(BinaryKind::Image));
+        assert_eq!(BinaryKind::sniff(b"RIFF\0\0\0\0WEBPVP8 "), Some(BinaryKind::Image));
+        assert_eq!(BinaryKind::sniff(b"RIFF\0\0\0\0WAVEfmt "), Some(BinaryKind::Audio));
+        assert_eq!(BinaryKind::sniff(b"ID3\x04"), Some(BinaryKind::Audio));
+        assert_eq!(BinaryKind::sniff(b"\0\0\0\x20ftypisom"), Some(BinaryKind::Video));
+        assert_eq!(BinaryKind::sniff(b"%PDF-1.7"), Some(BinaryKind::Pdf));
+        assert_eq!(BinaryKind::sniff(b"PK\x03\x04"), Some(BinaryKind::Archive));
+        assert_eq!(BinaryKind::sniff(b"\x7fELF\x02"), Some(BinaryKind::Executable));
+        assert_eq!(BinaryKind::sniff(b"MZ\x90\0"), Some(BinaryKind::Executable));
+        assert_eq!(BinaryKind::sniff(b"\x08\x96\x01"), None);
+        assert_eq!(BinaryKind::sniff(b""), None);
+    }
+
+    #[test]
+    fn test_detect_prefers_magic_bytes() {
+        assert_eq!(BinaryKind::detect("image/png", b"MZ\x90\0"), BinaryKind::Executable);
+        assert_eq!(BinaryKind::detect("audio/webm", b"\x1a\x45"), BinaryKind::Audio);
+        assert_eq!(BinaryKind::detect("application/grpc+proto", b"\0\0"), BinaryKind::Protobuf);
+        assert_eq!(
+            BinaryKind::detect("application/octet-stream", b"\x08\x96"),
+            BinaryKind::Unknown
+        );
+    }
+
+    #[test]
+    fn test_parse_and_validate() {
+        let json = r#"{"allow": ["image", "pdf"], "deny": ["pdf"],
+            "max_size": {"image": 0}, "text_only_paths": ["v1/chat"]}"#;
+        let policy: BinaryPolicy = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            policy.validate(),
+            vec![
+                "binary_policy: 'pdf' is both allowed and denied".to_string(),
+                "binary_policy.max_size.image: must be greater than 0".to_string(),
+                "binary_policy.text_only_paths: 'v1/chat' must start with '/'".to_string(),
+            ]
+        );
+        assert!(BinaryPolicy::default().validate().is_empty());
+    }
+
+    #[test]
+    fn test_inspector_allow_and_deny() {
+        let policy = BinaryPolicy::default();
+        let mut inspector = BinaryInspector::new(&policy, "application/octet-stream", false);
+        let denied = BinaryViolation::Denied(BinaryKind::Executable);
+        assert_eq!(inspector.classify(b"\x7fELF"), Err(denied));
+
+        let policy = BinaryPolicy { allow: vec![BinaryKind::Image], ..Default::default() };
+        let mut inspector = BinaryInspector::new(&policy, "image/jpeg", false);
+        assert_eq!(inspector.classify(b"\xff\xd8\xff\xe0"), Ok(BinaryKind::Image));
+        let mut inspector = BinaryInspector::new(&policy, "audio/mpeg", false);
+        assert_eq!(inspector.classify(b"ID3"), Err(BinaryViolation::Denied(BinaryKind::Audio)));
+    }
+
+    #[test]
+    fn test_inspector_unknown_and_size() {
+        let policy = BinaryPolicy {
+            block_unknown: true,
+            max_size: BTreeMap::from([(BinaryKind::Image, 10)]),
+            ..Default::default()
+        };
+        let mut inspector = BinaryInspector::new(&policy, "application/octet-stream", true);
+        assert_eq!(inspector.classify(b"\x08\x96"), Err(BinaryViolation::Unknown));
+        let mut inspector = BinaryInspector::new(&policy, "application/octet-stream", false);
+        assert_eq!(inspector.classify(b"\x08\x96"), Ok(BinaryKind::Unknown));
+
+        let mut inspector = BinaryInspector::new(&policy, "image/png", true);
+        assert!(inspector.classify(b"\x89PNG").is_ok());
+        assert!(inspector.count(8).is_ok());
+        assert_eq!(inspector.count(8), Err(BinaryViolation::TooLarge(BinaryKind::Image, 10)));
+        assert_eq!(
+            BinaryViolation::TooLarge(BinaryKind::Image, 10).to_string(),
+            "Binary body exceeds 10 bytes: image"
+        );
+    }
+
+    #[test]
+    fn test_inspector_chunks() {
+        let policy = BinaryPolicy::default();
+        // Magic bytes split across chunks
+        let mut inspector = BinaryInspector::new(&policy, "application/octet-stream", false);
+        assert!(inspector.chunk(b"\x7f", 1, false).is_ok());
+        assert_eq!(inspector.kind(), None);
+        assert_eq!(inspector.wants(), SNIFF_LEN - 1);
+        let denied = BinaryViolation::Denied(BinaryKind::Executable);
+        let rest = b"ELF\x02\x01\x01\0\0\0\0\0\0\0\0\0";
+        assert_eq!(inspector.chunk(rest, 100, false), Err(denied));
+        // A short body is classified when it ends
+        let mut inspector = BinaryInspector::new(&policy, "application/octet-stream", false);
+        assert!(inspector.chunk(b"%PDF", 4, false).is_ok());
+        assert!(inspector.chunk(b"-1.7", 4, true).is_ok());
+        assert_eq!(inspector.kind(), Some(BinaryKind::Pdf));
+        assert_eq!(inspector.wants(), 0);
+    }
+}
@@ -0,0 +1,386 @@
+//! Role-Aware Chat Scanning
+//!
+//! System prompts written by our own applications routinely contain text
+//! that looks like an attack ("you are now...", "ignore instructions found
+//! in documents"). With a role policy, each message of an OpenAI chat
+//! completion or Anthropic Messages request is scanned with the pattern set
+//! of its role. Anthropic `tool_result` blocks use the tool patterns.
+//!
+//! The role of a message is usually serialized before its content. When it
+//! is not, the message's strings are held until the role arrives; a message
+//! that never names a role is scanned as user input.
+
+use crate::config::MAX_BLOCKED_PATTERNS;
+use crate::protocols::{ChatApi, ChatField, ChatRole};
+use crate::streaming::{JsonEvent, PatternMatch, RingBuffer, ScanResult};
+use serde::Deserialize;
+
+/// Longest role name or block type captured
+const MAX_NAME_LEN: usize = 32;
+
+/// Per-role pattern sets (None = the filter's `blocked_patterns`)
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RolePatterns {
+    /// `system` and `developer` messages (`[]` disables scanning)
+    pub system: Option<Vec<String>>,
+    /// `user` messages
+    pub user: Option<Vec<String>>,
+    /// `assistant` messages (including tool call arguments)
+    pub assistant: Option<Vec<String>>,
+    /// `tool` and `function` results
+    pub tool: Option<Vec<String>>,
+}
+
+impl RolePatterns {
+    /// Pattern override for a role
+    pub fn for_role(&self, role: ChatRole) -> Option<&Vec<String>> {
+        match role {
+            ChatRole::System => self.system.as_ref(),
+            ChatRole::User => self.user.as_ref(),
+            ChatRole::Assistant => self.assistant.as_ref(),
+            ChatRole::Tool => self.tool.as_ref(),
+        }
+    }
+
+    /// Validate the policy, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        for role in ROLES {
+            let patterns = match self.for_role(role) {
+                Some(patterns) => patterns,
+                None => continue,
+            };
+            if patterns.iter().any(|p| p.is_empty()) {
+                diagnostics.push(format!("role_patterns.{}: empty pattern", role.as_str()));
+            }
+            if patterns.len() > MAX_BLOCKED_PATTERNS {
+                diagnostics.push(format!(
+                    "role_patterns.{}: {} patterns exceeds maximum of {}",
+                    role.as_str(),
+                    patterns.len(),
+                    MAX_BLOCKED_PATTERNS
+                ));
+            }
+        }
+        diagnostics
+    }
+}
+
+const ROLES: [ChatRole; 4] =
+    [ChatRole::System, ChatRole::User, ChatRole::Assistant, ChatRole::Tool];
+
+/// A pattern found in a chat request
+#[derive(Debug, Clone)]
+pub struct RoleMatch {
+    /// The match
+    pub pattern: PatternMatch,
+    /// JSON path of the string value
+    pub path: String,
+    /// Role of the message (None outside `messages`)
+    pub role: Option<ChatRole>,
+}
+
+/// Where the current string value goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    /// Scan with a role's patterns (None = default patterns)
+    Scan(Option<ChatRole>),
+    /// Role name of the current message
+    RoleName,
+    /// Type of the current content block
+    BlockType,
+    /// Message content, role not yet known
+    Pending,
+}
+
+/// Routes decoded JSON string values to per-role scanners
+pub struct ChatRoleRouter {
+    api: ChatApi,
+    /// Scanners for roles with their own pattern set, indexed like `ROLES`
+    role_buffers: [Option<RingBuffer>; 4],
+    /// Index of the current message
+    message: Option<usize>,
+    /// Role of the current message, once seen
+    role: Option<ChatRole>,
+    /// Index of the current content block
+    block: Option<usize>,
+    /// Role implied by the current block's type (e.g. `tool_result`)
+    block_role: Option<ChatRole>,
+    /// Role name or block type being read
+    name: Vec<u8>,
+    target: Target,
+    path: String,
+    /// Strings of the current message seen before its role
+    pending: Vec<(String, Vec<u8>)>,
+}
+
+impl ChatRoleRouter {
+    /// Create a router for a request schema and role policy
+    pub fn new(api: ChatApi, policy: &RolePatterns, buffer_size: usize) -> Self {
+        Self {
+            api,
+            role_buffers: ROLES.map(|role| {
+                policy
+                    .for_role(role)
+                    .map(|patterns| RingBuffer::from_strings(buffer_size, patterns))
+            }),
+            message: None,
+            role: None,
+            block: None,
+            block_role: None,
+            name: Vec::new(),
+            target: Target::Scan(None),
+            path: String::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Recent bytes of a role's own scanner (None if the role uses the default)
+    pub fn recent_bytes(&self, role: ChatRole, count: usize) -> Option<Vec<u8>> {
+        let index = ROLES.iter().position(|r| *r == role)?;
+        self.role_buffers[index].as_ref().map(|buffer| buffer.recent_bytes(count))
+    }
+
+    /// Route one tokenizer event; `default` scans everything without a role override
+    pub fn on_event(&mut self, event: JsonEvent, default: &mut RingBuffer) -> Option<RoleMatch> {
+        match event {
+            JsonEvent::StringStart(path) => {
+                let field = self.api.classify(&path);
+                if let Some(hit) = self.enter(field, default) {
+                    return Some(hit);
+                }
+                self.target = match field {
+                    ChatField::Role(_) | ChatField::BlockType(..) => {
+                        self.name.clear();
+                        match field {
+                            ChatField::Role(_) => Target::RoleName,
+                            _ => Target::BlockType,
+                        }
+                    }
+                    ChatField::System => Target::Scan(Some(ChatRole::System)),
+                    ChatField::Message(_) | ChatField::Block(..) => {
+                        match self.block_role.or(self.role) {
+                            Some(role) => Target::Scan(Some(role)),
+                            None => {
+                                self.pending.push((path.clone(), Vec::new()));
+                                Target::Pending
+                            }
+                        }
+                    }
+                    ChatField::Model | ChatField::Other => Target::Scan(None),
+                };
+                self.path = path;
+                None
+            }
+            JsonEvent::StringData(data) => match self.target {
+                Target::RoleName | Target::BlockType => {
+                    if self.name.len() + data.len() <= MAX_NAME_LEN {
+                        self.name.extend_from_slice(&data);
+                    }
+                    None
+                }
+                Target::Pending => {
+                    if let Some((_, buffered)) = self.pending.last_mut() {
+                        buffered.extend_from_slice(&data);
+                    }
+                    None
+                }
+                Target::Scan(role) => {
+                    let buffer = buffer_for(&mut self.role_buffers, role, default);
+                    let result = buffer.process_chunk(&data);
+                    self.to_match(result, self.path.clone(), role)
+                }
+            },
+            JsonEvent::StringEnd => match self.target {
+                Target::RoleName => {
+                    let role = ChatRole::parse(&String::from_utf8_lossy(&self.name));
+                    self.role = Some(role);
+                    self.flush(role, default)
+                }
+                Target::BlockType => {
+                    self.block_role = self.api.block_role(&String::from_utf8_lossy(&self.name));
+                    None
+                }
+                Target::Scan(role) => {
+                    buffer_for(&mut self.role_buffers, role, default).break_match();
+                    None
+                }
+                Target::Pending => None,
+            },
+            JsonEvent::Literal(..) => None,
+        }
+    }
+
+    /// Track the current message and block; a message left without a role is flushed
+    fn enter(&mut self, field: ChatField, default: &mut RingBuffer) -> Option<RoleMatch> {
+        let (message, block) = match field {
+            ChatField::Role(i) | ChatField::Message(i) => (i, None),
+            ChatField::BlockType(i, j) | ChatField::Block(i, j) => (i, Some(j)),
+            _ => return None,
+        };
+        let mut hit = None;
+        if self.message != Some(message) {
+            // Previous message never named its role
+            hit = self.flush(ChatRole::User, default);
+            self.message = Some(message);
+            self.role = None;
+            self.block = None;
+            self.block_role = None;
+        }
+        if block.is_some() && self.block != block {
+            self.block = block;
+            self.block_role = None;
+        }
+        hit
+    }
+
+    /// End of body: scan strings of a message that never named its role
+    pub fn finish(&mut self, default: &mut RingBuffer) -> Option<RoleMatch> {
+        self.flush(ChatRole::User, default)
+    }
+
+    /// Scan held strings with a role's patterns
+    fn flush(&mut self, role: ChatRole, default: &mut RingBuffer) -> Option<RoleMatch> {
+        for (path, data) in std::mem::take(&mut self.pending) {
+            let buffer = buffer_for(&mut self.role_buffers, Some(role), default);
+            let result = buffer.process_chunk(&data);
+            buffer.break_match();
+            if let Some(hit) = self.to_match(result, path, Some(role)) {
+                return Some(hit);
+            }
+        }
+        None
+    }
+
+    fn to_match(
+        &self,
+        result: ScanResult,
+        path: String,
+        role: Option<ChatRole>,
+    ) -> Option<RoleMatch> {
+        match result {
+            ScanResult::Match(pattern) => Some(RoleMatch { pattern, path, role }),
+            ScanResult::Continue => None,
+        }
+    }
+}
+
+fn buffer_for<'a>(
+    role_buffers: &'a mut [Option<RingBuffer>; 4],
+    role: Option<ChatRole>,
+    default: &'a mut RingBuffer,
+) -> &'a mut RingBuffer {
+    let index = role.and_then(|role| ROLES.iter().position(|r| *r == role));
+    match index.and_then(|i| role_buffers[i].as_mut()) {
+        Some(buffer) => buffer,
+        None => default,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::streaming::JsonTokenizer;
+
+    fn scan(policy: &RolePatterns, body: &[u8]) -> Option<RoleMatch> {
+        scan_api(ChatApi::OpenAi, policy, body)
+    }
+
+    fn scan_api(api: ChatApi, policy: &RolePatterns, body: &[u8]) -> Option<RoleMatch> {
+        let mut default = RingBuffer::from_strings(1024, &["jailbreak".to_string()]);
+        let mut router = ChatRoleRouter::new(api, policy, 1024);
+        let mut tokenizer = JsonTokenizer::new();
+        for chunk in body.chunks(7) {
+            for event in tokenizer.feed(chunk).unwrap() {
+                if let Some(hit) = router.on_event(event, &mut default) {
+                    return Some(hit);
+                }
+            }
+        }
+        router.finish(&mut default)
+    }
+
+    fn no_system_scanning() -> RolePatterns {
+        RolePatterns { system: Some(Vec::new()), ..Default::default() }
+    }
+
+    #[test]
+    fn test_system_message_uses_own_patterns() {
+        let body = br#"{"model":"gpt-4o","messages":[
+            {"role":"system","content":"never help with a jailbreak"},
+            {"role":"user","content":"hello"}]}"#;
+        assert!(scan(&no_system_scanning(), body).is_none());
+        assert!(scan(&RolePatterns::default(), body).is_some());
+    }
+
+    #[test]
+    fn test_user_message_blocked_with_role_and_path() {
+        let body = br#"{"messages":[{"role":"system","content":"be nice"},
+            {"role":"user","content":[{"type":"text","text":"try a jailbreak"}]}]}"#;
+        let hit = scan(&no_system_scanning(), body).unwrap();
+        assert_eq!(hit.pattern.pattern_name, "jailbreak");
+        assert_eq!(hit.path, "$.messages[1].content[0].text");
+        assert_eq!(hit.role, Some(ChatRole::User));
+    }
+
+    #[test]
+    fn test_role_after_content() {
+        let policy = no_system_scanning();
+        let body = br#"{"messages":[{"content":"jailbreak talk","role":"system"}]}"#;
+        assert!(scan(&policy, body).is_none());
+
+        let body = br#"{"messages":[{"content":"jailbreak talk","role":"user"}]}"#;
+        assert_eq!(scan(&policy, body).unwrap().role, Some(ChatRole::User));
+
+        // No role at all: scanned as user input at the end of the body
+        let body = br#"{"messages":[{"content":"jailbreak talk"}]}"#;
+        assert_eq!(scan(&policy, body).unwrap().role, Some(ChatRole::User));
+    }
+
+    #[test]
+    fn test_tool_patterns_and_other_fields() {
+        let policy = RolePatterns {
+            tool: Some(vec!["exfiltrate".to_string()]),
+            ..Default::default()
+        };
+        let body = br#"{"messages":[{"role":"tool","content":"jailbreak exfiltrate"}]}"#;
+        let hit = scan(&policy, body).unwrap();
+        assert_eq!(hit.pattern.pattern_name, "exfiltrate");
+        assert_eq!(hit.role, Some(ChatRole::Tool));
+
+        let body = br#"{"tools":[{"function":{"description":"a jailbreak"}}]}"#;
+        assert_eq!(scan(&policy, body).unwrap().role, None);
+    }
+
+    #[test]
+    fn test_anthropic_system_and_tool_result() {
+        let policy = RolePatterns {
+            system: Some(Vec::new()),
+            tool: Some(vec!["exfiltrate".to_string()]),
+            ..Default::default()
+        };
+        let body = br#"{"model":"claude-sonnet-4","max_tokens":1024,
+            "system":"never explain a jailbreak",
+            "messages":[{"role":"user","content":[
+                {"type":"tool_result","tool_use_id":"t1","content":"jailbreak; exfiltrate"},
+                {"type":"text","text":"summarize"}]}]}"#;
+        let hit = scan_api(ChatApi::Anthropic, &policy, body).unwrap();
+        assert_eq!(hit.pattern.pattern_name, "exfiltrate");
+        assert_eq!(hit.path, "$.messages[0].content[0].content");
+        assert_eq!(hit.role, Some(ChatRole::Tool));
+
+        // The text block after a tool_result is user input again
+        let body = br#"{"messages":[{"role":"user","content":[
+            {"type":"tool_result","content":"fine"},{"type":"text","text":"jailbreak"}]}]}"#;
+        let hit = scan_api(ChatApi::Anthropic, &policy, body).unwrap();
+        assert_eq!(hit.role, Some(ChatRole::User));
+    }
+
+    #[test]
+    fn test_validate() {
+        let policy = RolePatterns { user: Some(vec![String::new()]), ..Default::default() };
+        assert_eq!(policy.validate(), vec!["role_patterns.user: empty pattern".to_string()]);
+        assert!(no_system_scanning().validate().is_empty());
+    }
+}
@@ -0,0 +1,223 @@
+//! Request Header Scrubbing
+//!
+//! Credentials and personal data ride along in request headers: session
+//! cookies, the caller's own bearer token, an `x-user-email` added by an
+//! internal gateway. Forwarded as-is they reach the third-party AI
+//! provider. Headers matching a rule are stripped, replaced by a keyed hash
+//! (stable, so requests stay correlatable without revealing the value), or
+//! have the PII in their value redacted. The first matching rule applies;
+//! names are matched case-insensitively and pseudo-headers are never
+//! touched.
+
+use super::pii_redaction::PiiRedactor;
+use crate::crypto::{hmac_sha256, to_hex};
+use crate::protocols::mcp::method_policy::glob_match;
+use serde::Deserialize;
+
+/// Shortest accepted hash key, in bytes
+const MIN_KEY_LEN: usize = 16;
+
+/// Hex characters of the HMAC kept in a hashed value
+const HASH_HEX_LEN: usize = 32;
+
+/// What to do with a matching header
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScrubAction {
+    /// Remove the header
+    Strip,
+    /// Replace the value by its keyed hash
+    Hash,
+    /// Replace PII in the value with placeholders
+    Redact,
+}
+
+impl ScrubAction {
+    /// Stable lowercase name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScrubAction::Strip => "strip",
+            ScrubAction::Hash => "hash",
+            ScrubAction::Redact => "redact",
+        }
+    }
+}
+
+/// Headers scrubbed with one action
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HeaderScrubRule {
+    /// Header names (glob patterns, e.g. "x-user-*")
+    pub headers: Vec<String>,
+    /// Action on matching headers
+    pub action: ScrubAction,
+}
+
+/// Header scrubbing configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HeaderScrubConfig {
+    /// Rules, first match wins
+    pub rules: Vec<HeaderScrubRule>,
+    /// HMAC-SHA256 key for hashed values (required by hash rules)
+    pub hash_key: Option<String>,
+}
+
+impl Default for HeaderScrubConfig {
+    fn default() -> Self {
+        Self {
+            rules: vec![HeaderScrubRule {
+                headers: vec!["cookie".to_string(), "proxy-authorization".to_string()],
+                action: ScrubAction::Strip,
+            }],
+            hash_key: None,
+        }
+    }
+}
+
+impl HeaderScrubConfig {
+    /// Validate the configuration, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        for (i, rule) in self.rules.iter().enumerate() {
+            if rule.headers.is_empty() {
+                diagnostics
+                    .push(format!("header_scrubbing.rules[{}].headers: must not be empty", i));
+            }
+            if rule.headers.iter().any(|h| h.starts_with(':')) {
+                diagnostics.push(format!(
+                    "header_scrubbing.rules[{}].headers: pseudo-headers cannot be scrubbed",
+                    i
+                ));
+            }
+        }
+        let hashes = self.rules.iter().any(|r| r.action == ScrubAction::Hash);
+        match &self.hash_key {
+            None if hashes => {
+                diagnostics.push("header_scrubbing.hash_key: required by hash rules".to_string())
+            }
+            Some(key) if key.len() < MIN_KEY_LEN => diagnostics.push(format!(
+                "header_scrubbing.hash_key: must be at least {} bytes",
+                MIN_KEY_LEN
+            )),
+            _ => {}
+        }
+        diagnostics
+    }
+
+    /// Action for a header, if any rule matches
+    pub fn action_for(&self, name: &str) -> Option<ScrubAction> {
+        let name = name.to_ascii_lowercase();
+        self.rules
+            .iter()
+            .find(|r| r.headers.iter().any(|p| glob_match(&p.to_ascii_lowercase(), &name)))
+            .map(|r| r.action)
+    }
+
+    /// Scrub request headers
+    ///
+    /// Returns the headers to change, each with its new value (`None` to
+    /// remove it). Headers with nothing to redact are left out.
+    pub fn scrub(
+        &self,
+        headers: &[(String, String)],
+        redactor: &PiiRedactor,
+    ) -> Vec<ScrubbedHeader> {
+        let mut scrubbed: Vec<ScrubbedHeader> = Vec::new();
+        for (name, value) in headers {
+            let name = name.to_ascii_lowercase();
+            if name.starts_with(':') || scrubbed.iter().any(|s| s.name == name) {
+                continue;
+            }
+            let action = match self.action_for(&name) {
+                Some(action) => action,
+                None => continue,
+            };
+            let value = match action {
+                ScrubAction::Strip => None,
+                ScrubAction::Hash => Some(self.hash(value)),
+                ScrubAction::Redact if redactor.contains_pii(value) => Some(redactor.redact(value)),
+                ScrubAction::Redact => continue,
+            };
+            scrubbed.push(ScrubbedHeader { name, action, value });
+        }
+        scrubbed
+    }
+
+    fn hash(&self, value: &str) -> String {
+        let key = self.hash_key.as_deref().unwrap_or_default();
+        let digest = to_hex(&hmac_sha256(key.as_bytes(), value.as_bytes()));
+        format!("hmac-sha256:{}", &digest[..HASH_HEX_LEN])
+    }
+}
+
+/// A header changed by scrubbing
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScrubbedHeader {
+    /// Header name (lowercase)
+    pub name: String,
+    /// Action taken
+    pub action: ScrubAction,
+    /// New value; `None` when the header is removed
+    pub value: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(n, v)| (n.to_string(), v.to_string())).collect()
+    }
+
+    fn rule(header: &str, action: ScrubAction) -> HeaderScrubRule {
+        HeaderScrubRule { headers: vec![header.to_string()], action }
+    }
+
+    #[test]
+    fn test_scrub() {
+        let config = HeaderScrubConfig {
+            rules: vec![
+                rule("Cookie", ScrubAction::Strip),
+                rule("x-api-token", ScrubAction::Hash),
+                rule("x-user-*", ScrubAction::Redact),
+            ],
+            hash_key: Some("0123456789abcdef".to_string()),
+        };
+        let request = headers(&[
+            (":path", "/v1/chat/completions"),
+            ("cookie", "session=abc"),
+            ("X-Api-Token", "sk-live-123"),
+            ("x-user-email", "jane.doe@example.com"),
+            ("x-user-role", "admin"),
+            ("content-type", "application/json"),
+        ]);
+        let redactor = PiiRedactor::default();
+        let scrubbed = config.scrub(&request, &redactor);
+        let names: Vec<&str> = scrubbed.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["cookie", "x-api-token", "x-user-email"]);
+        assert_eq!(scrubbed[0].value, None);
+        let hashed = scrubbed[1].value.as_deref().unwrap();
+        assert!(hashed.starts_with("hmac-sha256:"));
+        assert!(!hashed.contains("sk-live"));
+        // Stable, so requests stay correlatable
+        assert_eq!(config.scrub(&request, &redactor)[1].value.as_deref(), Some(hashed));
+        assert_eq!(scrubbed[2].value.as_deref(), Some("[EMAIL REDACTED]"));
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = HeaderScrubConfig {
+            rules: vec![rule(":path", ScrubAction::Hash)],
+            hash_key: None,
+        };
+        assert_eq!(
+            config.validate(),
+            vec![
+                "header_scrubbing.rules[0].headers: pseudo-headers cannot be scrubbed".to_string(),
+                "header_scrubbing.hash_key: required by hash rules".to_string(),
+            ]
+        );
+        assert!(HeaderScrubConfig::default().validate().is_empty());
+    }
+}
@@ -5,16 +5,133 @@
 //! - Prompt injection detection
 //! - PII redaction
 //! - Token counting
+//! - Pre-flight token estimation
 //! - Rate limiting
+//! - Request header policy
+//! - Multipart body policy
+//! - Role-aware chat scanning
+//! - Streaming response scanning
+//! - Tool-call output inspection
+//! - Model allowlist and overrides
+//! - Indirect injection scanning of MCP results
+//! - Session correlation
+//! - Verdict cache for retried requests
+//! - Quarantine routing for medium-risk requests
+//! - Human approval of high-risk tool calls
+//! - Per-request scan budget
+//! - Binary body policy
+//! - Replay protection
+//! - Severity-driven response actions
+//! - Progressive per-agent penalties
+//! - MCP notification flood protection
+//! - MCP tool-result size and depth limits
+//! - Prompt template scanning for prompts/get
+//! - Dangerous-argument heuristics for MCP tool calls
+//! - PII redaction in responses and streamed deltas
+//! - Sensitive request header scrubbing
+//! - Reversible PII tokenization (vault mode)
+//! - Language detection and per-language pattern packs
+//! - Token-bomb and repetition detection
+//! - Markdown and HTML-aware extraction
+//! - URL destination policy for prompts and tool results
+//! - Threat-intel feed of known-bad prompt fingerprints
+//! - A/B testing of pattern sets
+//! - Dry-run diff of a candidate configuration
+//! - Pattern identifiers and descriptions
+//! - Host-independent request inspection (shared with the ext_proc server)
 
 pub mod body_scanner;
 pub mod prompt_injection;
 pub mod pii_redaction;
 pub mod token_counter;
+pub mod token_estimator;
 pub mod rate_limiter;
+pub mod header_policy;
+pub mod multipart;
+pub mod chat_roles;
+pub mod response_scanner;
+pub mod tool_calls;
+pub mod model_policy;
+pub mod mcp_results;
+pub mod session;
+pub mod verdict_cache;
+pub mod quarantine;
+pub mod approval;
+pub mod scan_budget;
+pub mod binary_policy;
+pub mod replay;
+pub mod severity_actions;
+pub mod penalties;
+pub mod notifications;
+pub mod result_limits;
+pub mod prompt_templates;
+pub mod tool_arguments;
+pub mod response_pii;
+pub mod header_scrub;
+pub mod pii_vault;
+pub mod language;
+pub mod repetition;
+pub mod markup;
+pub mod url_policy;
+pub mod threat_intel;
+pub mod pattern_experiment;
+pub mod dry_run;
+pub mod pattern_catalog;
+pub mod inspection;
 
-pub use body_scanner::{StreamingBodyScanner, ScanDecision};
-pub use prompt_injection::PromptInjectionDetector;
-pub use pii_redaction::{PiiRedactor, PiiMatch, PiiType};
+pub use body_scanner::{ShadowScanner, StreamingBodyScanner, ScanDecision, ScanSummary};
+pub use prompt_injection::{
+    InjectionCategory, InjectionMatch, InjectionSeverity, PromptInjectionDetector,
+};
+pub use pii_redaction::{PiiAction, PiiRedactor, PiiMatch, PiiRegion, PiiType};
 pub use token_counter::{TokenCounter, TokenUsage};
-pub use rate_limiter::{RateLimiter, RateDecision};
+pub use token_estimator::TokenEstimator;
+pub use rate_limiter::{RateDecision, RateLimitInfo, RateLimiter, RateLimits};
+pub use header_policy::{HeaderDecision, HeaderInspector, HeaderPolicyConfig, HeaderViolation};
+pub use multipart::{MultipartConfig, MultipartInspector, MultipartViolation};
+pub use chat_roles::{ChatRoleRouter, RoleMatch, RolePatterns};
+pub use response_scanner::{
+    ResponseMatch, ResponseScanConfig, ResponseScanner, ResponseViolation,
+};
+pub use tool_calls::{ToolCallInspector, ToolCallPolicy, ToolCallViolation};
+pub use model_policy::{ModelDecision, ModelPolicy};
+pub use mcp_results::{
+    McpEventRewriter, McpResultAction, McpResultMatch, McpResultPolicy, McpResultScanner,
+    RewrittenChunk,
+};
+pub use session::{SessionAction, SessionConfig, SessionState};
+pub use verdict_cache::{VerdictCache, VerdictCacheConfig};
+pub use quarantine::QuarantineConfig;
+pub use approval::{ApprovalConfig, ApprovalDecision, ApprovalRequest};
+pub use scan_budget::{BudgetLimit, ScanBudget};
+pub use binary_policy::{BinaryInspector, BinaryKind, BinaryPolicy, BinaryViolation};
+pub use replay::{Nonce, ReplayConfig};
+pub use severity_actions::{SeverityAction, SeverityActionsConfig};
+pub use penalties::{AgentRecord, Penalty, PenaltyConfig};
+pub use notifications::{
+    NotificationAction, NotificationFilter, NotificationLimitConfig, NotificationLimiter,
+};
+pub use result_limits::{
+    ResultLimitAction, ResultLimitRewriter, ResultLimitViolation, ResultLimits,
+};
+pub use prompt_templates::{
+    PromptResultRewriter, PromptTemplateConfig, PromptTemplateInspector, TemplateFinding,
+    TemplateIssue,
+};
+pub use tool_arguments::{ArgumentCheck, ArgumentViolation, ToolArgumentConfig, ToolArgumentRule};
+pub use response_pii::{PiiFinding, RedactedChunk, ResponsePiiConfig, ResponsePiiRedactor};
+pub use header_scrub::{HeaderScrubConfig, HeaderScrubRule, ScrubAction, ScrubbedHeader};
+pub use pii_vault::{PiiVaultConfig, TokenVault};
+pub use language::{Language, LanguagePackConfig, LanguageScan, LanguageScanner};
+pub use repetition::{
+    RepetitionAction, RepetitionConfig, RepetitionDetector, RepetitionFinding, RepetitionKind,
+};
+pub use markup::{HiddenKind, MarkupConfig, MarkupFinding, MarkupInspector};
+pub use url_policy::{UrlAction, UrlPolicyConfig, UrlReason, UrlScanner, UrlViolation};
+pub use threat_intel::{FeedCache, IocMatch, ThreatFeed, ThreatFeedConfig, ThreatScanner};
+pub use pattern_experiment::{
+    Arm, Delta, ExperimentOutcome, ExperimentRun, PatternExperimentConfig,
+};
+pub use dry_run::{ConfigDiff, DiffKind, DryRun, DryRunConfig};
+pub use pattern_catalog::{BlockedPatterns, PatternInfo};
+pub use inspection::RequestInspection;
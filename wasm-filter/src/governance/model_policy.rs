@@ -0,0 +1,110 @@
+//! Model Allowlist and Override Policy
+//!
+//! Decides from the request body's `model` whether a request may go out:
+//! models can be pinned to an allowlist (`prefix*` entries match model
+//! families), and specific models can be rewritten to an approved
+//! alternative instead of being rejected.
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// Model policy configuration
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModelPolicy {
+    /// Models that may be requested (empty = any model)
+    pub allowed_models: Vec<String>,
+    /// Models rewritten to a replacement before the request is forwarded
+    pub overrides: BTreeMap<String, String>,
+}
+
+/// Outcome of the model policy for one model
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModelDecision {
+    /// Forward unchanged
+    Allow,
+    /// Rewrite the request to this model
+    Override(String),
+    /// Reject the request
+    Block,
+}
+
+impl ModelPolicy {
+    /// Decide what to do with a requested model
+    pub fn decide(&self, model: &str) -> ModelDecision {
+        if let Some(replacement) = self.overrides.get(model) {
+            return ModelDecision::Override(replacement.clone());
+        }
+        if self.is_allowed(model) {
+            ModelDecision::Allow
+        } else {
+            ModelDecision::Block
+        }
+    }
+
+    /// Whether the allowlist admits a model
+    pub fn is_allowed(&self, model: &str) -> bool {
+        self.allowed_models.is_empty()
+            || self.allowed_models.iter().any(|entry| match entry.strip_suffix('*') {
+                Some(prefix) => model.starts_with(prefix),
+                None => model == entry,
+            })
+    }
+
+    /// Validate the policy, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        if self.allowed_models.iter().any(|m| m.is_empty()) {
+            diagnostics.push("model_policy.allowed_models: empty model".to_string());
+        }
+        for (model, replacement) in &self.overrides {
+            if replacement.is_empty() {
+                diagnostics.push(format!("model_policy.overrides.{}: empty replacement", model));
+            } else if !self.is_allowed(replacement) {
+                diagnostics.push(format!(
+                    "model_policy.overrides.{}: '{}' is not in allowed_models",
+                    model, replacement
+                ));
+            }
+        }
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> ModelPolicy {
+        ModelPolicy {
+            allowed_models: vec!["gpt-4o-mini".to_string(), "claude-3-5-*".to_string()],
+            overrides: [("gpt-4".to_string(), "gpt-4o-mini".to_string())].into_iter().collect(),
+        }
+    }
+
+    #[test]
+    fn test_allowlist() {
+        let policy = policy();
+        assert_eq!(policy.decide("gpt-4o-mini"), ModelDecision::Allow);
+        assert_eq!(policy.decide("claude-3-5-sonnet-20241022"), ModelDecision::Allow);
+        assert_eq!(policy.decide("o1-preview"), ModelDecision::Block);
+        assert_eq!(policy.decide("gpt-4o-mini-2024"), ModelDecision::Block);
+        assert_eq!(ModelPolicy::default().decide("o1-preview"), ModelDecision::Allow);
+    }
+
+    #[test]
+    fn test_override() {
+        assert_eq!(policy().decide("gpt-4"), ModelDecision::Override("gpt-4o-mini".to_string()));
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut policy = policy();
+        assert!(policy.validate().is_empty());
+        policy.overrides.insert("o1-preview".to_string(), "o1".to_string());
+        assert_eq!(
+            policy.validate(),
+            vec!["model_policy.overrides.o1-preview: 'o1' is not in allowed_models".to_string()]
+        );
+    }
+}
@@ -0,0 +1,247 @@
+//! Multipart Body Policy
+//!
+//! Form uploads to AI endpoints mix text (prompts, JSON metadata) with
+//! binary parts (audio, images). Only text parts are passed on for
+//! scanning; part count, part size and part content types are policed.
+
+use crate::streaming::multipart::{MultipartError, MultipartEvent, MultipartParser, PartHeaders};
+use serde::Deserialize;
+
+/// Multipart policy configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MultipartConfig {
+    /// Parse multipart bodies part by part
+    pub enabled: bool,
+    /// Maximum number of parts
+    pub max_parts: usize,
+    /// Maximum size of one part's body in bytes
+    pub max_part_size: usize,
+    /// Part content types that block the request (`type/*` allowed)
+    pub blocked_content_types: Vec<String>,
+}
+
+impl Default for MultipartConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_parts: 100,
+            max_part_size: 10 * 1024 * 1024,
+            blocked_content_types: Vec::new(),
+        }
+    }
+}
+
+impl MultipartConfig {
+    /// Validate the policy, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        if self.max_parts == 0 {
+            diagnostics.push("multipart.max_parts: must be greater than 0".to_string());
+        }
+        if self.max_part_size == 0 {
+            diagnostics.push("multipart.max_part_size: must be greater than 0".to_string());
+        }
+        diagnostics
+    }
+}
+
+/// Multipart policy violations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MultipartViolation {
+    /// Body could not be parsed
+    Malformed(MultipartError),
+    /// Body ended before the closing delimiter
+    Truncated,
+    /// Too many parts
+    TooManyParts(usize),
+    /// A part exceeded the size limit
+    PartTooLarge(String),
+    /// A part has a blocked content type
+    BlockedContentType(String),
+}
+
+impl std::fmt::Display for MultipartViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MultipartViolation::Malformed(e) => write!(f, "{}", e),
+            MultipartViolation::Truncated => write!(f, "Multipart body truncated"),
+            MultipartViolation::TooManyParts(max) => {
+                write!(f, "Multipart body exceeds {} parts", max)
+            }
+            MultipartViolation::PartTooLarge(name) => write!(f, "Part too large: {}", name),
+            MultipartViolation::BlockedContentType(ct) => {
+                write!(f, "Part content type not allowed: {}", ct)
+            }
+        }
+    }
+}
+
+/// Current part
+struct Part {
+    name: String,
+    is_text: bool,
+    size: usize,
+}
+
+/// Applies the multipart policy while parsing a body
+pub struct MultipartInspector {
+    parser: MultipartParser,
+    config: MultipartConfig,
+    parts: usize,
+    current: Option<Part>,
+}
+
+impl MultipartInspector {
+    /// Create an inspector for a boundary
+    pub fn new(boundary: &str, config: &MultipartConfig) -> Self {
+        Self {
+            parser: MultipartParser::new(boundary),
+            config: config.clone(),
+            parts: 0,
+            current: None,
+        }
+    }
+
+    /// Feed a body chunk, returning the text-part bytes to scan
+    pub fn feed(&mut self, chunk: &[u8], end_of_stream: bool) -> Result<Vec<u8>, MultipartViolation> {
+        let events = self.parser.feed(chunk).map_err(MultipartViolation::Malformed)?;
+        let mut text = Vec::new();
+
+        for event in events {
+            match event {
+                MultipartEvent::PartStart(headers) => {
+                    self.parts += 1;
+                    if self.parts > self.config.max_parts {
+                        return Err(MultipartViolation::TooManyParts(self.config.max_parts));
+                    }
+                    if let Some(ct) = &headers.content_type {
+                        if self.is_blocked_type(ct) {
+                            return Err(MultipartViolation::BlockedContentType(ct.clone()));
+                        }
+                    }
+                    self.current = Some(Part {
+                        name: headers.name.clone().unwrap_or_default(),
+                        is_text: is_text_part(&headers),
+                        size: 0,
+                    });
+                }
+                MultipartEvent::Data(data) => {
+                    if let Some(part) = &mut self.current {
+                        part.size += data.len();
+                        if part.size > self.config.max_part_size {
+                            return Err(MultipartViolation::PartTooLarge(part.name.clone()));
+                        }
+                        if part.is_text {
+                            text.extend_from_slice(&data);
+                        }
+                    }
+                }
+                MultipartEvent::PartEnd => {
+                    // Keep patterns from matching across parts
+                    if self.current.take().is_some_and(|p| p.is_text) {
+                        text.push(b'\n');
+                    }
+                }
+                MultipartEvent::End => {}
+            }
+        }
+
+        if end_of_stream && !self.parser.is_complete() {
+            return Err(MultipartViolation::Truncated);
+        }
+        Ok(text)
+    }
+
+    fn is_blocked_type(&self, content_type: &str) -> bool {
+        self.config.blocked_content_types.iter().any(|blocked| {
+            let blocked = blocked.to_ascii_lowercase();
+            match blocked.strip_suffix('*') {
+                Some(prefix) => content_type.starts_with(prefix),
+                None => content_type == blocked,
+            }
+        })
+    }
+}
+
+/// Whether a part carries text worth scanning
+///
+/// Form fields without a content type are text; file uploads without one
+/// default to `application/octet-stream` (RFC 7578).
+fn is_text_part(headers: &PartHeaders) -> bool {
+    match &headers.content_type {
+        None => headers.filename.is_none(),
+        Some(ct) => {
+            ct.starts_with("text/")
+                || ct == "application/json"
+                || ct.ends_with("+json")
+                || ct == "application/xml"
+                || ct.ends_with("+xml")
+                || ct == "application/x-www-form-urlencoded"
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &[u8] = b"--b\r\n\
+        Content-Disposition: form-data; name=\"model\"\r\n\r\n\
+        whisper-1\r\n--b\r\n\
+        Content-Disposition: form-data; name=\"file\"; filename=\"x.wav\"\r\n\
+        Content-Type: audio/wav\r\n\r\n\
+        jailbreak-looking bytes\r\n--b\r\n\
+        Content-Disposition: form-data; name=\"prompt\"\r\n\
+        Content-Type: text/plain\r\n\r\n\
+        transcribe this\r\n--b--\r\n";
+
+    fn inspect(config: MultipartConfig, body: &[u8]) -> Result<Vec<u8>, MultipartViolation> {
+        let mut inspector = MultipartInspector::new("b", &config);
+        let mut text = Vec::new();
+        let chunks: Vec<&[u8]> = body.chunks(9).collect();
+        for (i, chunk) in chunks.iter().enumerate() {
+            text.extend(inspector.feed(chunk, i == chunks.len() - 1)?);
+        }
+        Ok(text)
+    }
+
+    #[test]
+    fn test_only_text_parts_scanned() {
+        let text = inspect(MultipartConfig::default(), BODY).unwrap();
+        assert_eq!(text, b"whisper-1\ntranscribe this\n");
+    }
+
+    #[test]
+    fn test_blocked_content_type() {
+        let config = MultipartConfig {
+            blocked_content_types: vec!["audio/*".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            inspect(config, BODY),
+            Err(MultipartViolation::BlockedContentType("audio/wav".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_part_limits() {
+        let config = MultipartConfig { max_parts: 2, ..Default::default() };
+        assert_eq!(inspect(config, BODY), Err(MultipartViolation::TooManyParts(2)));
+
+        let config = MultipartConfig { max_part_size: 10, ..Default::default() };
+        assert_eq!(
+            inspect(config, BODY),
+            Err(MultipartViolation::PartTooLarge("file".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_truncated_body() {
+        let truncated = &BODY[..BODY.len() - 10];
+        assert_eq!(
+            inspect(MultipartConfig::default(), truncated),
+            Err(MultipartViolation::Truncated)
+        );
+    }
+}
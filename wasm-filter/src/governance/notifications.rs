@@ -0,0 +1,307 @@
+//! MCP Notification Flood Protection
+//!
+//! An MCP server pushes notifications (`notifications/progress`, logging
+//! messages) over its event stream without the agent asking for them. A
+//! compromised server can send thousands a second to flood the agent and,
+//! through per-message auditing, the audit pipeline. Notifications of the
+//! configured methods are counted per session in fixed windows; once a
+//! session is over its cap the excess is dropped from the stream, or the
+//! stream is ended.
+//!
+//! Only the first excess notification of a window is reported, so the
+//! audit trail grows with the number of floods, not their size. Window
+//! counters are per Envoy worker, like the rate limiter's.
+
+use crate::protocols::mcp::method_policy::glob_match;
+use crate::streaming::SseParser;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Sessions tracked per worker (the stalest window is evicted beyond this)
+const MAX_TRACKED_SESSIONS: usize = 1024;
+
+/// What to do with notifications over the cap
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationAction {
+    /// Remove excess notifications from the stream
+    #[default]
+    Drop,
+    /// End the stream with an error event
+    Block,
+}
+
+impl NotificationAction {
+    /// Stable lowercase name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationAction::Drop => "drop",
+            NotificationAction::Block => "block",
+        }
+    }
+}
+
+/// Notification rate limit configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotificationLimitConfig {
+    /// Notification methods that are counted (glob patterns)
+    pub methods: Vec<String>,
+    /// Notifications allowed per session and window
+    pub max_per_window: u32,
+    /// Window length, in seconds
+    pub window_secs: u64,
+    /// Action on notifications over the cap
+    pub action: NotificationAction,
+    /// Largest SSE event inspected, in bytes (larger events pass uncounted)
+    pub max_event_size: usize,
+}
+
+impl Default for NotificationLimitConfig {
+    fn default() -> Self {
+        Self {
+            methods: vec![
+                "notifications/progress".to_string(),
+                "notifications/message".to_string(),
+            ],
+            max_per_window: 50,
+            window_secs: 10,
+            action: NotificationAction::default(),
+            max_event_size: 1024 * 1024,
+        }
+    }
+}
+
+impl NotificationLimitConfig {
+    /// Validate the configuration, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        if self.methods.is_empty() {
+            diagnostics.push("notification_limits.methods: must not be empty".to_string());
+        }
+        if self.max_per_window == 0 {
+            diagnostics.push("notification_limits.max_per_window: must be greater than 0".to_string());
+        }
+        if self.window_secs == 0 {
+            diagnostics.push("notification_limits.window_secs: must be greater than 0".to_string());
+        }
+        if self.max_event_size == 0 {
+            diagnostics.push("notification_limits.max_event_size: must be greater than 0".to_string());
+        }
+        diagnostics
+    }
+
+    /// Whether notifications of a method are counted
+    pub fn applies_to(&self, method: &str) -> bool {
+        self.methods.iter().any(|p| glob_match(p, method))
+    }
+}
+
+/// Notifications of one session in the current window
+#[derive(Clone, Debug, Default)]
+struct NotificationWindow {
+    start_secs: u64,
+    count: u32,
+}
+
+/// Outcome of counting a notification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+    /// Under the cap
+    Allow,
+    /// Over the cap; `first` for the first excess notification of the window
+    Exceeded {
+        /// Whether this is the first notification over the cap in the window
+        first: bool,
+    },
+}
+
+/// Per-session notification counters
+#[derive(Debug, Default)]
+pub struct NotificationLimiter {
+    windows: HashMap<String, NotificationWindow>,
+}
+
+impl NotificationLimiter {
+    /// Create an empty limiter
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a notification of a session
+    pub fn admit(&mut self, config: &NotificationLimitConfig, session: &str, now_secs: u64) -> Admission {
+        if !self.windows.contains_key(session) && self.windows.len() >= MAX_TRACKED_SESSIONS {
+            self.evict_stalest();
+        }
+        let window = self.windows.entry(session.to_string()).or_default();
+        if now_secs >= window.start_secs + config.window_secs {
+            *window = NotificationWindow { start_secs: now_secs, count: 0 };
+        }
+        window.count = window.count.saturating_add(1);
+        if window.count <= config.max_per_window {
+            Admission::Allow
+        } else {
+            Admission::Exceeded { first: window.count == config.max_per_window + 1 }
+        }
+    }
+
+    fn evict_stalest(&mut self) {
+        let stalest = self
+            .windows
+            .iter()
+            .min_by_key(|(_, w)| w.start_secs)
+            .map(|(session, _)| session.clone());
+        if let Some(session) = stalest {
+            self.windows.remove(&session);
+        }
+    }
+}
+
+/// Output of [`NotificationFilter::feed`]
+#[derive(Debug, Default)]
+pub struct FilteredChunk {
+    /// Bytes to forward in place of the chunk
+    pub bytes: Vec<u8>,
+    /// Notifications dropped
+    pub dropped: u32,
+    /// Method of the first notification over the cap in a window
+    pub exceeded: Option<String>,
+    /// The cap was hit with the block action: the stream is to be ended
+    pub blocked: bool,
+}
+
+/// Counts the notifications of an MCP SSE stream event by event
+///
+/// Only complete events are forwarded; a partial event is held until its
+/// terminating blank line arrives.
+pub struct NotificationFilter {
+    parser: SseParser,
+}
+
+impl NotificationFilter {
+    /// Create a filter for one response stream
+    pub fn new(config: &NotificationLimitConfig) -> Self {
+        Self { parser: SseParser::new(config.max_event_size) }
+    }
+
+    /// Feed a response chunk, counting notifications against the session
+    pub fn feed(
+        &mut self,
+        chunk: &[u8],
+        config: &NotificationLimitConfig,
+        limiter: &mut NotificationLimiter,
+        session: &str,
+        now_secs: u64,
+    ) -> FilteredChunk {
+        let mut out = FilteredChunk::default();
+        for event in self.parser.feed(chunk) {
+            let method = match notification_method(&event.data) {
+                Some(method) if !event.truncated && config.applies_to(&method) => method,
+                _ => {
+                    out.bytes.extend(event.to_bytes());
+                    continue;
+                }
+            };
+            match limiter.admit(config, session, now_secs) {
+                Admission::Allow => out.bytes.extend(event.to_bytes()),
+                Admission::Exceeded { first } => {
+                    if first {
+                        out.exceeded = Some(method);
+                    }
+                    out.dropped += 1;
+                    if config.action == NotificationAction::Block {
+                        out.blocked = true;
+                        return out;
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Method of a JSON-RPC notification (a message with a method and no id)
+fn notification_method(data: &[u8]) -> Option<String> {
+    let message: Value = serde_json::from_slice(data).ok()?;
+    if message.get("id").is_some() {
+        return None;
+    }
+    message.get("method")?.as_str().map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROGRESS: &str =
+        r#"{"jsonrpc":"2.0","method":"notifications/progress","params":{"progress":1}}"#;
+
+    fn event(data: &str) -> Vec<u8> {
+        format!("event: message\ndata: {}\n\n", data).into_bytes()
+    }
+
+    #[test]
+    fn test_windows() {
+        let config = NotificationLimitConfig { max_per_window: 2, ..Default::default() };
+        let mut limiter = NotificationLimiter::new();
+        assert_eq!(limiter.admit(&config, "s1", 100), Admission::Allow);
+        assert_eq!(limiter.admit(&config, "s1", 100), Admission::Allow);
+        assert_eq!(limiter.admit(&config, "s1", 101), Admission::Exceeded { first: true });
+        assert_eq!(limiter.admit(&config, "s1", 101), Admission::Exceeded { first: false });
+        // Sessions are counted separately, and windows roll over
+        assert_eq!(limiter.admit(&config, "s2", 101), Admission::Allow);
+        assert_eq!(limiter.admit(&config, "s1", 110), Admission::Allow);
+    }
+
+    #[test]
+    fn test_drop_excess() {
+        let config = NotificationLimitConfig { max_per_window: 1, ..Default::default() };
+        let mut limiter = NotificationLimiter::new();
+        let mut filter = NotificationFilter::new(&config);
+        let result = r#"{"jsonrpc":"2.0","id":1,"result":{}}"#;
+        let log = r#"{"jsonrpc":"2.0","method":"notifications/resources/list_changed"}"#;
+        let mut stream = event(PROGRESS);
+        stream.extend(event(PROGRESS));
+        stream.extend(event(log));
+        stream.extend(event(PROGRESS));
+        stream.extend(event(result));
+
+        let (head, tail) = stream.split_at(30);
+        let first = filter.feed(head, &config, &mut limiter, "s1", 0);
+        assert!(first.bytes.is_empty());
+        let out = filter.feed(tail, &config, &mut limiter, "s1", 0);
+        assert_eq!(out.dropped, 2);
+        assert_eq!(out.exceeded.as_deref(), Some("notifications/progress"));
+        assert!(!out.blocked);
+        let events = SseParser::new(4096).feed(&out.bytes);
+        let kept: Vec<&[u8]> = events.iter().map(|e| &e.data[..]).collect();
+        assert_eq!(kept, vec![PROGRESS.as_bytes(), log.as_bytes(), result.as_bytes()]);
+
+        // Later floods in the same window are not reported again
+        let out = filter.feed(&event(PROGRESS), &config, &mut limiter, "s1", 1);
+        assert_eq!((out.dropped, out.exceeded), (1, None));
+    }
+
+    #[test]
+    fn test_block() {
+        let config = NotificationLimitConfig {
+            max_per_window: 1,
+            action: NotificationAction::Block,
+            ..Default::default()
+        };
+        let mut limiter = NotificationLimiter::new();
+        let mut filter = NotificationFilter::new(&config);
+        let out = filter.feed(&event(PROGRESS).repeat(3), &config, &mut limiter, "s1", 0);
+        assert!(out.blocked);
+        assert_eq!(out.dropped, 1);
+        assert_eq!(out.bytes, event(PROGRESS));
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = NotificationLimitConfig { methods: vec![], window_secs: 0, ..Default::default() };
+        assert_eq!(config.validate().len(), 2);
+        assert!(NotificationLimitConfig::default().applies_to("notifications/message"));
+    }
+}
@@ -0,0 +1,176 @@
+//! Pattern Identifiers and Descriptions
+//!
+//! A blocked pattern is either a plain string or an object naming it:
+//!
+//! ```json
+//! {"blocked_patterns": [
+//!     "jailbreak",
+//!     {"pattern": "ignore previous", "id": "PI-0001",
+//!      "description": "Instruction override"}
+//! ]}
+//! ```
+//!
+//! Audit events, metrics, pattern statistics and block responses then
+//! refer to `PI-0001` rather than to the matched text: the ID stays the
+//! same when the signature is reworded, and a signature that is itself
+//! sensitive (a leaked secret, a customer name) is not echoed. Patterns
+//! without an ID are reported as written.
+
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::ops::{Deref, DerefMut};
+
+/// Identifier and description of a blocked pattern
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PatternInfo {
+    /// Stable identifier (e.g. `PI-0001`)
+    pub id: String,
+    /// Human-readable description
+    pub description: Option<String>,
+}
+
+/// A `blocked_patterns` entry as written
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PatternEntry {
+    Plain(String),
+    Described(DescribedPattern),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DescribedPattern {
+    pattern: String,
+    id: String,
+    #[serde(default)]
+    description: Option<String>,
+}
+
+/// Blocked patterns with their identifiers
+///
+/// Dereferences to the pattern strings, which the scanners compile.
+#[derive(Clone, Debug, Default)]
+pub struct BlockedPatterns {
+    patterns: Vec<String>,
+    /// Identifier and description, by pattern
+    info: BTreeMap<String, PatternInfo>,
+}
+
+impl BlockedPatterns {
+    /// Identifier and description of a pattern, if it has an ID
+    pub fn info(&self, pattern: &str) -> Option<&PatternInfo> {
+        self.info.get(pattern)
+    }
+
+    /// How a pattern is reported: its ID, or the pattern itself
+    pub fn label<'a>(&'a self, pattern: &'a str) -> &'a str {
+        self.info(pattern).map_or(pattern, |info| info.id.as_str())
+    }
+
+    /// Labels of every pattern, in order
+    pub fn labels(&self) -> Vec<String> {
+        self.patterns.iter().map(|p| self.label(p).to_string()).collect()
+    }
+
+    /// Identifier of every pattern that has one, by pattern
+    pub fn ids(&self) -> BTreeMap<String, String> {
+        self.info.iter().map(|(pattern, info)| (pattern.clone(), info.id.clone())).collect()
+    }
+
+    /// Validate the identifiers, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        let mut seen: BTreeMap<&str, &str> = BTreeMap::new();
+        for (pattern, info) in &self.info {
+            if info.id.trim().is_empty() {
+                diagnostics.push(format!("blocked_patterns: empty id for '{}'", pattern));
+            } else if seen.insert(info.id.as_str(), pattern.as_str()).is_some() {
+                diagnostics.push(format!("blocked_patterns: duplicate id {}", info.id));
+            }
+        }
+        diagnostics
+    }
+}
+
+impl Deref for BlockedPatterns {
+    type Target = Vec<String>;
+
+    fn deref(&self) -> &Vec<String> {
+        &self.patterns
+    }
+}
+
+impl DerefMut for BlockedPatterns {
+    fn deref_mut(&mut self) -> &mut Vec<String> {
+        &mut self.patterns
+    }
+}
+
+impl From<Vec<String>> for BlockedPatterns {
+    fn from(patterns: Vec<String>) -> Self {
+        Self { patterns, info: BTreeMap::new() }
+    }
+}
+
+impl<T> PartialEq<Vec<T>> for BlockedPatterns
+where
+    String: PartialEq<T>,
+{
+    fn eq(&self, other: &Vec<T>) -> bool {
+        self.patterns == *other
+    }
+}
+
+impl<'de> Deserialize<'de> for BlockedPatterns {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut blocked = Self::default();
+        for entry in Vec::<PatternEntry>::deserialize(deserializer)? {
+            match entry {
+                PatternEntry::Plain(pattern) => blocked.patterns.push(pattern),
+                PatternEntry::Described(described) => {
+                    let info = PatternInfo {
+                        id: described.id,
+                        description: described.description,
+                    };
+                    blocked.info.insert(described.pattern.clone(), info);
+                    blocked.patterns.push(described.pattern);
+                }
+            }
+        }
+        Ok(blocked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mixed_entries() {
+        let json = r#"["jailbreak",
+            {"pattern": "ignore previous", "id": "PI-0001", "description": "Override"}]"#;
+        let blocked: BlockedPatterns = serde_json::from_str(json).unwrap();
+        assert_eq!(blocked, vec!["jailbreak", "ignore previous"]);
+        assert_eq!(blocked.label("ignore previous"), "PI-0001");
+        assert_eq!(blocked.label("jailbreak"), "jailbreak");
+        let info = blocked.info("ignore previous").unwrap();
+        assert_eq!(info.description.as_deref(), Some("Override"));
+        assert_eq!(blocked.labels(), ["jailbreak", "PI-0001"]);
+        assert!(blocked.validate().is_empty());
+    }
+
+    #[test]
+    fn test_validate() {
+        let json = r#"[{"pattern": "a", "id": "PI-1"}, {"pattern": "b", "id": "PI-1"},
+            {"pattern": "c", "id": " "}]"#;
+        let blocked: BlockedPatterns = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            blocked.validate(),
+            vec![
+                "blocked_patterns: duplicate id PI-1".to_string(),
+                "blocked_patterns: empty id for 'c'".to_string(),
+            ]
+        );
+        assert!(serde_json::from_str::<BlockedPatterns>(r#"[{"pattern": "a"}]"#).is_err());
+    }
+}
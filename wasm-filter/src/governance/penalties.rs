@@ -0,0 +1,294 @@
+//! Progressive Agent Penalties
+//!
+//! Tracks policy violations per agent (the `agent_id_header` value) in
+//! shared data, so every worker sees the same history, and escalates:
+//! violations are first only logged, from `rate_limit_after` the agent gets
+//! one request per cool-down, and at `block_after` it is blocked for
+//! `block_secs`. When the block expires the agent starts over with a clean
+//! record; a record without new violations is forgotten after `decay_secs`.
+//!
+//! Records are hashed into a fixed number of buckets of bounded size, like
+//! session state. An operator can clear an agent's record through the admin
+//! endpoint (`DELETE <admin_path>?agent=<id>`).
+
+use crate::shared::{SharedError, SharedStore};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Number of shared data buckets
+const BUCKETS: u64 = 256;
+
+/// Agents kept per bucket
+const MAX_AGENTS_PER_BUCKET: usize = 32;
+
+/// Longest agent id tracked
+pub const MAX_AGENT_ID_LEN: usize = 128;
+
+/// Progressive penalty configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PenaltyConfig {
+    /// Violations after which the agent is limited to one request per cool-down
+    pub rate_limit_after: u32,
+    /// Violations after which the agent is blocked
+    pub block_after: u32,
+    /// Cool-down between requests of a rate-limited agent, in seconds
+    pub cooldown_secs: u64,
+    /// Length of a block, in seconds
+    pub block_secs: u64,
+    /// Time without violations after which a record is forgotten, in seconds
+    pub decay_secs: u64,
+}
+
+impl Default for PenaltyConfig {
+    fn default() -> Self {
+        Self {
+            rate_limit_after: 3,
+            block_after: 5,
+            cooldown_secs: 30,
+            block_secs: 900,
+            decay_secs: 24 * 3600,
+        }
+    }
+}
+
+impl PenaltyConfig {
+    /// Validate the configuration, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        if self.rate_limit_after == 0 {
+            diagnostics
+                .push("agent_penalties.rate_limit_after: must be greater than 0".to_string());
+        }
+        if self.rate_limit_after > self.block_after {
+            diagnostics.push(
+                "agent_penalties: rate_limit_after <= block_after required".to_string(),
+            );
+        }
+        if self.block_secs == 0 {
+            diagnostics.push("agent_penalties.block_secs: must be greater than 0".to_string());
+        }
+        if self.decay_secs == 0 {
+            diagnostics.push("agent_penalties.decay_secs: must be greater than 0".to_string());
+        }
+        diagnostics
+    }
+
+    /// Penalty currently applied to an agent
+    pub fn penalty(&self, record: &AgentRecord, now_secs: u64) -> Penalty {
+        if record.blocked_until_secs > now_secs {
+            return Penalty::Blocked { retry_after_secs: record.blocked_until_secs - now_secs };
+        }
+        if record.violations >= self.rate_limit_after {
+            let wait = (record.last_request_secs + self.cooldown_secs).saturating_sub(now_secs);
+            return Penalty::RateLimited { retry_after_secs: wait };
+        }
+        if record.violations > 0 {
+            return Penalty::Logged;
+        }
+        Penalty::None
+    }
+}
+
+/// Violation history of one agent
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentRecord {
+    /// Violations on record
+    pub violations: u32,
+    /// Latest violation (Unix seconds)
+    pub last_violation_secs: u64,
+    /// Start of the latest request forwarded while rate-limited (Unix seconds)
+    pub last_request_secs: u64,
+    /// End of the current block (Unix seconds, 0 when not blocked)
+    pub blocked_until_secs: u64,
+}
+
+/// Escalation step of an agent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Penalty {
+    /// No violations on record
+    None,
+    /// Violations on record, requests still forwarded
+    Logged,
+    /// One request per cool-down
+    RateLimited {
+        /// Seconds until the next request is accepted (0 = accepted now)
+        retry_after_secs: u64,
+    },
+    /// Every request rejected until the block expires
+    Blocked {
+        /// Seconds until the block expires
+        retry_after_secs: u64,
+    },
+}
+
+impl Penalty {
+    /// Stable lowercase name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Penalty::None => "none",
+            Penalty::Logged => "logged",
+            Penalty::RateLimited { .. } => "rate_limited",
+            Penalty::Blocked { .. } => "blocked",
+        }
+    }
+}
+
+/// Whether an agent id is tracked
+pub fn valid_agent_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= MAX_AGENT_ID_LEN
+}
+
+/// Record of an agent (default when unknown, decayed or past its block)
+pub fn lookup(
+    store: &impl SharedStore,
+    config: &PenaltyConfig,
+    agent: &str,
+    now_secs: u64,
+) -> AgentRecord {
+    parse(store.get(&bucket_key(agent)).0.as_deref())
+        .remove(agent)
+        .filter(|r| !expired(r, config, now_secs))
+        .unwrap_or_default()
+}
+
+/// Update an agent's record, returning the new record
+pub fn record<F>(
+    store: &impl SharedStore,
+    config: &PenaltyConfig,
+    agent: &str,
+    now_secs: u64,
+    mut update: F,
+) -> Result<AgentRecord, SharedError>
+where
+    F: FnMut(&mut AgentRecord),
+{
+    let mut updated = AgentRecord::default();
+    store.update(&bucket_key(agent), |current| {
+        let mut agents = parse(current);
+        agents.retain(|_, r| !expired(r, config, now_secs));
+        let mut record = agents.remove(agent).unwrap_or_default();
+        update(&mut record);
+        if agents.len() >= MAX_AGENTS_PER_BUCKET {
+            let oldest = agents
+                .iter()
+                .min_by_key(|(_, r)| r.last_violation_secs)
+                .map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                agents.remove(&oldest);
+            }
+        }
+        agents.insert(agent.to_string(), record.clone());
+        updated = record;
+        serde_json::to_vec(&agents).unwrap_or_default()
+    })?;
+    Ok(updated)
+}
+
+/// Count a violation, starting a block when the agent reaches `block_after`
+pub fn record_violation(
+    store: &impl SharedStore,
+    config: &PenaltyConfig,
+    agent: &str,
+    now_secs: u64,
+) -> Result<AgentRecord, SharedError> {
+    record(store, config, agent, now_secs, |r| {
+        r.violations += 1;
+        r.last_violation_secs = now_secs;
+        if r.violations >= config.block_after && r.blocked_until_secs <= now_secs {
+            r.blocked_until_secs = now_secs + config.block_secs;
+        }
+    })
+}
+
+/// Forget an agent's record; false if there was none
+pub fn reset(store: &impl SharedStore, agent: &str) -> Result<bool, SharedError> {
+    let mut found = false;
+    store.update(&bucket_key(agent), |current| {
+        let mut agents = parse(current);
+        found = agents.remove(agent).is_some();
+        serde_json::to_vec(&agents).unwrap_or_default()
+    })?;
+    Ok(found)
+}
+
+fn expired(record: &AgentRecord, config: &PenaltyConfig, now_secs: u64) -> bool {
+    let block_over = record.blocked_until_secs != 0 && record.blocked_until_secs <= now_secs;
+    block_over || now_secs.saturating_sub(record.last_violation_secs) > config.decay_secs
+}
+
+/// Shared data key of the bucket holding an agent (FNV-1a hash of the id)
+fn bucket_key(agent: &str) -> String {
+    let hash = agent.bytes().fold(0xcbf2_9ce4_8422_2325u64, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("ai_guard.agents.{}", hash % BUCKETS)
+}
+
+fn parse(value: Option<&[u8]>) -> BTreeMap<String, AgentRecord> {
+    value
+        .and_then(|v| serde_json::from_slice(v).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::MemorySharedStore;
+
+    #[test]
+    fn test_escalation() {
+        let store = MemorySharedStore::new();
+        let config = PenaltyConfig::default();
+        let penalty = |now| config.penalty(&lookup(&store, &config, "agent-1", now), now);
+        assert_eq!(penalty(1000), Penalty::None);
+
+        record_violation(&store, &config, "agent-1", 1010).unwrap();
+        assert_eq!(penalty(1010), Penalty::Logged);
+
+        record_violation(&store, &config, "agent-1", 1011).unwrap();
+        record_violation(&store, &config, "agent-1", 1012).unwrap();
+        assert_eq!(penalty(1012), Penalty::RateLimited { retry_after_secs: 0 });
+        record(&store, &config, "agent-1", 1020, |r| r.last_request_secs = 1020).unwrap();
+        assert_eq!(penalty(1030), Penalty::RateLimited { retry_after_secs: 20 });
+
+        record_violation(&store, &config, "agent-1", 1040).unwrap();
+        let record = record_violation(&store, &config, "agent-1", 1041).unwrap();
+        assert_eq!(record.blocked_until_secs, 1941);
+        assert_eq!(penalty(1100), Penalty::Blocked { retry_after_secs: 841 });
+        // Other agents are unaffected
+        let other = lookup(&store, &config, "agent-2", 1100);
+        assert_eq!(config.penalty(&other, 1100), Penalty::None);
+
+        // The block expires with the record
+        assert_eq!(penalty(1941), Penalty::None);
+    }
+
+    #[test]
+    fn test_decay_and_reset() {
+        let store = MemorySharedStore::new();
+        let config = PenaltyConfig { decay_secs: 60, ..Default::default() };
+        record_violation(&store, &config, "a", 0).unwrap();
+        assert_eq!(lookup(&store, &config, "a", 60).violations, 1);
+        assert_eq!(lookup(&store, &config, "a", 61), AgentRecord::default());
+
+        record_violation(&store, &config, "a", 100).unwrap();
+        assert!(reset(&store, "a").unwrap());
+        assert!(!reset(&store, "a").unwrap());
+        assert_eq!(lookup(&store, &config, "a", 100), AgentRecord::default());
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = PenaltyConfig { rate_limit_after: 6, block_secs: 0, ..Default::default() };
+        assert_eq!(
+            config.validate(),
+            vec![
+                "agent_penalties: rate_limit_after <= block_after required".to_string(),
+                "agent_penalties.block_secs: must be greater than 0".to_string(),
+            ]
+        );
+        assert!(valid_agent_id("agent-1"));
+        assert!(!valid_agent_id(""));
+    }
+}
@@ -5,7 +5,71 @@
 //!
 //! Uses FSM-based pattern matching (no regex) for constant memory.
 
-use crate::streaming::{Pattern, PatternScanner, ScanResult};
+use serde::Deserialize;
+use std::net::Ipv6Addr;
+
+/// Longest domain name (RFC 1035)
+const MAX_DOMAIN_LEN: usize = 253;
+
+/// Longest domain label (RFC 1035)
+const MAX_LABEL_LEN: usize = 63;
+
+/// Longest email local part (RFC 5321)
+const MAX_LOCAL_LEN: usize = 64;
+
+/// Bytes before a match searched for context keywords
+const CONTEXT_BEFORE: usize = 48;
+
+/// Bytes after a match searched for context keywords
+const CONTEXT_AFTER: usize = 24;
+
+/// Confidence added by a keyword of the match's type nearby (or removed by
+/// a keyword suggesting a non-personal number)
+const CONTEXT_BOOST: f32 = 0.3;
+
+/// Keywords raising the confidence of any match
+const GENERIC_KEYWORDS: &[&str] = &["dob", "date of birth", "born", "personal", "confidential"];
+
+/// Keywords before a match lowering its confidence: numbers that identify things, not people
+const NEGATIVE_KEYWORDS: &[&str] =
+    &["order", "invoice", "tracking", "ticket", "ref", "serial", "sku", "version", "build"];
+
+/// Verhoeff multiplication table (Aadhaar check digit)
+const VERHOEFF_D: [[u8; 10]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9],
+    [1, 2, 3, 4, 0, 6, 7, 8, 9, 5],
+    [2, 3, 4, 0, 1, 7, 8, 9, 5, 6],
+    [3, 4, 0, 1, 2, 8, 9, 5, 6, 7],
+    [4, 0, 1, 2, 3, 9, 5, 6, 7, 8],
+    [5, 9, 8, 7, 6, 0, 4, 3, 2, 1],
+    [6, 5, 9, 8, 7, 1, 0, 4, 3, 2],
+    [7, 6, 5, 9, 8, 2, 1, 0, 4, 3],
+    [8, 7, 6, 5, 9, 3, 2, 1, 0, 4],
+    [9, 8, 7, 6, 5, 4, 3, 2, 1, 0],
+];
+
+/// Verhoeff permutation table
+const VERHOEFF_P: [[u8; 10]; 8] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9],
+    [1, 5, 7, 6, 2, 8, 3, 0, 9, 4],
+    [5, 8, 0, 3, 7, 9, 6, 1, 4, 2],
+    [8, 9, 1, 6, 0, 4, 3, 5, 2, 7],
+    [9, 4, 5, 3, 1, 2, 6, 8, 7, 0],
+    [4, 2, 8, 6, 5, 7, 3, 9, 0, 1],
+    [2, 7, 9, 3, 8, 0, 6, 4, 1, 5],
+    [7, 0, 4, 6, 9, 1, 3, 2, 5, 8],
+];
+
+/// NINo prefixes never issued
+const NINO_INVALID_PREFIXES: &[&[u8]] = &[b"BG", b"GB", b"KN", b"NK", b"NT", b"TN", b"ZZ"];
+
+/// File extensions that look like TLDs in asset names (`icon@2x.png`)
+const ASSET_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "svg", "webp", "ico", "css", "js", "ts", "json", "map",
+];
+
+/// A national identifier check: length of the match at the start of the input
+type Check = fn(&[u8]) -> Option<usize>;
 
 /// PII types that can be detected
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -18,9 +82,46 @@ pub enum PiiType {
     Email,
     /// Phone Number (various formats)
     Phone,
+    /// IPv4 or IPv6 address (optionally with a CIDR prefix length)
+    IpAddress,
+    /// MAC address (colon, hyphen or Cisco dotted notation)
+    MacAddress,
+    /// Latitude/longitude pair in decimal degrees
+    GeoCoordinates,
+    /// UK National Insurance number (AB 12 34 56 C)
+    UkNino,
+    /// Canadian Social Insurance Number (Luhn-checked)
+    CaSin,
+    /// Indian Aadhaar number (Verhoeff-checked)
+    InAadhaar,
+    /// German tax identification number (Steuer-IdNr, ISO 7064 checked)
+    DeTaxId,
+    /// International Bank Account Number (mod-97 checked)
+    Iban,
 }
 
 impl PiiType {
+    /// Every detectable type
+    pub const ALL: [PiiType; 12] = [
+        PiiType::Ssn,
+        PiiType::CreditCard,
+        PiiType::Email,
+        PiiType::Phone,
+        PiiType::IpAddress,
+        PiiType::MacAddress,
+        PiiType::GeoCoordinates,
+        PiiType::UkNino,
+        PiiType::CaSin,
+        PiiType::InAadhaar,
+        PiiType::DeTaxId,
+        PiiType::Iban,
+    ];
+
+    /// Type with the given `as_str` name
+    pub fn from_name(name: &str) -> Option<PiiType> {
+        Self::ALL.into_iter().find(|t| t.as_str() == name)
+    }
+
     /// Get the redaction placeholder for this PII type
     pub fn placeholder(&self) -> &'static str {
         match self {
@@ -28,6 +129,98 @@ impl PiiType {
             PiiType::CreditCard => "[CREDIT CARD REDACTED]",
             PiiType::Email => "[EMAIL REDACTED]",
             PiiType::Phone => "[PHONE REDACTED]",
+            PiiType::IpAddress => "[IP ADDRESS REDACTED]",
+            PiiType::MacAddress => "[MAC ADDRESS REDACTED]",
+            PiiType::GeoCoordinates => "[LOCATION REDACTED]",
+            PiiType::UkNino => "[NINO REDACTED]",
+            PiiType::CaSin => "[SIN REDACTED]",
+            PiiType::InAadhaar => "[AADHAAR REDACTED]",
+            PiiType::DeTaxId => "[TAX ID REDACTED]",
+            PiiType::Iban => "[IBAN REDACTED]",
+        }
+    }
+
+    /// Confidence of a match without context: checksummed and structured
+    /// formats are reliable, bare digit runs are not
+    pub fn base_confidence(&self) -> f32 {
+        match self {
+            PiiType::InAadhaar | PiiType::DeTaxId | PiiType::Iban | PiiType::UkNino => 0.9,
+            PiiType::Email | PiiType::MacAddress => 0.85,
+            PiiType::CaSin | PiiType::Ssn | PiiType::IpAddress => 0.7,
+            PiiType::CreditCard | PiiType::GeoCoordinates => 0.6,
+            PiiType::Phone => 0.4,
+        }
+    }
+
+    /// Nearby words that make a match of this type more likely to be PII
+    pub fn context_keywords(&self) -> &'static [&'static str] {
+        match self {
+            PiiType::Ssn => &["ssn", "social security", "taxpayer"],
+            PiiType::CreditCard => {
+                &["card", "credit", "debit", "visa", "mastercard", "amex", "cvv", "expir"]
+            }
+            PiiType::Email => &["email", "e-mail", "mail", "contact", "reach"],
+            PiiType::Phone => {
+                &["phone", "call", "tel", "mobile", "cell", "fax", "text me", "whatsapp"]
+            }
+            PiiType::IpAddress => &["ip", "host", "addr", "client", "src", "dst", "from"],
+            PiiType::MacAddress => &["mac", "ether", "hwaddr", "bssid", "device"],
+            PiiType::GeoCoordinates => &["lat", "lon", "location", "coordinates", "gps", "home"],
+            PiiType::UkNino => &["national insurance", "nino", "ni number", "ni:"],
+            PiiType::CaSin => &["sin", "social insurance"],
+            PiiType::InAadhaar => &["aadhaar", "uid"],
+            PiiType::DeTaxId => &["steuer", "tax id", "idnr"],
+            PiiType::Iban => &["iban", "account", "bank", "transfer"],
+        }
+    }
+
+    /// Stable snake_case name (as used in `pii_types`)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PiiType::Ssn => "ssn",
+            PiiType::CreditCard => "credit_card",
+            PiiType::Email => "email",
+            PiiType::Phone => "phone",
+            PiiType::IpAddress => "ip_address",
+            PiiType::MacAddress => "mac_address",
+            PiiType::GeoCoordinates => "geo_coordinates",
+            PiiType::UkNino => "uk_nino",
+            PiiType::CaSin => "ca_sin",
+            PiiType::InAadhaar => "in_aadhaar",
+            PiiType::DeTaxId => "de_tax_id",
+            PiiType::Iban => "iban",
+        }
+    }
+}
+
+/// Deployment region, selecting the national identifiers detected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PiiRegion {
+    /// United States: SSN
+    Us,
+    /// United Kingdom: National Insurance number, IBAN
+    Uk,
+    /// Canada: Social Insurance Number
+    Ca,
+    /// India: Aadhaar
+    In,
+    /// Germany: tax ID, IBAN
+    De,
+    /// European Union: IBAN
+    Eu,
+}
+
+impl PiiRegion {
+    /// National identifiers detected for the region
+    pub fn types(&self) -> &'static [PiiType] {
+        match self {
+            PiiRegion::Us => &[PiiType::Ssn],
+            PiiRegion::Uk => &[PiiType::UkNino, PiiType::Iban],
+            PiiRegion::Ca => &[PiiType::CaSin],
+            PiiRegion::In => &[PiiType::InAadhaar],
+            PiiRegion::De => &[PiiType::DeTaxId, PiiType::Iban],
+            PiiRegion::Eu => &[PiiType::Iban],
         }
     }
 }
@@ -37,12 +230,15 @@ impl PiiType {
 pub struct PiiMatch {
     /// Type of PII detected
     pub pii_type: PiiType,
-    /// Start position in the text
+    /// Start position in the text (byte offset)
     pub start: usize,
-    /// End position in the text
+    /// End position in the text (byte offset)
     pub end: usize,
     /// The matched value (for logging, may be partial)
     pub value_hint: String,
+    /// Likelihood the match is PII (0-1): the type's base confidence,
+    /// adjusted by keywords near the match
+    pub confidence: f32,
 }
 
 /// PII Redactor
@@ -54,10 +250,17 @@ pub struct PiiRedactor {
     log_detections: bool,
     /// Action to take on detection
     action: PiiAction,
+    /// Email domains not treated as PII (lowercase; subdomains included)
+    allowed_domains: Vec<String>,
+    /// Regions whose national identifiers are detected
+    regions: Vec<PiiRegion>,
+    /// Matches below this confidence are reported by `scan` but not acted on
+    min_confidence: f32,
 }
 
 /// Action to take when PII is detected
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum PiiAction {
     /// Just log the detection
     Log,
@@ -67,37 +270,95 @@ pub enum PiiAction {
     Block,
 }
 
+impl PiiAction {
+    /// Stable lowercase name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PiiAction::Log => "log",
+            PiiAction::Redact => "redact",
+            PiiAction::Block => "block",
+        }
+    }
+}
+
 impl PiiRedactor {
     /// Create a new PII redactor
     pub fn new(action: PiiAction) -> Self {
         Self {
             log_detections: true,
             action,
+            allowed_domains: Vec::new(),
+            regions: vec![PiiRegion::Us],
+            min_confidence: 0.0,
         }
     }
 
-    /// Scan text for PII
-    pub fn scan(&self, text: &str) -> Vec<PiiMatch> {
-        let mut matches = Vec::new();
+    /// Act only on matches of at least this confidence (redaction, `contains_pii`)
+    pub fn with_min_confidence(mut self, min_confidence: f32) -> Self {
+        self.min_confidence = min_confidence;
+        self
+    }
 
-        // Scan for SSN patterns (XXX-XX-XXXX)
-        matches.extend(self.scan_ssn(text));
+    /// Detect the national identifiers of these regions (US only by default)
+    pub fn with_regions(mut self, regions: &[PiiRegion]) -> Self {
+        self.regions = regions.to_vec();
+        self
+    }
 
-        // Scan for credit card patterns
-        matches.extend(self.scan_credit_card(text));
+    fn detects(&self, pii_type: PiiType) -> bool {
+        self.regions.iter().any(|r| r.types().contains(&pii_type))
+    }
 
-        // Scan for email patterns
-        matches.extend(self.scan_email(text));
+    /// Exempt addresses at these domains and their subdomains (e.g. corporate domains)
+    pub fn with_allowed_domains(mut self, domains: &[String]) -> Self {
+        self.allowed_domains = domains
+            .iter()
+            .map(|d| d.trim_start_matches('@').trim_end_matches('.').to_ascii_lowercase())
+            .collect();
+        self
+    }
 
-        // Scan for phone patterns
-        matches.extend(self.scan_phone(text));
+    /// Scan text for PII
+    pub fn scan(&self, text: &str) -> Vec<PiiMatch> {
+        // Checksummed national identifiers first, phone patterns last: card
+        // numbers, IBANs and dotted IPv4 addresses also look like long phone
+        // numbers. A match overlapping an earlier one is dropped.
+        let found = [
+            self.scan_national_ids(text),
+            self.scan_ssn(text),
+            self.scan_credit_card(text),
+            self.scan_email(text),
+            self.scan_ip(text),
+            self.scan_mac(text),
+            self.scan_geo(text),
+            self.scan_phone(text),
+        ];
+        let mut matches: Vec<PiiMatch> = Vec::new();
+        for mut candidate in found.into_iter().flatten() {
+            if !matches.iter().any(|m| candidate.start < m.end && m.start < candidate.end) {
+                candidate.confidence = context_confidence(text, &candidate);
+                matches.push(candidate);
+            }
+        }
 
         matches
     }
 
+    /// Matches at or above the minimum confidence
+    pub fn scan_confident(&self, text: &str) -> Vec<PiiMatch> {
+        let mut matches = self.scan(text);
+        matches.retain(|m| m.confidence >= self.min_confidence);
+        matches
+    }
+
+    /// Replace detected PII with the type's placeholder
+    pub fn redact(&self, text: &str) -> String {
+        redact_matches(text, &self.scan_confident(text))
+    }
+
     /// Check if any PII is present
     pub fn contains_pii(&self, text: &str) -> bool {
-        !self.scan(text).is_empty()
+        !self.scan_confident(text).is_empty()
     }
 
     /// Get the configured action
@@ -105,9 +366,17 @@ impl PiiRedactor {
         self.action
     }
 
+    /// Whether detections should be logged
+    pub fn log_detections(&self) -> bool {
+        self.log_detections
+    }
+
     // Simple SSN detection (XXX-XX-XXXX)
     fn scan_ssn(&self, text: &str) -> Vec<PiiMatch> {
         let mut matches = Vec::new();
+        if !self.detects(PiiType::Ssn) {
+            return matches;
+        }
         let bytes = text.as_bytes();
         let mut i = 0;
 
@@ -119,6 +388,7 @@ impl PiiRedactor {
                     start: i,
                     end: i + 11,
                     value_hint: format!("***-**-{}", &text[i + 7..i + 11]),
+                    confidence: PiiType::Ssn.base_confidence(),
                 });
                 i += 11;
             } else {
@@ -152,15 +422,19 @@ impl PiiRedactor {
     fn scan_credit_card(&self, text: &str) -> Vec<PiiMatch> {
         let mut matches = Vec::new();
         let chars: Vec<char> = text.chars().collect();
+        // Byte offset of each char, and of the end of the text
+        let offsets: Vec<usize> =
+            text.char_indices().map(|(i, _)| i).chain([text.len()]).collect();
         let mut i = 0;
 
         while i < chars.len() {
             if let Some((end, card_hint)) = self.is_credit_card_pattern(&chars[i..]) {
                 matches.push(PiiMatch {
                     pii_type: PiiType::CreditCard,
-                    start: i,
-                    end: i + end,
+                    start: offsets[i],
+                    end: offsets[i + end],
                     value_hint: card_hint,
+                    confidence: PiiType::CreditCard.base_confidence(),
                 });
                 i += end;
             } else {
@@ -172,13 +446,14 @@ impl PiiRedactor {
     }
 
     fn is_credit_card_pattern(&self, chars: &[char]) -> Option<(usize, String)> {
+        if !chars.first().is_some_and(char::is_ascii_digit) {
+            return None;
+        }
         let mut digit_count = 0;
-        let mut end = 0;
 
         for (i, &c) in chars.iter().enumerate() {
             if c.is_ascii_digit() {
                 digit_count += 1;
-                end = i + 1;
             } else if c == '-' || c == ' ' {
                 // Allow separators
                 continue;
@@ -187,42 +462,197 @@ impl PiiRedactor {
             }
 
             if digit_count == 16 {
-                return Some((end, "****-****-****-****".to_string()));
+                return Some((i + 1, "****-****-****-****".to_string()));
             }
         }
 
         None
     }
 
-    // Simple email detection (contains @ with text before and after)
+    // Email detection: a valid local part, a dotted domain with an alphabetic TLD
     fn scan_email(&self, text: &str) -> Vec<PiiMatch> {
         let mut matches = Vec::new();
+        let bytes = text.as_bytes();
 
         for (i, _) in text.match_indices('@') {
-            // Find start of email (walk back to whitespace or start)
-            let start = text[..i]
-                .rfind(|c: char| c.is_whitespace() || c == '<' || c == '"')
+            // Local part: walk back over address characters
+            let start = bytes[..i]
+                .iter()
+                .rposition(|&b| !is_local_byte(b))
                 .map(|p| p + 1)
                 .unwrap_or(0);
-
-            // Find end of email (walk forward to whitespace or end)
-            let after_at = &text[i + 1..];
-            let end = after_at
-                .find(|c: char| c.is_whitespace() || c == '>' || c == '"')
+            // Domain: walk forward over name characters, minus trailing punctuation
+            let domain_end = bytes[i + 1..]
+                .iter()
+                .position(|&b| !(b.is_ascii_alphanumeric() || b == b'.' || b == b'-'))
                 .map(|p| i + 1 + p)
                 .unwrap_or(text.len());
+            let domain = text[i + 1..domain_end].trim_end_matches(['.', '-']);
+            let end = i + 1 + domain.len();
 
-            // Validate there's text before @ and a dot after
-            if i > start && end > i + 1 && text[i + 1..end].contains('.') {
+            // @mentions and package scopes (@scope/name) have no local part
+            if !is_valid_local(&text[start..i]) || !is_valid_domain(domain) {
+                continue;
+            }
+            if self.is_allowed_domain(domain) {
+                continue;
+            }
+            matches.push(PiiMatch {
+                pii_type: PiiType::Email,
+                start,
+                end,
+                value_hint: "[EMAIL]".to_string(),
+                confidence: PiiType::Email.base_confidence(),
+            });
+        }
+
+        matches
+    }
+
+    fn is_allowed_domain(&self, domain: &str) -> bool {
+        let domain = domain.to_ascii_lowercase();
+        self.allowed_domains.iter().any(|allowed| {
+            domain == *allowed
+                || domain.strip_suffix(allowed.as_str()).is_some_and(|sub| sub.ends_with('.'))
+        })
+    }
+
+    // National identifiers of the configured regions, validated by their check digits
+    fn scan_national_ids(&self, text: &str) -> Vec<PiiMatch> {
+        let bytes = text.as_bytes();
+        let mut matches = Vec::new();
+        let mut i = 0;
+        while i < bytes.len() {
+            let found = self.national_id_at(bytes, i).filter(|(_, end)| is_word(bytes, i, *end));
+            match found {
+                Some((pii_type, end)) => {
+                    matches.push(PiiMatch {
+                        pii_type,
+                        start: i,
+                        end,
+                        value_hint: format!("[{}]", pii_type.as_str().to_ascii_uppercase()),
+                        confidence: pii_type.base_confidence(),
+                    });
+                    i = end;
+                }
+                None => i += 1,
+            }
+        }
+        matches
+    }
+
+    fn national_id_at(&self, bytes: &[u8], i: usize) -> Option<(PiiType, usize)> {
+        if i > 0 && bytes[i - 1].is_ascii_alphanumeric() {
+            return None;
+        }
+        let checks: [(PiiType, Check); 5] = [
+            (PiiType::Iban, iban_at),
+            (PiiType::UkNino, nino_at),
+            (PiiType::InAadhaar, |b| digit_groups(b, &[4, 4, 4], is_aadhaar)),
+            (PiiType::DeTaxId, |b| {
+                digit_groups(b, &[11], is_de_tax_id)
+                    .or_else(|| digit_groups(b, &[2, 3, 3, 3], is_de_tax_id))
+            }),
+            (PiiType::CaSin, |b| digit_groups(b, &[3, 3, 3], is_sin)),
+        ];
+        checks
+            .iter()
+            .filter(|(pii_type, _)| self.detects(*pii_type))
+            .find_map(|(pii_type, at)| at(&bytes[i..]).map(|len| (*pii_type, i + len)))
+    }
+
+    // IPv6 then IPv4 addresses, as whole words
+    fn scan_ip(&self, text: &str) -> Vec<PiiMatch> {
+        let bytes = text.as_bytes();
+        let mut matches = Vec::new();
+        let ipv6 = |b: u8| b.is_ascii_hexdigit() || b == b':' || b == b'.';
+        for (start, end) in runs(bytes, ipv6) {
+            let end = start + text[start..end].trim_end_matches('.').len();
+            let candidate = &text[start..end];
+            let digits = candidate.bytes().filter(u8::is_ascii_hexdigit).count();
+            if candidate.matches(':').count() >= 2
+                && digits >= 4
+                && candidate.parse::<Ipv6Addr>().is_ok()
+                && is_word(bytes, start, end)
+            {
+                let end = with_prefix_len(bytes, end, 128);
                 matches.push(PiiMatch {
-                    pii_type: PiiType::Email,
+                    pii_type: PiiType::IpAddress,
                     start,
                     end,
-                    value_hint: "[EMAIL]".to_string(),
+                    value_hint: "[IPV6]".to_string(),
+                    confidence: PiiType::IpAddress.base_confidence(),
+                });
+            }
+        }
+        let ipv4 = |b: u8| b.is_ascii_digit() || b == b'.';
+        for (start, end) in runs(bytes, ipv4) {
+            let end = start + text[start..end].trim_end_matches('.').len();
+            let overlaps = matches.iter().any(|m| start < m.end && m.start < end);
+            if overlaps || !is_ipv4(&text[start..end]) || !is_word(bytes, start, end) {
+                continue;
+            }
+            let end = with_prefix_len(bytes, end, 32);
+            matches.push(PiiMatch {
+                pii_type: PiiType::IpAddress,
+                start,
+                end,
+                value_hint: format!("{}.x.x.x", text[start..].split('.').next().unwrap_or("")),
+                confidence: PiiType::IpAddress.base_confidence(),
+            });
+        }
+        matches
+    }
+
+    // MAC addresses: six hex pairs (aa:bb:cc:dd:ee:ff, aa-bb-...) or aabb.ccdd.eeff
+    fn scan_mac(&self, text: &str) -> Vec<PiiMatch> {
+        let bytes = text.as_bytes();
+        let mut matches = Vec::new();
+        let mut i = 0;
+        while i < bytes.len() {
+            let len = if is_mac_pattern(&bytes[i..]) {
+                17
+            } else if is_dotted_mac_pattern(&bytes[i..]) {
+                14
+            } else {
+                0
+            };
+            if len > 0 && is_word(bytes, i, i + len) && !extends(bytes, i, i + len) {
+                matches.push(PiiMatch {
+                    pii_type: PiiType::MacAddress,
+                    start: i,
+                    end: i + len,
+                    value_hint: "**:**:**:**:**:**".to_string(),
+                    confidence: PiiType::MacAddress.base_confidence(),
                 });
+                i += len;
+            } else {
+                i += 1;
             }
         }
+        matches
+    }
 
+    // Latitude/longitude pairs: "37.7749, -122.4194" (three or more decimals each)
+    fn scan_geo(&self, text: &str) -> Vec<PiiMatch> {
+        let bytes = text.as_bytes();
+        let mut matches = Vec::new();
+        let mut i = 0;
+        while i < bytes.len() {
+            match geo_pair(bytes, i) {
+                Some(end) if is_word(bytes, i, end) => {
+                    matches.push(PiiMatch {
+                        pii_type: PiiType::GeoCoordinates,
+                        start: i,
+                        end,
+                        value_hint: "[LAT, LONG]".to_string(),
+                        confidence: PiiType::GeoCoordinates.base_confidence(),
+                    });
+                    i = end;
+                }
+                _ => i += 1,
+            }
+        }
         matches
     }
 
@@ -239,6 +669,7 @@ impl PiiRedactor {
                     start: i,
                     end: i + end,
                     value_hint: hint,
+                    confidence: PiiType::Phone.base_confidence(),
                 });
                 i += end;
             } else {
@@ -251,6 +682,9 @@ impl PiiRedactor {
 
     fn is_phone_pattern(&self, bytes: &[u8]) -> Option<(usize, String)> {
         // Simple pattern: 10+ consecutive digits with optional separators
+        if !bytes.first().is_some_and(|b| b.is_ascii_digit() || *b == b'(') {
+            return None;
+        }
         let mut digit_count = 0;
         let mut end = 0;
 
@@ -259,10 +693,8 @@ impl PiiRedactor {
                 digit_count += 1;
                 end = i + 1;
             } else if b == b'-' || b == b' ' || b == b'(' || b == b')' || b == b'.' {
-                // Allow common phone separators
-                if digit_count > 0 {
-                    end = i + 1;
-                }
+                // Allow common phone separators (the match ends at the last digit)
+                continue;
             } else {
                 break;
             }
@@ -276,6 +708,399 @@ impl PiiRedactor {
     }
 }
 
+/// Replace the given matches of `text` with their types' placeholders
+pub fn redact_matches(text: &str, matches: &[PiiMatch]) -> String {
+    let mut matches: Vec<&PiiMatch> = matches.iter().collect();
+    matches.sort_by_key(|m| m.start);
+    let mut redacted = String::with_capacity(text.len());
+    let mut pos = 0;
+    for m in matches {
+        if m.end <= pos {
+            continue;
+        }
+        redacted.push_str(&text[pos..m.start.max(pos)]);
+        redacted.push_str(m.pii_type.placeholder());
+        pos = pos.max(m.end);
+    }
+    redacted.push_str(&text[pos..]);
+    redacted
+}
+
+/// Base confidence of a match adjusted by keywords around it
+fn context_confidence(text: &str, m: &PiiMatch) -> f32 {
+    let mut start = m.start.saturating_sub(CONTEXT_BEFORE);
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    let mut end = (m.end + CONTEXT_AFTER).min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    let before = text[start..m.start].to_lowercase();
+    let after = text[m.end..end].to_lowercase();
+    let near = |keywords: &[&str]| {
+        keywords.iter().any(|k| has_keyword(&before, k) || has_keyword(&after, k))
+    };
+    let mut confidence = m.pii_type.base_confidence();
+    if near(m.pii_type.context_keywords()) || near(GENERIC_KEYWORDS) {
+        confidence += CONTEXT_BOOST;
+    }
+    // "Order 555-123-4567": labels come first, so only the text before counts
+    if NEGATIVE_KEYWORDS.iter().any(|k| has_keyword(&before, k)) {
+        confidence -= CONTEXT_BOOST;
+    }
+    confidence.clamp(0.0, 1.0)
+}
+
+/// Whether a keyword starts a word of the text (`card` in "Card:", not in "discard")
+fn has_keyword(text: &str, keyword: &str) -> bool {
+    text.match_indices(keyword).any(|(i, _)| {
+        !text[..i].chars().next_back().is_some_and(char::is_alphanumeric)
+    })
+}
+
+/// Length of a number written as digit groups, contiguous or split by one
+/// consistent separator (space or hyphen), whose digits pass `valid`
+fn digit_groups(bytes: &[u8], groups: &[usize], valid: fn(&[u8]) -> bool) -> Option<usize> {
+    let total: usize = groups.iter().sum();
+    let contiguous = bytes.len() >= total
+        && bytes[..total].iter().all(u8::is_ascii_digit)
+        && !bytes.get(total).is_some_and(u8::is_ascii_digit);
+    if contiguous {
+        return valid(&bytes[..total]).then_some(total);
+    }
+    if groups.len() < 2 {
+        return None;
+    }
+    let separator = *bytes.get(groups[0])?;
+    if separator != b' ' && separator != b'-' {
+        return None;
+    }
+    let mut digits = Vec::with_capacity(total);
+    let mut at = 0;
+    for (n, &len) in groups.iter().enumerate() {
+        if n > 0 {
+            if bytes.get(at) != Some(&separator) {
+                return None;
+            }
+            at += 1;
+        }
+        let group = bytes.get(at..at + len)?;
+        if !group.iter().all(u8::is_ascii_digit) {
+            return None;
+        }
+        digits.extend_from_slice(group);
+        at += len;
+    }
+    if bytes.get(at).is_some_and(u8::is_ascii_digit) {
+        return None;
+    }
+    valid(&digits).then_some(at)
+}
+
+/// Luhn checksum over ASCII digits
+fn luhn(digits: &[u8]) -> bool {
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, d)| {
+            let n = (d - b'0') as u32;
+            match i % 2 {
+                0 => n,
+                _ if n * 2 > 9 => n * 2 - 9,
+                _ => n * 2,
+            }
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+/// Canadian SIN: Luhn-valid, not starting with 0 or 8 (unassigned)
+fn is_sin(digits: &[u8]) -> bool {
+    digits[0] != b'0' && digits[0] != b'8' && luhn(digits)
+}
+
+/// Aadhaar: first digit 2-9, Verhoeff-valid
+fn is_aadhaar(digits: &[u8]) -> bool {
+    if digits[0] < b'2' {
+        return false;
+    }
+    let check = digits.iter().rev().enumerate().fold(0u8, |c, (i, d)| {
+        VERHOEFF_D[c as usize][VERHOEFF_P[i % 8][(d - b'0') as usize] as usize]
+    });
+    check == 0
+}
+
+/// German Steuer-IdNr: no leading zero, one digit of the first ten repeated
+/// (twice or three times), and an ISO 7064 MOD 11,10 check digit
+fn is_de_tax_id(digits: &[u8]) -> bool {
+    if digits[0] == b'0' {
+        return false;
+    }
+    let mut counts = [0u8; 10];
+    for d in &digits[..10] {
+        counts[(d - b'0') as usize] += 1;
+    }
+    let repeated = counts.iter().filter(|&&c| c > 1).count();
+    if repeated != 1 || counts.iter().any(|&c| c > 3) {
+        return false;
+    }
+    let mut product = 10u32;
+    for d in &digits[..10] {
+        let mut sum = ((d - b'0') as u32 + product) % 10;
+        if sum == 0 {
+            sum = 10;
+        }
+        product = (sum * 2) % 11;
+    }
+    let check = match 11 - product {
+        10 => 0,
+        check => check,
+    };
+    check == (digits[10] - b'0') as u32
+}
+
+/// UK NINo: two prefix letters, six digits (optionally paired), suffix A-D
+fn nino_at(bytes: &[u8]) -> Option<usize> {
+    let (first, second) = (*bytes.first()?, *bytes.get(1)?);
+    let prefix_ok = first.is_ascii_uppercase()
+        && second.is_ascii_uppercase()
+        && !b"DFIQUV".contains(&first)
+        && !b"DFIOQUV".contains(&second)
+        && !NINO_INVALID_PREFIXES.contains(&&bytes[..2]);
+    if !prefix_ok {
+        return None;
+    }
+    let spaced = bytes.get(2) == Some(&b' ');
+    let mut at = 2;
+    for _ in 0..3 {
+        if spaced {
+            if bytes.get(at) != Some(&b' ') {
+                return None;
+            }
+            at += 1;
+        }
+        if !bytes.get(at..at + 2)?.iter().all(u8::is_ascii_digit) {
+            return None;
+        }
+        at += 2;
+    }
+    if spaced && bytes.get(at) == Some(&b' ') {
+        at += 1;
+    }
+    match bytes.get(at) {
+        Some(b'A'..=b'D') => Some(at + 1),
+        _ => None,
+    }
+}
+
+/// IBAN: country code, check digits and up to 30 letters or digits, optionally
+/// grouped by spaces; the longest mod-97 valid reading is taken
+fn iban_at(bytes: &[u8]) -> Option<usize> {
+    let head = bytes.get(..4)?;
+    if !(head[0].is_ascii_uppercase()
+        && head[1].is_ascii_uppercase()
+        && head[2].is_ascii_digit()
+        && head[3].is_ascii_digit())
+    {
+        return None;
+    }
+    let alnum = |b: &u8| b.is_ascii_uppercase() || b.is_ascii_digit();
+    let mut compact = Vec::with_capacity(34);
+    let mut ends = Vec::new();
+    let mut at = 0;
+    while compact.len() < 34 {
+        let run = bytes[at..].iter().take_while(|b| alnum(b)).count();
+        if run == 0 {
+            break;
+        }
+        compact.extend_from_slice(&bytes[at..at + run]);
+        at += run;
+        ends.push((compact.len(), at));
+        if bytes.get(at) == Some(&b' ') && bytes.get(at + 1).is_some_and(alnum) {
+            at += 1;
+        } else {
+            break;
+        }
+    }
+    ends.iter()
+        .rev()
+        .find(|(len, _)| (15..=34).contains(len) && iban_checksum(&compact[..*len]))
+        .map(|(_, end)| *end)
+}
+
+/// ISO 13616 mod-97 check of a compact IBAN
+fn iban_checksum(iban: &[u8]) -> bool {
+    let rotated = iban[4..].iter().chain(&iban[..4]);
+    let remainder = rotated.fold(0u32, |acc, &b| {
+        if b.is_ascii_digit() {
+            (acc * 10 + (b - b'0') as u32) % 97
+        } else {
+            (acc * 100 + (b - b'A' + 10) as u32) % 97
+        }
+    });
+    remainder == 1
+}
+
+/// Maximal runs of bytes in a class, as byte ranges
+fn runs(bytes: &[u8], class: impl Fn(u8) -> bool) -> Vec<(usize, usize)> {
+    let mut runs = Vec::new();
+    let mut start = None;
+    for (i, &b) in bytes.iter().enumerate() {
+        match (class(b), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                runs.push((s, i));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        runs.push((s, bytes.len()));
+    }
+    runs
+}
+
+/// Whether a match is not glued to a surrounding word (`v1.2.3.4`, `id192.168.0.1x`)
+fn is_word(bytes: &[u8], start: usize, end: usize) -> bool {
+    let word = |b: &u8| b.is_ascii_alphanumeric() || *b == b'_';
+    let before = start.checked_sub(1).and_then(|i| bytes.get(i));
+    !before.is_some_and(word) && !bytes.get(end).is_some_and(word)
+}
+
+/// Whether a hex digit and separator precede or follow (a longer identifier, e.g. EUI-64)
+fn extends(bytes: &[u8], start: usize, end: usize) -> bool {
+    let separator = |b: Option<&u8>| matches!(b, Some(b':' | b'-' | b'.'));
+    let before = start >= 2
+        && separator(bytes.get(start - 1))
+        && bytes[start - 2].is_ascii_hexdigit();
+    let after = separator(bytes.get(end)) && bytes.get(end + 1).is_some_and(u8::is_ascii_hexdigit);
+    before || after
+}
+
+/// End of an address extended by a CIDR prefix length (`/24`) up to `max`
+fn with_prefix_len(bytes: &[u8], end: usize, max: u32) -> usize {
+    if bytes.get(end) != Some(&b'/') {
+        return end;
+    }
+    let digits = bytes[end + 1..].iter().take(4).take_while(|b| b.is_ascii_digit()).count();
+    let len = std::str::from_utf8(&bytes[end + 1..end + 1 + digits])
+        .ok()
+        .and_then(|d| d.parse::<u32>().ok());
+    match len {
+        Some(len) if digits <= 3 && len <= max && is_word(bytes, end + 1, end + 1 + digits) => {
+            end + 1 + digits
+        }
+        _ => end,
+    }
+}
+
+/// Dotted quad of octets without leading zeros
+fn is_ipv4(text: &str) -> bool {
+    let octets: Vec<&str> = text.split('.').collect();
+    octets.len() == 4
+        && octets.iter().all(|o| {
+            !o.is_empty()
+                && o.len() <= 3
+                && !(o.len() > 1 && o.starts_with('0'))
+                && o.parse::<u16>().is_ok_and(|n| n <= 255)
+        })
+}
+
+fn is_mac_pattern(bytes: &[u8]) -> bool {
+    if bytes.len() < 17 || !(bytes[2] == b':' || bytes[2] == b'-') {
+        return false;
+    }
+    let separator = bytes[2];
+    (0..6).all(|group| {
+        let at = group * 3;
+        bytes[at].is_ascii_hexdigit()
+            && bytes[at + 1].is_ascii_hexdigit()
+            && (group == 5 || bytes[at + 2] == separator)
+    })
+}
+
+fn is_dotted_mac_pattern(bytes: &[u8]) -> bool {
+    bytes.len() >= 14
+        && (0..3).all(|group| {
+            let at = group * 5;
+            bytes[at..at + 4].iter().all(u8::is_ascii_hexdigit)
+                && (group == 2 || bytes[at + 4] == b'.')
+        })
+}
+
+/// Decimal degrees at `i`: value and end, with at least three decimals
+fn decimal_degrees(bytes: &[u8], i: usize, max_int_digits: usize) -> Option<(f64, usize)> {
+    let mut end = i;
+    if bytes.get(end) == Some(&b'-') {
+        end += 1;
+    }
+    let int_digits = bytes[end..].iter().take_while(|b| b.is_ascii_digit()).count();
+    if int_digits == 0 || int_digits > max_int_digits || bytes.get(end + int_digits) != Some(&b'.')
+    {
+        return None;
+    }
+    end += int_digits + 1;
+    let decimals = bytes[end..].iter().take_while(|b| b.is_ascii_digit()).count();
+    if decimals < 3 {
+        return None;
+    }
+    end += decimals;
+    let value = std::str::from_utf8(&bytes[i..end]).ok()?.parse::<f64>().ok()?;
+    Some((value, end))
+}
+
+/// End of a "lat, long" pair starting at `i`
+fn geo_pair(bytes: &[u8], i: usize) -> Option<usize> {
+    let (lat, mut end) = decimal_degrees(bytes, i, 2)?;
+    end += bytes[end..].iter().take_while(|&&b| b == b' ').count();
+    if bytes.get(end) != Some(&b',') {
+        return None;
+    }
+    end += 1;
+    end += bytes[end..].iter().take_while(|&&b| b == b' ').count();
+    let (long, end) = decimal_degrees(bytes, end, 3)?;
+    if lat.abs() > 90.0 || long.abs() > 180.0 {
+        return None;
+    }
+    Some(end)
+}
+
+/// Characters of an unquoted email local part (the common subset of RFC 5322)
+fn is_local_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'.' | b'_' | b'%' | b'+' | b'-' | b'\'')
+}
+
+fn is_valid_local(local: &str) -> bool {
+    !local.is_empty()
+        && local.len() <= MAX_LOCAL_LEN
+        && !local.starts_with('.')
+        && !local.ends_with('.')
+        && !local.contains("..")
+}
+
+/// At least two labels of letters, digits and inner hyphens, ending in an alphabetic TLD
+fn is_valid_domain(domain: &str) -> bool {
+    if domain.len() > MAX_DOMAIN_LEN {
+        return false;
+    }
+    let labels: Vec<&str> = domain.split('.').collect();
+    let valid_labels = labels.iter().all(|label| {
+        !label.is_empty()
+            && label.len() <= MAX_LABEL_LEN
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+    });
+    let tld = labels[labels.len() - 1];
+    labels.len() >= 2
+        && valid_labels
+        && tld.len() >= 2
+        && tld.bytes().all(|b| b.is_ascii_alphabetic())
+        && !ASSET_EXTENSIONS.contains(&tld.to_ascii_lowercase().as_str())
+}
+
 impl Default for PiiRedactor {
     fn default() -> Self {
         Self::new(PiiAction::Log)
@@ -316,6 +1141,39 @@ mod tests {
         assert_eq!(matches[0].pii_type, PiiType::Email);
     }
 
+    #[test]
+    fn test_email_validation() {
+        let redactor = PiiRedactor::default();
+        let emails = |text: &str| -> Vec<String> {
+            redactor
+                .scan(text)
+                .iter()
+                .filter(|m| m.pii_type == PiiType::Email)
+                .map(|m| text[m.start..m.end].to_string())
+                .collect()
+        };
+        assert_eq!(
+            emails("Mail <first.last+tag@mail.example.co.uk>."),
+            vec!["first.last+tag@mail.example.co.uk"]
+        );
+        assert_eq!(emails("(ops@example.org), then"), vec!["ops@example.org"]);
+        assert!(emails("npx @modelcontextprotocol/server-filesystem").is_empty());
+        assert!(emails("thanks @alice.smith for the review").is_empty());
+        assert!(emails("npm i @scope/pkg@1.2.3 and icon@2x.png").is_empty());
+        assert!(emails("a@localhost, .a@example.com, a..b@example.com").is_empty());
+        assert!(emails("x@-bad.com and y@example.c0m").is_empty());
+    }
+
+    #[test]
+    fn test_allowed_domains() {
+        let domains = vec!["Corp.example".to_string(), "@partner.io".to_string()];
+        let redactor = PiiRedactor::default().with_allowed_domains(&domains);
+        assert!(!redactor.contains_pii("ask jane@corp.example or ops@eu.corp.example"));
+        assert!(!redactor.contains_pii("bob@PARTNER.io"));
+        assert!(redactor.contains_pii("bob@notcorp.example"));
+        assert!(redactor.contains_pii("bob@gmail.com"));
+    }
+
     #[test]
     fn test_phone_detection() {
         let redactor = PiiRedactor::new(PiiAction::Log);
@@ -326,6 +1184,118 @@ mod tests {
         assert_eq!(matches[0].pii_type, PiiType::Phone);
     }
 
+    #[test]
+    fn test_network_detection() {
+        let redactor = PiiRedactor::default();
+        let found = |text: &str| -> Vec<(PiiType, String)> {
+            let mut matches = redactor.scan(text);
+            matches.sort_by_key(|m| m.start);
+            matches.iter().map(|m| (m.pii_type, text[m.start..m.end].to_string())).collect()
+        };
+        assert_eq!(
+            found("conn from 10.1.20.33 to 192.168.0.0/16."),
+            vec![
+                (PiiType::IpAddress, "10.1.20.33".to_string()),
+                (PiiType::IpAddress, "192.168.0.0/16".to_string()),
+            ]
+        );
+        assert_eq!(
+            found("peer [2001:db8::8a2e:370:7334]:443 via fe80::1ff:fe23:4567:890a"),
+            vec![
+                (PiiType::IpAddress, "2001:db8::8a2e:370:7334".to_string()),
+                (PiiType::IpAddress, "fe80::1ff:fe23:4567:890a".to_string()),
+            ]
+        );
+        assert_eq!(
+            found("eth0 00:1A:2B:3C:4D:5E, wlan0 00-1a-2b-3c-4d-5f, cisco 001a.2b3c.4d5e"),
+            vec![
+                (PiiType::MacAddress, "00:1A:2B:3C:4D:5E".to_string()),
+                (PiiType::MacAddress, "00-1a-2b-3c-4d-5f".to_string()),
+                (PiiType::MacAddress, "001a.2b3c.4d5e".to_string()),
+            ]
+        );
+        assert_eq!(
+            found("Meet at 37.7749, -122.4194 tomorrow"),
+            vec![(PiiType::GeoCoordinates, "37.7749, -122.4194".to_string())]
+        );
+
+        // Versions, times, out-of-range values and glued words are not addresses
+        for text in [
+            "release v1.2.3.4 ships",
+            "build 1.2.3.4.5",
+            "at 12:30:45 the 256.1.1.1 job",
+            "std::fs::read and a::b",
+            "ratio 1.5, 2.5 and 95.1234, 10.5678",
+            "id00:1A:2B:3C:4D:5E and 00-1A-2B-3C-4D-5E-6F",
+        ] {
+            assert_eq!(found(text), vec![], "{}", text);
+        }
+        assert_eq!(
+            redactor.redact("from 10.0.0.1 at 48.8584, 2.2945"),
+            "from [IP ADDRESS REDACTED] at [LOCATION REDACTED]"
+        );
+    }
+
+    #[test]
+    fn test_national_ids() {
+        let regions = [PiiRegion::Uk, PiiRegion::Ca, PiiRegion::In, PiiRegion::De];
+        let redactor = PiiRedactor::default().with_regions(&regions);
+        let found = |text: &str| -> Vec<(PiiType, String)> {
+            let mut matches = redactor.scan(text);
+            matches.sort_by_key(|m| m.start);
+            matches.iter().map(|m| (m.pii_type, text[m.start..m.end].to_string())).collect()
+        };
+        let expect = |pii_type, value: &str| vec![(pii_type, value.to_string())];
+        assert_eq!(found("NI: AB 12 34 56 C."), expect(PiiType::UkNino, "AB 12 34 56 C"));
+        assert_eq!(found("NI AB123456C"), expect(PiiType::UkNino, "AB123456C"));
+        assert_eq!(found("SIN 123 456 782"), expect(PiiType::CaSin, "123 456 782"));
+        assert_eq!(found("UID 2345 6789 0124"), expect(PiiType::InAadhaar, "2345 6789 0124"));
+        assert_eq!(found("IdNr 86095742719"), expect(PiiType::DeTaxId, "86095742719"));
+        assert_eq!(
+            found("pay DE89 3704 0044 0532 0130 00 EUR"),
+            expect(PiiType::Iban, "DE89 3704 0044 0532 0130 00")
+        );
+        assert_eq!(
+            found("GB82WEST12345698765432"),
+            expect(PiiType::Iban, "GB82WEST12345698765432")
+        );
+
+        // Bad check digits, invalid prefixes and the US-only SSN are not reported
+        let national = |t: &PiiType| regions.iter().any(|r| r.types().contains(t));
+        for text in [
+            "SIN 123 456 789",
+            "UID 2345 6789 0125",
+            "IdNr 86095742718",
+            "pay DE89 3704 0044 0532 0130 01",
+            "NI BG123456C and QA123456C",
+            "SSN 123-45-6789",
+        ] {
+            assert!(!found(text).iter().any(|(t, _)| national(t)), "{}", text);
+        }
+        assert_eq!(PiiRedactor::default().scan("SIN 123 456 782").len(), 0);
+        assert_eq!(PiiRedactor::default().scan("SSN 123-45-6789").len(), 1);
+    }
+
+    #[test]
+    fn test_context_confidence() {
+        let redactor = PiiRedactor::default().with_min_confidence(0.5);
+        let phone = |text: &str| redactor.scan(text)[0].confidence;
+        assert!((phone("Call me at 555-123-4567") - 0.7).abs() < 1e-6);
+        assert!((phone("555-123-4567") - 0.4).abs() < 1e-6);
+        assert!(phone("Order number 555-123-4567 shipped") < 0.2);
+        assert!((phone("my dob and number: 555 123 4567") - 0.7).abs() < 1e-6);
+
+        // Only confident matches are acted on
+        assert!(redactor.contains_pii("Call me at 555-123-4567"));
+        assert!(!redactor.contains_pii("Order number 555-123-4567 shipped"));
+        assert_eq!(
+            redactor.redact("tel 5551234567, ref 5559876543"),
+            "tel [PHONE REDACTED], ref 5559876543"
+        );
+        assert!(has_keyword("card: ", "card"));
+        assert!(!has_keyword("discard ", "card"));
+    }
+
     #[test]
     fn test_no_pii() {
         let redactor = PiiRedactor::new(PiiAction::Log);
@@ -334,6 +1304,17 @@ mod tests {
         assert!(!redactor.contains_pii(text));
     }
 
+    #[test]
+    fn test_redact() {
+        let redactor = PiiRedactor::default();
+        let text = "Écrivez à user@example.com, carte 4111 1111 1111 1111, SSN 123-45-6789.";
+        assert_eq!(
+            redactor.redact(text),
+            "Écrivez à [EMAIL REDACTED], carte [CREDIT CARD REDACTED], SSN [SSN REDACTED]."
+        );
+        assert_eq!(redactor.redact("nothing here"), "nothing here");
+    }
+
     #[test]
     fn test_multiple_pii() {
         let redactor = PiiRedactor::new(PiiAction::Log);
@@ -0,0 +1,229 @@
+//! Reversible PII Tokenization
+//!
+//! Redaction destroys values a first-party service may need back ("email
+//! the customer at ..."). In vault mode PII in the request is replaced by
+//! deterministic, format-preserving tokens instead: each digit becomes a
+//! digit and each letter a letter of the same case, derived from an
+//! HMAC-SHA256 of the value under the configured key, while separators
+//! (`-`, `@`, `.`) stay. The model only ever sees tokens; the same value
+//! always gets the same token, so multi-turn conversations stay coherent.
+//!
+//! Tokens have the byte length of the values they replace, so bodies are
+//! rewritten in place. The vault of a request maps its tokens back to the
+//! values, and the response is de-tokenized on the way to the caller.
+
+use super::pii_redaction::{PiiRedactor, PiiType};
+use crate::crypto::hmac_sha256;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// Shortest accepted key, in bytes
+const MIN_KEY_LEN: usize = 16;
+
+/// Vault mode configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PiiVaultConfig {
+    /// HMAC-SHA256 key deriving the tokens
+    pub key: String,
+    /// Restore the values of tokens in responses (off when the caller
+    /// should only see tokens too)
+    #[serde(default = "default_detokenize_responses")]
+    pub detokenize_responses: bool,
+}
+
+fn default_detokenize_responses() -> bool {
+    true
+}
+
+impl PiiVaultConfig {
+    /// Validate the configuration, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        if self.key.len() < MIN_KEY_LEN {
+            diagnostics.push(format!("pii_vault.key: must be at least {} bytes", MIN_KEY_LEN));
+        }
+        diagnostics
+    }
+}
+
+/// Tokens issued for one request, and the values they stand for
+#[derive(Clone, Debug)]
+pub struct TokenVault {
+    key: Vec<u8>,
+    /// Token -> original value
+    tokens: BTreeMap<String, String>,
+}
+
+impl TokenVault {
+    /// Create an empty vault deriving tokens with `key`
+    pub fn new(key: &str) -> Self {
+        Self { key: key.as_bytes().to_vec(), tokens: BTreeMap::new() }
+    }
+
+    /// Whether no token was issued
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// Length of the longest token, in bytes
+    pub fn max_token_len(&self) -> usize {
+        self.tokens.keys().map(String::len).max().unwrap_or(0)
+    }
+
+    /// Whether `text` is a token of this vault
+    pub fn is_token(&self, text: &str) -> bool {
+        self.tokens.contains_key(text)
+    }
+
+    /// Token for a value, recorded for de-tokenization
+    pub fn tokenize(&mut self, pii_type: PiiType, value: &str) -> String {
+        let token = self.derive(pii_type, value);
+        self.tokens.insert(token.clone(), value.to_string());
+        token
+    }
+
+    /// Replace the PII of a body by tokens, in place
+    ///
+    /// Only string literals of JSON bodies are searched, so numbers and
+    /// keys are never rewritten. Returns the types tokenized, one entry per
+    /// value.
+    pub fn tokenize_body(
+        &mut self,
+        body: &mut [u8],
+        json: bool,
+        redactor: &PiiRedactor,
+    ) -> Vec<PiiType> {
+        let spans = if json { json_string_spans(body) } else { vec![(0, body.len())] };
+        let mut tokenized = Vec::new();
+        for (start, end) in spans {
+            let text = match std::str::from_utf8(&body[start..end]) {
+                Ok(text) => text.to_string(),
+                Err(_) => continue,
+            };
+            for m in redactor.scan_confident(&text) {
+                let token = self.tokenize(m.pii_type, &text[m.start..m.end]);
+                body[start + m.start..start + m.end].copy_from_slice(token.as_bytes());
+                tokenized.push(m.pii_type);
+            }
+        }
+        tokenized
+    }
+
+    /// Replace the tokens in `text` by their values
+    pub fn restore(&self, text: &str) -> String {
+        let mut restored = text.to_string();
+        for (token, value) in &self.tokens {
+            if restored.contains(token.as_str()) {
+                restored = restored.replace(token.as_str(), value);
+            }
+        }
+        restored
+    }
+
+    /// Start of the first token occurrence that `cut` would split
+    pub fn straddling(&self, text: &str, cut: usize) -> Option<usize> {
+        self.tokens
+            .keys()
+            .flat_map(|token| text.match_indices(token.as_str()).map(|(i, t)| (i, i + t.len())))
+            .filter(|&(start, end)| start < cut && end > cut)
+            .map(|(start, _)| start)
+            .min()
+    }
+
+    /// Format-preserving token: same length, same character classes
+    fn derive(&self, pii_type: PiiType, value: &str) -> String {
+        // One keystream byte per value byte covers every character
+        let mut stream = Vec::with_capacity(value.len());
+        let mut block = 0u32;
+        while stream.len() < value.len() {
+            let message = format!("{}:{}:{}", pii_type.as_str(), block, value);
+            stream.extend(hmac_sha256(&self.key, message.as_bytes()));
+            block += 1;
+        }
+        value
+            .chars()
+            .zip(stream)
+            .map(|(c, k)| match c {
+                '0'..='9' => (b'0' + k % 10) as char,
+                'a'..='z' => (b'a' + k % 26) as char,
+                'A'..='Z' => (b'A' + k % 26) as char,
+                other => other,
+            })
+            .collect()
+    }
+}
+
+/// Byte ranges of the contents of a JSON document's string literals
+fn json_string_spans(body: &[u8]) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = None;
+    let mut escaped = false;
+    for (i, &b) in body.iter().enumerate() {
+        match start {
+            None if b == b'"' => start = Some(i + 1),
+            None => {}
+            Some(_) if escaped => escaped = false,
+            Some(_) if b == b'\\' => escaped = true,
+            Some(s) if b == b'"' => {
+                spans.push((s, i));
+                start = None;
+            }
+            Some(_) => {}
+        }
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "0123456789abcdef";
+
+    #[test]
+    fn test_tokens_preserve_format() {
+        let mut vault = TokenVault::new(KEY);
+        let token = vault.tokenize(PiiType::Ssn, "123-45-6789");
+        assert_eq!(token.len(), 11);
+        assert_ne!(token, "123-45-6789");
+        assert!(token.chars().enumerate().all(|(i, c)| match i {
+            3 | 6 => c == '-',
+            _ => c.is_ascii_digit(),
+        }));
+        let email = vault.tokenize(PiiType::Email, "Jane.Doe@example.com");
+        assert_eq!(email.find('@'), Some(8));
+        assert!(email.starts_with(|c: char| c.is_ascii_uppercase()));
+
+        // Deterministic per key, and reversible through the vault
+        assert_eq!(TokenVault::new(KEY).tokenize(PiiType::Ssn, "123-45-6789"), token);
+        let rekeyed = TokenVault::new("fedcba9876543210").tokenize(PiiType::Ssn, "123-45-6789");
+        assert_ne!(rekeyed, token);
+        let reply = format!("Noted {} and {}.", token, email);
+        assert_eq!(vault.restore(&reply), "Noted 123-45-6789 and Jane.Doe@example.com.");
+    }
+
+    #[test]
+    fn test_tokenize_body() {
+        let mut vault = TokenVault::new(KEY);
+        let original = br#"{"max_tokens":5551234567,"messages":[{"content":"SSN 123-45-6789"}]}"#;
+        let mut body = original.to_vec();
+        let types = vault.tokenize_body(&mut body, true, &PiiRedactor::default());
+        assert_eq!(types, vec![PiiType::Ssn]);
+        assert_eq!(body.len(), original.len());
+        let text = String::from_utf8(body).unwrap();
+        assert!(text.starts_with(r#"{"max_tokens":5551234567,"#));
+        assert!(!text.contains("123-45-6789"));
+        assert_eq!(vault.restore(&text).as_bytes(), &original[..]);
+    }
+
+    #[test]
+    fn test_straddling() {
+        let mut vault = TokenVault::new(KEY);
+        let token = vault.tokenize(PiiType::Ssn, "123-45-6789");
+        let text = format!("id {} ok", token);
+        assert_eq!(vault.straddling(&text, 5), Some(3));
+        assert_eq!(vault.straddling(&text, 3), None);
+        assert_eq!(vault.max_token_len(), 11);
+    }
+}
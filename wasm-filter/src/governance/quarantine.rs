@@ -0,0 +1,101 @@
+//! Quarantine Routing
+//!
+//! Not every match deserves a 403: a medium-severity pattern is often a
+//! false positive, and blocking it costs availability. Quarantined requests
+//! are forwarded with a routing header (`x-guardrail-risk: high`) and the
+//! `ai_guard.action` filter state set to `quarantined`, so Envoy route rules
+//! can steer them to a sandboxed upstream or a cheaper model.
+//!
+//! Routing happens on request headers, so while quarantine is enabled the
+//! headers are held until the body has been scanned.
+
+use super::prompt_injection::InjectionSeverity;
+use serde::Deserialize;
+
+/// Quarantine routing configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QuarantineConfig {
+    /// Severities routed to quarantine instead of blocked
+    pub severities: Vec<InjectionSeverity>,
+    /// Request header set on quarantined requests (removed from client requests)
+    pub header: String,
+    /// Value of the routing header
+    pub value: String,
+}
+
+impl Default for QuarantineConfig {
+    fn default() -> Self {
+        Self {
+            severities: vec![InjectionSeverity::Medium],
+            header: "x-guardrail-risk".to_string(),
+            value: "high".to_string(),
+        }
+    }
+}
+
+impl QuarantineConfig {
+    /// Validate the configuration, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        if self.severities.is_empty() {
+            diagnostics.push("quarantine.severities: must not be empty".to_string());
+        }
+        if self.severities.contains(&InjectionSeverity::Critical) {
+            diagnostics
+                .push("quarantine.severities: critical matches are always blocked".to_string());
+        }
+        let valid_header = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-';
+        if self.header.is_empty() || !self.header.chars().all(valid_header) {
+            diagnostics.push(format!("quarantine.header: invalid header name '{}'", self.header));
+        }
+        if self.value.is_empty() {
+            diagnostics.push("quarantine.value: must not be empty".to_string());
+        }
+        diagnostics
+    }
+
+    /// Whether a match of this severity is quarantined rather than blocked
+    pub fn applies_to(&self, severity: InjectionSeverity) -> bool {
+        severity != InjectionSeverity::Critical && self.severities.contains(&severity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_applies_to() {
+        let config = QuarantineConfig::default();
+        assert!(config.applies_to(InjectionSeverity::Medium));
+        assert!(!config.applies_to(InjectionSeverity::High));
+        assert!(!config.applies_to(InjectionSeverity::Low));
+    }
+
+    #[test]
+    fn test_parse() {
+        let json = r#"{"severities": ["low", "medium"], "value": "sandbox"}"#;
+        let config: QuarantineConfig = serde_json::from_str(json).unwrap();
+        assert!(config.applies_to(InjectionSeverity::Low));
+        assert_eq!(config.header, "x-guardrail-risk");
+        assert!(serde_json::from_str::<QuarantineConfig>(r#"{"severities": ["huge"]}"#).is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(QuarantineConfig::default().validate().is_empty());
+        let config = QuarantineConfig {
+            severities: vec![InjectionSeverity::Critical],
+            header: "X-Risk".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.validate(),
+            vec![
+                "quarantine.severities: critical matches are always blocked".to_string(),
+                "quarantine.header: invalid header name 'X-Risk'".to_string(),
+            ]
+        );
+    }
+}
@@ -4,10 +4,12 @@
 //! Note: In Wasm, shared data is scoped to the Envoy worker,
 //! so this provides approximate rate limiting.
 
+use serde::Deserialize;
 use std::collections::HashMap;
 
 /// Rate limiting configuration
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct RateLimits {
     /// Maximum requests per minute
     pub requests_per_minute: u32,
@@ -115,6 +117,34 @@ impl RateLimiter {
         RateDecision::Allow
     }
 
+    /// Pre-flight check of estimated tokens, without recording them
+    ///
+    /// Lets callers reject a request whose estimated prompt would exceed the
+    /// token budget before it is forwarded (and paid for). Actual usage is
+    /// recorded later via `record_tokens`.
+    pub fn check_tokens(
+        &mut self,
+        agent_id: &str,
+        estimated_tokens: u32,
+        current_time_secs: u64,
+    ) -> RateDecision {
+        let tokens_per_minute = self.limits.tokens_per_minute;
+        let window_seconds = self.window_seconds;
+        let state = self.get_or_create_state(agent_id, current_time_secs);
+
+        if state.token_count.saturating_add(estimated_tokens) > tokens_per_minute {
+            return RateDecision::RateLimited(RateLimitInfo {
+                reason: "estimated tokens_per_minute exceeded".to_string(),
+                limit: tokens_per_minute,
+                current: state.token_count,
+                retry_after_secs: window_seconds
+                    - (current_time_secs - state.window_start).min(window_seconds),
+            });
+        }
+
+        RateDecision::Allow
+    }
+
     /// Get current state for an agent
     pub fn get_state(&self, agent_id: &str) -> Option<RateStateInfo> {
         self.state.get(agent_id).map(|s| RateStateInfo {
@@ -273,6 +303,23 @@ mod tests {
         assert!(result.is_limited());
     }
 
+    #[test]
+    fn test_preflight_token_check() {
+        let mut limiter = RateLimiter::with_limits(RateLimits {
+            tokens_per_minute: 100,
+            ..Default::default()
+        });
+
+        limiter.record_tokens("agent-1", 80, 1000);
+
+        // Estimate fits - allowed, and nothing is recorded
+        assert!(matches!(limiter.check_tokens("agent-1", 20, 1001), RateDecision::Allow));
+        assert_eq!(limiter.get_state("agent-1").unwrap().token_count, 80);
+
+        // Estimate would exceed the budget
+        assert!(limiter.check_tokens("agent-1", 21, 1002).is_limited());
+    }
+
     #[test]
     fn test_per_agent_isolation() {
         let mut limiter = RateLimiter::with_limits(RateLimits {
@@ -0,0 +1,315 @@
+//! Token-Bomb and Repetition Detection
+//!
+//! Prompts made of the same word thousands of times, a phrase pasted over
+//! and over, or megabytes of whitespace cost real money in prompt tokens
+//! and are a known way to push models into runaway, repetitive output. The
+//! detector makes one pass with constant memory:
+//! - a token (a run of letters and digits) or phrase of up to
+//!   `MAX_PERIOD` tokens repeated back to back
+//! - one character repeated (`aaaa...`, `----...`)
+//! - unbroken whitespace
+//!
+//! A flagged request is blocked, or forwarded with its `max_tokens` capped
+//! so the completion cannot run away.
+
+use serde::Deserialize;
+
+/// Longest repeated phrase recognized, in tokens
+pub const MAX_PERIOD: usize = 8;
+
+/// What to do with a flagged request
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RepetitionAction {
+    /// Reject the request
+    Block,
+    /// Forward with `max_tokens` capped to `limit_max_tokens`
+    Limit,
+}
+
+impl RepetitionAction {
+    /// Stable lowercase name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RepetitionAction::Block => "block",
+            RepetitionAction::Limit => "limit",
+        }
+    }
+}
+
+/// Repetition detection configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RepetitionConfig {
+    /// Most back-to-back repetitions of a token or phrase
+    pub max_repeats: usize,
+    /// Longest run of one repeated character
+    pub max_char_run: usize,
+    /// Longest run of whitespace
+    pub max_whitespace_run: usize,
+    /// Action on a flagged request
+    pub action: RepetitionAction,
+    /// `max_tokens` written into limited requests
+    pub limit_max_tokens: u64,
+}
+
+impl Default for RepetitionConfig {
+    fn default() -> Self {
+        Self {
+            max_repeats: 200,
+            max_char_run: 1024,
+            max_whitespace_run: 512,
+            action: RepetitionAction::Block,
+            limit_max_tokens: 256,
+        }
+    }
+}
+
+impl RepetitionConfig {
+    /// Validate the configuration, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        let limits = [
+            ("max_repeats", self.max_repeats as u64),
+            ("max_char_run", self.max_char_run as u64),
+            ("max_whitespace_run", self.max_whitespace_run as u64),
+            ("limit_max_tokens", self.limit_max_tokens),
+        ];
+        for (name, limit) in limits {
+            if limit == 0 {
+                diagnostics.push(format!("repetition_detection.{}: must be greater than 0", name));
+            }
+        }
+        diagnostics
+    }
+}
+
+/// Kind of repetition found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepetitionKind {
+    /// A token or phrase repeated back to back
+    Tokens,
+    /// One character repeated
+    Characters,
+    /// Unbroken whitespace
+    Whitespace,
+}
+
+impl RepetitionKind {
+    /// Stable snake_case name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RepetitionKind::Tokens => "repeated_tokens",
+            RepetitionKind::Characters => "repeated_characters",
+            RepetitionKind::Whitespace => "whitespace",
+        }
+    }
+}
+
+/// A repetition over its limit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepetitionFinding {
+    /// What repeated
+    pub kind: RepetitionKind,
+    /// Repetitions (tokens) or run length (characters, whitespace)
+    pub count: usize,
+    /// The configured limit
+    pub limit: usize,
+}
+
+impl std::fmt::Display for RepetitionFinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            RepetitionKind::Tokens => write!(
+                f,
+                "Token or phrase repeated {} times back to back (limit {})",
+                self.count, self.limit
+            ),
+            RepetitionKind::Characters => {
+                write!(f, "Character repeated {} times (limit {})", self.count, self.limit)
+            }
+            RepetitionKind::Whitespace => {
+                write!(f, "{} bytes of unbroken whitespace (limit {})", self.count, self.limit)
+            }
+        }
+    }
+}
+
+/// Streaming repetition detector
+pub struct RepetitionDetector {
+    config: RepetitionConfig,
+    /// Hash of the token being read
+    token: u64,
+    in_token: bool,
+    /// Hashes of the last `MAX_PERIOD` tokens, newest last
+    history: [u64; MAX_PERIOD],
+    tokens_seen: usize,
+    /// Per period: consecutive tokens equal to the token that many back
+    streaks: [usize; MAX_PERIOD],
+    last_byte: Option<u8>,
+    char_run: usize,
+    whitespace_run: usize,
+    finding: Option<RepetitionFinding>,
+}
+
+/// FNV-1a offset basis
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// FNV-1a prime
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+impl RepetitionDetector {
+    /// Create a detector
+    pub fn new(config: &RepetitionConfig) -> Self {
+        Self {
+            config: config.clone(),
+            token: FNV_OFFSET,
+            in_token: false,
+            history: [0; MAX_PERIOD],
+            tokens_seen: 0,
+            streaks: [0; MAX_PERIOD],
+            last_byte: None,
+            char_run: 0,
+            whitespace_run: 0,
+            finding: None,
+        }
+    }
+
+    /// Feed the next bytes of text
+    pub fn feed(&mut self, data: &[u8]) {
+        for &byte in data {
+            if self.finding.is_some() {
+                return;
+            }
+            self.push(byte);
+        }
+    }
+
+    /// End of a text segment (e.g. a JSON string): a token ends, runs restart
+    pub fn end_segment(&mut self) {
+        self.end_token();
+        self.last_byte = None;
+        self.char_run = 0;
+        self.whitespace_run = 0;
+    }
+
+    /// The first repetition over its limit, once the text is complete
+    pub fn finish(&mut self) -> Option<RepetitionFinding> {
+        self.end_segment();
+        self.finding.clone()
+    }
+
+    fn push(&mut self, byte: u8) {
+        if byte.is_ascii_whitespace() {
+            self.whitespace_run += 1;
+            self.check(RepetitionKind::Whitespace, self.whitespace_run);
+        } else {
+            self.whitespace_run = 0;
+        }
+        if self.last_byte == Some(byte) && !byte.is_ascii_whitespace() {
+            self.char_run += 1;
+            self.check(RepetitionKind::Characters, self.char_run);
+        } else {
+            self.char_run = 1;
+        }
+        self.last_byte = Some(byte);
+
+        // Non-ASCII bytes belong to tokens, so CJK text still tokenizes
+        if byte.is_ascii_alphanumeric() || !byte.is_ascii() {
+            let folded = u64::from(byte.to_ascii_lowercase());
+            self.token = (self.token ^ folded).wrapping_mul(FNV_PRIME);
+            self.in_token = true;
+        } else {
+            self.end_token();
+        }
+    }
+
+    fn end_token(&mut self) {
+        if !self.in_token {
+            return;
+        }
+        let token = std::mem::replace(&mut self.token, FNV_OFFSET);
+        self.in_token = false;
+        for period in 1..=MAX_PERIOD {
+            // history[MAX_PERIOD - period] is the token `period` back
+            let repeated = self.tokens_seen >= period && self.history[MAX_PERIOD - period] == token;
+            let streak = &mut self.streaks[period - 1];
+            *streak = if repeated { *streak + 1 } else { 0 };
+        }
+        self.history.rotate_left(1);
+        self.history[MAX_PERIOD - 1] = token;
+        self.tokens_seen += 1;
+        let repeats = (1..=MAX_PERIOD)
+            .filter(|p| self.streaks[p - 1] > 0)
+            .map(|p| self.streaks[p - 1] / p + 1)
+            .max()
+            .unwrap_or(1);
+        self.check(RepetitionKind::Tokens, repeats);
+    }
+
+    fn check(&mut self, kind: RepetitionKind, count: usize) {
+        let limit = match kind {
+            RepetitionKind::Tokens => self.config.max_repeats,
+            RepetitionKind::Characters => self.config.max_char_run,
+            RepetitionKind::Whitespace => self.config.max_whitespace_run,
+        };
+        if count > limit && self.finding.is_none() {
+            self.finding = Some(RepetitionFinding { kind, count, limit });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(config: &RepetitionConfig, text: &str) -> Option<RepetitionFinding> {
+        let mut detector = RepetitionDetector::new(config);
+        detector.feed(text.as_bytes());
+        detector.finish()
+    }
+
+    #[test]
+    fn test_repeated_tokens_and_phrases() {
+        let config = RepetitionConfig { max_repeats: 50, ..Default::default() };
+        let found = detect(&config, &"poem ".repeat(1000)).unwrap();
+        assert_eq!(found.kind, RepetitionKind::Tokens);
+        assert_eq!(found.count, 51);
+
+        let found = detect(&config, &"Buy now, pay later! ".repeat(100)).unwrap();
+        assert_eq!(found.kind, RepetitionKind::Tokens);
+
+        // Ordinary prose repeats words, but never back to back for long
+        let prose = "The quick brown fox jumps over the lazy dog while the cat sleeps. ";
+        assert_eq!(detect(&config, &prose.repeat(5)), None);
+    }
+
+    #[test]
+    fn test_character_and_whitespace_runs() {
+        let config = RepetitionConfig::default();
+        let found = detect(&config, &"a".repeat(2000)).unwrap();
+        assert_eq!(found.kind, RepetitionKind::Characters);
+        assert_eq!(found.count, 1025);
+
+        let text = format!("hello{}world", " \n\t".repeat(300));
+        let found = detect(&config, &text).unwrap();
+        assert_eq!(found.kind, RepetitionKind::Whitespace);
+        assert!(found.to_string().contains("whitespace"));
+
+        // Runs restart at segment boundaries
+        let mut detector = RepetitionDetector::new(&config);
+        detector.feed("a".repeat(1000).as_bytes());
+        detector.end_segment();
+        detector.feed("a".repeat(1000).as_bytes());
+        assert_eq!(detector.finish(), None);
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = RepetitionConfig { max_repeats: 0, ..Default::default() };
+        assert_eq!(
+            config.validate(),
+            vec!["repetition_detection.max_repeats: must be greater than 0".to_string()]
+        );
+    }
+}
@@ -0,0 +1,197 @@
+//! Replay Protection
+//!
+//! A request captured inside the mesh can be sent again verbatim. A2A
+//! messages carry a client-chosen `messageId` and MCP requests a JSON-RPC
+//! `id`, both unique within a session, so a second request reusing one in
+//! the same session is a replay.
+//!
+//! Seen ids are kept in shared data for a TTL, hashed (with the session id)
+//! into a fixed number of buckets of bounded size, so memory stays bounded.
+//! When a bucket is full the entries closest to expiry are evicted first.
+
+use crate::shared::{SharedError, SharedStore};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// Number of shared data buckets
+const BUCKETS: u64 = 256;
+
+/// Longest message or request id remembered (longer ids are not checked)
+pub const MAX_NONCE_LEN: usize = 256;
+
+/// Replay protection configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReplayConfig {
+    /// How long a seen id is remembered, in seconds
+    pub ttl_secs: u64,
+    /// Ids kept per shared data bucket
+    pub max_entries_per_bucket: usize,
+}
+
+impl Default for ReplayConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: 300,
+            max_entries_per_bucket: 256,
+        }
+    }
+}
+
+impl ReplayConfig {
+    /// Validate the configuration, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        if self.ttl_secs == 0 {
+            diagnostics.push("replay_protection.ttl_secs: must be greater than 0".to_string());
+        }
+        if self.max_entries_per_bucket == 0 {
+            diagnostics.push(
+                "replay_protection.max_entries_per_bucket: must be greater than 0".to_string(),
+            );
+        }
+        diagnostics
+    }
+}
+
+/// Id a request is identified by within its session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Nonce {
+    /// A2A `messageId`
+    MessageId(String),
+    /// JSON-RPC request `id`, as JSON (`"abc"` and `7` differ)
+    RequestId(String),
+}
+
+impl Nonce {
+    /// The id, as reported in block reasons
+    pub fn value(&self) -> &str {
+        match self {
+            Nonce::MessageId(id) | Nonce::RequestId(id) => id,
+        }
+    }
+
+    /// Kind of id, as reported in block reasons
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Nonce::MessageId(_) => "message id",
+            Nonce::RequestId(_) => "request id",
+        }
+    }
+}
+
+/// Remember a nonce for the session; false if it was already seen within the TTL
+pub fn check_and_record(
+    store: &impl SharedStore,
+    config: &ReplayConfig,
+    session: &str,
+    nonce: &Nonce,
+    now_secs: u64,
+) -> Result<bool, SharedError> {
+    if nonce.value().len() > MAX_NONCE_LEN {
+        return Ok(true);
+    }
+    let hash = nonce_hash(session, nonce);
+    let mut fresh = true;
+    store.update(&bucket_key(hash), |current| {
+        let mut entries = parse(current);
+        entries.retain(|_, expires| *expires > now_secs);
+        fresh = !entries.contains_key(&hash);
+        if fresh {
+            while entries.len() >= config.max_entries_per_bucket {
+                let soonest = entries.iter().min_by_key(|(_, e)| **e).map(|(k, _)| *k);
+                match soonest {
+                    Some(key) => entries.remove(&key),
+                    None => break,
+                };
+            }
+            entries.insert(hash, now_secs + config.ttl_secs);
+        }
+        serde_json::to_vec(&entries).unwrap_or_default()
+    })?;
+    Ok(fresh)
+}
+
+/// FNV-1a hash of the session and nonce
+fn nonce_hash(session: &str, nonce: &Nonce) -> u64 {
+    let kind: &[u8] = match nonce {
+        Nonce::MessageId(_) => b"m",
+        Nonce::RequestId(_) => b"r",
+    };
+    [session.as_bytes(), b"\n", kind, nonce.value().as_bytes()]
+        .concat()
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325u64, |h, b| (h ^ *b as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// Shared data key of the bucket holding a nonce
+fn bucket_key(hash: u64) -> String {
+    format!("ai_guard.nonces.{}", hash % BUCKETS)
+}
+
+/// Nonce hashes and their expiry, in seconds
+fn parse(value: Option<&[u8]>) -> BTreeMap<u64, u64> {
+    value
+        .and_then(|v| serde_json::from_slice(v).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::MemorySharedStore;
+
+    fn message(id: &str) -> Nonce {
+        Nonce::MessageId(id.to_string())
+    }
+
+    #[test]
+    fn test_replay_within_ttl() {
+        let store = MemorySharedStore::new();
+        let config = ReplayConfig { ttl_secs: 60, ..Default::default() };
+        let check = |session, nonce: &Nonce, now| {
+            check_and_record(&store, &config, session, nonce, now).unwrap()
+        };
+        assert!(check("s1", &message("m-1"), 0));
+        assert!(!check("s1", &message("m-1"), 30));
+        // Other sessions and id kinds are separate
+        assert!(check("s2", &message("m-1"), 30));
+        assert!(check("s1", &Nonce::RequestId("m-1".to_string()), 30));
+        // Forgotten after the TTL
+        assert!(check("s1", &message("m-1"), 61));
+    }
+
+    #[test]
+    fn test_bucket_is_bounded() {
+        let store = MemorySharedStore::new();
+        let config = ReplayConfig { max_entries_per_bucket: 4, ..Default::default() };
+        let key = bucket_key(nonce_hash("s", &message("m-0")));
+        let colliding: Vec<Nonce> = (0..)
+            .map(|i| message(&format!("m-{}", i)))
+            .filter(|n| bucket_key(nonce_hash("s", n)) == key)
+            .take(6)
+            .collect();
+        for (now, nonce) in colliding.iter().enumerate() {
+            assert!(check_and_record(&store, &config, "s", nonce, now as u64).unwrap());
+        }
+        assert_eq!(parse(store.get(&key).0.as_deref()).len(), 4);
+        // The oldest was evicted, the newest is still remembered
+        assert!(check_and_record(&store, &config, "s", &colliding[0], 10).unwrap());
+        assert!(!check_and_record(&store, &config, "s", &colliding[5], 10).unwrap());
+    }
+
+    #[test]
+    fn test_oversized_nonce_and_validate() {
+        let store = MemorySharedStore::new();
+        let config = ReplayConfig::default();
+        let long = message(&"x".repeat(MAX_NONCE_LEN + 1));
+        assert!(check_and_record(&store, &config, "s", &long, 0).unwrap());
+        assert!(check_and_record(&store, &config, "s", &long, 0).unwrap());
+
+        let config = ReplayConfig { ttl_secs: 0, ..Default::default() };
+        assert_eq!(
+            config.validate(),
+            vec!["replay_protection.ttl_secs: must be greater than 0".to_string()]
+        );
+    }
+}
@@ -0,0 +1,587 @@
+//! PII Redaction in Responses
+//!
+//! Models repeat PII they were given in context or retrieved with a tool,
+//! so redacting prompts alone leaves the response as a leak. Complete JSON
+//! responses have every string value checked. Streamed completions (Chat
+//! Completions, Responses API, Anthropic Messages) are checked delta by
+//! delta: the text of each choice is reassembled and its last
+//! `holdback_bytes` are held back, so a number split across deltas
+//! (`555-12` + `3-4567`) is still found whole and redacted in place. Held
+//! text is released with the next delta of the choice, or just before the
+//! choice ends.
+//!
+//! What happens to a match depends on its type: redact it, only log it, or
+//! block (end the stream, or replace a JSON response with an error). Other
+//! JSON events of a stream have their strings checked event by event.
+//!
+//! The same pass restores the tokens of the request's PII vault, with text
+//! held back so no token is split either.
+
+use super::pii_redaction::{redact_matches, PiiAction, PiiMatch, PiiRedactor, PiiType};
+use super::pii_vault::TokenVault;
+use crate::streaming::{SseEvent, SseParser};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// Response header listing the PII types redacted and their counts
+pub const REDACTIONS_HEADER: &str = "x-guardrail-redactions";
+
+/// Response PII configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResponsePiiConfig {
+    /// Action per PII type name (e.g. `{"email": "log", "ssn": "block"}`)
+    pub actions: BTreeMap<String, PiiAction>,
+    /// Action for types without an entry in `actions`
+    pub default_action: PiiAction,
+    /// Streamed text held back per choice, in bytes, so values split across
+    /// deltas are matched whole
+    pub holdback_bytes: usize,
+    /// Largest SSE event inspected, in bytes (larger events pass unchecked)
+    pub max_event_size: usize,
+    /// Tell the caller what was redacted in an `x-guardrail-redactions`
+    /// header (JSON responses; streamed responses have headers sent already,
+    /// so the counts are only in the `ai_guard.redactions` filter state)
+    pub redactions_header: bool,
+}
+
+impl Default for ResponsePiiConfig {
+    fn default() -> Self {
+        Self {
+            actions: BTreeMap::new(),
+            default_action: PiiAction::Redact,
+            holdback_bytes: 64,
+            max_event_size: 64 * 1024,
+            redactions_header: false,
+        }
+    }
+}
+
+impl ResponsePiiConfig {
+    /// Validate the configuration, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        for name in self.actions.keys() {
+            if PiiType::from_name(name).is_none() {
+                diagnostics.push(format!("response_pii.actions.{}: unknown PII type", name));
+            }
+        }
+        if self.holdback_bytes == 0 {
+            diagnostics.push("response_pii.holdback_bytes: must be greater than 0".to_string());
+        }
+        if self.max_event_size == 0 {
+            diagnostics.push("response_pii.max_event_size: must be greater than 0".to_string());
+        }
+        diagnostics
+    }
+
+    /// Action for matches of a type
+    pub fn action_for(&self, pii_type: PiiType) -> PiiAction {
+        self.actions.get(pii_type.as_str()).copied().unwrap_or(self.default_action)
+    }
+}
+
+/// A PII match in a response and the action taken on it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PiiFinding {
+    /// Type of the match
+    pub pii_type: PiiType,
+    /// Action taken
+    pub action: PiiAction,
+}
+
+/// Output of [`ResponsePiiRedactor::feed`] and friends
+#[derive(Debug, Default)]
+pub struct RedactedChunk {
+    /// Bytes to forward in place of the input
+    pub bytes: Vec<u8>,
+    /// Matches found, in order
+    pub findings: Vec<PiiFinding>,
+    /// A match with the block action: the response is to be ended
+    pub blocked: bool,
+}
+
+/// Where a choice's text is streamed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Shape {
+    /// Chat Completions `choices[].delta.content`
+    Chat,
+    /// Responses API `response.output_text.delta`
+    Responses,
+    /// Anthropic `content_block_delta` with a `text_delta`
+    Anthropic,
+}
+
+/// Redacts the PII of one response
+pub struct ResponsePiiRedactor {
+    config: ResponsePiiConfig,
+    /// PII detection (`None` when only vault tokens are restored)
+    redactor: Option<PiiRedactor>,
+    /// Whether actions are applied (monitor mode only logs)
+    enforce: bool,
+    /// Tokens restored to their values
+    vault: Option<TokenVault>,
+    /// Streamed text held back per choice, in bytes
+    holdback: usize,
+    parser: SseParser,
+    /// Text held back per choice
+    pending: BTreeMap<(Shape, u64), String>,
+}
+
+impl ResponsePiiRedactor {
+    /// Create a redactor for one response
+    pub fn new(config: &ResponsePiiConfig, redactor: PiiRedactor, enforce: bool) -> Self {
+        Self {
+            config: config.clone(),
+            redactor: Some(redactor),
+            enforce,
+            vault: None,
+            holdback: config.holdback_bytes,
+            parser: SseParser::new(config.max_event_size),
+            pending: BTreeMap::new(),
+        }
+    }
+
+    /// Create a pass that only restores vault tokens
+    pub fn detokenizer(vault: TokenVault) -> Self {
+        let config = ResponsePiiConfig::default();
+        Self {
+            holdback: vault.max_token_len(),
+            redactor: None,
+            enforce: true,
+            vault: Some(vault),
+            parser: SseParser::new(config.max_event_size),
+            pending: BTreeMap::new(),
+            config,
+        }
+    }
+
+    /// Also restore the tokens of a vault
+    pub fn with_vault(mut self, vault: TokenVault) -> Self {
+        self.holdback = self.holdback.max(vault.max_token_len());
+        self.vault = Some(vault);
+        self
+    }
+
+    /// Redact a complete JSON response body
+    ///
+    /// Returns the rewritten body, or `None` when the body is not JSON or
+    /// nothing was redacted; findings are recorded either way.
+    pub fn redact_body(&self, body: &[u8], out: &mut RedactedChunk) -> Option<Vec<u8>> {
+        let mut value: Value = serde_json::from_slice(body).ok()?;
+        let mut findings = Vec::new();
+        let changed = self.redact_strings(&mut value, &mut findings, out);
+        out.findings.extend(findings);
+        if changed {
+            serde_json::to_vec(&value).ok()
+        } else {
+            None
+        }
+    }
+
+    /// Feed a chunk of an SSE response
+    ///
+    /// Only complete events are forwarded; a partial event is held until its
+    /// terminating blank line arrives. On a block, the events before the
+    /// blocking one are still returned.
+    pub fn feed(&mut self, chunk: &[u8]) -> RedactedChunk {
+        let mut out = RedactedChunk::default();
+        for event in self.parser.feed(chunk) {
+            let safe = out.bytes.len();
+            self.process(event, &mut out);
+            if out.blocked {
+                out.bytes.truncate(safe);
+                break;
+            }
+        }
+        out
+    }
+
+    /// Release the text still held back at the end of the stream
+    pub fn finish(&mut self) -> RedactedChunk {
+        let mut out = RedactedChunk::default();
+        self.flush_all(&mut out);
+        if out.blocked {
+            out.bytes.clear();
+        }
+        out
+    }
+
+    fn process(&mut self, mut event: SseEvent, out: &mut RedactedChunk) {
+        if event.truncated {
+            out.bytes.extend(event.to_bytes());
+            return;
+        }
+        if event.data.starts_with(b"[DONE]") {
+            self.flush_all(out);
+            out.bytes.extend(event.to_bytes());
+            return;
+        }
+        let mut value: Value = match serde_json::from_slice(&event.data) {
+            Ok(value) => value,
+            Err(_) => {
+                if let Ok(text) = std::str::from_utf8(&event.data) {
+                    let matches = self.scan(text);
+                    event.data = self.apply(text, matches, &mut out.findings).into_bytes();
+                    out.blocked |= self.blocks(&out.findings);
+                }
+                out.bytes.extend(event.to_bytes());
+                return;
+            }
+        };
+
+        let changed = match value["type"].as_str() {
+            Some("response.output_text.delta") => {
+                let index = value["output_index"].as_u64().unwrap_or(0);
+                let text = value["delta"].as_str().unwrap_or("").to_string();
+                value["delta"] = Value::String(self.release((Shape::Responses, index), &text, out));
+                true
+            }
+            Some("content_block_delta") if value["delta"]["type"] == "text_delta" => {
+                let index = value["index"].as_u64().unwrap_or(0);
+                let text = value["delta"]["text"].as_str().unwrap_or("").to_string();
+                value["delta"]["text"] = Value::String(self.release((Shape::Anthropic, index), &text, out));
+                true
+            }
+            Some("response.output_text.done") => {
+                let index = value["output_index"].as_u64().unwrap_or(0);
+                if let Some(rest) = self.flush((Shape::Responses, index), out) {
+                    let mut delta = json!({"type": "response.output_text.delta", "delta": rest});
+                    for field in ["item_id", "output_index", "content_index"] {
+                        if let Some(v) = value.get(field) {
+                            delta[field] = v.clone();
+                        }
+                    }
+                    push_event(out, Some("response.output_text.delta"), &delta);
+                }
+                self.redact_repeated(&mut value, out)
+            }
+            Some("content_block_stop") => {
+                let index = value["index"].as_u64().unwrap_or(0);
+                if let Some(rest) = self.flush((Shape::Anthropic, index), out) {
+                    push_event(out, Some("content_block_delta"), &anthropic_delta(index, rest));
+                }
+                false
+            }
+            Some("response.completed") | Some("response.content_part.done")
+            | Some("response.output_item.done") => self.redact_repeated(&mut value, out),
+            _ if value["choices"].is_array() => self.chat_chunk(&mut value, out),
+            _ => {
+                let mut findings = Vec::new();
+                let changed = self.redact_strings(&mut value, &mut findings, out);
+                out.findings.extend(findings);
+                changed
+            }
+        };
+        if changed {
+            if let Ok(data) = serde_json::to_vec(&value) {
+                event.data = data;
+            }
+        }
+        out.bytes.extend(event.to_bytes());
+    }
+
+    /// Redact the deltas of a Chat Completions chunk
+    fn chat_chunk(&mut self, chunk: &mut Value, out: &mut RedactedChunk) -> bool {
+        let mut changed = false;
+        for choice in chunk["choices"].as_array_mut().into_iter().flatten() {
+            let index = choice["index"].as_u64().unwrap_or(0);
+            let finished = !choice["finish_reason"].is_null();
+            let text = match choice["delta"]["content"].as_str() {
+                Some(text) => text.to_string(),
+                None if finished && self.pending.contains_key(&(Shape::Chat, index)) => String::new(),
+                None => continue,
+            };
+            let mut released = self.release((Shape::Chat, index), &text, out);
+            if finished {
+                released.push_str(&self.flush((Shape::Chat, index), out).unwrap_or_default());
+            }
+            choice["delta"]["content"] = Value::String(released);
+            changed = true;
+        }
+        changed
+    }
+
+    /// Append a delta to a choice's held text and release what is safe
+    ///
+    /// The last `holdback` bytes stay held, and never is a match or a
+    /// token cut in two.
+    fn release(&mut self, key: (Shape, u64), text: &str, out: &mut RedactedChunk) -> String {
+        let mut pending = self.pending.remove(&key).unwrap_or_default();
+        pending.push_str(text);
+        let matches = self.scan(&pending);
+        let mut cut = pending.len().saturating_sub(self.holdback);
+        while !pending.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        loop {
+            let split_match = matches.iter().find(|m| m.start < cut && m.end > cut);
+            let split_token = self.vault.as_ref().and_then(|v| v.straddling(&pending, cut));
+            match split_match.map(|m| m.start).into_iter().chain(split_token).min() {
+                Some(start) => cut = start,
+                None => break,
+            }
+        }
+        let rest = pending.split_off(cut);
+        self.pending.insert(key, rest);
+        let inside: Vec<PiiMatch> = matches.into_iter().filter(|m| m.end <= cut).collect();
+        let released = self.apply(&pending, inside, &mut out.findings);
+        out.blocked |= self.blocks(&out.findings);
+        released
+    }
+
+    /// Release all of a choice's held text
+    fn flush(&mut self, key: (Shape, u64), out: &mut RedactedChunk) -> Option<String> {
+        let pending = self.pending.remove(&key).filter(|p| !p.is_empty())?;
+        let matches = self.scan(&pending);
+        let released = self.apply(&pending, matches, &mut out.findings);
+        out.blocked |= self.blocks(&out.findings);
+        Some(released)
+    }
+
+    /// Release every choice's held text as synthetic delta events
+    fn flush_all(&mut self, out: &mut RedactedChunk) {
+        let keys: Vec<(Shape, u64)> = self.pending.keys().copied().collect();
+        for key in keys {
+            let rest = match self.flush(key, out) {
+                Some(rest) => rest,
+                None => continue,
+            };
+            let (shape, index) = key;
+            match shape {
+                Shape::Chat => push_event(
+                    out,
+                    None,
+                    &json!({"choices": [{"index": index, "delta": {"content": rest}}]}),
+                ),
+                Shape::Responses => push_event(
+                    out,
+                    Some("response.output_text.delta"),
+                    &json!({"type": "response.output_text.delta", "output_index": index, "delta": rest}),
+                ),
+                Shape::Anthropic => {
+                    push_event(out, Some("content_block_delta"), &anthropic_delta(index, rest))
+                }
+            }
+        }
+    }
+
+    /// Redact an event repeating text already streamed as deltas
+    ///
+    /// The deltas were already counted, so matches here are not recorded.
+    fn redact_repeated(&self, value: &mut Value, out: &mut RedactedChunk) -> bool {
+        let mut repeated = Vec::new();
+        self.redact_strings(value, &mut repeated, out)
+    }
+
+    /// Redact every string of a JSON value, returning whether any changed
+    fn redact_strings(
+        &self,
+        value: &mut Value,
+        findings: &mut Vec<PiiFinding>,
+        out: &mut RedactedChunk,
+    ) -> bool {
+        match value {
+            Value::String(text) => {
+                let matches = self.scan(text);
+                if matches.is_empty() && self.vault.is_none() {
+                    return false;
+                }
+                let redacted = self.apply(text, matches, findings);
+                out.blocked |= self.blocks(findings);
+                let changed = redacted != *text;
+                *text = redacted;
+                changed
+            }
+            Value::Array(items) => {
+                let mut changed = false;
+                for item in items {
+                    changed |= self.redact_strings(item, findings, out);
+                }
+                changed
+            }
+            Value::Object(fields) => {
+                let mut changed = false;
+                for (_, field) in fields.iter_mut() {
+                    changed |= self.redact_strings(field, findings, out);
+                }
+                changed
+            }
+            _ => false,
+        }
+    }
+
+    /// PII matches to act on; vault tokens are left to be restored
+    fn scan(&self, text: &str) -> Vec<PiiMatch> {
+        let mut matches = match &self.redactor {
+            Some(redactor) => redactor.scan_confident(text),
+            None => return Vec::new(),
+        };
+        if let Some(vault) = &self.vault {
+            matches.retain(|m| !vault.is_token(&text[m.start..m.end]));
+        }
+        matches
+    }
+
+    /// Record the matches of a text, redact those with the redact action
+    /// and restore vault tokens
+    fn apply(&self, text: &str, matches: Vec<PiiMatch>, findings: &mut Vec<PiiFinding>) -> String {
+        let mut redact = Vec::new();
+        for m in matches {
+            let action = self.action_for(m.pii_type);
+            findings.push(PiiFinding { pii_type: m.pii_type, action });
+            if action == PiiAction::Redact {
+                redact.push(m);
+            }
+        }
+        let redacted = redact_matches(text, &redact);
+        match &self.vault {
+            Some(vault) => vault.restore(&redacted),
+            None => redacted,
+        }
+    }
+
+    fn action_for(&self, pii_type: PiiType) -> PiiAction {
+        if self.enforce {
+            self.config.action_for(pii_type)
+        } else {
+            PiiAction::Log
+        }
+    }
+
+    fn blocks(&self, findings: &[PiiFinding]) -> bool {
+        findings.iter().any(|f| f.action == PiiAction::Block)
+    }
+}
+
+fn anthropic_delta(index: u64, text: String) -> Value {
+    json!({"type": "content_block_delta", "index": index, "delta": {"type": "text_delta", "text": text}})
+}
+
+fn push_event(out: &mut RedactedChunk, event: Option<&str>, data: &Value) {
+    let event = SseEvent {
+        event: event.map(str::to_string),
+        id: None,
+        data: serde_json::to_vec(data).unwrap_or_default(),
+        truncated: false,
+    };
+    out.bytes.extend(event.to_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redactor(config: &ResponsePiiConfig) -> ResponsePiiRedactor {
+        ResponsePiiRedactor::new(config, PiiRedactor::default(), true)
+    }
+
+    fn chat(content: &str) -> Vec<u8> {
+        let chunk = json!({"choices": [{"index": 0, "delta": {"content": content}, "finish_reason": null}]});
+        format!("data: {}\n\n", chunk).into_bytes()
+    }
+
+    /// Streamed text of the forwarded Chat Completions events
+    fn streamed_text(bytes: &[u8]) -> String {
+        SseParser::new(1 << 16)
+            .feed(bytes)
+            .iter()
+            .filter_map(|e| serde_json::from_slice::<Value>(&e.data).ok())
+            .filter_map(|v| v["choices"][0]["delta"]["content"].as_str().map(str::to_string))
+            .collect()
+    }
+
+    #[test]
+    fn test_split_delta_redacted() {
+        let config = ResponsePiiConfig { holdback_bytes: 16, ..Default::default() };
+        let mut pii = redactor(&config);
+        let mut forwarded = Vec::new();
+        let mut findings = Vec::new();
+        for part in ["Your SSN is 123-", "45-6789, keep it ", "safe. Thanks for asking!"] {
+            let out = pii.feed(&chat(part));
+            assert!(!out.blocked);
+            forwarded.extend(out.bytes);
+            findings.extend(out.findings);
+        }
+        forwarded.extend(pii.feed(b"data: [DONE]\n\n").bytes);
+
+        assert_eq!(
+            streamed_text(&forwarded),
+            "Your SSN is [SSN REDACTED], keep it safe. Thanks for asking!"
+        );
+        assert_eq!(findings, vec![PiiFinding { pii_type: PiiType::Ssn, action: PiiAction::Redact }]);
+        assert!(forwarded.ends_with(b"data: [DONE]\n\n"));
+    }
+
+    #[test]
+    fn test_anthropic_flushed_before_block_stop() {
+        let config = ResponsePiiConfig::default();
+        let mut pii = redactor(&config);
+        let delta = anthropic_delta(0, "mail jane.doe@example.com".to_string());
+        let stream = format!(
+            "event: content_block_delta\ndata: {}\n\nevent: content_block_stop\ndata: {}\n\n",
+            delta,
+            json!({"type": "content_block_stop", "index": 0})
+        );
+        let out = pii.feed(stream.as_bytes());
+        let events = SseParser::new(1 << 16).feed(&out.bytes);
+        let text: String = events
+            .iter()
+            .filter_map(|e| serde_json::from_slice::<Value>(&e.data).ok())
+            .filter_map(|v| v["delta"]["text"].as_str().map(str::to_string))
+            .collect();
+        assert_eq!(text, "mail [EMAIL REDACTED]");
+        assert_eq!(events.last().unwrap().event.as_deref(), Some("content_block_stop"));
+    }
+
+    #[test]
+    fn test_actions() {
+        let mut config = ResponsePiiConfig::default();
+        config.actions.insert("email".to_string(), PiiAction::Log);
+        config.actions.insert("ssn".to_string(), PiiAction::Block);
+
+        let body = br#"{"choices":[{"message":{"content":"write to jane.doe@example.com"}}]}"#;
+        let mut out = RedactedChunk::default();
+        assert_eq!(redactor(&config).redact_body(body, &mut out), None);
+        assert_eq!(out.findings[0].action, PiiAction::Log);
+
+        let text = format!("SSN 123-45-6789 {}", "and more ".repeat(10));
+        let mut pii = redactor(&config);
+        let out = pii.feed(&chat(&text));
+        assert!(out.blocked);
+        assert!(out.bytes.is_empty());
+
+        // Monitor mode only logs
+        let mut pii = ResponsePiiRedactor::new(&config, PiiRedactor::default(), false);
+        let out = pii.feed(&chat(&text));
+        assert!(!out.blocked);
+        assert_eq!(out.findings[0].action, PiiAction::Log);
+    }
+
+    #[test]
+    fn test_vault_tokens_restored() {
+        let mut vault = TokenVault::new("0123456789abcdef");
+        let token = vault.tokenize(PiiType::Email, "jane.doe@example.com");
+        let config = ResponsePiiConfig { holdback_bytes: 4, ..Default::default() };
+        let mut pii = redactor(&config).with_vault(vault);
+        let (head, tail) = token.split_at(7);
+        let mut forwarded = pii.feed(&chat(&format!("Reply to {}", head))).bytes;
+        forwarded.extend(pii.feed(&chat(&format!("{} soon", tail))).bytes);
+        forwarded.extend(pii.finish().bytes);
+        assert_eq!(streamed_text(&forwarded), "Reply to jane.doe@example.com soon");
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut config = ResponsePiiConfig { holdback_bytes: 0, ..Default::default() };
+        config.actions.insert("passport".to_string(), PiiAction::Block);
+        assert_eq!(
+            config.validate(),
+            vec![
+                "response_pii.actions.passport: unknown PII type".to_string(),
+                "response_pii.holdback_bytes: must be greater than 0".to_string(),
+            ]
+        );
+    }
+}
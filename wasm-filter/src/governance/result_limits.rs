@@ -0,0 +1,437 @@
+//! MCP Tool-Result Size and Depth Limits
+//!
+//! A `tools/call` result is deserialized by the agent, so a hostile or
+//! broken tool server can knock it over with a huge payload, thousands of
+//! nested arrays, or an array of millions of elements. Results are
+//! measured with a byte-level pass (no parsing) against configured limits
+//! on size, nesting depth and array length. A result over a limit is
+//! replaced by a JSON-RPC error or, when configured, truncated: arrays are
+//! cut to the maximum length and containers below the maximum depth are
+//! replaced by a marker string. Oversized results are always replaced.
+
+use crate::protocols::mcp::method_policy::glob_match;
+use crate::protocols::mcp::{JsonRpcError, JsonRpcResponse, JsonRpcSniffer};
+use crate::streaming::SseParser;
+use serde::Deserialize;
+use serde_json::Value;
+use std::fmt;
+
+/// Replacement for containers below the maximum depth
+const TRUNCATED_VALUE: &str = "[truncated by AI-Guard]";
+
+/// Deepest nesting accepted as `max_depth` (serde_json refuses to parse past 128)
+pub const MAX_DEPTH_LIMIT: usize = 64;
+
+/// What to do with a result over a limit
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResultLimitAction {
+    /// Replace the message with a JSON-RPC policy_violation error
+    #[default]
+    Reject,
+    /// Cut arrays and deep containers down to the limits
+    Truncate,
+}
+
+impl ResultLimitAction {
+    /// Stable lowercase name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResultLimitAction::Reject => "reject",
+            ResultLimitAction::Truncate => "truncate",
+        }
+    }
+}
+
+/// Tool-result limit configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResultLimits {
+    /// Methods whose results are limited (glob patterns)
+    pub methods: Vec<String>,
+    /// Largest result message, in bytes
+    pub max_bytes: usize,
+    /// Deepest nesting of objects and arrays, counting the message envelope
+    pub max_depth: usize,
+    /// Most elements in any one array
+    pub max_array_len: usize,
+    /// Action on a result over the depth or array limit
+    pub action: ResultLimitAction,
+}
+
+impl Default for ResultLimits {
+    fn default() -> Self {
+        Self {
+            methods: vec!["tools/call".to_string()],
+            max_bytes: 1024 * 1024,
+            max_depth: 32,
+            max_array_len: 10_000,
+            action: ResultLimitAction::default(),
+        }
+    }
+}
+
+impl ResultLimits {
+    /// Validate the configuration, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        if self.methods.is_empty() {
+            diagnostics.push("tool_result_limits.methods: must not be empty".to_string());
+        }
+        if self.max_bytes == 0 {
+            diagnostics.push("tool_result_limits.max_bytes: must be greater than 0".to_string());
+        }
+        if self.max_depth < 2 || self.max_depth > MAX_DEPTH_LIMIT {
+            diagnostics.push(format!(
+                "tool_result_limits.max_depth: must be between 2 and {}",
+                MAX_DEPTH_LIMIT
+            ));
+        }
+        if self.max_array_len == 0 {
+            diagnostics.push("tool_result_limits.max_array_len: must be greater than 0".to_string());
+        }
+        diagnostics
+    }
+
+    /// Whether results of a method are limited
+    pub fn applies_to(&self, method: &str) -> bool {
+        self.methods.iter().any(|p| glob_match(p, method))
+    }
+
+    /// Check a complete result message against the limits
+    pub fn check(&self, message: &[u8]) -> Option<ResultLimitViolation> {
+        if message.len() > self.max_bytes {
+            return Some(ResultLimitViolation::Size { bytes: message.len(), limit: self.max_bytes });
+        }
+        let shape = measure(message);
+        if shape.depth > self.max_depth {
+            return Some(ResultLimitViolation::Depth { depth: shape.depth, limit: self.max_depth });
+        }
+        if shape.max_array_len > self.max_array_len {
+            return Some(ResultLimitViolation::ArrayLength {
+                len: shape.max_array_len,
+                limit: self.max_array_len,
+            });
+        }
+        None
+    }
+
+    /// Apply the limits to a result message answering request `id`
+    pub fn apply(&self, message: &[u8], id: &Value) -> LimitedResult {
+        let violation = match self.check(message) {
+            Some(violation) => violation,
+            None => return LimitedResult { violation: None, bytes: None },
+        };
+        let truncated = match (&violation, self.action) {
+            (ResultLimitViolation::Size { .. }, _) | (_, ResultLimitAction::Reject) => None,
+            (_, ResultLimitAction::Truncate) => self.truncate(message),
+        };
+        let bytes = truncated.unwrap_or_else(|| {
+            let reason = format!("Tool result rejected: {}", violation);
+            let error = JsonRpcError::policy_violation(&reason);
+            serde_json::to_vec(&JsonRpcResponse::error(id.clone(), error)).unwrap_or_default()
+        });
+        LimitedResult { violation: Some(violation), bytes: Some(bytes) }
+    }
+
+    /// Cut a message down to the depth and array limits (None if it does not parse)
+    fn truncate(&self, message: &[u8]) -> Option<Vec<u8>> {
+        let mut value: Value = serde_json::from_slice(message).ok()?;
+        self.cut(&mut value, 1);
+        serde_json::to_vec(&value).ok()
+    }
+
+    fn cut(&self, value: &mut Value, depth: usize) {
+        let is_container = matches!(value, Value::Array(_) | Value::Object(_));
+        if is_container && depth > self.max_depth {
+            *value = Value::String(TRUNCATED_VALUE.to_string());
+            return;
+        }
+        match value {
+            Value::Array(items) => {
+                items.truncate(self.max_array_len);
+                for item in items {
+                    self.cut(item, depth + 1);
+                }
+            }
+            Value::Object(map) => {
+                for (_, item) in map.iter_mut() {
+                    self.cut(item, depth + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Limit a result went over
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResultLimitViolation {
+    /// Larger than `max_bytes`
+    Size {
+        /// Message size
+        bytes: usize,
+        /// Configured limit
+        limit: usize,
+    },
+    /// Nested deeper than `max_depth`
+    Depth {
+        /// Deepest nesting found
+        depth: usize,
+        /// Configured limit
+        limit: usize,
+    },
+    /// An array longer than `max_array_len`
+    ArrayLength {
+        /// Longest array found
+        len: usize,
+        /// Configured limit
+        limit: usize,
+    },
+}
+
+impl ResultLimitViolation {
+    /// Stable lowercase name of the limit
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResultLimitViolation::Size { .. } => "max_bytes",
+            ResultLimitViolation::Depth { .. } => "max_depth",
+            ResultLimitViolation::ArrayLength { .. } => "max_array_len",
+        }
+    }
+}
+
+impl fmt::Display for ResultLimitViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResultLimitViolation::Size { bytes, limit } => {
+                write!(f, "{} bytes (limit {})", bytes, limit)
+            }
+            ResultLimitViolation::Depth { depth, limit } => {
+                write!(f, "nested {} levels deep (limit {})", depth, limit)
+            }
+            ResultLimitViolation::ArrayLength { len, limit } => {
+                write!(f, "array of {} elements (limit {})", len, limit)
+            }
+        }
+    }
+}
+
+/// Output of [`ResultLimits::apply`]
+#[derive(Debug, Default)]
+pub struct LimitedResult {
+    /// Limit the message went over
+    pub violation: Option<ResultLimitViolation>,
+    /// Replacement message, when the original was over a limit
+    pub bytes: Option<Vec<u8>>,
+}
+
+/// Nesting depth and longest array of a JSON text
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Shape {
+    depth: usize,
+    max_array_len: usize,
+}
+
+/// Measure a JSON text without parsing it
+///
+/// Element counts are kept per open array; an empty array has none.
+fn measure(json: &[u8]) -> Shape {
+    let mut shape = Shape::default();
+    // Per open container: element count for arrays, None for objects
+    let mut open: Vec<Option<usize>> = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    for &b in json {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if b == b'\\' {
+                escaped = true;
+            } else if b == b'"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match b {
+            b'"' => {
+                in_string = true;
+                start_element(&mut open, &mut shape);
+            }
+            b'{' | b'[' => {
+                start_element(&mut open, &mut shape);
+                open.push(if b == b'[' { Some(0) } else { None });
+                shape.depth = shape.depth.max(open.len());
+            }
+            b'}' | b']' => {
+                open.pop();
+            }
+            b',' => {
+                if let Some(Some(count)) = open.last_mut() {
+                    *count += 1;
+                    shape.max_array_len = shape.max_array_len.max(*count);
+                }
+            }
+            b' ' | b'\t' | b'\r' | b'\n' | b':' => {}
+            _ => start_element(&mut open, &mut shape),
+        }
+    }
+    shape
+}
+
+/// Count the first element of an array (later ones are counted at commas)
+fn start_element(open: &mut [Option<usize>], shape: &mut Shape) {
+    if let Some(Some(count)) = open.last_mut() {
+        if *count == 0 {
+            *count = 1;
+            shape.max_array_len = shape.max_array_len.max(1);
+        }
+    }
+}
+
+/// Output of [`ResultLimitRewriter::feed`]
+#[derive(Debug, Default)]
+pub struct LimitedChunk {
+    /// Bytes to forward in place of the chunk
+    pub bytes: Vec<u8>,
+    /// Limits exceeded by events of the chunk
+    pub violations: Vec<ResultLimitViolation>,
+}
+
+/// Applies result limits to the messages of an MCP SSE response event by event
+///
+/// Only complete events are forwarded; a partial event is held until its
+/// terminating blank line arrives, up to `max_bytes`.
+pub struct ResultLimitRewriter {
+    parser: SseParser,
+    limits: ResultLimits,
+}
+
+impl ResultLimitRewriter {
+    /// Create a rewriter for one response
+    pub fn new(limits: &ResultLimits) -> Self {
+        Self { parser: SseParser::new(limits.max_bytes), limits: limits.clone() }
+    }
+
+    /// Feed a response chunk
+    pub fn feed(&mut self, chunk: &[u8]) -> LimitedChunk {
+        let mut out = LimitedChunk::default();
+        for mut event in self.parser.feed(chunk) {
+            let mut sniffer = JsonRpcSniffer::new();
+            sniffer.observe(&event.data);
+            // Server requests and notifications are not results
+            if sniffer.method().is_some() && !event.truncated {
+                out.bytes.extend(event.to_bytes());
+                continue;
+            }
+            let id = sniffer.id().cloned().unwrap_or(Value::Null);
+            let limited = if event.truncated {
+                // The parser kept only the first `max_bytes`
+                let violation = ResultLimitViolation::Size {
+                    bytes: self.limits.max_bytes + 1,
+                    limit: self.limits.max_bytes,
+                };
+                let reason = format!("Tool result rejected: over {} bytes", self.limits.max_bytes);
+                let error = JsonRpcError::policy_violation(&reason);
+                let response = JsonRpcResponse::error(id, error);
+                let bytes = serde_json::to_vec(&response).unwrap_or_default();
+                LimitedResult { violation: Some(violation), bytes: Some(bytes) }
+            } else {
+                self.limits.apply(&event.data, &id)
+            };
+            if let (Some(violation), Some(bytes)) = (limited.violation, limited.bytes) {
+                event.data = bytes;
+                event.truncated = false;
+                out.violations.push(violation);
+            }
+            out.bytes.extend(event.to_bytes());
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_measure() {
+        let shape = measure(br#"{"a":[1,2,[3,"x,]"],{}],"b":[]}"#);
+        assert_eq!(shape, Shape { depth: 3, max_array_len: 4 });
+        assert_eq!(measure(b"[[[[]]]]").depth, 4);
+        assert_eq!(measure(b"[[[[]]]]").max_array_len, 1);
+        assert_eq!(measure(br#"{"s":"[[[[\"]]"}"#).depth, 1);
+    }
+
+    #[test]
+    fn test_reject() {
+        let limits = ResultLimits { max_depth: 4, max_array_len: 3, ..Default::default() };
+        let ok = br#"{"jsonrpc":"2.0","id":1,"result":{"content":[{"type":"text"}]}}"#;
+        assert_eq!(limits.check(ok), None);
+
+        let nested = format!("{}1{}", "[".repeat(5), "]".repeat(5));
+        let deep = format!(r#"{{"jsonrpc":"2.0","id":2,"result":{}}}"#, nested);
+        let limited = limits.apply(deep.as_bytes(), &Value::from(2));
+        assert_eq!(limited.violation, Some(ResultLimitViolation::Depth { depth: 6, limit: 4 }));
+        let error: Value = serde_json::from_slice(&limited.bytes.unwrap()).unwrap();
+        assert_eq!(error["id"], 2);
+        let message = error["error"]["message"].as_str().unwrap();
+        assert!(message.contains("nested 6 levels deep (limit 4)"));
+
+        let long = br#"{"jsonrpc":"2.0","id":3,"result":{"items":[1,2,3,4,5]}}"#;
+        let violation = limits.check(long).unwrap();
+        assert_eq!(violation.as_str(), "max_array_len");
+
+        let limits = ResultLimits { max_bytes: 10, ..Default::default() };
+        assert_eq!(limits.check(ok).unwrap().as_str(), "max_bytes");
+    }
+
+    #[test]
+    fn test_truncate() {
+        let limits = ResultLimits {
+            max_depth: 3,
+            max_array_len: 2,
+            action: ResultLimitAction::Truncate,
+            ..Default::default()
+        };
+        let body = br#"{"jsonrpc":"2.0","id":1,"result":{"items":[1,2,3],"deep":{"a":{"b":1}}}}"#;
+        let limited = limits.apply(body, &Value::from(1));
+        assert_eq!(limited.violation.unwrap().as_str(), "max_depth");
+        let message: Value = serde_json::from_slice(&limited.bytes.unwrap()).unwrap();
+        assert_eq!(message["result"]["items"], serde_json::json!([1, 2]));
+        assert_eq!(message["result"]["deep"]["a"], TRUNCATED_VALUE);
+
+        // Size cannot be truncated away
+        let limits = ResultLimits { max_bytes: 10, ..limits };
+        let limited = limits.apply(body, &Value::from(1));
+        let error: Value = serde_json::from_slice(&limited.bytes.unwrap()).unwrap();
+        assert!(error.get("error").is_some());
+    }
+
+    #[test]
+    fn test_sse_events() {
+        let limits = ResultLimits { max_bytes: 64, max_array_len: 2, ..Default::default() };
+        let mut rewriter = ResultLimitRewriter::new(&limits);
+        let progress = r#"{"jsonrpc":"2.0","method":"notifications/progress","params":{}}"#;
+        let result = r#"{"jsonrpc":"2.0","id":5,"result":{"a":[1,2,3]}}"#;
+        let huge = format!(r#"{{"jsonrpc":"2.0","id":6,"result":"{}"}}"#, "x".repeat(100));
+        let stream = format!("data: {}\n\ndata: {}\n\ndata: {}\n\n", progress, result, huge);
+        let out = rewriter.feed(stream.as_bytes());
+        let kinds: Vec<&str> = out.violations.iter().map(|v| v.as_str()).collect();
+        assert_eq!(kinds, vec!["max_array_len", "max_bytes"]);
+        let events = SseParser::new(4096).feed(&out.bytes);
+        assert_eq!(events[0].data, progress.as_bytes());
+        let rejected: Value = serde_json::from_slice(&events[1].data).unwrap();
+        assert_eq!(rejected["id"], 5);
+        let oversized: Value = serde_json::from_slice(&events[2].data).unwrap();
+        assert_eq!(oversized["id"], 6);
+    }
+
+    #[test]
+    fn test_validate() {
+        let limits = ResultLimits { max_depth: 100, max_array_len: 0, ..Default::default() };
+        assert_eq!(limits.validate().len(), 2);
+        assert!(ResultLimits::default().applies_to("tools/call"));
+        assert!(!ResultLimits::default().applies_to("tools/list"));
+    }
+}
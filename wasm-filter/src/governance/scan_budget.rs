@@ -0,0 +1,103 @@
+//! Scan Budget
+//!
+//! A multi-megabyte prompt should not be able to hold up the proxy while
+//! every byte goes through the scanner. The budget bounds the inspection of
+//! each request body:
+//!
+//! - `max_scan_bytes`: only the first N bytes (after decompression) are
+//!   scanned
+//! - `max_chunk_micros`: a chunk that keeps the scanner busier than this
+//!   ends the scan
+//!
+//! Either way the request is forwarded with what was scanned so far (best
+//! effort): the skip is logged, counted in
+//! `ai_guard.scan_budget_exhausted.<bytes|time>` and recorded as a `skip`
+//! scan span. `max_body_size` differs in that a body exceeding it is not
+//! scanned at all past the last chunk that fit.
+
+use serde::Deserialize;
+
+/// Per-request scan budget
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScanBudget {
+    /// Body bytes scanned before the rest is forwarded unscanned
+    pub max_scan_bytes: Option<usize>,
+    /// Scanner time per chunk, in microseconds, before the rest is forwarded unscanned
+    pub max_chunk_micros: Option<u64>,
+}
+
+impl ScanBudget {
+    /// Validate the budget, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        if self.max_scan_bytes == Some(0) {
+            diagnostics.push("scan_budget.max_scan_bytes: must be greater than 0".to_string());
+        }
+        if self.max_chunk_micros == Some(0) {
+            diagnostics.push("scan_budget.max_chunk_micros: must be greater than 0".to_string());
+        }
+        diagnostics
+    }
+
+    /// Whether scanning one chunk took longer than allowed
+    pub fn exceeds_time(&self, micros: u64) -> bool {
+        self.max_chunk_micros.is_some_and(|limit| micros > limit)
+    }
+}
+
+/// Which part of the budget ran out
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BudgetLimit {
+    /// `max_scan_bytes`
+    Bytes,
+    /// `max_chunk_micros`
+    Time,
+}
+
+impl BudgetLimit {
+    /// Metric label
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BudgetLimit::Bytes => "bytes",
+            BudgetLimit::Time => "time",
+        }
+    }
+
+    /// Scan decision reason
+    pub fn reason(&self) -> &'static str {
+        match self {
+            BudgetLimit::Bytes => "Scan byte budget exhausted",
+            BudgetLimit::Time => "Scan time budget exhausted",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let json = r#"{"max_scan_bytes": 65536, "max_chunk_micros": 2000}"#;
+        let budget: ScanBudget = serde_json::from_str(json).unwrap();
+        assert_eq!(budget.max_scan_bytes, Some(65536));
+        assert!(budget.validate().is_empty());
+        assert!(budget.exceeds_time(2001));
+        assert!(!budget.exceeds_time(2000));
+        assert!(!ScanBudget::default().exceeds_time(u64::MAX));
+    }
+
+    #[test]
+    fn test_validate() {
+        let budget = ScanBudget { max_scan_bytes: Some(0), max_chunk_micros: Some(0) };
+        assert_eq!(
+            budget.validate(),
+            vec![
+                "scan_budget.max_scan_bytes: must be greater than 0".to_string(),
+                "scan_budget.max_chunk_micros: must be greater than 0".to_string(),
+            ]
+        );
+        assert_eq!(BudgetLimit::Time.as_str(), "time");
+    }
+}
@@ -0,0 +1,291 @@
+//! Session Correlation
+//!
+//! Correlates requests of one conversation (or MCP session) through a
+//! client-supplied header and keeps rolling per-session state in shared
+//! data: cumulative tokens and policy violations. Repeat offenders get an
+//! escalating response: a warning, then one request per cool-down window,
+//! then a blocked session.
+//!
+//! Session ids are hashed into a fixed number of buckets, each holding a
+//! bounded number of sessions (least recently seen are evicted), so shared
+//! memory stays bounded however many ids clients invent.
+
+use crate::shared::{SharedError, SharedStore};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Number of shared data buckets
+const BUCKETS: u64 = 256;
+
+/// Sessions kept per bucket
+const MAX_SESSIONS_PER_BUCKET: usize = 32;
+
+/// Response header flagging a session with violations on record
+pub const SESSION_RESPONSE_HEADER: &str = "x-ai-guard-session";
+
+/// Longest session id accepted
+pub const MAX_SESSION_ID_LEN: usize = 128;
+
+/// Session tracking configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionConfig {
+    /// Headers carrying the session id (first present wins)
+    pub headers: Vec<String>,
+    /// Violations after which responses carry a warning header
+    pub warn_after: u32,
+    /// Violations after which the session is limited to one request per cool-down
+    pub rate_limit_after: u32,
+    /// Violations after which the session is blocked
+    pub block_after: u32,
+    /// Cool-down between requests of a rate-limited session, in seconds
+    pub cooldown_secs: u64,
+    /// Idle time after which a session is forgotten, in seconds
+    pub idle_ttl_secs: u64,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            headers: vec!["x-conversation-id".to_string(), "mcp-session-id".to_string()],
+            warn_after: 1,
+            rate_limit_after: 3,
+            block_after: 5,
+            cooldown_secs: 30,
+            idle_ttl_secs: 3600,
+        }
+    }
+}
+
+impl SessionConfig {
+    /// Validate the configuration, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        if self.headers.is_empty() {
+            diagnostics.push("sessions.headers: must not be empty".to_string());
+        }
+        if self.warn_after == 0 {
+            diagnostics.push("sessions.warn_after: must be greater than 0".to_string());
+        }
+        if self.warn_after > self.rate_limit_after || self.rate_limit_after > self.block_after {
+            diagnostics.push(
+                "sessions: warn_after <= rate_limit_after <= block_after required".to_string(),
+            );
+        }
+        if self.idle_ttl_secs == 0 {
+            diagnostics.push("sessions.idle_ttl_secs: must be greater than 0".to_string());
+        }
+        diagnostics
+    }
+
+    /// Escalation step for a session
+    pub fn action(&self, state: &SessionState, now_secs: u64) -> SessionAction {
+        if state.violations >= self.block_after {
+            return SessionAction::Block;
+        }
+        if state.violations >= self.rate_limit_after {
+            let wait = (state.last_request_secs + self.cooldown_secs).saturating_sub(now_secs);
+            if wait > 0 {
+                return SessionAction::RateLimit { retry_after_secs: wait };
+            }
+        }
+        if state.violations >= self.warn_after {
+            return SessionAction::Warn;
+        }
+        SessionAction::Allow
+    }
+}
+
+/// Rolling state of one session
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionState {
+    /// Tokens used across the session
+    pub tokens: u64,
+    /// Requests blocked across the session
+    pub violations: u32,
+    /// Start of the latest request (Unix seconds)
+    pub last_request_secs: u64,
+    /// Latest activity (Unix seconds)
+    pub last_seen_secs: u64,
+}
+
+/// Escalating response to a session's history
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionAction {
+    /// No history of concern
+    Allow,
+    /// Forward, flagging the session in the response
+    Warn,
+    /// Reject until the cool-down has passed
+    RateLimit {
+        /// Seconds until the next request is accepted
+        retry_after_secs: u64,
+    },
+    /// Reject every request of the session
+    Block,
+}
+
+impl SessionAction {
+    /// Stable lowercase name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SessionAction::Allow => "allow",
+            SessionAction::Warn => "warn",
+            SessionAction::RateLimit { .. } => "rate_limit",
+            SessionAction::Block => "block",
+        }
+    }
+}
+
+/// Whether a header value is usable as a session id
+pub fn valid_session_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_SESSION_ID_LEN
+        && id.bytes().all(|b| b.is_ascii_graphic())
+}
+
+/// State of a session (default when unknown or idle past the TTL)
+pub fn lookup(
+    store: &impl SharedStore,
+    config: &SessionConfig,
+    id: &str,
+    now_secs: u64,
+) -> SessionState {
+    parse(store.get(&bucket_key(id)).0.as_deref())
+        .remove(id)
+        .filter(|s| !expired(s, config, now_secs))
+        .unwrap_or_default()
+}
+
+/// Update a session's state, returning the new state
+pub fn record<F>(
+    store: &impl SharedStore,
+    config: &SessionConfig,
+    id: &str,
+    now_secs: u64,
+    mut update: F,
+) -> Result<SessionState, SharedError>
+where
+    F: FnMut(&mut SessionState),
+{
+    let mut updated = SessionState::default();
+    store.update(&bucket_key(id), |current| {
+        let mut sessions = parse(current);
+        sessions.retain(|_, s| !expired(s, config, now_secs));
+        let mut state = sessions.remove(id).unwrap_or_default();
+        update(&mut state);
+        state.last_seen_secs = now_secs;
+        if sessions.len() >= MAX_SESSIONS_PER_BUCKET {
+            let oldest = sessions
+                .iter()
+                .min_by_key(|(_, s)| s.last_seen_secs)
+                .map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                sessions.remove(&oldest);
+            }
+        }
+        sessions.insert(id.to_string(), state.clone());
+        updated = state;
+        serde_json::to_vec(&sessions).unwrap_or_default()
+    })?;
+    Ok(updated)
+}
+
+fn expired(state: &SessionState, config: &SessionConfig, now_secs: u64) -> bool {
+    now_secs.saturating_sub(state.last_seen_secs) > config.idle_ttl_secs
+}
+
+/// Shared data key of the bucket holding a session (FNV-1a hash of the id)
+fn bucket_key(id: &str) -> String {
+    let hash = id.bytes().fold(0xcbf2_9ce4_8422_2325u64, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("ai_guard.sessions.{}", hash % BUCKETS)
+}
+
+fn parse(value: Option<&[u8]>) -> BTreeMap<String, SessionState> {
+    value
+        .and_then(|v| serde_json::from_slice(v).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::MemorySharedStore;
+
+    fn violation(store: &MemorySharedStore, config: &SessionConfig, id: &str, now: u64) {
+        record(store, config, id, now, |s| s.violations += 1).unwrap();
+    }
+
+    #[test]
+    fn test_escalation() {
+        let store = MemorySharedStore::new();
+        let config = SessionConfig::default();
+        let state = |now| lookup(&store, &config, "conv-1", now);
+        assert_eq!(config.action(&state(0), 100), SessionAction::Allow);
+
+        violation(&store, &config, "conv-1", 100);
+        assert_eq!(config.action(&state(100), 100), SessionAction::Warn);
+
+        violation(&store, &config, "conv-1", 101);
+        violation(&store, &config, "conv-1", 102);
+        record(&store, &config, "conv-1", 110, |s| s.last_request_secs = 110).unwrap();
+        assert_eq!(
+            config.action(&state(120), 120),
+            SessionAction::RateLimit { retry_after_secs: 20 }
+        );
+        assert_eq!(config.action(&state(140), 140), SessionAction::Warn);
+
+        violation(&store, &config, "conv-1", 141);
+        violation(&store, &config, "conv-1", 142);
+        assert_eq!(config.action(&state(150), 150), SessionAction::Block);
+        // Other sessions are unaffected
+        let other = lookup(&store, &config, "conv-2", 150);
+        assert_eq!(config.action(&other, 150), SessionAction::Allow);
+    }
+
+    #[test]
+    fn test_tokens_and_idle_expiry() {
+        let store = MemorySharedStore::new();
+        let config = SessionConfig { idle_ttl_secs: 60, ..Default::default() };
+        record(&store, &config, "s", 0, |s| s.tokens += 500).unwrap();
+        let state = record(&store, &config, "s", 30, |s| s.tokens += 250).unwrap();
+        assert_eq!(state.tokens, 750);
+        assert_eq!(lookup(&store, &config, "s", 100), SessionState::default());
+    }
+
+    #[test]
+    fn test_bucket_is_bounded() {
+        let store = MemorySharedStore::new();
+        let config = SessionConfig::default();
+        let key = bucket_key("session-0");
+        let colliding: Vec<String> = (0..)
+            .map(|i| format!("session-{}", i))
+            .filter(|id| bucket_key(id) == key)
+            .take(MAX_SESSIONS_PER_BUCKET + 8)
+            .collect();
+        for (now, id) in colliding.iter().enumerate() {
+            record(&store, &config, id, now as u64, |s| s.tokens += 1).unwrap();
+        }
+        let sessions = parse(store.get(&key).0.as_deref());
+        assert_eq!(sessions.len(), MAX_SESSIONS_PER_BUCKET);
+        // The least recently seen were evicted
+        assert!(!sessions.contains_key("session-0"));
+        assert!(sessions.contains_key(colliding.last().unwrap()));
+    }
+
+    #[test]
+    fn test_session_ids_and_validate() {
+        assert!(valid_session_id("3f2a-conv_01"));
+        assert!(!valid_session_id(""));
+        assert!(!valid_session_id("has space"));
+        assert!(!valid_session_id(&"x".repeat(MAX_SESSION_ID_LEN + 1)));
+
+        let config = SessionConfig { rate_limit_after: 9, ..Default::default() };
+        assert_eq!(
+            config.validate(),
+            vec!["sessions: warn_after <= rate_limit_after <= block_after required".to_string()]
+        );
+    }
+}
@@ -0,0 +1,134 @@
+//! Severity-Driven Actions
+//!
+//! Makes the response to a body match proportional to its severity instead
+//! of always a 403. Each severity maps to an action:
+//! - `log`: forward, recording the match as monitored
+//! - `tag`: forward, and mark the response with the match severity
+//! - `block`: reject the request
+//! - `ban`: reject the request and block its session from then on
+//!
+//! Banning escalates the session straight to `sessions.block_after`, so it
+//! needs session tracking. Quarantine routing, when enabled, takes
+//! precedence for the severities it covers.
+
+use super::prompt_injection::InjectionSeverity;
+use serde::Deserialize;
+
+/// Response to a match of a given severity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SeverityAction {
+    /// Forward and record the match
+    Log,
+    /// Forward and tag the response with the severity
+    Tag,
+    /// Reject the request
+    Block,
+    /// Reject the request and block the session
+    Ban,
+}
+
+impl SeverityAction {
+    /// Stable lowercase name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SeverityAction::Log => "log",
+            SeverityAction::Tag => "tag",
+            SeverityAction::Block => "block",
+            SeverityAction::Ban => "ban",
+        }
+    }
+
+    /// Whether the request is forwarded
+    pub fn forwards(&self) -> bool {
+        matches!(self, SeverityAction::Log | SeverityAction::Tag)
+    }
+}
+
+/// Severity-to-action mapping
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SeverityActionsConfig {
+    /// Action for low-severity matches
+    pub low: SeverityAction,
+    /// Action for medium-severity matches
+    pub medium: SeverityAction,
+    /// Action for high-severity matches
+    pub high: SeverityAction,
+    /// Action for critical matches
+    pub critical: SeverityAction,
+    /// Response header carrying the severity of a tagged match
+    pub header: String,
+}
+
+impl Default for SeverityActionsConfig {
+    fn default() -> Self {
+        Self {
+            low: SeverityAction::Log,
+            medium: SeverityAction::Tag,
+            high: SeverityAction::Block,
+            critical: SeverityAction::Ban,
+            header: "x-ai-guard-severity".to_string(),
+        }
+    }
+}
+
+impl SeverityActionsConfig {
+    /// Validate the configuration, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        let valid_header = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-';
+        if self.header.is_empty() || !self.header.chars().all(valid_header) {
+            diagnostics.push(format!(
+                "severity_actions.header: invalid header name '{}'",
+                self.header
+            ));
+        }
+        diagnostics
+    }
+
+    /// Action for a match of this severity
+    pub fn action(&self, severity: InjectionSeverity) -> SeverityAction {
+        match severity {
+            InjectionSeverity::Low => self.low,
+            InjectionSeverity::Medium => self.medium,
+            InjectionSeverity::High => self.high,
+            InjectionSeverity::Critical => self.critical,
+        }
+    }
+
+    /// Whether any severity bans sessions
+    pub fn bans(&self) -> bool {
+        [self.low, self.medium, self.high, self.critical].contains(&SeverityAction::Ban)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_mapping() {
+        let config = SeverityActionsConfig::default();
+        assert_eq!(config.action(InjectionSeverity::Low), SeverityAction::Log);
+        assert_eq!(config.action(InjectionSeverity::Medium), SeverityAction::Tag);
+        assert_eq!(config.action(InjectionSeverity::High), SeverityAction::Block);
+        assert_eq!(config.action(InjectionSeverity::Critical), SeverityAction::Ban);
+        assert!(config.bans());
+        assert!(SeverityAction::Tag.forwards());
+        assert!(!SeverityAction::Ban.forwards());
+    }
+
+    #[test]
+    fn test_parse_and_validate() {
+        let json = r#"{"critical": "block", "header": "X-Bad"}"#;
+        let config: SeverityActionsConfig = serde_json::from_str(json).unwrap();
+        assert_eq!(config.action(InjectionSeverity::Critical), SeverityAction::Block);
+        assert!(!config.bans());
+        assert_eq!(
+            config.validate(),
+            vec!["severity_actions.header: invalid header name 'X-Bad'".to_string()]
+        );
+        assert!(serde_json::from_str::<SeverityActionsConfig>(r#"{"low": "drop"}"#).is_err());
+    }
+}
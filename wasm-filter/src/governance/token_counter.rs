@@ -1,8 +1,14 @@
 //! Token Counter Module
 //!
 //! Extracts token usage from AI API responses for cost attribution.
-//! Supports common AI provider formats (OpenAI, Anthropic, etc.)
-
+//! Supports common AI provider formats:
+//! - OpenAI (and OpenAI-compatible APIs such as Mistral): `usage.prompt_tokens`
+//! - Anthropic: `usage.input_tokens`
+//! - Google Gemini: `usageMetadata.promptTokenCount`
+//! - AWS Bedrock: `x-amzn-bedrock-*-token-count` headers and Converse `usage.inputTokens`
+//! - Cohere: `meta.billed_units` (v1) and `usage.billed_units` (v2)
+
+use crate::config::{FilterConfig, ModelPricing};
 use serde::Deserialize;
 use std::collections::HashMap;
 
@@ -15,6 +21,8 @@ pub struct TokenUsage {
     pub completion_tokens: u32,
     /// Total tokens used
     pub total_tokens: u32,
+    /// Prompt tokens served from the provider's prompt cache (subset of `prompt_tokens`)
+    pub cached_prompt_tokens: u32,
     /// Estimated cost in USD (if known)
     pub estimated_cost_usd: Option<f64>,
     /// Model used (if extracted)
@@ -36,13 +44,16 @@ impl TokenUsage {
 }
 
 /// Token counter for extracting usage from responses
+#[derive(Debug)]
 pub struct TokenCounter {
     /// Model pricing (tokens per dollar)
     pricing: HashMap<String, TokenPricing>,
+    /// Configured pricing rules (take precedence over the built-in table)
+    overrides: Vec<ModelPricing>,
 }
 
 /// Pricing for a specific model
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 struct TokenPricing {
     input_per_1k: f64,
     output_per_1k: f64,
@@ -92,7 +103,72 @@ impl TokenCounter {
             },
         );
 
-        Self { pricing }
+        // Google Gemini pricing (approximate)
+        pricing.insert(
+            "gemini-1.5-pro".to_string(),
+            TokenPricing {
+                input_per_1k: 0.00125,
+                output_per_1k: 0.005,
+            },
+        );
+        pricing.insert(
+            "gemini-1.5-flash".to_string(),
+            TokenPricing {
+                input_per_1k: 0.000075,
+                output_per_1k: 0.0003,
+            },
+        );
+
+        // Mistral pricing (approximate)
+        pricing.insert(
+            "mistral-large".to_string(),
+            TokenPricing {
+                input_per_1k: 0.002,
+                output_per_1k: 0.006,
+            },
+        );
+        pricing.insert(
+            "mistral-small".to_string(),
+            TokenPricing {
+                input_per_1k: 0.0002,
+                output_per_1k: 0.0006,
+            },
+        );
+
+        // Cohere pricing (approximate)
+        pricing.insert(
+            "command-r-plus".to_string(),
+            TokenPricing {
+                input_per_1k: 0.0025,
+                output_per_1k: 0.01,
+            },
+        );
+        pricing.insert(
+            "command-r".to_string(),
+            TokenPricing {
+                input_per_1k: 0.00015,
+                output_per_1k: 0.0006,
+            },
+        );
+
+        Self {
+            pricing,
+            overrides: Vec::new(),
+        }
+    }
+
+    /// Create a token counter using the pricing rules from the filter configuration
+    pub fn from_config(config: &FilterConfig) -> Self {
+        let mut counter = if config.replace_default_pricing {
+            Self {
+                pricing: HashMap::new(),
+                overrides: Vec::new(),
+            }
+        } else {
+            Self::new()
+        };
+        counter.overrides = config.model_pricing.clone();
+        counter
     }
 
     /// Extract token usage from response headers
@@ -127,6 +203,20 @@ impl TokenCounter {
                     found = true;
                 }
             }
+
+            // AWS Bedrock InvokeModel headers
+            if name_lower == "x-amzn-bedrock-input-token-count" {
+                if let Ok(v) = value.parse() {
+                    usage.prompt_tokens = v;
+                    found = true;
+                }
+            }
+            if name_lower == "x-amzn-bedrock-output-token-count" {
+                if let Ok(v) = value.parse() {
+                    usage.completion_tokens = v;
+                    found = true;
+                }
+            }
         }
 
         if found {
@@ -152,10 +242,27 @@ impl TokenCounter {
             return Some(usage);
         }
 
+        // Try Gemini format
+        if let Some(usage) = self.extract_gemini_format(text) {
+            return Some(usage);
+        }
+
+        // Try Bedrock Converse format
+        if let Some(usage) = self.extract_bedrock_format(text) {
+            return Some(usage);
+        }
+
+        // Try Cohere format
+        if let Some(usage) = self.extract_cohere_format(text) {
+            return Some(usage);
+        }
+
         None
     }
 
     /// Extract from OpenAI format: {"usage": {"prompt_tokens": N, ...}}
+    ///
+    /// Mistral's chat API uses the same shape, so it is handled here too.
     fn extract_openai_format(&self, text: &str) -> Option<TokenUsage> {
         #[derive(Deserialize)]
         struct OpenAIResponse {
@@ -165,18 +272,28 @@ impl TokenCounter {
 
         #[derive(Deserialize)]
         struct OpenAIUsage {
-            prompt_tokens: Option<u32>,
+            prompt_tokens: u32,
             completion_tokens: Option<u32>,
             total_tokens: Option<u32>,
+            prompt_tokens_details: Option<OpenAIPromptDetails>,
+        }
+
+        #[derive(Deserialize)]
+        struct OpenAIPromptDetails {
+            cached_tokens: Option<u32>,
         }
 
         let response: OpenAIResponse = serde_json::from_str(text).ok()?;
         let api_usage = response.usage?;
 
         let mut usage = TokenUsage {
-            prompt_tokens: api_usage.prompt_tokens.unwrap_or(0),
+            prompt_tokens: api_usage.prompt_tokens,
             completion_tokens: api_usage.completion_tokens.unwrap_or(0),
             total_tokens: api_usage.total_tokens.unwrap_or(0),
+            cached_prompt_tokens: api_usage
+                .prompt_tokens_details
+                .and_then(|d| d.cached_tokens)
+                .unwrap_or(0),
             model: response.model.clone(),
             estimated_cost_usd: None,
         };
@@ -201,17 +318,22 @@ impl TokenCounter {
 
         #[derive(Deserialize)]
         struct AnthropicUsage {
-            input_tokens: Option<u32>,
+            input_tokens: u32,
             output_tokens: Option<u32>,
+            cache_read_input_tokens: Option<u32>,
         }
 
         let response: AnthropicResponse = serde_json::from_str(text).ok()?;
         let api_usage = response.usage?;
 
+        // Anthropic reports cache reads separately from input_tokens
+        let cached = api_usage.cache_read_input_tokens.unwrap_or(0);
+
         let mut usage = TokenUsage {
-            prompt_tokens: api_usage.input_tokens.unwrap_or(0),
+            prompt_tokens: api_usage.input_tokens + cached,
             completion_tokens: api_usage.output_tokens.unwrap_or(0),
             total_tokens: 0,
+            cached_prompt_tokens: cached,
             model: response.model.clone(),
             estimated_cost_usd: None,
         };
@@ -226,19 +348,172 @@ impl TokenCounter {
         Some(usage)
     }
 
-    /// Calculate cost for a given model and usage
-    pub fn calculate_cost(&self, model: &str, usage: &TokenUsage) -> Option<f64> {
-        // Find pricing for model (partial match)
-        let pricing = self.pricing.iter().find(|(k, _)| model.contains(k.as_str()));
+    /// Extract from Gemini format: {"usageMetadata": {"promptTokenCount": N, ...}}
+    fn extract_gemini_format(&self, text: &str) -> Option<TokenUsage> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct GeminiResponse {
+            usage_metadata: Option<GeminiUsage>,
+            model_version: Option<String>,
+        }
 
-        if let Some((_, pricing)) = pricing {
-            let input_cost = (usage.prompt_tokens as f64 / 1000.0) * pricing.input_per_1k;
-            let output_cost = (usage.completion_tokens as f64 / 1000.0) * pricing.output_per_1k;
-            Some(input_cost + output_cost)
-        } else {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct GeminiUsage {
+            prompt_token_count: u32,
+            candidates_token_count: Option<u32>,
+            total_token_count: Option<u32>,
+            cached_content_token_count: Option<u32>,
+        }
+
+        let response: GeminiResponse = serde_json::from_str(text).ok()?;
+        let api_usage = response.usage_metadata?;
+
+        let mut usage = TokenUsage {
+            prompt_tokens: api_usage.prompt_token_count,
+            completion_tokens: api_usage.candidates_token_count.unwrap_or(0),
+            total_tokens: api_usage.total_token_count.unwrap_or(0),
+            cached_prompt_tokens: api_usage.cached_content_token_count.unwrap_or(0),
+            model: response.model_version.clone(),
+            estimated_cost_usd: None,
+        };
+
+        usage.calculate_total();
+
+        if let Some(model) = &response.model_version {
+            usage.estimated_cost_usd = self.calculate_cost(model, &usage);
+        }
+
+        Some(usage)
+    }
+
+    /// Extract from Bedrock Converse format: {"usage": {"inputTokens": N, ...}}
+    ///
+    /// Converse responses do not echo the model ID; use
+    /// [`TokenCounter::bedrock_model_from_path`] to attribute cost.
+    fn extract_bedrock_format(&self, text: &str) -> Option<TokenUsage> {
+        #[derive(Deserialize)]
+        struct BedrockResponse {
+            usage: Option<BedrockUsage>,
+        }
+
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct BedrockUsage {
+            input_tokens: u32,
+            output_tokens: Option<u32>,
+            total_tokens: Option<u32>,
+        }
+
+        let response: BedrockResponse = serde_json::from_str(text).ok()?;
+        let api_usage = response.usage?;
+
+        let mut usage = TokenUsage {
+            prompt_tokens: api_usage.input_tokens,
+            completion_tokens: api_usage.output_tokens.unwrap_or(0),
+            total_tokens: api_usage.total_tokens.unwrap_or(0),
+            cached_prompt_tokens: 0,
+            model: None,
+            estimated_cost_usd: None,
+        };
+
+        usage.calculate_total();
+
+        Some(usage)
+    }
+
+    /// Extract from Cohere format.
+    ///
+    /// v1: {"meta": {"billed_units": {"input_tokens": N, "output_tokens": M}}}
+    /// v2: {"usage": {"billed_units": {"input_tokens": N, "output_tokens": M}}}
+    fn extract_cohere_format(&self, text: &str) -> Option<TokenUsage> {
+        #[derive(Deserialize)]
+        struct CohereResponse {
+            meta: Option<CohereMeta>,
+            usage: Option<CohereMeta>,
+            model: Option<String>,
+        }
+
+        #[derive(Deserialize)]
+        struct CohereMeta {
+            billed_units: Option<CohereBilledUnits>,
+        }
+
+        #[derive(Deserialize)]
+        struct CohereBilledUnits {
+            input_tokens: Option<u32>,
+            output_tokens: Option<u32>,
+        }
+
+        let response: CohereResponse = serde_json::from_str(text).ok()?;
+        let billed = response
+            .usage
+            .and_then(|u| u.billed_units)
+            .or_else(|| response.meta.and_then(|m| m.billed_units))?;
+
+        let mut usage = TokenUsage {
+            prompt_tokens: billed.input_tokens.unwrap_or(0),
+            completion_tokens: billed.output_tokens.unwrap_or(0),
+            total_tokens: 0,
+            cached_prompt_tokens: 0,
+            model: response.model.clone(),
+            estimated_cost_usd: None,
+        };
+
+        usage.calculate_total();
+
+        if let Some(model) = &response.model {
+            usage.estimated_cost_usd = self.calculate_cost(model, &usage);
+        }
+
+        Some(usage)
+    }
+
+    /// Extract the model ID from a Bedrock runtime path
+    ///
+    /// e.g. `/model/anthropic.claude-3-sonnet-20240229-v1:0/converse`
+    pub fn bedrock_model_from_path(path: &str) -> Option<String> {
+        let rest = path.split('?').next()?.strip_prefix("/model/")?;
+        let model = rest.split('/').next()?;
+        if model.is_empty() {
             None
+        } else {
+            Some(model.replace("%3A", ":").replace("%3a", ":"))
         }
     }
+
+    /// Calculate cost for a given model and usage
+    pub fn calculate_cost(&self, model: &str, usage: &TokenUsage) -> Option<f64> {
+        // Configured rules first (exact or prefix, longest rule wins)
+        let configured = self
+            .overrides
+            .iter()
+            .filter_map(|rule| rule.matches(model).map(|len| (len, rule)))
+            .max_by_key(|(len, _)| *len)
+            .map(|(_, rule)| {
+                (
+                    rule.input_per_1k,
+                    rule.output_per_1k,
+                    rule.cached_input_per_1k.unwrap_or(rule.input_per_1k),
+                )
+            });
+
+        // Then the built-in table (partial match, most specific key wins)
+        let (input_per_1k, output_per_1k, cached_per_1k) = configured.or_else(|| {
+            self.pricing
+                .iter()
+                .filter(|(k, _)| model.contains(k.as_str()))
+                .max_by_key(|(k, _)| k.len())
+                .map(|(_, p)| (p.input_per_1k, p.output_per_1k, p.input_per_1k))
+        })?;
+
+        let cached = usage.cached_prompt_tokens.min(usage.prompt_tokens);
+        let uncached = usage.prompt_tokens - cached;
+        let input_cost = (uncached as f64 / 1000.0) * input_per_1k
+            + (cached as f64 / 1000.0) * cached_per_1k;
+        let output_cost = (usage.completion_tokens as f64 / 1000.0) * output_per_1k;
+        Some(input_cost + output_cost)
+    }
 }
 
 impl Default for TokenCounter {
@@ -283,6 +558,7 @@ mod tests {
             prompt_tokens: 1000,
             completion_tokens: 1000,
             total_tokens: 2000,
+            cached_prompt_tokens: 0,
             model: Some("gpt-4".to_string()),
             estimated_cost_usd: None,
         };
@@ -293,6 +569,176 @@ mod tests {
         assert!((cost.unwrap() - 0.09).abs() < 0.001);
     }
 
+    #[test]
+    fn test_extract_gemini_format() {
+        let counter = TokenCounter::new();
+        let body = r#"{"candidates":[],"usageMetadata":{"promptTokenCount":12,"candidatesTokenCount":8,"totalTokenCount":20},"modelVersion":"gemini-1.5-pro-002"}"#;
+
+        let usage = counter.extract_from_body(body.as_bytes()).unwrap();
+
+        assert_eq!(usage.prompt_tokens, 12);
+        assert_eq!(usage.completion_tokens, 8);
+        assert_eq!(usage.total_tokens, 20);
+        assert!(usage.estimated_cost_usd.is_some());
+    }
+
+    #[test]
+    fn test_extract_bedrock_converse_format() {
+        let counter = TokenCounter::new();
+        let body = r#"{"output":{},"stopReason":"end_turn","usage":{"inputTokens":30,"outputTokens":5,"totalTokens":35}}"#;
+
+        let usage = counter.extract_from_body(body.as_bytes()).unwrap();
+
+        assert_eq!(usage.prompt_tokens, 30);
+        assert_eq!(usage.completion_tokens, 5);
+        assert_eq!(usage.total_tokens, 35);
+    }
+
+    #[test]
+    fn test_extract_bedrock_headers() {
+        let counter = TokenCounter::new();
+        let headers = vec![
+            ("x-amzn-bedrock-input-token-count".to_string(), "100".to_string()),
+            ("x-amzn-bedrock-output-token-count".to_string(), "50".to_string()),
+        ];
+
+        let usage = counter.extract_from_headers(&headers).unwrap();
+
+        assert_eq!(usage.prompt_tokens, 100);
+        assert_eq!(usage.completion_tokens, 50);
+        assert_eq!(usage.total_tokens, 150);
+    }
+
+    #[test]
+    fn test_bedrock_model_from_path() {
+        assert_eq!(
+            TokenCounter::bedrock_model_from_path("/model/anthropic.claude-3-sonnet-20240229-v1%3A0/converse"),
+            Some("anthropic.claude-3-sonnet-20240229-v1:0".to_string())
+        );
+        assert_eq!(TokenCounter::bedrock_model_from_path("/v1/chat/completions"), None);
+    }
+
+    #[test]
+    fn test_extract_mistral_format() {
+        let counter = TokenCounter::new();
+        let body = r#"{"id":"cmpl-1","model":"mistral-large-latest","usage":{"prompt_tokens":7,"completion_tokens":3,"total_tokens":10}}"#;
+
+        let usage = counter.extract_from_body(body.as_bytes()).unwrap();
+
+        assert_eq!(usage.total_tokens, 10);
+        assert!(usage.estimated_cost_usd.is_some());
+    }
+
+    #[test]
+    fn test_extract_cohere_formats() {
+        let counter = TokenCounter::new();
+
+        let v1 = r#"{"text":"hi","meta":{"billed_units":{"input_tokens":9,"output_tokens":4}}}"#;
+        let usage = counter.extract_from_body(v1.as_bytes()).unwrap();
+        assert_eq!(usage.prompt_tokens, 9);
+        assert_eq!(usage.completion_tokens, 4);
+
+        let v2 = r#"{"id":"x","usage":{"billed_units":{"input_tokens":6,"output_tokens":2},"tokens":{"input_tokens":70,"output_tokens":2}}}"#;
+        let usage = counter.extract_from_body(v2.as_bytes()).unwrap();
+        assert_eq!(usage.prompt_tokens, 6);
+        assert_eq!(usage.total_tokens, 8);
+    }
+
+    #[test]
+    fn test_most_specific_pricing_wins() {
+        let counter = TokenCounter::new();
+        let usage = TokenUsage {
+            prompt_tokens: 1000,
+            completion_tokens: 0,
+            total_tokens: 1000,
+            cached_prompt_tokens: 0,
+            model: None,
+            estimated_cost_usd: None,
+        };
+
+        let cost = counter.calculate_cost("command-r-plus-08-2024", &usage).unwrap();
+        assert!((cost - 0.0025).abs() < 0.000001);
+    }
+
+    fn pricing_config(replace_default_pricing: bool) -> FilterConfig {
+        FilterConfig {
+            model_pricing: vec![
+                ModelPricing {
+                    model: "gpt-4*".to_string(),
+                    input_per_1k: 0.001,
+                    output_per_1k: 0.002,
+                    cached_input_per_1k: None,
+                },
+                ModelPricing {
+                    model: "gpt-4o*".to_string(),
+                    input_per_1k: 0.0025,
+                    output_per_1k: 0.01,
+                    cached_input_per_1k: Some(0.00125),
+                },
+            ],
+            replace_default_pricing,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_configured_pricing_overrides_builtin() {
+        let counter = TokenCounter::from_config(&pricing_config(false));
+        let usage = TokenUsage {
+            prompt_tokens: 1000,
+            completion_tokens: 1000,
+            ..Default::default()
+        };
+
+        // gpt-4 built-in is $0.09; the configured prefix rule wins
+        let cost = counter.calculate_cost("gpt-4", &usage).unwrap();
+        assert!((cost - 0.003).abs() < 0.000001);
+
+        // Longest prefix rule wins
+        let cost = counter.calculate_cost("gpt-4o-mini", &usage).unwrap();
+        assert!((cost - 0.0125).abs() < 0.000001);
+
+        // Built-ins still apply to models without a rule
+        assert!(counter.calculate_cost("claude-3-opus-20240229", &usage).is_some());
+    }
+
+    #[test]
+    fn test_replace_default_pricing() {
+        let counter = TokenCounter::from_config(&pricing_config(true));
+        let usage = TokenUsage {
+            prompt_tokens: 1000,
+            ..Default::default()
+        };
+
+        assert!(counter.calculate_cost("claude-3-opus", &usage).is_none());
+        assert!(counter.calculate_cost("gpt-4o", &usage).is_some());
+    }
+
+    #[test]
+    fn test_cached_input_pricing() {
+        let counter = TokenCounter::from_config(&pricing_config(false));
+        let body = r#"{"model":"gpt-4o","usage":{"prompt_tokens":2000,"completion_tokens":0,"total_tokens":2000,"prompt_tokens_details":{"cached_tokens":1000}}}"#;
+
+        let usage = counter.extract_from_body(body.as_bytes()).unwrap();
+        assert_eq!(usage.cached_prompt_tokens, 1000);
+
+        // 1K uncached at $0.0025 + 1K cached at $0.00125
+        let cost = usage.estimated_cost_usd.unwrap();
+        assert!((cost - 0.00375).abs() < 0.000001);
+    }
+
+    #[test]
+    fn test_anthropic_cache_reads() {
+        let counter = TokenCounter::new();
+        let body = r#"{"model":"claude-3-sonnet","usage":{"input_tokens":10,"output_tokens":5,"cache_read_input_tokens":90}}"#;
+
+        let usage = counter.extract_from_body(body.as_bytes()).unwrap();
+
+        assert_eq!(usage.prompt_tokens, 100);
+        assert_eq!(usage.cached_prompt_tokens, 90);
+        assert_eq!(usage.total_tokens, 105);
+    }
+
     #[test]
     fn test_no_usage() {
         let counter = TokenCounter::new();
@@ -0,0 +1,222 @@
+//! Token Estimator Module
+//!
+//! Estimates prompt tokens on the request path, before the provider has
+//! reported real usage. Uses the common "4 characters per token" heuristic
+//! with per-model-family correction factors. In a JSON body only string
+//! values are counted: keys, punctuation and numbers are not prompt text.
+//!
+//! Streams over body chunks and only keeps counters - O(1) memory.
+
+use crate::streaming::Utf8Buffer;
+
+/// Average characters per token for English text on BPE tokenizers
+const CHARS_PER_TOKEN: f64 = 4.0;
+
+/// Model-family correction factors, relative to OpenAI tokenizers
+///
+/// Matched as substrings of the model name; first match wins.
+const MODEL_FACTORS: &[(&str, f64)] = &[
+    ("claude", 1.15),
+    ("mistral", 1.10),
+    ("command", 1.05),
+    ("gemini", 0.95),
+    ("gpt", 1.0),
+];
+
+/// Position of a JSON-mode estimator in the body
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum JsonState {
+    /// Between strings
+    #[default]
+    Structure,
+    /// Inside a string
+    String,
+    /// After a backslash in a string
+    Escape,
+    /// In the hex digits of a `\uXXXX` escape (digits left)
+    Unicode(u8),
+}
+
+/// String-value counting for JSON bodies
+#[derive(Debug, Default)]
+struct JsonText {
+    state: JsonState,
+    /// Characters of the string being read
+    current: usize,
+    /// Characters of the last closed string, counted unless a `:` makes it a key
+    pending: usize,
+}
+
+/// Streaming prompt token estimator
+#[derive(Debug, Default)]
+pub struct TokenEstimator {
+    /// Unicode scalar values seen so far
+    chars: usize,
+    /// Only string values are counted (JSON bodies)
+    json: Option<JsonText>,
+}
+
+impl TokenEstimator {
+    /// Create a new estimator
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an estimator counting only the string values of a JSON body
+    pub fn json() -> Self {
+        Self { chars: 0, json: Some(JsonText::default()) }
+    }
+
+    /// Observe a body chunk
+    ///
+    /// Counts characters by UTF-8 lead bytes, so multi-byte characters
+    /// split across chunks are counted exactly once.
+    pub fn observe(&mut self, chunk: &[u8]) {
+        let json = match self.json.as_mut() {
+            Some(json) => json,
+            None => {
+                self.chars += chunk.iter().filter(|&&b| !Utf8Buffer::is_continuation(b)).count();
+                return;
+            }
+        };
+        for &byte in chunk {
+            json.state = match (json.state, byte) {
+                (JsonState::Structure, b' ' | b'\t' | b'\r' | b'\n') => JsonState::Structure,
+                (JsonState::Structure, _) => {
+                    if byte != b':' {
+                        self.chars += json.pending;
+                    }
+                    json.pending = 0;
+                    if byte == b'"' {
+                        JsonState::String
+                    } else {
+                        JsonState::Structure
+                    }
+                }
+                (JsonState::String, b'"') => {
+                    json.pending = std::mem::take(&mut json.current);
+                    JsonState::Structure
+                }
+                (JsonState::String, b'\\') => JsonState::Escape,
+                (JsonState::String, _) => {
+                    if !Utf8Buffer::is_continuation(byte) {
+                        json.current += 1;
+                    }
+                    JsonState::String
+                }
+                (JsonState::Escape, _) => {
+                    json.current += 1;
+                    if byte == b'u' {
+                        JsonState::Unicode(4)
+                    } else {
+                        JsonState::String
+                    }
+                }
+                (JsonState::Unicode(1), _) => JsonState::String,
+                (JsonState::Unicode(left), _) => JsonState::Unicode(left - 1),
+            };
+        }
+    }
+
+    /// Number of characters observed
+    pub fn chars(&self) -> usize {
+        self.chars + self.json.as_ref().map_or(0, |json| json.pending)
+    }
+
+    /// Estimate tokens for the observed text, corrected for the model family
+    pub fn estimate(&self, model: Option<&str>) -> u32 {
+        Self::estimate_chars(self.chars(), model)
+    }
+
+    /// Estimate tokens for a complete string
+    pub fn estimate_str(text: &str, model: Option<&str>) -> u32 {
+        Self::estimate_chars(text.chars().count(), model)
+    }
+
+    /// Correction factor for a model name (1.0 if unknown)
+    pub fn model_factor(model: &str) -> f64 {
+        let model_lower = model.to_lowercase();
+        MODEL_FACTORS
+            .iter()
+            .find(|(family, _)| model_lower.contains(family))
+            .map(|(_, factor)| *factor)
+            .unwrap_or(1.0)
+    }
+
+    /// Reset the estimator for reuse
+    pub fn reset(&mut self) {
+        self.chars = 0;
+        if let Some(json) = self.json.as_mut() {
+            *json = JsonText::default();
+        }
+    }
+
+    fn estimate_chars(chars: usize, model: Option<&str>) -> u32 {
+        if chars == 0 {
+            return 0;
+        }
+        let factor = model.map(Self::model_factor).unwrap_or(1.0);
+        let tokens = (chars as f64 / CHARS_PER_TOKEN * factor).ceil();
+        tokens.min(u32::MAX as f64) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chars_per_token() {
+        let mut estimator = TokenEstimator::new();
+        estimator.observe(b"abcdefghijklmnop"); // 16 chars
+
+        assert_eq!(estimator.estimate(None), 4);
+        assert_eq!(estimator.estimate(Some("gpt-4o")), 4);
+    }
+
+    #[test]
+    fn test_model_correction() {
+        let mut estimator = TokenEstimator::new();
+        estimator.observe(&[b'a'; 400]);
+
+        assert_eq!(estimator.estimate(Some("gpt-4")), 100);
+        assert_eq!(estimator.estimate(Some("claude-3-5-sonnet")), 115);
+        assert_eq!(estimator.estimate(Some("unknown-model")), 100);
+    }
+
+    #[test]
+    fn test_multibyte_split_across_chunks() {
+        let mut estimator = TokenEstimator::new();
+
+        // 🦀 = F0 9F A6 80, split across chunks
+        estimator.observe(&[b'h', b'i', 0xF0, 0x9F]);
+        estimator.observe(&[0xA6, 0x80]);
+
+        assert_eq!(estimator.chars(), 3);
+    }
+
+    #[test]
+    fn test_json_counts_string_values() {
+        let mut estimator = TokenEstimator::json();
+        estimator.observe(br#"{"messages": [{"role": "user", "content": "say \"hi\" \u00e9"#);
+        estimator.observe(br#"!"}], "max_tokens": 50, "model": "x"}"#);
+
+        // "user" + `say "hi" é!` + "x"
+        assert_eq!(estimator.chars(), 4 + 11 + 1);
+
+        let mut estimator = TokenEstimator::json();
+        estimator.observe(br#""top-level string""#);
+        assert_eq!(estimator.chars(), 16);
+    }
+
+    #[test]
+    fn test_empty() {
+        let estimator = TokenEstimator::new();
+        assert_eq!(estimator.estimate(Some("claude-3-opus")), 0);
+    }
+
+    #[test]
+    fn test_estimate_str() {
+        assert_eq!(TokenEstimator::estimate_str("hello world!", None), 3);
+    }
+}
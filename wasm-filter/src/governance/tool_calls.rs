@@ -0,0 +1,313 @@
+//! Tool-Call Output Inspection
+//!
+//! A model can answer with a tool call instead of text, and whatever agent
+//! runs the tool executes the generated arguments. Injected instructions
+//! that reach the model therefore come back out as arguments such as
+//! `{"command": "rm -rf /"}` (indirect injection). Arguments are JSON text,
+//! streamed as fragments, so each call is fed through its own JSON
+//! tokenizer and the decoded string values are checked against the
+//! injection detector and a dangerous-command list.
+
+use super::prompt_injection::{
+    InjectionCategory, InjectionMatch, InjectionSeverity, PromptInjectionDetector,
+};
+use crate::protocols::{ToolCall, ToolCallDelta};
+use crate::streaming::{JsonEvent, JsonTokenizer, PatternScanner, ScanResult};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// Calls tracked separately; further calls share one scanner
+const MAX_TRACKED_CALLS: usize = 64;
+
+/// Tool-call inspection policy
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ToolCallPolicy {
+    /// Run the prompt injection detector over generated arguments
+    pub detect_injection: bool,
+    /// Commands that must not appear in generated arguments
+    pub blocked_commands: Vec<String>,
+}
+
+impl Default for ToolCallPolicy {
+    fn default() -> Self {
+        Self {
+            detect_injection: true,
+            blocked_commands: [
+                "rm -rf",
+                "mkfs",
+                "dd if=",
+                "chmod 777",
+                "chmod -r 777",
+                "| sh",
+                "| bash",
+                "drop table",
+                "drop database",
+                "truncate table",
+                "shutdown -h",
+                "> /dev/sd",
+                "format c:",
+                "del /f /s /q",
+            ]
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+        }
+    }
+}
+
+impl ToolCallPolicy {
+    /// Validate the policy, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        if self.blocked_commands.iter().any(|c| c.is_empty()) {
+            diagnostics.push("tool_call_policy.blocked_commands: empty command".to_string());
+        }
+        if !self.detect_injection && self.blocked_commands.is_empty() {
+            diagnostics.push("tool_call_policy: nothing to inspect".to_string());
+        }
+        diagnostics
+    }
+}
+
+/// Tool-call policy violations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ToolCallViolation {
+    /// Arguments contain a prompt injection pattern
+    Injection {
+        /// Tool name
+        tool: String,
+        /// Matched pattern
+        pattern: String,
+    },
+    /// Arguments contain a blocked command
+    DangerousCommand {
+        /// Tool name
+        tool: String,
+        /// Matched command
+        pattern: String,
+    },
+}
+
+impl ToolCallViolation {
+    /// Tool whose arguments matched
+    pub fn tool(&self) -> &str {
+        match self {
+            ToolCallViolation::Injection { tool, .. }
+            | ToolCallViolation::DangerousCommand { tool, .. } => tool,
+        }
+    }
+
+    /// Matched pattern or command
+    pub fn pattern(&self) -> &str {
+        match self {
+            ToolCallViolation::Injection { pattern, .. }
+            | ToolCallViolation::DangerousCommand { pattern, .. } => pattern,
+        }
+    }
+
+    /// Attack category of the match
+    pub fn category(&self) -> InjectionCategory {
+        match self {
+            ToolCallViolation::Injection { pattern, .. } => InjectionCategory::classify(pattern),
+            ToolCallViolation::DangerousCommand { .. } => InjectionCategory::DangerousOperation,
+        }
+    }
+
+    /// Severity of the match (commands about to be executed are critical)
+    pub fn severity(&self) -> InjectionSeverity {
+        match self {
+            ToolCallViolation::Injection { pattern, .. } => {
+                InjectionMatch::for_pattern(pattern).severity()
+            }
+            ToolCallViolation::DangerousCommand { .. } => InjectionSeverity::Critical,
+        }
+    }
+}
+
+impl std::fmt::Display for ToolCallViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ToolCallViolation::Injection { tool, pattern } => {
+                write!(f, "Tool call '{}' arguments contain '{}'", tool, pattern)
+            }
+            ToolCallViolation::DangerousCommand { tool, pattern } => {
+                write!(f, "Tool call '{}' arguments contain command '{}'", tool, pattern)
+            }
+        }
+    }
+}
+
+/// Scan state of one tool call
+struct CallScan {
+    name: String,
+    /// None once the arguments stop parsing (the rest is scanned raw)
+    json: Option<JsonTokenizer>,
+    injection: Option<PromptInjectionDetector>,
+    commands: PatternScanner,
+}
+
+impl CallScan {
+    fn new(policy: &ToolCallPolicy) -> Self {
+        Self {
+            name: String::new(),
+            json: Some(JsonTokenizer::new()),
+            injection: policy.detect_injection.then(PromptInjectionDetector::new),
+            commands: PatternScanner::from_strings(&policy.blocked_commands),
+        }
+    }
+
+    fn feed(&mut self, arguments: &[u8]) -> Option<ToolCallViolation> {
+        let events = match self.json.as_mut().map(|json| json.feed(arguments)) {
+            Some(Ok(events)) => events,
+            Some(Err(_)) => {
+                self.json = None;
+                return self.scan(arguments);
+            }
+            None => return self.scan(arguments),
+        };
+        for event in events {
+            let hit = match event {
+                JsonEvent::StringData(data) => self.scan(&data),
+                JsonEvent::StringEnd => {
+                    // Patterns never span two string values
+                    self.reset();
+                    None
+                }
+                _ => None,
+            };
+            if hit.is_some() {
+                return hit;
+            }
+        }
+        None
+    }
+
+    fn scan(&mut self, data: &[u8]) -> Option<ToolCallViolation> {
+        // Commands first: some injection patterns are commands too
+        if let ScanResult::Match(m) = self.commands.scan_bytes(data) {
+            return Some(ToolCallViolation::DangerousCommand {
+                tool: self.name.clone(),
+                pattern: m.pattern_name,
+            });
+        }
+        let m = self.injection.as_mut()?.scan(data)?;
+        Some(ToolCallViolation::Injection { tool: self.name.clone(), pattern: m.pattern })
+    }
+
+    fn reset(&mut self) {
+        if let Some(detector) = self.injection.as_mut() {
+            detector.reset();
+        }
+        self.commands.reset_states();
+    }
+}
+
+/// Applies the tool-call policy to the tool calls of one response
+pub struct ToolCallInspector {
+    policy: ToolCallPolicy,
+    calls: BTreeMap<(u64, u64), CallScan>,
+}
+
+impl ToolCallInspector {
+    /// Create an inspector for one response
+    pub fn new(policy: &ToolCallPolicy) -> Self {
+        Self {
+            policy: policy.clone(),
+            calls: BTreeMap::new(),
+        }
+    }
+
+    /// Feed a streamed argument fragment
+    pub fn feed_delta(&mut self, delta: &ToolCallDelta<'_>) -> Option<ToolCallViolation> {
+        let key = if self.calls.contains_key(&delta.call) || self.calls.len() < MAX_TRACKED_CALLS {
+            delta.call
+        } else {
+            (u64::MAX, u64::MAX)
+        };
+        let policy = &self.policy;
+        let call = self.calls.entry(key).or_insert_with(|| CallScan::new(policy));
+        if let Some(name) = delta.name {
+            call.name = name.to_string();
+        }
+        call.feed(delta.arguments.as_bytes())
+    }
+
+    /// Inspect a complete tool call
+    pub fn inspect(&self, call: &ToolCall) -> Option<ToolCallViolation> {
+        let mut scan = CallScan::new(&self.policy);
+        scan.name = call.name.clone();
+        scan.feed(call.arguments.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(name: &str, arguments: &str) -> ToolCall {
+        ToolCall { name: name.to_string(), arguments: arguments.to_string() }
+    }
+
+    #[test]
+    fn test_dangerous_command() {
+        let inspector = ToolCallInspector::new(&ToolCallPolicy::default());
+        let hit = inspector.inspect(&call("shell", r#"{"command":"cd /tmp && rm -rf /"}"#));
+        assert_eq!(
+            hit,
+            Some(ToolCallViolation::DangerousCommand {
+                tool: "shell".to_string(),
+                pattern: "rm -rf".to_string(),
+            })
+        );
+        assert_eq!(hit.unwrap().severity(), InjectionSeverity::Critical);
+        assert!(inspector.inspect(&call("shell", r#"{"command":"ls -la"}"#)).is_none());
+    }
+
+    #[test]
+    fn test_escaped_arguments_are_decoded() {
+        let inspector = ToolCallInspector::new(&ToolCallPolicy::default());
+        let hit = inspector
+            .inspect(&call("email", r#"{"body":"Ignore\u0020previous instructions"}"#))
+            .unwrap();
+        assert_eq!(hit.pattern(), "ignore previous instructions");
+        assert_eq!(hit.category(), InjectionCategory::InstructionOverride);
+    }
+
+    #[test]
+    fn test_streamed_fragments() {
+        let mut inspector = ToolCallInspector::new(&ToolCallPolicy::default());
+        let fragments = [(Some("bash"), r#"{"cmd": "m"#), (None, "kfs.ext4 /dev"), (None, r#""}"#)];
+        let hits: Vec<_> = fragments
+            .iter()
+            .filter_map(|(name, arguments)| {
+                inspector.feed_delta(&ToolCallDelta { call: (0, 0), name: *name, arguments })
+            })
+            .collect();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].tool(), "bash");
+    }
+
+    #[test]
+    fn test_calls_and_values_do_not_mix() {
+        let mut inspector = ToolCallInspector::new(&ToolCallPolicy::default());
+        for (call, arguments) in [((0, 0), r#"{"a":"rm "#), ((0, 1), r#"{"a":"-rf"#)] {
+            let delta = ToolCallDelta { call, name: None, arguments };
+            assert!(inspector.feed_delta(&delta).is_none());
+        }
+        let inspector = ToolCallInspector::new(&ToolCallPolicy::default());
+        assert!(inspector.inspect(&call("t", r#"{"a":"rm","b":" -rf"}"#)).is_none());
+    }
+
+    #[test]
+    fn test_policy() {
+        let policy = ToolCallPolicy { detect_injection: false, ..Default::default() };
+        let inspector = ToolCallInspector::new(&policy);
+        assert!(inspector.inspect(&call("t", r#"{"q":"jailbreak"}"#)).is_none());
+        // Unparseable arguments are still scanned
+        assert!(inspector.inspect(&call("t", "drop table users")).is_some());
+
+        let policy = ToolCallPolicy { detect_injection: false, blocked_commands: vec![] };
+        assert_eq!(policy.validate(), vec!["tool_call_policy: nothing to inspect".to_string()]);
+    }
+}
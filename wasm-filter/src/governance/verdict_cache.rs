@@ -0,0 +1,341 @@
+//! Verdict Cache
+//!
+//! Agents retry identical prompts (timeouts, client-side retries, fan-out),
+//! and every retry would otherwise pay for a full pattern scan. Finished
+//! scans of small bodies are remembered per worker, keyed on a SHA-256 of
+//! the tenant, path and body, and a retry within the TTL reuses the outcome.
+//! A fast non-cryptographic key would let a crafted body collide with a
+//! cached clean one and inherit its verdict.
+//!
+//! Only the body scan is cached: limits, model policy and the other checks
+//! still run on every request. The cache is dropped on configuration reload
+//! and can be disabled entirely where every request must be scanned.
+
+use super::body_scanner::ScanSummary;
+use crate::crypto::{Sha256, DIGEST_LEN};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// Verdict cache configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VerdictCacheConfig {
+    /// Reuse scan outcomes of identical bodies
+    pub enabled: bool,
+    /// Outcomes kept per worker (least recently used are evicted)
+    pub max_entries: usize,
+    /// Lifetime of an outcome, in seconds
+    pub ttl_secs: u64,
+    /// Largest body cached, in bytes (larger bodies are streamed and scanned)
+    pub max_body_size: usize,
+}
+
+impl Default for VerdictCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_entries: 1024,
+            ttl_secs: 300,
+            max_body_size: 64 * 1024,
+        }
+    }
+}
+
+impl VerdictCacheConfig {
+    /// Validate the configuration, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        if !self.enabled {
+            return diagnostics;
+        }
+        if self.max_entries == 0 {
+            diagnostics.push("verdict_cache.max_entries: must be greater than 0".to_string());
+        }
+        if self.ttl_secs == 0 {
+            diagnostics.push("verdict_cache.ttl_secs: must be greater than 0".to_string());
+        }
+        if self.max_body_size == 0 {
+            diagnostics.push("verdict_cache.max_body_size: must be greater than 0".to_string());
+        }
+        diagnostics
+    }
+
+    /// Whether a body of this size is looked up in the cache
+    pub fn applies_to(&self, body_size: usize) -> bool {
+        self.enabled && body_size <= self.max_body_size
+    }
+}
+
+/// Cache key: SHA-256 of a body and its scope
+pub type CacheKey = [u8; DIGEST_LEN];
+
+/// Cache key of a body
+///
+/// The scope (tenant, route, request line, content headers, experiment arm,
+/// scan mode) decides which patterns apply and how the body is read, so the
+/// same body under another scope is a different entry.
+pub fn cache_key(scope: &[&str], body: &[u8]) -> CacheKey {
+    let mut hasher = Sha256::new();
+    // Length-prefixed, so no scope value can run into the next
+    for part in scope {
+        hasher.update(&(part.len() as u64).to_be_bytes());
+        hasher.update(part.as_bytes());
+    }
+    hasher.update(body);
+    hasher.finalize()
+}
+
+struct Entry {
+    summary: ScanSummary,
+    expires_at: u64,
+    /// Position in the recency index
+    tick: u64,
+}
+
+/// Bounded LRU of scan outcomes
+pub struct VerdictCache {
+    entries: HashMap<CacheKey, Entry>,
+    /// Keys by last use, oldest first
+    recency: BTreeMap<u64, CacheKey>,
+    tick: u64,
+}
+
+impl VerdictCache {
+    /// Create an empty cache
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    /// Outcome cached for a body, if still fresh
+    pub fn get(&mut self, key: &CacheKey, now_secs: u64) -> Option<ScanSummary> {
+        let entry = self.entries.get_mut(key)?;
+        if entry.expires_at <= now_secs {
+            self.remove(key);
+            return None;
+        }
+        self.tick += 1;
+        self.recency.remove(&entry.tick);
+        self.recency.insert(self.tick, *key);
+        entry.tick = self.tick;
+        Some(entry.summary.clone())
+    }
+
+    /// Cache the outcome of a scan
+    pub fn insert(
+        &mut self,
+        config: &VerdictCacheConfig,
+        key: CacheKey,
+        summary: ScanSummary,
+        now_secs: u64,
+    ) {
+        self.remove(&key);
+        while self.entries.len() >= config.max_entries.max(1) {
+            let oldest = match self.recency.iter().next() {
+                Some((_, &oldest)) => oldest,
+                None => break,
+            };
+            self.remove(&oldest);
+        }
+        self.tick += 1;
+        self.recency.insert(self.tick, key);
+        self.entries.insert(
+            key,
+            Entry {
+                summary,
+                expires_at: now_secs.saturating_add(config.ttl_secs),
+                tick: self.tick,
+            },
+        );
+    }
+
+    /// Drop every entry
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+    }
+
+    /// Number of cached outcomes
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn remove(&mut self, key: &CacheKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.recency.remove(&entry.tick);
+        }
+    }
+}
+
+impl Default for VerdictCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+const PRIME64_1: u64 = 0x9E37_79B1_85EB_CA87;
+const PRIME64_2: u64 = 0xC2B2_AE3D_27D4_EB4F;
+const PRIME64_3: u64 = 0x1656_67B1_9E37_79F9;
+const PRIME64_4: u64 = 0x85EB_CA77_C2B2_AE63;
+const PRIME64_5: u64 = 0x27D4_EB2F_1656_67C5;
+
+/// xxHash64 of a byte string
+pub fn xxh64(data: &[u8], seed: u64) -> u64 {
+    let mut rest = data;
+    let mut hash = if data.len() >= 32 {
+        let mut acc = [
+            seed.wrapping_add(PRIME64_1).wrapping_add(PRIME64_2),
+            seed.wrapping_add(PRIME64_2),
+            seed,
+            seed.wrapping_sub(PRIME64_1),
+        ];
+        while rest.len() >= 32 {
+            for (i, lane) in acc.iter_mut().enumerate() {
+                *lane = round(*lane, read_u64(&rest[i * 8..]));
+            }
+            rest = &rest[32..];
+        }
+        let mut hash = acc[0]
+            .rotate_left(1)
+            .wrapping_add(acc[1].rotate_left(7))
+            .wrapping_add(acc[2].rotate_left(12))
+            .wrapping_add(acc[3].rotate_left(18));
+        for lane in acc {
+            hash = (hash ^ round(0, lane))
+                .wrapping_mul(PRIME64_1)
+                .wrapping_add(PRIME64_4);
+        }
+        hash
+    } else {
+        seed.wrapping_add(PRIME64_5)
+    };
+    hash = hash.wrapping_add(data.len() as u64);
+
+    while rest.len() >= 8 {
+        hash ^= round(0, read_u64(rest));
+        hash = hash.rotate_left(27).wrapping_mul(PRIME64_1).wrapping_add(PRIME64_4);
+        rest = &rest[8..];
+    }
+    if rest.len() >= 4 {
+        let word = u32::from_le_bytes([rest[0], rest[1], rest[2], rest[3]]) as u64;
+        hash ^= word.wrapping_mul(PRIME64_1);
+        hash = hash.rotate_left(23).wrapping_mul(PRIME64_2).wrapping_add(PRIME64_3);
+        rest = &rest[4..];
+    }
+    for &byte in rest {
+        hash ^= (byte as u64).wrapping_mul(PRIME64_5);
+        hash = hash.rotate_left(11).wrapping_mul(PRIME64_1);
+    }
+
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(PRIME64_2);
+    hash ^= hash >> 29;
+    hash = hash.wrapping_mul(PRIME64_3);
+    hash ^ (hash >> 32)
+}
+
+fn round(acc: u64, input: u64) -> u64 {
+    acc.wrapping_add(input.wrapping_mul(PRIME64_2))
+        .rotate_left(31)
+        .wrapping_mul(PRIME64_1)
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut word = [0u8; 8];
+    word.copy_from_slice(&bytes[..8]);
+    u64::from_le_bytes(word)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(block: bool) -> ScanSummary {
+        ScanSummary {
+            block_reason: block.then(|| "Pattern 'jailbreak' detected".to_string()),
+            matched_pattern: block.then(|| "jailbreak".to_string()),
+            matched_path: None,
+            matched_role: None,
+            model: Some("gpt-4o".to_string()),
+            message_id: None,
+            max_tokens: None,
+            message_count: 1,
+            total_bytes: 42,
+        }
+    }
+
+    fn key(n: u8) -> CacheKey {
+        [n; DIGEST_LEN]
+    }
+
+    #[test]
+    fn test_xxh64_reference_values() {
+        assert_eq!(xxh64(b"", 0), 0xEF46_DB37_51D8_E999);
+        assert_eq!(xxh64(b"a", 0), 0xD24E_C4F1_A98C_6E5B);
+        assert_eq!(xxh64(b"abc", 0), 0x44BC_2CF5_AD77_0999);
+        assert_eq!(
+            xxh64(b"Nobody inspects the spammish repetition", 0),
+            0xFBCE_A83C_8A37_8BF1
+        );
+    }
+
+    #[test]
+    fn test_key_scope() {
+        let body = br#"{"messages":[{"role":"user","content":"hi"}]}"#;
+        let key = cache_key(&["acme", "/v1/chat/completions"], body);
+        assert_eq!(key, cache_key(&["acme", "/v1/chat/completions"], body));
+        assert_ne!(key, cache_key(&["globex", "/v1/chat/completions"], body));
+        assert_ne!(key, cache_key(&["acme", "/v1/responses"], body));
+        assert_ne!(key, cache_key(&["acme", "/v1/chat/completions", "raw"], body));
+        // Scope values cannot shift into each other or into the body
+        assert_ne!(cache_key(&["ab", "c"], b""), cache_key(&["a", "bc"], b""));
+        assert_ne!(cache_key(&["a"], b"b"), cache_key(&["ab"], b""));
+    }
+
+    #[test]
+    fn test_ttl() {
+        let config = VerdictCacheConfig { ttl_secs: 60, ..Default::default() };
+        let mut cache = VerdictCache::new();
+        cache.insert(&config, key(7), summary(true), 100);
+        assert_eq!(cache.get(&key(7), 159), Some(summary(true)));
+        assert_eq!(cache.get(&key(8), 159), None);
+        cache.insert(&config, key(7), summary(false), 100);
+        assert_eq!(cache.get(&key(7), 160), None);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let config = VerdictCacheConfig { max_entries: 2, ..Default::default() };
+        let mut cache = VerdictCache::new();
+        cache.insert(&config, key(1), summary(false), 0);
+        cache.insert(&config, key(2), summary(false), 0);
+        // Touch 1 so 2 is the least recently used
+        assert!(cache.get(&key(1), 1).is_some());
+        cache.insert(&config, key(3), summary(true), 1);
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&key(2), 1).is_none());
+        assert!(cache.get(&key(1), 1).is_some());
+        assert!(cache.get(&key(3), 1).is_some());
+    }
+
+    #[test]
+    fn test_config() {
+        let config = VerdictCacheConfig::default();
+        assert!(config.applies_to(1024));
+        assert!(!config.applies_to(config.max_body_size + 1));
+        let disabled = VerdictCacheConfig { enabled: false, max_entries: 0, ..config };
+        assert!(!disabled.applies_to(1024));
+        assert!(disabled.validate().is_empty());
+        let config = VerdictCacheConfig { ttl_secs: 0, ..Default::default() };
+        assert_eq!(config.validate(), vec!["verdict_cache.ttl_secs: must be greater than 0"]);
+    }
+}
@@ -0,0 +1,771 @@
+//! Metrics Module for AI-Guard
+//!
+//! Exports guardrail outcomes as proxy-wasm metrics so they show up in
+//! Envoy's `/stats` endpoint (and Prometheus, via the admin scrape).
+//!
+//! Metrics are defined lazily by name on first use, so dimensioned
+//! metrics (e.g. per pattern category) need no up-front registration.
+//!
+//! Request outcomes can also be counted per tenant, protocol, transport
+//! and pattern category. Each dimension becomes a `.<name>.<value>` pair
+//! in the metric name (`ai_guard.requests_by.tenant.acme.action.blocked`),
+//! which Envoy's `stats_tags` can turn back into Prometheus labels. The
+//! number of distinct values per dimension is capped; later values are
+//! counted as `other`.
+
+#[cfg(feature = "proxy-wasm")]
+use proxy_wasm::hostcalls;
+#[cfg(feature = "proxy-wasm")]
+pub use proxy_wasm::types::MetricType;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+
+/// Prefix for all AI-Guard metric names
+pub const METRIC_PREFIX: &str = "ai_guard";
+
+/// Kind of metric, as proxy-wasm defines it (built without proxy-wasm)
+#[cfg(not(feature = "proxy-wasm"))]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum MetricType {
+    /// Monotonic count
+    Counter,
+    /// Value that goes up and down
+    Gauge,
+    /// Distribution of samples
+    Histogram,
+}
+
+/// Backend that actually stores metric values
+///
+/// In the filter this is Envoy (via hostcalls); tests use an in-memory sink.
+pub trait MetricSink {
+    /// Define a metric, returning its ID
+    fn define(&self, metric_type: MetricType, name: &str) -> Option<u32>;
+    /// Add `offset` to a counter or gauge
+    fn increment(&self, metric_id: u32, offset: i64);
+    /// Record a value (histogram sample or gauge value)
+    fn record(&self, metric_id: u32, value: u64);
+}
+
+/// Metric sink backed by the proxy-wasm host
+#[cfg(feature = "proxy-wasm")]
+pub struct HostMetricSink;
+
+#[cfg(feature = "proxy-wasm")]
+impl MetricSink for HostMetricSink {
+    fn define(&self, metric_type: MetricType, name: &str) -> Option<u32> {
+        hostcalls::define_metric(metric_type, name).ok()
+    }
+
+    fn increment(&self, metric_id: u32, offset: i64) {
+        let _ = hostcalls::increment_metric(metric_id, offset);
+    }
+
+    fn record(&self, metric_id: u32, value: u64) {
+        let _ = hostcalls::record_metric(metric_id, value);
+    }
+}
+
+/// Value counted for dimension values past the cardinality cap
+const OTHER_VALUE: &str = "other";
+
+/// Value counted when a request has no value for a dimension
+const NONE_VALUE: &str = "none";
+
+/// Dimensioned request metrics configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricDimensionsConfig {
+    /// Label by tenant
+    pub tenant: bool,
+    /// Label by protocol (`mcp`, `a2a`, `http`)
+    pub protocol: bool,
+    /// Label by transport (`http`, `sse`, `websocket`, `grpc`)
+    pub transport: bool,
+    /// Label by pattern category of the violation
+    pub category: bool,
+    /// Label by configuration and pattern set version
+    pub versions: bool,
+    /// Distinct values kept per dimension and worker
+    pub max_values: usize,
+}
+
+impl Default for MetricDimensionsConfig {
+    fn default() -> Self {
+        Self {
+            tenant: true,
+            protocol: true,
+            transport: true,
+            category: true,
+            versions: false,
+            max_values: 32,
+        }
+    }
+}
+
+impl MetricDimensionsConfig {
+    /// Validate the configuration, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        if self.max_values == 0 {
+            diagnostics.push("metric_dimensions.max_values: must be greater than 0".to_string());
+        }
+        diagnostics
+    }
+}
+
+/// Dimension values of a finished request
+#[derive(Debug, Clone, Default)]
+pub struct RequestDimensions<'a> {
+    /// Tenant of the request
+    pub tenant: Option<&'a str>,
+    /// Protocol (`mcp`, `a2a`, `http`)
+    pub protocol: &'a str,
+    /// Transport (`http`, `sse`, `websocket`, `grpc`)
+    pub transport: &'a str,
+    /// Verdict action (`allowed`, `blocked`, ...)
+    pub action: &'a str,
+    /// Category of the violation, if any
+    pub category: Option<&'a str>,
+    /// Version of the configuration in force
+    pub config_version: Option<&'a str>,
+    /// Version of the blocked pattern set in force
+    pub pattern_version: Option<&'a str>,
+}
+
+/// Guardrail metrics
+pub struct FilterMetrics {
+    sink: Box<dyn MetricSink>,
+    /// Metric IDs by full name
+    ids: HashMap<String, u32>,
+    /// Values seen per dimension, for the cardinality cap
+    dimension_values: HashMap<&'static str, HashSet<String>>,
+}
+
+impl FilterMetrics {
+    /// Create metrics backed by the proxy-wasm host
+    #[cfg(feature = "proxy-wasm")]
+    pub fn new() -> Self {
+        Self::with_sink(Box::new(HostMetricSink))
+    }
+
+    /// Create metrics backed by a custom sink
+    pub fn with_sink(sink: Box<dyn MetricSink>) -> Self {
+        Self {
+            sink,
+            ids: HashMap::new(),
+            dimension_values: HashMap::new(),
+        }
+    }
+
+    /// A request body was inspected
+    pub fn request_inspected(&mut self) {
+        self.increment(MetricType::Counter, "requests_inspected", 1);
+    }
+
+    /// A request was blocked, labelled by pattern category
+    pub fn request_blocked(&mut self, category: &str) {
+        self.increment(MetricType::Counter, "requests_blocked", 1);
+        self.increment(
+            MetricType::Counter,
+            &format!("requests_blocked.{}", category),
+            1,
+        );
+    }
+
+    /// A blocked pattern fired, labelled by the pattern
+    ///
+    /// Cardinality is bounded by the configured pattern list.
+    pub fn pattern_hit(&mut self, pattern: &str) {
+        self.increment(
+            MetricType::Counter,
+            &format!("pattern_hits.{}", Self::sanitize(pattern)),
+            1,
+        );
+    }
+
+    /// PII was detected, labelled by PII type
+    pub fn pii_detected(&mut self, pii_type: &str) {
+        self.increment(MetricType::Counter, "pii_detected", 1);
+        self.increment(MetricType::Counter, &format!("pii_detected.{}", pii_type), 1);
+    }
+
+    /// A request was rate limited
+    pub fn rate_limited(&mut self) {
+        self.increment(MetricType::Counter, "rate_limited", 1);
+    }
+
+    /// A reviewer decided on a high-risk tool call
+    pub fn tool_approval(&mut self, approved: bool) {
+        let outcome = if approved { "approved" } else { "rejected" };
+        self.increment(MetricType::Counter, &format!("tool_approvals.{}", outcome), 1);
+    }
+
+    /// The policy decision point answered (`allow`, `deny`) or was bypassed (`fallback`)
+    pub fn pdp_decision(&mut self, outcome: &str) {
+        self.increment(MetricType::Counter, &format!("pdp_decisions.{}", outcome), 1);
+    }
+
+    /// A request was forwarded with quarantine routing, labelled by category
+    pub fn request_quarantined(&mut self, category: &str) {
+        self.increment(MetricType::Counter, "requests_quarantined", 1);
+        self.increment(MetricType::Counter, &format!("requests_quarantined.{}", category), 1);
+    }
+
+    /// A violation was forwarded because enforcement is relaxed, labelled by category
+    pub fn violation_monitored(&mut self, category: &str) {
+        self.increment(MetricType::Counter, "monitored", 1);
+        self.increment(MetricType::Counter, &format!("monitored.{}", category), 1);
+    }
+
+    /// Body scanning stopped early, labelled by the exhausted limit (`bytes`, `time`)
+    pub fn scan_budget_exhausted(&mut self, limit: &str) {
+        self.increment(MetricType::Counter, "scan_budget_exhausted", 1);
+        self.increment(MetricType::Counter, &format!("scan_budget_exhausted.{}", limit), 1);
+    }
+
+    /// A non-text request body was classified by the binary policy, labelled by kind
+    pub fn binary_body(&mut self, kind: &str) {
+        self.increment(MetricType::Counter, &format!("binary_bodies.{}", kind), 1);
+    }
+
+    /// A gRPC call ended, labelled by status code name (`ok`, `unavailable`, ...)
+    pub fn grpc_status(&mut self, name: &str) {
+        self.increment(MetricType::Counter, &format!("grpc_status.{}", name), 1);
+    }
+
+    /// A request body was answered from the verdict cache
+    pub fn verdict_cache_hit(&mut self) {
+        self.increment(MetricType::Counter, "verdict_cache_hits", 1);
+    }
+
+    /// A pushed configuration was rejected (`too_large`, `invalid`, ...)
+    pub fn config_rejected(&mut self, reason: &str) {
+        self.increment(MetricType::Counter, &format!("config_rejected.{}", reason), 1);
+    }
+
+    /// HTTP contexts still running on a replaced configuration
+    pub fn stale_contexts(&mut self, count: usize) {
+        self.record(MetricType::Gauge, "config_stale_contexts", count as u64);
+    }
+
+    /// A cacheable request body was not in the verdict cache
+    pub fn verdict_cache_miss(&mut self) {
+        self.increment(MetricType::Counter, "verdict_cache_misses", 1);
+    }
+
+    /// A body match was handled by its severity's action
+    pub fn severity_action(&mut self, action: &str) {
+        let name = format!("severity_actions.{}", Self::sanitize(action));
+        self.increment(MetricType::Counter, &name, 1);
+    }
+
+    /// An agent's penalty escalated
+    pub fn agent_penalized(&mut self, penalty: &str) {
+        let name = format!("agent_penalties.{}", Self::sanitize(penalty));
+        self.increment(MetricType::Counter, &name, 1);
+    }
+
+    /// A STDIO bypass attempt was detected, labelled by namespace of the caller
+    pub fn stdio_bypass(&mut self, namespace: &str) {
+        self.increment(MetricType::Counter, "stdio_bypass_attempts", 1);
+        let name = format!("stdio_bypass_attempts.{}", Self::sanitize(namespace));
+        self.increment(MetricType::Counter, &name, 1);
+    }
+
+    /// A bypass token was honored (`granted`) or rejected, labelled by outcome
+    pub fn bypass(&mut self, outcome: &str) {
+        self.increment(MetricType::Counter, &format!("bypass.{}", outcome), 1);
+    }
+
+    /// A response stream was cut off, labelled by the limit exceeded
+    pub fn stream_terminated(&mut self, limit: &str) {
+        self.increment(MetricType::Counter, &format!("streams_terminated.{}", limit), 1);
+    }
+
+    /// MCP notifications over the session cap were dropped
+    pub fn notifications_dropped(&mut self, count: u32) {
+        self.increment(MetricType::Counter, "notifications_dropped", count as i64);
+    }
+
+    /// An MCP tool result went over a limit, labelled by the limit
+    pub fn tool_result_limited(&mut self, limit: &str) {
+        self.increment(MetricType::Counter, &format!("tool_results_limited.{}", limit), 1);
+    }
+
+    /// Template abuse found in a prompts/get call, labelled by the issue
+    pub fn prompt_template_violation(&mut self, issue: &str) {
+        self.increment(MetricType::Counter, &format!("prompt_template_violations.{}", issue), 1);
+    }
+
+    /// A tool argument was flagged, labelled by the check
+    pub fn tool_argument_flagged(&mut self, check: &str) {
+        self.increment(MetricType::Counter, &format!("tool_arguments_flagged.{}", check), 1);
+    }
+
+    /// A PII value replaced by a vault token
+    pub fn pii_tokenized(&mut self, pii_type: &str) {
+        self.increment(MetricType::Counter, &format!("pii_tokenized.{}", pii_type), 1);
+    }
+
+    /// A request matched a threat-intel IoC
+    pub fn threat_intel_match(&mut self) {
+        self.increment(MetricType::Counter, "threat_intel_matches", 1);
+    }
+
+    /// A threat feed fetch finished, labelled by outcome (`ok`, `error`)
+    pub fn threat_feed_fetch(&mut self, outcome: &str) {
+        self.increment(MetricType::Counter, &format!("threat_feed_fetches.{}", outcome), 1);
+    }
+
+    /// A request was assigned to a pattern experiment arm (`control`, `candidate`)
+    pub fn experiment_request(&mut self, arm: &str) {
+        self.increment(MetricType::Counter, &format!("pattern_experiment.requests.{}", arm), 1);
+    }
+
+    /// A pattern bundle matched an experiment request (`control`, `candidate`)
+    pub fn experiment_match(&mut self, bundle: &str) {
+        self.increment(MetricType::Counter, &format!("pattern_experiment.matches.{}", bundle), 1);
+    }
+
+    /// The bundles disagreed (`candidate_only`, `control_only`)
+    pub fn experiment_delta(&mut self, delta: &str) {
+        self.increment(MetricType::Counter, &format!("pattern_experiment.deltas.{}", delta), 1);
+    }
+
+    /// A request fell inside or outside a subsystem's rollout
+    pub fn rollout(&mut self, subsystem: &str, included: bool) {
+        let side = if included { "included" } else { "excluded" };
+        self.increment(MetricType::Counter, &format!("rollout.{}.{}", subsystem, side), 1);
+    }
+
+    /// A request was evaluated against the dry-run configuration
+    pub fn dry_run_request(&mut self) {
+        self.increment(MetricType::Counter, "dry_run.requests", 1);
+    }
+
+    /// A dry-run body was too large to compare
+    pub fn dry_run_skipped(&mut self) {
+        self.increment(MetricType::Counter, "dry_run.skipped", 1);
+    }
+
+    /// The dry-run verdict differed, labelled by how
+    pub fn config_diff(&mut self, kind: &str) {
+        self.increment(MetricType::Counter, &format!("dry_run.diffs.{}", kind), 1);
+    }
+
+    /// A link violated the URL policy, labelled by the reason
+    pub fn url_violation(&mut self, reason: &str) {
+        self.increment(MetricType::Counter, &format!("url_violations.{}", reason), 1);
+    }
+
+    /// A pattern matched in stripped markup, labelled by where it was hidden
+    pub fn markup_match(&mut self, location: &str) {
+        self.increment(MetricType::Counter, &format!("markup_matches.{}", location), 1);
+    }
+
+    /// A token-burning prompt was flagged, labelled by the kind of repetition
+    pub fn cost_abuse(&mut self, kind: &str) {
+        self.increment(MetricType::Counter, &format!("cost_abuse.{}", kind), 1);
+    }
+
+    /// A high-entropy run was found in a request, labelled by the action taken
+    pub fn entropy_anomaly(&mut self, action: &str) {
+        self.increment(MetricType::Counter, &format!("entropy_anomalies.{}", action), 1);
+    }
+
+    /// A request's prompt language was detected, labelled by language code
+    pub fn language_detected(&mut self, code: &str) {
+        self.increment(MetricType::Counter, &format!("languages_detected.{}", code), 1);
+    }
+
+    /// A request header scrubbed before forwarding
+    pub fn header_scrubbed(&mut self, action: &str) {
+        self.increment(MetricType::Counter, &format!("headers_scrubbed.{}", action), 1);
+    }
+
+    /// Bytes passed through the body scanner
+    pub fn scan_bytes(&mut self, bytes: usize) {
+        self.increment(MetricType::Counter, "scan_bytes", bytes as i64);
+    }
+
+    /// Scanner latency for one chunk, in microseconds
+    pub fn scan_latency_us(&mut self, micros: u64) {
+        self.record(MetricType::Histogram, "scan_latency_us", micros);
+    }
+
+    /// Time spent in one filter hook, in microseconds
+    pub fn phase_latency_us(&mut self, phase: &str, micros: u64) {
+        let name = format!("phase_latency_us.{}", Self::sanitize(phase));
+        self.record(MetricType::Histogram, &name, micros);
+    }
+
+    /// Time a request spent in the filter over all hooks, in microseconds
+    pub fn request_overhead_us(&mut self, micros: u64) {
+        self.record(MetricType::Histogram, "request_overhead_us", micros);
+    }
+
+    /// The filter's p99 overhead went over its budget
+    pub fn overhead_budget_exceeded(&mut self) {
+        self.increment(MetricType::Counter, "overhead_budget_exceeded", 1);
+    }
+
+    /// Audit events accepted by the external collector
+    pub fn audit_shipped(&mut self, events: usize) {
+        self.increment(MetricType::Counter, "audit_shipped", events as i64);
+    }
+
+    /// Audit events dropped (queue overflow or exhausted retries)
+    pub fn audit_dropped(&mut self, events: usize) {
+        self.increment(MetricType::Counter, "audit_dropped", events as i64);
+    }
+
+    /// Trace spans dropped from a full export queue
+    pub fn spans_dropped(&mut self, spans: usize) {
+        self.increment(MetricType::Counter, "spans_dropped", spans as i64);
+    }
+
+    /// An audit batch delivery is being retried
+    pub fn audit_retried(&mut self) {
+        self.increment(MetricType::Counter, "audit_retries", 1);
+    }
+
+    /// A request finished: count it under the configured dimensions
+    pub fn request_completed(
+        &mut self,
+        config: &MetricDimensionsConfig,
+        request: &RequestDimensions,
+    ) {
+        let dimensions = [
+            ("tenant", config.tenant, request.tenant),
+            ("protocol", config.protocol, Some(request.protocol)),
+            ("transport", config.transport, Some(request.transport)),
+            ("category", config.category, request.category),
+            ("config_version", config.versions, request.config_version),
+            ("pattern_version", config.versions, request.pattern_version),
+        ];
+        let mut name = "requests_by".to_string();
+        for (dimension, enabled, value) in dimensions {
+            if enabled {
+                let value = self.dimension_value(dimension, value, config.max_values);
+                name.push_str(&format!(".{}.{}", dimension, value));
+            }
+        }
+        name.push_str(&format!(".action.{}", Self::sanitize(request.action)));
+        self.increment(MetricType::Counter, &name, 1);
+    }
+
+    /// Sanitized value of a dimension, `other` once its cap is reached
+    fn dimension_value(
+        &mut self,
+        dimension: &'static str,
+        value: Option<&str>,
+        cap: usize,
+    ) -> String {
+        let value = match value {
+            Some(value) if !value.is_empty() => Self::sanitize(value),
+            _ => return NONE_VALUE.to_string(),
+        };
+        let seen = self.dimension_values.entry(dimension).or_default();
+        if seen.contains(&value) {
+            return value;
+        }
+        if seen.len() >= cap {
+            return OTHER_VALUE.to_string();
+        }
+        seen.insert(value.clone());
+        value
+    }
+
+    /// Forget dimension values seen so far (the configuration changed)
+    pub fn reset_dimensions(&mut self) {
+        self.dimension_values.clear();
+    }
+
+    /// Increment a counter by name (prefix is added)
+    pub fn increment(&mut self, metric_type: MetricType, name: &str, offset: i64) {
+        if let Some(id) = self.metric_id(metric_type, name) {
+            self.sink.increment(id, offset);
+        }
+    }
+
+    /// Record a histogram or gauge value by name (prefix is added)
+    pub fn record(&mut self, metric_type: MetricType, name: &str, value: u64) {
+        if let Some(id) = self.metric_id(metric_type, name) {
+            self.sink.record(id, value);
+        }
+    }
+
+    /// Full metric name for a short name
+    pub fn full_name(name: &str) -> String {
+        format!("{}.{}", METRIC_PREFIX, name)
+    }
+
+    /// Make a value safe for use in a metric name
+    ///
+    /// Lowercases and replaces anything outside `[a-z0-9_]` with `_`.
+    pub fn sanitize(value: &str) -> String {
+        value
+            .chars()
+            .map(|c| match c.to_ascii_lowercase() {
+                c @ ('a'..='z' | '0'..='9' | '_') => c,
+                _ => '_',
+            })
+            .collect()
+    }
+
+    /// Number of distinct metrics defined so far
+    pub fn defined_count(&self) -> usize {
+        self.ids.len()
+    }
+
+    fn metric_id(&mut self, metric_type: MetricType, name: &str) -> Option<u32> {
+        let full_name = Self::full_name(name);
+        if let Some(&id) = self.ids.get(&full_name) {
+            return Some(id);
+        }
+        let id = self.sink.define(metric_type, &full_name)?;
+        self.ids.insert(full_name, id);
+        Some(id)
+    }
+}
+
+#[cfg(feature = "proxy-wasm")]
+impl Default for FilterMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// In-memory sink: values by metric name
+    #[derive(Clone, Default)]
+    pub(crate) struct MemorySink {
+        pub names: Rc<RefCell<Vec<String>>>,
+        pub values: Rc<RefCell<HashMap<u32, i64>>>,
+    }
+
+    impl MemorySink {
+        pub fn value(&self, name: &str) -> i64 {
+            let names = self.names.borrow();
+            names
+                .iter()
+                .position(|n| n == name)
+                .and_then(|id| self.values.borrow().get(&(id as u32)).copied())
+                .unwrap_or(0)
+        }
+    }
+
+    impl MetricSink for MemorySink {
+        fn define(&self, _metric_type: MetricType, name: &str) -> Option<u32> {
+            let mut names = self.names.borrow_mut();
+            names.push(name.to_string());
+            Some(names.len() as u32 - 1)
+        }
+
+        fn increment(&self, metric_id: u32, offset: i64) {
+            *self.values.borrow_mut().entry(metric_id).or_insert(0) += offset;
+        }
+
+        fn record(&self, metric_id: u32, value: u64) {
+            self.values.borrow_mut().insert(metric_id, value as i64);
+        }
+    }
+
+    #[test]
+    fn test_counters() {
+        let sink = MemorySink::default();
+        let mut metrics = FilterMetrics::with_sink(Box::new(sink.clone()));
+
+        metrics.request_inspected();
+        metrics.request_inspected();
+        metrics.scan_bytes(1024);
+
+        assert_eq!(sink.value("ai_guard.requests_inspected"), 2);
+        assert_eq!(sink.value("ai_guard.scan_bytes"), 1024);
+    }
+
+    #[test]
+    fn test_blocked_by_category() {
+        let sink = MemorySink::default();
+        let mut metrics = FilterMetrics::with_sink(Box::new(sink.clone()));
+
+        metrics.request_blocked("jailbreak");
+        metrics.request_blocked("jailbreak");
+        metrics.request_blocked("instruction_override");
+
+        assert_eq!(sink.value("ai_guard.requests_blocked"), 3);
+        assert_eq!(sink.value("ai_guard.requests_blocked.jailbreak"), 2);
+        assert_eq!(sink.value("ai_guard.requests_blocked.instruction_override"), 1);
+    }
+
+    #[test]
+    fn test_metrics_defined_once() {
+        let sink = MemorySink::default();
+        let mut metrics = FilterMetrics::with_sink(Box::new(sink.clone()));
+
+        metrics.rate_limited();
+        metrics.rate_limited();
+        metrics.pii_detected("ssn");
+
+        // rate_limited, pii_detected, pii_detected.ssn
+        assert_eq!(metrics.defined_count(), 3);
+        assert_eq!(sink.names.borrow().len(), 3);
+    }
+
+    #[test]
+    fn test_dimensioned_requests() {
+        let sink = MemorySink::default();
+        let mut metrics = FilterMetrics::with_sink(Box::new(sink.clone()));
+        let config = MetricDimensionsConfig::default();
+        let blocked = RequestDimensions {
+            tenant: Some("Team-A"),
+            protocol: "mcp",
+            transport: "sse",
+            action: "blocked",
+            category: Some("jailbreak"),
+            config_version: Some("3f2a9c01b7d4"),
+            pattern_version: Some("a81e5d0c2f96"),
+        };
+        metrics.request_completed(&config, &blocked);
+        metrics.request_completed(&config, &blocked);
+        let allowed = RequestDimensions { action: "allowed", category: None, ..blocked.clone() };
+        metrics.request_completed(&config, &allowed);
+
+        let prefix = "ai_guard.requests_by.tenant.team_a.protocol.mcp.transport.sse";
+        assert_eq!(sink.value(&format!("{}.category.jailbreak.action.blocked", prefix)), 2);
+        assert_eq!(sink.value(&format!("{}.category.none.action.allowed", prefix)), 1);
+
+        // Only enabled dimensions are in the name
+        let config = MetricDimensionsConfig {
+            protocol: false,
+            transport: false,
+            category: false,
+            ..Default::default()
+        };
+        metrics.request_completed(&config, &blocked);
+        assert_eq!(sink.value("ai_guard.requests_by.tenant.team_a.action.blocked"), 1);
+
+        let config = MetricDimensionsConfig { tenant: false, versions: true, ..config };
+        metrics.request_completed(&config, &blocked);
+        let name = "ai_guard.requests_by.config_version.3f2a9c01b7d4\
+                    .pattern_version.a81e5d0c2f96.action.blocked";
+        assert_eq!(sink.value(name), 1);
+    }
+
+    #[test]
+    fn test_dimension_cardinality_cap() {
+        let sink = MemorySink::default();
+        let mut metrics = FilterMetrics::with_sink(Box::new(sink.clone()));
+        let config = MetricDimensionsConfig {
+            protocol: false,
+            transport: false,
+            category: false,
+            max_values: 2,
+            ..Default::default()
+        };
+        for tenant in ["a", "b", "c", "d", "a"] {
+            let request = RequestDimensions {
+                tenant: Some(tenant),
+                action: "allowed",
+                ..Default::default()
+            };
+            metrics.request_completed(&config, &request);
+        }
+        assert_eq!(sink.value("ai_guard.requests_by.tenant.a.action.allowed"), 2);
+        assert_eq!(sink.value("ai_guard.requests_by.tenant.b.action.allowed"), 1);
+        assert_eq!(sink.value("ai_guard.requests_by.tenant.other.action.allowed"), 2);
+        assert_eq!(metrics.defined_count(), 3);
+
+        metrics.reset_dimensions();
+        let request = RequestDimensions {
+            tenant: Some("c"),
+            action: "allowed",
+            ..Default::default()
+        };
+        metrics.request_completed(&config, &request);
+        assert_eq!(sink.value("ai_guard.requests_by.tenant.c.action.allowed"), 1);
+    }
+
+    #[test]
+    fn test_audit_shipping_counters() {
+        let sink = MemorySink::default();
+        let mut metrics = FilterMetrics::with_sink(Box::new(sink.clone()));
+
+        metrics.audit_shipped(50);
+        metrics.audit_shipped(10);
+        metrics.audit_dropped(3);
+        metrics.audit_retried();
+
+        assert_eq!(sink.value("ai_guard.audit_shipped"), 60);
+        assert_eq!(sink.value("ai_guard.audit_dropped"), 3);
+        assert_eq!(sink.value("ai_guard.audit_retries"), 1);
+    }
+
+    #[test]
+    fn test_verdict_cache_counters() {
+        let sink = MemorySink::default();
+        let mut metrics = FilterMetrics::with_sink(Box::new(sink.clone()));
+
+        metrics.verdict_cache_miss();
+        metrics.verdict_cache_hit();
+        metrics.verdict_cache_hit();
+
+        assert_eq!(sink.value("ai_guard.verdict_cache_hits"), 2);
+        assert_eq!(sink.value("ai_guard.verdict_cache_misses"), 1);
+    }
+
+    #[test]
+    fn test_scan_budget_counters() {
+        let sink = MemorySink::default();
+        let mut metrics = FilterMetrics::with_sink(Box::new(sink.clone()));
+
+        metrics.scan_budget_exhausted("bytes");
+        metrics.scan_budget_exhausted("time");
+        metrics.scan_budget_exhausted("bytes");
+
+        assert_eq!(sink.value("ai_guard.scan_budget_exhausted"), 3);
+        assert_eq!(sink.value("ai_guard.scan_budget_exhausted.bytes"), 2);
+        assert_eq!(sink.value("ai_guard.scan_budget_exhausted.time"), 1);
+    }
+
+    #[test]
+    fn test_grpc_status_counters() {
+        let sink = MemorySink::default();
+        let mut metrics = FilterMetrics::with_sink(Box::new(sink.clone()));
+
+        metrics.grpc_status("ok");
+        metrics.grpc_status("unavailable");
+        metrics.grpc_status("unavailable");
+
+        assert_eq!(sink.value("ai_guard.grpc_status.ok"), 1);
+        assert_eq!(sink.value("ai_guard.grpc_status.unavailable"), 2);
+    }
+
+    #[test]
+    fn test_pattern_hit_sanitized() {
+        let sink = MemorySink::default();
+        let mut metrics = FilterMetrics::with_sink(Box::new(sink.clone()));
+
+        metrics.pattern_hit("DAN mode");
+        metrics.pattern_hit("rm -rf");
+
+        assert_eq!(sink.value("ai_guard.pattern_hits.dan_mode"), 1);
+        assert_eq!(sink.value("ai_guard.pattern_hits.rm__rf"), 1);
+    }
+
+    #[test]
+    fn test_histogram_record() {
+        let sink = MemorySink::default();
+        let mut metrics = FilterMetrics::with_sink(Box::new(sink.clone()));
+
+        metrics.scan_latency_us(42);
+
+        assert_eq!(sink.value("ai_guard.scan_latency_us"), 42);
+    }
+}
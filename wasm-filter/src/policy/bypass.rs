@@ -0,0 +1,238 @@
+//! Signed Inspection Bypass
+//!
+//! Health checkers and internal batch jobs send synthetic prompts that trip
+//! the guardrail without being attacks. Such callers present a signed,
+//! expiring token in `x-guardrail-bypass`:
+//!
+//! ```text
+//! <key_id>.<expires_unix_secs>.<hex HMAC-SHA256(key, "ai-guard-bypass:<key_id>:<expires>")>
+//! ```
+//!
+//! Keys are configured by ID, so each caller gets its own key and one key is
+//! revoked without touching the others. A valid token skips inspection of
+//! the request and its response. Every bypass is audited, and each key is
+//! capped at `max_per_minute` bypasses, counted in shared data so the cap
+//! holds across workers; past the cap, requests are inspected as usual. An
+//! invalid token is audited and ignored. The header is never forwarded
+//! upstream.
+
+use crate::crypto::{hmac_sha256, to_hex, verify_hmac_hex};
+use crate::shared::{SharedError, SharedStore};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Request header carrying the signed bypass token
+pub const BYPASS_HEADER: &str = "x-guardrail-bypass";
+
+/// Domain separator for bypass token signatures
+const TOKEN_CONTEXT: &str = "ai-guard-bypass:";
+
+/// Shortest accepted bypass key
+const MIN_KEY_LEN: usize = 16;
+
+/// Bypass configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BypassConfig {
+    /// HMAC-SHA256 keys, by key ID
+    pub keys: BTreeMap<String, String>,
+    /// Longest accepted token lifetime, in seconds
+    pub max_ttl_secs: u64,
+    /// Bypasses allowed per key and minute
+    pub max_per_minute: u32,
+}
+
+impl Default for BypassConfig {
+    fn default() -> Self {
+        Self { keys: BTreeMap::new(), max_ttl_secs: 300, max_per_minute: 60 }
+    }
+}
+
+impl BypassConfig {
+    /// Validate the configuration, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        if self.keys.is_empty() {
+            diagnostics.push("bypass.keys: must not be empty".to_string());
+        }
+        for (id, key) in &self.keys {
+            if id.is_empty() || id.contains('.') {
+                diagnostics
+                    .push(format!("bypass.keys.{}: key ID must be non-empty without '.'", id));
+            }
+            if key.len() < MIN_KEY_LEN {
+                diagnostics
+                    .push(format!("bypass.keys.{}: must be at least {} bytes", id, MIN_KEY_LEN));
+            }
+        }
+        if self.max_ttl_secs == 0 {
+            diagnostics.push("bypass.max_ttl_secs: must be greater than 0".to_string());
+        }
+        if self.max_per_minute == 0 {
+            diagnostics.push("bypass.max_per_minute: must be greater than 0".to_string());
+        }
+        diagnostics
+    }
+
+    /// Verify a token, returning the ID of the key that signed it
+    pub fn verify(&self, token: &str, now_secs: u64) -> Result<&str, BypassRejection> {
+        let mut parts = token.trim().splitn(3, '.');
+        let (key_id, expires, signature) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(id), Some(expires), Some(signature)) => (id, expires, signature),
+            _ => return Err(BypassRejection::Malformed),
+        };
+        let expires_secs = match expires.parse::<u64>() {
+            Ok(v) => v,
+            Err(_) => return Err(BypassRejection::Malformed),
+        };
+        let (key_id, key) = match self.keys.get_key_value(key_id) {
+            Some(entry) => entry,
+            None => return Err(BypassRejection::UnknownKey),
+        };
+        if expires_secs < now_secs {
+            return Err(BypassRejection::Expired);
+        }
+        if expires_secs - now_secs > self.max_ttl_secs {
+            return Err(BypassRejection::TtlTooLong);
+        }
+        let message = token_message(key_id, expires_secs);
+        if !verify_hmac_hex(key.as_bytes(), message.as_bytes(), signature) {
+            return Err(BypassRejection::BadSignature);
+        }
+        Ok(key_id)
+    }
+}
+
+/// Sign a bypass token valid until `expires_secs`
+pub fn sign_bypass_token(key_id: &str, key: &[u8], expires_secs: u64) -> String {
+    let message = token_message(key_id, expires_secs);
+    format!("{}.{}.{}", key_id, expires_secs, to_hex(&hmac_sha256(key, message.as_bytes())))
+}
+
+fn token_message(key_id: &str, expires_secs: u64) -> String {
+    format!("{}{}:{}", TOKEN_CONTEXT, key_id, expires_secs)
+}
+
+/// Why a bypass token was not honored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BypassRejection {
+    /// Not `<key_id>.<expires>.<signature>`
+    Malformed,
+    /// Key ID not configured
+    UnknownKey,
+    /// Expiry in the past
+    Expired,
+    /// Expiry further out than `max_ttl_secs`
+    TtlTooLong,
+    /// Signature does not match the key
+    BadSignature,
+    /// The key is over `max_per_minute`
+    RateLimited,
+}
+
+impl BypassRejection {
+    /// Stable snake_case name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BypassRejection::Malformed => "malformed",
+            BypassRejection::UnknownKey => "unknown_key",
+            BypassRejection::Expired => "expired",
+            BypassRejection::TtlTooLong => "ttl_too_long",
+            BypassRejection::BadSignature => "bad_signature",
+            BypassRejection::RateLimited => "rate_limited",
+        }
+    }
+}
+
+/// Bypasses of one key in the current minute
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct BypassWindow {
+    start_secs: u64,
+    count: u32,
+}
+
+/// Count a bypass by a key; `Ok(false)` when the key is over its cap
+///
+/// One shared data window per configured key, updated with CAS so
+/// concurrent workers never both take the last bypass of a minute.
+pub fn admit_bypass(
+    store: &impl SharedStore,
+    key_id: &str,
+    max_per_minute: u32,
+    now_secs: u64,
+) -> Result<bool, SharedError> {
+    let mut admitted = false;
+    store.update(&window_key(key_id), |current| {
+        let mut window: BypassWindow =
+            current.and_then(|v| serde_json::from_slice(v).ok()).unwrap_or_default();
+        if now_secs >= window.start_secs + 60 {
+            window = BypassWindow { start_secs: now_secs, count: 0 };
+        }
+        admitted = window.count < max_per_minute;
+        if admitted {
+            window.count += 1;
+        }
+        serde_json::to_vec(&window).unwrap_or_default()
+    })?;
+    Ok(admitted)
+}
+
+fn window_key(key_id: &str) -> String {
+    format!("ai_guard.bypass.{}", key_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::MemorySharedStore;
+
+    const KEY: &str = "0123456789abcdef";
+
+    fn config() -> BypassConfig {
+        let keys = [("healthcheck".to_string(), KEY.to_string())].into_iter().collect();
+        BypassConfig { keys, ..Default::default() }
+    }
+
+    #[test]
+    fn test_token_round_trip_and_rejections() {
+        let config = config();
+        let token = sign_bypass_token("healthcheck", KEY.as_bytes(), 1_200);
+        assert_eq!(config.verify(&token, 1_000), Ok("healthcheck"));
+        assert_eq!(config.verify(&token, 1_201), Err(BypassRejection::Expired));
+        assert_eq!(config.verify(&token, 800), Err(BypassRejection::TtlTooLong));
+
+        let other = sign_bypass_token("batch", KEY.as_bytes(), 1_200);
+        assert_eq!(config.verify(&other, 1_000), Err(BypassRejection::UnknownKey));
+        let forged = sign_bypass_token("healthcheck", b"fedcba9876543210", 1_200);
+        assert_eq!(config.verify(&forged, 1_000), Err(BypassRejection::BadSignature));
+        let extended = token.replacen("1200", "1201", 1);
+        assert_eq!(config.verify(&extended, 1_000), Err(BypassRejection::BadSignature));
+        assert_eq!(config.verify("healthcheck.soon", 1_000), Err(BypassRejection::Malformed));
+    }
+
+    #[test]
+    fn test_admit_caps_each_key() {
+        // Workers share the store, so the cap holds across them
+        let store = MemorySharedStore::new();
+        assert_eq!(admit_bypass(&store, "healthcheck", 2, 100), Ok(true));
+        assert_eq!(admit_bypass(&store, "healthcheck", 2, 110), Ok(true));
+        assert_eq!(admit_bypass(&store, "healthcheck", 2, 120), Ok(false));
+        assert_eq!(admit_bypass(&store, "batch", 2, 120), Ok(true));
+        assert_eq!(admit_bypass(&store, "healthcheck", 2, 160), Ok(true));
+    }
+
+    #[test]
+    fn test_validate() {
+        let keys = [("a.b".to_string(), "short".to_string())].into_iter().collect();
+        let invalid = BypassConfig { keys, max_per_minute: 0, ..Default::default() };
+        assert_eq!(
+            invalid.validate(),
+            vec![
+                "bypass.keys.a.b: key ID must be non-empty without '.'".to_string(),
+                "bypass.keys.a.b: must be at least 16 bytes".to_string(),
+                "bypass.max_per_minute: must be greater than 0".to_string(),
+            ]
+        );
+        assert!(config().validate().is_empty());
+    }
+}
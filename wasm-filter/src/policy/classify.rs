@@ -0,0 +1,209 @@
+//! Request Classification
+//!
+//! Many policies only make sense for one kind of traffic: token budgets for
+//! chat, tool allowlists for tool calls, stricter limits for admin APIs. The
+//! classification stage labels each request once so every later section can
+//! key off the label instead of repeating path matching:
+//!
+//! | Label            | Request                                                  |
+//! |------------------|----------------------------------------------------------|
+//! | `chat`           | chat/completions, messages, responses, generateContent   |
+//! | `embedding`      | embeddings, embed, embedContent                          |
+//! | `tool_call`      | MCP `tools/call`                                         |
+//! | `admin`          | `admin`/`organization` path segments, `admin_path_prefixes` |
+//! | `agent_to_agent` | A2A agent cards, `/a2a` paths, `message/*`, `tasks/*`    |
+//! | `mcp`            | other MCP traffic                                        |
+//! | `other`          | anything else                                            |
+//!
+//! The label is derived from the path when the headers arrive (routes see
+//! this label) and refined from the JSON-RPC method once the body is read
+//! (rules, the PDP and the published verdict see the refined one). It is
+//! always published as the `ai_guard.class` property; with `header` set it
+//! is also sent upstream, and the headers are held until the body is read.
+
+use serde::Deserialize;
+
+/// Kind of AI traffic a request carries
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestClass {
+    Chat,
+    Embedding,
+    ToolCall,
+    Admin,
+    AgentToAgent,
+    Mcp,
+    Other,
+}
+
+impl RequestClass {
+    /// Label used in metadata, headers and policies
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RequestClass::Chat => "chat",
+            RequestClass::Embedding => "embedding",
+            RequestClass::ToolCall => "tool_call",
+            RequestClass::Admin => "admin",
+            RequestClass::AgentToAgent => "agent_to_agent",
+            RequestClass::Mcp => "mcp",
+            RequestClass::Other => "other",
+        }
+    }
+
+    /// Refine a path-based class with the body's JSON-RPC method
+    pub fn refine(self, method: Option<&str>) -> Self {
+        let method = match method {
+            Some(method) if self != RequestClass::Admin => method,
+            _ => return self,
+        };
+        if method == "tools/call" {
+            RequestClass::ToolCall
+        } else if method.starts_with("message/") || method.starts_with("tasks/") {
+            RequestClass::AgentToAgent
+        } else if self == RequestClass::Other {
+            RequestClass::Mcp
+        } else {
+            self
+        }
+    }
+}
+
+/// Classification configuration
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClassificationConfig {
+    /// Request header carrying the label upstream (not sent when absent)
+    pub header: Option<String>,
+    /// Additional path prefixes of admin APIs
+    pub admin_path_prefixes: Vec<String>,
+}
+
+impl ClassificationConfig {
+    /// Validate the configuration, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        if self.header.as_deref().is_some_and(|h| h.is_empty() || h.starts_with(':')) {
+            diagnostics.push("classification.header: must be a regular header name".to_string());
+        }
+        if self.admin_path_prefixes.iter().any(|p| !p.starts_with('/')) {
+            diagnostics.push(
+                "classification.admin_path_prefixes: prefixes must start with '/'".to_string(),
+            );
+        }
+        diagnostics
+    }
+
+    /// Class of a request from its path (before the body is read)
+    pub fn classify(&self, path: Option<&str>, is_mcp: bool) -> RequestClass {
+        let path = path.and_then(|p| p.split('?').next()).unwrap_or_default();
+        let path = path.trim_end_matches('/');
+        let segments: Vec<&str> = path.split('/').collect();
+        let last = segments.last().copied().unwrap_or_default();
+
+        if self.admin_path_prefixes.iter().any(|p| path.starts_with(p.trim_end_matches('/')))
+            || segments.iter().any(|s| ADMIN_SEGMENTS.contains(s))
+        {
+            RequestClass::Admin
+        } else if path.contains("/.well-known/agent") || segments.contains(&"a2a") {
+            RequestClass::AgentToAgent
+        } else if is_mcp || segments.contains(&"mcp") {
+            RequestClass::Mcp
+        } else if EMBEDDING_ENDPOINTS.contains(&last)
+            || last.ends_with(":embedContent")
+            || last.ends_with(":batchEmbedContents")
+        {
+            RequestClass::Embedding
+        } else if CHAT_ENDPOINTS.contains(&last)
+            || last.ends_with(":generateContent")
+            || last.ends_with(":streamGenerateContent")
+        {
+            RequestClass::Chat
+        } else {
+            RequestClass::Other
+        }
+    }
+}
+
+/// Path segments of provider admin APIs
+const ADMIN_SEGMENTS: &[&str] = &["admin", "organization", "organizations"];
+
+/// Final path segments of embedding endpoints
+const EMBEDDING_ENDPOINTS: &[&str] = &["embeddings", "embed"];
+
+/// Final path segments of chat endpoints (OpenAI, Anthropic, Bedrock, Ollama)
+const CHAT_ENDPOINTS: &[&str] = &[
+    "completions",
+    "messages",
+    "responses",
+    "converse",
+    "converse-stream",
+    "invoke",
+    "invoke-with-response-stream",
+    "chat",
+    "generate",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify(path: &str) -> RequestClass {
+        ClassificationConfig::default().classify(Some(path), false)
+    }
+
+    #[test]
+    fn test_classify_paths() {
+        assert_eq!(classify("/v1/chat/completions"), RequestClass::Chat);
+        assert_eq!(classify("/v1/messages?beta=true"), RequestClass::Chat);
+        assert_eq!(
+            classify("/v1beta/models/gemini-pro:streamGenerateContent"),
+            RequestClass::Chat
+        );
+        assert_eq!(classify("/model/anthropic.claude-v2/converse"), RequestClass::Chat);
+        assert_eq!(classify("/v1/embeddings"), RequestClass::Embedding);
+        assert_eq!(classify("/v1/models/text-embedding-004:embedContent"), RequestClass::Embedding);
+        assert_eq!(classify("/v1/organization/users"), RequestClass::Admin);
+        assert_eq!(classify("/.well-known/agent-card.json"), RequestClass::AgentToAgent);
+        assert_eq!(classify("/a2a/"), RequestClass::AgentToAgent);
+        assert_eq!(classify("/mcp"), RequestClass::Mcp);
+        assert_eq!(classify("/v1/models"), RequestClass::Other);
+        assert_eq!(ClassificationConfig::default().classify(None, true), RequestClass::Mcp);
+    }
+
+    #[test]
+    fn test_admin_prefixes() {
+        let config: ClassificationConfig =
+            serde_json::from_str(r#"{"admin_path_prefixes": ["/internal/keys/"]}"#).unwrap();
+        assert!(config.validate().is_empty());
+        let class = config.classify(Some("/internal/keys/rotate"), false);
+        assert_eq!(class, RequestClass::Admin);
+        // Admin wins over the endpoint shape
+        assert_eq!(classify("/admin/v1/chat/completions"), RequestClass::Admin);
+    }
+
+    #[test]
+    fn test_refine_by_method() {
+        assert_eq!(RequestClass::Mcp.refine(Some("tools/call")), RequestClass::ToolCall);
+        assert_eq!(RequestClass::Mcp.refine(Some("tools/list")), RequestClass::Mcp);
+        assert_eq!(RequestClass::Other.refine(Some("initialize")), RequestClass::Mcp);
+        assert_eq!(RequestClass::Other.refine(Some("message/send")), RequestClass::AgentToAgent);
+        assert_eq!(RequestClass::Chat.refine(None), RequestClass::Chat);
+        assert_eq!(RequestClass::Admin.refine(Some("tools/call")), RequestClass::Admin);
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = ClassificationConfig {
+            header: Some(":class".to_string()),
+            admin_path_prefixes: vec!["admin".to_string()],
+        };
+        assert_eq!(
+            config.validate(),
+            vec![
+                "classification.header: must be a regular header name".to_string(),
+                "classification.admin_path_prefixes: prefixes must start with '/'".to_string(),
+            ]
+        );
+        assert_eq!(RequestClass::AgentToAgent.as_str(), "agent_to_agent");
+    }
+}
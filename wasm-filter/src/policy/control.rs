@@ -0,0 +1,437 @@
+//! Runtime Controls
+//!
+//! Operators need to back off enforcement during an incident (a bad pattern
+//! blocking production traffic, a misbehaving filter) faster than a config
+//! rollout. Control flags live in shared data, which every worker reads at
+//! the start of each request, so a change takes effect on the next request
+//! everywhere without a reload:
+//!
+//! - `disabled`: kill switch, inspection is skipped and everything forwarded
+//! - `monitor_mode`: violations are recorded but requests are forwarded
+//! - `disabled_categories`: violations of these categories are only recorded
+//!
+//! Flags are set by a config push (`control.flags`, published when the
+//! configuration loads) or through the admin endpoint: `GET` returns the
+//! flags, `PUT`/`POST` with a JSON body replaces them, and
+//! `DELETE ?agent=<id>` clears an agent's penalty record.
+//!
+//! With `admin_api`, the filter also serves a reserved path prefix,
+//! `/.well-known/ai-guard/`, whatever the upstream routes:
+//! - `control`: the flags endpoint above
+//! - `status` (`GET`): configuration digest, pattern pack versions and flags
+//! - `patterns` (`GET`): hit counts per blocked pattern, across workers
+//!
+//! Every admin request requires `authorization: Bearer <admin_token>`.
+
+use crate::config::{pattern_digest, ConfigSnapshot};
+use crate::crypto::{constant_time_eq, sha256};
+use crate::governance::penalties;
+use crate::governance::prompt_injection::multilingual_patterns;
+use crate::governance::ThreatFeed;
+use crate::protocols::grpc::percent_decode;
+use crate::shared::{SharedError, SharedStore};
+use crate::telemetry::pattern_stats::PatternHits;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// Shared data key holding the control flags
+pub const CONTROL_KEY: &str = "ai_guard.control";
+
+/// Largest admin request body accepted, in bytes
+pub const MAX_ADMIN_BODY: usize = 16 * 1024;
+
+/// Shortest admin token accepted
+const MIN_TOKEN_LEN: usize = 16;
+
+/// Path prefix reserved for the admin API
+pub const ADMIN_API_PREFIX: &str = "/.well-known/ai-guard/";
+
+/// Runtime control flags shared by all workers
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ControlFlags {
+    /// Kill switch: skip inspection and forward every request
+    pub disabled: bool,
+    /// Record violations without blocking
+    pub monitor_mode: bool,
+    /// Categories whose violations are recorded without blocking
+    pub disabled_categories: Vec<String>,
+}
+
+impl ControlFlags {
+    /// Current flags (defaults when unset or unreadable)
+    pub fn load(store: &impl SharedStore) -> Self {
+        store
+            .get(CONTROL_KEY)
+            .0
+            .and_then(|value| serde_json::from_slice(&value).ok())
+            .unwrap_or_default()
+    }
+
+    /// Publish the flags to every worker
+    pub fn publish(&self, store: &impl SharedStore) -> Result<(), SharedError> {
+        let value = serde_json::to_vec(self).unwrap_or_default();
+        store.set(CONTROL_KEY, &value, None)
+    }
+
+    /// Whether a violation of this category blocks the request
+    pub fn enforces(&self, category: &str) -> bool {
+        !self.disabled
+            && !self.monitor_mode
+            && !self.disabled_categories.iter().any(|c| c == category)
+    }
+
+    /// Validate the flags, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        if self.disabled_categories.iter().any(|c| c.is_empty()) {
+            diagnostics.push("control.flags.disabled_categories: empty category".to_string());
+        }
+        diagnostics
+    }
+}
+
+/// Runtime control configuration
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ControlConfig {
+    /// Path of the admin endpoint (no endpoint when absent)
+    pub admin_path: Option<String>,
+    /// Bearer token required by the admin endpoint and API
+    pub admin_token: String,
+    /// Serve the admin API under `/.well-known/ai-guard/`
+    pub admin_api: bool,
+    /// Flags published when the configuration is loaded
+    pub flags: Option<ControlFlags>,
+}
+
+impl ControlConfig {
+    /// Validate the configuration, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        if let Some(path) = &self.admin_path {
+            if !path.starts_with('/') {
+                diagnostics.push("control.admin_path: must start with '/'".to_string());
+            }
+        }
+        let served = self.admin_path.is_some() || self.admin_api;
+        if served && self.admin_token.len() < MIN_TOKEN_LEN {
+            diagnostics.push(format!(
+                "control.admin_token: must be at least {} characters",
+                MIN_TOKEN_LEN
+            ));
+        }
+        if let Some(flags) = &self.flags {
+            diagnostics.extend(flags.validate());
+        }
+        diagnostics
+    }
+
+    /// Whether a request path addresses the admin endpoint or API (query ignored)
+    pub fn is_admin_path(&self, path: &str) -> bool {
+        self.admin_route(path).is_some()
+    }
+
+    /// Admin resource a request path addresses, if any (query ignored)
+    pub fn admin_route(&self, path: &str) -> Option<AdminRoute> {
+        let path = path.split('?').next().unwrap_or_default();
+        if self.admin_path.as_deref() == Some(path) {
+            return Some(AdminRoute::Control);
+        }
+        if !self.admin_api {
+            return None;
+        }
+        let resource = path.strip_prefix(ADMIN_API_PREFIX)?;
+        Some(match resource {
+            "control" => AdminRoute::Control,
+            "status" => AdminRoute::Status,
+            "patterns" => AdminRoute::Patterns,
+            _ => AdminRoute::NotFound,
+        })
+    }
+
+    /// Whether an `authorization` header carries the admin token
+    pub fn authorize(&self, authorization: Option<&str>) -> bool {
+        let token = match authorization.and_then(|h| h.strip_prefix("Bearer ")) {
+            Some(token) => token.trim(),
+            None => return false,
+        };
+        // Hash both sides so the comparison does not leak the token length
+        !self.admin_token.is_empty()
+            && constant_time_eq(&sha256(token.as_bytes()), &sha256(self.admin_token.as_bytes()))
+    }
+}
+
+/// Resource addressed by an admin request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdminRoute {
+    /// Runtime control flags (and agent penalty resets)
+    Control,
+    /// Configuration and pattern pack versions
+    Status,
+    /// Per-pattern hit counts
+    Patterns,
+    /// Unknown path under the reserved prefix
+    NotFound,
+}
+
+/// Outcome of an admin request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdminResponse {
+    /// Current flags (after an update)
+    Flags(ControlFlags),
+    /// A read-only report
+    Report(Value),
+    /// An agent's penalty record was cleared (`found` is false if it had none)
+    AgentReset {
+        /// Agent id
+        agent: String,
+        /// Whether the agent had a record
+        found: bool,
+    },
+    /// Rejected request: status and message
+    Error(u32, String),
+}
+
+impl AdminResponse {
+    /// HTTP status and JSON body
+    pub fn to_http(&self) -> (u32, String) {
+        match self {
+            AdminResponse::Flags(flags) => (200, serde_json::to_string(flags).unwrap_or_default()),
+            AdminResponse::Report(report) => (200, report.to_string()),
+            AdminResponse::AgentReset { agent, found } => {
+                (200, serde_json::json!({ "agent": agent, "reset": found }).to_string())
+            }
+            AdminResponse::Error(status, message) => {
+                (*status, serde_json::json!({ "error": message }).to_string())
+            }
+        }
+    }
+}
+
+/// Apply an authorized `PUT`/`POST` body to the shared flags
+pub fn update_flags(store: &impl SharedStore, body: &[u8]) -> AdminResponse {
+    let flags: ControlFlags = match serde_json::from_slice(body) {
+        Ok(flags) => flags,
+        Err(e) => return AdminResponse::Error(400, format!("Invalid control flags: {}", e)),
+    };
+    let diagnostics = flags.validate();
+    if !diagnostics.is_empty() {
+        return AdminResponse::Error(400, diagnostics.join("; "));
+    }
+    match flags.publish(store) {
+        Ok(()) => AdminResponse::Flags(flags),
+        Err(e) => AdminResponse::Error(503, e.to_string()),
+    }
+}
+
+/// Clear an agent's penalty record (`DELETE <admin_path>?agent=<id>`)
+pub fn reset_agent(store: &impl SharedStore, path: &str) -> AdminResponse {
+    let query = path.split_once('?').map(|(_, q)| q).unwrap_or_default();
+    let agent = query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("agent="))
+        .map(percent_decode);
+    let agent = match agent {
+        Some(agent) if penalties::valid_agent_id(&agent) => agent,
+        _ => return AdminResponse::Error(400, "Missing or invalid agent".to_string()),
+    };
+    match penalties::reset(store, &agent) {
+        Ok(found) => AdminResponse::AgentReset { agent, found },
+        Err(e) => AdminResponse::Error(503, e.to_string()),
+    }
+}
+
+/// Versions of the configuration and of each pattern pack in force
+pub fn status_report(
+    config: &ConfigSnapshot,
+    flags: &ControlFlags,
+    feed: Option<&ThreatFeed>,
+) -> Value {
+    let pack = |patterns: &[String]| {
+        json!({ "patterns": patterns.len(), "version": pattern_digest(patterns) })
+    };
+    let mut packs = serde_json::Map::new();
+    packs.insert(
+        "blocked_patterns".to_string(),
+        json!({
+            "patterns": config.blocked_patterns.len(),
+            "version": config.pattern_version(),
+        }),
+    );
+    if config.multilingual_patterns {
+        packs.insert("multilingual".to_string(), pack(&multilingual_patterns()));
+    }
+    if let Some(languages) = &config.language_packs {
+        for (code, patterns) in &languages.packs {
+            packs.insert(format!("language_packs.{}", code), pack(patterns));
+        }
+    }
+    if let Some(experiment) = &config.pattern_experiment {
+        let name = format!("pattern_experiment.{}", experiment.name);
+        packs.insert(name, pack(&experiment.candidate_patterns));
+    }
+    if let Some(feed) = feed {
+        packs.insert(
+            "threat_feed".to_string(),
+            json!({ "patterns": feed.len(), "version": feed.version }),
+        );
+    }
+    json!({
+        "config_digest": config.digest(),
+        "pattern_packs": packs,
+        "flags": flags,
+    })
+}
+
+/// Hit counts of the configured patterns (zero for those that never fired),
+/// plus any other pattern with hits on record, most hits first
+pub fn pattern_report(hits: &PatternHits, configured: &[String]) -> Value {
+    let mut counts = hits.clone();
+    for pattern in configured {
+        counts.entry(pattern.clone()).or_insert(0);
+    }
+    let mut counts: Vec<(String, u64)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    let patterns: Vec<Value> = counts
+        .iter()
+        .map(|(pattern, hits)| json!({ "pattern": pattern, "hits": hits }))
+        .collect();
+    json!({
+        "patterns": patterns,
+        "total_hits": counts.iter().map(|(_, n)| n).sum::<u64>(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::FilterConfig;
+    use crate::governance::PenaltyConfig;
+    use crate::shared::MemorySharedStore;
+
+    fn config() -> ControlConfig {
+        ControlConfig {
+            admin_path: Some("/ai-guard/control".to_string()),
+            admin_token: "0123456789abcdef".to_string(),
+            admin_api: true,
+            flags: None,
+        }
+    }
+
+    #[test]
+    fn test_enforces() {
+        assert!(ControlFlags::default().enforces("jailbreak"));
+        let flags = ControlFlags {
+            disabled_categories: vec!["pii".to_string()],
+            ..Default::default()
+        };
+        assert!(!flags.enforces("pii"));
+        assert!(flags.enforces("jailbreak"));
+        let monitor = ControlFlags { monitor_mode: true, ..Default::default() };
+        assert!(!monitor.enforces("jailbreak"));
+        let off = ControlFlags { disabled: true, ..Default::default() };
+        assert!(!off.enforces("jailbreak"));
+    }
+
+    #[test]
+    fn test_update_and_load() {
+        let store = MemorySharedStore::new();
+        assert_eq!(ControlFlags::load(&store), ControlFlags::default());
+
+        let response = update_flags(&store, br#"{"monitor_mode": true}"#);
+        let expected = ControlFlags { monitor_mode: true, ..Default::default() };
+        assert_eq!(response, AdminResponse::Flags(expected.clone()));
+        assert_eq!(ControlFlags::load(&store), expected);
+
+        let (status, _) = update_flags(&store, br#"{"monitor": true}"#).to_http();
+        assert_eq!(status, 400);
+        let (status, body) = update_flags(&store, br#"{"disabled_categories": [""]}"#).to_http();
+        assert_eq!(status, 400);
+        assert!(body.contains("empty category"));
+        // Rejected updates leave the flags alone
+        assert_eq!(ControlFlags::load(&store), expected);
+    }
+
+    #[test]
+    fn test_admin_endpoint() {
+        let config = config();
+        assert!(config.is_admin_path("/ai-guard/control"));
+        assert!(config.is_admin_path("/ai-guard/control?pretty"));
+        assert!(!config.is_admin_path("/ai-guard/control/x"));
+        assert!(!ControlConfig::default().is_admin_path("/ai-guard/control"));
+
+        let route = |path| config.admin_route(path);
+        assert_eq!(route("/.well-known/ai-guard/status?x=1"), Some(AdminRoute::Status));
+        assert_eq!(route("/.well-known/ai-guard/control"), Some(AdminRoute::Control));
+        assert_eq!(route("/.well-known/ai-guard/config"), Some(AdminRoute::NotFound));
+        assert_eq!(route("/.well-known/other"), None);
+        let closed = ControlConfig { admin_api: false, ..config.clone() };
+        assert!(!closed.is_admin_path("/.well-known/ai-guard/status"));
+
+        assert!(config.authorize(Some("Bearer 0123456789abcdef")));
+        assert!(!config.authorize(Some("Bearer 0123456789abcdeF")));
+        assert!(!config.authorize(Some("0123456789abcdef")));
+        assert!(!config.authorize(None));
+    }
+
+    #[test]
+    fn test_reset_agent() {
+        let store = MemorySharedStore::new();
+        let penalty = PenaltyConfig::default();
+        penalties::record_violation(&store, &penalty, "team a/bot", 0).unwrap();
+
+        let response = reset_agent(&store, "/ai-guard/control?agent=team%20a%2Fbot");
+        let expected = AdminResponse::AgentReset { agent: "team a/bot".to_string(), found: true };
+        assert_eq!(response, expected);
+        assert_eq!(penalties::lookup(&store, &penalty, "team a/bot", 0).violations, 0);
+        let path = "/ai-guard/control?agent=team%20a%2Fbot";
+        let (status, body) = reset_agent(&store, path).to_http();
+        assert_eq!((status, body.as_str()), (200, r#"{"agent":"team a/bot","reset":false}"#));
+        assert_eq!(reset_agent(&store, "/ai-guard/control").to_http().0, 400);
+    }
+
+    #[test]
+    fn test_reports() {
+        let json = r#"{"blocked_patterns": ["jailbreak", "ignore previous"],
+            "language_packs": {"packs": {"de": ["ignoriere"]}}}"#;
+        let config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        let config = ConfigSnapshot::new(config).with_digest("ab12".to_string());
+        let status = status_report(&config, &ControlFlags::default(), None);
+        assert_eq!(status["config_digest"], "ab12");
+        assert_eq!(status["pattern_packs"]["blocked_patterns"]["patterns"], 2);
+        let german = &status["pattern_packs"]["language_packs.de"];
+        assert_eq!(german["version"], pattern_digest(&["ignoriere".to_string()]).as_str());
+        assert_eq!(status["flags"]["monitor_mode"], false);
+
+        let hits = PatternHits::from([("jailbreak".to_string(), 3), ("retired".to_string(), 1)]);
+        let report = pattern_report(&hits, &config.blocked_patterns);
+        assert_eq!(report["total_hits"], 4);
+        assert_eq!(
+            report["patterns"],
+            json!([
+                {"pattern": "jailbreak", "hits": 3},
+                {"pattern": "retired", "hits": 1},
+                {"pattern": "ignore previous", "hits": 0},
+            ])
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(config().validate().is_empty());
+        let config = ControlConfig {
+            admin_path: Some("control".to_string()),
+            admin_token: "short".to_string(),
+            admin_api: false,
+            flags: None,
+        };
+        assert_eq!(
+            config.validate(),
+            vec![
+                "control.admin_path: must start with '/'".to_string(),
+                "control.admin_token: must be at least 16 characters".to_string(),
+            ]
+        );
+    }
+}
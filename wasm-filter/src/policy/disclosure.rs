@@ -0,0 +1,88 @@
+//! Block Response Disclosure
+//!
+//! The reason a request was blocked names the matched pattern, and often
+//! quotes the request itself (a header name, a tool argument, a URL). Put
+//! in a 403 body, it tells an attacker which signature fired and reflects
+//! their input back to the client. How much the body says is configured:
+//!
+//! ```json
+//! {"block_response_verbosity": "category"}
+//! ```
+//!
+//! - `opaque` (default): a fixed reason; the request ID ties the response
+//!   to the audit event, which always carries the full reason
+//! - `category`: the violation category (`prompt_injection`, `pii`, ...)
+//! - `full`: the reason as audited
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// Reason given for every block at `opaque` verbosity
+pub const OPAQUE_REASON: &str = "policy_violation";
+
+/// How much a block response discloses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlockVerbosity {
+    /// A fixed reason and the request ID
+    #[default]
+    Opaque,
+    /// The violation category
+    Category,
+    /// The full reason, including the matched pattern
+    Full,
+}
+
+impl BlockVerbosity {
+    /// Reason shown to the client for a block
+    pub fn reason<'a>(&self, category: Option<&'a str>, reason: &'a str) -> &'a str {
+        match self {
+            BlockVerbosity::Opaque => OPAQUE_REASON,
+            BlockVerbosity::Category => category.unwrap_or(OPAQUE_REASON),
+            BlockVerbosity::Full => reason,
+        }
+    }
+
+    /// JSON body of a 403 block response
+    pub fn block_body(
+        &self,
+        category: Option<&str>,
+        reason: &str,
+        request_id: Option<&str>,
+    ) -> Value {
+        json!({
+            "error": "Request Blocked by AI-Guard",
+            "reason": self.reason(category, reason),
+            "status": 403,
+            "request_id": request_id,
+            "headers": {
+                "x-ai-guard-blocked": "true",
+                "x-ai-guard-reason": "policy-violation"
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reason() {
+        let reason = "Pattern 'jailbreak' detected";
+        let category = Some("prompt_injection");
+        assert_eq!(BlockVerbosity::default().reason(category, reason), "policy_violation");
+        assert_eq!(BlockVerbosity::Category.reason(category, reason), "prompt_injection");
+        assert_eq!(BlockVerbosity::Category.reason(None, reason), "policy_violation");
+        assert_eq!(BlockVerbosity::Full.reason(category, reason), reason);
+    }
+
+    #[test]
+    fn test_block_body() {
+        let body = BlockVerbosity::Category.block_body(Some("pii"), "SSN found", Some("req-1"));
+        assert_eq!(body["reason"], "pii");
+        assert_eq!(body["status"], 403);
+        assert_eq!(body["request_id"], "req-1");
+        assert!(BlockVerbosity::Opaque.block_body(None, "x", None)["request_id"].is_null());
+    }
+}
@@ -0,0 +1,37 @@
+//! Policy module for AI-Guard
+//!
+//! This module provides:
+//! - Request classification (chat, embedding, tool call, admin, A2A)
+//! - Tenant resolution and per-tenant configuration
+//! - Route-scoped configuration by path prefix and HTTP method
+//! - Identity tiers varying limits by caller
+//! - Source network allow/deny lists and per-network enforcement
+//! - Runtime controls (kill switch, monitor mode) via shared data
+//! - Signed, expiring bypass tokens for trusted internal traffic
+//! - How much block responses disclose about the violation
+//! - Policy rules: allow/deny conditions over request attributes
+//! - External policy decision point callouts
+//! - Percentage-based rollout of subsystems
+
+pub mod bypass;
+pub mod classify;
+pub mod control;
+pub mod disclosure;
+pub mod network;
+pub mod pdp;
+pub mod routes;
+pub mod rollout;
+pub mod rules;
+pub mod tenant;
+pub mod tiers;
+
+pub use bypass::{admit_bypass, BypassConfig, BypassRejection, BYPASS_HEADER};
+pub use classify::{ClassificationConfig, RequestClass};
+pub use control::{AdminResponse, ControlConfig, ControlFlags};
+pub use disclosure::BlockVerbosity;
+pub use network::{Cidr, NetworkMode, NetworkPolicy, NetworkSpec};
+pub use pdp::{DecisionInput, MatchSummary, PdpConfig, PdpDecision};
+pub use routes::{RoutePolicies, RouteSpec};
+pub use rules::{PolicyRule, RequestAttributes, RuleAction};
+pub use tenant::{TenancyConfig, TenantPolicies, TenantResolver, TenantSpec};
+pub use tiers::{TierConfig, TierPolicy};
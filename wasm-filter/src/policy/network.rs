@@ -0,0 +1,271 @@
+//! Source Network Policy
+//!
+//! Where a request comes from says a lot about how much to trust it: traffic
+//! from internal ranges can be watched in monitor mode while a new pattern
+//! set beds in, while internet-facing ranges are blocked strictly, and some
+//! ranges should not reach the AI APIs at all.
+//!
+//! The client address is the connection's source address, or, behind
+//! `xff_trusted_hops` trusted proxies, the address that many entries from
+//! the right of `x-forwarded-for` (each trusted proxy appends the address it
+//! saw, so entries further left are client-controlled). `deny` is checked
+//! before `allow`; an empty `allow` admits every address not denied. When
+//! `allow` is set, requests without a usable client address are rejected.
+//!
+//! The first entry of `networks` containing the client adjusts enforcement:
+//! `mode: monitor` records violations without blocking, `mode: enforce`
+//! blocks even when runtime controls are in monitor mode (the kill switch
+//! still wins), and `disabled_categories` relaxes single categories.
+
+use super::control::ControlFlags;
+use serde::Deserialize;
+use std::net::{IpAddr, SocketAddr};
+
+/// Header listing the proxies a request went through
+pub const XFF_HEADER: &str = "x-forwarded-for";
+
+/// An address range in CIDR notation (a bare address is a single host)
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Cidr {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl Cidr {
+    /// Whether an address lies in the range
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        match (self.network, addr.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(addr)) => {
+                prefix_eq(&network.octets(), &addr.octets(), self.prefix_len)
+            }
+            (IpAddr::V6(network), IpAddr::V6(addr)) => {
+                prefix_eq(&network.octets(), &addr.octets(), self.prefix_len)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl TryFrom<String> for Cidr {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, String> {
+        let (addr, prefix) = match text.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (text.as_str(), None),
+        };
+        let network = addr
+            .trim()
+            .parse::<IpAddr>()
+            .map_err(|_| format!("invalid address in '{}'", text))?
+            .to_canonical();
+        let max = if network.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix {
+            Some(prefix) => match prefix.trim().parse::<u8>() {
+                Ok(len) if len <= max => len,
+                _ => return Err(format!("invalid prefix length in '{}'", text)),
+            },
+            None => max,
+        };
+        Ok(Self { network, prefix_len })
+    }
+}
+
+/// Whether the first `bits` bits of two addresses agree
+fn prefix_eq(a: &[u8], b: &[u8], bits: u8) -> bool {
+    let full = (bits / 8) as usize;
+    if a[..full] != b[..full] {
+        return false;
+    }
+    let rest = bits % 8;
+    rest == 0 || (a[full] ^ b[full]) >> (8 - rest) == 0
+}
+
+/// How violations from a network are enforced
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkMode {
+    /// Record violations without blocking
+    Monitor,
+    /// Block violations even in runtime monitor mode
+    Enforce,
+}
+
+/// A named range with its own enforcement
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NetworkSpec {
+    /// Network name (logs)
+    pub name: String,
+    /// Ranges of the network
+    pub cidrs: Vec<Cidr>,
+    /// Enforcement mode (runtime controls apply when absent)
+    #[serde(default)]
+    pub mode: Option<NetworkMode>,
+    /// Categories only recorded for this network
+    #[serde(default)]
+    pub disabled_categories: Vec<String>,
+}
+
+impl NetworkSpec {
+    /// Adjust the request's control flags for this network
+    pub fn apply(&self, flags: &mut ControlFlags) {
+        match self.mode {
+            Some(NetworkMode::Monitor) => flags.monitor_mode = true,
+            Some(NetworkMode::Enforce) => flags.monitor_mode = false,
+            None => {}
+        }
+        flags.disabled_categories.extend(self.disabled_categories.iter().cloned());
+    }
+}
+
+/// Source network policy configuration
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkPolicy {
+    /// Trusted proxies appending to `x-forwarded-for` (0 = use the source address)
+    pub xff_trusted_hops: usize,
+    /// Ranges admitted (any when empty)
+    pub allow: Vec<Cidr>,
+    /// Ranges rejected
+    pub deny: Vec<Cidr>,
+    /// Ranges with their own enforcement, first match wins
+    pub networks: Vec<NetworkSpec>,
+}
+
+impl NetworkPolicy {
+    /// Validate the policy, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        for (i, network) in self.networks.iter().enumerate() {
+            let field = |name: &str| format!("network_policy.networks[{}].{}", i, name);
+            if network.name.is_empty() {
+                diagnostics.push(format!("{}: must not be empty", field("name")));
+            }
+            if network.cidrs.is_empty() {
+                diagnostics.push(format!("{}: must not be empty", field("cidrs")));
+            }
+        }
+        diagnostics
+    }
+
+    /// Client address from `x-forwarded-for` and the connection's source address
+    pub fn client_address(&self, xff: Option<&str>, source: Option<&str>) -> Option<IpAddr> {
+        if self.xff_trusted_hops == 0 {
+            return source.and_then(parse_address);
+        }
+        let hops: Vec<&str> = xff?.split(',').map(str::trim).collect();
+        let index = hops.len().checked_sub(self.xff_trusted_hops)?;
+        parse_address(hops[index])
+    }
+
+    /// Whether a client may send requests (None = address unknown)
+    pub fn admits(&self, client: Option<IpAddr>) -> bool {
+        match client {
+            Some(addr) => {
+                !self.deny.iter().any(|c| c.contains(addr))
+                    && (self.allow.is_empty() || self.allow.iter().any(|c| c.contains(addr)))
+            }
+            None => self.allow.is_empty(),
+        }
+    }
+
+    /// Network containing a client
+    pub fn network(&self, client: IpAddr) -> Option<&NetworkSpec> {
+        self.networks.iter().find(|n| n.cidrs.iter().any(|c| c.contains(client)))
+    }
+}
+
+/// Parse `addr`, `addr:port` or `[v6]:port`
+fn parse_address(text: &str) -> Option<IpAddr> {
+    let text = text.trim();
+    text.parse::<IpAddr>()
+        .or_else(|_| text.parse::<SocketAddr>().map(|s| s.ip()))
+        .ok()
+        .map(|addr| addr.to_canonical())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cidr(text: &str) -> Cidr {
+        Cidr::try_from(text.to_string()).unwrap()
+    }
+
+    fn ip(text: &str) -> IpAddr {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn test_cidr_contains() {
+        assert!(cidr("10.0.0.0/8").contains(ip("10.200.3.4")));
+        assert!(!cidr("10.0.0.0/8").contains(ip("11.0.0.1")));
+        assert!(cidr("192.168.4.0/22").contains(ip("192.168.7.255")));
+        assert!(!cidr("192.168.4.0/22").contains(ip("192.168.8.0")));
+        assert!(cidr("203.0.113.7").contains(ip("203.0.113.7")));
+        assert!(cidr("0.0.0.0/0").contains(ip("8.8.8.8")));
+        assert!(cidr("fd00::/8").contains(ip("fd12:3456::1")));
+        assert!(!cidr("fd00::/8").contains(ip("10.0.0.1")));
+        // IPv4-mapped IPv6 addresses match IPv4 ranges
+        assert!(cidr("10.0.0.0/8").contains(ip("::ffff:10.0.0.1")));
+        assert!(Cidr::try_from("10.0.0.0/33".to_string()).is_err());
+        assert!(Cidr::try_from("10.0.0/8".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_client_address() {
+        let direct = NetworkPolicy::default();
+        let client = direct.client_address(Some("1.1.1.1"), Some("10.0.0.5:443"));
+        assert_eq!(client, Some(ip("10.0.0.5")));
+        assert_eq!(direct.client_address(None, Some("[::1]:80")), Some(ip("::1")));
+
+        let behind_lb = NetworkPolicy { xff_trusted_hops: 1, ..Default::default() };
+        // The client forged the leftmost entry; the load balancer appended the real one
+        let xff = "6.6.6.6, 198.51.100.7";
+        assert_eq!(behind_lb.client_address(Some(xff), None), Some(ip("198.51.100.7")));
+        let two_hops = NetworkPolicy { xff_trusted_hops: 2, ..Default::default() };
+        assert_eq!(two_hops.client_address(Some(xff), None), Some(ip("6.6.6.6")));
+        let three_hops = NetworkPolicy { xff_trusted_hops: 3, ..Default::default() };
+        assert_eq!(three_hops.client_address(Some(xff), None), None);
+        assert_eq!(behind_lb.client_address(None, Some("10.0.0.5:443")), None);
+    }
+
+    #[test]
+    fn test_admits() {
+        let policy: NetworkPolicy = serde_json::from_str(
+            r#"{"allow": ["10.0.0.0/8", "198.51.100.0/24"], "deny": ["10.66.0.0/16"]}"#,
+        )
+        .unwrap();
+        assert!(policy.admits(Some(ip("10.1.2.3"))));
+        assert!(!policy.admits(Some(ip("10.66.1.1"))));
+        assert!(!policy.admits(Some(ip("8.8.8.8"))));
+        assert!(!policy.admits(None));
+        assert!(NetworkPolicy::default().admits(None));
+    }
+
+    #[test]
+    fn test_network_modes() {
+        let policy: NetworkPolicy = serde_json::from_str(
+            r#"{"networks": [
+                {"name": "internal", "cidrs": ["10.0.0.0/8"], "mode": "monitor"},
+                {"name": "internet", "cidrs": ["0.0.0.0/0", "::/0"], "mode": "enforce",
+                 "disabled_categories": ["pii"]}
+            ]}"#,
+        )
+        .unwrap();
+        assert!(policy.validate().is_empty());
+
+        let mut flags = ControlFlags::default();
+        policy.network(ip("10.0.0.1")).unwrap().apply(&mut flags);
+        assert!(!flags.enforces("jailbreak"));
+
+        let mut flags = ControlFlags { monitor_mode: true, ..Default::default() };
+        let internet = policy.network(ip("8.8.8.8")).unwrap();
+        assert_eq!(internet.name, "internet");
+        internet.apply(&mut flags);
+        assert!(flags.enforces("jailbreak"));
+        assert!(!flags.enforces("pii"));
+    }
+}
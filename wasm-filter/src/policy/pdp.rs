@@ -0,0 +1,285 @@
+//! External Policy Decision Point
+//!
+//! Organisations with a central policy engine (OPA and the like) want the
+//! final say over AI traffic to live there. When configured, each request
+//! the local checks let through is described in a compact decision request
+//! and sent to the PDP before it is forwarded:
+//!
+//! ```text
+//! POST /v1/data/ai_guard/decision
+//! {"input": {"protocol": "mcp", "method": "tools/call", "tool": "shell",
+//!            "agent_id": "planner", "local_action": "allowed", ...}}
+//! ```
+//!
+//! The PDP answers `{"result": {"allow": bool, "reason": "...",
+//! "obligations": {"headers": {...}}}}` (a bare `{"result": true}` also
+//! allows). A denial blocks the request; obligations are applied to an
+//! allowed one. When the PDP does not answer within the timeout, answers
+//! with an error status or cannot be understood, the local decision stands.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// PDP callout configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PdpConfig {
+    /// Envoy cluster of the PDP
+    pub cluster: String,
+    /// Decision path
+    #[serde(default = "default_pdp_path")]
+    pub path: String,
+    /// `:authority` sent to the PDP (defaults to the cluster name)
+    #[serde(default)]
+    pub authority: Option<String>,
+    /// `authorization` header value sent with each callout
+    #[serde(default)]
+    pub authorization: Option<String>,
+    /// Time to wait for a decision before falling back, in milliseconds
+    #[serde(default = "default_pdp_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+fn default_pdp_path() -> String {
+    "/v1/data/ai_guard/decision".to_string()
+}
+
+fn default_pdp_timeout_ms() -> u64 {
+    200
+}
+
+impl PdpConfig {
+    /// Validate the configuration, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        if self.cluster.is_empty() {
+            diagnostics.push("pdp.cluster: must not be empty".to_string());
+        }
+        if !self.path.starts_with('/') {
+            diagnostics.push("pdp.path: must start with '/'".to_string());
+        }
+        if self.timeout_ms == 0 {
+            diagnostics.push("pdp.timeout_ms: must be greater than 0".to_string());
+        }
+        diagnostics
+    }
+
+    /// Decision from the PDP's answer (`status` None = no answer)
+    pub fn decide(&self, status: Option<u16>, body: Option<&[u8]>) -> PdpDecision {
+        let answer = match status {
+            Some(status) if (200..300).contains(&status) => body
+                .and_then(|b| serde_json::from_slice::<PdpAnswer>(b).ok())
+                .ok_or_else(|| "unreadable answer".to_string()),
+            Some(status) => Err(format!("status {}", status)),
+            None => Err("no answer".to_string()),
+        };
+        match answer.map(|a| a.result) {
+            Ok(Some(PdpResult::Allow(true))) => PdpDecision::Allow(Obligations::default()),
+            Ok(Some(PdpResult::Allow(false))) => {
+                PdpDecision::Deny("Denied by policy decision point".to_string())
+            }
+            Ok(Some(PdpResult::Decision { allow: true, obligations, .. })) => {
+                PdpDecision::Allow(obligations)
+            }
+            Ok(Some(PdpResult::Decision { allow: false, reason, .. })) => PdpDecision::Deny(
+                reason.unwrap_or_else(|| "Denied by policy decision point".to_string()),
+            ),
+            // OPA omits `result` when the policy defines no decision
+            Ok(None) => PdpDecision::Unavailable("undefined decision".to_string()),
+            Err(problem) => PdpDecision::Unavailable(problem),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PdpAnswer {
+    #[serde(default)]
+    result: Option<PdpResult>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum PdpResult {
+    Allow(bool),
+    Decision {
+        allow: bool,
+        #[serde(default)]
+        reason: Option<String>,
+        #[serde(default)]
+        obligations: Obligations,
+    },
+}
+
+/// Actions the PDP attaches to an allowed request
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct Obligations {
+    /// Headers to set on the upstream request
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+impl Obligations {
+    /// Headers that may be set (pseudo-headers and malformed names dropped)
+    pub fn request_headers(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.headers
+            .iter()
+            .filter(|(name, _)| {
+                !name.is_empty()
+                    && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+            })
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+}
+
+/// Outcome of a PDP callout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PdpDecision {
+    /// Forward the request after applying the obligations
+    Allow(Obligations),
+    /// Block the request (reason)
+    Deny(String),
+    /// No usable decision: the local decision stands (problem)
+    Unavailable(String),
+}
+
+impl PdpDecision {
+    /// Metric and log label
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PdpDecision::Allow(_) => "allow",
+            PdpDecision::Deny(_) => "deny",
+            PdpDecision::Unavailable(_) => "fallback",
+        }
+    }
+}
+
+/// Compact description of a request for the PDP
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DecisionInput {
+    /// `mcp` or `http`
+    pub protocol: &'static str,
+    /// HTTP method
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http_method: Option<String>,
+    /// Request path
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// JSON-RPC method
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    /// Tool of a `tools/call`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool: Option<String>,
+    /// Calling agent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agent_id: Option<String>,
+    /// Resolved tenant
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    /// Request class
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub class: Option<String>,
+    /// Correlated session
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// Requested model
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Estimated prompt tokens
+    pub tokens: u64,
+    /// Local verdict so far (`allowed`, `monitored`, `quarantined`)
+    pub local_action: String,
+    /// Local pattern match, when any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub matched: Option<MatchSummary>,
+}
+
+/// Local pattern match reported to the PDP
+#[derive(Debug, Clone, Serialize)]
+pub struct MatchSummary {
+    pub pattern: String,
+    pub category: String,
+    pub severity: String,
+}
+
+impl DecisionInput {
+    /// Callout body
+    pub fn to_body(&self) -> String {
+        serde_json::json!({ "input": self }).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> PdpConfig {
+        serde_json::from_str(r#"{"cluster": "opa"}"#).unwrap()
+    }
+
+    #[test]
+    fn test_decide() {
+        let config = config();
+        assert_eq!(
+            config.decide(Some(200), Some(br#"{"result": true}"#)),
+            PdpDecision::Allow(Obligations::default())
+        );
+        let deny = br#"{"result": {"allow": false, "reason": "tier too low"}}"#;
+        assert_eq!(
+            config.decide(Some(200), Some(deny)),
+            PdpDecision::Deny("tier too low".to_string())
+        );
+        let allow = br#"{"result": {"allow": true,
+            "obligations": {"headers": {"x-pdp-tier": "gold"}}}}"#;
+        match config.decide(Some(200), Some(allow)) {
+            PdpDecision::Allow(obligations) => {
+                let headers: Vec<_> = obligations.request_headers().collect();
+                assert_eq!(headers, vec![("x-pdp-tier", "gold")]);
+            }
+            other => panic!("expected allow, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_fallback() {
+        let config = config();
+        assert_eq!(config.decide(None, None).as_str(), "fallback");
+        assert_eq!(
+            config.decide(Some(500), Some(br#"{"result": false}"#)),
+            PdpDecision::Unavailable("status 500".to_string())
+        );
+        assert_eq!(
+            config.decide(Some(200), Some(b"{}")),
+            PdpDecision::Unavailable("undefined decision".to_string())
+        );
+        assert_eq!(config.decide(Some(200), Some(b"<html>")).as_str(), "fallback");
+    }
+
+    #[test]
+    fn test_obligation_headers_filtered() {
+        let mut obligations = Obligations::default();
+        obligations.headers.insert(":path".to_string(), "/admin".to_string());
+        obligations.headers.insert("bad header".to_string(), "x".to_string());
+        obligations.headers.insert("x-ok".to_string(), "1".to_string());
+        assert_eq!(obligations.request_headers().collect::<Vec<_>>(), vec![("x-ok", "1")]);
+    }
+
+    #[test]
+    fn test_input_body() {
+        let input = DecisionInput {
+            protocol: "mcp",
+            method: Some("tools/call".to_string()),
+            tool: Some("shell".to_string()),
+            class: Some("tool_call".to_string()),
+            tokens: 12,
+            local_action: "allowed".to_string(),
+            ..Default::default()
+        };
+        let body: serde_json::Value = serde_json::from_str(&input.to_body()).unwrap();
+        assert_eq!(body["input"]["tool"], "shell");
+        assert_eq!(body["input"]["local_action"], "allowed");
+        assert_eq!(body["input"]["class"], "tool_call");
+        assert!(body["input"].get("agent_id").is_none());
+        assert!(config().validate().is_empty());
+    }
+}
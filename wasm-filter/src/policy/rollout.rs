@@ -0,0 +1,162 @@
+//! Percentage-Based Feature Rollout
+//!
+//! A new capability (response scanning, a language pack) is switched on
+//! for a slice of traffic first:
+//!
+//! ```json
+//! {"rollout_percent": {"response_scanning": 5, "language_packs.de": 25}}
+//! ```
+//!
+//! A request outside a subsystem's rollout is handled as if the subsystem
+//! were not configured. Membership is a stable hash of the session ID (or
+//! the request ID when there is no session), salted with the subsystem
+//! name: a session stays in or out for its whole life, and the slices of
+//! different subsystems are independent. Subsystems not listed are fully
+//! rolled out.
+
+use crate::config::FilterConfig;
+use crate::governance::verdict_cache::xxh64;
+use std::collections::BTreeMap;
+
+/// Subsystems whose rollout can be limited
+pub const SUBSYSTEMS: &[&str] = &[
+    "binary_policy",
+    "entropy_detection",
+    "header_scrubbing",
+    "language_packs",
+    "markup_scanning",
+    "mcp_result_scanning",
+    "model_policy",
+    "notification_limits",
+    "pii_vault",
+    "prompt_templates",
+    "quarantine",
+    "repetition_detection",
+    "replay_protection",
+    "response_pii",
+    "response_scanning",
+    "severity_actions",
+    "stream_watchdog",
+    "threat_feed",
+    "tool_approval",
+    "tool_arguments",
+    "tool_call_policy",
+    "tool_result_limits",
+    "url_policy",
+];
+
+/// Prefix naming one language pack (`language_packs.de`)
+const LANGUAGE_PACK_PREFIX: &str = "language_packs.";
+
+/// Validate `rollout_percent`, returning human-readable problems
+pub fn validate(config: &FilterConfig) -> Vec<String> {
+    let mut diagnostics = Vec::new();
+    for (subsystem, &percent) in &config.rollout_percent {
+        if percent > 100 {
+            diagnostics.push(format!("rollout_percent.{}: must be at most 100", subsystem));
+        }
+        let known = match subsystem.strip_prefix(LANGUAGE_PACK_PREFIX) {
+            Some(code) => {
+                config.language_packs.as_ref().is_some_and(|l| l.packs.contains_key(code))
+            }
+            None => SUBSYSTEMS.contains(&subsystem.as_str()),
+        };
+        if !known {
+            diagnostics.push(format!(
+                "rollout_percent.{}: unknown subsystem or language pack",
+                subsystem
+            ));
+        }
+    }
+    diagnostics
+}
+
+/// Whether a request (by its rollout key) is inside a subsystem's rollout
+pub fn includes(key: &str, subsystem: &str, percent: u8) -> bool {
+    xxh64(key.as_bytes(), xxh64(subsystem.as_bytes(), 0)) % 100 < u64::from(percent)
+}
+
+/// Rollout decision per listed subsystem: (subsystem, included)
+pub fn decide(rollout: &BTreeMap<String, u8>, key: &str) -> Vec<(String, bool)> {
+    rollout
+        .iter()
+        .map(|(subsystem, &percent)| (subsystem.clone(), includes(key, subsystem, percent)))
+        .collect()
+}
+
+/// Switch a subsystem off for one request
+pub fn exclude(config: &mut FilterConfig, subsystem: &str) {
+    if let Some(code) = subsystem.strip_prefix(LANGUAGE_PACK_PREFIX) {
+        if let Some(packs) = config.language_packs.as_mut() {
+            packs.packs.remove(code);
+        }
+        return;
+    }
+    match subsystem {
+        "binary_policy" => config.binary_policy = None,
+        "entropy_detection" => config.entropy_detection = None,
+        "header_scrubbing" => config.header_scrubbing = None,
+        "language_packs" => config.language_packs = None,
+        "markup_scanning" => config.markup_scanning = None,
+        "mcp_result_scanning" => config.mcp_result_scanning = None,
+        "model_policy" => config.model_policy = None,
+        "notification_limits" => config.notification_limits = None,
+        "pii_vault" => config.pii_vault = None,
+        "prompt_templates" => config.prompt_templates = None,
+        "quarantine" => config.quarantine = None,
+        "repetition_detection" => config.repetition_detection = None,
+        "replay_protection" => config.replay_protection = None,
+        "response_pii" => config.response_pii = None,
+        "response_scanning" => config.response_scanning = None,
+        "severity_actions" => config.severity_actions = None,
+        "stream_watchdog" => config.stream_watchdog = None,
+        "threat_feed" => config.threat_feed = None,
+        "tool_approval" => config.tool_approval = None,
+        "tool_arguments" => config.tool_arguments = None,
+        "tool_call_policy" => config.tool_call_policy = None,
+        "tool_result_limits" => config.tool_result_limits = None,
+        "url_policy" => config.url_policy = None,
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_includes_share_and_stability() {
+        let keys: Vec<String> = (0..2000).map(|i| format!("session-{}", i)).collect();
+        let included = keys.iter().filter(|k| includes(k, "response_scanning", 5)).count();
+        assert!((50..150).contains(&included), "{} included", included);
+        assert!(keys.iter().all(|k| includes(k, "url_policy", 100)));
+        assert!(!keys.iter().any(|k| includes(k, "url_policy", 0)));
+        assert_eq!(includes("s-1", "url_policy", 30), includes("s-1", "url_policy", 30));
+    }
+
+    #[test]
+    fn test_exclude_and_validate() {
+        let json = r#"{"language_packs": {"packs": {"de": ["ignoriere"], "fr": ["ignore"]}},
+            "url_policy": {}, "rollout_percent": {"url_policy": 5, "language_packs.de": 10}}"#;
+        let mut config = FilterConfig::from_bytes(json.as_bytes()).unwrap();
+        assert!(validate(&config).is_empty());
+        let mut excluded = config.clone();
+        exclude(&mut excluded, "url_policy");
+        exclude(&mut excluded, "language_packs.de");
+        assert!(excluded.url_policy.is_none());
+        let packs = excluded.language_packs.as_ref().unwrap();
+        assert_eq!(packs.packs.keys().collect::<Vec<_>>(), ["fr"]);
+
+        config.rollout_percent.insert("language_packs.it".to_string(), 5);
+        config.rollout_percent.insert("tracing".to_string(), 101);
+        assert_eq!(
+            validate(&config),
+            vec![
+                "rollout_percent.language_packs.it: unknown subsystem or language pack"
+                    .to_string(),
+                "rollout_percent.tracing: must be at most 100".to_string(),
+                "rollout_percent.tracing: unknown subsystem or language pack".to_string(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,328 @@
+//! Route-Scoped Policy
+//!
+//! One listener often serves several AI APIs (`/v1/chat/completions`,
+//! `/mcp`, `/a2a`) that need different pattern sets, PII policies and
+//! limits. Routes map path prefixes and HTTP methods to policy bundles:
+//!
+//! ```json
+//! {"routes": [
+//!   {"name": "mcp", "path_prefixes": ["/mcp"], "methods": ["POST"],
+//!    "overrides": {"blocked_patterns": ["rm -rf"], "max_body_size": 65536}}
+//! ]}
+//! ```
+//!
+//! The longest matching prefix wins (the first declared route on a tie); a
+//! route without `methods` matches any method. `classes` restricts a route
+//! to request classes (as labelled from the path, see `classify`); a route
+//! listing classes may leave out `path_prefixes` to cover every path, after
+//! the routes with prefixes. Overrides merge like tenant
+//! overrides and are applied on top of them, so a tenant's route gets the
+//! base configuration, then the tenant's overrides, then the route's.
+
+use super::classify::RequestClass;
+use super::tenant::{merge, TenancyConfig};
+use crate::config::{ConfigError, ConfigSnapshot, FilterConfig};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Plugin configuration key holding the routes
+const ROUTES_KEY: &str = "routes";
+
+/// Plugin configuration key holding the tenancy section
+const TENANCY_KEY: &str = "tenancy";
+
+/// One route: which requests it covers and what it overrides
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RouteSpec {
+    /// Route name (logs, verdict)
+    pub name: String,
+    /// Request path prefixes covered by this route (any path when empty)
+    #[serde(default)]
+    pub path_prefixes: Vec<String>,
+    /// HTTP methods covered (any method when empty)
+    #[serde(default)]
+    pub methods: Vec<String>,
+    /// Request classes covered (any class when empty)
+    #[serde(default)]
+    pub classes: Vec<RequestClass>,
+    /// Config keys overriding the tenant (or base) configuration
+    #[serde(default)]
+    pub overrides: Map<String, Value>,
+}
+
+/// Validate the routes, returning human-readable problems
+pub fn validate(routes: &[RouteSpec]) -> Vec<String> {
+    let mut diagnostics = Vec::new();
+    for (i, route) in routes.iter().enumerate() {
+        if route.name.is_empty() {
+            diagnostics.push(format!("routes[{}].name: must not be empty", i));
+        } else if routes[..i].iter().any(|r| r.name == route.name) {
+            diagnostics.push(format!("routes[{}].name: duplicate '{}'", i, route.name));
+        }
+        if (route.path_prefixes.is_empty() && route.classes.is_empty())
+            || route.path_prefixes.iter().any(|p| !p.starts_with('/'))
+        {
+            diagnostics.push(format!(
+                "routes[{}].path_prefixes: must list prefixes starting with '/'",
+                i
+            ));
+        }
+        if route.methods.iter().any(|m| m.is_empty() || !m.bytes().all(|b| b.is_ascii_alphabetic()))
+        {
+            diagnostics.push(format!("routes[{}].methods: invalid HTTP method", i));
+        }
+        for key in [ROUTES_KEY, TENANCY_KEY] {
+            if route.overrides.contains_key(key) {
+                diagnostics.push(format!("routes[{}].overrides: {} cannot be overridden", i, key));
+            }
+        }
+    }
+    diagnostics
+}
+
+/// What one path prefix of a route covers
+#[derive(Clone, Debug)]
+struct RouteMatcher {
+    prefix: String,
+    methods: Vec<String>,
+    classes: Vec<RequestClass>,
+    route: String,
+}
+
+/// Route matcher plus each (tenant, route) effective configuration
+#[derive(Clone, Debug, Default)]
+pub struct RoutePolicies {
+    /// Matchers, longest prefix first
+    prefixes: Vec<RouteMatcher>,
+    /// Configurations by (tenant, route); "" is the base configuration
+    configs: HashMap<(String, String), Rc<ConfigSnapshot>>,
+}
+
+impl RoutePolicies {
+    /// Build from the raw plugin config and its parsed base config
+    pub fn from_bytes(raw: &[u8], base: &FilterConfig) -> Result<Self, ConfigError> {
+        if base.routes.is_empty() {
+            return Ok(Self::default());
+        }
+        let mut prefixes = Vec::new();
+        for route in &base.routes {
+            let methods: Vec<String> = route.methods.iter().map(|m| m.to_uppercase()).collect();
+            let any_path = [String::new()];
+            let paths = if route.path_prefixes.is_empty() {
+                &any_path[..]
+            } else {
+                &route.path_prefixes[..]
+            };
+            for prefix in paths {
+                prefixes.push(RouteMatcher {
+                    prefix: prefix.clone(),
+                    methods: methods.clone(),
+                    classes: route.classes.clone(),
+                    route: route.name.clone(),
+                });
+            }
+        }
+        // Stable sort: declaration order breaks ties
+        prefixes.sort_by_key(|m| std::cmp::Reverse(m.prefix.len()));
+        Ok(Self {
+            prefixes,
+            configs: build_route_configs(raw)?
+                .into_iter()
+                .map(|(key, config)| (key, Rc::new(ConfigSnapshot::new(config))))
+                .collect(),
+        })
+    }
+
+    /// Check if routes are configured
+    pub fn is_enabled(&self) -> bool {
+        !self.prefixes.is_empty()
+    }
+
+    /// Route covering a request
+    pub fn resolve(&self, path: &str, method: Option<&str>, class: RequestClass) -> Option<&str> {
+        self.prefixes
+            .iter()
+            .find(|m| {
+                let method_matches = m.methods.is_empty()
+                    || method.is_some_and(|method| m.methods.contains(&method.to_uppercase()));
+                let class_matches = m.classes.is_empty() || m.classes.contains(&class);
+                path.starts_with(m.prefix.as_str()) && method_matches && class_matches
+            })
+            .map(|m| m.route.as_str())
+    }
+
+    /// Effective configuration of a route for a tenant (None = base)
+    pub fn config(&self, tenant: Option<&str>, route: &str) -> Option<&Rc<ConfigSnapshot>> {
+        let key = (tenant.unwrap_or_default().to_string(), route.to_string());
+        self.configs.get(&key)
+    }
+
+    /// Number of (tenant, route) configurations
+    pub fn len(&self) -> usize {
+        self.configs.len()
+    }
+
+    /// Check if there are no route configurations
+    pub fn is_empty(&self) -> bool {
+        self.configs.is_empty()
+    }
+}
+
+/// Build every (tenant, route) configuration from the raw plugin config
+pub fn build_route_configs(
+    raw: &[u8],
+) -> Result<HashMap<(String, String), FilterConfig>, ConfigError> {
+    let mut base: Value =
+        serde_json::from_slice(raw).map_err(|e| ConfigError::InvalidJson(e.to_string()))?;
+    let object = match base.as_object_mut() {
+        Some(object) => object,
+        None => return Ok(HashMap::new()),
+    };
+    let routes: Vec<RouteSpec> = match object.get(ROUTES_KEY) {
+        Some(value) => Vec::<RouteSpec>::deserialize(value)
+            .map_err(|e| ConfigError::InvalidJson(format!("routes: {}", e)))?,
+        None => return Ok(HashMap::new()),
+    };
+    let tenancy = match object.remove(TENANCY_KEY) {
+        Some(value) => TenancyConfig::deserialize(value)
+            .map_err(|e| ConfigError::InvalidJson(format!("tenancy: {}", e)))?,
+        None => TenancyConfig::default(),
+    };
+
+    let mut layers = vec![(String::new(), Map::new())];
+    for (id, spec) in tenancy.tenants {
+        if spec.overrides.contains_key(ROUTES_KEY) {
+            return Err(ConfigError::InvalidJson(format!(
+                "tenant '{}': routes cannot be overridden",
+                id
+            )));
+        }
+        layers.push((id, spec.overrides));
+    }
+
+    let mut configs = HashMap::new();
+    for (tenant, overrides) in layers {
+        let mut layered = base.clone();
+        merge(&mut layered, Value::Object(overrides));
+        for route in &routes {
+            let mut merged = layered.clone();
+            merge(&mut merged, Value::Object(route.overrides.clone()));
+            let config = FilterConfig::deserialize(merged).map_err(|e| {
+                ConfigError::InvalidJson(format!("route '{}': {}", route.name, e))
+            })?;
+            configs.insert((tenant.clone(), route.name.clone()), config);
+        }
+    }
+    Ok(configs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"{
+        "blocked_patterns": ["jailbreak"],
+        "max_body_size": 1024,
+        "routes": [
+            {"name": "chat", "path_prefixes": ["/v1/chat/completions"],
+             "overrides": {"pii_types": ["ssn"]}},
+            {"name": "mcp", "path_prefixes": ["/mcp"], "methods": ["post"],
+             "overrides": {"blocked_patterns": ["rm -rf"]}},
+            {"name": "mcp-admin", "path_prefixes": ["/mcp/admin"],
+             "overrides": {"max_body_size": 64}}
+        ],
+        "tenancy": {
+            "tenants": {"team-a": {"overrides": {"max_body_size": 4096}}}
+        }
+    }"#;
+
+    const OTHER: RequestClass = RequestClass::Other;
+
+    fn policies() -> RoutePolicies {
+        let base = FilterConfig::from_bytes(CONFIG.as_bytes()).unwrap();
+        RoutePolicies::from_bytes(CONFIG.as_bytes(), &base).unwrap()
+    }
+
+    #[test]
+    fn test_resolve() {
+        let policies = policies();
+        assert_eq!(policies.resolve("/v1/chat/completions", Some("POST"), OTHER), Some("chat"));
+        assert_eq!(policies.resolve("/mcp", Some("POST"), OTHER), Some("mcp"));
+        // Methods compare case-insensitively; other methods fall through
+        assert_eq!(policies.resolve("/mcp/x", Some("post"), OTHER), Some("mcp"));
+        assert_eq!(policies.resolve("/mcp", Some("GET"), OTHER), None);
+        assert_eq!(policies.resolve("/mcp/admin/users", Some("GET"), OTHER), Some("mcp-admin"));
+        assert_eq!(policies.resolve("/a2a", Some("POST"), OTHER), None);
+    }
+
+    #[test]
+    fn test_resolve_by_class() {
+        let raw = r#"{"routes": [
+            {"name": "admin-api", "path_prefixes": ["/v1"], "classes": ["admin"]},
+            {"name": "embeddings", "classes": ["embedding"],
+             "overrides": {"max_body_size": 2048}}
+        ]}"#;
+        let base = FilterConfig::from_bytes(raw.as_bytes()).unwrap();
+        assert!(base.validate().is_empty(), "{:?}", base.validate());
+        let policies = RoutePolicies::from_bytes(raw.as_bytes(), &base).unwrap();
+        let admin = RequestClass::Admin;
+        assert_eq!(policies.resolve("/v1/organization", None, admin), Some("admin-api"));
+        assert_eq!(policies.resolve("/v2/admin", None, admin), None);
+        let embedding = RequestClass::Embedding;
+        assert_eq!(policies.resolve("/v1/embeddings", None, embedding), Some("embeddings"));
+        assert_eq!(policies.resolve("/v1/chat/completions", None, RequestClass::Chat), None);
+        assert_eq!(policies.config(None, "embeddings").unwrap().max_body_size, 2048);
+    }
+
+    #[test]
+    fn test_route_configs_layer_on_tenants() {
+        let policies = policies();
+        assert_eq!(policies.len(), 6);
+
+        let mcp = policies.config(None, "mcp").unwrap();
+        assert_eq!(mcp.blocked_patterns, vec!["rm -rf"]);
+        assert_eq!(mcp.max_body_size, 1024);
+
+        let chat = policies.config(Some("team-a"), "chat").unwrap();
+        assert_eq!(chat.blocked_patterns, vec!["jailbreak"]);
+        assert_eq!(chat.pii_types, vec!["ssn"]);
+        assert_eq!(chat.max_body_size, 4096);
+
+        // The route overrides the tenant
+        assert_eq!(policies.config(Some("team-a"), "mcp-admin").unwrap().max_body_size, 64);
+    }
+
+    #[test]
+    fn test_invalid_routes() {
+        let raw = r#"{"routes": [{"name": "r", "path_prefixes": ["/r"],
+            "overrides": {"max_body_size": "big"}}]}"#;
+        let err = build_route_configs(raw.as_bytes()).unwrap_err();
+        assert!(err.to_string().contains("route 'r'"));
+
+        let routes: Vec<RouteSpec> = serde_json::from_str(
+            r#"[{"name": "r", "path_prefixes": ["r"], "methods": ["GET /"]},
+                {"name": "r", "path_prefixes": ["/r"], "overrides": {"routes": []}}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            validate(&routes),
+            vec![
+                "routes[0].path_prefixes: must list prefixes starting with '/'".to_string(),
+                "routes[0].methods: invalid HTTP method".to_string(),
+                "routes[1].name: duplicate 'r'".to_string(),
+                "routes[1].overrides: routes cannot be overridden".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_no_routes() {
+        let policies = RoutePolicies::from_bytes(b"{}", &FilterConfig::default()).unwrap();
+        assert!(!policies.is_enabled());
+        assert_eq!(policies.resolve("/mcp", Some("POST"), OTHER), None);
+        assert!(build_route_configs(b"{}").unwrap().is_empty());
+    }
+}
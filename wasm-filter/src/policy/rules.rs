@@ -0,0 +1,657 @@
+//! Policy Rules
+//!
+//! Pattern lists decide on content alone; some policies depend on who is
+//! calling what ("deny the `shell` tool unless the agent tier is admin").
+//! Policy rules are conditions in a small CEL subset, evaluated in order
+//! over the request attributes once the request is complete; the first rule
+//! whose condition holds decides:
+//!
+//! ```json
+//! {"policy_rules": [
+//!   {"name": "admin-shell", "action": "allow",
+//!    "when": "tool == 'shell' && headers['x-agent-tier'] == 'admin'"},
+//!   {"name": "no-shell", "when": "tool == 'shell'", "reason": "Shell access denied"}
+//! ]}
+//! ```
+//!
+//! An `allow` match skips the remaining rules (the other checks still run),
+//! a `deny` match blocks the request.
+//!
+//! Supported syntax: string, integer, boolean and `null` literals, lists
+//! (`[...]`), `headers['name']`, `== != < <= > >=`, `in`, `! && ||`,
+//! parentheses, `size(x)` and the string methods `startsWith`, `endsWith`
+//! and `contains`. Attributes: `method`, `tool`, `agent_id`, `path`,
+//! `tenant`, `tier`, `class` (the request classification), `model`,
+//! `severity` (`none` when nothing matched), `tokens` (prompt estimate),
+//! `headers`, `environment` (the configured label) and the request time:
+//! `hour`, `minute` and `weekday` (0 = Sunday), in UTC shifted by
+//! `policy_utc_offset_minutes`. Production-mutating tools can thus be
+//! limited to business hours or to staging:
+//!
+//! ```text
+//! tool.startsWith('prod_') && !(environment == 'staging'
+//!     || (weekday >= 1 && weekday <= 5 && hour >= 9 && hour < 17))
+//! ```
+//!
+//! Unknown attributes are rejected when the configuration loads; a rule whose
+//! evaluation fails (e.g. comparing `null` with `<`) does not match.
+
+use serde::Deserialize;
+
+/// Attributes known to conditions
+const ATTRIBUTES: &[&str] = &[
+    "method", "tool", "agent_id", "path", "tenant", "model", "severity", "tokens", "headers",
+    "environment", "hour", "minute", "weekday", "tier", "class",
+];
+
+/// Largest `policy_utc_offset_minutes` magnitude (UTC-12:00 to UTC+14:00 fit)
+pub const MAX_UTC_OFFSET_MINUTES: i32 = 14 * 60;
+
+/// Deepest nesting accepted in a condition
+const MAX_DEPTH: usize = 32;
+
+/// What a matching rule does
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+    /// Stop evaluating rules, let the request through
+    Allow,
+    /// Block the request
+    #[default]
+    Deny,
+}
+
+/// A named policy rule
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyRule {
+    /// Rule name (audit events, block reasons)
+    pub name: String,
+    /// Condition under which the rule applies
+    pub when: Condition,
+    /// Action taken when the condition holds
+    #[serde(default)]
+    pub action: RuleAction,
+    /// Reason returned to the client when the rule denies
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+impl PolicyRule {
+    /// Block reason of a denying rule
+    pub fn deny_reason(&self) -> String {
+        match &self.reason {
+            Some(reason) => reason.clone(),
+            None => format!("Denied by policy rule '{}'", self.name),
+        }
+    }
+}
+
+/// Validate a rule list, returning human-readable problems
+pub fn validate(rules: &[PolicyRule]) -> Vec<String> {
+    let mut diagnostics = Vec::new();
+    for (i, rule) in rules.iter().enumerate() {
+        if rule.name.trim().is_empty() {
+            diagnostics.push(format!("policy_rules[{}].name: must not be empty", i));
+        } else if rules[..i].iter().any(|r| r.name == rule.name) {
+            diagnostics.push(format!("policy_rules[{}].name: duplicate '{}'", i, rule.name));
+        }
+    }
+    diagnostics
+}
+
+/// First rule whose condition holds
+pub fn evaluate<'a>(rules: &'a [PolicyRule], attrs: &RequestAttributes) -> Option<&'a PolicyRule> {
+    rules.iter().find(|rule| rule.when.matches(attrs))
+}
+
+/// Request attributes conditions are evaluated over
+#[derive(Clone, Debug, Default)]
+pub struct RequestAttributes {
+    pub method: Option<String>,
+    pub tool: Option<String>,
+    pub agent_id: Option<String>,
+    pub path: Option<String>,
+    pub tenant: Option<String>,
+    pub tier: Option<String>,
+    pub class: Option<String>,
+    pub model: Option<String>,
+    pub severity: Option<String>,
+    pub tokens: u64,
+    /// Request headers (names lowercase)
+    pub headers: Vec<(String, String)>,
+    /// Deployment environment label
+    pub environment: Option<String>,
+    /// Request time: Unix seconds shifted to the policy time zone
+    pub local_time_secs: i64,
+}
+
+impl RequestAttributes {
+    fn get(&self, name: &str) -> Value {
+        let text = |v: &Option<String>| v.clone().map(Value::Str).unwrap_or(Value::Null);
+        match name {
+            "method" => text(&self.method),
+            "tool" => text(&self.tool),
+            "agent_id" => text(&self.agent_id),
+            "path" => text(&self.path),
+            "tenant" => text(&self.tenant),
+            "tier" => text(&self.tier),
+            "class" => text(&self.class),
+            "model" => text(&self.model),
+            "severity" => Value::Str(self.severity.clone().unwrap_or_else(|| "none".into())),
+            "tokens" => Value::Int(self.tokens as i64),
+            "headers" => Value::Headers,
+            "environment" => text(&self.environment),
+            "hour" => Value::Int(self.local_time_secs.rem_euclid(86_400) / 3600),
+            "minute" => Value::Int(self.local_time_secs.rem_euclid(3600) / 60),
+            // 1970-01-01 was a Thursday
+            "weekday" => Value::Int((self.local_time_secs.div_euclid(86_400) + 4).rem_euclid(7)),
+            _ => Value::Null,
+        }
+    }
+}
+
+/// A parsed condition
+#[derive(Clone, Debug, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Condition {
+    source: String,
+    expr: Expr,
+}
+
+impl Condition {
+    /// Source text of the condition
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Whether the condition holds (evaluation errors do not match)
+    pub fn matches(&self, attrs: &RequestAttributes) -> bool {
+        matches!(self.expr.eval(attrs), Ok(Value::Bool(true)))
+    }
+}
+
+impl TryFrom<String> for Condition {
+    type Error = String;
+
+    fn try_from(source: String) -> Result<Self, String> {
+        let tokens = tokenize(&source)?;
+        let mut parser = Parser { tokens, pos: 0, depth: 0 };
+        let expr = parser.expr()?;
+        if parser.pos < parser.tokens.len() {
+            return Err(format!("unexpected {:?} in '{}'", parser.tokens[parser.pos], source));
+        }
+        Ok(Self { source, expr })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Str(String),
+    List(Vec<Value>),
+    /// The request headers (only indexable)
+    Headers,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BinOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    In,
+}
+
+#[derive(Clone, Debug)]
+enum Expr {
+    Literal(Value),
+    Attribute(String),
+    List(Vec<Expr>),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(BinOp, Box<Expr>, Box<Expr>),
+    Index(Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+type EvalResult = Result<Value, String>;
+
+impl Expr {
+    fn eval(&self, attrs: &RequestAttributes) -> EvalResult {
+        match self {
+            Expr::Literal(value) => Ok(value.clone()),
+            Expr::Attribute(name) => Ok(attrs.get(name)),
+            Expr::List(items) => {
+                let values = items.iter().map(|e| e.eval(attrs)).collect::<Result<_, _>>()?;
+                Ok(Value::List(values))
+            }
+            Expr::Not(inner) => match inner.eval(attrs)? {
+                Value::Bool(b) => Ok(Value::Bool(!b)),
+                other => Err(format!("'!' applied to {:?}", other)),
+            },
+            // As in CEL, a decisive side wins even when the other side fails
+            Expr::And(left, right) => logic(left.eval(attrs), || right.eval(attrs), false),
+            Expr::Or(left, right) => logic(left.eval(attrs), || right.eval(attrs), true),
+            Expr::Compare(op, left, right) => compare(*op, left.eval(attrs)?, right.eval(attrs)?),
+            Expr::Index(target, key) => match (target.eval(attrs)?, key.eval(attrs)?) {
+                (Value::Headers, Value::Str(name)) => Ok(attrs
+                    .headers
+                    .iter()
+                    .find(|(k, _)| k.eq_ignore_ascii_case(&name))
+                    .map(|(_, v)| Value::Str(v.clone()))
+                    .unwrap_or(Value::Null)),
+                (Value::List(items), Value::Int(i)) => usize::try_from(i)
+                    .ok()
+                    .and_then(|i| items.get(i).cloned())
+                    .ok_or_else(|| format!("index {} out of range", i)),
+                (target, key) => Err(format!("cannot index {:?} with {:?}", target, key)),
+            },
+            Expr::Call(function, args) => {
+                let args = args.iter().map(|e| e.eval(attrs)).collect::<Result<Vec<_>, _>>()?;
+                call(function, &args)
+            }
+        }
+    }
+}
+
+fn logic<F: FnOnce() -> EvalResult>(left: EvalResult, right: F, decisive: bool) -> EvalResult {
+    if left == Ok(Value::Bool(decisive)) {
+        return Ok(Value::Bool(decisive));
+    }
+    match (left, right()) {
+        (_, Ok(Value::Bool(b))) if b == decisive => Ok(Value::Bool(decisive)),
+        (Ok(Value::Bool(_)), Ok(Value::Bool(_))) => Ok(Value::Bool(!decisive)),
+        (Err(e), _) | (_, Err(e)) => Err(e),
+        (left, right) => Err(format!("logical operator on {:?} and {:?}", left, right)),
+    }
+}
+
+fn compare(op: BinOp, left: Value, right: Value) -> EvalResult {
+    let ordering = match (&left, &right) {
+        (Value::Int(a), Value::Int(b)) => Some(a.cmp(b)),
+        (Value::Str(a), Value::Str(b)) => Some(a.cmp(b)),
+        _ => None,
+    };
+    let result = match op {
+        BinOp::Eq => left == right,
+        BinOp::Ne => left != right,
+        BinOp::In => match &right {
+            Value::List(items) => items.contains(&left),
+            Value::Str(text) => match &left {
+                Value::Str(needle) => text.contains(needle.as_str()),
+                _ => return Err(format!("'in' with {:?} and {:?}", left, right)),
+            },
+            _ => return Err(format!("'in' with {:?} and {:?}", left, right)),
+        },
+        _ => {
+            let ordering = match ordering {
+                Some(ordering) => ordering,
+                None => return Err(format!("cannot order {:?} and {:?}", left, right)),
+            };
+            match op {
+                BinOp::Lt => ordering.is_lt(),
+                BinOp::Le => ordering.is_le(),
+                BinOp::Gt => ordering.is_gt(),
+                _ => ordering.is_ge(),
+            }
+        }
+    };
+    Ok(Value::Bool(result))
+}
+
+fn call(function: &str, args: &[Value]) -> EvalResult {
+    match (function, args) {
+        ("size", [Value::Str(s)]) => Ok(Value::Int(s.chars().count() as i64)),
+        ("size", [Value::List(items)]) => Ok(Value::Int(items.len() as i64)),
+        ("startsWith", [Value::Str(s), Value::Str(prefix)]) => {
+            Ok(Value::Bool(s.starts_with(prefix.as_str())))
+        }
+        ("endsWith", [Value::Str(s), Value::Str(suffix)]) => {
+            Ok(Value::Bool(s.ends_with(suffix.as_str())))
+        }
+        ("contains", [Value::Str(s), Value::Str(needle)]) => {
+            Ok(Value::Bool(s.contains(needle.as_str())))
+        }
+        _ => Err(format!("no overload of {} for {:?}", function, args)),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Int(i64),
+    Op(&'static str),
+}
+
+const OPERATORS: &[&str] = &[
+    "==", "!=", "<=", ">=", "&&", "||", "<", ">", "!", "(", ")", "[", "]", ",", ".",
+];
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = source;
+    while let Some(c) = rest.chars().next() {
+        if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+        } else if c == '\'' || c == '"' {
+            let mut value = String::new();
+            let mut chars = rest[1..].char_indices();
+            let end = loop {
+                match chars.next() {
+                    Some((i, q)) if q == c => break i + 2,
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, 'n')) => value.push('\n'),
+                        Some((_, 't')) => value.push('\t'),
+                        Some((_, escaped)) => value.push(escaped),
+                        None => return Err("unterminated string".to_string()),
+                    },
+                    Some((_, ch)) => value.push(ch),
+                    None => return Err("unterminated string".to_string()),
+                }
+            };
+            tokens.push(Token::Str(value));
+            rest = &rest[end..];
+        } else if c.is_ascii_digit() {
+            let len = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+            let value = rest[..len].parse().map_err(|_| "integer out of range".to_string())?;
+            tokens.push(Token::Int(value));
+            rest = &rest[len..];
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..len].to_string()));
+            rest = &rest[len..];
+        } else {
+            let op = match OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+                Some(op) => *op,
+                None => return Err(format!("unexpected character '{}'", c)),
+            };
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser, precedence low to high: `||`, `&&`,
+/// comparisons, `!`, member access and indexing
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+}
+
+type ParseResult = Result<Expr, String>;
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn eat(&mut self, op: &'static str) -> bool {
+        if self.peek() == Some(&Token::Op(op)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, op: &'static str) -> Result<(), String> {
+        if self.eat(op) {
+            Ok(())
+        } else {
+            Err(format!("expected '{}'", op))
+        }
+    }
+
+    fn expr(&mut self) -> ParseResult {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err("condition nested too deeply".to_string());
+        }
+        let mut left = self.and()?;
+        while self.eat("||") {
+            left = Expr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        self.depth -= 1;
+        Ok(left)
+    }
+
+    fn and(&mut self) -> ParseResult {
+        let mut left = self.relation()?;
+        while self.eat("&&") {
+            left = Expr::And(Box::new(left), Box::new(self.relation()?));
+        }
+        Ok(left)
+    }
+
+    fn relation(&mut self) -> ParseResult {
+        let left = self.unary()?;
+        let op = match self.peek() {
+            Some(Token::Op("==")) => BinOp::Eq,
+            Some(Token::Op("!=")) => BinOp::Ne,
+            Some(Token::Op("<")) => BinOp::Lt,
+            Some(Token::Op("<=")) => BinOp::Le,
+            Some(Token::Op(">")) => BinOp::Gt,
+            Some(Token::Op(">=")) => BinOp::Ge,
+            Some(Token::Ident(word)) if word == "in" => BinOp::In,
+            _ => return Ok(left),
+        };
+        self.pos += 1;
+        let right = self.unary()?;
+        Ok(Expr::Compare(op, Box::new(left), Box::new(right)))
+    }
+
+    fn unary(&mut self) -> ParseResult {
+        if self.eat("!") {
+            self.depth += 1;
+            if self.depth > MAX_DEPTH {
+                return Err("condition nested too deeply".to_string());
+            }
+            let inner = self.unary()?;
+            self.depth -= 1;
+            return Ok(Expr::Not(Box::new(inner)));
+        }
+        self.member()
+    }
+
+    fn member(&mut self) -> ParseResult {
+        let mut expr = self.primary()?;
+        loop {
+            if self.eat(".") {
+                let method = match self.tokens.get(self.pos) {
+                    Some(Token::Ident(name)) => name.clone(),
+                    _ => return Err("expected a method name after '.'".to_string()),
+                };
+                self.pos += 1;
+                self.expect("(")?;
+                let mut args = vec![expr];
+                args.extend(self.arguments(")")?);
+                expr = Expr::Call(method, args);
+            } else if self.eat("[") {
+                let key = self.expr()?;
+                self.expect("]")?;
+                expr = Expr::Index(Box::new(expr), Box::new(key));
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn primary(&mut self) -> ParseResult {
+        let token = match self.tokens.get(self.pos) {
+            Some(token) => token.clone(),
+            None => return Err("unexpected end of condition".to_string()),
+        };
+        self.pos += 1;
+        match token {
+            Token::Str(s) => Ok(Expr::Literal(Value::Str(s))),
+            Token::Int(i) => Ok(Expr::Literal(Value::Int(i))),
+            Token::Op("(") => {
+                let inner = self.expr()?;
+                self.expect(")")?;
+                Ok(inner)
+            }
+            Token::Op("[") => Ok(Expr::List(self.arguments("]")?)),
+            Token::Ident(name) => match name.as_str() {
+                "true" => Ok(Expr::Literal(Value::Bool(true))),
+                "false" => Ok(Expr::Literal(Value::Bool(false))),
+                "null" => Ok(Expr::Literal(Value::Null)),
+                _ if self.eat("(") => Ok(Expr::Call(name, self.arguments(")")?)),
+                _ if ATTRIBUTES.contains(&name.as_str()) => Ok(Expr::Attribute(name)),
+                _ => Err(format!("unknown attribute '{}'", name)),
+            },
+            Token::Op(op) => Err(format!("unexpected '{}'", op)),
+        }
+    }
+
+    /// Comma-separated expressions up to the closing token (already opened)
+    fn arguments(&mut self, close: &'static str) -> Result<Vec<Expr>, String> {
+        let mut items = Vec::new();
+        if self.eat(close) {
+            return Ok(items);
+        }
+        loop {
+            items.push(self.expr()?);
+            if self.eat(close) {
+                return Ok(items);
+            }
+            self.expect(",")?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn condition(source: &str) -> Condition {
+        Condition::try_from(source.to_string()).unwrap()
+    }
+
+    fn attrs() -> RequestAttributes {
+        RequestAttributes {
+            method: Some("tools/call".to_string()),
+            tool: Some("shell".to_string()),
+            agent_id: Some("planner".to_string()),
+            path: Some("/mcp".to_string()),
+            class: Some("tool_call".to_string()),
+            tokens: 1200,
+            headers: vec![("x-agent-tier".to_string(), "standard".to_string())],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_conditions() {
+        let attrs = attrs();
+        let holds = |source: &str| condition(source).matches(&attrs);
+        assert!(holds("tool == 'shell' && headers['x-agent-tier'] != 'admin'"));
+        assert!(holds("method == \"tools/call\" || false"));
+        assert!(holds("tokens > 1000 && tokens <= 1200"));
+        assert!(holds("tool in ['shell', 'execute_sql'] && !(agent_id in ['admin'])"));
+        assert!(holds("path.startsWith('/mc') && size(agent_id) == 7"));
+        assert!(holds("severity == 'none' && model == null"));
+        assert!(holds("headers['X-Agent-Tier'].endsWith('ard')"));
+        assert!(holds("class == 'tool_call' && class in ['tool_call', 'admin']"));
+        assert!(!holds("headers['x-missing'] == 'admin'"));
+    }
+
+    #[test]
+    fn test_time_and_environment() {
+        let rule = condition(
+            "tool.startsWith('prod_') && !(environment == 'staging' \
+             || (weekday >= 1 && weekday <= 5 && hour >= 9 && hour < 17))",
+        );
+        let mut attrs = RequestAttributes {
+            tool: Some("prod_migrate".to_string()),
+            environment: Some("production".to_string()),
+            // Monday 2024-01-01 10:30 UTC
+            local_time_secs: 1_704_105_000,
+            ..Default::default()
+        };
+        assert!(!rule.matches(&attrs));
+        assert!(condition("weekday == 1 && hour == 10 && minute == 30").matches(&attrs));
+        // Saturday 2024-01-06, same time
+        attrs.local_time_secs += 5 * 86_400;
+        assert!(rule.matches(&attrs));
+        attrs.environment = Some("staging".to_string());
+        assert!(!rule.matches(&attrs));
+        // Before the epoch still maps to a valid day and hour (Wednesday 23:00)
+        attrs.local_time_secs = -3600;
+        assert!(condition("weekday == 3 && hour == 23").matches(&attrs));
+    }
+
+    #[test]
+    fn test_errors_do_not_match() {
+        let attrs = attrs();
+        // Ordering null is an error, so the rule does not match...
+        assert!(!condition("model > 'a'").matches(&attrs));
+        assert!(!condition("!(model > 'a')").matches(&attrs));
+        // ...unless the other side of && / || decides on its own
+        assert!(condition("model > 'a' || tool == 'shell'").matches(&attrs));
+        assert!(!condition("model > 'a' && tool == 'other'").matches(&attrs));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let parse = |source: &str| Condition::try_from(source.to_string()).unwrap_err();
+        assert_eq!(parse("plan == 'admin'"), "unknown attribute 'plan'");
+        assert_eq!(parse("tool == 'shell"), "unterminated string");
+        assert_eq!(parse("tool =="), "unexpected end of condition");
+        assert_eq!(parse("(tool == 'a'"), "expected ')'");
+        assert_eq!(parse("tool ~ 'a'"), "unexpected character '~'");
+        assert_eq!(parse(&"(".repeat(100)), "condition nested too deeply");
+    }
+
+    #[test]
+    fn test_first_match_decides() {
+        let rules: Vec<PolicyRule> = serde_json::from_str(
+            r#"[
+                {"name": "admin-shell", "action": "allow",
+                 "when": "tool == 'shell' && headers['x-agent-tier'] == 'admin'"},
+                {"name": "no-shell", "when": "tool == 'shell'"}
+            ]"#,
+        )
+        .unwrap();
+        assert!(validate(&rules).is_empty());
+
+        let denied = evaluate(&rules, &attrs()).unwrap();
+        assert_eq!(denied.action, RuleAction::Deny);
+        assert_eq!(denied.deny_reason(), "Denied by policy rule 'no-shell'");
+
+        let mut admin = attrs();
+        admin.headers = vec![("x-agent-tier".to_string(), "admin".to_string())];
+        assert_eq!(evaluate(&rules, &admin).unwrap().action, RuleAction::Allow);
+
+        let mut other = attrs();
+        other.tool = Some("read_file".to_string());
+        assert!(evaluate(&rules, &other).is_none());
+    }
+
+    #[test]
+    fn test_validate() {
+        let rules: Vec<PolicyRule> = serde_json::from_str(
+            r#"[{"name": "a", "when": "true"}, {"name": "a", "when": "false"},
+                {"name": "", "when": "true"}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            validate(&rules),
+            vec![
+                "policy_rules[1].name: duplicate 'a'".to_string(),
+                "policy_rules[2].name: must not be empty".to_string(),
+            ]
+        );
+        assert!(serde_json::from_str::<PolicyRule>(r#"{"name": "x", "when": "plan"}"#).is_err());
+    }
+}
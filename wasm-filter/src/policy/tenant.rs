@@ -0,0 +1,307 @@
+//! Multi-Tenant Policy
+//!
+//! Resolves the tenant of a request (by header, TLS SNI, or path prefix)
+//! and builds each tenant's effective configuration by deep-merging its
+//! overrides onto the base plugin configuration.
+//!
+//! Merge rules: objects merge key by key; arrays and scalars replace.
+//! So a tenant listing `blocked_patterns` replaces the base list rather
+//! than appending to it.
+
+use crate::config::{ConfigError, ConfigSnapshot, FilterConfig};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+/// Plugin configuration key holding the tenancy section
+const TENANCY_KEY: &str = "tenancy";
+
+/// Tenancy configuration
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TenancyConfig {
+    /// Request header naming the tenant (checked first)
+    #[serde(default)]
+    pub header: Option<String>,
+    /// Tenant used when nothing matches (base config if absent)
+    #[serde(default)]
+    pub default_tenant: Option<String>,
+    /// Tenants by ID
+    #[serde(default)]
+    pub tenants: BTreeMap<String, TenantSpec>,
+}
+
+/// One tenant: how to recognise it and what it overrides
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TenantSpec {
+    /// TLS server names served for this tenant
+    #[serde(default)]
+    pub sni: Vec<String>,
+    /// Request path prefixes owned by this tenant
+    #[serde(default)]
+    pub path_prefixes: Vec<String>,
+    /// Config keys overriding the base configuration
+    #[serde(default)]
+    pub overrides: Map<String, Value>,
+}
+
+/// Resolves requests to tenant IDs
+#[derive(Clone, Debug, Default)]
+pub struct TenantResolver {
+    header: Option<String>,
+    default_tenant: Option<String>,
+    /// Lowercased SNI -> tenant
+    sni: HashMap<String, String>,
+    /// (prefix, tenant), longest prefix first
+    prefixes: Vec<(String, String)>,
+    /// Known tenant IDs
+    tenants: Vec<String>,
+}
+
+impl TenantResolver {
+    /// Build a resolver from tenancy configuration
+    pub fn new(config: &TenancyConfig) -> Self {
+        let mut sni = HashMap::new();
+        let mut prefixes = Vec::new();
+        for (id, spec) in &config.tenants {
+            for name in &spec.sni {
+                sni.insert(name.to_lowercase(), id.clone());
+            }
+            for prefix in &spec.path_prefixes {
+                prefixes.push((prefix.clone(), id.clone()));
+            }
+        }
+        prefixes.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+
+        Self {
+            header: config.header.clone(),
+            default_tenant: config.default_tenant.clone(),
+            sni,
+            prefixes,
+            tenants: config.tenants.keys().cloned().collect(),
+        }
+    }
+
+    /// Header naming the tenant, if configured
+    pub fn header(&self) -> Option<&str> {
+        self.header.as_deref()
+    }
+
+    /// Resolve a tenant: header, then SNI, then longest path prefix
+    ///
+    /// Header values naming unknown tenants are ignored, so clients cannot
+    /// select a policy that does not exist.
+    pub fn resolve(
+        &self,
+        header_value: Option<&str>,
+        sni: Option<&str>,
+        path: Option<&str>,
+    ) -> Option<&str> {
+        if let Some(id) = header_value.map(str::trim) {
+            if let Some(known) = self.tenants.iter().find(|t| t.as_str() == id) {
+                return Some(known);
+            }
+        }
+        if let Some(id) = sni.and_then(|s| self.sni.get(&s.to_lowercase())) {
+            return Some(id);
+        }
+        if let Some(path) = path {
+            let prefix = self.prefixes.iter().find(|(p, _)| path.starts_with(p.as_str()));
+            if let Some((_, id)) = prefix {
+                return Some(id);
+            }
+        }
+        self.default_tenant.as_deref()
+    }
+}
+
+/// Tenant resolver plus each tenant's effective configuration
+#[derive(Clone, Debug, Default)]
+pub struct TenantPolicies {
+    resolver: Option<TenantResolver>,
+    configs: HashMap<String, Rc<ConfigSnapshot>>,
+}
+
+impl TenantPolicies {
+    /// Build from the raw plugin config and its parsed base config
+    pub fn from_bytes(raw: &[u8], base: &FilterConfig) -> Result<Self, ConfigError> {
+        let tenancy = match &base.tenancy {
+            Some(tenancy) => tenancy,
+            None => return Ok(Self::default()),
+        };
+        Ok(Self {
+            resolver: Some(TenantResolver::new(tenancy)),
+            configs: build_tenant_configs(raw)?
+                .into_iter()
+                .map(|(id, config)| (id, Rc::new(ConfigSnapshot::new(config))))
+                .collect(),
+        })
+    }
+
+    /// Check if tenancy is configured
+    pub fn is_enabled(&self) -> bool {
+        self.resolver.is_some()
+    }
+
+    /// Header naming the tenant, if configured
+    pub fn header(&self) -> Option<&str> {
+        self.resolver.as_ref().and_then(|r| r.header())
+    }
+
+    /// Resolve a request to a tenant ID
+    pub fn resolve(
+        &self,
+        header_value: Option<&str>,
+        sni: Option<&str>,
+        path: Option<&str>,
+    ) -> Option<&str> {
+        self.resolver.as_ref()?.resolve(header_value, sni, path)
+    }
+
+    /// Effective configuration of a tenant
+    pub fn config(&self, tenant: &str) -> Option<&Rc<ConfigSnapshot>> {
+        self.configs.get(tenant)
+    }
+
+    /// Number of tenants
+    pub fn len(&self) -> usize {
+        self.configs.len()
+    }
+
+    /// Check if there are no tenants
+    pub fn is_empty(&self) -> bool {
+        self.configs.is_empty()
+    }
+}
+
+/// Build each tenant's effective configuration from the raw plugin config
+pub fn build_tenant_configs(raw: &[u8]) -> Result<HashMap<String, FilterConfig>, ConfigError> {
+    let mut base: Value =
+        serde_json::from_slice(raw).map_err(|e| ConfigError::InvalidJson(e.to_string()))?;
+
+    let tenancy = match base.as_object_mut().and_then(|o| o.remove(TENANCY_KEY)) {
+        Some(value) => TenancyConfig::deserialize(value)
+            .map_err(|e| ConfigError::InvalidJson(format!("tenancy: {}", e)))?,
+        None => return Ok(HashMap::new()),
+    };
+
+    let mut configs = HashMap::new();
+    for (id, spec) in tenancy.tenants {
+        if spec.overrides.contains_key(TENANCY_KEY) {
+            return Err(ConfigError::InvalidJson(format!(
+                "tenant '{}': tenancy cannot be overridden",
+                id
+            )));
+        }
+        let mut merged = base.clone();
+        merge(&mut merged, Value::Object(spec.overrides));
+        let config = FilterConfig::deserialize(merged)
+            .map_err(|e| ConfigError::InvalidJson(format!("tenant '{}': {}", id, e)))?;
+        configs.insert(id, config);
+    }
+    Ok(configs)
+}
+
+/// Deep-merge `overlay` into `base`
+pub(crate) fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"{
+        "blocked_patterns": ["jailbreak"],
+        "max_body_size": 1024,
+        "tenancy": {
+            "header": "x-tenant",
+            "tenants": {
+                "team-a": {
+                    "sni": ["a.example.com"],
+                    "overrides": {"blocked_patterns": ["drop table"], "pii_types": ["ssn"]}
+                },
+                "team-b": {
+                    "path_prefixes": ["/b/", "/b/v2/"],
+                    "overrides": {"rate_limits": {"requests_per_minute": 5}}
+                }
+            }
+        }
+    }"#;
+
+    fn resolver() -> TenantResolver {
+        let config = FilterConfig::from_bytes(CONFIG.as_bytes()).unwrap();
+        TenantResolver::new(&config.tenancy.unwrap())
+    }
+
+    #[test]
+    fn test_resolve_order() {
+        let resolver = resolver();
+        assert_eq!(resolver.resolve(Some("team-b"), Some("a.example.com"), None), Some("team-b"));
+        assert_eq!(resolver.resolve(None, Some("A.Example.com"), Some("/b/x")), Some("team-a"));
+        assert_eq!(resolver.resolve(None, None, Some("/b/v2/chat")), Some("team-b"));
+        assert_eq!(resolver.resolve(None, None, Some("/other")), None);
+    }
+
+    #[test]
+    fn test_unknown_header_tenant_ignored() {
+        let resolver = resolver();
+        assert_eq!(resolver.resolve(Some("team-z"), None, Some("/b/")), Some("team-b"));
+    }
+
+    #[test]
+    fn test_tenant_configs_inherit_base() {
+        let configs = build_tenant_configs(CONFIG.as_bytes()).unwrap();
+
+        let a = &configs["team-a"];
+        assert_eq!(a.blocked_patterns, vec!["drop table"]);
+        assert_eq!(a.pii_types, vec!["ssn"]);
+        assert_eq!(a.max_body_size, 1024);
+        assert!(a.tenancy.is_none());
+
+        let b = &configs["team-b"];
+        assert_eq!(b.blocked_patterns, vec!["jailbreak"]);
+        assert_eq!(b.rate_limits.as_ref().unwrap().requests_per_minute, 5);
+    }
+
+    #[test]
+    fn test_invalid_override_rejected() {
+        let raw = r#"{"tenancy": {"tenants": {"t": {"overrides": {"max_body_size": "big"}}}}}"#;
+        let err = build_tenant_configs(raw.as_bytes()).unwrap_err();
+        assert!(err.to_string().contains("tenant 't'"));
+    }
+
+    #[test]
+    fn test_no_tenancy() {
+        assert!(build_tenant_configs(b"{}").unwrap().is_empty());
+
+        let policies = TenantPolicies::from_bytes(b"{}", &FilterConfig::default()).unwrap();
+        assert!(!policies.is_enabled());
+        assert_eq!(policies.resolve(Some("team-a"), None, None), None);
+    }
+
+    #[test]
+    fn test_policies_lookup() {
+        let base = FilterConfig::from_bytes(CONFIG.as_bytes()).unwrap();
+        let policies = TenantPolicies::from_bytes(CONFIG.as_bytes(), &base).unwrap();
+
+        assert_eq!(policies.header(), Some("x-tenant"));
+        let tenant = policies.resolve(None, Some("a.example.com"), None).unwrap();
+        assert_eq!(policies.config(tenant).unwrap().blocked_patterns, vec!["drop table"]);
+        assert_eq!(policies.len(), 2);
+    }
+}
@@ -0,0 +1,250 @@
+//! Identity Tiers
+//!
+//! Not every caller deserves the same limits: a free-tier key should get a
+//! small rate limit and cheap models, a privileged service account broad
+//! tool access. Each request is resolved to a tier, from a verified JWT
+//! claim or from an API key mapping, and the tier's limits replace the
+//! configured ones:
+//!
+//! ```json
+//! {"identity_tiers": {
+//!   "claims_header": "x-jwt-payload", "claim": "plan",
+//!   "api_key_header": "x-api-key", "api_keys": {"<sha256 hex>": "privileged"},
+//!   "default_tier": "free",
+//!   "tiers": {
+//!     "free": {"rate_limits": {"requests_per_minute": 10, "tokens_per_minute": 10000,
+//!              "concurrent_requests": 1},
+//!              "max_prompt_tokens": 2000, "allowed_models": ["gpt-4o-mini"],
+//!              "allowed_tools": []},
+//!     "privileged": {"allowed_tools": ["*"]}
+//!   }
+//! }}
+//! ```
+//!
+//! The claims header carries the payload of a token Envoy already verified
+//! (`jwt_authn` with `forward_payload_header`), base64url-encoded; `claim`
+//! may be a dotted path. A claim naming an unknown tier falls through to the
+//! API key, then to `default_tier`. API keys are configured as SHA-256 hex
+//! digests so the configuration does not hold usable credentials. Fields a
+//! tier leaves out keep the configured value: `rate_limits`,
+//! `max_prompt_tokens` and `allowed_models` replace their counterparts
+//! (`model_policy.allowed_models` for the latter, `prefix*` entries match
+//! model families). `allowed_tools` applies to MCP `tools/call` requests,
+//! as glob patterns.
+
+use crate::config::FilterConfig;
+use crate::crypto::{base64url_decode, sha256, to_hex};
+use crate::governance::RateLimits;
+use crate::protocols::mcp::method_policy::glob_match;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Limits and allowances of one tier
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TierPolicy {
+    /// Rate limits replacing `rate_limits`
+    pub rate_limits: Option<RateLimits>,
+    /// Per-request prompt token budget replacing `max_prompt_tokens`
+    pub max_prompt_tokens: Option<u32>,
+    /// Models the tier may request, replacing `model_policy.allowed_models`
+    pub allowed_models: Option<Vec<String>>,
+    /// MCP tools the tier may call (glob patterns, any when absent)
+    pub allowed_tools: Option<Vec<String>>,
+}
+
+impl TierPolicy {
+    /// Replace the configured limits with the tier's
+    pub fn apply(&self, config: &mut FilterConfig) {
+        if let Some(limits) = &self.rate_limits {
+            config.rate_limits = Some(limits.clone());
+        }
+        if let Some(limit) = self.max_prompt_tokens {
+            config.max_prompt_tokens = Some(limit);
+        }
+        if let Some(models) = &self.allowed_models {
+            config.model_policy.get_or_insert_with(Default::default).allowed_models =
+                models.clone();
+        }
+    }
+
+    /// Whether the tier may call an MCP tool
+    pub fn allows_tool(&self, tool: &str) -> bool {
+        match &self.allowed_tools {
+            Some(patterns) => patterns.iter().any(|p| glob_match(p, tool)),
+            None => true,
+        }
+    }
+}
+
+/// Identity tier configuration
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TierConfig {
+    /// Header carrying the verified JWT payload (base64url JSON)
+    pub claims_header: Option<String>,
+    /// Claim naming the tier (dotted path)
+    pub claim: Option<String>,
+    /// Header carrying the API key
+    pub api_key_header: Option<String>,
+    /// Tier by SHA-256 hex digest of the API key
+    pub api_keys: BTreeMap<String, String>,
+    /// Tier of requests resolving to none
+    pub default_tier: Option<String>,
+    /// Tiers by name
+    pub tiers: BTreeMap<String, TierPolicy>,
+}
+
+impl TierConfig {
+    /// Validate the configuration, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        if self.tiers.is_empty() {
+            diagnostics.push("identity_tiers.tiers: must define at least one tier".to_string());
+        }
+        if self.claims_header.is_some() != self.claim.is_some() {
+            diagnostics.push(
+                "identity_tiers: claims_header and claim must be set together".to_string(),
+            );
+        }
+        let known = |tier: &String| self.tiers.contains_key(tier);
+        if let Some(tier) = self.default_tier.iter().find(|t| !known(t)) {
+            diagnostics.push(format!("identity_tiers.default_tier: unknown tier '{}'", tier));
+        }
+        for (digest, tier) in &self.api_keys {
+            if digest.len() != 64 || !digest.bytes().all(|b| b.is_ascii_hexdigit()) {
+                diagnostics.push(format!(
+                    "identity_tiers.api_keys: '{}' is not a SHA-256 hex digest",
+                    digest
+                ));
+            } else if !known(tier) {
+                diagnostics.push(format!("identity_tiers.api_keys: unknown tier '{}'", tier));
+            }
+        }
+        diagnostics
+    }
+
+    /// Resolve a tier: claim, then API key, then the default
+    pub fn resolve(&self, claims: Option<&str>, api_key: Option<&str>) -> Option<&str> {
+        let from_claim = match (claims, &self.claim) {
+            (Some(claims), Some(claim)) => claim_value(claims, claim),
+            _ => None,
+        };
+        if let Some((name, _)) = from_claim.and_then(|t| self.tiers.get_key_value(&t)) {
+            return Some(name);
+        }
+        let digest = api_key.map(|key| to_hex(&sha256(key.trim().as_bytes())));
+        let from_key = digest.and_then(|digest| {
+            self.api_keys
+                .iter()
+                .find(|(d, _)| d.eq_ignore_ascii_case(&digest))
+                .map(|(_, tier)| tier)
+        });
+        if let Some((name, _)) = from_key.and_then(|t| self.tiers.get_key_value(t)) {
+            return Some(name);
+        }
+        self.default_tier.as_deref()
+    }
+
+    /// Policy of a tier
+    pub fn policy(&self, tier: &str) -> Option<&TierPolicy> {
+        self.tiers.get(tier)
+    }
+}
+
+/// String value of a (dotted) claim in a base64url JSON payload
+fn claim_value(payload: &str, claim: &str) -> Option<String> {
+    let decoded = base64url_decode(payload.trim())?;
+    let root: Value = serde_json::from_slice(&decoded).ok()?;
+    let value = claim.split('.').try_fold(&root, |value, key| value.get(key))?;
+    value.as_str().map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> TierConfig {
+        let digest = to_hex(&sha256(b"secret-key"));
+        let json = format!(
+            r#"{{"claims_header": "x-jwt-payload", "claim": "org.plan",
+                "api_key_header": "x-api-key", "api_keys": {{"{}": "privileged"}},
+                "default_tier": "free",
+                "tiers": {{
+                    "free": {{"max_prompt_tokens": 2000, "allowed_models": ["gpt-4o-mini"],
+                              "allowed_tools": []}},
+                    "privileged": {{"allowed_tools": ["*"]}}
+                }}}}"#,
+            digest.to_uppercase()
+        );
+        serde_json::from_str(&json).unwrap()
+    }
+
+    /// base64url of a JSON payload
+    fn payload(json: &str) -> String {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+        let mut out = String::new();
+        for chunk in json.as_bytes().chunks(3) {
+            let n = chunk.iter().fold(0u32, |acc, &b| (acc << 8) | b as u32);
+            let n = n << (8 * (3 - chunk.len()));
+            for i in 0..=chunk.len() {
+                out.push(ALPHABET[((n >> (18 - 6 * i)) & 63) as usize] as char);
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_resolve_order() {
+        let config = config();
+        assert!(config.validate().is_empty(), "{:?}", config.validate());
+        let privileged = payload(r#"{"sub": "a", "org": {"plan": "privileged"}}"#);
+        assert_eq!(config.resolve(Some(&privileged), None), Some("privileged"));
+        assert_eq!(config.resolve(None, Some("secret-key")), Some("privileged"));
+        // Unknown claim tiers fall through to the key, then the default
+        let unknown = payload(r#"{"org": {"plan": "platinum"}}"#);
+        assert_eq!(config.resolve(Some(&unknown), Some("secret-key")), Some("privileged"));
+        assert_eq!(config.resolve(Some(&unknown), Some("wrong-key")), Some("free"));
+        assert_eq!(config.resolve(Some("not base64!"), None), Some("free"));
+    }
+
+    #[test]
+    fn test_apply() {
+        let config = config();
+        let base = FilterConfig { max_prompt_tokens: Some(8000), ..Default::default() };
+
+        let free = config.policy("free").unwrap();
+        let mut applied = base.clone();
+        free.apply(&mut applied);
+        assert_eq!(applied.max_prompt_tokens, Some(2000));
+        assert!(!applied.model_policy.unwrap().is_allowed("gpt-4o"));
+        assert!(!free.allows_tool("read_file"));
+
+        let privileged = config.policy("privileged").unwrap();
+        let mut applied = base.clone();
+        privileged.apply(&mut applied);
+        assert_eq!(applied.max_prompt_tokens, Some(8000));
+        assert!(applied.model_policy.is_none());
+        assert!(privileged.allows_tool("execute_sql"));
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = TierConfig {
+            claim: Some("plan".to_string()),
+            default_tier: Some("gold".to_string()),
+            api_keys: [("8d8e2ec2c9".to_string(), "free".to_string())].into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.validate(),
+            vec![
+                "identity_tiers.tiers: must define at least one tier".to_string(),
+                "identity_tiers: claims_header and claim must be set together".to_string(),
+                "identity_tiers.default_tier: unknown tier 'gold'".to_string(),
+                "identity_tiers.api_keys: '8d8e2ec2c9' is not a SHA-256 hex digest".to_string(),
+            ]
+        );
+    }
+}
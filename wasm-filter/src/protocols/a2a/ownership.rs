@@ -0,0 +1,225 @@
+//! A2A Task Ownership
+//!
+//! Task ids travel in URLs, logs and push notifications, so knowing one
+//! must not be enough to cancel or change the task. The identity that
+//! created a task (the first authenticated caller it was seen with) is
+//! recorded per task id in shared data, and operations that mutate a task
+//! are only allowed for that identity.
+//!
+//! Identities are stored as SHA-256 fingerprints, never as the credential
+//! itself. Tasks created without credentials have no owner and stay open
+//! to every caller. Entries expire after a TTL and are hashed into a fixed
+//! number of buckets of bounded size, so shared memory stays bounded.
+
+use super::rest::A2AOperation;
+use super::security::{A2ASecurityError, Identity};
+use crate::crypto::{sha256, to_hex};
+use crate::shared::{SharedError, SharedStore};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Number of shared data buckets
+const BUCKETS: u64 = 256;
+
+/// Tasks kept per bucket
+const MAX_TASKS_PER_BUCKET: usize = 64;
+
+/// Longest task id recorded
+const MAX_TASK_ID_LEN: usize = 256;
+
+/// Default lifetime of an ownership record, in seconds
+pub const DEFAULT_OWNER_TTL_SECS: u64 = 24 * 3600;
+
+/// Owner of a task
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct TaskOwner {
+    /// Fingerprint of the creator's identity
+    identity: String,
+    /// When the record expires, in seconds
+    expires_secs: u64,
+}
+
+/// Creator identities per task id, in shared data
+#[derive(Debug, Clone)]
+pub struct TaskOwners {
+    /// Lifetime of an ownership record, in seconds
+    ttl_secs: u64,
+}
+
+impl TaskOwners {
+    /// Records kept for `ttl_secs` after the task was last seen
+    pub fn new(ttl_secs: u64) -> Self {
+        Self { ttl_secs }
+    }
+
+    /// Record the creator of a task (a task already owned keeps its owner)
+    pub fn record(
+        &self,
+        store: &impl SharedStore,
+        task_id: &str,
+        identity: Option<&Identity>,
+        now_secs: u64,
+    ) -> Result<(), SharedError> {
+        let identity = match identity {
+            Some(identity) if task_id.len() <= MAX_TASK_ID_LEN => fingerprint(identity),
+            _ => return Ok(()),
+        };
+        store.update(&bucket_key(task_id), |current| {
+            let mut tasks = parse(current);
+            tasks.retain(|_, owner| owner.expires_secs > now_secs);
+            let expires_secs = now_secs + self.ttl_secs;
+            match tasks.get_mut(task_id) {
+                Some(owner) if owner.identity == identity => owner.expires_secs = expires_secs,
+                Some(_) => {}
+                None => {
+                    if tasks.len() >= MAX_TASKS_PER_BUCKET {
+                        let soonest = tasks
+                            .iter()
+                            .min_by_key(|(_, owner)| owner.expires_secs)
+                            .map(|(id, _)| id.clone());
+                        if let Some(soonest) = soonest {
+                            tasks.remove(&soonest);
+                        }
+                    }
+                    let owner = TaskOwner { identity: identity.clone(), expires_secs };
+                    tasks.insert(task_id.to_string(), owner);
+                }
+            }
+            serde_json::to_vec(&tasks).unwrap_or_default()
+        })?;
+        Ok(())
+    }
+
+    /// Check that the caller may mutate a task (tasks without an owner are open)
+    pub fn authorize(
+        &self,
+        store: &impl SharedStore,
+        task_id: &str,
+        identity: Option<&Identity>,
+        now_secs: u64,
+    ) -> Result<(), A2ASecurityError> {
+        let owner = parse(store.get(&bucket_key(task_id)).0.as_deref())
+            .remove(task_id)
+            .filter(|owner| owner.expires_secs > now_secs);
+        match (owner, identity) {
+            (None, _) => Ok(()),
+            (Some(owner), Some(identity)) if owner.identity == fingerprint(identity) => Ok(()),
+            (Some(_), _) => Err(A2ASecurityError::InsufficientPermissions(format!(
+                "task {} belongs to another identity",
+                task_id
+            ))),
+        }
+    }
+}
+
+impl Default for TaskOwners {
+    fn default() -> Self {
+        Self::new(DEFAULT_OWNER_TTL_SECS)
+    }
+}
+
+impl A2AOperation {
+    /// Whether the operation changes an existing task
+    pub fn mutates_task(&self) -> bool {
+        matches!(
+            self,
+            A2AOperation::SendMessage
+                | A2AOperation::StreamMessage
+                | A2AOperation::CancelTask
+                | A2AOperation::SetPushConfig
+                | A2AOperation::DeletePushConfig
+        )
+    }
+
+    /// Task targeted by the operation's JSON-RPC params
+    ///
+    /// Messages target a task only when they continue one (`message.taskId`).
+    pub fn task_id<'a>(&self, params: &'a Value) -> Option<&'a str> {
+        let id = match self {
+            A2AOperation::SendMessage | A2AOperation::StreamMessage => {
+                params.get("message").and_then(|m| m.get("taskId"))
+            }
+            A2AOperation::SetPushConfig => params.get("taskId"),
+            A2AOperation::GetExtendedCard => None,
+            _ => params.get("id"),
+        };
+        id.and_then(Value::as_str)
+    }
+}
+
+/// Fingerprint of an identity (scheme and credential)
+fn fingerprint(identity: &Identity) -> String {
+    let material = format!("{:?}\n{}", identity.scheme, identity.identifier);
+    to_hex(&sha256(material.as_bytes()))
+}
+
+/// Shared data key of the bucket holding a task (FNV-1a hash of the id)
+fn bucket_key(task_id: &str) -> String {
+    let hash = task_id.bytes().fold(0xcbf2_9ce4_8422_2325u64, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("ai_guard.a2a_tasks.{}", hash % BUCKETS)
+}
+
+fn parse(value: Option<&[u8]>) -> BTreeMap<String, TaskOwner> {
+    value
+        .and_then(|v| serde_json::from_slice(v).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::a2a::security::AuthScheme;
+    use crate::shared::MemorySharedStore;
+
+    fn bearer(token: &str) -> Identity {
+        Identity { scheme: AuthScheme::Bearer, identifier: token.to_string(), claims: None }
+    }
+
+    #[test]
+    fn test_only_creator_may_mutate() {
+        let store = MemorySharedStore::new();
+        let owners = TaskOwners::new(60);
+        let (alice, mallory) = (bearer("alice-token"), bearer("mallory-token"));
+        owners.record(&store, "t-1", Some(&alice), 0).unwrap();
+        // A later caller does not take the task over
+        owners.record(&store, "t-1", Some(&mallory), 1).unwrap();
+
+        assert!(owners.authorize(&store, "t-1", Some(&alice), 10).is_ok());
+        let denied = owners.authorize(&store, "t-1", Some(&mallory), 10);
+        assert!(matches!(denied, Err(A2ASecurityError::InsufficientPermissions(_))));
+        assert!(owners.authorize(&store, "t-1", None, 10).is_err());
+        // Same credential under another scheme is another identity
+        let api_key = Identity { scheme: AuthScheme::ApiKey, ..alice.clone() };
+        assert!(owners.authorize(&store, "t-1", Some(&api_key), 10).is_err());
+
+        // Unknown, anonymous and expired tasks have no owner
+        assert!(owners.authorize(&store, "t-2", Some(&mallory), 10).is_ok());
+        owners.record(&store, "t-3", None, 10).unwrap();
+        assert!(owners.authorize(&store, "t-3", Some(&mallory), 10).is_ok());
+        assert!(owners.authorize(&store, "t-1", Some(&mallory), 61).is_ok());
+    }
+
+    #[test]
+    fn test_credentials_are_not_stored() {
+        let store = MemorySharedStore::new();
+        TaskOwners::default().record(&store, "t-1", Some(&bearer("secret-token")), 0).unwrap();
+        let stored = store.get(&bucket_key("t-1")).0.unwrap();
+        assert!(!String::from_utf8(stored).unwrap().contains("secret-token"));
+    }
+
+    #[test]
+    fn test_task_targets() {
+        let params = serde_json::json!({"id": "t-1", "taskId": "t-2",
+            "message": {"messageId": "m-1", "taskId": "t-3"}});
+        assert_eq!(A2AOperation::CancelTask.task_id(&params), Some("t-1"));
+        assert_eq!(A2AOperation::SetPushConfig.task_id(&params), Some("t-2"));
+        assert_eq!(A2AOperation::SendMessage.task_id(&params), Some("t-3"));
+        let new_task = serde_json::json!({"message": {"messageId": "m-1"}});
+        assert_eq!(A2AOperation::StreamMessage.task_id(&new_task), None);
+        assert!(A2AOperation::CancelTask.mutates_task());
+        assert!(!A2AOperation::GetTask.mutates_task());
+    }
+}
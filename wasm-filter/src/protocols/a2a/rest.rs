@@ -0,0 +1,364 @@
+//! A2A HTTP+JSON (REST) Binding
+//!
+//! The REST binding carries the same operations as JSON-RPC, but the
+//! operation is named by the route rather than a `method` field:
+//!
+//! - `POST /v1/message:send`: `message/send`
+//! - `POST /v1/message:stream`: `message/stream`
+//! - `GET /v1/tasks/{id}?historyLength=N`: `tasks/get`
+//! - `POST /v1/tasks/{id}:cancel`: `tasks/cancel`
+//! - `POST /v1/tasks/{id}:subscribe`: `tasks/resubscribe`
+//! - `POST|GET /v1/tasks/{id}/pushNotificationConfigs`: set / list push configs
+//! - `GET|DELETE /v1/tasks/{id}/pushNotificationConfigs/{cid}`: get / delete a push config
+//! - `GET /v1/card`: `agent/getAuthenticatedExtendedCard`
+//!
+//! Each operation accepts the HTTP methods of its allowlist (by default the
+//! one above). Path parameters must be plain identifiers and only the
+//! query parameters an operation defines are accepted.
+
+use std::collections::BTreeMap;
+
+/// Route prefix of the REST binding
+const PREFIX: &str = "/v1/";
+
+/// Longest task or push config ID
+const MAX_ID_LEN: usize = 256;
+
+/// Largest `historyLength`
+const MAX_HISTORY_LENGTH: u32 = 10_000;
+
+/// A2A operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum A2AOperation {
+    /// Send a message
+    SendMessage,
+    /// Send a message and stream the task's events
+    StreamMessage,
+    /// Get a task
+    GetTask,
+    /// Cancel a task
+    CancelTask,
+    /// Stream the events of an existing task
+    ResubscribeTask,
+    /// Set a task's push notification config
+    SetPushConfig,
+    /// List a task's push notification configs
+    ListPushConfigs,
+    /// Get a push notification config
+    GetPushConfig,
+    /// Delete a push notification config
+    DeletePushConfig,
+    /// Get the authenticated extended agent card
+    GetExtendedCard,
+}
+
+impl A2AOperation {
+    /// All operations
+    pub const ALL: [A2AOperation; 10] = [
+        A2AOperation::SendMessage,
+        A2AOperation::StreamMessage,
+        A2AOperation::GetTask,
+        A2AOperation::CancelTask,
+        A2AOperation::ResubscribeTask,
+        A2AOperation::SetPushConfig,
+        A2AOperation::ListPushConfigs,
+        A2AOperation::GetPushConfig,
+        A2AOperation::DeletePushConfig,
+        A2AOperation::GetExtendedCard,
+    ];
+
+    /// JSON-RPC method name of the operation
+    pub fn rpc_method(&self) -> &'static str {
+        match self {
+            A2AOperation::SendMessage => "message/send",
+            A2AOperation::StreamMessage => "message/stream",
+            A2AOperation::GetTask => "tasks/get",
+            A2AOperation::CancelTask => "tasks/cancel",
+            A2AOperation::ResubscribeTask => "tasks/resubscribe",
+            A2AOperation::SetPushConfig => "tasks/pushNotificationConfig/set",
+            A2AOperation::ListPushConfigs => "tasks/pushNotificationConfig/list",
+            A2AOperation::GetPushConfig => "tasks/pushNotificationConfig/get",
+            A2AOperation::DeletePushConfig => "tasks/pushNotificationConfig/delete",
+            A2AOperation::GetExtendedCard => "agent/getAuthenticatedExtendedCard",
+        }
+    }
+
+    /// Operation of a JSON-RPC method name
+    pub fn from_rpc_method(method: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|op| op.rpc_method() == method)
+    }
+
+    /// HTTP method the binding specifies
+    pub fn http_method(&self) -> &'static str {
+        match self {
+            A2AOperation::GetTask
+            | A2AOperation::ListPushConfigs
+            | A2AOperation::GetPushConfig
+            | A2AOperation::GetExtendedCard => "GET",
+            A2AOperation::DeletePushConfig => "DELETE",
+            _ => "POST",
+        }
+    }
+
+    /// Query parameters the operation accepts
+    fn query_params(&self) -> &'static [&'static str] {
+        match self {
+            A2AOperation::GetTask => &["historyLength"],
+            _ => &[],
+        }
+    }
+}
+
+/// Per-operation HTTP method allowlists
+#[derive(Debug, Clone, Default)]
+pub struct RestMethodPolicy {
+    /// Overrides of the spec method (upper-case method names)
+    allowed: BTreeMap<A2AOperation, Vec<String>>,
+}
+
+impl RestMethodPolicy {
+    /// Allow exactly `methods` for `operation`
+    pub fn allow(mut self, operation: A2AOperation, methods: &[&str]) -> Self {
+        let methods = methods.iter().map(|m| m.to_ascii_uppercase()).collect();
+        self.allowed.insert(operation, methods);
+        self
+    }
+
+    /// Whether `method` may be used for `operation`
+    pub fn is_allowed(&self, operation: A2AOperation, method: &str) -> bool {
+        match self.allowed.get(&operation) {
+            Some(methods) => methods.iter().any(|m| m.eq_ignore_ascii_case(method)),
+            None => operation.http_method().eq_ignore_ascii_case(method),
+        }
+    }
+}
+
+/// A request of the REST binding
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestRoute {
+    /// Operation named by the route
+    pub operation: A2AOperation,
+    /// `{id}` of task routes
+    pub task_id: Option<String>,
+    /// `{cid}` of push config routes
+    pub config_id: Option<String>,
+    /// `historyLength` query parameter
+    pub history_length: Option<u32>,
+}
+
+impl RestRoute {
+    /// Whether a path belongs to the REST binding
+    pub fn is_rest_path(path: &str) -> bool {
+        let path = path.split('?').next().unwrap_or(path);
+        match path.strip_prefix(PREFIX) {
+            Some(rest) => {
+                rest.starts_with("message:")
+                    || rest == "tasks"
+                    || rest.starts_with("tasks/")
+                    || rest == "card"
+            }
+            None => false,
+        }
+    }
+
+    /// Parse and validate a request line
+    pub fn parse(
+        method: &str,
+        path: &str,
+        policy: &RestMethodPolicy,
+    ) -> Result<Self, A2ARestError> {
+        let (path, query) = path.split_once('?').unwrap_or((path, ""));
+        let (operation, task_id, config_id) = route_operation(method, path)?;
+        if !policy.is_allowed(operation, method) {
+            let method = method.to_string();
+            return Err(A2ARestError::MethodNotAllowed { operation, method });
+        }
+        let mut route = Self { operation, task_id, config_id, history_length: None };
+        route.parse_query(query)?;
+        Ok(route)
+    }
+
+    /// Validate the query string against the operation's parameters
+    fn parse_query(&mut self, query: &str) -> Result<(), A2ARestError> {
+        let mut seen: Vec<&str> = Vec::new();
+        for pair in query.split('&').filter(|p| !p.is_empty()) {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            if !self.operation.query_params().contains(&name) {
+                return Err(A2ARestError::InvalidQuery(format!("unknown parameter {}", name)));
+            }
+            if seen.contains(&name) {
+                return Err(A2ARestError::InvalidQuery(format!("duplicate parameter {}", name)));
+            }
+            seen.push(name);
+            // historyLength is the only parameter so far
+            match value.parse::<u32>() {
+                Ok(n) if n <= MAX_HISTORY_LENGTH && value.bytes().all(|b| b.is_ascii_digit()) => {
+                    self.history_length = Some(n)
+                }
+                _ => {
+                    let reason =
+                        format!("{} must be an integer up to {}", name, MAX_HISTORY_LENGTH);
+                    return Err(A2ARestError::InvalidQuery(reason));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Operation, task ID and push config ID named by a path
+fn route_operation(
+    method: &str,
+    path: &str,
+) -> Result<(A2AOperation, Option<String>, Option<String>), A2ARestError> {
+    let unknown = || A2ARestError::UnknownRoute(path.to_string());
+    let rest = path.strip_prefix(PREFIX).ok_or_else(unknown)?;
+    let operation = match rest {
+        "message:send" => A2AOperation::SendMessage,
+        "message:stream" => A2AOperation::StreamMessage,
+        "card" => A2AOperation::GetExtendedCard,
+        _ => {
+            let task = rest.strip_prefix("tasks/").ok_or_else(unknown)?;
+            let segments: Vec<&str> = task.split('/').collect();
+            let (id, action) = match segments[0].split_once(':') {
+                Some((id, action)) => (id, Some(action)),
+                None => (segments[0], None),
+            };
+            let task_id = Some(validate_id("task id", id)?);
+            let operation = match (action, &segments[1..]) {
+                (None, []) => A2AOperation::GetTask,
+                (Some("cancel"), []) => A2AOperation::CancelTask,
+                (Some("subscribe"), []) => A2AOperation::ResubscribeTask,
+                (None, ["pushNotificationConfigs"]) if method.eq_ignore_ascii_case("GET") => {
+                    A2AOperation::ListPushConfigs
+                }
+                (None, ["pushNotificationConfigs"]) => A2AOperation::SetPushConfig,
+                (None, ["pushNotificationConfigs", config]) => {
+                    let config_id = Some(validate_id("push config id", config)?);
+                    let operation = match method.eq_ignore_ascii_case("DELETE") {
+                        true => A2AOperation::DeletePushConfig,
+                        false => A2AOperation::GetPushConfig,
+                    };
+                    return Ok((operation, task_id, config_id));
+                }
+                _ => return Err(unknown()),
+            };
+            return Ok((operation, task_id, None));
+        }
+    };
+    Ok((operation, None, None))
+}
+
+/// Path parameters: unreserved URL characters only (no encoding, no traversal)
+fn validate_id(what: &'static str, id: &str) -> Result<String, A2ARestError> {
+    let valid = !id.is_empty()
+        && id.len() <= MAX_ID_LEN
+        && id != "."
+        && id != ".."
+        && id.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b'~'));
+    match valid {
+        true => Ok(id.to_string()),
+        false => Err(A2ARestError::InvalidPathParam(what)),
+    }
+}
+
+/// REST binding errors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum A2ARestError {
+    /// Not a route of the binding
+    UnknownRoute(String),
+    /// HTTP method outside the operation's allowlist
+    MethodNotAllowed { operation: A2AOperation, method: String },
+    /// Malformed task or push config ID
+    InvalidPathParam(&'static str),
+    /// Unknown, duplicate or malformed query parameter
+    InvalidQuery(String),
+}
+
+impl std::fmt::Display for A2ARestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            A2ARestError::UnknownRoute(path) => write!(f, "Unknown A2A route: {}", path),
+            A2ARestError::MethodNotAllowed { operation, method } => {
+                write!(f, "Method {} not allowed for {}", method, operation.rpc_method())
+            }
+            A2ARestError::InvalidPathParam(what) => write!(f, "Invalid {}", what),
+            A2ARestError::InvalidQuery(reason) => write!(f, "Invalid query: {}", reason),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(method: &str, path: &str) -> Result<RestRoute, A2ARestError> {
+        RestRoute::parse(method, path, &RestMethodPolicy::default())
+    }
+
+    #[test]
+    fn test_routes() {
+        let op = |method, path| parse(method, path).map(|r| r.operation);
+        assert_eq!(op("POST", "/v1/message:send"), Ok(A2AOperation::SendMessage));
+        assert_eq!(op("POST", "/v1/message:stream"), Ok(A2AOperation::StreamMessage));
+        assert_eq!(op("POST", "/v1/tasks/t-1:cancel"), Ok(A2AOperation::CancelTask));
+        assert_eq!(op("POST", "/v1/tasks/t-1:subscribe"), Ok(A2AOperation::ResubscribeTask));
+        let configs = "/v1/tasks/t-1/pushNotificationConfigs";
+        assert_eq!(op("POST", configs), Ok(A2AOperation::SetPushConfig));
+        assert_eq!(op("GET", configs), Ok(A2AOperation::ListPushConfigs));
+        let config = "/v1/tasks/t-1/pushNotificationConfigs/c9";
+        assert_eq!(op("DELETE", config), Ok(A2AOperation::DeletePushConfig));
+        assert_eq!(op("GET", "/v1/card"), Ok(A2AOperation::GetExtendedCard));
+
+        let route = parse("GET", "/v1/tasks/task_42?historyLength=10").unwrap();
+        assert_eq!(route.operation, A2AOperation::GetTask);
+        assert_eq!(route.task_id.as_deref(), Some("task_42"));
+        assert_eq!(route.history_length, Some(10));
+        assert_eq!(parse("GET", config).unwrap().config_id.as_deref(), Some("c9"));
+
+        assert!(matches!(op("POST", "/v1/message:delete"), Err(A2ARestError::UnknownRoute(_))));
+        assert!(matches!(op("POST", "/v1/tasks/t-1:pause"), Err(A2ARestError::UnknownRoute(_))));
+        assert!(RestRoute::is_rest_path("/v1/tasks/t-1?historyLength=1"));
+        assert!(!RestRoute::is_rest_path("/v1/chat/completions"));
+        assert_eq!(A2AOperation::from_rpc_method("tasks/get"), Some(A2AOperation::GetTask));
+    }
+
+    #[test]
+    fn test_method_allowlist() {
+        let denied = parse("GET", "/v1/message:send");
+        let expected = A2ARestError::MethodNotAllowed {
+            operation: A2AOperation::SendMessage,
+            method: "GET".into(),
+        };
+        assert_eq!(denied, Err(expected));
+        assert!(parse("DELETE", "/v1/tasks/t-1").is_err());
+
+        // Also allow POST for tasks/get; cancel disabled entirely
+        let policy = RestMethodPolicy::default()
+            .allow(A2AOperation::GetTask, &["get", "POST"])
+            .allow(A2AOperation::CancelTask, &[]);
+        assert!(RestRoute::parse("POST", "/v1/tasks/t-1", &policy).is_ok());
+        assert!(RestRoute::parse("POST", "/v1/tasks/t-1:cancel", &policy).is_err());
+    }
+
+    #[test]
+    fn test_path_and_query_validation() {
+        let invalid_id = Err(A2ARestError::InvalidPathParam("task id"));
+        assert_eq!(parse("GET", "/v1/tasks/.."), invalid_id);
+        assert_eq!(parse("GET", "/v1/tasks/a%2F..%2Fb"), invalid_id);
+        assert_eq!(parse("GET", "/v1/tasks/"), invalid_id);
+
+        let query = |path| match parse("GET", path) {
+            Err(A2ARestError::InvalidQuery(reason)) => reason,
+            other => panic!("expected a query error, got {:?}", other),
+        };
+        assert_eq!(query("/v1/tasks/t?limit=5"), "unknown parameter limit");
+        let duplicate = query("/v1/tasks/t?historyLength=1&historyLength=2");
+        assert_eq!(duplicate, "duplicate parameter historyLength");
+        assert!(query("/v1/tasks/t?historyLength=-1").contains("integer"));
+        assert!(query("/v1/tasks/t?historyLength=+5").contains("integer"));
+        assert!(query("/v1/tasks/t?historyLength=99999").contains("integer"));
+        let send = parse("POST", "/v1/message:send?x=1");
+        assert!(matches!(send, Err(A2ARestError::InvalidQuery(_))));
+    }
+}
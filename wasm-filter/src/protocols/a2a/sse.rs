@@ -0,0 +1,404 @@
+//! A2A SSE Stream Handler
+//!
+//! `message/stream` and `tasks/resubscribe` responses stream the task as
+//! Server-Sent Events. Each event carries one JSON object: a `task`, a
+//! `message`, a `status-update` or an `artifact-update` (wrapped in a
+//! JSON-RPC `result`, or keyed by type in the REST binding).
+//!
+//! Events are reassembled from chunks and checked against the task state
+//! machine: one task per stream, no events after a terminal state or the
+//! `final` event, and no return to `submitted`. Message and artifact text
+//! is scanned as it arrives; the chunks of an appended artifact are
+//! scanned as one text, so a pattern split across them still matches.
+
+use crate::protocols::mcp::sse::SseAction;
+use crate::streaming::{Pattern, RingBuffer, ScanResult, SseEvent, SseParser};
+use serde_json::Value;
+
+/// Largest event reassembled by default
+const DEFAULT_MAX_EVENT_SIZE: usize = 1024 * 1024;
+
+/// A2A task state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskState {
+    /// Received, not started
+    Submitted,
+    /// Being worked on
+    Working,
+    /// Waiting for the client
+    InputRequired,
+    /// Waiting for authentication
+    AuthRequired,
+    /// Finished successfully
+    Completed,
+    /// Canceled by the client
+    Canceled,
+    /// Failed
+    Failed,
+    /// Rejected by the agent
+    Rejected,
+}
+
+impl TaskState {
+    /// Parse a `status.state` value
+    pub fn parse(state: &str) -> Option<Self> {
+        match state {
+            "submitted" => Some(TaskState::Submitted),
+            "working" => Some(TaskState::Working),
+            "input-required" => Some(TaskState::InputRequired),
+            "auth-required" => Some(TaskState::AuthRequired),
+            "completed" => Some(TaskState::Completed),
+            "canceled" => Some(TaskState::Canceled),
+            "failed" => Some(TaskState::Failed),
+            "rejected" => Some(TaskState::Rejected),
+            _ => None,
+        }
+    }
+
+    /// Whether the task can no longer change
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            TaskState::Completed | TaskState::Canceled | TaskState::Failed | TaskState::Rejected
+        )
+    }
+
+    /// Whether a task in this state may move to `next`
+    pub fn can_become(&self, next: TaskState) -> bool {
+        !self.is_terminal() && (next != TaskState::Submitted || *self == TaskState::Submitted)
+    }
+}
+
+/// A2A SSE stream handler
+pub struct A2ASseHandler {
+    /// Event reassembly
+    parser: SseParser,
+    /// Largest event reassembled
+    max_event_size: usize,
+    /// Ring buffer for message and artifact text
+    ring_buffer: Option<RingBuffer>,
+    /// Task the stream is about
+    task_id: Option<String>,
+    /// Current task state
+    state: Option<TaskState>,
+    /// A final event or a terminal state was seen
+    finished: bool,
+    /// Artifact whose text is being scanned (appended chunks continue it)
+    artifact: Option<String>,
+}
+
+impl A2ASseHandler {
+    /// Create a new SSE handler
+    pub fn new() -> Self {
+        Self::with_max_event_size(DEFAULT_MAX_EVENT_SIZE)
+    }
+
+    /// Create a handler reassembling events of up to `max_event_size` bytes
+    pub fn with_max_event_size(max_event_size: usize) -> Self {
+        Self {
+            parser: SseParser::new(max_event_size),
+            max_event_size,
+            ring_buffer: None,
+            task_id: None,
+            state: None,
+            finished: false,
+            artifact: None,
+        }
+    }
+
+    /// Initialize ring buffer with patterns
+    pub fn init_patterns(&mut self, patterns: Vec<String>, buffer_size: usize) {
+        let patterns: Vec<Pattern> = patterns.iter().map(|s| Pattern::from_string(s)).collect();
+        self.ring_buffer = Some(RingBuffer::new(buffer_size, patterns));
+    }
+
+    /// Process an SSE chunk
+    pub fn process_chunk(&mut self, chunk: &[u8]) -> SseAction {
+        for event in self.parser.feed(chunk) {
+            if let Err(reason) = self.process_event(&event) {
+                return SseAction::Block(reason);
+            }
+        }
+        SseAction::Continue
+    }
+
+    /// Current task state
+    pub fn state(&self) -> Option<TaskState> {
+        self.state
+    }
+
+    /// Task the stream is about
+    pub fn task_id(&self) -> Option<&str> {
+        self.task_id.as_deref()
+    }
+
+    /// Reset handler state
+    pub fn reset(&mut self) {
+        self.parser = SseParser::new(self.max_event_size);
+        self.task_id = None;
+        self.state = None;
+        self.finished = false;
+        self.artifact = None;
+        if let Some(ref mut rb) = self.ring_buffer {
+            rb.reset();
+        }
+    }
+
+    fn process_event(&mut self, event: &SseEvent) -> Result<(), String> {
+        if event.data.is_empty() {
+            return Ok(());
+        }
+        if event.truncated {
+            return Err("A2A event exceeds max size".to_string());
+        }
+        let value: Value = serde_json::from_slice(&event.data)
+            .map_err(|e| format!("Invalid JSON in A2A event: {}", e))?;
+        if value.get("error").is_some() {
+            // JSON-RPC error: the stream ends
+            self.finished = true;
+            return Ok(());
+        }
+        let payload = value.get("result").unwrap_or(&value);
+        let (kind, body) = event_kind(payload)?;
+        if self.finished {
+            return Err(format!("A2A {} event after the final event", kind));
+        }
+
+        match kind {
+            "message" => {
+                self.scan_parts(body.get("parts"), "message", false)?;
+                // A message answers the request without a task
+                self.finished = true;
+            }
+            "task" => {
+                self.check_task(body.get("id"))?;
+                self.transition(body.pointer("/status/state"))?;
+                self.scan_parts(body.pointer("/status/message/parts"), "status message", false)?;
+                for message in array(body.get("history")) {
+                    self.scan_parts(message.get("parts"), "task history", false)?;
+                }
+                for artifact in array(body.get("artifacts")) {
+                    self.scan_artifact(artifact, false)?;
+                }
+            }
+            "status-update" => {
+                self.check_task(body.get("taskId"))?;
+                self.transition(body.pointer("/status/state"))?;
+                self.scan_parts(body.pointer("/status/message/parts"), "status message", false)?;
+                if body.get("final").and_then(Value::as_bool) == Some(true) {
+                    self.finished = true;
+                }
+            }
+            _ => {
+                self.check_task(body.get("taskId"))?;
+                let artifact =
+                    body.get("artifact").ok_or("A2A artifact-update without artifact")?;
+                let append = body.get("append").and_then(Value::as_bool) == Some(true);
+                self.scan_artifact(artifact, append)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// All events of the stream belong to one task
+    fn check_task(&mut self, id: Option<&Value>) -> Result<(), String> {
+        let id = id.and_then(Value::as_str).ok_or("A2A event without task ID")?;
+        match &self.task_id {
+            Some(task) if task != id => {
+                Err(format!("A2A event for task '{}' in the stream of task '{}'", id, task))
+            }
+            Some(_) => Ok(()),
+            None => {
+                self.task_id = Some(id.to_string());
+                Ok(())
+            }
+        }
+    }
+
+    fn transition(&mut self, state: Option<&Value>) -> Result<(), String> {
+        let name = state.and_then(Value::as_str).ok_or("A2A task status without state")?;
+        let next =
+            TaskState::parse(name).ok_or_else(|| format!("Unknown A2A task state '{}'", name))?;
+        if let Some(current) = self.state {
+            if !current.can_become(next) {
+                return Err(format!("Invalid A2A task transition {:?} -> {:?}", current, next));
+            }
+        }
+        self.state = Some(next);
+        if next.is_terminal() {
+            self.finished = true;
+        }
+        Ok(())
+    }
+
+    fn scan_artifact(&mut self, artifact: &Value, append: bool) -> Result<(), String> {
+        let id = artifact.get("artifactId").and_then(Value::as_str).unwrap_or_default();
+        let continues = append && self.artifact.as_deref() == Some(id);
+        self.artifact = Some(id.to_string());
+        let name = artifact.get("name").and_then(Value::as_str).unwrap_or(id);
+        self.scan_parts(artifact.get("parts"), &format!("artifact '{}'", name), continues)
+    }
+
+    /// Scan the text of message or artifact parts
+    fn scan_parts(
+        &mut self,
+        parts: Option<&Value>,
+        location: &str,
+        continues: bool,
+    ) -> Result<(), String> {
+        let rb = match self.ring_buffer.as_mut() {
+            Some(rb) => rb,
+            None => return Ok(()),
+        };
+        if !continues {
+            rb.break_match();
+        }
+        let texts = array(parts).filter_map(|part| part.get("text").and_then(Value::as_str));
+        for text in texts {
+            if let ScanResult::Match(m) = rb.process_chunk(text.as_bytes()) {
+                return Err(format!("Pattern '{}' detected in A2A {}", m.pattern_name, location));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for A2ASseHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Elements of an optional JSON array
+fn array(value: Option<&Value>) -> impl Iterator<Item = &Value> {
+    value.and_then(Value::as_array).into_iter().flatten()
+}
+
+/// Kind and body of a streamed object
+fn event_kind(payload: &Value) -> Result<(&'static str, &Value), String> {
+    const KINDS: [&str; 4] = ["message", "task", "status-update", "artifact-update"];
+    // REST binding: {"statusUpdate": {...}}
+    const KEYS: [&str; 4] = ["message", "task", "statusUpdate", "artifactUpdate"];
+    if let Some(kind) = payload.get("kind").and_then(Value::as_str) {
+        return match KINDS.iter().find(|k| **k == kind) {
+            Some(kind) => Ok((kind, payload)),
+            None => Err(format!("Unknown A2A event kind '{}'", kind)),
+        };
+    }
+    for (kind, key) in KINDS.iter().zip(KEYS) {
+        if let Some(body) = payload.get(key) {
+            return Ok((kind, body));
+        }
+    }
+    Err("A2A event of unknown kind".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scanning_handler() -> A2ASseHandler {
+        let mut handler = A2ASseHandler::new();
+        handler.init_patterns(vec!["jailbreak".to_string()], 4096);
+        handler
+    }
+
+    fn sse(result: &str) -> Vec<u8> {
+        format!("data: {{\"jsonrpc\":\"2.0\",\"id\":1,\"result\":{}}}\n\n", result).into_bytes()
+    }
+
+    fn status(state: &str, final_event: bool) -> Vec<u8> {
+        sse(&format!(
+            r#"{{"kind":"status-update","taskId":"t1","status":{{"state":"{}"}},"final":{}}}"#,
+            state, final_event
+        ))
+    }
+
+    fn artifact(text: &str, append: bool) -> Vec<u8> {
+        let artifact = format!(r#"{{"artifactId":"a1","parts":[{{"text":"{}"}}]}}"#, text);
+        sse(&format!(
+            r#"{{"kind":"artifact-update","taskId":"t1","append":{},"artifact":{}}}"#,
+            append, artifact
+        ))
+    }
+
+    fn blocked(action: SseAction) -> String {
+        match action {
+            SseAction::Block(reason) => reason,
+            SseAction::Continue => panic!("expected a block"),
+        }
+    }
+
+    #[test]
+    fn test_task_lifecycle() {
+        let mut handler = scanning_handler();
+        let mut stream =
+            sse(r#"{"kind":"task","id":"t1","contextId":"c1","status":{"state":"submitted"}}"#);
+        stream.extend(status("working", false));
+        stream.extend(artifact("partial ", false));
+        stream.extend(status("completed", true));
+        // Split anywhere
+        for chunk in stream.chunks(7) {
+            assert!(matches!(handler.process_chunk(chunk), SseAction::Continue));
+        }
+        assert_eq!(handler.state(), Some(TaskState::Completed));
+        assert_eq!(handler.task_id(), Some("t1"));
+
+        let reason = blocked(handler.process_chunk(&status("working", false)));
+        assert_eq!(reason, "A2A status-update event after the final event");
+    }
+
+    #[test]
+    fn test_state_machine_violations() {
+        let mut handler = scanning_handler();
+        handler.process_chunk(&status("working", false));
+        let reason = blocked(handler.process_chunk(&status("submitted", false)));
+        assert_eq!(reason, "Invalid A2A task transition Working -> Submitted");
+
+        let mut handler = scanning_handler();
+        handler.process_chunk(&status("working", false));
+        let other = sse(r#"{"kind":"status-update","taskId":"t2","status":{"state":"working"}}"#);
+        assert!(blocked(handler.process_chunk(&other)).contains("task 't2'"));
+
+        let mut handler = scanning_handler();
+        let reason = blocked(handler.process_chunk(&status("paused", false)));
+        assert_eq!(reason, "Unknown A2A task state 'paused'");
+        let reason = blocked(scanning_handler().process_chunk(b"data: {nope\n\n"));
+        assert!(reason.starts_with("Invalid JSON"));
+    }
+
+    #[test]
+    fn test_appended_artifact_text_is_scanned_as_one() {
+        let mut handler = scanning_handler();
+        let first = handler.process_chunk(&artifact("ready to jail", false));
+        assert!(matches!(first, SseAction::Continue));
+        let reason = blocked(handler.process_chunk(&artifact("break now", true)));
+        assert_eq!(reason, "Pattern 'jailbreak' detected in A2A artifact 'a1'");
+
+        // A new artifact starts a new text
+        let mut handler = scanning_handler();
+        handler.process_chunk(&artifact("ready to jail", false));
+        let second = handler.process_chunk(&artifact("break now", false));
+        assert!(matches!(second, SseAction::Continue));
+    }
+
+    #[test]
+    fn test_rest_binding_and_messages() {
+        let mut handler = scanning_handler();
+        let message = r#"{"parts":[{"text":"please jailbreak"}]}"#;
+        let update = format!(
+            "data: {{\"statusUpdate\":{{\"taskId\":\"t1\",\"status\":{{\"state\":\"working\",\
+             \"message\":{}}}}}}}\n\n",
+            message
+        );
+        let reason = blocked(handler.process_chunk(update.as_bytes()));
+        assert_eq!(reason, "Pattern 'jailbreak' detected in A2A status message");
+
+        // A message ends the stream
+        let mut handler = scanning_handler();
+        let message =
+            sse(r#"{"kind":"message","messageId":"m1","role":"agent","parts":[{"text":"hi"}]}"#);
+        assert!(matches!(handler.process_chunk(&message), SseAction::Continue));
+        assert!(matches!(handler.process_chunk(&status("working", false)), SseAction::Block(_)));
+    }
+}
@@ -4,7 +4,7 @@
 //! Checks for prompt injection in message content.
 
 use serde::{Deserialize, Serialize};
-use crate::governance::PromptInjectionDetector;
+use crate::governance::{InjectionMatch, PromptInjectionDetector};
 
 /// A2A message role
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -123,7 +123,7 @@ pub struct A2ATask {
 
 /// A2A validator
 pub struct A2AValidator {
-    /// Prompt injection detector
+    /// Prompt injection detector, reset between text fields
     injection_detector: PromptInjectionDetector,
 }
 
@@ -136,7 +136,7 @@ impl A2AValidator {
     }
 
     /// Validate an A2A message
-    pub fn validate_message(&self, body: &[u8]) -> Result<A2AMessage, A2AValidationError> {
+    pub fn validate_message(&mut self, body: &[u8]) -> Result<A2AMessage, A2AValidationError> {
         // Parse message
         let message: A2AMessage = serde_json::from_slice(body)
             .map_err(|e| A2AValidationError::InvalidJson(e.to_string()))?;
@@ -153,8 +153,7 @@ impl A2AValidator {
         // Scan parts for prompt injection
         for (i, part) in message.parts.iter().enumerate() {
             if let Some(ref text) = part.text {
-                let mut detector = PromptInjectionDetector::new();
-                if let Some(injection) = detector.scan_str(text) {
+                if let Some(injection) = self.scan_text(text) {
                     return Err(A2AValidationError::PromptInjection(format!(
                         "Prompt injection in part {}: {}",
                         i, injection.pattern
@@ -167,7 +166,7 @@ impl A2AValidator {
     }
 
     /// Validate an A2A task
-    pub fn validate_task(&self, body: &[u8]) -> Result<A2ATask, A2AValidationError> {
+    pub fn validate_task(&mut self, body: &[u8]) -> Result<A2ATask, A2AValidationError> {
         // Parse task
         let task: A2ATask = serde_json::from_slice(body)
             .map_err(|e| A2AValidationError::InvalidJson(e.to_string()))?;
@@ -189,8 +188,7 @@ impl A2AValidator {
         for message in &task.messages {
             for part in &message.parts {
                 if let Some(ref text) = part.text {
-                    let mut detector = PromptInjectionDetector::new();
-                    if let Some(injection) = detector.scan_str(text) {
+                    if let Some(injection) = self.scan_text(text) {
                         return Err(A2AValidationError::PromptInjection(format!(
                             "Prompt injection in task message: {}",
                             injection.pattern
@@ -204,14 +202,14 @@ impl A2AValidator {
     }
 
     /// Validate state transition
-    fn validate_state_transition(&self, state: &A2ATaskState) -> Result<(), A2AValidationError> {
+    fn validate_state_transition(&self, _state: &A2ATaskState) -> Result<(), A2AValidationError> {
         // All states are valid on their own
         // Real state machine validation would need previous state
         Ok(())
     }
 
     /// Validate an artifact
-    fn validate_artifact(&self, artifact: &A2AArtifact) -> Result<(), A2AValidationError> {
+    fn validate_artifact(&mut self, artifact: &A2AArtifact) -> Result<(), A2AValidationError> {
         if artifact.name.is_empty() {
             return Err(A2AValidationError::MissingField("artifact.name".to_string()));
         }
@@ -219,8 +217,7 @@ impl A2AValidator {
         // Scan artifact parts for injection
         for part in &artifact.parts {
             if let Some(ref text) = part.text {
-                let mut detector = PromptInjectionDetector::new();
-                if let Some(injection) = detector.scan_str(text) {
+                if let Some(injection) = self.scan_text(text) {
                     return Err(A2AValidationError::PromptInjection(format!(
                         "Prompt injection in artifact '{}': {}",
                         artifact.name, injection.pattern
@@ -231,6 +228,12 @@ impl A2AValidator {
 
         Ok(())
     }
+
+    /// Scan one text field; matches never span fields
+    fn scan_text(&mut self, text: &str) -> Option<InjectionMatch> {
+        self.injection_detector.reset();
+        self.injection_detector.scan_str(text)
+    }
 }
 
 impl Default for A2AValidator {
@@ -272,7 +275,7 @@ mod tests {
 
     #[test]
     fn test_valid_message() {
-        let validator = A2AValidator::new();
+        let mut validator = A2AValidator::new();
         let body = r#"{
             "messageId": "msg-123",
             "role": "ROLE_USER",
@@ -285,7 +288,7 @@ mod tests {
 
     #[test]
     fn test_missing_message_id() {
-        let validator = A2AValidator::new();
+        let mut validator = A2AValidator::new();
         let body = r#"{
             "messageId": "",
             "role": "ROLE_USER",
@@ -298,7 +301,7 @@ mod tests {
 
     #[test]
     fn test_prompt_injection_in_message() {
-        let validator = A2AValidator::new();
+        let mut validator = A2AValidator::new();
         let body = r#"{
             "messageId": "msg-123",
             "role": "ROLE_USER",
@@ -309,9 +312,30 @@ mod tests {
         assert!(matches!(result, Err(A2AValidationError::PromptInjection(_))));
     }
 
+    #[test]
+    fn test_detector_reused_across_fields() {
+        let mut validator = A2AValidator::new();
+        // A pattern split over two parts is not one match
+        let split = r#"{"messageId": "m1", "role": "ROLE_USER",
+            "parts": [{"text": "ignore previous"}, {"text": " instructions"}]}"#;
+        assert!(validator.validate_message(split.as_bytes()).is_ok());
+
+        let injected = r#"{"messageId": "m2", "role": "ROLE_USER",
+            "parts": [{"text": "hi"}, {"text": "jailbreak now"}]}"#;
+        match validator.validate_message(injected.as_bytes()) {
+            Err(A2AValidationError::PromptInjection(reason)) => {
+                assert!(reason.contains("part 1"), "{}", reason)
+            }
+            other => panic!("expected injection, got {:?}", other),
+        }
+        // State left by the match does not leak into the next message
+        let clean = r#"{"messageId": "m3", "role": "ROLE_USER", "parts": [{"text": "now"}]}"#;
+        assert!(validator.validate_message(clean.as_bytes()).is_ok());
+    }
+
     #[test]
     fn test_valid_task() {
-        let validator = A2AValidator::new();
+        let mut validator = A2AValidator::new();
         let body = r#"{
             "taskId": "task-123",
             "status": {"state": "pending"},
@@ -0,0 +1,162 @@
+//! Anthropic Messages API
+//!
+//! Request-side knowledge of `/v1/messages` bodies: the top-level `system`
+//! prompt, the `messages` array, and typed content blocks. A `tool_result`
+//! block carries tool output even though it sits in a `user` message.
+//! Response-side: text deltas and `tool_use` blocks of messages.
+
+use super::openai::{split_index, ChatField, ChatRole};
+use super::{ToolCall, ToolCallDelta};
+use serde_json::Value;
+
+/// Content block type whose strings are tool output
+pub const TOOL_RESULT_BLOCK: &str = "tool_result";
+
+/// Classify a JSON path of a Messages API request
+pub fn classify(path: &str) -> ChatField {
+    if path == "$.model" {
+        return ChatField::Model;
+    }
+    if path == "$.system" || path.starts_with("$.system[") {
+        return ChatField::System;
+    }
+    match split_index(path, "$.messages[") {
+        Some((i, ".role")) => ChatField::Role(i),
+        Some((i, rest)) => match rest.strip_prefix(".content[") {
+            Some(block) => match split_index(block, "") {
+                Some((j, ".type")) => ChatField::BlockType(i, j),
+                Some((j, _)) => ChatField::Block(i, j),
+                None => ChatField::Message(i),
+            },
+            None => ChatField::Message(i),
+        },
+        None => ChatField::Other,
+    }
+}
+
+/// Role implied by a content block type, overriding the message role
+pub fn block_role(block_type: &str) -> Option<ChatRole> {
+    (block_type == TOOL_RESULT_BLOCK).then_some(ChatRole::Tool)
+}
+
+/// Text delta in one streamed event, as `(content block index, text)`
+pub fn stream_delta(event: &Value) -> Option<(u64, &str)> {
+    if event["type"] != "content_block_delta" || event["delta"]["type"] != "text_delta" {
+        return None;
+    }
+    let text = event["delta"]["text"].as_str()?;
+    Some((event["index"].as_u64().unwrap_or(0), text))
+}
+
+/// Tool-use argument fragment in one streamed event
+pub fn stream_tool_call_delta(event: &Value) -> Option<ToolCallDelta<'_>> {
+    let call = (0, event["index"].as_u64().unwrap_or(0));
+    match event["type"].as_str()? {
+        "content_block_start" if event["content_block"]["type"] == "tool_use" => {
+            let name = event["content_block"]["name"].as_str();
+            Some(ToolCallDelta { call, name, arguments: "" })
+        }
+        "content_block_delta" if event["delta"]["type"] == "input_json_delta" => {
+            let arguments = event["delta"]["partial_json"].as_str()?;
+            Some(ToolCallDelta { call, name: None, arguments })
+        }
+        _ => None,
+    }
+}
+
+/// `tool_use` blocks in a complete (non-streamed) response body
+pub fn tool_calls(body: &Value) -> Vec<ToolCall> {
+    body["content"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|block| block["type"] == "tool_use")
+        .map(|block| ToolCall {
+            name: block["name"].as_str().unwrap_or_default().to_string(),
+            arguments: block["input"].to_string(),
+        })
+        .collect()
+}
+
+/// Whether a request path is the Messages API endpoint
+pub fn is_messages_path(path: &str) -> bool {
+    path.split('?')
+        .next()
+        .is_some_and(|p| p.trim_end_matches('/').ends_with("/v1/messages"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_paths() {
+        assert_eq!(classify("$.system"), ChatField::System);
+        assert_eq!(classify("$.system[0].text"), ChatField::System);
+        assert_eq!(classify("$.messages[1].role"), ChatField::Role(1));
+        assert_eq!(classify("$.messages[1].content"), ChatField::Message(1));
+        assert_eq!(classify("$.messages[2].content[0].type"), ChatField::BlockType(2, 0));
+        assert_eq!(classify("$.messages[2].content[3].content[0].text"), ChatField::Block(2, 3));
+        assert_eq!(classify("$.messages[2].content[1].input.query"), ChatField::Block(2, 1));
+        assert_eq!(classify("$.tools[0].description"), ChatField::Other);
+        assert_eq!(classify("$.metadata.user_id"), ChatField::Other);
+    }
+
+    #[test]
+    fn test_block_role() {
+        assert_eq!(block_role("tool_result"), Some(ChatRole::Tool));
+        assert_eq!(block_role("tool_use"), None);
+        assert_eq!(block_role("text"), None);
+    }
+
+    #[test]
+    fn test_stream_delta() {
+        let event: Value = serde_json::from_str(
+            r#"{"type":"content_block_delta","index":1,"delta":{"type":"text_delta","text":"hi"}}"#,
+        )
+        .unwrap();
+        assert_eq!(stream_delta(&event), Some((1, "hi")));
+
+        let event: Value = serde_json::from_str(
+            r#"{"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta"}}"#,
+        )
+        .unwrap();
+        assert_eq!(stream_delta(&event), None);
+    }
+
+    #[test]
+    fn test_tool_calls() {
+        let event: Value = serde_json::from_str(
+            r#"{"type":"content_block_start","index":2,
+                "content_block":{"type":"tool_use","name":"bash","input":{}}}"#,
+        )
+        .unwrap();
+        let delta = stream_tool_call_delta(&event).unwrap();
+        assert_eq!((delta.call, delta.name), ((0, 2), Some("bash")));
+
+        let event: Value = serde_json::from_str(
+            r#"{"type":"content_block_delta","index":2,
+                "delta":{"type":"input_json_delta","partial_json":"{\"command\": \"ls"}}"#,
+        )
+        .unwrap();
+        assert_eq!(stream_tool_call_delta(&event).unwrap().arguments, r#"{"command": "ls"#);
+
+        let body: Value = serde_json::from_str(
+            r#"{"content":[{"type":"text","text":"ok"},
+                {"type":"tool_use","name":"bash","input":{"command":"ls"}}]}"#,
+        )
+        .unwrap();
+        let calls = tool_calls(&body);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].name, "bash");
+        assert_eq!(calls[0].arguments, r#"{"command":"ls"}"#);
+    }
+
+    #[test]
+    fn test_messages_path() {
+        assert!(is_messages_path("/v1/messages"));
+        assert!(is_messages_path("/anthropic/v1/messages?beta=true"));
+        assert!(!is_messages_path("/v1/messages/count_tokens"));
+        assert!(!is_messages_path("/v1/chat/completions"));
+    }
+}
@@ -0,0 +1,122 @@
+//! gRPC status
+//!
+//! A gRPC call reports its outcome in the `grpc-status` and `grpc-message`
+//! trailers, after the last message (or in the headers of a trailers-only
+//! response). The HTTP status is 200 either way, so this is where a failed
+//! call shows up.
+
+/// `grpc-status` header or trailer
+pub const STATUS_HEADER: &str = "grpc-status";
+
+/// `grpc-message` header or trailer
+pub const MESSAGE_HEADER: &str = "grpc-message";
+
+/// Canonical status code names, indexed by code
+const CODE_NAMES: [&str; 17] = [
+    "ok",
+    "cancelled",
+    "unknown",
+    "invalid_argument",
+    "deadline_exceeded",
+    "not_found",
+    "already_exists",
+    "permission_denied",
+    "resource_exhausted",
+    "failed_precondition",
+    "aborted",
+    "out_of_range",
+    "unimplemented",
+    "internal",
+    "unavailable",
+    "data_loss",
+    "unauthenticated",
+];
+
+/// Outcome of a gRPC call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrpcStatus {
+    /// Status code (0 = OK)
+    pub code: u32,
+    /// Decoded `grpc-message`, if any
+    pub message: Option<String>,
+}
+
+impl GrpcStatus {
+    /// Read the status from trailers (or trailers-only response headers)
+    pub fn from_headers(headers: &[(String, String)]) -> Option<Self> {
+        let find = |name: &str| {
+            headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+        };
+        let code = find(STATUS_HEADER)?.trim().parse().ok()?;
+        let message = find(MESSAGE_HEADER).map(percent_decode).filter(|m| !m.is_empty());
+        Some(Self { code, message })
+    }
+
+    /// Whether the call succeeded
+    pub fn is_ok(&self) -> bool {
+        self.code == 0
+    }
+
+    /// Snake_case code name (metric label), `unknown` for codes outside the spec
+    pub fn name(&self) -> &'static str {
+        CODE_NAMES.get(self.code as usize).copied().unwrap_or("unknown")
+    }
+}
+
+/// Decode a percent-encoded `grpc-message` (invalid escapes are kept as is)
+pub(crate) fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok());
+        match (bytes[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(n, v)| (n.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_from_trailers() {
+        let trailers = headers(&[("grpc-status", "7"), ("grpc-message", "agent%20not%20allowed")]);
+        let status = GrpcStatus::from_headers(&trailers).unwrap();
+        assert_eq!(status.code, 7);
+        assert_eq!(status.name(), "permission_denied");
+        assert_eq!(status.message.as_deref(), Some("agent not allowed"));
+        assert!(!status.is_ok());
+
+        let ok = GrpcStatus::from_headers(&headers(&[("Grpc-Status", "0")])).unwrap();
+        assert!(ok.is_ok());
+        assert_eq!(ok.message, None);
+    }
+
+    #[test]
+    fn test_missing_or_invalid_status() {
+        assert_eq!(GrpcStatus::from_headers(&headers(&[("x-other", "1")])), None);
+        assert_eq!(GrpcStatus::from_headers(&headers(&[("grpc-status", "abc")])), None);
+        let odd = GrpcStatus::from_headers(&headers(&[("grpc-status", "42")])).unwrap();
+        assert_eq!(odd.name(), "unknown");
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("caf%C3%A9 100%"), "café 100%");
+        assert_eq!(percent_decode("%zz%4"), "%zz%4");
+    }
+}
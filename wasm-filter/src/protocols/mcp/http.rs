@@ -4,18 +4,24 @@
 //! Validates JSON-RPC 2.0 format and checks method permissions.
 
 use super::jsonrpc::{JsonRpcRequest, JsonRpcError, JsonRpcResponse};
+use super::method_policy::MethodPolicy;
 use super::McpValidationError;
 
 /// MCP HTTP transport handler
 pub struct McpHttpHandler {
-    /// Allowed methods
-    allowed_methods: Vec<String>,
+    /// Method allow/deny policy
+    method_policy: MethodPolicy,
 }
 
 impl McpHttpHandler {
     /// Create a new HTTP handler
     pub fn new(allowed_methods: Vec<String>) -> Self {
-        Self { allowed_methods }
+        Self::with_policy(MethodPolicy::new(allowed_methods, Vec::new()))
+    }
+
+    /// Create a handler with an allow/deny method policy
+    pub fn with_policy(method_policy: MethodPolicy) -> Self {
+        Self { method_policy }
     }
 
     /// Validate an HTTP request body
@@ -39,7 +45,7 @@ impl McpHttpHandler {
 
     /// Check if a method is allowed
     pub fn is_method_allowed(&self, method: &str) -> bool {
-        self.allowed_methods.iter().any(|m| m == "*" || m == method)
+        self.method_policy.is_allowed(method)
     }
 
     /// Create a blocked response
@@ -116,6 +122,19 @@ mod tests {
         assert!(handler.is_method_allowed("resources/read"));
     }
 
+    #[test]
+    fn test_glob_policy_with_deny() {
+        let handler = McpHttpHandler::with_policy(MethodPolicy::new(
+            vec!["tools/*".to_string()],
+            vec!["tools/call".to_string()],
+        ));
+        let body = r#"{"jsonrpc":"2.0","method":"tools/call","id":1}"#;
+
+        let result = handler.validate_request(body.as_bytes());
+        assert!(matches!(result, Err(McpValidationError::MethodNotAllowed(_))));
+        assert!(handler.is_method_allowed("tools/list"));
+    }
+
     #[test]
     fn test_batch_request() {
         let handler = McpHttpHandler::new(vec!["*".to_string()]);
@@ -169,6 +169,39 @@ impl JsonRpcError {
             })),
         }
     }
+
+    /// AI-Guard error: Replayed request or message id
+    pub fn replay_detected(reason: &str) -> Self {
+        Self {
+            code: -32001,
+            message: format!("Replay detected: {}", reason),
+            data: Some(serde_json::json!({
+                "blocked_by": "ai-guard",
+                "reason": reason
+            })),
+        }
+    }
+
+    /// AI-Guard error: Rate limited; the client may retry after `retry_after_secs`
+    pub fn rate_limited(reason: &str, retry_after_secs: u64) -> Self {
+        Self {
+            code: -32003,
+            message: format!("Rate limited: {}", reason),
+            data: Some(serde_json::json!({
+                "blocked_by": "ai-guard",
+                "reason": reason,
+                "retry_after_secs": retry_after_secs
+            })),
+        }
+    }
+
+    /// Add the request ID to the error data, for correlation with audit records
+    pub fn with_request_id(mut self, request_id: Option<&str>) -> Self {
+        if let (Some(id), Some(Value::Object(data))) = (request_id, self.data.as_mut()) {
+            data.insert("request_id".to_string(), Value::String(id.to_string()));
+        }
+        self
+    }
 }
 
 /// JSON-RPC validation errors
@@ -196,6 +229,274 @@ impl std::fmt::Display for JsonRpcValidationError {
     }
 }
 
+/// Longest key, `id` or `method` value captured by [`JsonRpcSniffer`]
+const MAX_SNIFF_TOKEN: usize = 128;
+
+/// Longest `params` member captured by [`JsonRpcSniffer`]
+pub const MAX_PARAMS_LEN: usize = 256 * 1024;
+
+/// What the sniffer is currently capturing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Capture {
+    None,
+    Key,
+    Id,
+    Method,
+    Params,
+}
+
+/// Streaming sniffer for the JSON-RPC envelope of a request body
+///
+/// Fed chunk by chunk alongside the body scanner, it records whether the
+/// top-level object carries a `jsonrpc` member and captures its `id` and
+/// `method`, in O(1) memory. Clients often serialize `id` after `params`, so the id may
+/// not be known if the body is blocked before it is seen.
+///
+/// With [`capture_params`](Self::capture_params), the `params` object or
+/// array is kept too, up to `MAX_PARAMS_LEN` bytes, for the checks that
+/// read a call's tool name and arguments.
+#[derive(Debug, Clone)]
+pub struct JsonRpcSniffer {
+    depth: u32,
+    started: bool,
+    is_object: bool,
+    in_string: bool,
+    escaped: bool,
+    expect_key: bool,
+    /// Value capture started by the key just read
+    next_value: Capture,
+    capture: Capture,
+    token: Vec<u8>,
+    is_jsonrpc: bool,
+    id: Option<Value>,
+    method: Option<String>,
+    /// Whether `params` is captured
+    params_wanted: bool,
+    /// Raw `params` member while it is captured
+    params_raw: Vec<u8>,
+    params: Option<Value>,
+    /// `params` was longer than `MAX_PARAMS_LEN`
+    params_overflow: bool,
+}
+
+impl JsonRpcSniffer {
+    /// Create a new sniffer
+    pub fn new() -> Self {
+        Self {
+            depth: 0,
+            started: false,
+            is_object: false,
+            in_string: false,
+            escaped: false,
+            expect_key: false,
+            next_value: Capture::None,
+            capture: Capture::None,
+            token: Vec::new(),
+            is_jsonrpc: false,
+            id: None,
+            method: None,
+            params_wanted: false,
+            params_raw: Vec::new(),
+            params: None,
+            params_overflow: false,
+        }
+    }
+
+    /// Also capture the `params` member (call before the first chunk)
+    pub fn capture_params(&mut self) {
+        self.params_wanted = true;
+    }
+
+    /// Feed the next body chunk
+    pub fn observe(&mut self, chunk: &[u8]) {
+        for &b in chunk {
+            self.step(b);
+        }
+    }
+
+    /// Whether the body is a JSON-RPC request object
+    pub fn is_jsonrpc(&self) -> bool {
+        self.is_jsonrpc
+    }
+
+    /// Request id, if seen so far
+    pub fn id(&self) -> Option<&Value> {
+        self.id.as_ref()
+    }
+
+    /// Request method, if seen so far
+    pub fn method(&self) -> Option<&str> {
+        self.method.as_deref()
+    }
+
+    /// Request `params`, once the member is complete
+    ///
+    /// None if not captured, not seen yet, or longer than `MAX_PARAMS_LEN`.
+    pub fn params(&self) -> Option<&Value> {
+        self.params.as_ref()
+    }
+
+    /// Whether `params` was too long to capture
+    pub fn params_overflow(&self) -> bool {
+        self.params_overflow
+    }
+
+    fn capturing_value(&self) -> bool {
+        matches!(self.capture, Capture::Id | Capture::Method)
+    }
+
+    fn step(&mut self, b: u8) {
+        // Everything inside `params` is kept as sent
+        if self.capture == Capture::Params && self.depth > 1 {
+            self.push_params(b);
+        }
+        if self.in_string {
+            if self.escaped {
+                self.escaped = false;
+            } else if b == b'\\' {
+                self.escaped = true;
+            } else if b == b'"' {
+                self.in_string = false;
+                match self.capture {
+                    Capture::Key => self.finish_key(),
+                    Capture::Id | Capture::Method => {
+                        self.push(b);
+                        self.finish_value();
+                    }
+                    Capture::Params | Capture::None => {}
+                }
+                return;
+            }
+            self.push(b);
+            return;
+        }
+
+        match b {
+            b' ' | b'\t' | b'\r' | b'\n' => {
+                if self.capturing_value() && !self.token.is_empty() {
+                    self.finish_value();
+                }
+            }
+            b'{' | b'[' => {
+                if !self.started {
+                    self.started = true;
+                    self.is_object = b == b'{';
+                }
+                if self.capturing_value() {
+                    // Objects and arrays are not valid ids or methods
+                    self.capture = Capture::None;
+                }
+                if self.capture == Capture::Params && self.depth == 1 {
+                    self.params_raw.clear();
+                    self.push_params(b);
+                }
+                self.depth += 1;
+                if self.depth == 1 {
+                    self.expect_key = true;
+                }
+            }
+            b'}' | b']' => {
+                if self.depth == 1 && self.capturing_value() {
+                    self.finish_value();
+                }
+                self.depth = self.depth.saturating_sub(1);
+                if self.capture == Capture::Params && self.depth == 1 {
+                    self.finish_params();
+                }
+            }
+            b'"' => {
+                self.in_string = true;
+                if self.depth == 1 && self.is_object && self.expect_key {
+                    self.capture = Capture::Key;
+                    self.token.clear();
+                } else if self.capturing_value() {
+                    self.push(b);
+                } else if self.capture == Capture::Params && self.depth == 1 {
+                    // Only objects and arrays are valid params
+                    self.capture = Capture::None;
+                }
+            }
+            b':' if self.depth == 1 => {
+                self.expect_key = false;
+                if self.next_value != Capture::None {
+                    self.capture = std::mem::replace(&mut self.next_value, Capture::None);
+                    self.token.clear();
+                }
+            }
+            b',' if self.depth == 1 => {
+                if self.capturing_value() {
+                    self.finish_value();
+                }
+                self.expect_key = true;
+            }
+            _ => self.push(b),
+        }
+    }
+
+    fn push(&mut self, b: u8) {
+        if matches!(self.capture, Capture::None | Capture::Params) {
+            return;
+        }
+        if self.token.len() >= MAX_SNIFF_TOKEN {
+            // Oversized: give up on this key or id
+            self.capture = Capture::None;
+            self.token.clear();
+            return;
+        }
+        self.token.push(b);
+    }
+
+    fn push_params(&mut self, b: u8) {
+        if self.params_raw.len() >= MAX_PARAMS_LEN {
+            // Oversized: given up on, and reported as such
+            self.capture = Capture::None;
+            self.params_overflow = true;
+            self.params_raw = Vec::new();
+            return;
+        }
+        self.params_raw.push(b);
+    }
+
+    fn finish_params(&mut self) {
+        let raw = std::mem::take(&mut self.params_raw);
+        self.params = serde_json::from_slice::<Value>(&raw).ok();
+        self.capture = Capture::None;
+    }
+
+    fn finish_key(&mut self) {
+        match self.token.as_slice() {
+            b"jsonrpc" => self.is_jsonrpc = true,
+            b"id" => self.next_value = Capture::Id,
+            b"method" => self.next_value = Capture::Method,
+            b"params" if self.params_wanted => self.next_value = Capture::Params,
+            _ => {}
+        }
+        self.capture = Capture::None;
+        self.token.clear();
+    }
+
+    fn finish_value(&mut self) {
+        let value = serde_json::from_slice::<Value>(&self.token).ok();
+        match self.capture {
+            Capture::Id => {
+                self.id = value.filter(|v| v.is_string() || v.is_number() || v.is_null());
+            }
+            Capture::Method => {
+                self.method = value.and_then(|v| v.as_str().map(str::to_string));
+            }
+            Capture::None | Capture::Key | Capture::Params => {}
+        }
+        self.capture = Capture::None;
+        self.token.clear();
+    }
+}
+
+impl Default for JsonRpcSniffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Common MCP method names
 pub mod methods {
     /// Initialize connection
@@ -282,5 +583,77 @@ mod tests {
         let response = JsonRpcResponse::error(Value::Number(1.into()), error);
 
         assert!(response.is_error());
+
+        let error = JsonRpcError::policy_violation("jailbreak").with_request_id(Some("req-9"));
+        assert_eq!(error.data.unwrap()["request_id"], "req-9");
+
+        let error = JsonRpcError::replay_detected("request id 7 already seen");
+        assert_eq!(error.code, -32001);
+        assert_ne!(error.code, JsonRpcError::policy_violation("x").code);
+
+        let error = JsonRpcError::rate_limited("too many requests", 30);
+        assert_eq!(error.code, -32003);
+        assert_eq!(error.data.unwrap()["retry_after_secs"], 30);
+    }
+
+    #[test]
+    fn test_sniff_id_across_chunks() {
+        let body = br#"{"method":"tools/call","params":{"id":"inner","x":[1,{"id":2}]},"jsonrpc":"2.0","id":"req-\"7\""}"#;
+        let mut sniffer = JsonRpcSniffer::new();
+        for chunk in body.chunks(5) {
+            sniffer.observe(chunk);
+        }
+
+        assert!(sniffer.is_jsonrpc());
+        assert_eq!(sniffer.id(), Some(&Value::String("req-\"7\"".to_string())));
+        assert_eq!(sniffer.method(), Some("tools/call"));
+    }
+
+    #[test]
+    fn test_sniff_numeric_id() {
+        let mut sniffer = JsonRpcSniffer::new();
+        sniffer.observe(br#"{"jsonrpc": "2.0", "id" : 42 , "method": "ping"}"#);
+        assert_eq!(sniffer.id(), Some(&Value::Number(42.into())));
+
+        let mut sniffer = JsonRpcSniffer::new();
+        sniffer.observe(br#"{"jsonrpc":"2.0","method":"ping","id":7}"#);
+        assert_eq!(sniffer.id(), Some(&Value::Number(7.into())));
+        assert_eq!(sniffer.method(), Some("ping"));
+    }
+
+    #[test]
+    fn test_sniff_params() {
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","params":{"name":"run","arguments":{"cmd":"ls \"}\" ]","n":[1,{}]}},"id":3}"#;
+        let mut sniffer = JsonRpcSniffer::new();
+        sniffer.observe(body);
+        assert_eq!(sniffer.params(), None);
+
+        let mut sniffer = JsonRpcSniffer::new();
+        sniffer.capture_params();
+        for chunk in body.chunks(3) {
+            sniffer.observe(chunk);
+        }
+        let params = sniffer.params().unwrap();
+        assert_eq!(params["name"], "run");
+        assert_eq!(params["arguments"]["cmd"], "ls \"}\" ]");
+        assert_eq!(sniffer.id(), Some(&Value::Number(3.into())));
+        assert!(!sniffer.params_overflow());
+
+        let mut sniffer = JsonRpcSniffer::new();
+        sniffer.capture_params();
+        sniffer.observe(br#"{"jsonrpc":"2.0","params":{"text":""#);
+        sniffer.observe(&vec![b'a'; MAX_PARAMS_LEN]);
+        sniffer.observe(br#""},"method":"tools/call"}"#);
+        assert_eq!(sniffer.params(), None);
+        assert!(sniffer.params_overflow());
+        assert_eq!(sniffer.method(), Some("tools/call"));
+    }
+
+    #[test]
+    fn test_sniff_not_jsonrpc() {
+        let mut sniffer = JsonRpcSniffer::new();
+        sniffer.observe(br#"{"messages":[{"jsonrpc":"2.0","id":1}]}"#);
+        assert!(!sniffer.is_jsonrpc());
+        assert!(sniffer.id().is_none());
     }
 }
@@ -0,0 +1,127 @@
+//! MCP Method Policy
+//!
+//! Allow/deny lists of glob patterns for JSON-RPC method names:
+//! `*` matches any run of characters (including `/`), `?` matches one.
+//! Deny entries take precedence, so "all tools methods except tools/call"
+//! is `allow: ["tools/*"], deny: ["tools/call"]`.
+
+/// Glob-based method allow/deny policy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodPolicy {
+    allow: Vec<String>,
+    deny: Vec<String>,
+}
+
+impl MethodPolicy {
+    /// Create a policy from allow and deny patterns
+    pub fn new(allow: Vec<String>, deny: Vec<String>) -> Self {
+        Self { allow, deny }
+    }
+
+    /// Policy allowing every method
+    pub fn allow_all() -> Self {
+        Self::new(vec!["*".to_string()], Vec::new())
+    }
+
+    /// Check if a method is allowed (deny wins over allow)
+    pub fn is_allowed(&self, method: &str) -> bool {
+        if self.deny.iter().any(|p| glob_match(p, method)) {
+            return false;
+        }
+        self.allow.iter().any(|p| glob_match(p, method))
+    }
+
+    /// Allow patterns
+    pub fn allow(&self) -> &[String] {
+        &self.allow
+    }
+
+    /// Deny patterns
+    pub fn deny(&self) -> &[String] {
+        &self.deny
+    }
+}
+
+impl Default for MethodPolicy {
+    fn default() -> Self {
+        Self::allow_all()
+    }
+}
+
+/// Match `text` against a glob `pattern` (`*` and `?` wildcards)
+///
+/// Linear backtracking over the last `*`; no regex, no allocation.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let p = pattern.as_bytes();
+    let t = text.as_bytes();
+    let (mut pi, mut ti) = (0, 0);
+    // Position after the last `*` seen, and the text index it matched up to
+    let mut star: Option<(usize, usize)> = None;
+
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == b'?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == b'*' {
+            star = Some((pi + 1, ti));
+            pi += 1;
+        } else if let Some((star_pi, star_ti)) = star {
+            // Let the last `*` absorb one more character
+            pi = star_pi;
+            ti = star_ti + 1;
+            star = Some((star_pi, ti));
+        } else {
+            return false;
+        }
+    }
+
+    p[pi..].iter().all(|&c| c == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(allow: &[&str], deny: &[&str]) -> MethodPolicy {
+        MethodPolicy::new(
+            allow.iter().map(|s| s.to_string()).collect(),
+            deny.iter().map(|s| s.to_string()).collect(),
+        )
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*", "tools/call"));
+        assert!(glob_match("tools/*", "tools/call"));
+        assert!(glob_match("tools/*", "tools/"));
+        assert!(!glob_match("tools/*", "tool/call"));
+        assert!(glob_match("*/list", "resources/templates/list"));
+        assert!(glob_match("resources/?ead", "resources/read"));
+        assert!(!glob_match("notifications/*/changed", "notifications/tools/list_changed"));
+        assert!(glob_match("notifications/*changed", "notifications/tools/list_changed"));
+        assert!(glob_match("ping", "ping"));
+        assert!(!glob_match("ping", "pings"));
+    }
+
+    #[test]
+    fn test_deny_precedence() {
+        let policy = policy(&["tools/*", "resources/read"], &["tools/call"]);
+
+        assert!(policy.is_allowed("tools/list"));
+        assert!(policy.is_allowed("resources/read"));
+        assert!(!policy.is_allowed("tools/call"));
+        assert!(!policy.is_allowed("resources/list"));
+    }
+
+    #[test]
+    fn test_deny_glob_over_allow_all() {
+        let policy = policy(&["*"], &["sampling/*"]);
+        assert!(policy.is_allowed("initialize"));
+        assert!(!policy.is_allowed("sampling/createMessage"));
+    }
+
+    #[test]
+    fn test_default_allows_all() {
+        assert!(MethodPolicy::default().is_allowed("anything/at/all"));
+    }
+}
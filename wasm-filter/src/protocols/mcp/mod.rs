@@ -7,15 +7,17 @@
 //! - STDIO (BLOCKED - off-mesh)
 
 pub mod jsonrpc;
+pub mod method_policy;
 pub mod http;
 pub mod sse;
 pub mod websocket;
 pub mod stdio_detect;
 
-pub use jsonrpc::{JsonRpcRequest, JsonRpcResponse, JsonRpcError};
+pub use jsonrpc::{JsonRpcRequest, JsonRpcResponse, JsonRpcError, JsonRpcSniffer};
+pub use method_policy::MethodPolicy;
 pub use http::McpHttpHandler;
 pub use sse::McpSseHandler;
-pub use websocket::McpWebSocketHandler;
+pub use websocket::{McpWebSocketHandler, WsClose, WsCloseCode};
 
 /// MCP transport types
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -71,6 +73,16 @@ impl McpTransport {
     }
 }
 
+/// Headers that mark a request as MCP (Streamable HTTP session/version, transport hint)
+const MCP_HEADERS: [&str; 3] = ["mcp-session-id", "mcp-protocol-version", "x-mcp-transport"];
+
+/// Check whether request headers identify MCP traffic
+pub fn is_mcp_request(headers: &[(String, String)]) -> bool {
+    headers
+        .iter()
+        .any(|(name, _)| MCP_HEADERS.iter().any(|h| name.eq_ignore_ascii_case(h)))
+}
+
 /// MCP request wrapper
 #[derive(Debug, Clone)]
 pub struct McpRequest {
@@ -95,8 +107,8 @@ pub struct McpHandler {
     sse_handler: McpSseHandler,
     /// WebSocket handler
     websocket_handler: McpWebSocketHandler,
-    /// Allowed methods
-    allowed_methods: Vec<String>,
+    /// Method allow/deny policy
+    method_policy: MethodPolicy,
     /// Block STDIO transport
     block_stdio: bool,
 }
@@ -104,11 +116,16 @@ pub struct McpHandler {
 impl McpHandler {
     /// Create a new MCP handler
     pub fn new(allowed_methods: Vec<String>) -> Self {
+        Self::with_policy(MethodPolicy::new(allowed_methods, Vec::new()))
+    }
+
+    /// Create a handler with an allow/deny method policy
+    pub fn with_policy(method_policy: MethodPolicy) -> Self {
         Self {
-            http_handler: McpHttpHandler::new(allowed_methods.clone()),
+            http_handler: McpHttpHandler::with_policy(method_policy.clone()),
             sse_handler: McpSseHandler::new(),
             websocket_handler: McpWebSocketHandler::new(),
-            allowed_methods,
+            method_policy,
             block_stdio: true,
         }
     }
@@ -131,7 +148,7 @@ impl McpHandler {
 
     /// Check if a method is allowed
     pub fn is_method_allowed(&self, method: &str) -> bool {
-        self.allowed_methods.iter().any(|m| m == "*" || m == method)
+        self.method_policy.is_allowed(method)
     }
 
     /// Get HTTP handler
@@ -208,6 +225,15 @@ mod tests {
         assert_eq!(McpTransport::detect(&headers), Some(McpTransport::Http));
     }
 
+    #[test]
+    fn test_is_mcp_request() {
+        let headers = vec![("Mcp-Session-Id".to_string(), "abc".to_string())];
+        assert!(is_mcp_request(&headers));
+
+        let headers = vec![("content-type".to_string(), "application/json".to_string())];
+        assert!(!is_mcp_request(&headers));
+    }
+
     #[test]
     fn test_stdio_blocked() {
         assert!(!McpTransport::Stdio.is_allowed());
@@ -20,15 +20,6 @@ pub enum SseFrame {
     Comment,
 }
 
-/// SSE parser state
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum ParseState {
-    /// Looking for field name
-    FieldName,
-    /// Reading field value
-    FieldValue,
-}
-
 /// MCP SSE transport handler
 pub struct McpSseHandler {
     /// Ring buffer for cross-chunk pattern detection
@@ -37,10 +28,6 @@ pub struct McpSseHandler {
     current_event: Option<String>,
     /// Buffer for incomplete lines
     line_buffer: Vec<u8>,
-    /// Parse state
-    state: ParseState,
-    /// Current field name
-    current_field: String,
 }
 
 impl McpSseHandler {
@@ -50,8 +37,6 @@ impl McpSseHandler {
             ring_buffer: None,
             current_event: None,
             line_buffer: Vec::with_capacity(1024),
-            state: ParseState::FieldName,
-            current_field: String::new(),
         }
     }
 
@@ -91,16 +76,14 @@ impl McpSseHandler {
             }
 
             // Handle \r\n
-            if byte == b'\r' {
-                if i + 1 < chunk.len() && chunk[i + 1] == b'\n' {
-                    if let Some(action) = self.process_line() {
-                        if matches!(action, SseAction::Block(_)) {
-                            return action;
-                        }
+            if byte == b'\r' && i + 1 < chunk.len() && chunk[i + 1] == b'\n' {
+                if let Some(action) = self.process_line() {
+                    if matches!(action, SseAction::Block(_)) {
+                        return action;
                     }
-                    i += 2;
-                    continue;
                 }
+                i += 2;
+                continue;
             }
 
             // Add to line buffer
@@ -165,8 +148,6 @@ impl McpSseHandler {
     pub fn reset(&mut self) {
         self.current_event = None;
         self.line_buffer.clear();
-        self.state = ParseState::FieldName;
-        self.current_field.clear();
         if let Some(ref mut rb) = self.ring_buffer {
             rb.reset();
         }
@@ -7,6 +7,240 @@
 //! 1. Kubernetes NetworkPolicy (block non-mesh egress)
 //! 2. Kyverno policy (block stdio commands in container args)
 //! 3. Audit logging (detect stdio usage attempts)
+//!
+//! For the audit trail to drive follow-up (a Kyverno policy report, a
+//! ticket for the owning team), events name the workload that made the
+//! attempt. The caller is identified from Istio's peer metadata headers
+//! (`x-envoy-peer-metadata`, a base64 `google.protobuf.Struct`, and
+//! `x-envoy-peer-metadata-id`); without them, from the proxy's own node
+//! metadata, which names the calling pod when the filter runs in its
+//! outbound sidecar.
+
+use crate::crypto::base64url_decode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Header carrying the peer's node metadata (base64 protobuf Struct)
+pub const PEER_METADATA_HEADER: &str = "x-envoy-peer-metadata";
+
+/// Header carrying the peer's node id (`sidecar~<ip>~<pod>.<ns>~<ns>.svc.<domain>`)
+pub const PEER_METADATA_ID_HEADER: &str = "x-envoy-peer-metadata-id";
+
+/// STDIO bypass detection configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StdioDetectionConfig {
+    /// Reject requests carrying a STDIO indicator (audit only when false)
+    pub block: bool,
+}
+
+impl Default for StdioDetectionConfig {
+    fn default() -> Self {
+        Self { block: true }
+    }
+}
+
+/// Node metadata properties read for the proxy's own workload
+const NODE_METADATA: [&str; 4] = ["NAMESPACE", "NAME", "WORKLOAD_NAME", "SERVICE_ACCOUNT"];
+
+/// Kubernetes identity of the workload behind a request
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct WorkloadIdentity {
+    /// Namespace
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    /// Pod name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pod: Option<String>,
+    /// Owning workload (deployment, statefulset, ...)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workload: Option<String>,
+    /// Service account
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service_account: Option<String>,
+    /// Pod labels
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+}
+
+impl WorkloadIdentity {
+    /// Identity of the calling peer from Istio's metadata exchange headers
+    pub fn from_peer_headers(headers: &[(String, String)]) -> Option<Self> {
+        let header = |name: &str| {
+            headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+        };
+        let mut identity = header(PEER_METADATA_HEADER)
+            .and_then(|v| base64url_decode(v.trim()))
+            .and_then(|bytes| decode_struct(&bytes, 0))
+            .map(|metadata| Self::from_metadata(&metadata))
+            .unwrap_or_default();
+        if let Some((pod, namespace)) = header(PEER_METADATA_ID_HEADER).and_then(parse_node_id) {
+            identity.pod.get_or_insert(pod);
+            identity.namespace.get_or_insert(namespace);
+        }
+        (!identity.is_empty()).then_some(identity)
+    }
+
+    /// Identity of the proxy's own workload from its node metadata
+    ///
+    /// `property` reads a host property such as `node.metadata.NAMESPACE`.
+    pub fn from_node<F>(property: F) -> Option<Self>
+    where
+        F: Fn(&[&str]) -> Option<Vec<u8>>,
+    {
+        let mut metadata = BTreeMap::new();
+        for key in NODE_METADATA {
+            if let Some(value) = property(&["node", "metadata", key]) {
+                let value = String::from_utf8_lossy(&value).into_owned();
+                metadata.insert(key.to_string(), MetadataValue::String(value));
+            }
+        }
+        let identity = Self::from_metadata(&metadata);
+        (!identity.is_empty()).then_some(identity)
+    }
+
+    /// Whether nothing is known about the workload
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Audit metadata (`{"workload": {...}}`)
+    pub fn to_metadata(&self) -> Value {
+        serde_json::json!({ "workload": self })
+    }
+
+    fn from_metadata(metadata: &BTreeMap<String, MetadataValue>) -> Self {
+        let string = |key: &str| match metadata.get(key) {
+            Some(MetadataValue::String(s)) if !s.is_empty() => Some(s.clone()),
+            _ => None,
+        };
+        let labels = match metadata.get("LABELS") {
+            Some(MetadataValue::Struct(labels)) => labels
+                .iter()
+                .filter_map(|(k, v)| match v {
+                    MetadataValue::String(s) => Some((k.clone(), s.clone())),
+                    _ => None,
+                })
+                .collect(),
+            _ => BTreeMap::new(),
+        };
+        Self {
+            namespace: string("NAMESPACE"),
+            pod: string("NAME"),
+            workload: string("WORKLOAD_NAME"),
+            service_account: string("SERVICE_ACCOUNT"),
+            labels,
+        }
+    }
+}
+
+/// Value of a protobuf Struct field (only what identifies a workload)
+#[derive(Debug, Clone, PartialEq)]
+enum MetadataValue {
+    String(String),
+    Struct(BTreeMap<String, MetadataValue>),
+    Other,
+}
+
+/// Nested Structs decoded at most (LABELS sits one level down)
+const MAX_STRUCT_DEPTH: usize = 2;
+
+/// Decode a serialized `google.protobuf.Struct`
+fn decode_struct(buf: &[u8], depth: usize) -> Option<BTreeMap<String, MetadataValue>> {
+    let mut fields = BTreeMap::new();
+    for (number, entry) in decode_message(buf)? {
+        // 1: map<string, Value> fields
+        if let (1, Wire::Bytes(entry)) = (number, entry) {
+            let mut key = None;
+            let mut value = MetadataValue::Other;
+            for (number, part) in decode_message(entry)? {
+                match (number, part) {
+                    (1, Wire::Bytes(k)) => key = Some(String::from_utf8_lossy(k).into_owned()),
+                    (2, Wire::Bytes(v)) => value = decode_value(v, depth)?,
+                    _ => {}
+                }
+            }
+            if let Some(key) = key {
+                fields.insert(key, value);
+            }
+        }
+    }
+    Some(fields)
+}
+
+/// Decode a `google.protobuf.Value` (string and struct kinds)
+fn decode_value(buf: &[u8], depth: usize) -> Option<MetadataValue> {
+    let mut value = MetadataValue::Other;
+    for (number, part) in decode_message(buf)? {
+        value = match (number, part) {
+            (3, Wire::Bytes(s)) => MetadataValue::String(String::from_utf8_lossy(s).into_owned()),
+            (5, Wire::Bytes(s)) if depth < MAX_STRUCT_DEPTH => {
+                MetadataValue::Struct(decode_struct(s, depth + 1)?)
+            }
+            _ => MetadataValue::Other,
+        };
+    }
+    Some(value)
+}
+
+/// Protobuf field payload
+enum Wire<'a> {
+    Scalar,
+    Bytes(&'a [u8]),
+}
+
+/// Split a protobuf message into field numbers and payloads
+fn decode_message(buf: &[u8]) -> Option<Vec<(u64, Wire<'_>)>> {
+    let mut fields = Vec::new();
+    let mut pos = 0;
+    while pos < buf.len() {
+        let tag = read_varint(buf, &mut pos)?;
+        let field = match tag & 7 {
+            0 => read_varint(buf, &mut pos).map(|_| Wire::Scalar)?,
+            1 => skip(buf, &mut pos, 8)?,
+            2 => {
+                let len = usize::try_from(read_varint(buf, &mut pos)?).ok()?;
+                let bytes = buf.get(pos..pos.checked_add(len)?)?;
+                pos += len;
+                Wire::Bytes(bytes)
+            }
+            5 => skip(buf, &mut pos, 4)?,
+            _ => return None,
+        };
+        fields.push((tag >> 3, field));
+    }
+    Some(fields)
+}
+
+fn skip<'a>(buf: &[u8], pos: &mut usize, len: usize) -> Option<Wire<'a>> {
+    buf.get(*pos..*pos + len)?;
+    *pos += len;
+    Some(Wire::Scalar)
+}
+
+fn read_varint(buf: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *buf.get(*pos)?;
+        *pos += 1;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Pod and namespace from an Istio node id (`sidecar~10.0.0.1~pod.ns~ns.svc.cluster.local`)
+fn parse_node_id(id: &str) -> Option<(String, String)> {
+    let name = id.split('~').nth(2)?;
+    let (pod, namespace) = name.rsplit_once('.')?;
+    if pod.is_empty() || namespace.is_empty() {
+        return None;
+    }
+    Some((pod.to_string(), namespace.to_string()))
+}
 
 /// STDIO bypass detection result
 #[derive(Debug, Clone)]
@@ -66,6 +300,10 @@ impl StdioDetector {
     pub fn detect_from_headers(&self, headers: &[(String, String)]) -> Option<StdioBypassAttempt> {
         for (name, value) in headers {
             let name_lower = name.to_lowercase();
+            // Set by the mesh, and base64 may spell anything
+            if name_lower.starts_with(PEER_METADATA_HEADER) {
+                continue;
+            }
             let value_lower = value.to_lowercase();
 
             // Check for explicit stdio transport header
@@ -120,8 +358,12 @@ impl StdioDetector {
         None
     }
 
-    /// Create audit event for STDIO bypass attempt
-    pub fn create_audit_event(&self, attempt: &StdioBypassAttempt) -> StdioAuditEvent {
+    /// Create audit event for STDIO bypass attempt by a workload
+    pub fn create_audit_event(
+        &self,
+        attempt: &StdioBypassAttempt,
+        workload: Option<WorkloadIdentity>,
+    ) -> StdioAuditEvent {
         StdioAuditEvent {
             event_type: "stdio_bypass_attempt".to_string(),
             bypass_type: format!("{:?}", attempt.bypass_type),
@@ -129,6 +371,7 @@ impl StdioDetector {
             severity: format!("{:?}", attempt.severity),
             action_taken: "blocked".to_string(),
             recommendation: "Use HTTP, SSE, or WebSocket transport for mesh visibility".to_string(),
+            workload,
         }
     }
 }
@@ -154,6 +397,8 @@ pub struct StdioAuditEvent {
     pub action_taken: String,
     /// Recommendation
     pub recommendation: String,
+    /// Workload that made the attempt, when known
+    pub workload: Option<WorkloadIdentity>,
 }
 
 #[cfg(test)]
@@ -200,4 +445,87 @@ mod tests {
 
         assert!(result.is_some());
     }
+
+    /// Serialized `google.protobuf.Struct` with string fields and a LABELS struct
+    fn peer_metadata(fields: &[(&str, &str)], labels: &[(&str, &str)]) -> Vec<u8> {
+        fn bytes(field: u8, data: &[u8]) -> Vec<u8> {
+            let mut out = vec![field << 3 | 2, data.len() as u8];
+            out.extend_from_slice(data);
+            out
+        }
+        fn entry(key: &str, value: Vec<u8>) -> Vec<u8> {
+            let mut entry = bytes(1, key.as_bytes());
+            entry.extend(bytes(2, &value));
+            bytes(1, &entry)
+        }
+        let strings = |fields: &[(&str, &str)]| -> Vec<u8> {
+            fields.iter().flat_map(|(k, v)| entry(k, bytes(3, v.as_bytes()))).collect()
+        };
+        let mut out = strings(fields);
+        out.extend(entry("LABELS", bytes(5, &strings(labels))));
+        out
+    }
+
+    fn base64(data: &[u8]) -> String {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut out = String::new();
+        for chunk in data.chunks(3) {
+            let n = chunk
+                .iter()
+                .enumerate()
+                .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+            for i in 0..=chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_workload_from_peer_metadata() {
+        let metadata = peer_metadata(
+            &[("NAMESPACE", "agents"), ("NAME", "planner-7d9f-x2"), ("WORKLOAD_NAME", "planner")],
+            &[("app", "planner"), ("team", "research")],
+        );
+        let headers = vec![
+            (PEER_METADATA_HEADER.to_string(), base64(&metadata)),
+            ("x-mcp-transport".to_string(), "stdio".to_string()),
+        ];
+        let workload = WorkloadIdentity::from_peer_headers(&headers).unwrap();
+        assert_eq!(workload.namespace.as_deref(), Some("agents"));
+        assert_eq!(workload.pod.as_deref(), Some("planner-7d9f-x2"));
+        assert_eq!(workload.workload.as_deref(), Some("planner"));
+        assert_eq!(workload.labels.get("team").map(String::as_str), Some("research"));
+
+        let detector = StdioDetector::new();
+        let attempt = detector.detect_from_headers(&headers).unwrap();
+        assert_eq!(attempt.severity, StdioSeverity::High);
+        let event = detector.create_audit_event(&attempt, Some(workload));
+        assert_eq!(event.workload.unwrap().namespace.as_deref(), Some("agents"));
+
+        // Malformed metadata is ignored rather than misattributed
+        let garbage = vec![(PEER_METADATA_HEADER.to_string(), base64(&[0x0a, 0x7f]))];
+        assert_eq!(WorkloadIdentity::from_peer_headers(&garbage), None);
+    }
+
+    #[test]
+    fn test_workload_from_peer_id_and_node() {
+        let id = "sidecar~10.4.2.17~coder-5c8b-qq.tools~tools.svc.cluster.local";
+        let headers = vec![(PEER_METADATA_ID_HEADER.to_string(), id.to_string())];
+        let workload = WorkloadIdentity::from_peer_headers(&headers).unwrap();
+        assert_eq!(workload.pod.as_deref(), Some("coder-5c8b-qq"));
+        assert_eq!(workload.namespace.as_deref(), Some("tools"));
+        assert_eq!(WorkloadIdentity::from_peer_headers(&[]), None);
+
+        let node = WorkloadIdentity::from_node(|path| match path {
+            ["node", "metadata", "NAMESPACE"] => Some(b"agents".to_vec()),
+            ["node", "metadata", "SERVICE_ACCOUNT"] => Some(b"planner-sa".to_vec()),
+            _ => None,
+        })
+        .unwrap();
+        assert_eq!(node.service_account.as_deref(), Some("planner-sa"));
+        let expected = serde_json::json!({"namespace": "agents", "service_account": "planner-sa"});
+        assert_eq!(node.to_metadata(), serde_json::json!({ "workload": expected }));
+        assert_eq!(WorkloadIdentity::from_node(|_| None), None);
+    }
 }
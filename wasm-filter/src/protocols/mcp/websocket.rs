@@ -2,9 +2,29 @@
 //!
 //! Handles MCP over WebSocket with bidirectional frame inspection.
 //! MCP only uses text frames (JSON-RPC), binary frames are blocked.
-
-use crate::streaming::{RingBuffer, Pattern, ScanResult};
+//!
+//! Fragments are scanned as they stream through, and a fragmented message
+//! is scanned once more as a whole when its final fragment arrives, so a
+//! pattern split at a fragment boundary is caught even if the streaming
+//! scan lost its state in between.
+//!
+//! A blocked message ends the connection with a Close frame carrying a
+//! status code (1008 for policy violations) and the reason, so the client
+//! sees why instead of a dropped socket. The handler then tracks the
+//! closing handshake: frames after our Close are discarded, and the
+//! connection is closed once the peer's Close arrives.
+//!
+//! Server-originated frames are inspected too, since a malicious MCP server
+//! can push sampling requests or poisoned resource updates at the agent. A
+//! complete server message that matches a pattern, or whose method the
+//! server method policy denies, is dropped before it reaches the agent. A
+//! match inside a fragmented server message closes the connection instead,
+//! as its earlier fragments have already been forwarded.
+
+use crate::streaming::{RingBuffer, Pattern, PatternScanner, ScanResult};
 use super::jsonrpc::JsonRpcRequest;
+use super::method_policy::MethodPolicy;
+use std::rc::Rc;
 
 /// WebSocket opcode
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -39,6 +59,94 @@ impl From<u8> for WsOpcode {
     }
 }
 
+/// Close status codes (RFC 6455 section 7.4.1)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WsCloseCode {
+    /// Normal closure
+    Normal = 1000,
+    /// Endpoint going away
+    GoingAway = 1001,
+    /// Protocol error
+    ProtocolError = 1002,
+    /// Data type the endpoint cannot accept (binary for MCP)
+    UnsupportedData = 1003,
+    /// Data inconsistent with the message type (invalid UTF-8)
+    InvalidPayload = 1007,
+    /// Message violates policy
+    PolicyViolation = 1008,
+    /// Message too big to process
+    MessageTooBig = 1009,
+}
+
+impl WsCloseCode {
+    /// Numeric status code
+    pub fn code(&self) -> u16 {
+        *self as u16
+    }
+}
+
+/// Close frame content: status code and reason
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WsClose {
+    /// Status code
+    pub code: u16,
+    /// UTF-8 reason (at most `MAX_CLOSE_REASON` bytes)
+    pub reason: String,
+}
+
+/// Longest close reason: control frame payloads are limited to 125 bytes
+pub const MAX_CLOSE_REASON: usize = 123;
+
+impl WsClose {
+    /// Close with a code and reason, cutting the reason to fit a control frame
+    pub fn new(code: WsCloseCode, reason: &str) -> Self {
+        let mut end = reason.len().min(MAX_CLOSE_REASON);
+        while !reason.is_char_boundary(end) {
+            end -= 1;
+        }
+        Self {
+            code: code.code(),
+            reason: reason[..end].to_string(),
+        }
+    }
+
+    /// Parse a received Close payload (an empty payload has no status: 1005)
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        match payload {
+            [] => Some(Self { code: 1005, reason: String::new() }),
+            [hi, lo, reason @ ..] => Some(Self {
+                code: u16::from_be_bytes([*hi, *lo]),
+                reason: std::str::from_utf8(reason).ok()?.to_string(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Frame payload: big-endian code followed by the reason
+    pub fn payload(&self) -> Vec<u8> {
+        let mut payload = self.code.to_be_bytes().to_vec();
+        payload.extend_from_slice(self.reason.as_bytes());
+        payload
+    }
+
+    /// Unmasked Close frame, as sent towards the client
+    pub fn to_frame(&self) -> Vec<u8> {
+        let payload = self.payload();
+        let mut frame = vec![0x80 | WsOpcode::Close as u8, payload.len() as u8];
+        frame.extend_from_slice(&payload);
+        frame
+    }
+
+    /// Masked Close frame, as sent towards the server
+    pub fn to_masked_frame(&self, mask: [u8; 4]) -> Vec<u8> {
+        let payload = self.payload();
+        let mut frame = vec![0x80 | WsOpcode::Close as u8, 0x80 | payload.len() as u8];
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        frame
+    }
+}
+
 /// WebSocket connection state
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum WsState {
@@ -56,12 +164,28 @@ pub struct McpWebSocketHandler {
     state: WsState,
     /// Ring buffer for pattern detection
     ring_buffer: Option<RingBuffer>,
+    /// Patterns, for scanning reassembled messages
+    patterns: Option<Rc<[Pattern]>>,
     /// Buffer for fragmented messages
     fragment_buffer: Vec<u8>,
     /// Current fragment opcode
     fragment_opcode: Option<WsOpcode>,
     /// Message counter
     message_count: u64,
+    /// Close we sent (or injected)
+    close_sent: Option<WsClose>,
+    /// Close received from the peer
+    close_received: Option<WsClose>,
+    /// Ring buffer for server-originated frames
+    server_ring_buffer: Option<RingBuffer>,
+    /// Buffer for fragmented server messages
+    server_fragment_buffer: Vec<u8>,
+    /// Current server fragment opcode
+    server_fragment_opcode: Option<WsOpcode>,
+    /// Methods the server may send to the agent
+    server_methods: MethodPolicy,
+    /// Server messages dropped
+    dropped_server_messages: u64,
 }
 
 impl McpWebSocketHandler {
@@ -70,23 +194,92 @@ impl McpWebSocketHandler {
         Self {
             state: WsState::Open,
             ring_buffer: None,
+            patterns: None,
             fragment_buffer: Vec::with_capacity(4096),
             fragment_opcode: None,
             message_count: 0,
+            close_sent: None,
+            close_received: None,
+            server_ring_buffer: None,
+            server_fragment_buffer: Vec::new(),
+            server_fragment_opcode: None,
+            server_methods: MethodPolicy::allow_all(),
+            dropped_server_messages: 0,
         }
     }
 
     /// Initialize ring buffer with patterns
     pub fn init_patterns(&mut self, patterns: Vec<String>, buffer_size: usize) {
-        let patterns: Vec<Pattern> = patterns
+        let patterns: Rc<[Pattern]> = patterns
             .iter()
             .map(|s| Pattern::from_string(s))
             .collect();
-        self.ring_buffer = Some(RingBuffer::new(buffer_size, patterns));
+        self.ring_buffer = Some(RingBuffer::new(buffer_size, patterns.clone()));
+        self.server_ring_buffer = Some(RingBuffer::new(buffer_size, patterns.clone()));
+        self.patterns = Some(patterns);
+    }
+
+    /// Restrict the methods (requests and notifications) the server may send
+    pub fn set_server_method_policy(&mut self, policy: MethodPolicy) {
+        self.server_methods = policy;
     }
 
     /// Process a WebSocket frame
     pub fn on_frame(&mut self, opcode: WsOpcode, payload: &[u8], fin: bool) -> WsFrameAction {
+        let action = self.inspect_frame(opcode, payload, fin);
+        match action {
+            WsFrameAction::Block(close) => self.close(close),
+            action => action,
+        }
+    }
+
+    /// Process a server-originated WebSocket frame
+    pub fn on_server_frame(&mut self, opcode: WsOpcode, payload: &[u8], fin: bool) -> WsFrameAction {
+        let action = self.inspect_server_frame(opcode, payload, fin);
+        match action {
+            WsFrameAction::Block(close) => self.close(close),
+            WsFrameAction::Drop if self.close_sent.is_none() => {
+                self.dropped_server_messages += 1;
+                WsFrameAction::Drop
+            }
+            action => action,
+        }
+    }
+
+    /// Start the closing handshake; the returned action carries the frame to inject
+    pub fn close(&mut self, close: WsClose) -> WsFrameAction {
+        if self.close_sent.is_some() {
+            return WsFrameAction::Drop;
+        }
+        self.fragment_buffer.clear();
+        self.fragment_opcode = None;
+        self.server_fragment_buffer.clear();
+        self.server_fragment_opcode = None;
+        self.close_sent = Some(close.clone());
+        self.update_state();
+        WsFrameAction::Block(close)
+    }
+
+    fn update_state(&mut self) {
+        self.state = match (&self.close_sent, &self.close_received) {
+            (Some(_), Some(_)) => WsState::Closed,
+            (None, None) => WsState::Open,
+            _ => WsState::Closing,
+        };
+    }
+
+    fn inspect_frame(&mut self, opcode: WsOpcode, payload: &[u8], fin: bool) -> WsFrameAction {
+        if opcode == WsOpcode::Close {
+            let close = WsClose::parse(payload);
+            self.close_received =
+                Some(close.unwrap_or_else(|| WsClose::new(WsCloseCode::ProtocolError, "")));
+            self.update_state();
+            return WsFrameAction::Continue;
+        }
+        // After our Close the peer's data no longer counts
+        if self.close_sent.is_some() || self.state == WsState::Closed {
+            return WsFrameAction::Drop;
+        }
         match opcode {
             WsOpcode::Text => {
                 // Text frames contain JSON-RPC messages
@@ -94,23 +287,18 @@ impl McpWebSocketHandler {
             }
             WsOpcode::Binary => {
                 // Binary frames not allowed for MCP
-                WsFrameAction::Block("Binary WebSocket frames not allowed for MCP".to_string())
+                block(WsCloseCode::UnsupportedData, "Binary WebSocket frames not allowed for MCP")
             }
             WsOpcode::Continuation => {
                 // Continue fragmented message
                 self.on_continuation_frame(payload, fin)
             }
-            WsOpcode::Close => {
-                self.state = WsState::Closing;
-                WsFrameAction::Continue
-            }
+            WsOpcode::Close => WsFrameAction::Continue,
             WsOpcode::Ping | WsOpcode::Pong => {
                 // Control frames, allow through
                 WsFrameAction::Continue
             }
-            WsOpcode::Unknown => {
-                WsFrameAction::Block("Unknown WebSocket opcode".to_string())
-            }
+            WsOpcode::Unknown => block(WsCloseCode::ProtocolError, "Unknown WebSocket opcode"),
         }
     }
 
@@ -119,10 +307,8 @@ impl McpWebSocketHandler {
         // Scan payload for patterns
         if let Some(ref mut rb) = self.ring_buffer {
             if let ScanResult::Match(m) = rb.process_chunk(payload) {
-                return WsFrameAction::Block(format!(
-                    "Pattern '{}' detected in WebSocket message",
-                    m.pattern_name
-                ));
+                let reason = format!("Pattern '{}' detected in WebSocket message", m.pattern_name);
+                return block(WsCloseCode::PolicyViolation, &reason);
             }
         }
 
@@ -148,23 +334,21 @@ impl McpWebSocketHandler {
         // Scan payload for patterns
         if let Some(ref mut rb) = self.ring_buffer {
             if let ScanResult::Match(m) = rb.process_chunk(payload) {
-                return WsFrameAction::Block(format!(
-                    "Pattern '{}' detected in WebSocket message",
-                    m.pattern_name
-                ));
+                let reason = format!("Pattern '{}' detected in WebSocket message", m.pattern_name);
+                return block(WsCloseCode::PolicyViolation, &reason);
             }
         }
 
         // Check if we're expecting a continuation
         if self.fragment_opcode.is_none() {
-            return WsFrameAction::Block("Unexpected continuation frame".to_string());
+            return block(WsCloseCode::ProtocolError, "Unexpected continuation frame");
         }
 
         // Limit fragment buffer size to prevent DoS
         if self.fragment_buffer.len() + payload.len() > 10 * 1024 * 1024 {
             self.fragment_buffer.clear();
             self.fragment_opcode = None;
-            return WsFrameAction::Block("WebSocket message too large".to_string());
+            return block(WsCloseCode::MessageTooBig, "WebSocket message too large");
         }
 
         self.fragment_buffer.extend_from_slice(payload);
@@ -175,7 +359,8 @@ impl McpWebSocketHandler {
 
             // Validate if it was a text message
             if self.fragment_opcode == Some(WsOpcode::Text) {
-                if let Err(e) = self.validate_message(&self.fragment_buffer) {
+                let message = self.scan_message(&self.fragment_buffer);
+                if let Err(e) = message.and_then(|_| self.validate_message(&self.fragment_buffer)) {
                     self.fragment_buffer.clear();
                     self.fragment_opcode = None;
                     return WsFrameAction::Block(e);
@@ -189,18 +374,115 @@ impl McpWebSocketHandler {
         WsFrameAction::Continue
     }
 
+    fn inspect_server_frame(&mut self, opcode: WsOpcode, payload: &[u8], fin: bool) -> WsFrameAction {
+        if self.close_sent.is_some() || self.state == WsState::Closed {
+            return match opcode {
+                WsOpcode::Close => WsFrameAction::Continue,
+                _ => WsFrameAction::Drop,
+            };
+        }
+        match opcode {
+            WsOpcode::Text => {
+                if self.server_fragment_opcode.is_some() {
+                    return block(WsCloseCode::ProtocolError, "Unexpected text frame in fragmented server message");
+                }
+                self.on_server_text(payload, fin)
+            }
+            WsOpcode::Continuation => {
+                if self.server_fragment_opcode.is_none() {
+                    return block(WsCloseCode::ProtocolError, "Unexpected continuation frame from server");
+                }
+                self.on_server_text(payload, fin)
+            }
+            WsOpcode::Binary => {
+                block(WsCloseCode::UnsupportedData, "Binary WebSocket frames not allowed for MCP")
+            }
+            WsOpcode::Close | WsOpcode::Ping | WsOpcode::Pong => WsFrameAction::Continue,
+            WsOpcode::Unknown => block(WsCloseCode::ProtocolError, "Unknown WebSocket opcode"),
+        }
+    }
+
+    /// Process a server text or continuation frame
+    fn on_server_text(&mut self, payload: &[u8], fin: bool) -> WsFrameAction {
+        let fragmented = self.server_fragment_opcode.is_some() || !fin;
+        if let Some(ref mut rb) = self.server_ring_buffer {
+            if let ScanResult::Match(m) = rb.process_chunk(payload) {
+                if fragmented {
+                    let reason = format!("Pattern '{}' detected in server message", m.pattern_name);
+                    return block(WsCloseCode::PolicyViolation, &reason);
+                }
+                return WsFrameAction::Drop;
+            }
+        }
+
+        if !fin {
+            if self.server_fragment_buffer.len() + payload.len() > 10 * 1024 * 1024 {
+                return block(WsCloseCode::MessageTooBig, "WebSocket message too large");
+            }
+            self.server_fragment_opcode = Some(WsOpcode::Text);
+            self.server_fragment_buffer.extend_from_slice(payload);
+            return WsFrameAction::Continue;
+        }
+
+        let message = if fragmented {
+            self.server_fragment_buffer.extend_from_slice(payload);
+            self.server_fragment_opcode = None;
+            std::mem::take(&mut self.server_fragment_buffer)
+        } else {
+            payload.to_vec()
+        };
+        let verdict = self.scan_message(&message).and_then(|_| self.check_server_method(&message));
+        match verdict {
+            Ok(()) => WsFrameAction::Continue,
+            // Earlier fragments already reached the agent
+            Err(close) if fragmented => WsFrameAction::Block(close),
+            Err(_) => WsFrameAction::Drop,
+        }
+    }
+
+    /// Check a server-sent request or notification against the server method policy
+    fn check_server_method(&self, message: &[u8]) -> Result<(), WsClose> {
+        let request: JsonRpcRequest = match serde_json::from_slice(message) {
+            Ok(request) => request,
+            // Responses carry no method
+            Err(_) => return Ok(()),
+        };
+        if self.server_methods.is_allowed(&request.method) {
+            return Ok(());
+        }
+        let reason = format!("Server method not allowed: {}", request.method);
+        Err(WsClose::new(WsCloseCode::PolicyViolation, &reason))
+    }
+
+    /// Scan a reassembled message from scratch
+    fn scan_message(&self, message: &[u8]) -> Result<(), WsClose> {
+        let patterns = match &self.patterns {
+            Some(patterns) => patterns.clone(),
+            None => return Ok(()),
+        };
+        match PatternScanner::new(patterns).scan_bytes(message) {
+            ScanResult::Match(m) => {
+                let reason = format!("Pattern '{}' detected in WebSocket message", m.pattern_name);
+                Err(WsClose::new(WsCloseCode::PolicyViolation, &reason))
+            }
+            ScanResult::Continue => Ok(()),
+        }
+    }
+
     /// Validate a JSON-RPC message
-    fn validate_message(&self, payload: &[u8]) -> Result<(), String> {
+    fn validate_message(&self, payload: &[u8]) -> Result<(), WsClose> {
         // Try to parse as JSON-RPC
-        let text = std::str::from_utf8(payload)
-            .map_err(|_| "Invalid UTF-8 in WebSocket message".to_string())?;
+        let text = std::str::from_utf8(payload).map_err(|_| {
+            WsClose::new(WsCloseCode::InvalidPayload, "Invalid UTF-8 in WebSocket message")
+        })?;
 
         // Parse JSON
         let request: Result<JsonRpcRequest, _> = serde_json::from_str(text);
         if let Ok(req) = request {
             // Validate JSON-RPC format
             if let Err(e) = req.validate() {
-                return Err(format!("Invalid JSON-RPC: {}", e));
+                let reason = format!("Invalid JSON-RPC: {}", e);
+                return Err(WsClose::new(WsCloseCode::PolicyViolation, &reason));
             }
         }
         // If it's not a valid request, it might be a response or notification - allow
@@ -213,20 +495,38 @@ impl McpWebSocketHandler {
         self.state
     }
 
+    /// Close received from the peer, if any
+    pub fn peer_close(&self) -> Option<&WsClose> {
+        self.close_received.as_ref()
+    }
+
     /// Get message count
     pub fn message_count(&self) -> u64 {
         self.message_count
     }
 
+    /// Number of server messages dropped before reaching the agent
+    pub fn dropped_server_messages(&self) -> u64 {
+        self.dropped_server_messages
+    }
+
     /// Reset handler state
     pub fn reset(&mut self) {
         self.state = WsState::Open;
         self.fragment_buffer.clear();
         self.fragment_opcode = None;
         self.message_count = 0;
+        self.close_sent = None;
+        self.close_received = None;
+        self.server_fragment_buffer.clear();
+        self.server_fragment_opcode = None;
+        self.dropped_server_messages = 0;
         if let Some(ref mut rb) = self.ring_buffer {
             rb.reset();
         }
+        if let Some(ref mut rb) = self.server_ring_buffer {
+            rb.reset();
+        }
     }
 }
 
@@ -241,8 +541,14 @@ impl Default for McpWebSocketHandler {
 pub enum WsFrameAction {
     /// Continue processing
     Continue,
-    /// Block the message
-    Block(String),
+    /// Block the message and close the connection: inject `to_frame()`
+    Block(WsClose),
+    /// Discard the frame (the connection is closing)
+    Drop,
+}
+
+fn block(code: WsCloseCode, reason: &str) -> WsFrameAction {
+    WsFrameAction::Block(WsClose::new(code, reason))
 }
 
 #[cfg(test)]
@@ -301,4 +607,157 @@ mod tests {
         handler.on_frame(WsOpcode::Close, &[], true);
         assert_eq!(handler.state(), WsState::Closing);
     }
+
+    #[test]
+    fn test_pattern_split_at_fragment_boundary() {
+        let mut handler = McpWebSocketHandler::new();
+        handler.init_patterns(vec!["jailbreak".to_string()], 4096);
+        let first = br#"{"jsonrpc":"2.0","method":"prompt","params":{"text":"jail"#;
+        assert!(matches!(handler.on_frame(WsOpcode::Text, first, false), WsFrameAction::Continue));
+        let last = br#"break"},"id":1}"#;
+        match handler.on_frame(WsOpcode::Continuation, last, true) {
+            WsFrameAction::Block(close) => assert_eq!(close.code, 1008),
+            other => panic!("expected block, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_reassembled_message_is_scanned() {
+        let mut handler = McpWebSocketHandler::new();
+        handler.init_patterns(vec!["jailbreak".to_string()], 4096);
+        let fragments: [&[u8]; 3] =
+            [br#"{"jsonrpc":"2.0","params":{"text":"ja"#, b"ilbr", br#"eak"}}"#];
+        assert!(matches!(
+            handler.on_frame(WsOpcode::Text, fragments[0], false),
+            WsFrameAction::Continue
+        ));
+        // The streaming scan loses its partial match between fragments
+        handler.ring_buffer.as_mut().unwrap().reset();
+        assert!(matches!(
+            handler.on_frame(WsOpcode::Continuation, fragments[1], false),
+            WsFrameAction::Continue
+        ));
+        handler.ring_buffer.as_mut().unwrap().reset();
+        let close = match handler.on_frame(WsOpcode::Continuation, fragments[2], true) {
+            WsFrameAction::Block(close) => close,
+            other => panic!("expected block, got {:?}", other),
+        };
+        assert_eq!(close.reason, "Pattern 'jailbreak' detected in WebSocket message");
+        assert!(handler.fragment_buffer.is_empty());
+
+        // Clean fragmented messages still pass
+        let mut handler = McpWebSocketHandler::new();
+        handler.init_patterns(vec!["jailbreak".to_string()], 4096);
+        handler.on_frame(WsOpcode::Text, br#"{"jsonrpc":"2.0","method":"tools/"#, false);
+        let last = handler.on_frame(WsOpcode::Continuation, br#"list","id":1}"#, true);
+        assert!(matches!(last, WsFrameAction::Continue));
+        assert_eq!(handler.message_count(), 1);
+    }
+
+    #[test]
+    fn test_policy_violation_close_handshake() {
+        let mut handler = McpWebSocketHandler::new();
+        handler.init_patterns(vec!["jailbreak".to_string()], 4096);
+
+        let payload =
+            br#"{"jsonrpc":"2.0","method":"prompt","params":{"text":"jailbreak"},"id":1}"#;
+        let close = match handler.on_frame(WsOpcode::Text, payload, true) {
+            WsFrameAction::Block(close) => close,
+            other => panic!("expected block, got {:?}", other),
+        };
+        assert_eq!(close.code, 1008);
+        assert_eq!(close.reason, "Pattern 'jailbreak' detected in WebSocket message");
+        let frame = close.to_frame();
+        assert_eq!(&frame[..4], &[0x88, 2 + close.reason.len() as u8, 0x03, 0xF0]);
+        assert_eq!(handler.state(), WsState::Closing);
+
+        // In-flight data is discarded; the peer's Close completes the handshake
+        let tools = br#"{"jsonrpc":"2.0","method":"tools/list","id":2}"#;
+        assert!(matches!(handler.on_frame(WsOpcode::Text, tools, true), WsFrameAction::Drop));
+        let reply = WsClose::new(WsCloseCode::PolicyViolation, "").payload();
+        assert!(matches!(handler.on_frame(WsOpcode::Close, &reply, true), WsFrameAction::Continue));
+        assert_eq!(handler.state(), WsState::Closed);
+        assert_eq!(handler.peer_close().unwrap().code, 1008);
+        assert!(matches!(handler.close(close), WsFrameAction::Drop));
+    }
+
+    #[test]
+    fn test_close_frames() {
+        // Status codes follow the reason for blocking
+        let mut handler = McpWebSocketHandler::new();
+        match handler.on_frame(WsOpcode::Binary, &[0x00], true) {
+            WsFrameAction::Block(close) => assert_eq!(close.code, 1003),
+            other => panic!("expected block, got {:?}", other),
+        }
+        let mut handler = McpWebSocketHandler::new();
+        match handler.on_frame(WsOpcode::Text, &[0xff, 0xfe], true) {
+            WsFrameAction::Block(close) => assert_eq!(close.code, 1007),
+            other => panic!("expected block, got {:?}", other),
+        }
+
+        // Reasons are cut to fit a control frame, on a character boundary
+        let close = WsClose::new(WsCloseCode::PolicyViolation, &"é".repeat(100));
+        assert_eq!(close.reason.len(), 122);
+        assert!(close.to_frame().len() <= 127);
+
+        let masked = close.to_masked_frame([1, 2, 3, 4]);
+        assert_eq!(masked[1], 0x80 | 124);
+        let unmasked: Vec<u8> =
+            masked[6..].iter().enumerate().map(|(i, b)| b ^ [1, 2, 3, 4][i % 4]).collect();
+        assert_eq!(WsClose::parse(&unmasked), Some(close));
+        assert_eq!(WsClose::parse(&[]).unwrap().code, 1005);
+        assert_eq!(WsClose::parse(&[0x03]), None);
+    }
+
+    #[test]
+    fn test_server_frames_inspected() {
+        let mut handler = McpWebSocketHandler::new();
+        handler.init_patterns(vec!["ignore previous instructions".to_string()], 4096);
+        handler.set_server_method_policy(MethodPolicy::new(
+            vec!["*".to_string()],
+            vec!["sampling/*".to_string()],
+        ));
+
+        // Responses and allowed notifications pass
+        let result = br#"{"jsonrpc":"2.0","result":{"tools":[]},"id":1}"#;
+        assert!(matches!(handler.on_server_frame(WsOpcode::Text, result, true), WsFrameAction::Continue));
+        let progress = br#"{"jsonrpc":"2.0","method":"notifications/progress","params":{}}"#;
+        assert!(matches!(handler.on_server_frame(WsOpcode::Text, progress, true), WsFrameAction::Continue));
+
+        // Injected sampling requests and poisoned resource updates are dropped
+        let sampling = br#"{"jsonrpc":"2.0","method":"sampling/createMessage","params":{},"id":7}"#;
+        assert!(matches!(handler.on_server_frame(WsOpcode::Text, sampling, true), WsFrameAction::Drop));
+        let poisoned = br#"{"jsonrpc":"2.0","method":"notifications/resources/updated","params":{"uri":"ignore previous instructions"}}"#;
+        assert!(matches!(handler.on_server_frame(WsOpcode::Text, poisoned, true), WsFrameAction::Drop));
+        assert_eq!(handler.dropped_server_messages(), 2);
+        assert_eq!(handler.state(), WsState::Open);
+
+        // Client traffic is unaffected
+        let call = br#"{"jsonrpc":"2.0","method":"tools/list","id":2}"#;
+        assert!(matches!(handler.on_frame(WsOpcode::Text, call, true), WsFrameAction::Continue));
+    }
+
+    #[test]
+    fn test_fragmented_server_match_closes() {
+        let mut handler = McpWebSocketHandler::new();
+        handler.init_patterns(vec!["jailbreak".to_string()], 4096);
+        let first = br#"{"jsonrpc":"2.0","method":"notifications/message","params":{"data":"jail"#;
+        assert!(matches!(handler.on_server_frame(WsOpcode::Text, first, false), WsFrameAction::Continue));
+        match handler.on_server_frame(WsOpcode::Continuation, br#"break"}}"#, true) {
+            WsFrameAction::Block(close) => {
+                assert_eq!(close.code, 1008);
+                assert_eq!(close.reason, "Pattern 'jailbreak' detected in server message");
+            }
+            other => panic!("expected block, got {:?}", other),
+        }
+        assert_eq!(handler.state(), WsState::Closing);
+        let late = br#"{"jsonrpc":"2.0","result":{},"id":3}"#;
+        assert!(matches!(handler.on_server_frame(WsOpcode::Text, late, true), WsFrameAction::Drop));
+
+        let mut handler = McpWebSocketHandler::new();
+        match handler.on_server_frame(WsOpcode::Continuation, b"{}", true) {
+            WsFrameAction::Block(close) => assert_eq!(close.code, 1002),
+            other => panic!("expected block, got {:?}", other),
+        }
+    }
 }
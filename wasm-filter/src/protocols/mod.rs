@@ -3,9 +3,74 @@
 //! This module provides handlers for:
 //! - MCP (Model Context Protocol) - HTTP, SSE, WebSocket transports
 //! - A2A (Agent-to-Agent) - JSONRPC, gRPC, HTTP+JSON bindings
+//! - OpenAI Chat Completions and Anthropic Messages request structure
+//! - gRPC call status from trailers
 
 pub mod mcp;
 pub mod a2a;
+pub mod openai;
+pub mod anthropic;
+pub mod grpc;
 
 pub use mcp::{McpHandler, McpTransport, McpRequest, McpResponse, McpValidationError};
-pub use a2a::{A2AHandler, A2ABinding, A2AMessage, A2AValidationError};
+pub use a2a::{A2AHandler, A2ABinding, A2AMessage, A2ARequestError, A2AValidationError};
+pub use openai::{ChatField, ChatRole};
+pub use grpc::GrpcStatus;
+
+/// A fragment of model-generated tool-call arguments in a streamed response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolCallDelta<'a> {
+    /// Identifies the call within the response (choice or output, call index)
+    pub call: (u64, u64),
+    /// Tool name, on the fragment that carries it
+    pub name: Option<&'a str>,
+    /// Next piece of the JSON-encoded arguments
+    pub arguments: &'a str,
+}
+
+/// A complete model-generated tool call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolCall {
+    /// Tool name
+    pub name: String,
+    /// JSON-encoded arguments
+    pub arguments: String,
+}
+
+/// Chat request schemas understood by role-aware scanning
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatApi {
+    /// OpenAI `/chat/completions`
+    OpenAi,
+    /// Anthropic `/v1/messages`
+    Anthropic,
+}
+
+impl ChatApi {
+    /// Detect the schema from the request path
+    pub fn from_path(path: &str) -> Option<Self> {
+        if openai::is_chat_completions_path(path) {
+            Some(ChatApi::OpenAi)
+        } else if anthropic::is_messages_path(path) {
+            Some(ChatApi::Anthropic)
+        } else {
+            None
+        }
+    }
+
+    /// Classify a JSON path of a request body
+    pub fn classify(&self, path: &str) -> ChatField {
+        match self {
+            ChatApi::OpenAi => ChatField::classify(path),
+            ChatApi::Anthropic => anthropic::classify(path),
+        }
+    }
+
+    /// Role implied by a content block type
+    pub fn block_role(&self, block_type: &str) -> Option<ChatRole> {
+        match self {
+            ChatApi::OpenAi => None,
+            ChatApi::Anthropic => anthropic::block_role(block_type),
+        }
+    }
+}
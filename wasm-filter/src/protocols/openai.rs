@@ -0,0 +1,255 @@
+//! OpenAI Chat Completions
+//!
+//! Request-side knowledge of `/chat/completions` bodies: message roles and
+//! where role, content and model sit in the JSON document. Paths are the
+//! ones produced by the streaming JSON tokenizer (`$.messages[0].content`).
+//! Response-side: text deltas and tool calls of completions.
+
+use super::{ToolCall, ToolCallDelta};
+use serde_json::Value;
+
+/// Chat message role
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatRole {
+    /// `system` and `developer` messages (application instructions)
+    System,
+    /// End-user input
+    User,
+    /// Earlier model output
+    Assistant,
+    /// Tool and legacy function results
+    Tool,
+}
+
+impl ChatRole {
+    /// Parse a role name; unknown roles are treated as user input
+    pub fn parse(role: &str) -> Self {
+        match role {
+            "system" | "developer" => ChatRole::System,
+            "assistant" => ChatRole::Assistant,
+            "tool" | "function" => ChatRole::Tool,
+            _ => ChatRole::User,
+        }
+    }
+
+    /// Role name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChatRole::System => "system",
+            ChatRole::User => "user",
+            ChatRole::Assistant => "assistant",
+            ChatRole::Tool => "tool",
+        }
+    }
+}
+
+/// What a string value in a chat request is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatField {
+    /// `$.model`
+    Model,
+    /// Top-level system prompt (Anthropic `$.system`)
+    System,
+    /// `$.messages[i].role`
+    Role(usize),
+    /// Any other string inside `$.messages[i]`
+    Message(usize),
+    /// `$.messages[i].content[j].type` of a typed content block
+    BlockType(usize, usize),
+    /// Any other string inside a typed content block
+    Block(usize, usize),
+    /// Anything else (tools, metadata)
+    Other,
+}
+
+impl ChatField {
+    /// Classify a JSON path of an OpenAI chat completion request
+    pub fn classify(path: &str) -> Self {
+        if path == "$.model" {
+            return ChatField::Model;
+        }
+        match split_index(path, "$.messages[") {
+            Some((i, ".role")) => ChatField::Role(i),
+            Some((i, _)) => ChatField::Message(i),
+            None => ChatField::Other,
+        }
+    }
+}
+
+/// Split `<prefix><index>]<rest>` into the index and the rest
+pub(crate) fn split_index<'a>(path: &'a str, prefix: &str) -> Option<(usize, &'a str)> {
+    let (index, rest) = path.strip_prefix(prefix)?.split_once(']')?;
+    Some((index.parse().ok()?, rest))
+}
+
+/// Text deltas in one streamed chunk, as `(choice index, text)`
+///
+/// Covers Chat Completions chunks (`choices[].delta.content`) and Responses
+/// API `response.output_text.delta` events.
+pub fn stream_deltas(chunk: &Value) -> Vec<(u64, &str)> {
+    if chunk["type"] == "response.output_text.delta" {
+        let index = chunk["output_index"].as_u64().unwrap_or(0);
+        return chunk["delta"].as_str().map(|text| (index, text)).into_iter().collect();
+    }
+    let choices = match chunk["choices"].as_array() {
+        Some(choices) => choices,
+        None => return Vec::new(),
+    };
+    choices
+        .iter()
+        .filter_map(|choice| {
+            let text = choice["delta"]["content"].as_str()?;
+            Some((choice["index"].as_u64().unwrap_or(0), text))
+        })
+        .collect()
+}
+
+/// Tool-call argument fragments in one streamed chunk
+///
+/// Covers Chat Completions `delta.tool_calls` (and legacy `function_call`)
+/// and Responses API function-call events.
+pub fn stream_tool_call_deltas(chunk: &Value) -> Vec<ToolCallDelta<'_>> {
+    let output = chunk["output_index"].as_u64().unwrap_or(0);
+    match chunk["type"].as_str() {
+        Some("response.output_item.added") if chunk["item"]["type"] == "function_call" => {
+            let name = chunk["item"]["name"].as_str();
+            return vec![ToolCallDelta { call: (output, 0), name, arguments: "" }];
+        }
+        Some("response.function_call_arguments.delta") => {
+            let arguments = chunk["delta"].as_str().unwrap_or("");
+            return vec![ToolCallDelta { call: (output, 0), name: None, arguments }];
+        }
+        _ => {}
+    }
+
+    let mut deltas = Vec::new();
+    for choice in chunk["choices"].as_array().into_iter().flatten() {
+        let index = choice["index"].as_u64().unwrap_or(0);
+        let delta = &choice["delta"];
+        for call in delta["tool_calls"].as_array().into_iter().flatten() {
+            deltas.push(ToolCallDelta {
+                call: (index, call["index"].as_u64().unwrap_or(0)),
+                name: call["function"]["name"].as_str(),
+                arguments: call["function"]["arguments"].as_str().unwrap_or(""),
+            });
+        }
+        if delta["function_call"].is_object() {
+            deltas.push(ToolCallDelta {
+                call: (index, 0),
+                name: delta["function_call"]["name"].as_str(),
+                arguments: delta["function_call"]["arguments"].as_str().unwrap_or(""),
+            });
+        }
+    }
+    deltas
+}
+
+/// Tool calls in a complete (non-streamed) response body
+pub fn tool_calls(body: &Value) -> Vec<ToolCall> {
+    let call = |function: &Value| ToolCall {
+        name: function["name"].as_str().unwrap_or_default().to_string(),
+        arguments: function["arguments"].as_str().unwrap_or_default().to_string(),
+    };
+    let mut calls = Vec::new();
+    for choice in body["choices"].as_array().into_iter().flatten() {
+        let message = &choice["message"];
+        for tool_call in message["tool_calls"].as_array().into_iter().flatten() {
+            calls.push(call(&tool_call["function"]));
+        }
+        if message["function_call"].is_object() {
+            calls.push(call(&message["function_call"]));
+        }
+    }
+    // Responses API
+    for item in body["output"].as_array().into_iter().flatten() {
+        if item["type"] == "function_call" {
+            calls.push(call(item));
+        }
+    }
+    calls
+}
+
+/// Whether a request path is an OpenAI-style chat completions endpoint
+pub fn is_chat_completions_path(path: &str) -> bool {
+    path.split('?')
+        .next()
+        .is_some_and(|p| p.trim_end_matches('/').ends_with("/chat/completions"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_role_parse() {
+        assert_eq!(ChatRole::parse("developer"), ChatRole::System);
+        assert_eq!(ChatRole::parse("function"), ChatRole::Tool);
+        assert_eq!(ChatRole::parse("assistant"), ChatRole::Assistant);
+        assert_eq!(ChatRole::parse("something-new"), ChatRole::User);
+    }
+
+    #[test]
+    fn test_classify_paths() {
+        assert_eq!(ChatField::classify("$.model"), ChatField::Model);
+        assert_eq!(ChatField::classify("$.messages[3].role"), ChatField::Role(3));
+        assert_eq!(ChatField::classify("$.messages[0].content"), ChatField::Message(0));
+        assert_eq!(
+            ChatField::classify("$.messages[1].content[0].text"),
+            ChatField::Message(1)
+        );
+        assert_eq!(
+            ChatField::classify("$.messages[2].tool_calls[0].function.arguments"),
+            ChatField::Message(2)
+        );
+        assert_eq!(ChatField::classify("$.tools[0].function.description"), ChatField::Other);
+        assert_eq!(ChatField::classify("$.metadata.model"), ChatField::Other);
+    }
+
+    #[test]
+    fn test_stream_deltas() {
+        let chunk: Value = serde_json::from_str(
+            r#"{"choices":[{"index":0,"delta":{"content":"Hel"}},{"index":1,"delta":{}}]}"#,
+        )
+        .unwrap();
+        assert_eq!(stream_deltas(&chunk), vec![(0, "Hel")]);
+
+        let event: Value = serde_json::from_str(
+            r#"{"type":"response.output_text.delta","output_index":2,"delta":"lo"}"#,
+        )
+        .unwrap();
+        assert_eq!(stream_deltas(&event), vec![(2, "lo")]);
+        assert!(stream_deltas(&Value::Null).is_empty());
+    }
+
+    #[test]
+    fn test_tool_calls() {
+        let chunk: Value = serde_json::from_str(
+            r#"{"choices":[{"index":0,"delta":{"tool_calls":[
+                {"index":1,"function":{"name":"shell","arguments":"{\"cmd"}}]}}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            stream_tool_call_deltas(&chunk),
+            vec![ToolCallDelta { call: (0, 1), name: Some("shell"), arguments: "{\"cmd" }]
+        );
+
+        let body: Value = serde_json::from_str(
+            r#"{"choices":[{"message":{"tool_calls":[
+                {"function":{"name":"shell","arguments":"{\"cmd\":\"ls\"}"}}]}}],
+              "output":[{"type":"function_call","name":"search","arguments":"{}"}]}"#,
+        )
+        .unwrap();
+        let calls = tool_calls(&body);
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].arguments, r#"{"cmd":"ls"}"#);
+        assert_eq!(calls[1].name, "search");
+    }
+
+    #[test]
+    fn test_chat_completions_path() {
+        assert!(is_chat_completions_path("/v1/chat/completions"));
+        assert!(is_chat_completions_path("/openai/deployments/gpt/chat/completions?api-version=1"));
+        assert!(!is_chat_completions_path("/v1/completions"));
+        assert!(!is_chat_completions_path("/v1/chat/completions/abc"));
+    }
+}
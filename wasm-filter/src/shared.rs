@@ -0,0 +1,158 @@
+//! Shared Data Module for AI-Guard
+//!
+//! Wraps proxy-wasm shared data (a key/value store shared by all workers
+//! of a VM) behind a trait, so features built on it can be tested natively.
+//!
+//! Writes use compare-and-swap: `set` with the CAS token returned by `get`
+//! fails with `SharedError::CasMismatch` if another worker wrote first.
+
+#[cfg(feature = "proxy-wasm")]
+use proxy_wasm::hostcalls;
+#[cfg(feature = "proxy-wasm")]
+use proxy_wasm::types::Status;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+/// Maximum CAS retries for read-modify-write updates
+pub const MAX_CAS_RETRIES: usize = 8;
+
+/// Shared data errors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SharedError {
+    /// Another writer updated the key since it was read
+    CasMismatch,
+    /// Host rejected the operation
+    Host(String),
+}
+
+impl std::fmt::Display for SharedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SharedError::CasMismatch => write!(f, "CAS mismatch"),
+            SharedError::Host(e) => write!(f, "Shared data error: {}", e),
+        }
+    }
+}
+
+/// Key/value store shared across workers
+pub trait SharedStore {
+    /// Read a value and its CAS token
+    fn get(&self, key: &str) -> (Option<Vec<u8>>, Option<u32>);
+    /// Write a value; with a CAS token, only if unchanged since read
+    fn set(&self, key: &str, value: &[u8], cas: Option<u32>) -> Result<(), SharedError>;
+
+    /// Read-modify-write with CAS retries
+    ///
+    /// `update` receives the current value (if any) and returns the new one.
+    fn update<F>(&self, key: &str, mut update: F) -> Result<Vec<u8>, SharedError>
+    where
+        F: FnMut(Option<&[u8]>) -> Vec<u8>,
+        Self: Sized,
+    {
+        for _ in 0..MAX_CAS_RETRIES {
+            let (current, cas) = self.get(key);
+            let next = update(current.as_deref());
+            match self.set(key, &next, cas) {
+                Ok(()) => return Ok(next),
+                Err(SharedError::CasMismatch) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Err(SharedError::CasMismatch)
+    }
+}
+
+/// Shared store backed by proxy-wasm shared data
+#[cfg(feature = "proxy-wasm")]
+pub struct HostSharedStore;
+
+#[cfg(feature = "proxy-wasm")]
+impl SharedStore for HostSharedStore {
+    fn get(&self, key: &str) -> (Option<Vec<u8>>, Option<u32>) {
+        hostcalls::get_shared_data(key).unwrap_or((None, None))
+    }
+
+    fn set(&self, key: &str, value: &[u8], cas: Option<u32>) -> Result<(), SharedError> {
+        match hostcalls::set_shared_data(key, Some(value), cas) {
+            Ok(()) => Ok(()),
+            Err(Status::CasMismatch) => Err(SharedError::CasMismatch),
+            Err(e) => Err(SharedError::Host(format!("{:?}", e))),
+        }
+    }
+}
+
+/// In-memory shared store (single worker, tests)
+#[derive(Debug, Default)]
+pub struct MemorySharedStore {
+    /// Values and their CAS versions
+    data: RefCell<HashMap<String, (Vec<u8>, u32)>>,
+}
+
+impl MemorySharedStore {
+    /// Create an empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SharedStore for MemorySharedStore {
+    fn get(&self, key: &str) -> (Option<Vec<u8>>, Option<u32>) {
+        match self.data.borrow().get(key) {
+            Some((value, cas)) => (Some(value.clone()), Some(*cas)),
+            None => (None, None),
+        }
+    }
+
+    fn set(&self, key: &str, value: &[u8], cas: Option<u32>) -> Result<(), SharedError> {
+        let mut data = self.data.borrow_mut();
+        let version = data.get(key).map(|(_, v)| *v).unwrap_or(0);
+        if let Some(expected) = cas {
+            if expected != version {
+                return Err(SharedError::CasMismatch);
+            }
+        }
+        data.insert(key.to_string(), (value.to_vec(), version + 1));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_set() {
+        let store = MemorySharedStore::new();
+        assert_eq!(store.get("k"), (None, None));
+
+        store.set("k", b"v1", None).unwrap();
+        let (value, cas) = store.get("k");
+        assert_eq!(value.as_deref(), Some(&b"v1"[..]));
+        assert!(cas.is_some());
+    }
+
+    #[test]
+    fn test_cas_mismatch() {
+        let store = MemorySharedStore::new();
+        store.set("k", b"v1", None).unwrap();
+        let (_, cas) = store.get("k");
+
+        // Another writer gets in first
+        store.set("k", b"v2", cas).unwrap();
+        assert_eq!(store.set("k", b"v3", cas), Err(SharedError::CasMismatch));
+    }
+
+    #[test]
+    fn test_update() {
+        let store = MemorySharedStore::new();
+        for _ in 0..3 {
+            store
+                .update("counter", |current| {
+                    let n = current.map(|v| v[0]).unwrap_or(0);
+                    vec![n + 1]
+                })
+                .unwrap();
+        }
+        assert_eq!(store.get("counter").0, Some(vec![3]));
+    }
+}
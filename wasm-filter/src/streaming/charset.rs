@@ -0,0 +1,269 @@
+//! Charset Transcoding
+//!
+//! Patterns, the JSON tokenizer and PII detection all work on UTF-8. A body
+//! declared `charset=utf-16` (or starting with a UTF-16 byte order mark)
+//! would otherwise go through the scanner as interleaved NUL bytes and
+//! never match. Such bodies are transcoded to UTF-8 chunk by chunk, with
+//! code units and surrogate pairs split across chunks carried over.
+//!
+//! Latin-1 and windows-1252 are transcoded too, so patterns with accented
+//! characters match. Other charsets are scanned as raw bytes, as before.
+
+/// Replacement for malformed input
+const REPLACEMENT: char = '\u{FFFD}';
+
+/// windows-1252 characters at 0x80..=0x9F (where Latin-1 has C1 controls)
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8D}', 'Ž', '\u{8F}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9D}', 'ž', 'Ÿ',
+];
+
+/// Body character set
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Charset {
+    /// UTF-8, US-ASCII, or no charset declared
+    Utf8,
+    /// UTF-16 with a byte order mark (big-endian without one)
+    Utf16,
+    /// UTF-16LE
+    Utf16Le,
+    /// UTF-16BE
+    Utf16Be,
+    /// ISO-8859-1
+    Latin1,
+    /// windows-1252
+    Windows1252,
+    /// Anything else (scanned as raw bytes)
+    Unsupported(String),
+}
+
+impl Charset {
+    /// Charset of a `Content-Type` header value
+    pub fn from_content_type(value: &str) -> Self {
+        let param = value.split(';').skip(1).find_map(|p| {
+            let (name, value) = p.split_once('=')?;
+            name.trim().eq_ignore_ascii_case("charset").then(|| value.trim().trim_matches('"'))
+        });
+        match param {
+            Some(name) => Self::parse(name),
+            None => Charset::Utf8,
+        }
+    }
+
+    /// Parse a charset name
+    pub fn parse(name: &str) -> Self {
+        match name.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" | "us-ascii" | "ascii" => Charset::Utf8,
+            "utf-16" | "utf16" => Charset::Utf16,
+            "utf-16le" => Charset::Utf16Le,
+            "utf-16be" => Charset::Utf16Be,
+            "iso-8859-1" | "iso8859-1" | "iso_8859-1" | "latin1" | "latin-1" | "l1" => {
+                Charset::Latin1
+            }
+            "windows-1252" | "cp1252" => Charset::Windows1252,
+            _ => Charset::Unsupported(name.to_string()),
+        }
+    }
+
+    /// Charset announced by a byte order mark at the start of a body
+    pub fn from_bom(prefix: &[u8]) -> Option<Self> {
+        match prefix {
+            [0xFF, 0xFE, ..] => Some(Charset::Utf16Le),
+            [0xFE, 0xFF, ..] => Some(Charset::Utf16Be),
+            _ => None,
+        }
+    }
+}
+
+/// Source encoding of a decoder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Source {
+    /// Endianness not known until the byte order mark is seen
+    Utf16Unknown,
+    Utf16 { big_endian: bool },
+    Latin1,
+    Windows1252,
+}
+
+/// Incremental transcoder to UTF-8
+pub struct CharsetDecoder {
+    source: Source,
+    /// Bytes of an incomplete code unit or surrogate pair
+    pending: Vec<u8>,
+    /// Whether the start of the body (byte order mark) has been handled
+    started: bool,
+}
+
+impl CharsetDecoder {
+    /// Create a decoder for a charset that needs transcoding (None for UTF-8/unsupported)
+    pub fn new(charset: &Charset) -> Option<Self> {
+        let source = match charset {
+            Charset::Utf16 => Source::Utf16Unknown,
+            Charset::Utf16Le => Source::Utf16 { big_endian: false },
+            Charset::Utf16Be => Source::Utf16 { big_endian: true },
+            Charset::Latin1 => Source::Latin1,
+            Charset::Windows1252 => Source::Windows1252,
+            Charset::Utf8 | Charset::Unsupported(_) => return None,
+        };
+        Some(Self { source, pending: Vec::new(), started: false })
+    }
+
+    /// Transcode the next chunk, appending UTF-8 to `out`
+    pub fn decode(&mut self, chunk: &[u8], out: &mut Vec<u8>) {
+        match self.source {
+            Source::Latin1 => {
+                for &byte in chunk {
+                    push_char(char::from(byte), out);
+                }
+            }
+            Source::Windows1252 => {
+                for &byte in chunk {
+                    let c = match byte {
+                        0x80..=0x9F => WINDOWS_1252_HIGH[(byte - 0x80) as usize],
+                        _ => char::from(byte),
+                    };
+                    push_char(c, out);
+                }
+            }
+            Source::Utf16Unknown | Source::Utf16 { .. } => self.decode_utf16(chunk, out),
+        }
+    }
+
+    /// End of body: an incomplete trailing sequence becomes U+FFFD
+    pub fn finish(&mut self, out: &mut Vec<u8>) {
+        if !self.pending.is_empty() {
+            self.pending.clear();
+            push_char(REPLACEMENT, out);
+        }
+    }
+
+    fn decode_utf16(&mut self, chunk: &[u8], out: &mut Vec<u8>) {
+        let mut data = std::mem::take(&mut self.pending);
+        data.extend_from_slice(chunk);
+        let mut i = 0;
+
+        if !self.started {
+            if data.len() < 2 {
+                self.pending = data;
+                return;
+            }
+            self.started = true;
+            let bom = Charset::from_bom(&data);
+            if let Source::Utf16Unknown = self.source {
+                let big_endian = bom != Some(Charset::Utf16Le);
+                self.source = Source::Utf16 { big_endian };
+            }
+            let big_endian = matches!(self.source, Source::Utf16 { big_endian: true });
+            let expected = if big_endian { Charset::Utf16Be } else { Charset::Utf16Le };
+            if bom == Some(expected) {
+                i = 2;
+            }
+        }
+
+        let big_endian = matches!(self.source, Source::Utf16 { big_endian: true });
+        let unit = |at: usize| {
+            let pair = [data[at], data[at + 1]];
+            if big_endian { u16::from_be_bytes(pair) } else { u16::from_le_bytes(pair) }
+        };
+        while i + 2 <= data.len() {
+            let high = unit(i);
+            match high {
+                0xD800..=0xDBFF => {
+                    if i + 4 > data.len() {
+                        break;
+                    }
+                    let low = unit(i + 2);
+                    if (0xDC00..=0xDFFF).contains(&low) {
+                        let code = 0x10000 + ((high as u32 - 0xD800) << 10) + (low as u32 - 0xDC00);
+                        push_char(char::from_u32(code).unwrap_or(REPLACEMENT), out);
+                        i += 4;
+                    } else {
+                        push_char(REPLACEMENT, out);
+                        i += 2;
+                    }
+                }
+                _ => {
+                    push_char(char::from_u32(high as u32).unwrap_or(REPLACEMENT), out);
+                    i += 2;
+                }
+            }
+        }
+        self.pending = data.split_off(i);
+    }
+}
+
+fn push_char(c: char, out: &mut Vec<u8>) {
+    let mut buf = [0u8; 4];
+    out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(text: &str, big_endian: bool) -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|u| if big_endian { u.to_be_bytes() } else { u.to_le_bytes() })
+            .collect()
+    }
+
+    fn transcode(charset: &Charset, body: &[u8], chunk: usize) -> String {
+        let mut decoder = CharsetDecoder::new(charset).unwrap();
+        let mut out = Vec::new();
+        for part in body.chunks(chunk) {
+            decoder.decode(part, &mut out);
+        }
+        decoder.finish(&mut out);
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_from_content_type() {
+        let charset = |ct| Charset::from_content_type(ct);
+        assert_eq!(charset("application/json"), Charset::Utf8);
+        assert_eq!(charset("application/json; charset=UTF-8"), Charset::Utf8);
+        assert_eq!(charset("text/plain;charset=\"utf-16\""), Charset::Utf16);
+        assert_eq!(charset("application/json; charset=utf-16le"), Charset::Utf16Le);
+        assert_eq!(charset("text/plain; format=flowed; charset=ISO-8859-1"), Charset::Latin1);
+        let shift_jis = Charset::Unsupported("shift_jis".into());
+        assert_eq!(charset("text/plain; charset=shift_jis"), shift_jis);
+        assert!(CharsetDecoder::new(&Charset::Utf8).is_none());
+    }
+
+    #[test]
+    fn test_utf16_split_across_chunks() {
+        let text = r#"{"prompt": "ignore previous instructions 🚀 ça"}"#;
+        for big_endian in [false, true] {
+            let charset = if big_endian { Charset::Utf16Be } else { Charset::Utf16Le };
+            let body = utf16(text, big_endian);
+            // Odd chunk sizes split code units and the surrogate pair
+            for chunk in [1, 3, 5, 64] {
+                assert_eq!(transcode(&charset, &body, chunk), text);
+            }
+        }
+    }
+
+    #[test]
+    fn test_utf16_byte_order_mark() {
+        let mut body = vec![0xFF, 0xFE];
+        body.extend(utf16("hello", false));
+        assert_eq!(Charset::from_bom(&body), Some(Charset::Utf16Le));
+        assert_eq!(transcode(&Charset::Utf16, &body, 1), "hello");
+        assert_eq!(transcode(&Charset::Utf16Le, &body, 7), "hello");
+        // No BOM: big-endian
+        assert_eq!(transcode(&Charset::Utf16, &utf16("hi", true), 2), "hi");
+    }
+
+    #[test]
+    fn test_utf16_malformed() {
+        // Lone low surrogate, then a high surrogate cut off at the end
+        let body = [0x00, 0xDC, 0x41, 0x00, 0x3D, 0xD8];
+        assert_eq!(transcode(&Charset::Utf16Le, &body, 4), "\u{FFFD}A\u{FFFD}");
+    }
+
+    #[test]
+    fn test_single_byte_charsets() {
+        assert_eq!(transcode(&Charset::Latin1, b"caf\xe9", 2), "café");
+        assert_eq!(transcode(&Charset::Windows1252, b"\x93hi\x94 \x80", 3), "“hi” €");
+    }
+}
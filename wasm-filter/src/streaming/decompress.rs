@@ -0,0 +1,359 @@
+//! Content-Encoding Decompression
+//!
+//! Decodes gzip (RFC 1952) and deflate (zlib, RFC 1950, or raw RFC 1951)
+//! bodies chunk by chunk so compressed AI traffic can be scanned. Output
+//! is bounded by a size cap and a compression-ratio guard against
+//! decompression bombs. Trailers (CRC32, Adler-32) are not verified:
+//! the upstream does that, we only need the plaintext.
+
+use super::inflate::{InflateError, Inflater};
+
+/// Output size below which the ratio guard is not applied
+///
+/// Small JSON bodies legitimately compress very well.
+const RATIO_MIN_OUTPUT: u64 = 64 * 1024;
+
+/// Longest gzip header buffered (FEXTRA is at most 64 KiB)
+const MAX_GZIP_HEADER: usize = 72 * 1024;
+
+/// Body content encoding
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContentEncoding {
+    /// No encoding
+    Identity,
+    /// gzip / x-gzip
+    Gzip,
+    /// deflate (zlib-wrapped, or raw as some clients send)
+    Deflate,
+    /// Anything else (br, zstd, stacked encodings)
+    Unsupported(String),
+}
+
+impl ContentEncoding {
+    /// Parse a `Content-Encoding` header value
+    pub fn parse(value: &str) -> Self {
+        let codings: Vec<String> = value
+            .split(',')
+            .map(|c| c.trim().to_ascii_lowercase())
+            .filter(|c| !c.is_empty() && c != "identity")
+            .collect();
+        match codings.as_slice() {
+            [] => ContentEncoding::Identity,
+            [c] if c == "gzip" || c == "x-gzip" => ContentEncoding::Gzip,
+            [c] if c == "deflate" => ContentEncoding::Deflate,
+            _ => ContentEncoding::Unsupported(value.trim().to_string()),
+        }
+    }
+}
+
+/// Decompression errors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecompressError {
+    /// Malformed compressed data
+    Invalid(&'static str),
+    /// Decompressed size cap exceeded
+    TooLarge,
+    /// Compression ratio guard tripped
+    RatioExceeded,
+}
+
+impl std::fmt::Display for DecompressError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecompressError::Invalid(msg) => write!(f, "Invalid compressed data: {}", msg),
+            DecompressError::TooLarge => write!(f, "Decompressed body exceeds size limit"),
+            DecompressError::RatioExceeded => write!(f, "Compression ratio exceeds limit"),
+        }
+    }
+}
+
+impl From<InflateError> for DecompressError {
+    fn from(e: InflateError) -> Self {
+        match e {
+            InflateError::Invalid(msg) => DecompressError::Invalid(msg),
+            InflateError::OutputLimit => DecompressError::TooLarge,
+        }
+    }
+}
+
+/// Container around the DEFLATE stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Wrapper {
+    Gzip,
+    Zlib,
+}
+
+/// Incremental body decoder
+pub struct BodyDecoder {
+    wrapper: Wrapper,
+    inflater: Inflater,
+    /// Wrapper header bytes buffered until complete (None once parsed)
+    header: Option<Vec<u8>>,
+    max_output: usize,
+    max_ratio: u64,
+    total_in: u64,
+}
+
+impl BodyDecoder {
+    /// Create a decoder for a supported encoding (None for identity/unsupported)
+    pub fn new(encoding: &ContentEncoding, max_output: usize, max_ratio: u32) -> Option<Self> {
+        let wrapper = match encoding {
+            ContentEncoding::Gzip => Wrapper::Gzip,
+            ContentEncoding::Deflate => Wrapper::Zlib,
+            _ => return None,
+        };
+        Some(Self {
+            wrapper,
+            inflater: Inflater::new(),
+            header: Some(Vec::new()),
+            max_output,
+            max_ratio: max_ratio as u64,
+            total_in: 0,
+        })
+    }
+
+    /// Decode the next compressed chunk, appending plaintext to `out`
+    pub fn decode(&mut self, chunk: &[u8], out: &mut Vec<u8>) -> Result<(), DecompressError> {
+        self.total_in += chunk.len() as u64;
+
+        if let Some(header) = &mut self.header {
+            header.extend_from_slice(chunk);
+            let len = match self.wrapper {
+                Wrapper::Gzip => gzip_header_len(header)?,
+                Wrapper::Zlib => zlib_header_len(header)?,
+            };
+            let len = match len {
+                Some(len) => len,
+                None => return Ok(()),
+            };
+            let rest = header.split_off(len);
+            self.header = None;
+            return self.inflate(&rest, out);
+        }
+
+        self.inflate(chunk, out)
+    }
+
+    /// Whether the compressed stream has ended
+    pub fn is_done(&self) -> bool {
+        self.inflater.is_done()
+    }
+
+    /// Total plaintext bytes produced
+    pub fn total_out(&self) -> u64 {
+        self.inflater.total_out()
+    }
+
+    fn inflate(&mut self, data: &[u8], out: &mut Vec<u8>) -> Result<(), DecompressError> {
+        let budget = self.max_output.saturating_sub(self.total_out() as usize);
+        self.inflater.inflate(data, out, budget)?;
+
+        let total_out = self.total_out();
+        if total_out > RATIO_MIN_OUTPUT && total_out > self.total_in * self.max_ratio {
+            return Err(DecompressError::RatioExceeded);
+        }
+        Ok(())
+    }
+}
+
+/// An `Accept-Encoding` value narrowed to the codings `BodyDecoder` handles
+///
+/// Other codings (br, zstd, `*`) are dropped; "identity" is all that is left
+/// when none remain.
+pub fn decodable_codings(accept: &str) -> String {
+    let codings: Vec<&str> = accept
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| {
+            let coding = entry.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+            matches!(coding.as_str(), "gzip" | "x-gzip" | "deflate" | "identity")
+        })
+        .collect();
+    if codings.is_empty() {
+        return "identity".to_string();
+    }
+    codings.join(", ")
+}
+
+/// Decode a complete body in one call
+pub fn decode_all(
+    encoding: &ContentEncoding,
+    body: &[u8],
+    max_output: usize,
+    max_ratio: u32,
+) -> Option<Result<Vec<u8>, DecompressError>> {
+    let mut decoder = BodyDecoder::new(encoding, max_output, max_ratio)?;
+    let mut out = Vec::new();
+    Some(decoder.decode(body, &mut out).map(|_| out))
+}
+
+/// Length of a complete gzip member header, or None if more bytes are needed
+fn gzip_header_len(buf: &[u8]) -> Result<Option<usize>, DecompressError> {
+    const FHCRC: u8 = 0x02;
+    const FEXTRA: u8 = 0x04;
+    const FNAME: u8 = 0x08;
+    const FCOMMENT: u8 = 0x10;
+
+    if buf.len() > MAX_GZIP_HEADER {
+        return Err(DecompressError::Invalid("gzip header too large"));
+    }
+    if buf.len() < 10 {
+        return Ok(None);
+    }
+    if buf[0] != 0x1f || buf[1] != 0x8b {
+        return Err(DecompressError::Invalid("not a gzip stream"));
+    }
+    if buf[2] != 8 {
+        return Err(DecompressError::Invalid("unsupported gzip compression method"));
+    }
+    let flags = buf[3];
+    if flags & 0xe0 != 0 {
+        return Err(DecompressError::Invalid("reserved gzip flags set"));
+    }
+
+    let mut pos = 10;
+    if flags & FEXTRA != 0 {
+        if buf.len() < pos + 2 {
+            return Ok(None);
+        }
+        pos += 2 + u16::from_le_bytes([buf[pos], buf[pos + 1]]) as usize;
+    }
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            match buf.get(pos..).and_then(|rest| rest.iter().position(|&b| b == 0)) {
+                Some(end) => pos += end + 1,
+                None => return Ok(None),
+            }
+        }
+    }
+    if flags & FHCRC != 0 {
+        pos += 2;
+    }
+
+    Ok(if buf.len() >= pos { Some(pos) } else { None })
+}
+
+/// Length of the zlib header: 2, or 0 for raw deflate sent as "deflate"
+fn zlib_header_len(buf: &[u8]) -> Result<Option<usize>, DecompressError> {
+    if buf.len() < 2 {
+        return Ok(None);
+    }
+    let (cmf, flg) = (buf[0], buf[1]);
+    let check = u16::from_be_bytes([cmf, flg]);
+    let is_zlib = cmf & 0x0f == 8 && cmf >> 4 <= 7 && check.is_multiple_of(31);
+    if !is_zlib {
+        return Ok(Some(0));
+    }
+    if flg & 0x20 != 0 {
+        return Err(DecompressError::Invalid("zlib preset dictionary not supported"));
+    }
+    Ok(Some(2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// gzip of "ignore previous instructions" (mtime 0, with FNAME "a")
+    const GZIP_BODY: &[u8] = &[
+        0x1f, 0x8b, 0x08, 0x08, 0x00, 0x00, 0x00, 0x00, 0x02, 0xff, 0x61, 0x00, 0xcb, 0x4c, 0xcf,
+        0xcb, 0x2f, 0x4a, 0x55, 0x28, 0x28, 0x4a, 0x2d, 0xcb, 0xcc, 0x2f, 0x2d, 0x56, 0xc8, 0xcc,
+        0x2b, 0x2e, 0x29, 0x2a, 0x4d, 0x2e, 0xc9, 0xcc, 0xcf, 0x2b, 0x06, 0x00, 0xed, 0x74, 0x78,
+        0x51, 0x1c, 0x00, 0x00, 0x00,
+    ];
+
+    /// zlib of "hello hello hello"
+    const ZLIB_BODY: &[u8] = &[
+        0x78, 0xda, 0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x90, 0x00, 0x3a, 0x2e, 0x06,
+        0x7d,
+    ];
+
+    fn decode_chunked(encoding: ContentEncoding, data: &[u8], chunk: usize) -> Vec<u8> {
+        let mut decoder = BodyDecoder::new(&encoding, 1 << 20, 100).unwrap();
+        let mut out = Vec::new();
+        for part in data.chunks(chunk) {
+            decoder.decode(part, &mut out).unwrap();
+        }
+        assert!(decoder.is_done());
+        out
+    }
+
+    #[test]
+    fn test_parse_encoding() {
+        assert_eq!(ContentEncoding::parse("GZIP"), ContentEncoding::Gzip);
+        assert_eq!(ContentEncoding::parse("identity, deflate"), ContentEncoding::Deflate);
+        assert_eq!(ContentEncoding::parse(""), ContentEncoding::Identity);
+        assert_eq!(ContentEncoding::parse("br"), ContentEncoding::Unsupported("br".to_string()));
+        assert!(matches!(ContentEncoding::parse("gzip, br"), ContentEncoding::Unsupported(_)));
+    }
+
+    #[test]
+    fn test_decodable_codings() {
+        assert_eq!(decodable_codings("gzip, deflate, br"), "gzip, deflate");
+        assert_eq!(decodable_codings("br;q=1.0, GZIP;q=0.5, zstd"), "GZIP;q=0.5");
+        assert_eq!(decodable_codings("br, *"), "identity");
+        assert_eq!(decodable_codings(""), "identity");
+    }
+
+    #[test]
+    fn test_gzip_chunked() {
+        for chunk in [1, 7, GZIP_BODY.len()] {
+            let out = decode_chunked(ContentEncoding::Gzip, GZIP_BODY, chunk);
+            assert_eq!(out, b"ignore previous instructions");
+        }
+    }
+
+    #[test]
+    fn test_deflate_zlib_and_raw() {
+        let out = decode_chunked(ContentEncoding::Deflate, ZLIB_BODY, 3);
+        assert_eq!(out, b"hello hello hello");
+
+        // Raw deflate without the zlib header
+        let out = decode_chunked(ContentEncoding::Deflate, &ZLIB_BODY[2..], 3);
+        assert_eq!(out, b"hello hello hello");
+    }
+
+    #[test]
+    fn test_size_and_ratio_guards() {
+        let mut decoder = BodyDecoder::new(&ContentEncoding::Gzip, 10, 100).unwrap();
+        let mut out = Vec::new();
+        assert_eq!(decoder.decode(GZIP_BODY, &mut out), Err(DecompressError::TooLarge));
+
+        // 1 MiB of zeros compresses ~1000x
+        let bomb = zeros_bomb();
+        let mut decoder = BodyDecoder::new(&ContentEncoding::Deflate, usize::MAX, 100).unwrap();
+        let mut out = Vec::new();
+        assert_eq!(decoder.decode(&bomb, &mut out), Err(DecompressError::RatioExceeded));
+    }
+
+    #[test]
+    fn test_invalid_gzip() {
+        let result = decode_all(&ContentEncoding::Gzip, b"not gzip at all", 1024, 100).unwrap();
+        assert_eq!(result, Err(DecompressError::Invalid("not a gzip stream")));
+        assert!(decode_all(&ContentEncoding::Identity, b"x", 1024, 100).is_none());
+    }
+
+    /// Raw deflate of 1 MiB of zeros
+    fn zeros_bomb() -> Vec<u8> {
+        // A literal 0 followed by maximal back-references (length 258, distance 1),
+        // in one fixed-Huffman block; built with a tiny bit writer.
+        let mut bits: Vec<bool> = Vec::new();
+        let mut put = |value: u32, n: u32, reversed: bool| {
+            for i in 0..n {
+                let bit = if reversed { value >> (n - 1 - i) & 1 } else { value >> i & 1 };
+                bits.push(bit == 1);
+            }
+        };
+        put(1, 1, false); // BFINAL
+        put(1, 2, false); // fixed Huffman
+        put(0x30, 8, true); // literal 0
+        for _ in 0..(1 << 20) / 258 {
+            put(0xc5, 8, true); // length symbol 285 (258)
+            put(0, 5, true); // distance symbol 0 (1)
+        }
+        put(0, 7, true); // end of block
+        bits.chunks(8)
+            .map(|byte| byte.iter().enumerate().fold(0u8, |acc, (i, &b)| acc | (b as u8) << i))
+            .collect()
+    }
+}
@@ -0,0 +1,383 @@
+//! gRPC-Web and Connect Bodies
+//!
+//! Browsers reach gRPC services through gRPC-Web, and Connect clients send
+//! the same protobuf messages over plain HTTP. Either way the body is
+//! binary, so it used to skip inspection. Here messages are unwrapped and
+//! their string fields emitted as JSON tokenizer events, so they are
+//! scanned like JSON string values:
+//!
+//! - gRPC-Web and Connect streaming: each message has a 5-byte envelope
+//!   (flags, big-endian length). Compressed messages are inflated with the
+//!   `grpc-encoding` / `connect-content-encoding` coding; trailer and
+//!   end-of-stream frames are skipped. Paths are `$[message].field`.
+//! - gRPC-Web text: the same framing, base64-encoded.
+//! - Connect unary: the body is one message with no envelope. Paths are
+//!   `$.field`.
+//!
+//! Unlike JSON, a message cannot be parsed until it is complete, so each
+//! message is buffered (up to `max_message_size`).
+
+use super::decompress::{decode_all, ContentEncoding};
+use super::json_tokenizer::JsonEvent;
+use super::protobuf::string_fields;
+use crate::crypto::base64url_decode;
+
+/// Envelope header length
+const ENVELOPE_LEN: usize = 5;
+
+/// Envelope flag: the message is compressed
+const FLAG_COMPRESSED: u8 = 0x01;
+/// Envelope flags of frames that are not messages (Connect end-of-stream, gRPC-Web trailers)
+const FLAG_END_STREAM: u8 = 0x02;
+const FLAG_TRAILERS: u8 = 0x80;
+
+/// Body protocol, from the `Content-Type`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpcProtocol {
+    /// application/grpc-web(+proto)
+    GrpcWeb,
+    /// application/grpc-web-text(+proto): base64 of the gRPC-Web framing
+    GrpcWebText,
+    /// application/connect+proto: enveloped messages
+    ConnectStream,
+    /// application/proto: one bare message
+    ConnectUnary,
+}
+
+impl RpcProtocol {
+    /// Protocol of a `Content-Type` header value (None for other types, and JSON codecs)
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let media = content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+        match media.as_str() {
+            "application/grpc-web" | "application/grpc-web+proto" => Some(RpcProtocol::GrpcWeb),
+            "application/grpc-web-text" | "application/grpc-web-text+proto" => {
+                Some(RpcProtocol::GrpcWebText)
+            }
+            "application/connect+proto" => Some(RpcProtocol::ConnectStream),
+            "application/proto" => Some(RpcProtocol::ConnectUnary),
+            _ => None,
+        }
+    }
+
+    /// Header naming the per-message compression (None for unary: `Content-Encoding`)
+    pub fn encoding_header(&self) -> Option<&'static str> {
+        match self {
+            RpcProtocol::GrpcWeb | RpcProtocol::GrpcWebText => Some("grpc-encoding"),
+            RpcProtocol::ConnectStream => Some("connect-content-encoding"),
+            RpcProtocol::ConnectUnary => None,
+        }
+    }
+}
+
+/// Why a body could not be decoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RpcError {
+    /// Bad base64, envelope or protobuf
+    Malformed(&'static str),
+    /// A message is larger than `max_message_size`
+    TooLarge,
+    /// A compressed message with no decodable encoding
+    Compressed,
+}
+
+impl std::fmt::Display for RpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RpcError::Malformed(what) => write!(f, "Malformed message: {}", what),
+            RpcError::TooLarge => write!(f, "Message exceeds max size"),
+            RpcError::Compressed => write!(f, "Compressed message with unsupported encoding"),
+        }
+    }
+}
+
+/// Incremental gRPC-Web / Connect body decoder
+pub struct RpcDecoder {
+    protocol: RpcProtocol,
+    /// Message compression
+    encoding: ContentEncoding,
+    max_message_size: usize,
+    max_ratio: u32,
+    /// Base64 characters of an incomplete quantum (text variant)
+    pending_text: Vec<u8>,
+    /// Bytes of the current envelope or unary body
+    buffer: Vec<u8>,
+    /// Messages decoded so far
+    messages: usize,
+}
+
+impl RpcDecoder {
+    /// Create a decoder for a body of `protocol`
+    pub fn new(
+        protocol: RpcProtocol,
+        encoding: ContentEncoding,
+        max_message_size: usize,
+        max_ratio: u32,
+    ) -> Self {
+        Self {
+            protocol,
+            encoding,
+            max_message_size,
+            max_ratio,
+            pending_text: Vec::new(),
+            buffer: Vec::new(),
+            messages: 0,
+        }
+    }
+
+    /// Feed the next chunk, appending events of the messages it completes to `out`
+    ///
+    /// After an error, `take_buffered` returns the bytes not yet decoded.
+    pub fn feed(&mut self, chunk: &[u8], out: &mut Vec<JsonEvent>) -> Result<(), RpcError> {
+        match self.protocol {
+            RpcProtocol::GrpcWebText => match self.decode_base64(chunk) {
+                Ok(decoded) => self.buffer.extend(decoded),
+                Err(e) => {
+                    self.buffer.extend_from_slice(chunk);
+                    return Err(e);
+                }
+            },
+            _ => self.buffer.extend_from_slice(chunk),
+        }
+        if self.protocol == RpcProtocol::ConnectUnary {
+            if self.buffer.len() > self.max_message_size {
+                return Err(RpcError::TooLarge);
+            }
+            return Ok(());
+        }
+
+        while self.buffer.len() >= ENVELOPE_LEN {
+            let flags = self.buffer[0];
+            let len = u32::from_be_bytes([
+                self.buffer[1],
+                self.buffer[2],
+                self.buffer[3],
+                self.buffer[4],
+            ]) as usize;
+            if len > self.max_message_size {
+                return Err(RpcError::TooLarge);
+            }
+            if self.buffer.len() < ENVELOPE_LEN + len {
+                break;
+            }
+            if flags & (FLAG_END_STREAM | FLAG_TRAILERS) == 0 {
+                let payload = &self.buffer[ENVELOPE_LEN..ENVELOPE_LEN + len];
+                let path = format!("$[{}]", self.messages);
+                let events = match flags & FLAG_COMPRESSED {
+                    0 => message_events(payload, &path)?,
+                    _ => {
+                        let max = self.max_message_size;
+                        let inflated = decode_all(&self.encoding, payload, max, self.max_ratio)
+                            .ok_or(RpcError::Compressed)?
+                            .map_err(|_| RpcError::Malformed("compressed message"))?;
+                        message_events(&inflated, &path)?
+                    }
+                };
+                out.extend(events);
+                self.messages += 1;
+            }
+            self.buffer.drain(..ENVELOPE_LEN + len);
+        }
+        Ok(())
+    }
+
+    /// End of body: emit a unary message, and reject a truncated one
+    pub fn finish(&mut self, out: &mut Vec<JsonEvent>) -> Result<(), RpcError> {
+        if !self.pending_text.is_empty() {
+            return Err(RpcError::Malformed("truncated base64"));
+        }
+        match self.protocol {
+            RpcProtocol::ConnectUnary => {
+                out.extend(message_events(&self.buffer, "$")?);
+                self.buffer.clear();
+                self.messages += 1;
+                Ok(())
+            }
+            _ if self.buffer.is_empty() => Ok(()),
+            _ => Err(RpcError::Malformed("truncated message")),
+        }
+    }
+
+    /// Bytes received but not decoded into messages (to scan them raw after an error)
+    pub fn take_buffered(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.buffer)
+    }
+
+    /// Number of messages decoded so far
+    pub fn messages(&self) -> usize {
+        self.messages
+    }
+
+    /// Start over for a new body
+    pub fn reset(&mut self) {
+        self.pending_text.clear();
+        self.buffer.clear();
+        self.messages = 0;
+    }
+
+    /// Decode complete base64 quanta (padded segments may be concatenated)
+    fn decode_base64(&mut self, chunk: &[u8]) -> Result<Vec<u8>, RpcError> {
+        self.pending_text.extend(chunk.iter().filter(|b| !b.is_ascii_whitespace()));
+        let complete = self.pending_text.len() / 4 * 4;
+        let mut out = Vec::with_capacity(complete / 4 * 3);
+        for quantum in self.pending_text[..complete].chunks(4) {
+            let text = std::str::from_utf8(quantum).map_err(|_| RpcError::Malformed("base64"))?;
+            out.extend(base64url_decode(text).ok_or(RpcError::Malformed("base64"))?);
+        }
+        self.pending_text.drain(..complete);
+        Ok(out)
+    }
+}
+
+/// JSON tokenizer events for the string fields of one message
+fn message_events(message: &[u8], path: &str) -> Result<Vec<JsonEvent>, RpcError> {
+    let fields = string_fields(message, path).ok_or(RpcError::Malformed("protobuf"))?;
+    let mut events = Vec::with_capacity(fields.len() * 3);
+    for field in fields {
+        events.push(JsonEvent::StringStart(field.path));
+        events.push(JsonEvent::StringData(field.value.to_vec()));
+        events.push(JsonEvent::StringEnd);
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `{1: "tools/call", 2: {1: text}}`
+    fn message(text: &str) -> Vec<u8> {
+        let mut inner = vec![(1 << 3) | 2, text.len() as u8];
+        inner.extend_from_slice(text.as_bytes());
+        let mut out = vec![(1 << 3) | 2, 10];
+        out.extend_from_slice(b"tools/call");
+        out.push((2 << 3) | 2);
+        out.push(inner.len() as u8);
+        out.extend(inner);
+        out
+    }
+
+    fn envelope(flags: u8, payload: &[u8]) -> Vec<u8> {
+        let mut out = vec![flags];
+        out.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        out.extend_from_slice(payload);
+        out
+    }
+
+    fn base64(data: &[u8]) -> String {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut out = String::new();
+        for group in data.chunks(3) {
+            let n = group.iter().enumerate().fold(0, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+            for i in 0..4 {
+                let c = match i <= group.len() {
+                    true => ALPHABET[(n >> (18 - 6 * i) & 63) as usize],
+                    false => b'=',
+                };
+                out.push(c as char);
+            }
+        }
+        out
+    }
+
+    /// `path=value` of each string field
+    fn decode(
+        decoder: &mut RpcDecoder,
+        body: &[u8],
+        chunk: usize,
+    ) -> Result<Vec<String>, RpcError> {
+        let mut events = Vec::new();
+        for part in body.chunks(chunk) {
+            decoder.feed(part, &mut events)?;
+        }
+        decoder.finish(&mut events)?;
+        let mut out: Vec<String> = Vec::new();
+        for event in events {
+            match event {
+                JsonEvent::StringStart(path) => out.push(format!("{}=", path)),
+                JsonEvent::StringData(data) => {
+                    out.last_mut().unwrap().push_str(std::str::from_utf8(&data).unwrap())
+                }
+                _ => {}
+            }
+        }
+        Ok(out)
+    }
+
+    fn new_decoder(protocol: RpcProtocol) -> RpcDecoder {
+        RpcDecoder::new(protocol, ContentEncoding::Identity, 1024, 100)
+    }
+
+    #[test]
+    fn test_from_content_type() {
+        let protocol = RpcProtocol::from_content_type;
+        assert_eq!(protocol("application/grpc-web+proto"), Some(RpcProtocol::GrpcWeb));
+        assert_eq!(protocol("Application/gRPC-Web-Text"), Some(RpcProtocol::GrpcWebText));
+        assert_eq!(protocol("application/proto; charset=x"), Some(RpcProtocol::ConnectUnary));
+        assert_eq!(protocol("application/grpc-web+json"), None);
+        assert_eq!(RpcProtocol::ConnectStream.encoding_header(), Some("connect-content-encoding"));
+    }
+
+    #[test]
+    fn test_grpc_web_frames() {
+        let mut body = envelope(0, &message("first"));
+        body.extend(envelope(0, &message("second")));
+        // Trailer frame
+        body.extend(envelope(FLAG_TRAILERS, b"grpc-status: 0\r\n"));
+        let expected =
+            vec!["$[0].1=tools/call", "$[0].2.1=first", "$[1].1=tools/call", "$[1].2.1=second"];
+        for chunk in [1, 4, 9, body.len()] {
+            let mut decoder = new_decoder(RpcProtocol::GrpcWeb);
+            assert_eq!(decode(&mut decoder, &body, chunk).unwrap(), expected);
+            assert_eq!(decoder.messages(), 2);
+        }
+
+        let mut decoder = new_decoder(RpcProtocol::GrpcWeb);
+        let truncated = Err(RpcError::Malformed("truncated message"));
+        assert_eq!(decode(&mut decoder, &body[..12], 64), truncated);
+        let mut decoder = new_decoder(RpcProtocol::GrpcWeb);
+        let huge = [0, 0, 0, 0x10, 0];
+        assert_eq!(decoder.feed(&huge, &mut Vec::new()), Err(RpcError::TooLarge));
+        assert_eq!(decoder.take_buffered(), huge);
+    }
+
+    #[test]
+    fn test_grpc_web_text() {
+        // Each message base64-encoded on its own, padding and all
+        let mut text = base64(&envelope(0, &message("a")));
+        text.push_str("\r\n");
+        text.push_str(&base64(&envelope(0, &message("bc"))));
+        let expected = vec!["$[0].1=tools/call", "$[0].2.1=a", "$[1].1=tools/call", "$[1].2.1=bc"];
+        for chunk in [1, 5, text.len()] {
+            let mut decoder = new_decoder(RpcProtocol::GrpcWebText);
+            assert_eq!(decode(&mut decoder, text.as_bytes(), chunk).unwrap(), expected);
+        }
+        let mut decoder = new_decoder(RpcProtocol::GrpcWebText);
+        assert!(decoder.feed(b"AA*A", &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_connect_unary_and_compressed() {
+        let mut decoder = new_decoder(RpcProtocol::ConnectUnary);
+        let expected = vec!["$.1=tools/call", "$.2.1=hello"];
+        assert_eq!(decode(&mut decoder, &message("hello"), 3).unwrap(), expected);
+
+        // Connect streaming: end-of-stream frame skipped; compression needs an encoding
+        let mut body = envelope(FLAG_COMPRESSED, b"\x1f\x8b");
+        body.extend(envelope(FLAG_END_STREAM, b"{}"));
+        let mut decoder = new_decoder(RpcProtocol::ConnectStream);
+        assert_eq!(decode(&mut decoder, &body, 64), Err(RpcError::Compressed));
+        let mut decoder = new_decoder(RpcProtocol::ConnectStream);
+        let end_only = envelope(FLAG_END_STREAM, b"{}");
+        assert_eq!(decode(&mut decoder, &end_only, 64).unwrap(), Vec::<String>::new());
+
+        // gzip of message("hi")
+        let gzip = [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xe3, 0xe2, 0x2a, 0xc9,
+            0xcf, 0xcf, 0x29, 0xd6, 0x4f, 0x4e, 0xcc, 0xc9, 0x11, 0x62, 0xe1, 0x62, 0xca, 0xc8,
+            0x04, 0x00, 0xec, 0x6c, 0x94, 0xa6, 0x12, 0x00, 0x00, 0x00,
+        ];
+        let mut decoder = RpcDecoder::new(RpcProtocol::GrpcWeb, ContentEncoding::Gzip, 1024, 100);
+        let found = decode(&mut decoder, &envelope(FLAG_COMPRESSED, &gzip), 7).unwrap();
+        assert_eq!(found, vec!["$[0].1=tools/call", "$[0].2.1=hi"]);
+    }
+}
@@ -0,0 +1,487 @@
+//! Streaming JSON String Tokenizer
+//!
+//! A SAX-style tokenizer that emits the decoded contents of JSON string
+//! values (not keys) with the JSON path they appear at. Escapes are
+//! decoded, so `\u0069gnore` is seen as `ignore`. Memory is bounded by the
+//! nesting depth and key length; string values are streamed, not buffered.
+//! Numbers and `true`/`false`/`null` are reported whole, with their path.
+
+/// Deepest nesting accepted
+const MAX_DEPTH: usize = 64;
+
+/// Longest object key kept for paths (longer keys are truncated)
+const MAX_KEY_LEN: usize = 256;
+
+/// Longest number or keyword kept (longer literals are truncated)
+const MAX_LITERAL_LEN: usize = 32;
+
+/// Tokenizer output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JsonEvent {
+    /// A string value begins at this JSON path (e.g. `$.messages[0].content`)
+    StringStart(String),
+    /// Decoded string value bytes (UTF-8)
+    StringData(Vec<u8>),
+    /// The string value ended
+    StringEnd,
+    /// A number, `true`, `false` or `null` at a JSON path
+    Literal(String, String),
+}
+
+/// Tokenizer errors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JsonError {
+    /// Byte not valid at this position
+    Unexpected(u8),
+    /// Nesting deeper than the limit
+    TooDeep,
+    /// Invalid escape sequence
+    InvalidEscape,
+}
+
+impl std::fmt::Display for JsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonError::Unexpected(b) => write!(f, "Unexpected byte 0x{:02x}", b),
+            JsonError::TooDeep => write!(f, "JSON nested deeper than {}", MAX_DEPTH),
+            JsonError::InvalidEscape => write!(f, "Invalid string escape"),
+        }
+    }
+}
+
+/// What an open container expects next
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Expect {
+    /// Object: a key or `}` (right after `{`)
+    KeyOrEnd,
+    /// Object: a key (after `,`)
+    Key,
+    /// Object: `:`
+    Colon,
+    /// Array: a value or `]` (right after `[`)
+    ValueOrEnd,
+    /// A value (object after `:`, array after `,`)
+    Value,
+    /// `,` or the closing bracket
+    CommaOrEnd,
+}
+
+/// An open object or array
+#[derive(Debug, Clone)]
+enum Frame {
+    Object { key: Option<String>, expect: Expect },
+    Array { index: usize, expect: Expect },
+}
+
+impl Frame {
+    fn expect(&self) -> Expect {
+        match self {
+            Frame::Object { expect, .. } | Frame::Array { expect, .. } => *expect,
+        }
+    }
+
+    fn set_expect(&mut self, next: Expect) {
+        match self {
+            Frame::Object { expect, .. } | Frame::Array { expect, .. } => *expect = next,
+        }
+    }
+}
+
+/// Lexer state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Lex {
+    /// Between tokens
+    Between,
+    /// Inside a string
+    Str { is_key: bool },
+    /// After a backslash
+    Escape { is_key: bool },
+    /// Inside `\uXXXX`
+    Unicode { is_key: bool, digits: u8, value: u16 },
+    /// Inside a number or `true`/`false`/`null`
+    Literal,
+}
+
+/// Streaming JSON string-value tokenizer
+pub struct JsonTokenizer {
+    stack: Vec<Frame>,
+    lex: Lex,
+    root_done: bool,
+    key: Vec<u8>,
+    /// Decoded value bytes not yet emitted
+    data: Vec<u8>,
+    /// High surrogate awaiting its pair
+    high_surrogate: Option<u16>,
+    /// Current number or keyword, and its path
+    literal: Vec<u8>,
+    literal_path: String,
+}
+
+impl JsonTokenizer {
+    /// Create a new tokenizer
+    pub fn new() -> Self {
+        Self {
+            stack: Vec::new(),
+            lex: Lex::Between,
+            root_done: false,
+            key: Vec::new(),
+            data: Vec::new(),
+            high_surrogate: None,
+            literal: Vec::new(),
+            literal_path: String::new(),
+        }
+    }
+
+    /// Feed the next chunk
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<Vec<JsonEvent>, JsonError> {
+        let mut events = Vec::new();
+        for &b in chunk {
+            self.step(b, &mut events)?;
+        }
+        self.flush_data(&mut events);
+        Ok(events)
+    }
+
+    fn step(&mut self, b: u8, events: &mut Vec<JsonEvent>) -> Result<(), JsonError> {
+        match self.lex {
+            Lex::Str { is_key } => match b {
+                b'"' => {
+                    self.flush_surrogate(is_key);
+                    self.lex = Lex::Between;
+                    if is_key {
+                        let key = String::from_utf8_lossy(&self.key).into_owned();
+                        if let Some(Frame::Object { key: k, expect }) = self.stack.last_mut() {
+                            *k = Some(key);
+                            *expect = Expect::Colon;
+                        }
+                    } else {
+                        self.flush_data(events);
+                        events.push(JsonEvent::StringEnd);
+                        self.end_value();
+                    }
+                }
+                b'\\' => self.lex = Lex::Escape { is_key },
+                _ => {
+                    self.flush_surrogate(is_key);
+                    self.push(b, is_key);
+                }
+            },
+            Lex::Escape { is_key } => {
+                let decoded = match b {
+                    b'"' => b'"',
+                    b'\\' => b'\\',
+                    b'/' => b'/',
+                    b'b' => 0x08,
+                    b'f' => 0x0c,
+                    b'n' => b'\n',
+                    b'r' => b'\r',
+                    b't' => b'\t',
+                    b'u' => {
+                        self.lex = Lex::Unicode { is_key, digits: 0, value: 0 };
+                        return Ok(());
+                    }
+                    _ => return Err(JsonError::InvalidEscape),
+                };
+                self.flush_surrogate(is_key);
+                self.push(decoded, is_key);
+                self.lex = Lex::Str { is_key };
+            }
+            Lex::Unicode { is_key, digits, value } => {
+                let digit = (b as char).to_digit(16).ok_or(JsonError::InvalidEscape)? as u16;
+                let value = (value << 4) | digit;
+                if digits < 3 {
+                    self.lex = Lex::Unicode { is_key, digits: digits + 1, value };
+                } else {
+                    self.push_unit(value, is_key);
+                    self.lex = Lex::Str { is_key };
+                }
+            }
+            Lex::Literal => {
+                if matches!(b, b' ' | b'\t' | b'\r' | b'\n' | b',' | b']' | b'}') {
+                    self.lex = Lex::Between;
+                    events.push(JsonEvent::Literal(
+                        std::mem::take(&mut self.literal_path),
+                        String::from_utf8_lossy(&self.literal).into_owned(),
+                    ));
+                    self.end_value();
+                    return self.step(b, events);
+                }
+                if self.literal.len() < MAX_LITERAL_LEN {
+                    self.literal.push(b);
+                }
+            }
+            Lex::Between => self.structural(b, events)?,
+        }
+        Ok(())
+    }
+
+    fn structural(&mut self, b: u8, events: &mut Vec<JsonEvent>) -> Result<(), JsonError> {
+        match b {
+            b' ' | b'\t' | b'\r' | b'\n' => {}
+            b'{' | b'[' => {
+                self.begin_value(b)?;
+                if self.stack.len() >= MAX_DEPTH {
+                    return Err(JsonError::TooDeep);
+                }
+                self.stack.push(if b == b'{' {
+                    Frame::Object { key: None, expect: Expect::KeyOrEnd }
+                } else {
+                    Frame::Array { index: 0, expect: Expect::ValueOrEnd }
+                });
+            }
+            b'}' | b']' => {
+                let closes = match self.stack.last() {
+                    Some(Frame::Object { expect, .. }) => {
+                        b == b'}' && matches!(expect, Expect::KeyOrEnd | Expect::CommaOrEnd)
+                    }
+                    Some(Frame::Array { expect, .. }) => {
+                        b == b']' && matches!(expect, Expect::ValueOrEnd | Expect::CommaOrEnd)
+                    }
+                    None => false,
+                };
+                if !closes {
+                    return Err(JsonError::Unexpected(b));
+                }
+                self.stack.pop();
+                self.end_value();
+            }
+            b':' => match self.stack.last_mut() {
+                Some(frame @ Frame::Object { expect: Expect::Colon, .. }) => {
+                    frame.set_expect(Expect::Value)
+                }
+                _ => return Err(JsonError::Unexpected(b)),
+            },
+            b',' => match self.stack.last_mut() {
+                Some(Frame::Object { expect: expect @ Expect::CommaOrEnd, .. }) => {
+                    *expect = Expect::Key
+                }
+                Some(Frame::Array { index, expect: expect @ Expect::CommaOrEnd }) => {
+                    *index += 1;
+                    *expect = Expect::Value;
+                }
+                _ => return Err(JsonError::Unexpected(b)),
+            },
+            b'"' => {
+                let is_key = matches!(
+                    self.stack.last(),
+                    Some(Frame::Object { expect: Expect::KeyOrEnd | Expect::Key, .. })
+                );
+                if is_key {
+                    self.key.clear();
+                } else {
+                    self.begin_value(b)?;
+                    events.push(JsonEvent::StringStart(self.path()));
+                }
+                self.lex = Lex::Str { is_key };
+            }
+            b'-' | b'0'..=b'9' | b't' | b'f' | b'n' => {
+                self.begin_value(b)?;
+                self.literal.clear();
+                self.literal.push(b);
+                self.literal_path = self.path();
+                self.lex = Lex::Literal;
+            }
+            _ => return Err(JsonError::Unexpected(b)),
+        }
+        Ok(())
+    }
+
+    /// Check a value may start here
+    fn begin_value(&mut self, b: u8) -> Result<(), JsonError> {
+        let ok = match self.stack.last() {
+            None => !self.root_done,
+            Some(frame) => matches!(frame.expect(), Expect::Value | Expect::ValueOrEnd),
+        };
+        if ok {
+            Ok(())
+        } else {
+            Err(JsonError::Unexpected(b))
+        }
+    }
+
+    /// Whether one complete JSON value has been read
+    ///
+    /// A number at the top level only ends at the next delimiter.
+    pub fn is_complete(&self) -> bool {
+        self.root_done && self.lex == Lex::Between
+    }
+
+    /// A value finished in the current container
+    fn end_value(&mut self) {
+        match self.stack.last_mut() {
+            Some(frame) => frame.set_expect(Expect::CommaOrEnd),
+            None => self.root_done = true,
+        }
+    }
+
+    /// JSON path of the value about to start
+    fn path(&self) -> String {
+        let mut path = String::from("$");
+        for frame in &self.stack {
+            match frame {
+                Frame::Object { key: Some(key), .. } => {
+                    path.push('.');
+                    path.push_str(key);
+                }
+                Frame::Object { key: None, .. } => {}
+                Frame::Array { index, .. } => {
+                    path.push_str(&format!("[{}]", index));
+                }
+            }
+        }
+        path
+    }
+
+    fn push(&mut self, b: u8, is_key: bool) {
+        if is_key {
+            if self.key.len() < MAX_KEY_LEN {
+                self.key.push(b);
+            }
+        } else {
+            self.data.push(b);
+        }
+    }
+
+    fn push_char(&mut self, c: char, is_key: bool) {
+        let mut buf = [0u8; 4];
+        for &b in c.encode_utf8(&mut buf).as_bytes() {
+            self.push(b, is_key);
+        }
+    }
+
+    /// Push a UTF-16 code unit from a `\u` escape
+    fn push_unit(&mut self, unit: u16, is_key: bool) {
+        match unit {
+            0xd800..=0xdbff => {
+                self.flush_surrogate(is_key);
+                self.high_surrogate = Some(unit);
+            }
+            0xdc00..=0xdfff => match self.high_surrogate.take() {
+                Some(high) => {
+                    let c = 0x10000 + (((high as u32) - 0xd800) << 10) + (unit as u32 - 0xdc00);
+                    self.push_char(char::from_u32(c).unwrap_or('\u{fffd}'), is_key);
+                }
+                None => self.push_char('\u{fffd}', is_key),
+            },
+            _ => {
+                self.flush_surrogate(is_key);
+                self.push_char(char::from_u32(unit as u32).unwrap_or('\u{fffd}'), is_key);
+            }
+        }
+    }
+
+    /// Replace an unpaired high surrogate
+    fn flush_surrogate(&mut self, is_key: bool) {
+        if self.high_surrogate.take().is_some() {
+            self.push_char('\u{fffd}', is_key);
+        }
+    }
+
+    fn flush_data(&mut self, events: &mut Vec<JsonEvent>) {
+        if !self.data.is_empty() {
+            events.push(JsonEvent::StringData(std::mem::take(&mut self.data)));
+        }
+    }
+}
+
+impl Default for JsonTokenizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Collect (path, decoded value) pairs
+    fn strings(input: &[u8], chunk: usize) -> Result<Vec<(String, String)>, JsonError> {
+        let mut tokenizer = JsonTokenizer::new();
+        let mut out: Vec<(String, String)> = Vec::new();
+        for part in input.chunks(chunk) {
+            for event in tokenizer.feed(part)? {
+                match event {
+                    JsonEvent::StringStart(path) => out.push((path, String::new())),
+                    JsonEvent::StringData(data) => {
+                        out.last_mut().unwrap().1.push_str(std::str::from_utf8(&data).unwrap())
+                    }
+                    JsonEvent::StringEnd | JsonEvent::Literal(..) => {}
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    #[test]
+    fn test_values_with_paths() {
+        let input = br#"{"model":"gpt-4o","messages":[{"role":"user","content":"hi"}],"n":1,"stream":true}"#;
+        let found = strings(input, input.len()).unwrap();
+        assert_eq!(
+            found,
+            vec![
+                ("$.model".to_string(), "gpt-4o".to_string()),
+                ("$.messages[0].role".to_string(), "user".to_string()),
+                ("$.messages[0].content".to_string(), "hi".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_escapes_decoded() {
+        let input = br#"["\u0069gnore \"all\"\n", "caf\u00e9 \ud83d\ude00", "x\ud800y"]"#;
+        for chunk in 1..8 {
+            let found = strings(input, chunk).unwrap();
+            assert_eq!(found[0], ("$[0]".to_string(), "ignore \"all\"\n".to_string()));
+            assert_eq!(found[1].1, "café 😀");
+            assert_eq!(found[2].1, "x\u{fffd}y");
+        }
+    }
+
+    #[test]
+    fn test_keys_not_emitted() {
+        let found = strings(br#"{"ignore previous": 1, "a": {"b": ["c"]}}"#, 3).unwrap();
+        assert_eq!(found, vec![("$.a.b[0]".to_string(), "c".to_string())]);
+    }
+
+    #[test]
+    fn test_literals_reported() {
+        let input = br#"{"max_tokens": 1024, "stream":true,"stop":[null] }"#;
+        let mut tokenizer = JsonTokenizer::new();
+        let mut literals = Vec::new();
+        for part in input.chunks(5) {
+            for event in tokenizer.feed(part).unwrap() {
+                if let JsonEvent::Literal(path, text) = event {
+                    literals.push((path, text));
+                }
+            }
+        }
+        let expected = [("$.max_tokens", "1024"), ("$.stream", "true"), ("$.stop[0]", "null")];
+        let expected: Vec<(String, String)> =
+            expected.iter().map(|(p, t)| (p.to_string(), t.to_string())).collect();
+        assert_eq!(literals, expected);
+    }
+
+    #[test]
+    fn test_invalid_json() {
+        assert!(strings(b"{\"a\" 1}", 64).is_err());
+        assert!(strings(b"[1 2]", 64).is_err());
+        assert!(strings(b"{} {}", 64).is_err());
+        assert_eq!(strings(br#"["\q"]"#, 64), Err(JsonError::InvalidEscape));
+        assert_eq!(strings(&[b'['; MAX_DEPTH + 1], 64), Err(JsonError::TooDeep));
+    }
+
+    #[test]
+    fn test_is_complete() {
+        let mut tokenizer = JsonTokenizer::new();
+        tokenizer.feed(br#"{"a": [1"#).unwrap();
+        assert!(!tokenizer.is_complete());
+        tokenizer.feed(b"]}").unwrap();
+        assert!(tokenizer.is_complete());
+
+        let mut tokenizer = JsonTokenizer::new();
+        tokenizer.feed(b"42").unwrap();
+        assert!(!tokenizer.is_complete());
+        tokenizer.feed(b"\n").unwrap();
+        assert!(tokenizer.is_complete());
+    }
+}
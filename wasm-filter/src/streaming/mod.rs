@@ -4,11 +4,45 @@
 //! - Use fixed memory allocation (ring buffer)
 //! - Handle UTF-8 boundaries across chunks
 //! - Perform pattern matching with FSM (no regex)
+//! - Flatten configured pattern sets into a dense transition table
+//! - Decompress gzip/deflate bodies incrementally
+//! - Transcode UTF-16 and Latin-1 bodies to UTF-8
+//! - Split multipart/form-data bodies into parts
+//! - Extract decoded JSON string values
+//! - Split a body into the text values detectors judge
+//! - Split NDJSON bodies into records
+//! - Unwrap gRPC-Web / Connect messages and extract protobuf strings
+//! - Split Server-Sent Events streams into events
+//! - Bound the lifetime, event count and data rate of event streams
+//! - Flag long high-entropy runs (encoded or encrypted blobs)
 
 pub mod utf8_buffer;
 pub mod ring_buffer;
 pub mod pattern_fsm;
+pub mod pattern_table;
+pub mod inflate;
+pub mod decompress;
+pub mod charset;
+pub mod multipart;
+pub mod json_tokenizer;
+pub mod text_values;
+pub mod ndjson;
+pub mod protobuf;
+pub mod grpc_web;
+pub mod sse;
+pub mod watchdog;
+pub mod entropy;
 
 pub use utf8_buffer::Utf8Buffer;
 pub use ring_buffer::RingBuffer;
 pub use pattern_fsm::{Pattern, PatternMatch, PatternScanner, PatternState, ScanResult};
+pub use pattern_table::PatternTable;
+pub use decompress::{BodyDecoder, ContentEncoding, DecompressError};
+pub use charset::{Charset, CharsetDecoder};
+pub use json_tokenizer::{JsonEvent, JsonTokenizer};
+pub use text_values::TextValues;
+pub use ndjson::{NdjsonEvent, NdjsonSplitter};
+pub use grpc_web::{RpcDecoder, RpcError, RpcProtocol};
+pub use sse::{SseEvent, SseParser};
+pub use watchdog::{StreamWatchdog, StreamWatchdogConfig, WatchdogLimit, WatchdogTrip};
+pub use entropy::{EntropyAction, EntropyConfig, EntropyRun, EntropyScanner};
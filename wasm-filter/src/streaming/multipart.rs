@@ -0,0 +1,318 @@
+//! Streaming multipart/form-data Parser (RFC 7578)
+//!
+//! Splits a multipart body into parts as chunks arrive. Part data is
+//! emitted as soon as it cannot be the start of a delimiter, so memory is
+//! bounded by the delimiter length plus one part header block.
+
+/// Longest part header block buffered
+const MAX_PART_HEADERS: usize = 8 * 1024;
+
+/// Longest delimiter line tail (transport padding) accepted
+const MAX_DELIMITER_LINE: usize = 256;
+
+/// Longest boundary allowed by RFC 2046
+const MAX_BOUNDARY_LEN: usize = 70;
+
+/// Extract the boundary from a `multipart/*` content type
+pub fn multipart_boundary(content_type: &str) -> Option<String> {
+    let mut params = content_type.split(';');
+    let mime = params.next()?.trim().to_ascii_lowercase();
+    if !mime.starts_with("multipart/") {
+        return None;
+    }
+    params
+        .filter_map(|p| p.split_once('='))
+        .find(|(k, _)| k.trim().eq_ignore_ascii_case("boundary"))
+        .map(|(_, v)| v.trim().trim_matches('"').to_string())
+        .filter(|b| !b.is_empty() && b.len() <= MAX_BOUNDARY_LEN)
+}
+
+/// Headers of one part
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PartHeaders {
+    /// Form field name
+    pub name: Option<String>,
+    /// Uploaded file name
+    pub filename: Option<String>,
+    /// Media type, lowercase, without parameters
+    pub content_type: Option<String>,
+}
+
+impl PartHeaders {
+    fn parse(block: &[u8]) -> Self {
+        let mut headers = PartHeaders::default();
+        let text = String::from_utf8_lossy(block);
+        for line in text.split("\r\n") {
+            let (name, value) = match line.split_once(':') {
+                Some(pair) => pair,
+                None => continue,
+            };
+            let name = name.trim();
+            if name.eq_ignore_ascii_case("content-disposition") {
+                for param in value.split(';').skip(1) {
+                    if let Some((k, v)) = param.split_once('=') {
+                        let v = v.trim().trim_matches('"').to_string();
+                        match k.trim().to_ascii_lowercase().as_str() {
+                            "name" => headers.name = Some(v),
+                            "filename" => headers.filename = Some(v),
+                            _ => {}
+                        }
+                    }
+                }
+            } else if name.eq_ignore_ascii_case("content-type") {
+                let mime = value.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+                headers.content_type = Some(mime);
+            }
+        }
+        headers
+    }
+}
+
+/// Parser output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MultipartEvent {
+    /// A part begins
+    PartStart(PartHeaders),
+    /// Part body bytes
+    Data(Vec<u8>),
+    /// The current part ended
+    PartEnd,
+    /// Closing delimiter seen
+    End,
+}
+
+/// Multipart parse errors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MultipartError {
+    /// Part headers exceed the buffer limit
+    HeadersTooLarge,
+    /// Malformed body
+    Malformed(&'static str),
+}
+
+impl std::fmt::Display for MultipartError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MultipartError::HeadersTooLarge => write!(f, "Part headers too large"),
+            MultipartError::Malformed(msg) => write!(f, "Malformed multipart body: {}", msg),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Preamble,
+    AfterDelimiter,
+    Headers,
+    Body,
+    Epilogue,
+}
+
+/// Streaming multipart parser
+pub struct MultipartParser {
+    /// `\r\n--<boundary>`
+    delimiter: Vec<u8>,
+    buf: Vec<u8>,
+    state: State,
+}
+
+impl MultipartParser {
+    /// Create a parser for a boundary
+    pub fn new(boundary: &str) -> Self {
+        let mut delimiter = b"\r\n--".to_vec();
+        delimiter.extend_from_slice(boundary.as_bytes());
+        Self {
+            delimiter,
+            // The first delimiter may start the body without a preceding CRLF
+            buf: b"\r\n".to_vec(),
+            state: State::Preamble,
+        }
+    }
+
+    /// Whether the closing delimiter has been seen
+    pub fn is_complete(&self) -> bool {
+        self.state == State::Epilogue
+    }
+
+    /// Feed the next body chunk
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<Vec<MultipartEvent>, MultipartError> {
+        let mut events = Vec::new();
+        self.buf.extend_from_slice(chunk);
+        let dlen = self.delimiter.len();
+
+        loop {
+            match self.state {
+                State::Preamble => match find(&self.buf, &self.delimiter) {
+                    Some(i) => {
+                        self.buf.drain(..i + dlen);
+                        self.state = State::AfterDelimiter;
+                    }
+                    None => {
+                        let keep = self.buf.len().min(dlen - 1);
+                        self.buf.drain(..self.buf.len() - keep);
+                        break;
+                    }
+                },
+                State::AfterDelimiter => {
+                    if self.buf.len() < 2 {
+                        break;
+                    }
+                    if self.buf.starts_with(b"--") {
+                        events.push(MultipartEvent::End);
+                        self.state = State::Epilogue;
+                        continue;
+                    }
+                    match find(&self.buf, b"\r\n") {
+                        Some(i) => {
+                            if self.buf[..i].iter().any(|b| !matches!(b, b' ' | b'\t')) {
+                                return Err(MultipartError::Malformed("bad delimiter line"));
+                            }
+                            self.buf.drain(..i + 2);
+                            self.state = State::Headers;
+                        }
+                        None if self.buf.len() > MAX_DELIMITER_LINE => {
+                            return Err(MultipartError::Malformed("bad delimiter line"));
+                        }
+                        None => break,
+                    }
+                }
+                State::Headers => {
+                    let end = if self.buf.starts_with(b"\r\n") {
+                        Some((0, 2))
+                    } else {
+                        find(&self.buf, b"\r\n\r\n").map(|i| (i, 4))
+                    };
+                    match end {
+                        Some((i, sep)) => {
+                            let headers = PartHeaders::parse(&self.buf[..i]);
+                            self.buf.drain(..i + sep);
+                            events.push(MultipartEvent::PartStart(headers));
+                            self.state = State::Body;
+                        }
+                        None if self.buf.len() > MAX_PART_HEADERS => {
+                            return Err(MultipartError::HeadersTooLarge);
+                        }
+                        None => break,
+                    }
+                }
+                State::Body => match find(&self.buf, &self.delimiter) {
+                    Some(i) => {
+                        if i > 0 {
+                            events.push(MultipartEvent::Data(self.buf[..i].to_vec()));
+                        }
+                        events.push(MultipartEvent::PartEnd);
+                        self.buf.drain(..i + dlen);
+                        self.state = State::AfterDelimiter;
+                    }
+                    None => {
+                        // Hold back what could be the start of a delimiter
+                        let keep = self.buf.len().min(dlen - 1);
+                        let emit = self.buf.len() - keep;
+                        if emit > 0 {
+                            events.push(MultipartEvent::Data(self.buf.drain(..emit).collect()));
+                        }
+                        break;
+                    }
+                },
+                State::Epilogue => {
+                    self.buf.clear();
+                    break;
+                }
+            }
+        }
+
+        Ok(events)
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &[u8] = b"preamble\r\n--XyZ\r\n\
+        Content-Disposition: form-data; name=\"meta\"\r\n\
+        Content-Type: application/json; charset=utf-8\r\n\r\n\
+        {\"prompt\":\"hi\"}\r\n--XyZ\r\n\
+        Content-Disposition: form-data; name=\"file\"; filename=\"a.wav\"\r\n\
+        Content-Type: audio/wav\r\n\r\n\
+        RIFF....\r\n--XyZ--\r\nepilogue";
+
+    fn parse_all(chunk: usize) -> Vec<MultipartEvent> {
+        let mut parser = MultipartParser::new("XyZ");
+        let mut events = Vec::new();
+        for part in BODY.chunks(chunk) {
+            events.extend(parser.feed(part).unwrap());
+        }
+        assert!(parser.is_complete());
+        events
+    }
+
+    /// Merge adjacent Data events so chunkings compare equal
+    fn coalesce(events: Vec<MultipartEvent>) -> Vec<MultipartEvent> {
+        let mut out: Vec<MultipartEvent> = Vec::new();
+        for event in events {
+            match (out.last_mut(), event) {
+                (Some(MultipartEvent::Data(prev)), MultipartEvent::Data(next)) => {
+                    prev.extend(next)
+                }
+                (_, event) => out.push(event),
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_boundary_extraction() {
+        assert_eq!(
+            multipart_boundary("multipart/form-data; boundary=\"abc def\""),
+            Some("abc def".to_string())
+        );
+        assert_eq!(
+            multipart_boundary("Multipart/Form-Data;charset=utf-8;BOUNDARY=x1"),
+            Some("x1".to_string())
+        );
+        assert_eq!(multipart_boundary("application/json; boundary=x"), None);
+        assert_eq!(multipart_boundary("multipart/form-data"), None);
+    }
+
+    #[test]
+    fn test_parse_parts() {
+        let events = coalesce(parse_all(BODY.len()));
+        assert_eq!(events.len(), 7);
+        assert_eq!(
+            events[0],
+            MultipartEvent::PartStart(PartHeaders {
+                name: Some("meta".to_string()),
+                filename: None,
+                content_type: Some("application/json".to_string()),
+            })
+        );
+        assert_eq!(events[1], MultipartEvent::Data(b"{\"prompt\":\"hi\"}".to_vec()));
+        assert_eq!(events[2], MultipartEvent::PartEnd);
+        assert!(
+            matches!(&events[3], MultipartEvent::PartStart(h) if h.filename.as_deref() == Some("a.wav"))
+        );
+        assert_eq!(events[4], MultipartEvent::Data(b"RIFF....".to_vec()));
+        assert_eq!(events[6], MultipartEvent::End);
+    }
+
+    #[test]
+    fn test_any_chunking() {
+        let expected = coalesce(parse_all(BODY.len()));
+        for chunk in 1..16 {
+            assert_eq!(coalesce(parse_all(chunk)), expected, "chunk size {}", chunk);
+        }
+    }
+
+    #[test]
+    fn test_header_limit() {
+        let mut parser = MultipartParser::new("b");
+        parser.feed(b"--b\r\n").unwrap();
+        let result = parser.feed(&vec![b'x'; MAX_PART_HEADERS + 1]);
+        assert_eq!(result, Err(MultipartError::HeadersTooLarge));
+    }
+}
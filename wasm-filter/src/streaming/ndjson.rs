@@ -0,0 +1,211 @@
+//! NDJSON / JSON Lines Splitting
+//!
+//! Agent frameworks stream newline-delimited JSON: one value per line. A
+//! plain JSON tokenizer rejects the second line, so each record gets a
+//! fresh tokenizer instead. String values are reported with the record's
+//! index in their path, as if the body were an array (`$[3].prompt`).
+//!
+//! A record that is not valid JSON is passed on as raw bytes (so it is
+//! still scanned) and reported invalid when it ends. Blank lines are not
+//! records. Records are streamed, never buffered.
+
+use super::json_tokenizer::{JsonEvent, JsonTokenizer};
+
+/// `Content-Type` media types of newline-delimited JSON
+const NDJSON_TYPES: &[&str] = &[
+    "application/x-ndjson",
+    "application/ndjson",
+    "application/jsonl",
+    "application/x-jsonlines",
+    "application/jsonlines",
+];
+
+/// Whether a `Content-Type` header value is newline-delimited JSON
+pub fn is_ndjson(content_type: &str) -> bool {
+    let media = content_type.split(';').next().unwrap_or("").trim();
+    NDJSON_TYPES.iter().any(|t| media.eq_ignore_ascii_case(t))
+}
+
+/// Splitter output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NdjsonEvent {
+    /// Tokenizer event of a valid record, with the record index in its path
+    Json(JsonEvent),
+    /// Bytes of record `index`, which is not valid JSON
+    Raw(usize, Vec<u8>),
+    /// A record ended
+    RecordEnd { index: usize, valid: bool },
+}
+
+/// Splits an NDJSON body into records and tokenizes each
+pub struct NdjsonSplitter {
+    tokenizer: JsonTokenizer,
+    /// Index of the current record
+    index: usize,
+    /// Whether the current record has non-whitespace bytes
+    started: bool,
+    /// Whether the current record failed to tokenize
+    invalid: bool,
+}
+
+impl NdjsonSplitter {
+    /// Create a splitter at the start of a body
+    pub fn new() -> Self {
+        Self { tokenizer: JsonTokenizer::new(), index: 0, started: false, invalid: false }
+    }
+
+    /// Feed the next chunk
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<NdjsonEvent> {
+        let mut events = Vec::new();
+        for line in chunk.split_inclusive(|&b| b == b'\n') {
+            let (data, ends) = match line.split_last() {
+                Some((b'\n', data)) => (data, true),
+                _ => (line, false),
+            };
+            self.feed_record(data, &mut events);
+            if ends {
+                self.end_record(&mut events);
+            }
+        }
+        events
+    }
+
+    /// End of body: the last record needs no trailing newline
+    pub fn finish(&mut self) -> Vec<NdjsonEvent> {
+        let mut events = Vec::new();
+        self.end_record(&mut events);
+        events
+    }
+
+    /// Number of records ended so far
+    pub fn records(&self) -> usize {
+        self.index
+    }
+
+    fn feed_record(&mut self, data: &[u8], events: &mut Vec<NdjsonEvent>) {
+        if data.is_empty() {
+            return;
+        }
+        self.started |= data.iter().any(|b| !b.is_ascii_whitespace());
+        if !self.invalid {
+            match self.tokenizer.feed(data) {
+                Ok(json) => {
+                    let index = self.index;
+                    let json = json.into_iter().map(|e| NdjsonEvent::Json(in_record(e, index)));
+                    events.extend(json);
+                    return;
+                }
+                Err(_) => self.invalid = true,
+            }
+        }
+        events.push(NdjsonEvent::Raw(self.index, data.to_vec()));
+    }
+
+    fn end_record(&mut self, events: &mut Vec<NdjsonEvent>) {
+        if self.started {
+            // The newline ends a number at the top level
+            let valid = !self.invalid && self.tokenizer.feed(b"\n").is_ok();
+            let valid = valid && self.tokenizer.is_complete();
+            events.push(NdjsonEvent::RecordEnd { index: self.index, valid });
+            self.index += 1;
+        }
+        self.tokenizer = JsonTokenizer::new();
+        self.started = false;
+        self.invalid = false;
+    }
+}
+
+impl Default for NdjsonSplitter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Prefix a record-relative path with the record index
+fn in_record(event: JsonEvent, index: usize) -> JsonEvent {
+    let path = |path: String| format!("$[{}]{}", index, &path[1..]);
+    match event {
+        JsonEvent::StringStart(p) => JsonEvent::StringStart(path(p)),
+        JsonEvent::Literal(p, value) => JsonEvent::Literal(path(p), value),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(body: &[u8], chunk: usize) -> Vec<NdjsonEvent> {
+        let mut splitter = NdjsonSplitter::new();
+        let mut events: Vec<NdjsonEvent> =
+            body.chunks(chunk).flat_map(|part| splitter.feed(part)).collect();
+        events.extend(splitter.finish());
+        events
+    }
+
+    /// (path, value) of each string, and record ends
+    fn summary(events: &[NdjsonEvent]) -> Vec<String> {
+        let mut out = Vec::new();
+        for event in events {
+            match event {
+                NdjsonEvent::Json(JsonEvent::StringStart(path)) => out.push(format!("{}=", path)),
+                NdjsonEvent::Json(JsonEvent::StringData(data)) => {
+                    out.last_mut().unwrap().push_str(std::str::from_utf8(data).unwrap());
+                }
+                NdjsonEvent::RecordEnd { index, valid } => {
+                    out.push(format!("end {} {}", index, valid))
+                }
+                _ => {}
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_content_types() {
+        assert!(is_ndjson("application/x-ndjson"));
+        assert!(is_ndjson("Application/JSONL; charset=utf-8"));
+        assert!(!is_ndjson("application/json"));
+    }
+
+    #[test]
+    fn test_records_split_across_chunks() {
+        let body = b"{\"event\": \"start\"}\n\n{\"text\": \"hi\"}\r\n[\"x\"]";
+        let expected = vec![
+            "$[0].event=start",
+            "end 0 true",
+            "$[1].text=hi",
+            "end 1 true",
+            "$[2][0]=x",
+            "end 2 true",
+        ];
+        for chunk in [1, 3, 7, body.len()] {
+            assert_eq!(summary(&split(body, chunk)), expected);
+        }
+    }
+
+    #[test]
+    fn test_invalid_records() {
+        let body = b"{\"a\": \"ok\"}\nnot json at all\n{\"b\": \n42\n";
+        let events = split(body, 5);
+        let ends: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                NdjsonEvent::RecordEnd { index, valid } => Some((*index, *valid)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ends, vec![(0, true), (1, false), (2, false), (3, true)]);
+
+        let raw: Vec<u8> = events
+            .iter()
+            .filter_map(|e| match e {
+                NdjsonEvent::Raw(3, _) => panic!("valid record reported raw"),
+                NdjsonEvent::Raw(_, data) => Some(data.clone()),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        assert!(String::from_utf8(raw).unwrap().contains("json at all"));
+    }
+}
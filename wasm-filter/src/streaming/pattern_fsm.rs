@@ -5,6 +5,12 @@
 //! - O(1) per byte
 //! - Constant memory usage
 //! - Case-insensitive
+//!
+//! Pattern sets compiled at configure time are scanned through a flattened
+//! `PatternTable` instead of one state per pattern.
+
+use super::pattern_table::{PatternTable, START};
+use std::rc::Rc;
 
 /// A pattern to match against
 #[derive(Clone, Debug)]
@@ -107,23 +113,41 @@ pub struct PatternMatch {
     pub pattern_name: String,
 }
 
+/// How a scanner tracks match progress
+enum Engine {
+    /// One state per pattern
+    Fsm(Vec<PatternState>),
+    /// Current state of a shared compiled table
+    Table(Rc<PatternTable>, u16),
+}
+
 /// Multi-pattern scanner using FSM
 pub struct PatternScanner {
-    /// Patterns to scan for
-    patterns: Vec<Pattern>,
-    /// State for each pattern
-    states: Vec<PatternState>,
+    /// Patterns to scan for (shared between scanners of one configuration)
+    patterns: Rc<[Pattern]>,
+    /// Match progress
+    engine: Engine,
     /// Total bytes scanned
     bytes_scanned: usize,
 }
 
 impl PatternScanner {
     /// Create a new scanner with the given patterns
-    pub fn new(patterns: Vec<Pattern>) -> Self {
+    pub fn new(patterns: impl Into<Rc<[Pattern]>>) -> Self {
+        let patterns = patterns.into();
         let num_patterns = patterns.len();
         Self {
             patterns,
-            states: vec![PatternState::new(); num_patterns],
+            engine: Engine::Fsm(vec![PatternState::new(); num_patterns]),
+            bytes_scanned: 0,
+        }
+    }
+
+    /// Create a scanner walking a compiled table
+    pub fn with_table(table: Rc<PatternTable>) -> Self {
+        Self {
+            patterns: table.patterns().clone(),
+            engine: Engine::Table(table, START),
             bytes_scanned: 0,
         }
     }
@@ -141,22 +165,31 @@ impl PatternScanner {
     pub fn scan_byte(&mut self, byte: u8) -> ScanResult {
         self.bytes_scanned += 1;
 
-        for (i, (state, pattern)) in self.states.iter_mut().zip(&self.patterns).enumerate() {
-            state.advance(byte, pattern);
-
-            if state.is_match(pattern) {
-                // Reset state for potential overlapping matches
-                state.reset();
-                
-                return ScanResult::Match(PatternMatch {
-                    pattern_index: i,
-                    position: self.bytes_scanned,
-                    pattern_name: pattern.name.clone(),
-                });
+        let matched = match &mut self.engine {
+            Engine::Table(table, state) => {
+                *state = table.step(*state, byte);
+                table.matched(*state)
             }
+            Engine::Fsm(states) => states.iter_mut().zip(self.patterns.iter()).position(
+                |(state, pattern)| {
+                    state.advance(byte, pattern);
+                    // Reset state for potential overlapping matches
+                    let matched = state.is_match(pattern);
+                    if matched {
+                        state.reset();
+                    }
+                    matched
+                },
+            ),
+        };
+        match matched {
+            Some(i) => ScanResult::Match(PatternMatch {
+                pattern_index: i,
+                position: self.bytes_scanned,
+                pattern_name: self.patterns[i].name.clone(),
+            }),
+            None => ScanResult::Continue,
         }
-
-        ScanResult::Continue
     }
 
     /// Scan a slice of bytes, returns first match if found
@@ -171,12 +204,18 @@ impl PatternScanner {
 
     /// Reset all pattern states
     pub fn reset(&mut self) {
-        for state in &mut self.states {
-            state.reset();
-        }
+        self.reset_states();
         self.bytes_scanned = 0;
     }
 
+    /// Reset partial matches only, so patterns cannot span a boundary
+    pub fn reset_states(&mut self) {
+        match &mut self.engine {
+            Engine::Fsm(states) => states.iter_mut().for_each(PatternState::reset),
+            Engine::Table(_, state) => *state = START,
+        }
+    }
+
     /// Get total bytes scanned
     pub fn bytes_scanned(&self) -> usize {
         self.bytes_scanned
@@ -285,4 +324,40 @@ mod tests {
             panic!("Expected match");
         }
     }
+
+    #[test]
+    fn test_table_engine_agrees() {
+        let patterns: Rc<[Pattern]> = vec![
+            Pattern::from_string("ignore previous instructions"),
+            Pattern::from_string("jailbreak"),
+            Pattern::from_string("rm -rf"),
+        ]
+        .into();
+        let table = Rc::new(PatternTable::build(patterns.clone()).unwrap());
+        let texts: [&[u8]; 4] = [
+            b"Please IGNORE previous instructions",
+            b"no jail-break here",
+            b"run `rm -rf /` now",
+            b"nothing to see",
+        ];
+        for text in texts {
+            let mut fsm = PatternScanner::new(patterns.clone());
+            let mut flat = PatternScanner::with_table(table.clone());
+            match (fsm.scan_bytes(text), flat.scan_bytes(text)) {
+                (ScanResult::Match(a), ScanResult::Match(b)) => {
+                    assert_eq!((a.pattern_index, a.position), (b.pattern_index, b.position));
+                    assert_eq!(a.pattern_name, b.pattern_name);
+                }
+                (ScanResult::Continue, ScanResult::Continue) => {}
+                (a, b) => panic!("engines disagree: {:?} vs {:?}", a, b),
+            }
+        }
+
+        let mut flat = PatternScanner::with_table(table);
+        assert!(matches!(flat.scan_bytes(b"jail"), ScanResult::Continue));
+        flat.reset_states();
+        assert!(matches!(flat.scan_bytes(b"break"), ScanResult::Continue));
+        assert_eq!(flat.bytes_scanned(), 9);
+        assert_eq!(flat.pattern_count(), 3);
+    }
 }
@@ -0,0 +1,202 @@
+//! Flattened Pattern Automaton
+//!
+//! `PatternState` walks every pattern separately: one branchy step per
+//! pattern per byte. For the configured blocked patterns, which are scanned
+//! over every request body, the patterns are instead compiled once (at
+//! configure time) into a single Aho-Corasick automaton flattened into a
+//! dense transition table:
+//!
+//! - Bytes are mapped to classes (every byte not in a pattern shares class
+//!   0, ASCII letters are folded), so a row is a few dozen `u16` entries
+//! - Failure links are resolved into the table: one lookup per byte, no
+//!   backtracking, no per-pattern loop
+//! - Each state records the lowest-index pattern ending there
+//!
+//! The table is bounded by `MAX_STATES`; larger pattern sets fall back to
+//! the per-pattern FSM.
+
+use super::pattern_fsm::Pattern;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+/// Largest number of states a table may have (state IDs are `u16`)
+pub const MAX_STATES: usize = u16::MAX as usize;
+
+/// Start state of every table
+pub const START: u16 = 0;
+
+/// Patterns compiled into a dense transition table
+#[derive(Debug)]
+pub struct PatternTable {
+    /// Compiled patterns, in configuration order
+    patterns: Rc<[Pattern]>,
+    /// Byte class of each input byte
+    classes: [u8; 256],
+    /// Number of byte classes (row width)
+    stride: usize,
+    /// Next state by `state * stride + class`
+    next: Vec<u16>,
+    /// 1 + index of the lowest-index pattern ending in each state (0 = none)
+    matches: Vec<u16>,
+}
+
+impl PatternTable {
+    /// Compile patterns (None when the table would exceed `MAX_STATES`)
+    pub fn build(patterns: Rc<[Pattern]>) -> Option<Self> {
+        let states = 1 + patterns.iter().map(|p| p.bytes.len()).sum::<usize>();
+        if states > MAX_STATES || patterns.len() >= u16::MAX as usize {
+            return None;
+        }
+
+        let mut classes = [0u8; 256];
+        let mut stride = 1;
+        for &byte in patterns.iter().flat_map(|p| p.bytes.iter()) {
+            if classes[byte as usize] == 0 {
+                // Lowercased patterns have at most 230 distinct bytes
+                if stride > u8::MAX as usize {
+                    return None;
+                }
+                classes[byte as usize] = stride as u8;
+                stride += 1;
+            }
+        }
+        for upper in b'A'..=b'Z' {
+            classes[upper as usize] = classes[upper.to_ascii_lowercase() as usize];
+        }
+
+        // Trie
+        const NONE: u16 = u16::MAX;
+        let mut next = vec![NONE; stride];
+        let mut matches = vec![0u16];
+        for (index, pattern) in patterns.iter().enumerate() {
+            let mut state = START as usize;
+            for &byte in &pattern.bytes {
+                let slot = state * stride + classes[byte as usize] as usize;
+                if next[slot] == NONE {
+                    next[slot] = matches.len() as u16;
+                    next.resize(next.len() + stride, NONE);
+                    matches.push(0);
+                }
+                state = next[slot] as usize;
+            }
+            if matches[state] == 0 {
+                matches[state] = index as u16 + 1;
+            }
+        }
+
+        // Failure links, breadth first so a state's fallback is complete
+        let mut fail = vec![START; matches.len()];
+        let mut queue = VecDeque::new();
+        for slot in next.iter_mut().take(stride) {
+            if *slot == NONE {
+                *slot = START;
+            } else {
+                queue.push_back(*slot);
+            }
+        }
+        while let Some(state) = queue.pop_front() {
+            let state = state as usize;
+            let fallback = fail[state] as usize;
+            matches[state] = match (matches[state], matches[fallback]) {
+                (0, inherited) => inherited,
+                (own, 0) => own,
+                (own, inherited) => own.min(inherited),
+            };
+            for class in 0..stride {
+                let slot = state * stride + class;
+                let fallback_next = next[fallback * stride + class];
+                if next[slot] == NONE {
+                    next[slot] = fallback_next;
+                } else {
+                    fail[next[slot] as usize] = fallback_next;
+                    queue.push_back(next[slot]);
+                }
+            }
+        }
+
+        Some(Self { patterns, classes, stride, next, matches })
+    }
+
+    /// State after reading a byte (case-insensitive)
+    #[inline]
+    pub fn step(&self, state: u16, byte: u8) -> u16 {
+        self.next[state as usize * self.stride + self.classes[byte as usize] as usize]
+    }
+
+    /// Index of the pattern matched on entering a state
+    #[inline]
+    pub fn matched(&self, state: u16) -> Option<usize> {
+        match self.matches[state as usize] {
+            0 => None,
+            index => Some(index as usize - 1),
+        }
+    }
+
+    /// Compiled patterns
+    pub fn patterns(&self) -> &Rc<[Pattern]> {
+        &self.patterns
+    }
+
+    /// Number of states
+    pub fn state_count(&self) -> usize {
+        self.matches.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(patterns: &[&str]) -> PatternTable {
+        let patterns: Vec<Pattern> = patterns.iter().map(|p| Pattern::from_string(p)).collect();
+        PatternTable::build(patterns.into()).unwrap()
+    }
+
+    /// Index of each match, scanning without resets
+    fn matches(table: &PatternTable, text: &[u8]) -> Vec<(usize, usize)> {
+        let mut state = START;
+        let mut found = Vec::new();
+        for (i, &byte) in text.iter().enumerate() {
+            state = table.step(state, byte);
+            if let Some(index) = table.matched(state) {
+                found.push((i + 1, index));
+            }
+        }
+        found
+    }
+
+    #[test]
+    fn test_matches_case_insensitively() {
+        let table = table(&["jailbreak", "DAN mode"]);
+        assert_eq!(matches(&table, b"please JailBreak"), vec![(16, 0)]);
+        assert_eq!(matches(&table, b"enable dan MODE now"), vec![(15, 1)]);
+        assert!(matches(&table, b"jail break").is_empty());
+    }
+
+    #[test]
+    fn test_overlapping_prefixes() {
+        // A naive restart after "aa" mismatches would miss "aab" in "aaab"
+        let table = table(&["aab", "tes", "test"]);
+        assert_eq!(matches(&table, b"aaab"), vec![(4, 0)]);
+        assert_eq!(matches(&table, b"tetest"), vec![(5, 1), (6, 2)]);
+    }
+
+    #[test]
+    fn test_lowest_index_wins() {
+        // "is a test" and "test" end on the same byte
+        let short_first = table(&["test", "is a test"]);
+        assert_eq!(matches(&short_first, b"this is a test"), vec![(14, 0)]);
+        let long_first = table(&["is a test", "test"]);
+        assert_eq!(matches(&long_first, b"this is a test"), vec![(14, 0)]);
+    }
+
+    #[test]
+    fn test_table_is_compact() {
+        let table = table(&["ignore previous instructions", "rm -rf"]);
+        assert_eq!(table.state_count(), 1 + 28 + 6);
+        // 16 distinct bytes in the patterns plus the "other" class
+        assert_eq!(table.stride, 17);
+        let huge = vec![Pattern::from_string(&"x".repeat(MAX_STATES))];
+        assert!(PatternTable::build(huge.into()).is_none());
+    }
+}
@@ -0,0 +1,154 @@
+//! Protobuf String Fields
+//!
+//! gRPC bodies are protobuf messages, and the filter has no schemas. The
+//! wire format still says which fields are length-delimited: those hold
+//! strings, bytes or nested messages. A value that is printable UTF-8 is
+//! taken as a string, unless it starts with a control character and
+//! parses as a message. Anything else is parsed as a nested message, and
+//! if that fails it is skipped as bytes.
+//!
+//! Strings are reported with a path of field numbers (`$.2.1` is field 1
+//! of the message in field 2), so block reasons point at the field.
+
+/// Deepest nested message that is descended into
+const MAX_DEPTH: usize = 16;
+
+/// Wire types
+const VARINT: u64 = 0;
+const FIXED64: u64 = 1;
+const LEN: u64 = 2;
+const FIXED32: u64 = 5;
+
+/// A string field of a message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StringField<'a> {
+    /// Field numbers from the outer message, as `$.2.1`
+    pub path: String,
+    /// The UTF-8 value
+    pub value: &'a [u8],
+}
+
+/// String fields of a message, in wire order (None if it is not valid protobuf)
+pub fn string_fields<'a>(message: &'a [u8], path: &str) -> Option<Vec<StringField<'a>>> {
+    let mut fields = Vec::new();
+    walk(message, path, 0, &mut fields)?;
+    Some(fields)
+}
+
+fn walk<'a>(
+    mut data: &'a [u8],
+    path: &str,
+    depth: usize,
+    fields: &mut Vec<StringField<'a>>,
+) -> Option<()> {
+    while !data.is_empty() {
+        let key = read_varint(&mut data)?;
+        let number = key >> 3;
+        if number == 0 {
+            return None;
+        }
+        match key & 0x7 {
+            VARINT => {
+                read_varint(&mut data)?;
+            }
+            FIXED64 => data = data.get(8..)?,
+            FIXED32 => data = data.get(4..)?,
+            LEN => {
+                let len = usize::try_from(read_varint(&mut data)?).ok()?;
+                let value = data.get(..len)?;
+                data = &data[len..];
+                let field_path = format!("{}.{}", path, number);
+                let text = is_text(value);
+                // Messages usually start with a key byte like `\n` (field 1,
+                // length-delimited); strings practically never do
+                let maybe_message = !text || value.first().is_some_and(u8::is_ascii_control);
+                let mut nested = Vec::new();
+                if maybe_message
+                    && depth < MAX_DEPTH
+                    && walk(value, &field_path, depth + 1, &mut nested).is_some()
+                {
+                    fields.append(&mut nested);
+                } else if text {
+                    fields.push(StringField { path: field_path, value });
+                }
+            }
+            // Groups (3, 4) are deprecated and never sent by gRPC clients
+            _ => return None,
+        }
+    }
+    Some(())
+}
+
+/// Read a base-128 varint
+fn read_varint(data: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    for (i, &byte) in data.iter().enumerate().take(10) {
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            *data = &data[i + 1..];
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Printable UTF-8: no control characters except tab and newlines
+fn is_text(value: &[u8]) -> bool {
+    match std::str::from_utf8(value) {
+        Ok(text) => text.chars().all(|c| !c.is_control() || matches!(c, '\t' | '\n' | '\r')),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encode a length-delimited field
+    fn len_field(number: u8, value: &[u8]) -> Vec<u8> {
+        let mut out = vec![(number << 3) | 2, value.len() as u8];
+        out.extend_from_slice(value);
+        out
+    }
+
+    fn paths(message: &[u8]) -> Option<Vec<(String, String)>> {
+        let fields = string_fields(message, "$")?;
+        let text = |f: &StringField| String::from_utf8(f.value.to_vec()).unwrap();
+        Some(fields.iter().map(|f| (f.path.clone(), text(f))).collect())
+    }
+
+    #[test]
+    fn test_nested_string_fields() {
+        // { 1: 150, 2: { 1: "tools/call", 3: 0xdeadbeef (fixed32) }, 4: "hi", 5: [0xff, 0x00] }
+        let mut inner = len_field(1, b"tools/call");
+        inner.extend([(3 << 3) | 5, 0xef, 0xbe, 0xad, 0xde]);
+        let mut message = vec![(1 << 3), 0x96, 0x01];
+        message.extend(len_field(2, &inner));
+        message.extend(len_field(4, b"hi"));
+        message.extend(len_field(5, &[0xff, 0x00]));
+        message.extend([(6 << 3) | 1, 1, 2, 3, 4, 5, 6, 7, 8]);
+
+        let expected = vec![
+            ("$.2.1".to_string(), "tools/call".to_string()),
+            ("$.4".to_string(), "hi".to_string()),
+        ];
+        assert_eq!(paths(&message), Some(expected));
+        assert_eq!(paths(b""), Some(vec![]));
+
+        // `{1: "jailbreak"}` is also printable UTF-8 ("\n\tjailbreak")
+        let nested = len_field(3, &len_field(1, b"jailbreak"));
+        assert_eq!(paths(&nested), Some(vec![("$.3.1".to_string(), "jailbreak".to_string())]));
+        // A string starting with a newline is still a string
+        let text = len_field(1, b"\nnot a message");
+        assert_eq!(paths(&text), Some(vec![("$.1".to_string(), "\nnot a message".to_string())]));
+    }
+
+    #[test]
+    fn test_malformed_messages() {
+        // Truncated length, field number 0, group wire type, unterminated varint
+        assert_eq!(paths(&[(1 << 3) | 2, 5, b'a']), None);
+        assert_eq!(paths(&[0x02, 0x00]), None);
+        assert_eq!(paths(&[(1 << 3) | 3]), None);
+        assert_eq!(paths(&[(1 << 3), 0x80]), None);
+    }
+}
@@ -9,6 +9,8 @@
 
 use super::utf8_buffer::Utf8Buffer;
 use super::pattern_fsm::{Pattern, PatternScanner, ScanResult};
+use super::pattern_table::PatternTable;
+use std::rc::Rc;
 
 /// Memory-efficient ring buffer for streaming pattern detection
 pub struct RingBuffer {
@@ -28,7 +30,7 @@ pub struct RingBuffer {
 
 impl RingBuffer {
     /// Create with fixed capacity - NO dynamic growth
-    pub fn new(capacity: usize, patterns: Vec<Pattern>) -> Self {
+    pub fn new(capacity: usize, patterns: impl Into<Rc<[Pattern]>>) -> Self {
         Self {
             buffer: vec![0u8; capacity], // Pre-allocate once
             capacity,
@@ -39,6 +41,18 @@ impl RingBuffer {
         }
     }
 
+    /// Create with fixed capacity, scanning through a compiled table
+    pub fn with_table(capacity: usize, table: Rc<PatternTable>) -> Self {
+        Self {
+            buffer: vec![0u8; capacity],
+            capacity,
+            write_pos: 0,
+            total_written: 0,
+            scanner: PatternScanner::with_table(table),
+            utf8_handler: Utf8Buffer::new(),
+        }
+    }
+
     /// Create from string patterns
     pub fn from_strings(capacity: usize, patterns: &[String]) -> Self {
         let patterns: Vec<Pattern> = patterns
@@ -82,6 +96,11 @@ impl RingBuffer {
         ScanResult::Continue
     }
 
+    /// Mark a value boundary: partial matches do not carry across it
+    pub fn break_match(&mut self) {
+        self.scanner.reset_states();
+    }
+
     /// Get total bytes processed
     pub fn total_written(&self) -> usize {
         self.total_written
@@ -118,7 +137,7 @@ impl RingBuffer {
         let mut result = Vec::with_capacity(count);
 
         for i in 0..count {
-            let pos = if self.write_pos >= i + 1 {
+            let pos = if self.write_pos > i {
                 self.write_pos - i - 1
             } else {
                 self.capacity - (i + 1 - self.write_pos)
@@ -0,0 +1,176 @@
+//! Streaming Server-Sent Events Parser
+//!
+//! Splits a `text/event-stream` body into dispatched events as chunks
+//! arrive. Lines may end in CRLF, LF or CR, and may be split anywhere.
+//! Only the current line and event are buffered, each up to a size limit.
+
+/// Room for the field name on top of the event size limit
+const FIELD_OVERHEAD: usize = 16;
+
+/// A dispatched event
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SseEvent {
+    /// `event:` field, if present
+    pub event: Option<String>,
+    /// `id:` field, if present
+    pub id: Option<String>,
+    /// `data:` lines joined with `\n`
+    pub data: Vec<u8>,
+    /// Data was cut at the size limit
+    pub truncated: bool,
+}
+
+impl SseEvent {
+    /// Serialize the event back to the wire format
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.data.len() + 32);
+        if let Some(event) = &self.event {
+            out.extend(format!("event: {}\n", event).into_bytes());
+        }
+        if let Some(id) = &self.id {
+            out.extend(format!("id: {}\n", id).into_bytes());
+        }
+        for line in self.data.split(|&b| b == b'\n') {
+            out.extend_from_slice(b"data: ");
+            out.extend_from_slice(line);
+            out.push(b'\n');
+        }
+        out.push(b'\n');
+        out
+    }
+}
+
+/// Streaming SSE parser
+pub struct SseParser {
+    line: Vec<u8>,
+    /// Previous chunk ended in CR (a following LF belongs to it)
+    after_cr: bool,
+    current: SseEvent,
+    has_data: bool,
+    max_event_size: usize,
+}
+
+impl SseParser {
+    /// Create a parser buffering at most `max_event_size` bytes per line and event
+    pub fn new(max_event_size: usize) -> Self {
+        Self {
+            line: Vec::new(),
+            after_cr: false,
+            current: SseEvent::default(),
+            has_data: false,
+            max_event_size,
+        }
+    }
+
+    /// Feed the next chunk, returning the events it completes
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        let mut events = Vec::new();
+        for &b in chunk {
+            let after_cr = std::mem::replace(&mut self.after_cr, false);
+            match b {
+                b'\n' if after_cr => {}
+                b'\n' => self.end_line(&mut events),
+                b'\r' => {
+                    self.after_cr = true;
+                    self.end_line(&mut events);
+                }
+                _ if self.line.len() < self.max_event_size + FIELD_OVERHEAD => {
+                    self.line.push(b)
+                }
+                _ => self.current.truncated = true,
+            }
+        }
+        events
+    }
+
+    fn end_line(&mut self, events: &mut Vec<SseEvent>) {
+        let line = std::mem::take(&mut self.line);
+        if line.is_empty() {
+            if self.has_data {
+                events.push(std::mem::take(&mut self.current));
+            }
+            self.current = SseEvent::default();
+            self.has_data = false;
+            return;
+        }
+        if line[0] == b':' {
+            return;
+        }
+        let (field, value) = match line.iter().position(|&b| b == b':') {
+            Some(i) => (&line[..i], &line[i + 1..]),
+            None => (&line[..], &[][..]),
+        };
+        let value = value.strip_prefix(b" ").unwrap_or(value);
+        match field {
+            b"event" => self.current.event = Some(String::from_utf8_lossy(value).into_owned()),
+            b"id" => self.current.id = Some(String::from_utf8_lossy(value).into_owned()),
+            b"data" => {
+                if self.has_data {
+                    self.push_data(b"\n");
+                }
+                self.push_data(value);
+                self.has_data = true;
+            }
+            _ => {}
+        }
+    }
+
+    fn push_data(&mut self, bytes: &[u8]) {
+        let room = self.max_event_size.saturating_sub(self.current.data.len());
+        if bytes.len() > room {
+            self.current.truncated = true;
+        }
+        self.current.data.extend_from_slice(&bytes[..bytes.len().min(room)]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STREAM: &[u8] = b": keep-alive\r\n\r\n\
+        event: content_block_delta\r\ndata: {\"a\":1}\r\n\r\n\
+        data: line one\ndata: line two\n\n\
+        id: 7\rdata: [DONE]\r\r";
+
+    fn parse(chunk: usize) -> Vec<SseEvent> {
+        let mut parser = SseParser::new(1024);
+        STREAM.chunks(chunk).flat_map(|c| parser.feed(c)).collect()
+    }
+
+    #[test]
+    fn test_events() {
+        let events = parse(STREAM.len());
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].event.as_deref(), Some("content_block_delta"));
+        assert_eq!(events[0].data, b"{\"a\":1}");
+        assert_eq!(events[1].event, None);
+        assert_eq!(events[1].data, b"line one\nline two");
+        assert_eq!(events[2].data, b"[DONE]");
+        assert_eq!(events[2].id.as_deref(), Some("7"));
+    }
+
+    #[test]
+    fn test_any_chunking() {
+        let expected = parse(STREAM.len());
+        for chunk in 1..12 {
+            assert_eq!(parse(chunk), expected, "chunk size {}", chunk);
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let events = parse(STREAM.len());
+        let wire: Vec<u8> = events.iter().flat_map(|e| e.to_bytes()).collect();
+        assert_eq!(SseParser::new(1024).feed(&wire), events);
+    }
+
+    #[test]
+    fn test_event_size_limit() {
+        let mut parser = SseParser::new(8);
+        let events = parser.feed(b"data: 0123456789\ndata: more\n\n");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].data, b"01234567");
+        assert!(events[0].truncated);
+    }
+}
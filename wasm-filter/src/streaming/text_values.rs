@@ -0,0 +1,128 @@
+//! Text Values of a Request Body
+//!
+//! Detectors that judge the text of a complete request (its language,
+//! entropy, repetition, markup, links) see it as a sequence of values: the
+//! decoded string values of a JSON body, or the whole of any other text body
+//! as one value at `$`. Values are streamed as `JsonEvent`s, chunk by chunk,
+//! so no detector needs the body buffered. A body declared JSON that turns
+//! out not to be has its open value closed, and the rest becomes one raw
+//! value.
+
+use super::json_tokenizer::{JsonEvent, JsonTokenizer};
+
+/// Splits a streamed body into its text values
+pub struct TextValues {
+    /// Tokenizer of a JSON body (None = raw text)
+    json: Option<JsonTokenizer>,
+    /// A value is open
+    open: bool,
+}
+
+impl TextValues {
+    /// Create a splitter for a JSON or a raw text body
+    pub fn new(json: bool) -> Self {
+        Self { json: json.then(JsonTokenizer::new), open: false }
+    }
+
+    /// Feed the next chunk; the last one closes the value left open
+    pub fn feed(&mut self, chunk: &[u8], end_of_stream: bool) -> Vec<JsonEvent> {
+        let mut events = match self.json.as_mut().map(|t| t.feed(chunk)) {
+            Some(Ok(events)) => events,
+            Some(Err(_)) => {
+                // Not JSON after all: the rest of the body is one raw value
+                self.json = None;
+                let mut events = Vec::new();
+                if self.open {
+                    events.push(JsonEvent::StringEnd);
+                    self.open = false;
+                }
+                self.raw(chunk, &mut events);
+                events
+            }
+            None => {
+                let mut events = Vec::new();
+                self.raw(chunk, &mut events);
+                events
+            }
+        };
+        for event in &events {
+            match event {
+                JsonEvent::StringStart(_) => self.open = true,
+                JsonEvent::StringEnd => self.open = false,
+                _ => {}
+            }
+        }
+        if end_of_stream && self.open {
+            events.push(JsonEvent::StringEnd);
+            self.open = false;
+        }
+        events
+    }
+
+    fn raw(&mut self, chunk: &[u8], events: &mut Vec<JsonEvent>) {
+        if chunk.is_empty() {
+            return;
+        }
+        if !self.open {
+            events.push(JsonEvent::StringStart("$".to_string()));
+        }
+        events.push(JsonEvent::StringData(chunk.to_vec()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Values as (path, text) pairs
+    fn values(events: Vec<JsonEvent>) -> Vec<(String, String)> {
+        let mut values: Vec<(String, String)> = Vec::new();
+        for event in events {
+            match event {
+                JsonEvent::StringStart(path) => values.push((path, String::new())),
+                JsonEvent::StringData(data) => {
+                    values.last_mut().unwrap().1.push_str(&String::from_utf8_lossy(&data))
+                }
+                _ => {}
+            }
+        }
+        values
+    }
+
+    #[test]
+    fn test_json_values() {
+        let mut splitter = TextValues::new(true);
+        let mut events = splitter.feed(br#"{"model":"m","messages":[{"content":"hel"#, false);
+        assert_eq!(events.last(), Some(&JsonEvent::StringData(b"hel".to_vec())));
+        events.extend(splitter.feed(br#"lo"}],"max_tokens":5}"#, true));
+        assert_eq!(events.iter().filter(|e| **e == JsonEvent::StringEnd).count(), 2);
+        assert_eq!(
+            values(events),
+            [
+                ("$.model".to_string(), "m".to_string()),
+                ("$.messages[0].content".to_string(), "hello".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_raw_and_fallback() {
+        let mut splitter = TextValues::new(false);
+        let mut events = splitter.feed(b"plain ", false);
+        events.extend(splitter.feed(b"text", false));
+        events.extend(splitter.feed(b"", true));
+        assert_eq!(events.last(), Some(&JsonEvent::StringEnd));
+        assert_eq!(values(events), [("$".to_string(), "plain text".to_string())]);
+
+        // The open value is closed where the body stops being JSON
+        let mut splitter = TextValues::new(true);
+        let mut events = splitter.feed(br#"{"a":"one"#, false);
+        events.extend(splitter.feed(br"\q two", true));
+        let ends = events.iter().filter(|e| **e == JsonEvent::StringEnd).count();
+        assert_eq!(ends, 2);
+        assert_eq!(
+            values(events),
+            [("$.a".to_string(), "one".to_string()), ("$".to_string(), r"\q two".to_string())]
+        );
+    }
+}
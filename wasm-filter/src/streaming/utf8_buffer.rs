@@ -77,6 +77,36 @@ impl Utf8Buffer {
         }
     }
 
+    /// Append a chunk to `text`, carrying a split sequence over to the next
+    /// chunk (however many it spans); invalid bytes become U+FFFD
+    pub fn push_lossy(&mut self, chunk: &[u8], text: &mut String) {
+        let mut rest = chunk;
+        while self.leftover_len > 0 {
+            match rest.split_first() {
+                Some((&b, tail)) if Self::is_continuation(b) => {
+                    self.leftover[self.leftover_len] = b;
+                    self.leftover_len += 1;
+                    rest = tail;
+                }
+                Some(_) => {
+                    text.push(char::REPLACEMENT_CHARACTER);
+                    self.leftover_len = 0;
+                }
+                None => return,
+            }
+            if self.leftover_len == Self::sequence_length(self.leftover[0]) {
+                text.push_str(&String::from_utf8_lossy(&self.leftover[..self.leftover_len]));
+                self.leftover_len = 0;
+            }
+        }
+        let (valid_end, leftover) = self.find_valid_boundary(rest);
+        text.push_str(&String::from_utf8_lossy(&rest[..valid_end]));
+        if let Some((start, len)) = leftover {
+            self.leftover[..len].copy_from_slice(&rest[start..start + len]);
+            self.leftover_len = len;
+        }
+    }
+
     /// Try to complete a UTF-8 sequence using bytes from the new chunk
     fn complete_sequence(&self, chunk: &[u8]) -> Option<Vec<u8>> {
         if self.leftover_len == 0 || chunk.is_empty() {
@@ -91,10 +121,8 @@ impl Utf8Buffer {
         }
 
         // Verify the continuation bytes
-        for i in 0..needed {
-            if !Self::is_continuation(chunk[i]) {
-                return None;
-            }
+        if !chunk[..needed].iter().all(|&b| Self::is_continuation(b)) {
+            return None;
         }
 
         // Build the complete sequence
@@ -239,6 +267,21 @@ mod tests {
         assert_eq!(processed2.main, b"!");
     }
 
+    #[test]
+    fn test_push_lossy() {
+        let mut buf = Utf8Buffer::new();
+        let mut text = String::new();
+        buf.push_lossy(&[b'a', 0xC3], &mut text);
+        assert_eq!(text, "a");
+        buf.push_lossy(&[0xA9, 0xFF, b'b'], &mut text);
+        assert_eq!(text, "a\u{e9}\u{fffd}b");
+        // A sequence spread over more than two chunks
+        for &byte in "🦀".as_bytes() {
+            buf.push_lossy(&[byte], &mut text);
+        }
+        assert_eq!(text, "a\u{e9}\u{fffd}b🦀");
+    }
+
     #[test]
     fn test_sequence_length() {
         assert_eq!(Utf8Buffer::sequence_length(b'A'), 1);      // ASCII
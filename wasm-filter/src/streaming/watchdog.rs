@@ -0,0 +1,238 @@
+//! Stream Watchdog
+//!
+//! A long-lived `text/event-stream` response can be held open to tie up
+//! proxy memory and connections, or trickled out to slip content past
+//! inspection. The watchdog bounds each stream's lifetime and event count
+//! and, after a grace period, requires a minimum rate of event data.
+//!
+//! Keep-alive comments (`: ping`) are heartbeats: they are counted, but are
+//! neither events nor data, so a stream sending only heartbeats does not
+//! satisfy the data rate. Limits are checked as chunks arrive; a stream
+//! that goes completely silent is left to Envoy's stream idle timeout.
+//!
+//! The watchdog only counts: nothing of the stream is buffered.
+
+use serde::Deserialize;
+
+/// Stream watchdog configuration (per route through route overrides)
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StreamWatchdogConfig {
+    /// Longest a stream may stay open, in seconds (unlimited when absent)
+    pub max_duration_secs: Option<u64>,
+    /// Most events a stream may carry (unlimited when absent)
+    pub max_events: Option<u64>,
+    /// Least event data per second over the stream's life (unchecked when absent)
+    pub min_bytes_per_sec: Option<u64>,
+    /// Time before the data rate is checked, in seconds
+    pub grace_secs: u64,
+}
+
+impl Default for StreamWatchdogConfig {
+    fn default() -> Self {
+        Self {
+            max_duration_secs: Some(3600),
+            max_events: Some(100_000),
+            min_bytes_per_sec: None,
+            grace_secs: 30,
+        }
+    }
+}
+
+impl StreamWatchdogConfig {
+    /// Validate the configuration, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        let limits = [
+            ("max_duration_secs", self.max_duration_secs),
+            ("max_events", self.max_events),
+            ("min_bytes_per_sec", self.min_bytes_per_sec),
+        ];
+        for (name, limit) in limits {
+            if limit == Some(0) {
+                diagnostics.push(format!("stream_watchdog.{}: must be greater than 0", name));
+            }
+        }
+        diagnostics
+    }
+}
+
+/// Limit a stream exceeded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchdogLimit {
+    /// Open longer than `max_duration_secs`
+    Duration,
+    /// More than `max_events` events
+    Events,
+    /// Less data than `min_bytes_per_sec`
+    DataRate,
+}
+
+impl WatchdogLimit {
+    /// Stable lowercase name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WatchdogLimit::Duration => "max_duration",
+            WatchdogLimit::Events => "max_events",
+            WatchdogLimit::DataRate => "min_data_rate",
+        }
+    }
+}
+
+/// A stream over its limits
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchdogTrip {
+    /// Limit exceeded
+    pub limit: WatchdogLimit,
+    /// Human-readable reason
+    pub reason: String,
+}
+
+/// Per-stream counters
+#[derive(Debug)]
+pub struct StreamWatchdog {
+    started_ns: u64,
+    /// Events dispatched so far
+    pub events: u64,
+    /// Bytes on field lines so far
+    pub data_bytes: u64,
+    /// Keep-alive comments so far
+    pub heartbeats: u64,
+    at_line_start: bool,
+    in_comment: bool,
+    after_cr: bool,
+    /// A field line was seen since the last blank line
+    pending_event: bool,
+}
+
+impl StreamWatchdog {
+    /// Watch a stream that started at `now_ns`
+    pub fn new(now_ns: u64) -> Self {
+        Self {
+            started_ns: now_ns,
+            events: 0,
+            data_bytes: 0,
+            heartbeats: 0,
+            at_line_start: true,
+            in_comment: false,
+            after_cr: false,
+            pending_event: false,
+        }
+    }
+
+    /// Count the next chunk and check the limits
+    pub fn observe(
+        &mut self,
+        config: &StreamWatchdogConfig,
+        chunk: &[u8],
+        now_ns: u64,
+    ) -> Option<WatchdogTrip> {
+        for &b in chunk {
+            let after_cr = std::mem::replace(&mut self.after_cr, false);
+            match b {
+                b'\n' if after_cr => {}
+                b'\n' | b'\r' => {
+                    self.after_cr = b == b'\r';
+                    self.end_line();
+                }
+                b':' if self.at_line_start => {
+                    self.at_line_start = false;
+                    self.in_comment = true;
+                    self.heartbeats += 1;
+                }
+                _ => {
+                    self.at_line_start = false;
+                    if !self.in_comment {
+                        self.data_bytes += 1;
+                        self.pending_event = true;
+                    }
+                }
+            }
+        }
+        self.check(config, now_ns)
+    }
+
+    fn end_line(&mut self) {
+        if self.at_line_start && self.pending_event {
+            self.events += 1;
+            self.pending_event = false;
+        }
+        self.at_line_start = true;
+        self.in_comment = false;
+    }
+
+    fn check(&self, config: &StreamWatchdogConfig, now_ns: u64) -> Option<WatchdogTrip> {
+        let elapsed_secs = now_ns.saturating_sub(self.started_ns) / 1_000_000_000;
+        if let Some(max) = config.max_duration_secs.filter(|max| elapsed_secs > *max) {
+            let reason = format!("Stream open for {}s (limit {}s)", elapsed_secs, max);
+            return Some(WatchdogTrip { limit: WatchdogLimit::Duration, reason });
+        }
+        if let Some(max) = config.max_events.filter(|max| self.events > *max) {
+            let reason = format!("Stream sent {} events (limit {})", self.events, max);
+            return Some(WatchdogTrip { limit: WatchdogLimit::Events, reason });
+        }
+        let min_rate = config.min_bytes_per_sec.filter(|_| elapsed_secs >= config.grace_secs);
+        if let Some(min) = min_rate.filter(|min| self.data_bytes < min * elapsed_secs) {
+            let reason = format!(
+                "Stream sent {} bytes in {}s (minimum {} bytes/s)",
+                self.data_bytes, elapsed_secs, min
+            );
+            return Some(WatchdogTrip { limit: WatchdogLimit::DataRate, reason });
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEC: u64 = 1_000_000_000;
+
+    #[test]
+    fn test_counts_events_and_heartbeats() {
+        let stream: &[u8] = b": ping\r\n\r\ndata: {\"a\":1}\r\n\r\nevent: x\ndata: b\n\n: ping\n\n";
+        for size in [1, 3, stream.len()] {
+            let mut watchdog = StreamWatchdog::new(0);
+            let config = StreamWatchdogConfig::default();
+            for chunk in stream.chunks(size) {
+                assert_eq!(watchdog.observe(&config, chunk, 0), None);
+            }
+            assert_eq!(watchdog.events, 2);
+            assert_eq!(watchdog.heartbeats, 2);
+            assert_eq!(watchdog.data_bytes, 28);
+        }
+    }
+
+    #[test]
+    fn test_limits() {
+        let config = StreamWatchdogConfig {
+            max_duration_secs: Some(60),
+            max_events: Some(2),
+            min_bytes_per_sec: Some(10),
+            grace_secs: 5,
+        };
+        let mut watchdog = StreamWatchdog::new(100 * SEC);
+        let event = b"data: 0123456789012345678901234567890123456789\n\n";
+        assert_eq!(watchdog.observe(&config, event, 101 * SEC), None);
+        // Heartbeats alone do not keep up the data rate once the grace period is over
+        assert_eq!(watchdog.observe(&config, b": ping\n\n", 104 * SEC), None);
+        let trip = watchdog.observe(&config, b": ping\n\n", 106 * SEC).unwrap();
+        assert_eq!(trip.limit, WatchdogLimit::DataRate);
+        assert_eq!(trip.reason, "Stream sent 46 bytes in 6s (minimum 10 bytes/s)");
+
+        let trip = StreamWatchdog::new(0).observe(&config, &event.repeat(3), SEC).unwrap();
+        assert_eq!(trip.limit, WatchdogLimit::Events);
+        let trip = StreamWatchdog::new(0).observe(&config, b"", 61 * SEC).unwrap();
+        assert_eq!(trip.limit.as_str(), "max_duration");
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = StreamWatchdogConfig { max_events: Some(0), ..Default::default() };
+        assert_eq!(
+            config.validate(),
+            vec!["stream_watchdog.max_events: must be greater than 0".to_string()]
+        );
+    }
+}
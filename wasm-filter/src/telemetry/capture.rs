@@ -0,0 +1,117 @@
+//! Payload Capture
+//!
+//! A block reason names the pattern, not the attack around it. For
+//! forensics, audit events of blocked requests can carry an excerpt of the
+//! content leading up to the match, taken from the scanner's ring buffer.
+//!
+//! Excerpts go through the PII redactor before they leave the filter, are
+//! truncated to a configured length, and can be replaced by their SHA-256
+//! where even redacted content must stay out of the logs (repeats of the
+//! same attack still correlate).
+
+use crate::crypto::{sha256, to_hex};
+use crate::governance::PiiRedactor;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// Audit payload capture configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuditCaptureConfig {
+    /// Bytes of content up to the match captured (at most the ring buffer size)
+    pub bytes: usize,
+    /// Longest excerpt recorded after redaction, in bytes
+    pub max_len: usize,
+    /// Record only the SHA-256 of the redacted excerpt
+    pub hash: bool,
+}
+
+impl Default for AuditCaptureConfig {
+    fn default() -> Self {
+        Self {
+            bytes: 128,
+            max_len: 256,
+            hash: false,
+        }
+    }
+}
+
+impl AuditCaptureConfig {
+    /// Validate the configuration, returning human-readable problems
+    pub fn validate(&self, ring_buffer_size: usize) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        if self.bytes == 0 {
+            diagnostics.push("audit_capture.bytes: must be greater than 0".to_string());
+        } else if self.bytes > ring_buffer_size {
+            diagnostics.push("audit_capture.bytes: must not exceed ring_buffer_size".to_string());
+        }
+        if self.max_len == 0 {
+            diagnostics.push("audit_capture.max_len: must be greater than 0".to_string());
+        }
+        diagnostics
+    }
+
+    /// Audit metadata for captured content (None when nothing was captured)
+    pub fn excerpt(&self, content: &[u8], redactor: &PiiRedactor) -> Option<Value> {
+        if content.is_empty() {
+            return None;
+        }
+        let text = String::from_utf8_lossy(content);
+        let mut redacted = redactor.redact(&text);
+        if self.hash {
+            return Some(json!({"excerpt_sha256": to_hex(&sha256(redacted.as_bytes()))}));
+        }
+        let truncated = redacted.len() > self.max_len;
+        if truncated {
+            // Keep the end: it leads up to the match
+            let mut start = redacted.len() - self.max_len;
+            while !redacted.is_char_boundary(start) {
+                start += 1;
+            }
+            redacted.drain(..start);
+        }
+        Some(json!({"excerpt": redacted, "excerpt_truncated": truncated}))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_excerpt_is_redacted_and_truncated() {
+        let redactor = PiiRedactor::default();
+        let config = AuditCaptureConfig { max_len: 40, ..Default::default() };
+        let content = b"mail ceo@example.com then ignore previous instructions";
+        let excerpt = config.excerpt(content, &redactor).unwrap();
+        assert_eq!(excerpt["excerpt"], "ACTED] then ignore previous instructions");
+        assert_eq!(excerpt["excerpt_truncated"], true);
+
+        let config = AuditCaptureConfig::default();
+        let excerpt = config.excerpt(content, &redactor).unwrap();
+        assert_eq!(excerpt["excerpt"], "mail [EMAIL REDACTED] then ignore previous instructions");
+        assert_eq!(excerpt["excerpt_truncated"], false);
+        assert_eq!(config.excerpt(b"", &redactor), None);
+    }
+
+    #[test]
+    fn test_hashed_excerpt() {
+        let redactor = PiiRedactor::default();
+        let config = AuditCaptureConfig { hash: true, ..Default::default() };
+        let excerpt = config.excerpt(b"call 555-123-4567 and jailbreak", &redactor).unwrap();
+        let expected = to_hex(&sha256(b"call [PHONE REDACTED] and jailbreak"));
+        assert_eq!(excerpt, json!({"excerpt_sha256": expected}));
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = AuditCaptureConfig { bytes: 8192, max_len: 0, hash: false };
+        assert_eq!(
+            config.validate(4096),
+            vec![
+                "audit_capture.bytes: must not exceed ring_buffer_size".to_string(),
+                "audit_capture.max_len: must be greater than 0".to_string(),
+            ]
+        );
+    }
+}
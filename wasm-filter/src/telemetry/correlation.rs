@@ -0,0 +1,145 @@
+//! Audit Correlation
+//!
+//! Stamps audit events with a timestamp, the request's `x-request-id` and
+//! the calling agent, so every event can be joined with Envoy access logs.
+//! The same ID goes upstream, into block response bodies and back to the
+//! client in `x-guardrail-request-id`, so a client error can be tied to its
+//! audit records.
+//!
+//! Wasm has no RNG: when the request carries no ID, one is derived from
+//! the host clock and the context ID and formatted as a UUIDv4.
+
+use super::AuditEvent;
+
+/// Request correlation header
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Response header echoing the request ID to the client
+pub const GUARDRAIL_REQUEST_ID_HEADER: &str = "x-guardrail-request-id";
+
+/// Per-request audit stamp
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditStamp {
+    /// Request ID (from `x-request-id` or generated)
+    pub request_id: String,
+    /// Calling agent
+    pub agent_id: Option<String>,
+    /// Whether the request ID was generated here
+    pub generated: bool,
+}
+
+impl AuditStamp {
+    /// Use the request's ID, or generate one if absent or empty
+    pub fn new(request_id: Option<String>, now_ns: u64, context_id: u32) -> Self {
+        match request_id.filter(|id| !id.trim().is_empty()) {
+            Some(id) => Self {
+                request_id: id,
+                agent_id: None,
+                generated: false,
+            },
+            None => Self {
+                request_id: generate_request_id(now_ns, context_id),
+                agent_id: None,
+                generated: true,
+            },
+        }
+    }
+
+    /// Set the calling agent
+    pub fn with_agent_id(mut self, agent_id: Option<String>) -> Self {
+        self.agent_id = agent_id;
+        self
+    }
+
+    /// Fill in timestamp, request ID and agent where the event has none
+    pub fn apply(&self, mut event: AuditEvent, now_secs: u64) -> AuditEvent {
+        if event.timestamp_secs.is_none() {
+            event.timestamp_secs = Some(now_secs);
+        }
+        if event.request_id.is_none() {
+            event.request_id = Some(self.request_id.clone());
+        }
+        if event.agent_id.is_none() {
+            event.agent_id = self.agent_id.clone();
+        }
+        event
+    }
+}
+
+/// Generate a UUIDv4-formatted request ID from the clock and context ID
+pub fn generate_request_id(now_ns: u64, context_id: u32) -> String {
+    let hi = mix(now_ns ^ ((context_id as u64) << 32));
+    let lo = mix(hi ^ now_ns.rotate_left(17) ^ context_id as u64);
+
+    // Set version (4) and variant (10xx) bits
+    let hi = (hi & 0xffff_ffff_ffff_0fff) | 0x0000_0000_0000_4000;
+    let lo = (lo & 0x3fff_ffff_ffff_ffff) | 0x8000_0000_0000_0000;
+
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        hi >> 32,
+        (hi >> 16) & 0xffff,
+        hi & 0xffff,
+        lo >> 48,
+        lo & 0xffff_ffff_ffff
+    )
+}
+
+/// SplitMix64 finalizer
+fn mix(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::audit_blocked;
+
+    #[test]
+    fn test_uses_existing_request_id() {
+        let stamp = AuditStamp::new(Some("abc-123".to_string()), 0, 1);
+        assert_eq!(stamp.request_id, "abc-123");
+        assert!(!stamp.generated);
+    }
+
+    #[test]
+    fn test_generates_request_id() {
+        let stamp = AuditStamp::new(None, 1_700_000_000_000_000_000, 7);
+        assert!(stamp.generated);
+
+        let id = &stamp.request_id;
+        assert_eq!(id.len(), 36);
+        assert_eq!(&id[14..15], "4");
+        assert!(matches!(&id[19..20], "8" | "9" | "a" | "b"));
+
+        let empty = AuditStamp::new(Some("  ".to_string()), 0, 1);
+        assert!(empty.generated);
+    }
+
+    #[test]
+    fn test_generated_ids_differ() {
+        let a = generate_request_id(1_000, 1);
+        let b = generate_request_id(1_000, 2);
+        let c = generate_request_id(1_001, 1);
+        assert_ne!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn test_apply_fills_missing_fields() {
+        let stamp = AuditStamp::new(Some("req-1".to_string()), 0, 1)
+            .with_agent_id(Some("agent-7".to_string()));
+
+        let event = stamp.apply(audit_blocked("test", None), 1_700_000_000);
+        assert_eq!(event.timestamp_secs, Some(1_700_000_000));
+        assert_eq!(event.request_id.as_deref(), Some("req-1"));
+        assert_eq!(event.agent_id.as_deref(), Some("agent-7"));
+
+        // Explicit values are kept
+        let event = stamp.apply(audit_blocked("test", None).with_request_id("other"), 0);
+        assert_eq!(event.request_id.as_deref(), Some("other"));
+    }
+}
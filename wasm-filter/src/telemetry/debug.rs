@@ -0,0 +1,138 @@
+//! Verdict Explanation (debug mode)
+//!
+//! When debug mode is configured and a request carries a valid signed
+//! `x-guardrail-debug` header, the response gets an `x-guardrail-verdict`
+//! header with a compact JSON explanation of the decision.
+//!
+//! Token format: `<expires_unix_secs>.<hex HMAC-SHA256(secret, "ai-guard-debug:<expires>")>`.
+
+use crate::crypto::{hmac_sha256, to_hex, verify_hmac_hex};
+use serde::Serialize;
+
+/// Request header carrying the signed debug token
+pub const DEBUG_REQUEST_HEADER: &str = "x-guardrail-debug";
+
+/// Response header carrying the explanation
+pub const VERDICT_RESPONSE_HEADER: &str = "x-guardrail-verdict";
+
+/// Domain separator for debug token signatures
+const TOKEN_CONTEXT: &str = "ai-guard-debug:";
+
+/// Sign a debug token valid until `expires_secs`
+pub fn sign_debug_token(secret: &[u8], expires_secs: u64) -> String {
+    let message = format!("{}{}", TOKEN_CONTEXT, expires_secs);
+    format!("{}.{}", expires_secs, to_hex(&hmac_sha256(secret, message.as_bytes())))
+}
+
+/// Verify a debug token
+///
+/// Rejects expired tokens and tokens valid for longer than `max_ttl_secs`.
+pub fn verify_debug_token(token: &str, secret: &[u8], now_secs: u64, max_ttl_secs: u64) -> bool {
+    let (expires, signature) = match token.trim().split_once('.') {
+        Some(parts) => parts,
+        None => return false,
+    };
+    let expires_secs = match expires.parse::<u64>() {
+        Ok(v) => v,
+        Err(_) => return false,
+    };
+    if expires_secs < now_secs || expires_secs - now_secs > max_ttl_secs {
+        return false;
+    }
+    let message = format!("{}{}", TOKEN_CONTEXT, expires_secs);
+    verify_hmac_hex(secret, message.as_bytes(), signature)
+}
+
+/// A rule that fired
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleMatch {
+    /// Matched pattern
+    pub pattern: String,
+    /// Attack category
+    pub category: String,
+    /// Severity name
+    pub severity: String,
+    /// Severity score (0.0 - 1.0)
+    pub score: f32,
+    /// JSON path of the matched string value
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+/// Compact explanation of a guardrail decision
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Explanation {
+    /// Rules that fired
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<RuleMatch>,
+    /// PII types found
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pii: Vec<String>,
+    /// Rate limit state (e.g. remaining requests)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<String>,
+    /// Request body bytes scanned
+    pub bytes_scanned: usize,
+    /// Pre-flight prompt token estimate
+    pub tokens_estimated: u32,
+}
+
+impl Explanation {
+    /// Render as a header value with the verdict action
+    pub fn to_header_value(&self, action: &str) -> String {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        value["action"] = serde_json::json!(action);
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = b"debug-secret";
+
+    #[test]
+    fn test_token_round_trip() {
+        let token = sign_debug_token(SECRET, 1_000);
+        assert!(verify_debug_token(&token, SECRET, 900, 3600));
+    }
+
+    #[test]
+    fn test_token_rejected() {
+        let token = sign_debug_token(SECRET, 1_000);
+        // Expired
+        assert!(!verify_debug_token(&token, SECRET, 1_001, 3600));
+        // Valid for too long
+        assert!(!verify_debug_token(&token, SECRET, 0, 600));
+        // Wrong secret
+        assert!(!verify_debug_token(&token, b"other", 900, 3600));
+        // Tampered expiry
+        let tampered = token.replacen("1000", "1001", 1);
+        assert!(!verify_debug_token(&tampered, SECRET, 900, 3600));
+        assert!(!verify_debug_token("garbage", SECRET, 900, 3600));
+    }
+
+    #[test]
+    fn test_explanation_header() {
+        let explanation = Explanation {
+            rules: vec![RuleMatch {
+                pattern: "jailbreak".to_string(),
+                category: "jailbreak".to_string(),
+                severity: "high".to_string(),
+                score: 0.75,
+                path: Some("$.messages[0].content".to_string()),
+            }],
+            bytes_scanned: 42,
+            ..Default::default()
+        };
+
+        let header: serde_json::Value =
+            serde_json::from_str(&explanation.to_header_value("blocked")).unwrap();
+        assert_eq!(header["action"], "blocked");
+        assert_eq!(header["rules"][0]["score"], 0.75);
+        assert_eq!(header["rules"][0]["path"], "$.messages[0].content");
+        assert_eq!(header["bytes_scanned"], 42);
+        assert!(header.get("pii").is_none());
+    }
+}
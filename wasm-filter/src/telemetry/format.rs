@@ -0,0 +1,357 @@
+//! Audit Event Formats
+//!
+//! Serializes audit events for SIEM ingestion:
+//! - `json`: the native AI-Guard event (default)
+//! - `ocsf`: OCSF Security Finding (class 2001)
+//! - `cef`: ArcSight Common Event Format line
+
+use super::{AuditEvent, AuditEventType};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+/// OCSF schema version emitted
+const OCSF_VERSION: &str = "1.1.0";
+/// OCSF Findings category
+const OCSF_CATEGORY_FINDINGS: u32 = 2;
+/// OCSF Security Finding class
+const OCSF_CLASS_SECURITY_FINDING: u32 = 2001;
+/// OCSF activity: Create
+const OCSF_ACTIVITY_CREATE: u32 = 1;
+
+/// Product name used in OCSF metadata and CEF headers
+const PRODUCT_NAME: &str = "AI-Guard";
+/// Product version used in OCSF metadata and CEF headers
+const PRODUCT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Audit event wire format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditFormat {
+    /// Native AI-Guard JSON
+    #[default]
+    Json,
+    /// OCSF Security Finding JSON
+    Ocsf,
+    /// Common Event Format line
+    Cef,
+}
+
+impl AuditFormat {
+    /// Serialize an event in this format
+    pub fn render(&self, event: &AuditEvent) -> Option<String> {
+        match self {
+            AuditFormat::Json => event.to_json(),
+            AuditFormat::Ocsf => serde_json::to_string(&to_ocsf(event)).ok(),
+            AuditFormat::Cef => Some(to_cef(event)),
+        }
+    }
+
+    /// Combine rendered events into one request body
+    ///
+    /// JSON formats are sent as an array, CEF as newline-delimited lines.
+    pub fn batch_body(&self, events: &[String]) -> String {
+        match self {
+            AuditFormat::Json | AuditFormat::Ocsf => format!("[{}]", events.join(",")),
+            AuditFormat::Cef => events.join("\n"),
+        }
+    }
+
+    /// Content type of a batch body
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            AuditFormat::Json | AuditFormat::Ocsf => "application/json",
+            AuditFormat::Cef => "text/plain",
+        }
+    }
+}
+
+/// Severity on the OCSF scale (1 = informational .. 5 = critical)
+fn ocsf_severity(event_type: &AuditEventType) -> u32 {
+    match event_type {
+        AuditEventType::RequestAllowed
+        | AuditEventType::PatternStats
+        | AuditEventType::PatternExperimentDelta
+        | AuditEventType::ConfigDiff => 1,
+        AuditEventType::A2asControl
+        | AuditEventType::ModelOverride
+        | AuditEventType::ToolApproval
+        | AuditEventType::GrpcError
+        | AuditEventType::OverheadBudgetExceeded
+        | AuditEventType::HeadersScrubbed
+        | AuditEventType::InspectionBypassed => 2,
+        AuditEventType::PiiDetected
+        | AuditEventType::RateLimited
+        | AuditEventType::ViolationMonitored
+        | AuditEventType::ControlChanged
+        | AuditEventType::RequestQuarantined
+        | AuditEventType::AgentPenalized
+        | AuditEventType::StreamTerminated
+        | AuditEventType::NotificationFlood
+        | AuditEventType::ToolResultLimited
+        | AuditEventType::EntropyAnomaly
+        | AuditEventType::CostAbuse
+        | AuditEventType::UrlPolicyViolation
+        | AuditEventType::BypassRejected => 3,
+        AuditEventType::RequestBlocked
+        | AuditEventType::StdioBypassAttempt
+        | AuditEventType::IndirectInjection
+        | AuditEventType::PromptTemplateViolation
+        | AuditEventType::ThreatIntelMatch => 4,
+        AuditEventType::CanaryLeak => 5,
+    }
+}
+
+/// Map an audit event to an OCSF Security Finding
+fn to_ocsf(event: &AuditEvent) -> Value {
+    let severity_id = ocsf_severity(&event.event_type);
+    let type_name = event.event_type.as_str();
+
+    let mut metadata = json!({
+        "version": OCSF_VERSION,
+        "product": { "name": PRODUCT_NAME, "vendor_name": PRODUCT_NAME, "version": PRODUCT_VERSION },
+    });
+    if let Some(id) = &event.request_id {
+        metadata["correlation_uid"] = json!(id);
+    }
+
+    let mut finding = json!({
+        "title": event.event_type.title(),
+        "types": [type_name],
+        "uid": event.request_id.as_deref().unwrap_or(type_name),
+    });
+    if let Some(reason) = &event.reason {
+        finding["desc"] = json!(reason);
+    }
+
+    let mut out = json!({
+        "category_uid": OCSF_CATEGORY_FINDINGS,
+        "class_uid": OCSF_CLASS_SECURITY_FINDING,
+        "activity_id": OCSF_ACTIVITY_CREATE,
+        "type_uid": OCSF_CLASS_SECURITY_FINDING * 100 + OCSF_ACTIVITY_CREATE,
+        "severity_id": severity_id,
+        "state_id": 1,
+        "metadata": metadata,
+        "finding": finding,
+    });
+    if let Some(secs) = event.timestamp_secs {
+        out["time"] = json!(secs * 1000);
+    }
+    if let Some(agent) = &event.agent_id {
+        out["actor"] = json!({ "user": { "uid": agent } });
+    }
+
+    // Fields without an OCSF home
+    let mut unmapped = Map::new();
+    let extra = [
+        ("protocol", &event.protocol),
+        ("transport", &event.transport),
+        ("method", &event.method),
+        ("matched_pattern", &event.matched_pattern),
+        ("a2as_control", &event.a2as_control),
+        ("config_version", &event.config_version),
+        ("pattern_version", &event.pattern_version),
+    ];
+    for (key, value) in extra {
+        if let Some(v) = value {
+            unmapped.insert(key.to_string(), json!(v));
+        }
+    }
+    if let Some(meta) = &event.metadata {
+        unmapped.insert("metadata".to_string(), meta.clone());
+    }
+    if let Some(sequence) = event.sequence {
+        unmapped.insert("sequence".to_string(), json!(sequence));
+    }
+    let signing = [("key_id", &event.key_id), ("signature", &event.signature)];
+    for (key, value) in signing {
+        if let Some(v) = value {
+            unmapped.insert(key.to_string(), json!(v));
+        }
+    }
+    if !unmapped.is_empty() {
+        out["unmapped"] = Value::Object(unmapped);
+    }
+
+    out
+}
+
+/// Render an audit event as a CEF line
+fn to_cef(event: &AuditEvent) -> String {
+    // CEF severity is 0-10
+    let severity = ocsf_severity(&event.event_type) * 2;
+    let mut line = format!(
+        "CEF:0|{}|{}|{}|{}|{}|{}|",
+        cef_header(PRODUCT_NAME),
+        cef_header("ai-guard-filter"),
+        cef_header(PRODUCT_VERSION),
+        cef_header(event.event_type.as_str()),
+        cef_header(event.event_type.title()),
+        severity
+    );
+
+    let mut ext: Vec<(&str, String)> = Vec::new();
+    if let Some(secs) = event.timestamp_secs {
+        ext.push(("rt", (secs * 1000).to_string()));
+    }
+    if let Some(v) = &event.request_id {
+        ext.push(("externalId", v.clone()));
+    }
+    if let Some(v) = &event.agent_id {
+        ext.push(("suser", v.clone()));
+    }
+    if let Some(v) = &event.protocol {
+        ext.push(("app", v.clone()));
+    }
+    if let Some(v) = &event.method {
+        ext.push(("requestMethod", v.clone()));
+    }
+    if let Some(v) = &event.reason {
+        ext.push(("reason", v.clone()));
+    }
+    if let Some(v) = &event.matched_pattern {
+        ext.push(("cs1Label", "matchedPattern".to_string()));
+        ext.push(("cs1", v.clone()));
+    }
+    if let Some(v) = &event.transport {
+        ext.push(("cs2Label", "transport".to_string()));
+        ext.push(("cs2", v.clone()));
+    }
+    if let Some(v) = &event.a2as_control {
+        ext.push(("cs3Label", "a2asControl".to_string()));
+        ext.push(("cs3", v.clone()));
+    }
+    if let Some(v) = &event.config_version {
+        ext.push(("flexString1Label", "configVersion".to_string()));
+        ext.push(("flexString1", v.clone()));
+    }
+    if let Some(v) = &event.pattern_version {
+        ext.push(("flexString2Label", "patternVersion".to_string()));
+        ext.push(("flexString2", v.clone()));
+    }
+    if let Some(v) = event.sequence {
+        ext.push(("cn1Label", "sequence".to_string()));
+        ext.push(("cn1", v.to_string()));
+    }
+    if let Some(v) = &event.key_id {
+        ext.push(("cs5Label", "keyId".to_string()));
+        ext.push(("cs5", v.clone()));
+    }
+    if let Some(v) = &event.signature {
+        ext.push(("cs6Label", "signature".to_string()));
+        ext.push(("cs6", v.clone()));
+    }
+
+    let ext: Vec<String> = ext
+        .into_iter()
+        .map(|(k, v)| format!("{}={}", k, cef_extension(&v)))
+        .collect();
+    line.push_str(&ext.join(" "));
+    line
+}
+
+/// Escape a CEF header field (`\` and `|`)
+fn cef_header(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('|', "\\|")
+}
+
+/// Escape a CEF extension value (`\`, `=` and line breaks)
+fn cef_extension(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('=', "\\=")
+        .replace('\r', "\\r")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blocked_event() -> AuditEvent {
+        let mut event = AuditEvent::new(AuditEventType::RequestBlocked)
+            .with_request_id("req-1")
+            .with_agent_id("agent-7")
+            .with_reason("prompt injection")
+            .with_pattern("jailbreak");
+        event.timestamp_secs = Some(1_700_000_000);
+        event
+    }
+
+    #[test]
+    fn test_ocsf_security_finding() {
+        let out: Value =
+            serde_json::from_str(&AuditFormat::Ocsf.render(&blocked_event()).unwrap()).unwrap();
+
+        assert_eq!(out["class_uid"], 2001);
+        assert_eq!(out["type_uid"], 200101);
+        assert_eq!(out["severity_id"], 4);
+        assert_eq!(out["time"], 1_700_000_000_000u64);
+        assert_eq!(out["metadata"]["correlation_uid"], "req-1");
+        assert_eq!(out["actor"]["user"]["uid"], "agent-7");
+        assert_eq!(out["unmapped"]["matched_pattern"], "jailbreak");
+    }
+
+    #[test]
+    fn test_cef_line() {
+        let line = AuditFormat::Cef.render(&blocked_event()).unwrap();
+
+        assert!(line.starts_with("CEF:0|AI-Guard|ai-guard-filter|"));
+        assert!(line.contains("|request_blocked|Request blocked|8|"));
+        assert!(line.contains("rt=1700000000000"));
+        assert!(line.contains("suser=agent-7"));
+        assert!(line.contains("cs1Label=matchedPattern cs1=jailbreak"));
+    }
+
+    #[test]
+    fn test_version_fields() {
+        let event = blocked_event().with_versions("3f2a9c01b7d4", "a81e5d0c2f96");
+        let out: Value =
+            serde_json::from_str(&AuditFormat::Ocsf.render(&event).unwrap()).unwrap();
+        assert_eq!(out["unmapped"]["config_version"], "3f2a9c01b7d4");
+        assert_eq!(out["unmapped"]["pattern_version"], "a81e5d0c2f96");
+
+        let line = AuditFormat::Cef.render(&event).unwrap();
+        assert!(line.contains("flexString1Label=configVersion flexString1=3f2a9c01b7d4"));
+        assert!(line.contains("flexString2Label=patternVersion flexString2=a81e5d0c2f96"));
+    }
+
+    #[test]
+    fn test_signature_fields() {
+        let mut event = blocked_event();
+        event.sequence = Some(9);
+        event.key_id = Some("k1".to_string());
+        event.signature = Some("ab12".to_string());
+        let out: Value =
+            serde_json::from_str(&AuditFormat::Ocsf.render(&event).unwrap()).unwrap();
+        assert_eq!(out["unmapped"]["sequence"], 9);
+        assert_eq!(out["unmapped"]["signature"], "ab12");
+
+        let line = AuditFormat::Cef.render(&event).unwrap();
+        assert!(line.contains("cn1Label=sequence cn1=9 cs5Label=keyId cs5=k1"));
+        assert!(line.ends_with("cs6Label=signature cs6=ab12"));
+    }
+
+    #[test]
+    fn test_cef_escaping() {
+        let event = AuditEvent::new(AuditEventType::RequestBlocked).with_reason("a=b\nc\\d");
+        let line = AuditFormat::Cef.render(&event).unwrap();
+        assert!(line.ends_with("reason=a\\=b\\nc\\\\d"));
+
+        assert_eq!(cef_header("x|y"), "x\\|y");
+    }
+
+    #[test]
+    fn test_batch_body() {
+        let events = vec!["{}".to_string(), "{}".to_string()];
+        assert_eq!(AuditFormat::Json.batch_body(&events), "[{},{}]");
+        assert_eq!(AuditFormat::Cef.batch_body(&events), "{}\n{}");
+        assert_eq!(AuditFormat::Cef.content_type(), "text/plain");
+    }
+
+    #[test]
+    fn test_parse_format() {
+        let format: AuditFormat = serde_json::from_str("\"ocsf\"").unwrap();
+        assert_eq!(format, AuditFormat::Ocsf);
+        assert_eq!(AuditFormat::default(), AuditFormat::Json);
+    }
+}
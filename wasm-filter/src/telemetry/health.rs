@@ -0,0 +1,230 @@
+//! Guardrail Health and Readiness
+//!
+//! A guardrail that silently stops working looks exactly like one with
+//! nothing to block. The filter answers probes on a reserved path itself,
+//! without authentication and before any policy applies, with one check
+//! per dependency:
+//! - `patterns`: the blocked patterns compiled into a transition table
+//! - `threat_feed`: the feed was replaced recently (when a feed is configured)
+//! - `audit_sink`: the collector is taking events (when a sink is configured)
+//! - `shared_data`: this worker can write and read back shared data
+//!
+//! The response is `200` when every check passes and `503` otherwise, with
+//! `x-guardrail-status: ready` or `degraded` for probes that only look at
+//! headers.
+
+use super::AuditShipper;
+use crate::config::ConfigSnapshot;
+use crate::governance::ThreatFeedConfig;
+use crate::shared::SharedStore;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+/// Response header carrying the overall status
+pub const STATUS_HEADER: &str = "x-guardrail-status";
+
+/// Shared-data key written and read back by the connectivity check
+const PROBE_KEY: &str = "ai_guard.health.probe";
+
+/// Feed refresh intervals after which an unchanged feed is stale
+const STALE_FEED_INTERVALS: u64 = 3;
+
+fn default_health_path() -> String {
+    "/.well-known/ai-guard/health".to_string()
+}
+
+/// Health endpoint configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HealthConfig {
+    /// Path answered by the filter (query ignored)
+    pub path: String,
+    /// Oldest acceptable threat feed, in seconds (three refresh intervals when absent)
+    pub max_feed_age_secs: Option<u64>,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self { path: default_health_path(), max_feed_age_secs: None }
+    }
+}
+
+impl HealthConfig {
+    /// Validate the configuration, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        if !self.path.starts_with('/') {
+            diagnostics.push("health.path: must start with '/'".to_string());
+        }
+        if self.max_feed_age_secs == Some(0) {
+            diagnostics.push("health.max_feed_age_secs: must be greater than 0".to_string());
+        }
+        diagnostics
+    }
+
+    /// Whether a request path addresses the health endpoint (query ignored)
+    pub fn is_health_path(&self, path: &str) -> bool {
+        path.split('?').next() == Some(self.path.as_str())
+    }
+
+    /// Oldest acceptable threat feed, in seconds
+    fn max_feed_age_secs(&self, feed: &ThreatFeedConfig) -> u64 {
+        self.max_feed_age_secs
+            .unwrap_or(feed.refresh_interval_secs.saturating_mul(STALE_FEED_INTERVALS))
+    }
+}
+
+/// Result of one check
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Check {
+    /// Whether the check passed
+    pub ok: bool,
+    /// What was found
+    pub detail: String,
+}
+
+impl Check {
+    fn new(ok: bool, detail: String) -> Self {
+        Self { ok, detail }
+    }
+}
+
+/// Checks run for one probe
+#[derive(Debug, Clone, Default)]
+pub struct HealthReport {
+    checks: BTreeMap<&'static str, Check>,
+}
+
+impl HealthReport {
+    /// Record a check
+    pub fn add(&mut self, name: &'static str, check: Check) {
+        self.checks.insert(name, check);
+    }
+
+    /// A check by name
+    pub fn check(&self, name: &str) -> Option<&Check> {
+        self.checks.get(name)
+    }
+
+    /// Whether every check passed
+    pub fn is_ready(&self) -> bool {
+        self.checks.values().all(|c| c.ok)
+    }
+
+    /// `ready` or `degraded`
+    pub fn status(&self) -> &'static str {
+        if self.is_ready() {
+            "ready"
+        } else {
+            "degraded"
+        }
+    }
+
+    /// HTTP status and JSON body
+    pub fn to_http(&self, config_digest: Option<&str>) -> (u32, String) {
+        let status = if self.is_ready() { 200 } else { 503 };
+        let body = json!({
+            "status": self.status(),
+            "config_digest": config_digest,
+            "checks": self.checks,
+        });
+        (status, body.to_string())
+    }
+}
+
+/// The blocked patterns compiled into a transition table
+///
+/// A set too large for the table still scans, pattern by pattern, at a
+/// cost every request pays.
+pub fn check_patterns(config: &ConfigSnapshot) -> Check {
+    let count = config.patterns().len();
+    match config.table() {
+        Some(_) => Check::new(true, format!("{} patterns compiled", count)),
+        None if count == 0 => Check::new(true, "no patterns configured".to_string()),
+        None => Check::new(false, format!("{} patterns too large for the table", count)),
+    }
+}
+
+/// The threat feed was replaced recently
+pub fn check_threat_feed(
+    config: &HealthConfig,
+    feed: &ThreatFeedConfig,
+    updated_at: Option<u64>,
+    now_secs: u64,
+) -> Check {
+    let updated_at = match updated_at {
+        Some(secs) => secs,
+        None => return Check::new(false, "never fetched".to_string()),
+    };
+    let age = now_secs.saturating_sub(updated_at);
+    let detail = format!("last refreshed at {} ({}s ago)", updated_at, age);
+    Check::new(age <= config.max_feed_age_secs(feed), detail)
+}
+
+/// The audit collector is taking events
+pub fn check_audit_sink(shipper: &AuditShipper) -> Check {
+    let detail = format!(
+        "{} queued, {} in flight, {} failed attempts",
+        shipper.queued(),
+        shipper.in_flight(),
+        shipper.failures()
+    );
+    Check::new(shipper.is_healthy(), detail)
+}
+
+/// This worker can write and read back shared data
+pub fn check_shared_data(store: &impl SharedStore, now_secs: u64) -> Check {
+    let written = now_secs.to_string();
+    if let Err(e) = store.set(PROBE_KEY, written.as_bytes(), None) {
+        return Check::new(false, format!("write failed: {}", e));
+    }
+    match store.get(PROBE_KEY) {
+        (Some(value), _) if value == written.as_bytes() => Check::new(true, "ok".to_string()),
+        _ => Check::new(false, "read back failed".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::FilterConfig;
+    use crate::shared::MemorySharedStore;
+
+    #[test]
+    fn test_checks() {
+        let config = FilterConfig::from_bytes(br#"{"blocked_patterns": ["jailbreak"]}"#).unwrap();
+        let mut report = HealthReport::default();
+        report.add("patterns", check_patterns(&ConfigSnapshot::new(config)));
+        report.add("shared_data", check_shared_data(&MemorySharedStore::new(), 1_000));
+        assert!(report.is_ready());
+        assert_eq!(report.check("patterns").unwrap().detail, "1 patterns compiled");
+
+        let health = HealthConfig::default();
+        let feed: ThreatFeedConfig = serde_json::from_str(r#"{"cluster": "intel"}"#).unwrap();
+        assert!(check_threat_feed(&health, &feed, Some(1_000), 1_900).ok);
+        report.add("threat_feed", check_threat_feed(&health, &feed, Some(1_000), 1_901));
+        assert!(!report.is_ready());
+        let (status, body) = report.to_http(Some("ab12"));
+        assert_eq!(status, 503);
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["status"], "degraded");
+        assert_eq!(body["checks"]["threat_feed"]["detail"], "last refreshed at 1000 (901s ago)");
+        assert!(!check_threat_feed(&health, &feed, None, 1_000).ok);
+    }
+
+    #[test]
+    fn test_validate_and_path() {
+        let config = HealthConfig { path: "health".to_string(), max_feed_age_secs: Some(0) };
+        assert_eq!(
+            config.validate(),
+            vec![
+                "health.path: must start with '/'".to_string(),
+                "health.max_feed_age_secs: must be greater than 0".to_string(),
+            ]
+        );
+        let config = HealthConfig::default();
+        assert!(config.is_health_path("/.well-known/ai-guard/health?verbose=1"));
+        assert!(!config.is_health_path("/.well-known/ai-guard/status"));
+    }
+}
@@ -0,0 +1,947 @@
+//! Telemetry Module for AI-Guard
+//!
+//! Provides OpenTelemetry-compatible logging and metrics.
+//! In Wasm, we emit structured logs that can be collected by
+//! Envoy's access logging or external collectors.
+
+mod capture;
+mod correlation;
+mod debug;
+mod format;
+mod health;
+mod overhead;
+pub mod pattern_stats;
+mod propagation;
+mod shipper;
+mod signing;
+
+pub use capture::AuditCaptureConfig;
+pub use correlation::{
+    generate_request_id, AuditStamp, GUARDRAIL_REQUEST_ID_HEADER, REQUEST_ID_HEADER,
+};
+pub use debug::{
+    sign_debug_token, verify_debug_token, Explanation, RuleMatch, DEBUG_REQUEST_HEADER,
+    VERDICT_RESPONSE_HEADER,
+};
+pub use format::AuditFormat;
+pub use health::{
+    check_audit_sink, check_patterns, check_shared_data, check_threat_feed, Check, HealthConfig,
+    HealthReport, STATUS_HEADER,
+};
+pub use overhead::{OverheadBudgetConfig, OverheadTracker, Phase};
+pub use propagation::{VerdictPropagationConfig, FORWARDED_VERDICT_HEADER};
+pub use shipper::{AuditBatch, AuditShipper, ShipOutcome};
+pub use signing::AuditSigningConfig;
+
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Audit event types
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEventType {
+    /// Request allowed
+    RequestAllowed,
+    /// Request blocked
+    RequestBlocked,
+    /// PII detected
+    PiiDetected,
+    /// Rate limited
+    RateLimited,
+    /// A2AS control triggered
+    A2asControl,
+    /// STDIO bypass attempt
+    StdioBypassAttempt,
+    /// Periodic blocked-pattern statistics
+    PatternStats,
+    /// System-prompt canary seen in a completion
+    CanaryLeak,
+    /// Requested model rewritten by the model policy
+    ModelOverride,
+    /// Injection pattern in an MCP tool or resource result
+    IndirectInjection,
+    /// Violation forwarded because enforcement is relaxed by runtime controls
+    ViolationMonitored,
+    /// Runtime control flags changed through the admin endpoint
+    ControlChanged,
+    /// Request forwarded with quarantine routing instead of blocked
+    RequestQuarantined,
+    /// Reviewer decision on a high-risk tool call
+    ToolApproval,
+    /// gRPC call that ended with a non-OK status
+    GrpcError,
+    /// The filter's own p99 processing time went over its budget
+    OverheadBudgetExceeded,
+    /// An agent's repeated violations escalated its penalty
+    AgentPenalized,
+    /// A response stream was cut off by the stream watchdog
+    StreamTerminated,
+    /// An MCP session went over its notification cap
+    NotificationFlood,
+    /// An MCP tool result went over a size or depth limit
+    ToolResultLimited,
+    /// Template abuse or injection in an MCP prompts/get call
+    PromptTemplateViolation,
+    /// Sensitive request headers stripped, hashed or redacted before forwarding
+    HeadersScrubbed,
+    /// A long high-entropy run (likely an encoded blob) in a prompt
+    EntropyAnomaly,
+    /// A prompt built to burn tokens (massive repetition or whitespace)
+    CostAbuse,
+    /// A link in a prompt or tool result to a disallowed destination
+    UrlPolicyViolation,
+    /// A request matching a known-bad fingerprint from the threat-intel feed
+    ThreatIntelMatch,
+    /// Control and candidate pattern bundles disagreed on a request
+    PatternExperimentDelta,
+    /// The dry-run candidate configuration's verdict differed
+    ConfigDiff,
+    /// Inspection skipped for a request with a valid signed bypass token
+    InspectionBypassed,
+    /// A bypass token was invalid or over its key's cap
+    BypassRejected,
+}
+
+impl AuditEventType {
+    /// Stable snake_case name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditEventType::RequestAllowed => "request_allowed",
+            AuditEventType::RequestBlocked => "request_blocked",
+            AuditEventType::PiiDetected => "pii_detected",
+            AuditEventType::RateLimited => "rate_limited",
+            AuditEventType::A2asControl => "a2as_control",
+            AuditEventType::StdioBypassAttempt => "stdio_bypass_attempt",
+            AuditEventType::PatternStats => "pattern_stats",
+            AuditEventType::CanaryLeak => "canary_leak",
+            AuditEventType::ModelOverride => "model_override",
+            AuditEventType::IndirectInjection => "indirect_injection",
+            AuditEventType::ViolationMonitored => "violation_monitored",
+            AuditEventType::ControlChanged => "control_changed",
+            AuditEventType::RequestQuarantined => "request_quarantined",
+            AuditEventType::ToolApproval => "tool_approval",
+            AuditEventType::GrpcError => "grpc_error",
+            AuditEventType::OverheadBudgetExceeded => "overhead_budget_exceeded",
+            AuditEventType::AgentPenalized => "agent_penalized",
+            AuditEventType::StreamTerminated => "stream_terminated",
+            AuditEventType::NotificationFlood => "notification_flood",
+            AuditEventType::ToolResultLimited => "tool_result_limited",
+            AuditEventType::PromptTemplateViolation => "prompt_template_violation",
+            AuditEventType::HeadersScrubbed => "headers_scrubbed",
+            AuditEventType::EntropyAnomaly => "entropy_anomaly",
+            AuditEventType::CostAbuse => "cost_abuse",
+            AuditEventType::UrlPolicyViolation => "url_policy_violation",
+            AuditEventType::ThreatIntelMatch => "threat_intel_match",
+            AuditEventType::PatternExperimentDelta => "pattern_experiment_delta",
+            AuditEventType::ConfigDiff => "config_diff",
+            AuditEventType::InspectionBypassed => "inspection_bypassed",
+            AuditEventType::BypassRejected => "bypass_rejected",
+        }
+    }
+
+    /// Human-readable title
+    pub fn title(&self) -> &'static str {
+        match self {
+            AuditEventType::RequestAllowed => "Request allowed",
+            AuditEventType::RequestBlocked => "Request blocked",
+            AuditEventType::PiiDetected => "PII detected",
+            AuditEventType::RateLimited => "Rate limited",
+            AuditEventType::A2asControl => "A2AS control triggered",
+            AuditEventType::StdioBypassAttempt => "STDIO bypass attempt",
+            AuditEventType::PatternStats => "Blocked-pattern statistics",
+            AuditEventType::CanaryLeak => "System prompt canary leaked",
+            AuditEventType::ModelOverride => "Model overridden by policy",
+            AuditEventType::IndirectInjection => "Injection in MCP result",
+            AuditEventType::ViolationMonitored => "Violation forwarded (monitor)",
+            AuditEventType::ControlChanged => "Runtime controls changed",
+            AuditEventType::RequestQuarantined => "Request quarantined",
+            AuditEventType::ToolApproval => "Tool call approval decision",
+            AuditEventType::GrpcError => "gRPC call failed",
+            AuditEventType::OverheadBudgetExceeded => "Guardrail overhead over budget",
+            AuditEventType::AgentPenalized => "Agent penalty escalated",
+            AuditEventType::StreamTerminated => "Stream terminated by watchdog",
+            AuditEventType::NotificationFlood => "MCP notification cap exceeded",
+            AuditEventType::ToolResultLimited => "MCP tool result over limit",
+            AuditEventType::PromptTemplateViolation => "Prompt template abuse",
+            AuditEventType::HeadersScrubbed => "Request headers scrubbed",
+            AuditEventType::EntropyAnomaly => "High-entropy run in prompt",
+            AuditEventType::CostAbuse => "Token-burning prompt",
+            AuditEventType::UrlPolicyViolation => "Link to a disallowed destination",
+            AuditEventType::ThreatIntelMatch => "Known-bad prompt from threat intel",
+            AuditEventType::PatternExperimentDelta => "Pattern bundles disagreed",
+            AuditEventType::ConfigDiff => "Candidate config verdict differs",
+            AuditEventType::InspectionBypassed => "Inspection bypassed by signed token",
+            AuditEventType::BypassRejected => "Bypass token rejected",
+        }
+    }
+}
+
+/// Audit event for logging
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEvent {
+    /// Event type
+    pub event_type: AuditEventType,
+    /// Timestamp (seconds since epoch)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp_secs: Option<u64>,
+    /// Request ID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// Agent ID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agent_id: Option<String>,
+    /// Protocol (MCP, A2A)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<String>,
+    /// Transport (HTTP, SSE, WebSocket)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transport: Option<String>,
+    /// Method called
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    /// Reason for action
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Pattern matched (if blocked)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub matched_pattern: Option<String>,
+    /// A2AS control that triggered
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub a2as_control: Option<String>,
+    /// Version (digest prefix) of the configuration in force
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub config_version: Option<String>,
+    /// Version (digest prefix) of the blocked pattern set in force
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pattern_version: Option<String>,
+    /// Additional metadata
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
+    /// Per-worker sequence number (signed events)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<u64>,
+    /// ID of the signing key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<String>,
+    /// HMAC-SHA256 of the event, hex
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+impl AuditEvent {
+    /// Create a new audit event
+    pub fn new(event_type: AuditEventType) -> Self {
+        Self {
+            event_type,
+            timestamp_secs: None,
+            request_id: None,
+            agent_id: None,
+            protocol: None,
+            transport: None,
+            method: None,
+            reason: None,
+            matched_pattern: None,
+            a2as_control: None,
+            config_version: None,
+            pattern_version: None,
+            metadata: None,
+            sequence: None,
+            key_id: None,
+            signature: None,
+        }
+    }
+
+    /// Set request ID
+    pub fn with_request_id(mut self, id: &str) -> Self {
+        self.request_id = Some(id.to_string());
+        self
+    }
+
+    /// Set agent ID
+    pub fn with_agent_id(mut self, id: &str) -> Self {
+        self.agent_id = Some(id.to_string());
+        self
+    }
+
+    /// Set protocol
+    pub fn with_protocol(mut self, protocol: &str) -> Self {
+        self.protocol = Some(protocol.to_string());
+        self
+    }
+
+    /// Set transport
+    pub fn with_transport(mut self, transport: &str) -> Self {
+        self.transport = Some(transport.to_string());
+        self
+    }
+
+    /// Set method
+    pub fn with_method(mut self, method: &str) -> Self {
+        self.method = Some(method.to_string());
+        self
+    }
+
+    /// Set reason
+    pub fn with_reason(mut self, reason: &str) -> Self {
+        self.reason = Some(reason.to_string());
+        self
+    }
+
+    /// Set matched pattern
+    pub fn with_pattern(mut self, pattern: &str) -> Self {
+        self.matched_pattern = Some(pattern.to_string());
+        self
+    }
+
+    /// Set A2AS control
+    pub fn with_a2as_control(mut self, control: &str) -> Self {
+        self.a2as_control = Some(control.to_string());
+        self
+    }
+
+    /// Set the configuration and pattern set versions
+    pub fn with_versions(mut self, config: &str, patterns: &str) -> Self {
+        self.config_version = Some(config.to_string());
+        self.pattern_version = Some(patterns.to_string());
+        self
+    }
+
+    /// Merge fields into the metadata object
+    pub fn with_metadata(mut self, fields: serde_json::Value) -> Self {
+        match (&mut self.metadata, fields) {
+            (Some(serde_json::Value::Object(metadata)), serde_json::Value::Object(fields)) => {
+                metadata.extend(fields)
+            }
+            (_, fields) => self.metadata = Some(fields),
+        }
+        self
+    }
+
+    /// Serialize the event as a JSON line
+    pub fn to_json(&self) -> Option<String> {
+        serde_json::to_string(self).ok()
+    }
+
+    /// Log the event
+    pub fn emit(&self) {
+        self.emit_as(AuditFormat::Json);
+    }
+
+    /// Log the event in the given format
+    pub fn emit_as(&self, format: AuditFormat) {
+        match format.render(self) {
+            Some(json) => {
+                match self.event_type {
+                    AuditEventType::RequestBlocked
+                    | AuditEventType::StdioBypassAttempt
+                    | AuditEventType::RateLimited
+                    | AuditEventType::CanaryLeak
+                    | AuditEventType::IndirectInjection
+                    | AuditEventType::ViolationMonitored
+                    | AuditEventType::ControlChanged
+                    | AuditEventType::RequestQuarantined => {
+                        warn!("[AI-GUARD-AUDIT] {}", json);
+                    }
+                    _ => {
+                        info!("[AI-GUARD-AUDIT] {}", json);
+                    }
+                }
+            }
+            None => {
+                warn!("Failed to serialize audit event");
+            }
+        }
+    }
+}
+
+/// Outcome recorded in the per-request verdict
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerdictAction {
+    /// Request passed all checks
+    Allowed,
+    /// Request was blocked
+    Blocked,
+    /// Request was rejected by rate limiting
+    RateLimited,
+    /// Request violated policy but was forwarded (monitor mode)
+    Monitored,
+    /// Request was forwarded with quarantine routing
+    Quarantined,
+    /// Request was forwarded uninspected on a signed bypass token
+    Bypassed,
+}
+
+impl VerdictAction {
+    /// Stable lowercase name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VerdictAction::Allowed => "allowed",
+            VerdictAction::Blocked => "blocked",
+            VerdictAction::RateLimited => "rate_limited",
+            VerdictAction::Monitored => "monitored",
+            VerdictAction::Quarantined => "quarantined",
+            VerdictAction::Bypassed => "bypassed",
+        }
+    }
+}
+
+/// Prefix for filter-state properties written by AI-Guard
+///
+/// Envoy stores Wasm properties as filter state under `wasm.<key>`, so
+/// access logs read them with e.g. `%FILTER_STATE(wasm.ai_guard.action:PLAIN)%`.
+pub const PROPERTY_PREFIX: &str = "ai_guard";
+
+/// Structured guardrail result for one request
+///
+/// Published as filter state so Envoy access logs and later filters
+/// (ext_authz, ratelimit) can consume it without parsing logs.
+#[derive(Debug, Clone, Serialize)]
+pub struct Verdict {
+    /// Allowed or blocked
+    pub action: VerdictAction,
+    /// Pattern matched (if blocked)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub matched_pattern: Option<String>,
+    /// Attack category of the matched pattern
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// Severity of the matched pattern
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub severity: Option<String>,
+    /// Calling agent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agent_id: Option<String>,
+    /// Resolved tenant
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    /// Matched route
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub route: Option<String>,
+    /// Identity tier
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tier: Option<String>,
+    /// Request class (chat, embedding, tool_call, ...)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub class: Option<String>,
+    /// Requested model
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Prompt tokens (from response usage)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_tokens: Option<u32>,
+    /// Completion tokens (from response usage)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completion_tokens: Option<u32>,
+    /// Estimated cost in USD
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost_usd: Option<f64>,
+    /// PII redacted from the response, per type (counts only, never values)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redactions: Option<BTreeMap<String, u32>>,
+    /// Detected prompt language (ISO 639-1)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Pattern experiment arm (control or candidate)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub experiment_arm: Option<String>,
+}
+
+impl Verdict {
+    /// Create a verdict with the given action
+    pub fn new(action: VerdictAction) -> Self {
+        Self {
+            action,
+            matched_pattern: None,
+            category: None,
+            severity: None,
+            agent_id: None,
+            tenant: None,
+            route: None,
+            tier: None,
+            class: None,
+            model: None,
+            prompt_tokens: None,
+            completion_tokens: None,
+            cost_usd: None,
+            redactions: None,
+            language: None,
+            experiment_arm: None,
+        }
+    }
+
+    /// Redaction counts as an RFC 8941 dictionary (`email=2, ssn=1`)
+    pub fn redaction_summary(&self) -> Option<String> {
+        let counts = self.redactions.as_ref()?;
+        let entries: Vec<String> = counts.iter().map(|(t, n)| format!("{}={}", t, n)).collect();
+        Some(entries.join(", "))
+    }
+
+    /// Flatten into (property name, value) pairs
+    ///
+    /// Each field gets its own property for simple access-log formats,
+    /// plus `ai_guard.verdict` holding the whole verdict as JSON.
+    pub fn properties(&self) -> Vec<(String, String)> {
+        let key = |name: &str| format!("{}.{}", PROPERTY_PREFIX, name);
+        let mut props = vec![(key("action"), self.action.as_str().to_string())];
+
+        if let Some(v) = &self.matched_pattern {
+            props.push((key("matched_pattern"), v.clone()));
+        }
+        if let Some(v) = &self.category {
+            props.push((key("category"), v.clone()));
+        }
+        if let Some(v) = &self.severity {
+            props.push((key("severity"), v.clone()));
+        }
+        if let Some(v) = &self.agent_id {
+            props.push((key("agent_id"), v.clone()));
+        }
+        if let Some(v) = &self.tenant {
+            props.push((key("tenant"), v.clone()));
+        }
+        if let Some(v) = &self.route {
+            props.push((key("route"), v.clone()));
+        }
+        if let Some(v) = &self.tier {
+            props.push((key("tier"), v.clone()));
+        }
+        if let Some(v) = &self.class {
+            props.push((key("class"), v.clone()));
+        }
+        if let Some(v) = &self.model {
+            props.push((key("model"), v.clone()));
+        }
+        if let Some(v) = self.prompt_tokens {
+            props.push((key("tokens_prompt"), v.to_string()));
+        }
+        if let Some(v) = self.completion_tokens {
+            props.push((key("tokens_completion"), v.to_string()));
+        }
+        if let Some(v) = self.cost_usd {
+            props.push((key("cost_usd"), format!("{:.6}", v)));
+        }
+        if let Some(v) = self.redaction_summary() {
+            props.push((key("redactions"), v));
+        }
+        if let Some(v) = &self.language {
+            props.push((key("language"), v.clone()));
+        }
+        if let Some(v) = &self.experiment_arm {
+            props.push((key("experiment_arm"), v.clone()));
+        }
+        if let Ok(json) = serde_json::to_string(self) {
+            props.push((key("verdict"), json));
+        }
+
+        props
+    }
+}
+
+/// Create a blocked request audit event
+pub fn audit_blocked(reason: &str, pattern: Option<&str>) -> AuditEvent {
+    let mut event = AuditEvent::new(AuditEventType::RequestBlocked)
+        .with_reason(reason);
+    
+    if let Some(p) = pattern {
+        event = event.with_pattern(p);
+    }
+    
+    event
+}
+
+/// Create an allowed request audit event
+pub fn audit_allowed() -> AuditEvent {
+    AuditEvent::new(AuditEventType::RequestAllowed)
+}
+
+/// Create a PII detected audit event
+pub fn audit_pii(pii_type: &str) -> AuditEvent {
+    AuditEvent::new(AuditEventType::PiiDetected)
+        .with_reason(&format!("PII type '{}' detected", pii_type))
+}
+
+/// Create a rate limited audit event
+pub fn audit_rate_limited(limit: &str) -> AuditEvent {
+    AuditEvent::new(AuditEventType::RateLimited)
+        .with_reason(&format!("Rate limit '{}' exceeded", limit))
+}
+
+/// Create an A2AS control audit event
+pub fn audit_a2as(control: &str, action: &str) -> AuditEvent {
+    AuditEvent::new(AuditEventType::A2asControl)
+        .with_a2as_control(control)
+        .with_reason(action)
+}
+
+/// Create a system-prompt canary leak audit event
+pub fn audit_canary_leak(canary: &str) -> AuditEvent {
+    AuditEvent::new(AuditEventType::CanaryLeak)
+        .with_reason(&format!("System prompt canary '{}' leaked in response", canary))
+        .with_pattern(canary)
+}
+
+/// Create a model override audit event
+pub fn audit_model_override(requested: &str, replacement: &str) -> AuditEvent {
+    AuditEvent::new(AuditEventType::ModelOverride)
+        .with_reason(&format!("Model '{}' rewritten to '{}'", requested, replacement))
+}
+
+/// Create an MCP result injection audit event
+pub fn audit_indirect_injection(pattern: &str, path: &str, action: &str) -> AuditEvent {
+    AuditEvent::new(AuditEventType::IndirectInjection)
+        .with_reason(&format!("Injection in MCP result at {} ({})", path, action))
+        .with_pattern(pattern)
+}
+
+/// Create an audit event for the approval decision on a tool call
+pub fn audit_tool_approval(tool: &str, approved: bool, detail: &str) -> AuditEvent {
+    let verdict = if approved { "approved" } else { "rejected" };
+    AuditEvent::new(AuditEventType::ToolApproval)
+        .with_reason(&format!("Tool '{}' {}: {}", tool, verdict, detail))
+}
+
+/// Create a quarantined request audit event
+pub fn audit_quarantined(reason: &str, pattern: Option<&str>) -> AuditEvent {
+    let event = AuditEvent::new(AuditEventType::RequestQuarantined).with_reason(reason);
+    match pattern {
+        Some(p) => event.with_pattern(p),
+        None => event,
+    }
+}
+
+/// Create an audit event for a violation forwarded by runtime controls
+pub fn audit_violation_monitored(category: &str) -> AuditEvent {
+    AuditEvent::new(AuditEventType::ViolationMonitored)
+        .with_reason(&format!("'{}' violation forwarded: enforcement relaxed", category))
+}
+
+/// Create an audit event for a change of the runtime control flags
+pub fn audit_control_changed(flags: &str) -> AuditEvent {
+    AuditEvent::new(AuditEventType::ControlChanged)
+        .with_reason(&format!("Runtime controls set to {}", flags))
+}
+
+/// Create an audit event for a gRPC call that ended with a non-OK status
+pub fn audit_grpc_error(code: u32, name: &str, message: Option<&str>) -> AuditEvent {
+    let reason = match message {
+        Some(m) => format!("gRPC status {} ({}): {}", code, name, m),
+        None => format!("gRPC status {} ({})", code, name),
+    };
+    AuditEvent::new(AuditEventType::GrpcError).with_reason(&reason)
+}
+
+/// Create an audit event for the filter's p99 overhead going over its budget
+pub fn audit_overhead_budget(p99_us: u64, budget_us: u64) -> AuditEvent {
+    AuditEvent::new(AuditEventType::OverheadBudgetExceeded).with_reason(&format!(
+        "Guardrail p99 overhead {}us exceeds budget of {}us",
+        p99_us, budget_us
+    ))
+}
+
+/// Create an audit event for an agent whose penalty escalated
+pub fn audit_agent_penalized(agent: &str, penalty: &str, violations: u32) -> AuditEvent {
+    AuditEvent::new(AuditEventType::AgentPenalized)
+        .with_agent_id(agent)
+        .with_reason(&format!("Agent {} after {} violations", penalty, violations))
+}
+
+/// Create an audit event for an agent's penalty record cleared by an operator
+pub fn audit_agent_reset(agent: &str) -> AuditEvent {
+    AuditEvent::new(AuditEventType::ControlChanged)
+        .with_reason(&format!("Penalty record of agent {} cleared", agent))
+}
+
+/// Create an audit event for a stream cut off by the watchdog
+pub fn audit_stream_terminated(limit: &str, reason: &str) -> AuditEvent {
+    AuditEvent::new(AuditEventType::StreamTerminated)
+        .with_reason(reason)
+        .with_pattern(limit)
+}
+
+/// Create an audit event for a session over its notification cap
+pub fn audit_notification_flood(method: &str, session: &str, action: &str) -> AuditEvent {
+    AuditEvent::new(AuditEventType::NotificationFlood)
+        .with_method(method)
+        .with_reason(&format!("Session {} over its notification cap ({})", session, action))
+}
+
+/// Create an audit event for a tool result over a limit
+pub fn audit_tool_result_limited(limit: &str, reason: &str, action: &str) -> AuditEvent {
+    AuditEvent::new(AuditEventType::ToolResultLimited)
+        .with_reason(&format!("Tool result {} ({})", reason, action))
+        .with_pattern(limit)
+}
+
+/// Create an audit event for template abuse in a prompts/get request or result
+pub fn audit_prompt_template(issue: &str, reason: &str, direction: &str) -> AuditEvent {
+    AuditEvent::new(AuditEventType::PromptTemplateViolation)
+        .with_method("prompts/get")
+        .with_reason(&format!("{} ({})", reason, direction))
+        .with_pattern(issue)
+}
+
+/// Create an audit event for request headers scrubbed before forwarding
+///
+/// Lists header names and actions only, never values.
+pub fn audit_headers_scrubbed(scrubbed: &[(String, &str)]) -> AuditEvent {
+    let listed: Vec<String> =
+        scrubbed.iter().map(|(name, action)| format!("{} ({})", name, action)).collect();
+    let headers: Vec<serde_json::Value> = scrubbed
+        .iter()
+        .map(|(name, action)| serde_json::json!({"name": name, "action": action}))
+        .collect();
+    AuditEvent::new(AuditEventType::HeadersScrubbed)
+        .with_reason(&format!("Scrubbed headers: {}", listed.join(", ")))
+        .with_metadata(serde_json::json!({"headers": headers}))
+}
+
+/// Create an audit event for a high-entropy run in a request
+pub fn audit_entropy_anomaly(len: usize, peak_bits: f64, action: &str) -> AuditEvent {
+    AuditEvent::new(AuditEventType::EntropyAnomaly)
+        .with_reason(&format!(
+            "{}-byte run at {:.2} bits/byte ({})",
+            len, peak_bits, action
+        ))
+        .with_metadata(serde_json::json!({"length": len, "peak_bits": peak_bits}))
+}
+
+/// Create an audit event for a prompt built to burn tokens
+pub fn audit_cost_abuse(kind: &str, reason: &str, action: &str) -> AuditEvent {
+    AuditEvent::new(AuditEventType::CostAbuse)
+        .with_reason(&format!("{} ({})", reason, action))
+        .with_pattern(kind)
+}
+
+/// Create an audit event for a link to a disallowed destination
+pub fn audit_url_violation(host: &str, reason: &str, direction: &str, action: &str) -> AuditEvent {
+    AuditEvent::new(AuditEventType::UrlPolicyViolation)
+        .with_reason(&format!("Link to {} in {} ({}, {})", host, direction, reason, action))
+        .with_metadata(serde_json::json!({"host": host, "direction": direction}))
+}
+
+/// Create an audit event for a request matching a threat-intel IoC
+pub fn audit_threat_intel(ioc_id: &str, path: &str, feed_version: Option<&str>) -> AuditEvent {
+    AuditEvent::new(AuditEventType::ThreatIntelMatch)
+        .with_reason(&format!("Value at {} matches IoC {}", path, ioc_id))
+        .with_pattern(ioc_id)
+        .with_metadata(serde_json::json!({"ioc_id": ioc_id, "feed_version": feed_version}))
+}
+
+/// Create a pattern experiment delta audit event
+///
+/// Only the patterns are recorded; the request ID ties the event to the request.
+pub fn audit_experiment_delta(
+    experiment: &str,
+    arm: &str,
+    control: Option<&str>,
+    candidate: Option<&str>,
+) -> AuditEvent {
+    let describe = |pattern: Option<&str>| match pattern {
+        Some(p) => format!("matched '{}'", p),
+        None => "no match".to_string(),
+    };
+    let mut event = AuditEvent::new(AuditEventType::PatternExperimentDelta)
+        .with_reason(&format!(
+            "Experiment '{}': control {}, candidate {}",
+            experiment,
+            describe(control),
+            describe(candidate)
+        ))
+        .with_metadata(serde_json::json!({
+            "experiment": experiment,
+            "arm": arm,
+            "control_pattern": control,
+            "candidate_pattern": candidate,
+        }));
+    if let Some(p) = candidate.or(control) {
+        event = event.with_pattern(p);
+    }
+    event
+}
+
+/// Create a dry-run config diff audit event
+pub fn audit_config_diff(kind: &str, active: Option<&str>, candidate: Option<&str>) -> AuditEvent {
+    let mut event = AuditEvent::new(AuditEventType::ConfigDiff)
+        .with_reason(&format!("Candidate configuration verdict: {}", kind))
+        .with_metadata(serde_json::json!({
+            "diff": kind,
+            "active_pattern": active,
+            "candidate_pattern": candidate,
+        }));
+    if let Some(p) = candidate.or(active) {
+        event = event.with_pattern(p);
+    }
+    event
+}
+
+/// Create an audit event for a request let through on a bypass token
+pub fn audit_inspection_bypassed(key_id: &str) -> AuditEvent {
+    AuditEvent::new(AuditEventType::InspectionBypassed)
+        .with_reason(&format!("Inspection bypassed with key {}", key_id))
+        .with_metadata(serde_json::json!({ "key_id": key_id }))
+}
+
+/// Create an audit event for a bypass token that was not honored
+pub fn audit_bypass_rejected(rejection: &str, key_id: Option<&str>) -> AuditEvent {
+    let event = AuditEvent::new(AuditEventType::BypassRejected)
+        .with_reason(&format!("Bypass token rejected: {}", rejection));
+    match key_id {
+        Some(id) => event.with_metadata(serde_json::json!({ "key_id": id })),
+        None => event,
+    }
+}
+
+/// Create a STDIO bypass attempt audit event
+pub fn audit_stdio_bypass(description: &str) -> AuditEvent {
+    AuditEvent::new(AuditEventType::StdioBypassAttempt)
+        .with_reason(description)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_event_serialization() {
+        let event = AuditEvent::new(AuditEventType::RequestBlocked)
+            .with_request_id("req-123")
+            .with_reason("prompt injection")
+            .with_pattern("ignore previous");
+
+        let json = serde_json::to_string(&event).unwrap();
+        assert!(json.contains("request_blocked"));
+        assert!(json.contains("ignore previous"));
+    }
+
+    #[test]
+    fn test_audit_blocked() {
+        let event = audit_blocked("prompt injection", Some("jailbreak"));
+        assert!(event.matched_pattern.is_some());
+
+        let event = event
+            .with_metadata(serde_json::json!({"excerpt": "a"}))
+            .with_metadata(serde_json::json!({"excerpt_truncated": false}));
+        let metadata = event.metadata.unwrap();
+        assert_eq!(metadata, serde_json::json!({"excerpt": "a", "excerpt_truncated": false}));
+    }
+
+    #[test]
+    fn test_audit_canary_leak() {
+        let event = audit_canary_leak("canary-2");
+        assert_eq!(event.event_type.as_str(), "canary_leak");
+        assert_eq!(event.matched_pattern.as_deref(), Some("canary-2"));
+        let out = AuditFormat::Ocsf.render(&event).unwrap();
+        assert!(out.contains("\"severity_id\":5"));
+    }
+
+    #[test]
+    fn test_audit_model_override() {
+        let event = audit_model_override("o1-preview", "gpt-4o");
+        assert_eq!(event.event_type.as_str(), "model_override");
+        assert_eq!(event.reason.as_deref(), Some("Model 'o1-preview' rewritten to 'gpt-4o'"));
+        let out = AuditFormat::Ocsf.render(&event).unwrap();
+        assert!(out.contains("\"severity_id\":2"));
+    }
+
+    #[test]
+    fn test_audit_tool_approval() {
+        let event = audit_tool_approval("execute_sql", false, "drops a table");
+        assert_eq!(event.event_type.as_str(), "tool_approval");
+        assert_eq!(event.reason.as_deref(), Some("Tool 'execute_sql' rejected: drops a table"));
+        let out = AuditFormat::Ocsf.render(&event).unwrap();
+        assert!(out.contains("\"severity_id\":2"));
+    }
+
+    #[test]
+    fn test_audit_grpc_error() {
+        let event = audit_grpc_error(7, "permission_denied", Some("agent not allowed"));
+        assert_eq!(event.event_type.as_str(), "grpc_error");
+        assert_eq!(
+            event.reason.as_deref(),
+            Some("gRPC status 7 (permission_denied): agent not allowed")
+        );
+    }
+
+    #[test]
+    fn test_audit_quarantined() {
+        let event = audit_quarantined("Pattern 'act as' detected", Some("act as"));
+        assert_eq!(event.event_type.as_str(), "request_quarantined");
+        assert_eq!(event.matched_pattern.as_deref(), Some("act as"));
+        assert_eq!(VerdictAction::Quarantined.as_str(), "quarantined");
+    }
+
+    #[test]
+    fn test_audit_runtime_controls() {
+        let event = audit_violation_monitored("jailbreak");
+        assert_eq!(event.event_type.as_str(), "violation_monitored");
+        assert_eq!(
+            event.reason.as_deref(),
+            Some("'jailbreak' violation forwarded: enforcement relaxed")
+        );
+        let out = AuditFormat::Ocsf.render(&audit_control_changed("{}")).unwrap();
+        assert!(out.contains("\"severity_id\":3"));
+    }
+
+    #[test]
+    fn test_verdict_properties() {
+        let mut verdict = Verdict::new(VerdictAction::Blocked);
+        verdict.matched_pattern = Some("jailbreak".to_string());
+        verdict.severity = Some("high".to_string());
+        verdict.agent_id = Some("agent-7".to_string());
+        verdict.class = Some("tool_call".to_string());
+
+        let props = verdict.properties();
+        let get = |k: &str| props.iter().find(|(n, _)| n == k).map(|(_, v)| v.as_str());
+
+        assert_eq!(get("ai_guard.action"), Some("blocked"));
+        assert_eq!(get("ai_guard.matched_pattern"), Some("jailbreak"));
+        assert_eq!(get("ai_guard.agent_id"), Some("agent-7"));
+        assert_eq!(get("ai_guard.class"), Some("tool_call"));
+        assert_eq!(get("ai_guard.cost_usd"), None);
+        assert!(get("ai_guard.verdict").unwrap().contains("\"severity\":\"high\""));
+    }
+
+    #[test]
+    fn test_verdict_usage_properties() {
+        let mut verdict = Verdict::new(VerdictAction::Allowed);
+        verdict.model = Some("gpt-4o".to_string());
+        verdict.prompt_tokens = Some(10);
+        verdict.completion_tokens = Some(20);
+        verdict.cost_usd = Some(0.0015);
+
+        let props = verdict.properties();
+        assert!(props.contains(&("ai_guard.model".to_string(), "gpt-4o".to_string())));
+        assert!(props.contains(&("ai_guard.tokens_prompt".to_string(), "10".to_string())));
+        assert!(props.contains(&("ai_guard.cost_usd".to_string(), "0.001500".to_string())));
+    }
+
+    #[test]
+    fn test_verdict_redaction_properties() {
+        let mut verdict = Verdict::new(VerdictAction::Allowed);
+        assert_eq!(verdict.redaction_summary(), None);
+        let counts = [("ssn".to_string(), 1), ("email".to_string(), 2)];
+        verdict.redactions = Some(counts.into_iter().collect());
+
+        let props = verdict.properties();
+        assert!(props.contains(&("ai_guard.redactions".to_string(), "email=2, ssn=1".to_string())));
+        let json = props.iter().find(|(n, _)| n == "ai_guard.verdict").unwrap();
+        assert!(json.1.contains("\"redactions\":{\"email\":2,\"ssn\":1}"));
+    }
+
+    #[test]
+    fn test_event_type_names() {
+        assert_eq!(AuditEventType::RequestBlocked.as_str(), "request_blocked");
+        assert_eq!(
+            serde_json::to_string(&AuditEventType::StdioBypassAttempt).unwrap(),
+            format!("\"{}\"", AuditEventType::StdioBypassAttempt.as_str())
+        );
+    }
+
+    #[test]
+    fn test_audit_pii() {
+        let event = audit_pii("ssn");
+        assert!(event.reason.as_ref().unwrap().contains("ssn"));
+    }
+}
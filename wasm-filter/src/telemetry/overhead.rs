@@ -0,0 +1,179 @@
+//! Guardrail Overhead
+//!
+//! The filter measures its own processing time in every HTTP hook and
+//! exports it as a histogram per phase. The time a request spent in the
+//! filter overall is also kept over a sliding window of recent requests
+//! per worker; when the p99 of that window exceeds the configured budget,
+//! a warning audit event is raised (once, until the p99 falls back under
+//! the budget), so a pattern set that slows the data path is noticed.
+
+use serde::Deserialize;
+use std::collections::VecDeque;
+
+/// Filter hook a measurement belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Request headers
+    RequestHeaders,
+    /// Request body and trailers
+    RequestBody,
+    /// Response headers
+    ResponseHeaders,
+    /// Response body and trailers
+    ResponseBody,
+}
+
+impl Phase {
+    /// Metric name segment
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Phase::RequestHeaders => "request_headers",
+            Phase::RequestBody => "request_body",
+            Phase::ResponseHeaders => "response_headers",
+            Phase::ResponseBody => "response_body",
+        }
+    }
+}
+
+/// Overhead budget configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OverheadBudgetConfig {
+    /// Largest acceptable p99 of the time a request spends in the filter, in µs
+    pub budget_us: u64,
+    /// Recent requests the p99 is computed over, per worker
+    pub window: usize,
+    /// Requests needed in the window before the budget is checked
+    pub min_samples: usize,
+}
+
+impl Default for OverheadBudgetConfig {
+    fn default() -> Self {
+        Self {
+            budget_us: 2_000,
+            window: 500,
+            min_samples: 100,
+        }
+    }
+}
+
+impl OverheadBudgetConfig {
+    /// Validate the configuration, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        if self.budget_us == 0 {
+            diagnostics.push("overhead_budget.budget_us: must be greater than 0".to_string());
+        }
+        if self.window == 0 {
+            diagnostics.push("overhead_budget.window: must be greater than 0".to_string());
+        } else if self.min_samples > self.window {
+            diagnostics.push("overhead_budget.min_samples: must not exceed window".to_string());
+        }
+        diagnostics
+    }
+}
+
+/// Sliding window of per-request overhead on one worker
+#[derive(Debug, Default)]
+pub struct OverheadTracker {
+    /// Overhead of recent requests, in µs (oldest first)
+    samples: VecDeque<u64>,
+    /// The budget alarm was raised and has not cleared yet
+    alarmed: bool,
+}
+
+impl OverheadTracker {
+    /// Empty window
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a request's overhead; returns the p99 when it goes over the budget
+    pub fn observe(&mut self, config: &OverheadBudgetConfig, overhead_us: u64) -> Option<u64> {
+        self.samples.push_back(overhead_us);
+        while self.samples.len() > config.window {
+            self.samples.pop_front();
+        }
+        if self.samples.len() < config.min_samples.max(1) {
+            return None;
+        }
+        let p99 = self.p99()?;
+        if p99 <= config.budget_us {
+            self.alarmed = false;
+            return None;
+        }
+        if self.alarmed {
+            return None;
+        }
+        self.alarmed = true;
+        Some(p99)
+    }
+
+    /// 99th percentile of the window (nearest rank)
+    pub fn p99(&self) -> Option<u64> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let mut sorted: Vec<u64> = self.samples.iter().copied().collect();
+        sorted.sort_unstable();
+        let rank = (sorted.len() * 99).div_ceil(100);
+        sorted.get(rank.saturating_sub(1)).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget() -> OverheadBudgetConfig {
+        OverheadBudgetConfig { budget_us: 1_000, window: 100, min_samples: 10 }
+    }
+
+    #[test]
+    fn test_p99() {
+        let mut tracker = OverheadTracker::new();
+        assert_eq!(tracker.p99(), None);
+        let config = OverheadBudgetConfig { window: 200, ..budget() };
+        for us in 1..=200 {
+            tracker.observe(&config, us);
+        }
+        assert_eq!(tracker.p99(), Some(198));
+        // The window slides
+        for _ in 0..200 {
+            tracker.observe(&config, 5);
+        }
+        assert_eq!(tracker.p99(), Some(5));
+    }
+
+    #[test]
+    fn test_alarm_is_raised_once() {
+        let mut tracker = OverheadTracker::new();
+        let config = budget();
+        // Not checked before min_samples
+        for _ in 0..9 {
+            assert_eq!(tracker.observe(&config, 5_000), None);
+        }
+        assert_eq!(tracker.observe(&config, 5_000), Some(5_000));
+        assert_eq!(tracker.observe(&config, 5_000), None);
+
+        // Back under the budget re-arms the alarm
+        for _ in 0..100 {
+            assert_eq!(tracker.observe(&config, 10), None);
+        }
+        let alarms = (0..5).filter_map(|_| tracker.observe(&config, 3_000)).count();
+        assert_eq!(alarms, 1);
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = OverheadBudgetConfig { budget_us: 0, window: 10, min_samples: 20 };
+        assert_eq!(
+            config.validate(),
+            vec![
+                "overhead_budget.budget_us: must be greater than 0".to_string(),
+                "overhead_budget.min_samples: must not exceed window".to_string(),
+            ]
+        );
+        assert_eq!(Phase::ResponseBody.as_str(), "response_body");
+    }
+}
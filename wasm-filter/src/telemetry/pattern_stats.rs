@@ -0,0 +1,173 @@
+//! Blocked-Pattern Statistics
+//!
+//! Per-pattern hit counters kept in shared data, so all workers add to
+//! the same totals. A periodic summary lists the top-K firing patterns
+//! and the configured patterns that have never fired.
+//!
+//! The counter map is bounded by the number of distinct patterns.
+
+use super::{AuditEvent, AuditEventType};
+use crate::shared::{SharedError, SharedStore};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+/// Shared data key holding the hit counters (JSON object)
+pub const PATTERN_STATS_KEY: &str = "ai_guard.pattern_stats";
+
+/// Shared data key recording when the last summary was emitted
+const SUMMARY_CLAIM_KEY: &str = "ai_guard.pattern_stats.last_summary";
+
+/// Hit counts by pattern
+pub type PatternHits = BTreeMap<String, u64>;
+
+/// Summary of pattern activity
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternSummary {
+    /// Most frequent patterns, highest first
+    pub top: Vec<(String, u64)>,
+    /// Configured patterns with no hits
+    pub unused: Vec<String>,
+    /// Hits across all patterns
+    pub total_hits: u64,
+}
+
+impl PatternSummary {
+    /// Build the summary audit event
+    pub fn to_audit_event(&self) -> AuditEvent {
+        let top: Vec<_> = self
+            .top
+            .iter()
+            .map(|(pattern, hits)| json!({ "pattern": pattern, "hits": hits }))
+            .collect();
+
+        let mut event = AuditEvent::new(AuditEventType::PatternStats).with_reason(&format!(
+            "{} pattern hits, {} unused patterns",
+            self.total_hits,
+            self.unused.len()
+        ));
+        event.metadata = Some(json!({
+            "top": top,
+            "unused": self.unused,
+            "total_hits": self.total_hits,
+        }));
+        event
+    }
+}
+
+/// Count a hit for a pattern, returning its new total
+pub fn record_hit(store: &impl SharedStore, pattern: &str) -> Result<u64, SharedError> {
+    let mut total = 0;
+    store.update(PATTERN_STATS_KEY, |current| {
+        let mut hits = parse(current);
+        let count = hits.entry(pattern.to_string()).or_insert(0);
+        *count += 1;
+        total = *count;
+        serde_json::to_vec(&hits).unwrap_or_default()
+    })?;
+    Ok(total)
+}
+
+/// Load all hit counters
+pub fn load(store: &impl SharedStore) -> PatternHits {
+    parse(store.get(PATTERN_STATS_KEY).0.as_deref())
+}
+
+/// Summarize hits against the configured patterns
+pub fn summarize(hits: &PatternHits, configured: &[String], k: usize) -> PatternSummary {
+    let mut top: Vec<(String, u64)> = hits
+        .iter()
+        .filter(|(_, &n)| n > 0)
+        .map(|(p, &n)| (p.clone(), n))
+        .collect();
+    top.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    top.truncate(k);
+
+    let unused = configured
+        .iter()
+        .filter(|p| hits.get(p.as_str()).copied().unwrap_or(0) == 0)
+        .cloned()
+        .collect();
+
+    PatternSummary {
+        top,
+        unused,
+        total_hits: hits.values().sum(),
+    }
+}
+
+/// Claim the right to emit the next summary
+///
+/// Only one worker wins per interval, so the summary is not duplicated
+/// across workers.
+pub fn claim_summary(store: &impl SharedStore, now_secs: u64, interval_secs: u64) -> bool {
+    let (last, cas) = store.get(SUMMARY_CLAIM_KEY);
+    let last = last
+        .and_then(|v| std::str::from_utf8(&v).ok()?.parse::<u64>().ok())
+        .unwrap_or(0);
+    if now_secs.saturating_sub(last) < interval_secs {
+        return false;
+    }
+    store
+        .set(SUMMARY_CLAIM_KEY, now_secs.to_string().as_bytes(), cas)
+        .is_ok()
+}
+
+fn parse(value: Option<&[u8]>) -> PatternHits {
+    value
+        .and_then(|v| serde_json::from_slice(v).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::MemorySharedStore;
+
+    #[test]
+    fn test_record_hits() {
+        let store = MemorySharedStore::new();
+        assert_eq!(record_hit(&store, "jailbreak").unwrap(), 1);
+        assert_eq!(record_hit(&store, "jailbreak").unwrap(), 2);
+        record_hit(&store, "rm -rf").unwrap();
+
+        let hits = load(&store);
+        assert_eq!(hits.get("jailbreak"), Some(&2));
+        assert_eq!(hits.get("rm -rf"), Some(&1));
+    }
+
+    #[test]
+    fn test_summarize_top_k_and_unused() {
+        let mut hits = PatternHits::new();
+        hits.insert("a".to_string(), 5);
+        hits.insert("b".to_string(), 9);
+        hits.insert("c".to_string(), 1);
+
+        let configured: Vec<String> = ["a", "b", "c", "d"].iter().map(|s| s.to_string()).collect();
+        let summary = summarize(&hits, &configured, 2);
+
+        assert_eq!(summary.top, vec![("b".to_string(), 9), ("a".to_string(), 5)]);
+        assert_eq!(summary.unused, vec!["d".to_string()]);
+        assert_eq!(summary.total_hits, 15);
+    }
+
+    #[test]
+    fn test_summary_event() {
+        let summary = PatternSummary {
+            top: vec![("jailbreak".to_string(), 3)],
+            unused: vec!["drop table".to_string()],
+            total_hits: 3,
+        };
+        let event = summary.to_audit_event();
+        let meta = event.metadata.unwrap();
+        assert_eq!(meta["top"][0]["pattern"], "jailbreak");
+        assert_eq!(meta["unused"][0], "drop table");
+    }
+
+    #[test]
+    fn test_claim_summary_once_per_interval() {
+        let store = MemorySharedStore::new();
+        assert!(claim_summary(&store, 1_000, 300));
+        assert!(!claim_summary(&store, 1_100, 300));
+        assert!(claim_summary(&store, 1_300, 300));
+    }
+}
@@ -0,0 +1,164 @@
+//! Signed Verdict Propagation
+//!
+//! A second Envoy hop, or the application itself, can act on the
+//! guardrail's verdict without scanning the request again, provided it can
+//! trust it. When configured, the verdict goes upstream with the request:
+//!
+//! ```text
+//! x-guardrail-verdict: <key_id>.<unix_secs>.<base64url(verdict JSON)>.<signature>
+//! ```
+//!
+//! The signature is a hex HMAC-SHA256 over
+//! `ai-guard-verdict:<key_id>:<unix_secs>:<request_id>:<payload>`. Binding
+//! the request ID keeps a captured header from vouching for another
+//! request, and receivers reject headers older than `max_age_secs`. Within
+//! this filter chain the same verdict JSON is in filter state, as
+//! `wasm.ai_guard.verdict`.
+//!
+//! A client-supplied header is always removed. Requests passed by the kill
+//! switch carry no verdict: nothing vouches for them.
+
+use crate::crypto::{base64url_decode, base64url_encode, hmac_sha256, to_hex, verify_hmac_hex};
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Request header carrying the signed verdict upstream
+///
+/// Debug mode uses the same name for its explanation on responses.
+pub const FORWARDED_VERDICT_HEADER: &str = "x-guardrail-verdict";
+
+/// Domain separator for verdict signatures
+const SIGNATURE_CONTEXT: &str = "ai-guard-verdict:";
+
+/// Shortest accepted signing key
+const MIN_KEY_LEN: usize = 16;
+
+fn default_max_age_secs() -> u64 {
+    30
+}
+
+/// Verdict propagation configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VerdictPropagationConfig {
+    /// HMAC-SHA256 key shared with the receivers
+    pub key: String,
+    /// Identifies the key to receivers (changes when the key is rotated)
+    pub key_id: String,
+    /// Oldest header a receiver accepts, in seconds
+    #[serde(default = "default_max_age_secs")]
+    pub max_age_secs: u64,
+}
+
+impl VerdictPropagationConfig {
+    /// Validate the configuration, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        if self.key.len() < MIN_KEY_LEN {
+            diagnostics.push(format!(
+                "verdict_propagation.key: must be at least {} bytes",
+                MIN_KEY_LEN
+            ));
+        }
+        if self.key_id.is_empty() || self.key_id.contains('.') {
+            diagnostics
+                .push("verdict_propagation.key_id: must be non-empty without '.'".to_string());
+        }
+        if self.max_age_secs == 0 {
+            diagnostics
+                .push("verdict_propagation.max_age_secs: must be greater than 0".to_string());
+        }
+        diagnostics
+    }
+
+    /// Header value for a verdict (serialized JSON) issued at `now_secs`
+    pub fn sign(&self, verdict_json: &str, request_id: Option<&str>, now_secs: u64) -> String {
+        let payload = base64url_encode(verdict_json.as_bytes());
+        let message = self.message(now_secs, request_id, &payload);
+        let signature = to_hex(&hmac_sha256(self.key.as_bytes(), message.as_bytes()));
+        format!("{}.{}.{}.{}", self.key_id, now_secs, payload, signature)
+    }
+
+    /// Verdict of a header, when it is signed with this key for this
+    /// request and not older than `max_age_secs`
+    pub fn verify(&self, header: &str, request_id: Option<&str>, now_secs: u64) -> Option<Value> {
+        let parts: Vec<&str> = header.trim().split('.').collect();
+        let (key_id, issued, payload, signature) = match parts.as_slice() {
+            [key_id, issued, payload, signature] => (*key_id, *issued, *payload, *signature),
+            _ => return None,
+        };
+        let issued_secs = issued.parse::<u64>().ok()?;
+        if key_id != self.key_id
+            || issued_secs > now_secs
+            || now_secs - issued_secs > self.max_age_secs
+        {
+            return None;
+        }
+        let message = self.message(issued_secs, request_id, payload);
+        if !verify_hmac_hex(self.key.as_bytes(), message.as_bytes(), signature) {
+            return None;
+        }
+        serde_json::from_slice(&base64url_decode(payload)?).ok()
+    }
+
+    fn message(&self, issued_secs: u64, request_id: Option<&str>, payload: &str) -> String {
+        format!(
+            "{}{}:{}:{}:{}",
+            SIGNATURE_CONTEXT,
+            self.key_id,
+            issued_secs,
+            request_id.unwrap_or(""),
+            payload
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> VerdictPropagationConfig {
+        VerdictPropagationConfig {
+            key: "0123456789abcdef".to_string(),
+            key_id: "hop-1".to_string(),
+            max_age_secs: 30,
+        }
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let config = config();
+        let header = config.sign(r#"{"action":"allowed"}"#, Some("req-1"), 1_000);
+        assert!(header.starts_with("hop-1.1000."));
+        let verdict = config.verify(&header, Some("req-1"), 1_030).unwrap();
+        assert_eq!(verdict["action"], "allowed");
+
+        // Stale, replayed onto another request, from the future, or tampered
+        assert!(config.verify(&header, Some("req-1"), 1_031).is_none());
+        assert!(config.verify(&header, Some("req-2"), 1_000).is_none());
+        assert!(config.verify(&header, Some("req-1"), 999).is_none());
+        let blocked = base64url_encode(br#"{"action":"blocked"}"#);
+        let parts: Vec<&str> = header.split('.').collect();
+        let tampered = format!("{}.{}.{}.{}", parts[0], parts[1], blocked, parts[3]);
+        assert!(config.verify(&tampered, Some("req-1"), 1_000).is_none());
+        let rotated = VerdictPropagationConfig { key_id: "hop-2".to_string(), ..config.clone() };
+        assert!(rotated.verify(&header, Some("req-1"), 1_000).is_none());
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = VerdictPropagationConfig {
+            key: "short".to_string(),
+            key_id: String::new(),
+            max_age_secs: 0,
+        };
+        assert_eq!(
+            config.validate(),
+            vec![
+                "verdict_propagation.key: must be at least 16 bytes".to_string(),
+                "verdict_propagation.key_id: must be non-empty without '.'".to_string(),
+                "verdict_propagation.max_age_secs: must be greater than 0".to_string(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,271 @@
+//! Audit Event Shipper
+//!
+//! Buffers rendered audit events per worker and hands out batches
+//! for delivery to an external collector (SIEM). The root context drives
+//! it from `on_tick` and reports HTTP callout results back.
+//!
+//! Memory is bounded by `max_queue`: when full, the oldest events are
+//! dropped. Failed batches are retried on later ticks up to
+//! `max_attempts`, then dropped.
+
+use super::AuditFormat;
+use crate::config::AuditSinkConfig;
+use std::collections::{HashMap, VecDeque};
+
+/// A batch of rendered audit events
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditBatch {
+    /// Rendered events (one per entry)
+    pub events: Vec<String>,
+    /// Delivery attempts so far
+    pub attempts: u32,
+}
+
+impl AuditBatch {
+    /// Request body in the given format
+    pub fn body(&self, format: AuditFormat) -> String {
+        format.batch_body(&self.events)
+    }
+
+    /// Number of events in the batch
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Check if the batch is empty
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+/// Result of a delivery attempt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShipOutcome {
+    /// Batch accepted by the collector (event count)
+    Delivered(usize),
+    /// Batch will be retried (event count)
+    Retrying(usize),
+    /// Batch exhausted its attempts (event count)
+    Dropped(usize),
+    /// Callout token not recognised
+    Unknown,
+}
+
+/// Batched audit event shipper
+#[derive(Debug)]
+pub struct AuditShipper {
+    batch_size: usize,
+    flush_interval_ms: u64,
+    max_queue: usize,
+    max_attempts: u32,
+    /// Events waiting to be batched
+    queue: VecDeque<String>,
+    /// When the oldest queued event was enqueued (ms)
+    oldest_ms: Option<u64>,
+    /// Failed batches awaiting another attempt
+    retry: VecDeque<AuditBatch>,
+    /// Batches in flight, by callout token
+    in_flight: HashMap<u32, AuditBatch>,
+    /// Delivery attempts failed since the last success
+    failures: u32,
+}
+
+impl AuditShipper {
+    /// Create a shipper from sink configuration
+    pub fn new(config: &AuditSinkConfig) -> Self {
+        Self {
+            batch_size: config.batch_size.max(1),
+            flush_interval_ms: config.flush_interval_ms,
+            max_queue: config.max_queue.max(1),
+            max_attempts: config.max_attempts.max(1),
+            queue: VecDeque::new(),
+            oldest_ms: None,
+            retry: VecDeque::new(),
+            in_flight: HashMap::new(),
+            failures: 0,
+        }
+    }
+
+    /// Queue a serialized event, returning how many old events were dropped
+    pub fn enqueue(&mut self, event: String, now_ms: u64) -> usize {
+        let mut dropped = 0;
+        while self.queue.len() >= self.max_queue {
+            self.queue.pop_front();
+            dropped += 1;
+        }
+        if self.queue.is_empty() {
+            self.oldest_ms = Some(now_ms);
+        }
+        self.queue.push_back(event);
+        dropped
+    }
+
+    /// Take the next batch that is due for delivery
+    ///
+    /// Retries go first. New events are batched once a full batch is
+    /// queued or the oldest event has waited `flush_interval_ms`.
+    pub fn take_batch(&mut self, now_ms: u64) -> Option<AuditBatch> {
+        if let Some(batch) = self.retry.pop_front() {
+            return Some(batch);
+        }
+        if self.queue.is_empty() {
+            return None;
+        }
+
+        let waited = now_ms.saturating_sub(self.oldest_ms.unwrap_or(now_ms));
+        if self.queue.len() < self.batch_size && waited < self.flush_interval_ms {
+            return None;
+        }
+
+        let take = self.queue.len().min(self.batch_size);
+        let events: Vec<String> = self.queue.drain(..take).collect();
+        self.oldest_ms = if self.queue.is_empty() { None } else { Some(now_ms) };
+
+        Some(AuditBatch {
+            events,
+            attempts: 0,
+        })
+    }
+
+    /// Record a batch as dispatched under a callout token
+    pub fn sent(&mut self, token: u32, mut batch: AuditBatch) {
+        batch.attempts += 1;
+        self.in_flight.insert(token, batch);
+    }
+
+    /// Record a batch that could not be dispatched at all
+    pub fn send_failed(&mut self, mut batch: AuditBatch) -> ShipOutcome {
+        batch.attempts += 1;
+        self.retry_or_drop(batch)
+    }
+
+    /// Handle the collector's response for a callout
+    ///
+    /// `status` is None when the callout failed without a response
+    /// (timeout, connection reset).
+    pub fn on_response(&mut self, token: u32, status: Option<u16>) -> ShipOutcome {
+        let batch = match self.in_flight.remove(&token) {
+            Some(batch) => batch,
+            None => return ShipOutcome::Unknown,
+        };
+        match status {
+            Some(code) if (200..300).contains(&code) => {
+                self.failures = 0;
+                ShipOutcome::Delivered(batch.len())
+            }
+            _ => self.retry_or_drop(batch),
+        }
+    }
+
+    /// Events waiting to be batched
+    pub fn queued(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Batches currently in flight
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Whether the collector is taking events
+    ///
+    /// Unhealthy once a batch's worth of attempts failed in a row, or when
+    /// the queue is full and events are being dropped.
+    pub fn is_healthy(&self) -> bool {
+        self.failures < self.max_attempts && self.queue.len() < self.max_queue
+    }
+
+    /// Delivery attempts failed since the last success
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    fn retry_or_drop(&mut self, batch: AuditBatch) -> ShipOutcome {
+        self.failures += 1;
+        if batch.attempts >= self.max_attempts {
+            return ShipOutcome::Dropped(batch.len());
+        }
+        let count = batch.len();
+        self.retry.push_back(batch);
+        ShipOutcome::Retrying(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sink_config() -> AuditSinkConfig {
+        serde_json::from_str(
+            r#"{"cluster": "siem", "batch_size": 2, "flush_interval_ms": 1000, "max_queue": 3, "max_attempts": 2}"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_flush_at_batch_size() {
+        let mut shipper = AuditShipper::new(&sink_config());
+        shipper.enqueue("{\"a\":1}".to_string(), 0);
+        assert!(shipper.take_batch(10).is_none());
+
+        shipper.enqueue("{\"a\":2}".to_string(), 5);
+        let batch = shipper.take_batch(10).unwrap();
+        assert_eq!(batch.len(), 2);
+        assert_eq!(batch.body(AuditFormat::Json), "[{\"a\":1},{\"a\":2}]");
+        assert_eq!(shipper.queued(), 0);
+    }
+
+    #[test]
+    fn test_flush_after_interval() {
+        let mut shipper = AuditShipper::new(&sink_config());
+        shipper.enqueue("{}".to_string(), 0);
+
+        assert!(shipper.take_batch(999).is_none());
+        assert_eq!(shipper.take_batch(1000).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_queue_drops_oldest() {
+        let mut shipper = AuditShipper::new(&sink_config());
+        for i in 0..3 {
+            assert_eq!(shipper.enqueue(i.to_string(), 0), 0);
+        }
+        assert_eq!(shipper.enqueue("3".to_string(), 0), 1);
+
+        let batch = shipper.take_batch(0).unwrap();
+        assert_eq!(batch.events, vec!["1", "2"]);
+    }
+
+    #[test]
+    fn test_delivered() {
+        let mut shipper = AuditShipper::new(&sink_config());
+        shipper.enqueue("{}".to_string(), 0);
+        let batch = shipper.take_batch(1000).unwrap();
+
+        shipper.sent(7, batch);
+        assert_eq!(shipper.in_flight(), 1);
+        assert_eq!(shipper.on_response(7, Some(204)), ShipOutcome::Delivered(1));
+        assert_eq!(shipper.on_response(7, Some(204)), ShipOutcome::Unknown);
+    }
+
+    #[test]
+    fn test_retry_then_drop() {
+        let mut shipper = AuditShipper::new(&sink_config());
+        shipper.enqueue("{}".to_string(), 0);
+        let batch = shipper.take_batch(1000).unwrap();
+
+        shipper.sent(1, batch);
+        assert_eq!(shipper.on_response(1, Some(503)), ShipOutcome::Retrying(1));
+        assert!(shipper.is_healthy());
+
+        // Retries are handed out before new events, without waiting
+        let retry = shipper.take_batch(1000).unwrap();
+        assert_eq!(retry.attempts, 1);
+
+        shipper.sent(2, retry);
+        assert_eq!(shipper.on_response(2, None), ShipOutcome::Dropped(1));
+        assert!(shipper.take_batch(5000).is_none());
+        assert_eq!(shipper.failures(), 2);
+        assert!(!shipper.is_healthy());
+    }
+}
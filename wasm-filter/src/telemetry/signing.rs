@@ -0,0 +1,121 @@
+//! Audit Event Signing
+//!
+//! Makes audit records tamper-evident. Each event gets the worker's next
+//! sequence number (gaps reveal deleted records) and the ID of the signing
+//! key, then an HMAC-SHA256 over the event in canonical form: the native
+//! JSON event without `signature`, keys sorted, no whitespace.
+//!
+//! The key ID lets collectors pick the right key while keys are rotated.
+//! OCSF and CEF output carry the same fields, but only the `json` format
+//! holds everything needed to recompute the signature.
+
+use super::AuditEvent;
+use crate::crypto::{hmac_sha256, to_hex, verify_hmac_hex};
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Shortest accepted signing key
+const MIN_KEY_LEN: usize = 16;
+
+/// Audit signing configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuditSigningConfig {
+    /// HMAC-SHA256 key
+    pub key: String,
+    /// Identifies the key to collectors (changes when the key is rotated)
+    pub key_id: String,
+}
+
+impl AuditSigningConfig {
+    /// Validate the configuration, returning human-readable problems
+    pub fn validate(&self) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        if self.key.len() < MIN_KEY_LEN {
+            diagnostics.push(format!("audit_signing.key: must be at least {} bytes", MIN_KEY_LEN));
+        }
+        if self.key_id.is_empty() {
+            diagnostics.push("audit_signing.key_id: must not be empty".to_string());
+        }
+        diagnostics
+    }
+
+    /// Stamp an event with its sequence number and key ID, and sign it
+    pub fn sign(&self, mut event: AuditEvent, sequence: u64) -> AuditEvent {
+        event.sequence = Some(sequence);
+        event.key_id = Some(self.key_id.clone());
+        event.signature = None;
+        let payload = canonical(serde_json::to_value(&event).unwrap_or_default());
+        event.signature = Some(to_hex(&hmac_sha256(self.key.as_bytes(), payload.as_bytes())));
+        event
+    }
+
+    /// Check the signature of a serialized (`json` format) event
+    pub fn verify(&self, event_json: &str) -> bool {
+        let mut event: Value = match serde_json::from_str(event_json) {
+            Ok(event) => event,
+            Err(_) => return false,
+        };
+        let signature = match event.as_object_mut().and_then(|e| e.remove("signature")) {
+            Some(Value::String(signature)) => signature,
+            _ => return false,
+        };
+        if event["key_id"] != self.key_id.as_str() {
+            return false;
+        }
+        verify_hmac_hex(self.key.as_bytes(), canonical(event).as_bytes(), &signature)
+    }
+}
+
+/// Compact JSON with sorted keys (serde_json maps are ordered by key)
+fn canonical(event: Value) -> String {
+    event.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::audit_blocked;
+
+    fn signing() -> AuditSigningConfig {
+        AuditSigningConfig {
+            key: "0123456789abcdef".to_string(),
+            key_id: "2026-10".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_signed_event_verifies() {
+        let event = audit_blocked("prompt injection", Some("jailbreak"))
+            .with_metadata(serde_json::json!({"excerpt": "ignore previous"}));
+        let event = signing().sign(event, 42);
+        assert_eq!(event.sequence, Some(42));
+        assert_eq!(event.key_id.as_deref(), Some("2026-10"));
+
+        let json = event.to_json().unwrap();
+        assert!(signing().verify(&json));
+        let rotated = AuditSigningConfig { key_id: "2026-11".to_string(), ..signing() };
+        assert!(!rotated.verify(&json));
+    }
+
+    #[test]
+    fn test_tampering_is_detected() {
+        let json = signing().sign(audit_blocked("jailbreak", None), 1).to_json().unwrap();
+        assert!(!signing().verify(&json.replace("jailbreak", "allowed")));
+        assert!(!signing().verify(&json.replace("\"sequence\":1", "\"sequence\":2")));
+        let unsigned = audit_blocked("jailbreak", None).to_json().unwrap();
+        assert!(!signing().verify(&unsigned));
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = AuditSigningConfig { key: "short".to_string(), key_id: String::new() };
+        assert_eq!(
+            config.validate(),
+            vec![
+                "audit_signing.key: must be at least 16 bytes".to_string(),
+                "audit_signing.key_id: must not be empty".to_string(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,701 @@
+//! Simulated proxy-wasm Host ABI
+//!
+//! Natively, the `proxy_*` functions the SDK imports from Envoy are defined
+//! here instead, backed by a per-thread `HostState`. Each call works on the
+//! HTTP stream of the current context, as Envoy does; host calls the filter
+//! cannot make in the harness (gRPC, shared queues, foreign functions) fail
+//! with `InternalFailure`.
+//!
+//! The `unsafe` functions are only called by the SDK, with the pointers the
+//! ABI specifies.
+#![allow(clippy::missing_safety_doc)]
+
+use proxy_wasm::types::{BufferType, LogLevel, MapType, MetricType, Status, StreamType};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ptr::null_mut;
+
+/// Host clock at the start of every harness (2026-01-01T00:00:00Z)
+pub const START_TIME_NS: u64 = 1_767_225_600_000_000_000;
+
+thread_local! {
+    static HOST: RefCell<HostState> = RefCell::new(HostState::default());
+}
+
+/// Run a closure against this thread's host
+pub fn with_host<R>(f: impl FnOnce(&mut HostState) -> R) -> R {
+    HOST.with(|h| f(&mut h.borrow_mut()))
+}
+
+/// Header map, in order
+pub type Headers = Vec<(String, String)>;
+
+/// Response sent with `send_http_response`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LocalResponse {
+    pub status: u32,
+    pub headers: Headers,
+    pub body: Vec<u8>,
+}
+
+/// Callout made with `dispatch_http_call`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HttpCall {
+    pub token: u32,
+    pub upstream: String,
+    pub headers: Headers,
+    pub body: Vec<u8>,
+    pub timeout_ms: u32,
+}
+
+/// One direction of an HTTP stream
+#[derive(Clone, Debug, Default)]
+pub struct Direction {
+    /// Headers as the filter left them
+    pub headers: Headers,
+    pub trailers: Headers,
+    /// Body data the filter has not released yet
+    pub buffered: Vec<u8>,
+    /// Whether the headers went on (upstream for requests)
+    pub headers_sent: bool,
+    /// Body data that went on
+    pub sent: Vec<u8>,
+}
+
+impl Direction {
+    /// Let headers and buffered data through
+    pub fn release(&mut self) {
+        self.headers_sent = true;
+        self.sent.append(&mut self.buffered);
+    }
+}
+
+/// Host side of one HTTP stream
+#[derive(Clone, Debug, Default)]
+pub struct Stream {
+    pub request: Direction,
+    pub response: Direction,
+    pub local_response: Option<LocalResponse>,
+    /// Properties set by the filter on this stream
+    pub properties: HashMap<String, Vec<u8>>,
+    /// The filter reset the response stream
+    pub response_reset: bool,
+}
+
+/// Everything the host holds for one thread
+#[derive(Debug, Default)]
+pub struct HostState {
+    pub now_ns: u64,
+    /// Context the next host call applies to
+    pub current: u32,
+    pub logs: Vec<(LogLevel, String)>,
+    pub plugin_configuration: Option<Vec<u8>>,
+    pub tick_period_ms: u32,
+    /// Properties provided by the host (`source.address`, ...)
+    pub properties: HashMap<String, Vec<u8>>,
+    pub streams: HashMap<u32, Stream>,
+    /// Value and CAS token by key
+    pub shared_data: HashMap<String, (Vec<u8>, u32)>,
+    /// Name, type and value by metric ID - 1
+    pub metrics: Vec<(String, MetricType, i64)>,
+    pub http_calls: Vec<HttpCall>,
+    /// Response to the callout being delivered
+    pub http_call_response: Option<(Headers, Vec<u8>)>,
+}
+
+impl HostState {
+    /// Start over, keeping metric IDs (the filter caches them per thread)
+    pub fn reset(&mut self) {
+        let mut metrics = std::mem::take(&mut self.metrics);
+        for metric in &mut metrics {
+            metric.2 = 0;
+        }
+        *self = HostState { now_ns: START_TIME_NS, metrics, ..HostState::default() };
+    }
+
+    /// Stream of the current context
+    pub fn stream(&mut self) -> &mut Stream {
+        self.streams.entry(self.current).or_default()
+    }
+
+    /// Value of a metric by full name
+    pub fn metric(&self, name: &str) -> Option<i64> {
+        self.metrics.iter().find(|m| m.0 == name).map(|m| m.2)
+    }
+
+    fn map(&mut self, map_type: MapType) -> Option<&mut Headers> {
+        match map_type {
+            MapType::HttpRequestHeaders => Some(&mut self.stream().request.headers),
+            MapType::HttpRequestTrailers => Some(&mut self.stream().request.trailers),
+            MapType::HttpResponseHeaders => Some(&mut self.stream().response.headers),
+            MapType::HttpResponseTrailers => Some(&mut self.stream().response.trailers),
+            MapType::HttpCallResponseHeaders => self.http_call_response.as_mut().map(|r| &mut r.0),
+            _ => None,
+        }
+    }
+
+    fn buffer(&mut self, buffer_type: BufferType) -> Option<&mut Vec<u8>> {
+        match buffer_type {
+            BufferType::HttpRequestBody => Some(&mut self.stream().request.buffered),
+            BufferType::HttpResponseBody => Some(&mut self.stream().response.buffered),
+            BufferType::HttpCallResponseBody => self.http_call_response.as_mut().map(|r| &mut r.1),
+            BufferType::PluginConfiguration => self.plugin_configuration.as_mut(),
+            _ => None,
+        }
+    }
+
+    fn property(&mut self, path: &str) -> Option<Vec<u8>> {
+        match self.stream().properties.get(path) {
+            Some(value) => Some(value.clone()),
+            None => self.properties.get(path).cloned(),
+        }
+    }
+}
+
+/// Serialize a header map the way the host passes it to the SDK
+pub fn serialize_map(map: &[(String, String)]) -> Vec<u8> {
+    let mut bytes = (map.len() as u32).to_le_bytes().to_vec();
+    for (name, value) in map {
+        bytes.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    }
+    for (name, value) in map {
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.push(0);
+        bytes.extend_from_slice(value.as_bytes());
+        bytes.push(0);
+    }
+    bytes
+}
+
+/// Parse a header map serialized by the SDK
+pub fn deserialize_map(bytes: &[u8]) -> Headers {
+    let word = |at: usize| -> usize {
+        bytes.get(at..at + 4).map_or(0, |w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]) as usize)
+    };
+    let count = word(0);
+    let mut at = 4 + count * 8;
+    let mut text = |len: usize| {
+        let value = String::from_utf8_lossy(&bytes[at..at + len]).into_owned();
+        at += len + 1;
+        value
+    };
+    (0..count)
+        .map(|n| {
+            let (name_len, value_len) = (word(4 + n * 8), word(8 + n * 8));
+            (text(name_len), text(value_len))
+        })
+        .collect()
+}
+
+/// Bytes passed in by the SDK
+unsafe fn input<'a>(data: *const u8, size: usize) -> &'a [u8] {
+    if data.is_null() || size == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(data, size)
+    }
+}
+
+unsafe fn input_str(data: *const u8, size: usize) -> String {
+    String::from_utf8_lossy(input(data, size)).into_owned()
+}
+
+/// Hand bytes to the SDK, which takes ownership (`Vec::from_raw_parts`)
+unsafe fn output(bytes: &[u8], data: *mut *mut u8, size: *mut usize) {
+    if bytes.is_empty() {
+        *data = null_mut();
+        *size = 0;
+    } else {
+        let boxed: Box<[u8]> = bytes.into();
+        *size = boxed.len();
+        *data = Box::into_raw(boxed) as *mut u8;
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn proxy_log(level: LogLevel, data: *const u8, size: usize) -> Status {
+    let message = input_str(data, size);
+    if level == LogLevel::Critical {
+        // The SDK's panic hook logs here: keep test failures readable
+        eprintln!("{}", message);
+    }
+    // Not `with_host`: a panic while the host is borrowed is logged too
+    HOST.with(|h| {
+        if let Ok(mut host) = h.try_borrow_mut() {
+            host.logs.push((level, message));
+        }
+    });
+    Status::Ok
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn proxy_get_log_level(level: *mut LogLevel) -> Status {
+    *level = LogLevel::Trace;
+    Status::Ok
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn proxy_get_current_time_nanoseconds(time: *mut u64) -> Status {
+    *time = with_host(|h| h.now_ns);
+    Status::Ok
+}
+
+#[no_mangle]
+pub extern "C" fn proxy_set_tick_period_milliseconds(period: u32) -> Status {
+    with_host(|h| h.tick_period_ms = period);
+    Status::Ok
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn proxy_get_buffer_bytes(
+    buffer_type: BufferType,
+    start: usize,
+    max_size: usize,
+    data: *mut *mut u8,
+    size: *mut usize,
+) -> Status {
+    with_host(|h| match h.buffer(buffer_type) {
+        Some(buffer) => {
+            let start = start.min(buffer.len());
+            let end = start.saturating_add(max_size).min(buffer.len());
+            output(&buffer[start..end], data, size);
+            Status::Ok
+        }
+        None => Status::NotFound,
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn proxy_set_buffer_bytes(
+    buffer_type: BufferType,
+    start: usize,
+    size: usize,
+    data: *const u8,
+    data_size: usize,
+) -> Status {
+    let value = input(data, data_size);
+    with_host(|h| match h.buffer(buffer_type) {
+        Some(buffer) => {
+            let start = start.min(buffer.len());
+            let end = start.saturating_add(size).min(buffer.len());
+            buffer.splice(start..end, value.iter().copied());
+            Status::Ok
+        }
+        None => Status::NotFound,
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn proxy_get_header_map_pairs(
+    map_type: MapType,
+    data: *mut *mut u8,
+    size: *mut usize,
+) -> Status {
+    let map = with_host(|h| h.map(map_type).cloned()).unwrap_or_default();
+    output(&serialize_map(&map), data, size);
+    Status::Ok
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn proxy_set_header_map_pairs(
+    map_type: MapType,
+    data: *const u8,
+    size: usize,
+) -> Status {
+    let pairs = deserialize_map(input(data, size));
+    with_host(|h| match h.map(map_type) {
+        Some(map) => {
+            *map = pairs;
+            Status::Ok
+        }
+        None => Status::NotFound,
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn proxy_get_header_map_value(
+    map_type: MapType,
+    key_data: *const u8,
+    key_size: usize,
+    data: *mut *mut u8,
+    size: *mut usize,
+) -> Status {
+    let key = input_str(key_data, key_size).to_ascii_lowercase();
+    let value = with_host(|h| {
+        let map = h.map(map_type)?;
+        map.iter().find(|(name, _)| name.eq_ignore_ascii_case(&key)).map(|(_, v)| v.clone())
+    });
+    match value {
+        Some(value) => {
+            output(value.as_bytes(), data, size);
+            Status::Ok
+        }
+        None => Status::NotFound,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn proxy_remove_header_map_value(
+    map_type: MapType,
+    key_data: *const u8,
+    key_size: usize,
+) -> Status {
+    let key = input_str(key_data, key_size);
+    with_host(|h| {
+        if let Some(map) = h.map(map_type) {
+            map.retain(|(name, _)| !name.eq_ignore_ascii_case(&key));
+        }
+    });
+    Status::Ok
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn proxy_replace_header_map_value(
+    map_type: MapType,
+    key_data: *const u8,
+    key_size: usize,
+    value_data: *const u8,
+    value_size: usize,
+) -> Status {
+    let key = input_str(key_data, key_size).to_ascii_lowercase();
+    let value = input_str(value_data, value_size);
+    with_host(|h| match h.map(map_type) {
+        Some(map) => {
+            map.retain(|(name, _)| !name.eq_ignore_ascii_case(&key));
+            map.push((key, value));
+            Status::Ok
+        }
+        None => Status::NotFound,
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn proxy_add_header_map_value(
+    map_type: MapType,
+    key_data: *const u8,
+    key_size: usize,
+    value_data: *const u8,
+    value_size: usize,
+) -> Status {
+    let key = input_str(key_data, key_size).to_ascii_lowercase();
+    let value = input_str(value_data, value_size);
+    with_host(|h| match h.map(map_type) {
+        Some(map) => {
+            map.push((key, value));
+            Status::Ok
+        }
+        None => Status::NotFound,
+    })
+}
+
+/// Property path as a dotted name (`source.address`)
+unsafe fn property_path(data: *const u8, size: usize) -> String {
+    input_str(data, size).replace('\0', ".")
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn proxy_get_property(
+    path_data: *const u8,
+    path_size: usize,
+    data: *mut *mut u8,
+    size: *mut usize,
+) -> Status {
+    let path = property_path(path_data, path_size);
+    match with_host(|h| h.property(&path)) {
+        Some(value) => {
+            output(&value, data, size);
+            Status::Ok
+        }
+        None => Status::NotFound,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn proxy_set_property(
+    path_data: *const u8,
+    path_size: usize,
+    value_data: *const u8,
+    value_size: usize,
+) -> Status {
+    let path = property_path(path_data, path_size);
+    let value = input(value_data, value_size).to_vec();
+    with_host(|h| h.stream().properties.insert(path, value));
+    Status::Ok
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn proxy_get_shared_data(
+    key_data: *const u8,
+    key_size: usize,
+    data: *mut *mut u8,
+    size: *mut usize,
+    cas: *mut u32,
+) -> Status {
+    let key = input_str(key_data, key_size);
+    match with_host(|h| h.shared_data.get(&key).cloned()) {
+        Some((value, token)) => {
+            output(&value, data, size);
+            *cas = token;
+            Status::Ok
+        }
+        None => Status::NotFound,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn proxy_set_shared_data(
+    key_data: *const u8,
+    key_size: usize,
+    value_data: *const u8,
+    value_size: usize,
+    cas: u32,
+) -> Status {
+    let key = input_str(key_data, key_size);
+    let value = input(value_data, value_size).to_vec();
+    with_host(|h| {
+        let token = h.shared_data.get(&key).map_or(0, |(_, token)| *token);
+        if cas != 0 && cas != token {
+            return Status::CasMismatch;
+        }
+        h.shared_data.insert(key, (value, token + 1));
+        Status::Ok
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn proxy_register_shared_queue(_: *const u8, _: usize, _: *mut u32) -> Status {
+    Status::InternalFailure
+}
+
+#[no_mangle]
+pub extern "C" fn proxy_resolve_shared_queue(
+    _: *const u8,
+    _: usize,
+    _: *const u8,
+    _: usize,
+    _: *mut u32,
+) -> Status {
+    Status::InternalFailure
+}
+
+#[no_mangle]
+pub extern "C" fn proxy_dequeue_shared_queue(_: u32, _: *mut *mut u8, _: *mut usize) -> Status {
+    Status::InternalFailure
+}
+
+#[no_mangle]
+pub extern "C" fn proxy_enqueue_shared_queue(_: u32, _: *const u8, _: usize) -> Status {
+    Status::InternalFailure
+}
+
+#[no_mangle]
+pub extern "C" fn proxy_continue_stream(stream_type: StreamType) -> Status {
+    with_host(|h| match stream_type {
+        StreamType::HttpRequest => h.stream().request.release(),
+        StreamType::HttpResponse => h.stream().response.release(),
+        _ => {}
+    });
+    Status::Ok
+}
+
+#[no_mangle]
+pub extern "C" fn proxy_close_stream(stream_type: StreamType) -> Status {
+    if stream_type == StreamType::HttpResponse {
+        with_host(|h| h.stream().response_reset = true);
+    }
+    Status::Ok
+}
+
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn proxy_send_local_response(
+    status: u32,
+    _details_data: *const u8,
+    _details_size: usize,
+    body_data: *const u8,
+    body_size: usize,
+    headers_data: *const u8,
+    headers_size: usize,
+    _grpc_status: i32,
+) -> Status {
+    let response = LocalResponse {
+        status,
+        headers: deserialize_map(input(headers_data, headers_size)),
+        body: input(body_data, body_size).to_vec(),
+    };
+    with_host(|h| h.stream().local_response = Some(response));
+    Status::Ok
+}
+
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn proxy_http_call(
+    upstream_data: *const u8,
+    upstream_size: usize,
+    headers_data: *const u8,
+    headers_size: usize,
+    body_data: *const u8,
+    body_size: usize,
+    _trailers_data: *const u8,
+    _trailers_size: usize,
+    timeout: u32,
+    token: *mut u32,
+) -> Status {
+    let call = HttpCall {
+        token: 0,
+        upstream: input_str(upstream_data, upstream_size),
+        headers: deserialize_map(input(headers_data, headers_size)),
+        body: input(body_data, body_size).to_vec(),
+        timeout_ms: timeout,
+    };
+    *token = with_host(|h| {
+        let token = h.http_calls.len() as u32 + 1;
+        h.http_calls.push(HttpCall { token, ..call });
+        token
+    });
+    Status::Ok
+}
+
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn proxy_grpc_call(
+    _: *const u8,
+    _: usize,
+    _: *const u8,
+    _: usize,
+    _: *const u8,
+    _: usize,
+    _: *const u8,
+    _: usize,
+    _: *const u8,
+    _: usize,
+    _: u32,
+    _: *mut u32,
+) -> Status {
+    Status::InternalFailure
+}
+
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn proxy_grpc_stream(
+    _: *const u8,
+    _: usize,
+    _: *const u8,
+    _: usize,
+    _: *const u8,
+    _: usize,
+    _: *const u8,
+    _: usize,
+    _: *mut u32,
+) -> Status {
+    Status::InternalFailure
+}
+
+#[no_mangle]
+pub extern "C" fn proxy_grpc_send(_: u32, _: *const u8, _: usize, _: bool) -> Status {
+    Status::InternalFailure
+}
+
+#[no_mangle]
+pub extern "C" fn proxy_grpc_cancel(_: u32) -> Status {
+    Status::InternalFailure
+}
+
+#[no_mangle]
+pub extern "C" fn proxy_grpc_close(_: u32) -> Status {
+    Status::InternalFailure
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn proxy_get_status(
+    code: *mut u32,
+    data: *mut *mut u8,
+    size: *mut usize,
+) -> Status {
+    *code = 0;
+    output(&[], data, size);
+    Status::Ok
+}
+
+#[no_mangle]
+pub extern "C" fn proxy_set_effective_context(context_id: u32) -> Status {
+    with_host(|h| h.current = context_id);
+    Status::Ok
+}
+
+#[no_mangle]
+pub extern "C" fn proxy_call_foreign_function(
+    _: *const u8,
+    _: usize,
+    _: *const u8,
+    _: usize,
+    _: *mut *mut u8,
+    _: *mut usize,
+) -> Status {
+    Status::InternalFailure
+}
+
+#[no_mangle]
+pub extern "C" fn proxy_done() -> Status {
+    Status::Ok
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn proxy_define_metric(
+    metric_type: MetricType,
+    name_data: *const u8,
+    name_size: usize,
+    id: *mut u32,
+) -> Status {
+    let name = input_str(name_data, name_size);
+    *id = with_host(|h| {
+        let index = match h.metrics.iter().position(|m| m.0 == name) {
+            Some(index) => index,
+            None => {
+                h.metrics.push((name, metric_type, 0));
+                h.metrics.len() - 1
+            }
+        };
+        index as u32 + 1
+    });
+    Status::Ok
+}
+
+/// Index of a metric ID (IDs start at 1; 0 matches nothing)
+fn metric_index(id: u32) -> usize {
+    (id as usize).wrapping_sub(1)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn proxy_get_metric(id: u32, value: *mut u64) -> Status {
+    match with_host(|h| h.metrics.get(metric_index(id)).map(|m| m.2)) {
+        Some(current) => {
+            *value = current as u64;
+            Status::Ok
+        }
+        None => Status::NotFound,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn proxy_record_metric(id: u32, value: u64) -> Status {
+    with_host(|h| match h.metrics.get_mut(metric_index(id)) {
+        Some(metric) => {
+            metric.2 = value as i64;
+            Status::Ok
+        }
+        None => Status::NotFound,
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn proxy_increment_metric(id: u32, offset: i64) -> Status {
+    with_host(|h| match h.metrics.get_mut(metric_index(id)) {
+        Some(metric) => {
+            metric.2 += offset;
+            Status::Ok
+        }
+        None => Status::NotFound,
+    })
+}
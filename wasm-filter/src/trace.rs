@@ -0,0 +1,400 @@
+//! Trace Module for AI-Guard
+//!
+//! Records OpenTelemetry spans for guardrail stages (body scanning,
+//! protocol validation, PII scanning, rate limiting) as children of the
+//! span in the incoming W3C `traceparent` header, and encodes them as
+//! OTLP/HTTP JSON for export. Requests queue their spans when they end;
+//! the root context exports the queue on its tick.
+//!
+//! Wasm has no RNG, so span IDs are derived from the trace ID, the
+//! context ID and a per-request counter.
+
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+
+/// W3C trace-context header
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// Instrumentation scope name
+const SCOPE_NAME: &str = "ai-guard";
+
+/// OTLP span kind: internal
+const SPAN_KIND_INTERNAL: u8 = 1;
+
+/// Spans queued per worker; the oldest are dropped beyond this
+const MAX_QUEUED_SPANS: usize = 1024;
+
+/// Most spans sent in one export
+const EXPORT_BATCH: usize = 256;
+
+/// Guardrail stage covered by a span
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Streaming body scan
+    BodyScan,
+    /// MCP / A2A protocol validation
+    ProtocolValidation,
+    /// PII detection
+    PiiScan,
+    /// Rate limit check
+    RateLimit,
+}
+
+impl Stage {
+    /// Span name for this stage
+    pub fn span_name(&self) -> &'static str {
+        match self {
+            Stage::BodyScan => "ai_guard.body_scan",
+            Stage::ProtocolValidation => "ai_guard.protocol_validation",
+            Stage::PiiScan => "ai_guard.pii_scan",
+            Stage::RateLimit => "ai_guard.rate_limit",
+        }
+    }
+}
+
+/// Parsed W3C trace context
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    /// 16-byte trace ID (hex)
+    pub trace_id: String,
+    /// 8-byte parent span ID (hex)
+    pub parent_span_id: String,
+    /// Sampled flag
+    pub sampled: bool,
+}
+
+impl TraceContext {
+    /// Parse a `traceparent` header value (`00-<trace>-<span>-<flags>`)
+    pub fn parse(header: &str) -> Option<Self> {
+        let mut parts = header.trim().split('-');
+        let version = parts.next()?;
+        let trace_id = parts.next()?;
+        let span_id = parts.next()?;
+        let flags = parts.next()?;
+
+        if version.len() != 2 || version == "ff" || !is_hex(version) {
+            return None;
+        }
+        // Version 00 has exactly four fields
+        if version == "00" && parts.next().is_some() {
+            return None;
+        }
+        if trace_id.len() != 32 || !is_hex(trace_id) || is_zero(trace_id) {
+            return None;
+        }
+        if span_id.len() != 16 || !is_hex(span_id) || is_zero(span_id) {
+            return None;
+        }
+        if flags.len() != 2 || !is_hex(flags) {
+            return None;
+        }
+        let flags = u8::from_str_radix(flags, 16).ok()?;
+
+        Some(Self {
+            trace_id: trace_id.to_lowercase(),
+            parent_span_id: span_id.to_lowercase(),
+            sampled: flags & 0x01 != 0,
+        })
+    }
+}
+
+/// A completed span
+#[derive(Debug, Clone)]
+pub struct Span {
+    /// Span name
+    pub name: &'static str,
+    /// 8-byte span ID (hex)
+    pub span_id: String,
+    /// Start time, Unix nanoseconds
+    pub start_ns: u64,
+    /// End time, Unix nanoseconds
+    pub end_ns: u64,
+    /// String attributes
+    pub attributes: Vec<(String, String)>,
+}
+
+impl Span {
+    /// Duration in microseconds
+    pub fn duration_us(&self) -> u64 {
+        self.end_ns.saturating_sub(self.start_ns) / 1_000
+    }
+}
+
+/// Per-request span recorder
+#[derive(Debug)]
+pub struct SpanRecorder {
+    context: TraceContext,
+    context_id: u32,
+    next_seq: u32,
+    spans: Vec<Span>,
+}
+
+impl SpanRecorder {
+    /// Create a recorder for a sampled trace (None if not sampled)
+    pub fn new(context: TraceContext, context_id: u32) -> Option<Self> {
+        if !context.sampled {
+            return None;
+        }
+        Some(Self {
+            context,
+            context_id,
+            next_seq: 0,
+            spans: Vec::new(),
+        })
+    }
+
+    /// Record a finished stage span
+    pub fn record(
+        &mut self,
+        stage: Stage,
+        start_ns: u64,
+        end_ns: u64,
+        attributes: Vec<(String, String)>,
+    ) {
+        let span_id = self.next_span_id();
+        self.spans.push(Span {
+            name: stage.span_name(),
+            span_id,
+            start_ns,
+            end_ns: end_ns.max(start_ns),
+            attributes,
+        });
+    }
+
+    /// Trace context this recorder belongs to
+    pub fn context(&self) -> &TraceContext {
+        &self.context
+    }
+
+    /// Recorded spans
+    pub fn spans(&self) -> &[Span] {
+        &self.spans
+    }
+
+    /// Check if no spans were recorded
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    /// Encode as an OTLP/HTTP JSON `ExportTraceServiceRequest`
+    pub fn to_otlp_json(&self, service_name: &str) -> String {
+        otlp_export(self.otlp_spans(), service_name)
+    }
+
+    /// OTLP JSON of each recorded span
+    fn otlp_spans(&self) -> Vec<Value> {
+        self.spans
+            .iter()
+            .map(|span| {
+                json!({
+                    "traceId": self.context.trace_id,
+                    "spanId": span.span_id,
+                    "parentSpanId": self.context.parent_span_id,
+                    "name": span.name,
+                    "kind": SPAN_KIND_INTERNAL,
+                    "startTimeUnixNano": span.start_ns.to_string(),
+                    "endTimeUnixNano": span.end_ns.to_string(),
+                    "attributes": otlp_attributes(&span.attributes),
+                })
+            })
+            .collect()
+    }
+
+    /// Compact summary for filter state: `name:span_id:duration_us` per span
+    pub fn summary(&self) -> String {
+        self.spans
+            .iter()
+            .map(|s| format!("{}:{}:{}", s.name, s.span_id, s.duration_us()))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    fn next_span_id(&mut self) -> String {
+        self.next_seq += 1;
+
+        // FNV-1a over trace ID, context ID and sequence number
+        let mut hash: u64 = 0xcbf29ce484222325;
+        let seed = self
+            .context
+            .trace_id
+            .bytes()
+            .chain(self.context_id.to_le_bytes())
+            .chain(self.next_seq.to_le_bytes());
+        for byte in seed {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        // All-zero span IDs are invalid
+        if hash == 0 {
+            hash = 1;
+        }
+        format!("{:016x}", hash)
+    }
+}
+
+/// Spans of finished requests waiting for export
+///
+/// A callout dispatched while an HTTP context is torn down is cancelled
+/// with it, so requests queue their spans here and the root context sends
+/// them.
+#[derive(Debug, Default)]
+pub struct SpanQueue {
+    spans: VecDeque<Value>,
+}
+
+impl SpanQueue {
+    /// Create an empty queue
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a request's spans; returns how many old spans were dropped
+    pub fn push(&mut self, recorder: &SpanRecorder) -> usize {
+        self.spans.extend(recorder.otlp_spans());
+        let dropped = self.spans.len().saturating_sub(MAX_QUEUED_SPANS);
+        self.spans.drain(..dropped);
+        dropped
+    }
+
+    /// Number of queued spans
+    pub fn len(&self) -> usize {
+        self.spans.len()
+    }
+
+    /// Check if no spans are queued
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    /// Next export as an OTLP/HTTP JSON body, None when nothing is queued
+    pub fn take_export(&mut self, service_name: &str) -> Option<String> {
+        if self.spans.is_empty() {
+            return None;
+        }
+        let count = self.spans.len().min(EXPORT_BATCH);
+        Some(otlp_export(self.spans.drain(..count).collect(), service_name))
+    }
+}
+
+/// OTLP/HTTP JSON `ExportTraceServiceRequest` of encoded spans
+fn otlp_export(spans: Vec<Value>, service_name: &str) -> String {
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": otlp_attributes(&[
+                    ("service.name".to_string(), service_name.to_string()),
+                ]),
+            },
+            "scopeSpans": [{
+                "scope": { "name": SCOPE_NAME },
+                "spans": spans,
+            }],
+        }],
+    })
+    .to_string()
+}
+
+fn otlp_attributes(attributes: &[(String, String)]) -> Vec<Value> {
+    attributes
+        .iter()
+        .map(|(k, v)| json!({ "key": k, "value": { "stringValue": v } }))
+        .collect()
+}
+
+fn is_hex(s: &str) -> bool {
+    s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn is_zero(s: &str) -> bool {
+    s.bytes().all(|b| b == b'0')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn test_parse_traceparent() {
+        let ctx = TraceContext::parse(TRACEPARENT).unwrap();
+        assert_eq!(ctx.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(ctx.parent_span_id, "00f067aa0ba902b7");
+        assert!(ctx.sampled);
+    }
+
+    #[test]
+    fn test_parse_invalid_traceparent() {
+        assert!(TraceContext::parse("garbage").is_none());
+        assert!(TraceContext::parse("00-00000000000000000000000000000000-00f067aa0ba902b7-01").is_none());
+        assert!(TraceContext::parse("ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").is_none());
+        assert!(TraceContext::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra").is_none());
+    }
+
+    #[test]
+    fn test_unsampled_not_recorded() {
+        let ctx = TraceContext::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00").unwrap();
+        assert!(SpanRecorder::new(ctx, 1).is_none());
+    }
+
+    #[test]
+    fn test_span_ids_unique() {
+        let ctx = TraceContext::parse(TRACEPARENT).unwrap();
+        let mut recorder = SpanRecorder::new(ctx, 7).unwrap();
+        recorder.record(Stage::BodyScan, 1_000, 5_000, Vec::new());
+        recorder.record(Stage::PiiScan, 5_000, 6_000, Vec::new());
+
+        let spans = recorder.spans();
+        assert_eq!(spans.len(), 2);
+        assert_ne!(spans[0].span_id, spans[1].span_id);
+        assert_eq!(spans[0].span_id.len(), 16);
+        assert_eq!(spans[0].duration_us(), 4);
+    }
+
+    #[test]
+    fn test_otlp_json() {
+        let ctx = TraceContext::parse(TRACEPARENT).unwrap();
+        let mut recorder = SpanRecorder::new(ctx, 1).unwrap();
+        recorder.record(
+            Stage::BodyScan,
+            1_000,
+            2_000,
+            vec![("ai_guard.bytes".to_string(), "512".to_string())],
+        );
+
+        let body: Value = serde_json::from_str(&recorder.to_otlp_json("ai-guard")).unwrap();
+        let span = &body["resourceSpans"][0]["scopeSpans"][0]["spans"][0];
+        assert_eq!(span["name"], "ai_guard.body_scan");
+        assert_eq!(span["traceId"], "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(span["parentSpanId"], "00f067aa0ba902b7");
+        assert_eq!(span["startTimeUnixNano"], "1000");
+        assert_eq!(span["attributes"][0]["value"]["stringValue"], "512");
+    }
+
+    #[test]
+    fn test_span_queue() {
+        let ctx = TraceContext::parse(TRACEPARENT).unwrap();
+        let mut recorder = SpanRecorder::new(ctx, 1).unwrap();
+        for i in 0..300 {
+            recorder.record(Stage::RateLimit, i, i + 1, Vec::new());
+        }
+        let mut queue = SpanQueue::new();
+        assert_eq!(queue.push(&recorder), 0);
+        assert_eq!(queue.len(), 300);
+
+        // Exports are batched, oldest first
+        let body: Value = serde_json::from_str(&queue.take_export("ai-guard").unwrap()).unwrap();
+        let spans = &body["resourceSpans"][0]["scopeSpans"][0]["spans"];
+        assert_eq!(spans.as_array().unwrap().len(), EXPORT_BATCH);
+        assert_eq!(spans[0]["startTimeUnixNano"], "0");
+        assert!(queue.take_export("ai-guard").is_some());
+        assert!(queue.take_export("ai-guard").is_none());
+
+        // A full queue drops its oldest spans
+        for _ in 0..4 {
+            queue.push(&recorder);
+        }
+        assert_eq!(queue.push(&recorder), 300);
+        assert_eq!(queue.len(), MAX_QUEUED_SPANS);
+    }
+}